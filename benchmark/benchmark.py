@@ -91,4 +91,124 @@ def benchmark_call_def_8name():
     return y
 
 
+def op8(_a, _b, _c=None, _d=None):
+    pass
+
+
+def benchmark_call_def_2pos_defaults():
+    y = 0
+    for x in range(REPEAT_100M):
+        op8(x, x)
+    return y
+
+
+def op9(x):
+    return x
+
+
+def benchmark_map_100k():
+    xs = list(range(100000))
+    y = []
+    for _x in range(REPEAT_100M // 100000):
+        # `list` is only needed for Python, where `map` is lazy
+        y = list(map(op9, xs))
+    return len(y)
+
+
+def op10(**kwargs):
+    return len(kwargs)
+
+
+def op11(*args, **kwargs):
+    return op10(*args, **kwargs)
+
+
+def op12(*args, **kwargs):
+    return op11(*args, **kwargs)
+
+
+def benchmark_call_def_forward_kwargs():
+    kwargs = {"k" + str(i): i for i in range(20)}
+    y = 0
+    for _x in range(REPEAT_100M // 100):
+        y = op12(**kwargs)
+    return y
+
+
+def benchmark_type_eq():
+    y = 0
+    x = {}
+    for _x in range(REPEAT_100M):
+        # In Python `type` returns a class, so this is always false, but costs the same
+        if type(x) == "dict":
+            y += 1
+    return y
+
+
+def benchmark_list_copy_100k():
+    xs = list(range(100000))
+    y = 0
+    for _x in range(REPEAT_100M // 100000):
+        # The copy shares the elements of `xs` until one of them is modified
+        ys = xs[:]
+        y += len(ys)
+    return y
+
+
+def benchmark_sorted_100k():
+    xs = []
+    x = 1
+    for _x in range(100000):
+        x = (x * 75 + 74) % 65537
+        xs.append(x)
+    strs = [str(x) for x in xs]
+    y = 0
+    for _x in range(REPEAT_100M // 1000000):
+        # Int and string keys are compared directly, rather than by dynamic dispatch
+        y += len(sorted(xs, reverse=True))
+        y += len(sorted(strs, key=len))
+    return y
+
+
+def benchmark_for_loop_1m():
+    xs = list(range(1000000))
+    ys = tuple(xs)
+    zs = {x: None for x in xs}
+    y = 0
+    for _x in range(REPEAT_100M // 4000000):
+        # Each of these loops goes through `for_each_element`, rather than an iterator
+        for _y in xs:
+            y += 1
+        for _y in ys:
+            y += 1
+        for _y in zs:
+            y += 1
+        for _y in range(1000000):
+            y += 1
+    return y
+
+
+def benchmark_for_dict_items_100k():
+    d = {str(x): x for x in range(100000)}
+    y = 0
+    for _x in range(REPEAT_100M // 1000000):
+        # Each of these walks the dict, rather than allocating a list (of tuples) to iterate
+        for _k, v in d.items():
+            y += v
+        for v in d.values():
+            y += v
+        for i, _k in enumerate(d.keys()):
+            y += i
+    return y
+
+
+def benchmark_dict_3_entries():
+    y = 0
+    for x in range(REPEAT_100M // 10):
+        # Small dicts are stored without a hash index
+        d = {"a": x, "b": x, "c": x}
+        y += len(d)
+    return y
+
+
 print(benchmark_call_def_1name())
@@ -16,7 +16,7 @@
  */
 
 use std::{
-    collections::{HashMap, HashSet},
+    collections::HashMap,
     path::{Path, PathBuf},
     sync::{
         atomic::{AtomicUsize, Ordering},
@@ -41,14 +41,18 @@ use crate::eval::{dialect, globals};
 
 mod library;
 
+// Map from filename to the breakpoints in that file.
+// These breakpoints must all match statements as per before_stmt.
+// Those values for which we abort the execution, along with an optional condition,
+// parsed when the breakpoint is set, which must evaluate to a truthy value for us to stop.
+type Breakpoints = HashMap<String, HashMap<FileSpan, Option<AstModule>>>;
+
 #[derive(Debug)]
 struct Backend {
     client: Client,
     file: Mutex<Option<String>>,
 
-    // These breakpoints must all match statements as per before_stmt.
-    // Those values for which we abort the execution.
-    breakpoints: Arc<Mutex<HashMap<String, HashSet<FileSpan>>>>,
+    breakpoints: Arc<Mutex<Breakpoints>>,
     // Set while we are doing evaluate calls (>= 1 means disable)
     disable_breakpoints: Arc<AtomicUsize>,
 
@@ -103,14 +107,14 @@ impl Backend {
             let globals = globals();
             let mut eval = Evaluator::new(&module);
             let fun = |span_loc: FileSpanRef, eval: &mut Evaluator| {
-                let stop = if disable_breakpoints.load(Ordering::SeqCst) > 0 {
-                    false
-                } else {
-                    let breaks = breakpoints.lock().unwrap();
-                    breaks
-                        .get(span_loc.file().filename())
-                        .map(|set| set.contains(&span_loc.to_file_span()))
-                        .unwrap_or_default()
+                let stop = match should_stop(&breakpoints, &disable_breakpoints, span_loc, eval)
+                {
+                    Ok(stop) => stop,
+                    Err(e) => {
+                        // Stop anyway, so the user gets to see the state which caused the failure
+                        client.log(&format!("BREAKPOINT CONDITION FAILED: {:#}", e));
+                        true
+                    }
                 };
                 if stop {
                     client.event_stopped(StoppedEventBody {
@@ -162,6 +166,43 @@ impl Backend {
     }
 }
 
+fn parse_condition(condition: &str) -> anyhow::Result<AstModule> {
+    AstModule::parse("condition", condition.to_owned(), &Dialect::Extended)
+}
+
+/// Should we stop at this statement. Conditions are evaluated in the scope
+/// of the statement, with breakpoints disabled while we do so.
+fn should_stop(
+    breakpoints: &Mutex<Breakpoints>,
+    disable_breakpoints: &AtomicUsize,
+    span_loc: FileSpanRef,
+    eval: &mut Evaluator,
+) -> anyhow::Result<bool> {
+    if disable_breakpoints.load(Ordering::SeqCst) > 0 {
+        return Ok(false);
+    }
+    // Take a copy of the condition, so we don't hold the lock while evaluating it,
+    // and because evaluating consumes it
+    let condition = match breakpoints
+        .lock()
+        .unwrap()
+        .get(span_loc.file().filename())
+        .and_then(|file| file.get(&span_loc.to_file_span()))
+    {
+        None => return Ok(false),
+        Some(condition) => condition.clone(),
+    };
+    match condition {
+        None => Ok(true),
+        Some(condition) => {
+            disable_breakpoints.fetch_add(1, Ordering::SeqCst);
+            let res = eval.eval_statements(condition).map(|v| v.to_bool());
+            disable_breakpoints.fetch_sub(1, Ordering::SeqCst);
+            res
+        }
+    }
+}
+
 fn breakpoint(verified: bool) -> Breakpoint {
     Breakpoint {
         column: None,
@@ -179,6 +220,7 @@ impl DebugServer for Backend {
     fn initialize(&self, _: InitializeRequestArguments) -> anyhow::Result<Option<Capabilities>> {
         self.client.event_initialized(None);
         Ok(Some(Capabilities {
+            supports_conditional_breakpoints: Some(true),
             supports_configuration_done_request: Some(true),
             supports_evaluate_for_hovers: Some(true),
             supports_set_variable: Some(true),
@@ -213,14 +255,35 @@ impl DebugServer for Backend {
                         .iter()
                         .map(|span| (span.resolve_span().begin_line, span.dupe()))
                         .collect();
-                    let list = breakpoints.map(|x| poss.get(&(x.line as usize - 1)));
-                    self.breakpoints
-                        .lock()
-                        .unwrap()
-                        .insert(source, list.iter().filter_map(|x| x.duped()).collect());
-                    Ok(SetBreakpointsResponseBody {
-                        breakpoints: list.map(|x| breakpoint(x.is_some())),
-                    })
+                    let mut file = HashMap::new();
+                    let list = breakpoints
+                        .iter()
+                        .map(|x| {
+                            let pos = match poss.get(&(x.line as usize - 1)) {
+                                None => return breakpoint(false),
+                                Some(pos) => pos,
+                            };
+                            let condition = match x
+                                .condition
+                                .as_ref()
+                                .filter(|c| !c.trim().is_empty())
+                                .map(|c| parse_condition(c))
+                            {
+                                None => None,
+                                Some(Ok(condition)) => Some(condition),
+                                Some(Err(e)) => {
+                                    return Breakpoint {
+                                        message: Some(format!("Invalid condition: {:#}", e)),
+                                        ..breakpoint(false)
+                                    };
+                                }
+                            };
+                            file.insert(pos.dupe(), condition);
+                            breakpoint(true)
+                        })
+                        .collect();
+                    self.breakpoints.lock().unwrap().insert(source, file);
+                    Ok(SetBreakpointsResponseBody { breakpoints: list })
                 }
             }
         }
@@ -388,3 +451,55 @@ pub fn server() {
         receiver: Arc::new(Mutex::new(receiver)),
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    #[test]
+    fn test_conditional_breakpoint() {
+        let program = r#"
+def f():
+    for i in range(5):
+        x = i
+f()
+"#;
+        let ast = AstModule::parse("loop.star", program.to_owned(), &dialect()).unwrap();
+        // The statement `x = i` on the fourth line
+        let target = ast
+            .stmt_locations()
+            .into_iter()
+            .find(|x| x.resolve_span().begin_line == 3)
+            .unwrap();
+        let breakpoints = Mutex::new(HashMap::from([(
+            "loop.star".to_owned(),
+            HashMap::from([(target, Some(parse_condition("i == 3").unwrap()))]),
+        )]));
+        let disable_breakpoints = AtomicUsize::new(0);
+
+        let stops = Cell::new(0);
+        let module = Module::new();
+        let globals = globals();
+        let mut eval = Evaluator::new(&module);
+        let fun = |span_loc: FileSpanRef, eval: &mut Evaluator| {
+            if should_stop(&breakpoints, &disable_breakpoints, span_loc, eval).unwrap() {
+                assert_eq!(
+                    eval.local_variables().get("i").unwrap().unpack_int(),
+                    Some(3)
+                );
+                stops.set(stops.get() + 1);
+            }
+        };
+        eval.before_stmt(&fun);
+        eval.eval_module(ast, &globals).unwrap();
+        assert_eq!(stops.get(), 1);
+    }
+
+    #[test]
+    fn test_invalid_condition() {
+        assert!(parse_condition("i == 3").is_ok());
+        assert!(parse_condition("i ==").is_err());
+    }
+}
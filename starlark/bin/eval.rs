@@ -16,34 +16,61 @@
  */
 
 use std::{
+    cell::RefCell,
     fs, iter,
     path::{Path, PathBuf},
 };
 
+use anyhow::anyhow;
 use gazebo::prelude::*;
 use itertools::Either;
 use starlark::{
     environment::{FrozenModule, Globals, Module},
-    eval::Evaluator,
+    errors::{Lint, LintBaseline, LintConfig},
+    eval::{eval_many_parsed, Evaluator},
     syntax::{AstModule, Dialect},
 };
 
-use crate::types::Message;
+use crate::{suppression::Suppressions, types::Message};
+
+/// Read a source file, saying where the first invalid byte is if it isn't UTF-8.
+fn read_source(file: &Path) -> anyhow::Result<String> {
+    String::from_utf8(fs::read(file)?).map_err(|e| {
+        anyhow!(
+            "File is not valid UTF-8, invalid byte at byte offset {}",
+            e.utf8_error().valid_up_to()
+        )
+    })
+}
 
 #[derive(Debug)]
 pub struct Context {
     pub check: bool,
     pub info: bool,
+    pub ast: bool,
+    // Print the AST or the module info as JSON, rather than as text.
+    pub json: bool,
     pub run: bool,
+    pub dump_bytecode: bool,
+    // Print the documentation of each evaluated module.
+    pub docs: bool,
     pub prelude: Vec<FrozenModule>,
     pub module: Option<Module>,
+    pub lint_config: LintConfig,
+    // Lints to report as disabled, from `--baseline`.
+    pub baseline: Option<RefCell<LintBaseline>>,
+    // The lints found, for `--write-baseline`.
+    pub write_baseline: Option<RefCell<LintBaseline>>,
 }
 
 impl Context {
     pub fn new(
         check: bool,
         info: bool,
+        ast: bool,
+        json: bool,
         run: bool,
+        dump_bytecode: bool,
         prelude: &[PathBuf],
         module: bool,
     ) -> anyhow::Result<Self> {
@@ -66,9 +93,16 @@ impl Context {
         Ok(Self {
             check,
             info,
+            ast,
+            json,
             run,
+            dump_bytecode,
+            docs: false,
             prelude,
             module,
+            lint_config: LintConfig::default(),
+            baseline: None,
+            write_baseline: None,
         })
     }
 
@@ -81,20 +115,29 @@ impl Context {
     }
 
     fn go(&self, file: &str, ast: AstModule) -> impl Iterator<Item = Message> {
-        let mut warnings = Either::Left(iter::empty());
+        let warnings = self.inspect(&ast);
         let mut errors = Either::Left(iter::empty());
-        if self.info {
-            self.info(&ast);
-        }
-        if self.check {
-            warnings = Either::Right(self.check(&ast));
-        }
         if self.run {
             errors = Either::Right(self.run(file, ast));
         }
         warnings.chain(errors)
     }
 
+    // Everything `go` does apart from running the code.
+    fn inspect(&self, ast: &AstModule) -> impl Iterator<Item = Message> {
+        let mut warnings = Either::Left(iter::empty());
+        if self.info {
+            self.info(ast);
+        }
+        if self.ast {
+            self.ast(ast);
+        }
+        if self.check {
+            warnings = Either::Right(self.check(ast));
+        }
+        warnings
+    }
+
     // Convert an anyhow over iterator of Message, into an iterator of Message
     fn err(
         file: &str,
@@ -118,9 +161,7 @@ impl Context {
         let filename = &file.to_string_lossy();
         Self::err(
             filename,
-            fs::read_to_string(file)
-                .map(|content| self.file_with_contents(filename, content))
-                .map_err(|e| e.into()),
+            read_source(file).map(|content| self.file_with_contents(filename, content)),
         )
     }
 
@@ -135,22 +176,106 @@ impl Context {
         )
     }
 
+    /// Like [`file`](Context::file) for each of `files`, but evaluates the files
+    /// in parallel on up to `jobs` threads, each in a fresh module.
+    /// Returns the messages for each file, in order.
+    pub fn files(&self, files: &[PathBuf], jobs: usize) -> Vec<Vec<Message>> {
+        let mut res = Vec::with_capacity(files.len());
+        // The modules to evaluate, and the index in `res` and file name of each.
+        let mut modules = Vec::new();
+        let mut indices = Vec::new();
+        for file in files {
+            let filename = file.to_string_lossy().into_owned();
+            let content = match read_source(file) {
+                Ok(content) => content,
+                Err(e) => {
+                    res.push(vec![Message::from_anyhow(&filename, e)]);
+                    continue;
+                }
+            };
+            let ast = match AstModule::parse(&filename, content, &dialect()) {
+                Ok(ast) => ast,
+                Err(e) => {
+                    res.push(vec![Message::from_anyhow(&filename, e)]);
+                    continue;
+                }
+            };
+            res.push(self.inspect(&ast).collect());
+            if self.run {
+                indices.push((res.len() - 1, filename));
+                modules.push(ast);
+            }
+        }
+
+        let results = eval_many_parsed(&self.prelude, modules, &globals(), jobs);
+        for ((i, filename), result) in indices.into_iter().zip(results) {
+            match result {
+                Ok(module) => self.evaluated(&filename, &module),
+                Err(e) => res[i].push(Message::from_anyhow(&filename, e)),
+            }
+        }
+        res
+    }
+
     fn run(&self, file: &str, ast: AstModule) -> impl Iterator<Item = Message> {
-        let new_module;
-        let module = match self.module.as_ref() {
-            Some(module) => module,
+        let res = match self.module.as_ref() {
+            // A persistent module is built up statement by statement, e.g. in the REPL.
+            Some(module) => Self::eval(module, ast, true),
             None => {
-                new_module = Self::new_module(&self.prelude);
-                &new_module
+                let module = Self::new_module(&self.prelude);
+                Self::eval(&module, ast, false).and_then(|()| {
+                    if self.dump_bytecode || self.docs {
+                        self.evaluated(file, &module.freeze()?);
+                    }
+                    Ok(())
+                })
             }
         };
+        Self::err(file, res.map(|()| iter::empty()))
+    }
+
+    fn eval(module: &Module, ast: AstModule, statements: bool) -> anyhow::Result<()> {
         let mut eval = Evaluator::new(module);
         eval.enable_terminal_breakpoint_console();
         let globals = globals();
-        Self::err(file, eval.eval_module(ast, &globals).map(|_| iter::empty()))
+        if statements {
+            eval.eval_snippet(ast, &globals)?;
+        } else {
+            eval.eval_module(ast, &globals)?;
+        }
+        Ok(())
+    }
+
+    // Report on a module which evaluated successfully.
+    fn evaluated(&self, file: &str, module: &FrozenModule) {
+        if self.dump_bytecode {
+            Self::dump_bytecode(module);
+        }
+        if self.docs {
+            if let Some(docs) = module.documentation() {
+                if self.json {
+                    println!("{}", serde_json::to_string(&docs).unwrap());
+                } else {
+                    print!("{}", docs.render_as_markdown(file));
+                }
+            }
+        }
+    }
+
+    fn dump_bytecode(module: &FrozenModule) {
+        for name in module.names() {
+            if let Some(bytecode) = module.dump_bytecode(name) {
+                println!("def {}:", name);
+                bytecode.lines().for_each(|x| println!("  {}", x));
+            }
+        }
     }
 
     fn info(&self, module: &AstModule) {
+        if self.json {
+            println!("{}", serde_json::to_string(&module.info()).unwrap());
+            return;
+        }
         let exports = module.exported_symbols();
         println!("Exports {} symbol(s)", exports.len());
         for (loc, name) in exports {
@@ -158,18 +283,80 @@ impl Context {
         }
     }
 
-    fn check(&self, module: &AstModule) -> impl Iterator<Item = Message> {
-        let mut globals = Vec::new();
-        for x in &self.prelude {
-            globals.extend(x.names());
+    fn ast(&self, module: &AstModule) {
+        if self.json {
+            println!("{}", module.to_ast_json());
+        } else {
+            print!("{}", module.to_ast_string());
         }
+    }
+
+    pub fn check(&self, module: &AstModule) -> impl Iterator<Item = Message> {
+        // Without a prelude we don't know what the file expects to be in scope,
+        // so don't report undefined variables at all.
         let globals = if self.prelude.is_empty() {
+            Vec::new()
+        } else {
+            globals().names()
+        };
+        let mut names: Vec<&str> = globals.iter().map(|x| x.as_str()).collect();
+        for x in &self.prelude {
+            // Only the public symbols are imported into the evaluated module.
+            names.extend(x.names().filter(|x| !x.starts_with('_')));
+        }
+        let names = if self.prelude.is_empty() {
             None
         } else {
-            Some(globals.as_slice())
+            Some(names.as_slice())
+        };
+
+        let mut lints = module.lint_with_config(names, &self.lint_config);
+        let suppressions = match lints.first() {
+            Some(x) => Suppressions::new(x.location.file()),
+            None => Suppressions::default(),
         };
+        if let Some(write_baseline) = &self.write_baseline {
+            write_baseline.borrow_mut().record(
+                module,
+                lints.iter().filter(|x| !suppressions.is_suppressed(x)),
+            );
+        }
+        if let Some(baseline) = &self.baseline {
+            baseline.borrow_mut().apply(module, &mut lints);
+        }
+        lints.into_iter().map(move |x| {
+            let suppressed = suppressions.is_suppressed(&x);
+            Message::from_lint(x, suppressed)
+        })
+    }
 
-        module.lint(globals).into_iter().map(Message::from_lint)
+    /// The messages for a file checked by a [`CheckerCache`](starlark::errors::CheckerCache):
+    /// its lints, the `missing_symbols` enabled by the lint config and, if `run` is set
+    /// and the file has no syntax errors, any error from evaluating it.
+    pub fn checked_file(
+        &self,
+        file: &str,
+        ast: &AstModule,
+        content: &str,
+        missing_symbols: Vec<Lint>,
+    ) -> Vec<Message> {
+        let mut res: Vec<Message> = self.check(ast).collect();
+        res.extend(
+            missing_symbols
+                .into_iter()
+                .filter(|x| self.lint_config.is_enabled(&x.short_name))
+                .map(|mut x| {
+                    x.severity = self.lint_config.severity(&x.short_name, x.serious);
+                    Message::from_lint(x, false)
+                }),
+        );
+        if self.run && !ast.has_syntax_errors() {
+            // Evaluation consumes the AST, so parse the file again.
+            if let Ok(ast) = AstModule::parse(file, content.to_owned(), &dialect()) {
+                res.extend(self.run(file, ast));
+            }
+        }
+        res
     }
 }
 
@@ -180,3 +367,52 @@ pub fn globals() -> Globals {
 pub fn dialect() -> Dialect {
     Dialect::Extended
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context_with_prelude(prelude: &str) -> Context {
+        let env = Module::new();
+        let mut eval = Evaluator::new(&env);
+        let ast = AstModule::parse("prelude.bzl", prelude.to_owned(), &dialect()).unwrap();
+        eval.eval_module(ast, &globals()).unwrap();
+        Context {
+            check: true,
+            info: false,
+            ast: false,
+            json: false,
+            run: false,
+            dump_bytecode: false,
+            docs: false,
+            prelude: vec![env.freeze().unwrap()],
+            module: None,
+            lint_config: LintConfig::default(),
+            baseline: None,
+            write_baseline: None,
+        }
+    }
+
+    fn check(ctx: &Context, content: &str) -> Vec<String> {
+        let ast = AstModule::parse("build.bzl", content.to_owned(), &dialect()).unwrap();
+        ctx.check(&ast).map(|x| x.name).collect()
+    }
+
+    #[test]
+    fn test_check_knows_prelude_globals() {
+        let ctx = context_with_prelude("def my_rule(): pass\ndef _private(): pass");
+        assert_eq!(
+            check(&ctx, "my_rule()\nprint(len([]))"),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            check(&ctx, "_private()\nnot_defined()"),
+            vec!["using-undefined", "using-undefined"]
+        );
+        // Shadowing a prelude name locally is fine.
+        assert_eq!(
+            check(&ctx, "def f():\n    my_rule = 1\n    return my_rule\nf()"),
+            Vec::<String>::new()
+        );
+    }
+}
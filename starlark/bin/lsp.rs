@@ -17,27 +17,83 @@
 
 //! Based on the reference lsp-server example at <https://github.com/rust-analyzer/lsp-server/blob/master/examples/goto_def.rs>.
 
-use lsp_server::{Connection, Message, Notification};
+use std::{cell::RefCell, collections::HashMap};
+
+use gazebo::prelude::*;
+use lsp_server::{Connection, Message, Notification, Request, RequestId, Response};
 use lsp_types::{
     notification::{
-        DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, LogMessage,
-        PublishDiagnostics,
+        DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, DidSaveTextDocument,
+        LogMessage, PublishDiagnostics,
     },
-    Diagnostic, DiagnosticSeverity, DidChangeTextDocumentParams, DidCloseTextDocumentParams,
-    DidOpenTextDocumentParams, InitializeParams, LogMessageParams, MessageType, NumberOrString,
-    Position, PublishDiagnosticsParams, Range, ServerCapabilities, TextDocumentSyncCapability,
-    TextDocumentSyncKind, Url,
+    request::{CodeActionRequest, DocumentSymbolRequest, GotoDefinition, HoverRequest},
+    CodeAction, CodeActionKind, CodeActionOrCommand, CodeActionParams,
+    CodeActionProviderCapability, Diagnostic, DiagnosticRelatedInformation, DiagnosticSeverity,
+    DidChangeTextDocumentParams, DidCloseTextDocumentParams, DidOpenTextDocumentParams,
+    DidSaveTextDocumentParams, DocumentSymbol, DocumentSymbolParams, DocumentSymbolResponse,
+    GotoDefinitionParams, GotoDefinitionResponse, Hover, HoverContents, HoverParams,
+    HoverProviderCapability, InitializeParams, Location, LogMessageParams, MarkupContent,
+    MarkupKind, MessageType, NumberOrString, OneOf, Position, PublishDiagnosticsParams, Range,
+    SaveOptions, ServerCapabilities, SymbolKind, TextDocumentSyncCapability, TextDocumentSyncKind,
+    TextDocumentSyncOptions, TextDocumentSyncSaveOptions, TextEdit, Url, WorkspaceEdit,
 };
 use serde::de::DeserializeOwned;
+use starlark::{
+    codemap::ResolvedSpan,
+    environment::Globals,
+    errors::{
+        CheckerCache, CheckerContext, Diagnostic as StarlarkDiagnostic, DiagnosticSpan, Lint,
+    },
+    lsp::{LoadResolver, WorkspaceResolver},
+    syntax::{AstModule, Definition, Symbol, SymbolKind as StarlarkSymbolKind},
+    values::docs::{DocItem, DocString, DocStringKind, Function, Member, Param, Type},
+};
 
 use crate::{
-    eval::Context,
-    types::{Message as StarlarkMessage, Severity},
+    eval::{dialect, globals, Context},
+    types::{ErrorDetails, Message as StarlarkMessage, Severity},
 };
 
+/// Checks files with a [`Context`], following loads with a [`LoadResolver`].
+struct LspChecker {
+    starlark: Context,
+    resolver: Box<dyn LoadResolver>,
+}
+
+impl CheckerContext for LspChecker {
+    type File = Url;
+    type Message = StarlarkMessage;
+
+    fn resolve(&self, path: &str, file: &Url) -> Option<Url> {
+        self.resolver.resolve(path, file)
+    }
+
+    fn contents(&self, url: &Url) -> Option<String> {
+        self.resolver.contents(url)
+    }
+
+    fn syntax_error(&self, url: &Url, error: StarlarkDiagnostic) -> StarlarkMessage {
+        StarlarkMessage::from_anyhow(url.as_str(), error.into())
+    }
+
+    fn check(
+        &self,
+        url: &Url,
+        ast: &AstModule,
+        content: &str,
+        missing_symbols: Vec<Lint>,
+    ) -> Vec<StarlarkMessage> {
+        self.starlark
+            .checked_file(url.as_str(), ast, content, missing_symbols)
+    }
+}
+
 struct Backend {
     connection: Connection,
-    starlark: Context,
+    // The diagnostics of every file we know about, open or loaded by an open file.
+    checker: RefCell<CheckerCache<LspChecker>>,
+    // The latest contents of all the open documents.
+    documents: RefCell<HashMap<Url, String>>,
 }
 
 fn to_severity(x: Severity) -> DiagnosticSeverity {
@@ -49,43 +105,398 @@ fn to_severity(x: Severity) -> DiagnosticSeverity {
     }
 }
 
-fn to_diagnostic(x: StarlarkMessage) -> Diagnostic {
-    let range = match x.span {
-        Some(s) => Range::new(
-            Position::new(s.begin_line as u32, s.begin_column as u32),
-            Position::new(s.end_line as u32, s.end_column as u32),
+/// Convert a 0-based `column` on `line` of `text`, counted in characters as in a
+/// `ResolvedSpan`, to UTF-16 code units, which is what LSP positions count.
+fn to_utf16_column(text: &str, line: usize, column: usize) -> u32 {
+    match text.lines().nth(line) {
+        Some(x) => x.chars().take(column).map(char::len_utf16).sum::<usize>() as u32,
+        None => column as u32,
+    }
+}
+
+/// The inverse of `to_utf16_column`. A position within a character
+/// refers to that character.
+fn from_utf16_column(text: &str, line: usize, character: u32) -> usize {
+    match text.lines().nth(line) {
+        Some(x) => {
+            let mut units = 0;
+            x.chars()
+                .take_while(|c| {
+                    units += c.len_utf16();
+                    units <= character as usize
+                })
+                .count()
+        }
+        None => character as usize,
+    }
+}
+
+/// The LSP range of a span in `text`.
+fn to_range(text: &str, x: ResolvedSpan) -> Range {
+    Range::new(
+        Position::new(
+            x.begin_line as u32,
+            to_utf16_column(text, x.begin_line, x.begin_column),
         ),
-        _ => Range::default(),
-    };
-    Diagnostic::new(
+        Position::new(
+            x.end_line as u32,
+            to_utf16_column(text, x.end_line, x.end_column),
+        ),
+    )
+}
+
+/// The LSP range of a `DiagnosticSpan`, which is 1-based.
+/// Columns are only converted to UTF-16 if `text` is the file's text.
+fn diagnostic_span_range(text: &str, x: &DiagnosticSpan) -> Range {
+    to_range(
+        text,
+        ResolvedSpan {
+            begin_line: x.begin_line.saturating_sub(1),
+            begin_column: x.begin_column.saturating_sub(1),
+            end_line: x.end_line.saturating_sub(1),
+            end_column: x.end_column.saturating_sub(1),
+        },
+    )
+}
+
+/// The call stack and secondary spans of an error, as related locations.
+fn to_related_information(
+    path: &str,
+    text: &str,
+    error: &ErrorDetails,
+) -> Vec<DiagnosticRelatedInformation> {
+    let frames = error.call_stack.iter().filter_map(|(name, location)| {
+        Some((location.as_ref()?, format!("In call to `{}`", name)))
+    });
+    let secondary = error
+        .secondary_spans
+        .iter()
+        .map(|(span, label)| (span, label.clone()));
+    frames
+        .chain(secondary)
+        .filter_map(|(span, message)| {
+            let text = if span.path == path { text } else { "" };
+            Some(DiagnosticRelatedInformation {
+                location: Location::new(
+                    Url::parse(&span.path).ok()?,
+                    diagnostic_span_range(text, span),
+                ),
+                message,
+            })
+        })
+        .collect()
+}
+
+fn to_diagnostic(text: &str, x: StarlarkMessage) -> Diagnostic {
+    let range = x.span.map_or_else(Range::default, |x| to_range(text, x));
+    // Errors are described by the same code and locations the CLI reports.
+    let code = x
+        .error
+        .as_ref()
+        .and_then(|e| e.code.clone())
+        .unwrap_or(x.name);
+    let related = x
+        .error
+        .as_ref()
+        .map(|e| to_related_information(&x.path, text, e))
+        .filter(|x| !x.is_empty());
+    let mut res = Diagnostic::new(
         range,
         Some(to_severity(x.severity)),
-        Some(NumberOrString::String(x.name)),
+        Some(NumberOrString::String(code)),
         None,
         x.description,
+        related,
         None,
-        None,
-    )
+    );
+    // Stash the fix on the diagnostic, so the client gives it back to us
+    // when asking for code actions.
+    res.data = x.fix.map(|fix| {
+        serde_json::to_value(TextEdit::new(to_range(text, fix.span), fix.replacement)).unwrap()
+    });
+    res
+}
+
+/// Turn the fixes attached to diagnostics (by `to_diagnostic`) into quick fixes.
+fn to_code_actions(params: CodeActionParams) -> Vec<CodeActionOrCommand> {
+    let uri = params.text_document.uri;
+    params
+        .context
+        .diagnostics
+        .into_iter()
+        .filter_map(|diagnostic| {
+            let edit: TextEdit = serde_json::from_value(diagnostic.data.clone()?).ok()?;
+            let changes = HashMap::from([(uri.clone(), vec![edit])]);
+            Some(CodeActionOrCommand::CodeAction(CodeAction {
+                title: format!("Fix: {}", diagnostic.message),
+                kind: Some(CodeActionKind::QUICKFIX),
+                diagnostics: Some(vec![diagnostic]),
+                edit: Some(WorkspaceEdit::new(changes)),
+                is_preferred: Some(true),
+                ..CodeAction::default()
+            }))
+        })
+        .collect()
+}
+
+fn to_document_symbol(text: &str, x: Symbol) -> DocumentSymbol {
+    #[allow(deprecated)] // The `deprecated` field
+    DocumentSymbol {
+        name: x.name,
+        detail: x.detail,
+        kind: match x.kind {
+            StarlarkSymbolKind::Function => SymbolKind::Function,
+            StarlarkSymbolKind::Variable => SymbolKind::Variable,
+            StarlarkSymbolKind::Constant => SymbolKind::Constant,
+            StarlarkSymbolKind::Load => SymbolKind::Module,
+        },
+        tags: None,
+        deprecated: None,
+        range: to_range(text, x.span.resolve_span()),
+        selection_range: to_range(text, x.name_span.resolve_span()),
+        children: if x.children.is_empty() {
+            None
+        } else {
+            Some(x.children.into_map(|x| to_document_symbol(text, x)))
+        },
+    }
+}
+
+fn render_code(code: &str) -> String {
+    format!("```starlark\n{}\n```\n", code)
+}
+
+fn render_docs(res: &mut String, docs: &Option<DocString>) {
+    if let Some(docs) = docs {
+        res.push('\n');
+        res.push_str(&docs.summary);
+        res.push('\n');
+        if let Some(details) = &docs.details {
+            res.push('\n');
+            res.push_str(details);
+            res.push('\n');
+        }
+    }
+}
+
+fn render_param(x: &Param) -> String {
+    let typed = |name: &str, typ: &Option<Type>| match typ {
+        Some(typ) => format!("{}: {}", name, typ.raw_type),
+        None => name.to_owned(),
+    };
+    match x {
+        Param::Arg {
+            name,
+            typ,
+            default_value,
+            ..
+        } => match default_value {
+            Some(default) => format!("{} = {}", typed(name, typ), default),
+            None => typed(name, typ),
+        },
+        Param::NoArgs => "*".to_owned(),
+        Param::Args { name, typ, .. } => format!("*{}", typed(name, typ)),
+        Param::Kwargs { name, typ, .. } => format!("**{}", typed(name, typ)),
+    }
+}
+
+fn render_function(name: &str, x: &Function) -> String {
+    let params = x.params.iter().map(render_param).collect::<Vec<_>>();
+    let ret = match &x.ret.typ {
+        Some(typ) => format!(" -> {}", typ.raw_type),
+        None => String::new(),
+    };
+    let mut res = render_code(&format!("def {}({}){}", name, params.join(", "), ret));
+    render_docs(&mut res, &x.docs);
+    res
+}
+
+/// Render the documentation of a global called `name` as markdown.
+fn render_doc_item(name: &str, x: &DocItem) -> String {
+    match x {
+        DocItem::Function(x) => render_function(name, x),
+        DocItem::Module(x) => {
+            let mut res = render_code(name);
+            render_docs(&mut res, &x.docs);
+            res
+        }
+        DocItem::Object(x) => {
+            let mut res = render_code(name);
+            render_docs(&mut res, &x.docs);
+            if !x.members.is_empty() {
+                res.push('\n');
+                for (member, kind) in &x.members {
+                    let summary = match kind {
+                        Member::Property(x) => x.docs.as_ref(),
+                        Member::Function(x) => x.docs.as_ref(),
+                        Member::Object(x) => x.docs.as_ref(),
+                        _ => None,
+                    };
+                    match summary {
+                        Some(docs) => res.push_str(&format!("* `{}`: {}\n", member, docs.summary)),
+                        None => res.push_str(&format!("* `{}`\n", member)),
+                    }
+                }
+            }
+            res
+        }
+    }
+}
+
+fn render_symbol(x: &Symbol) -> String {
+    match x.kind {
+        StarlarkSymbolKind::Function => {
+            let mut res = render_code(&format!(
+                "def {}{}",
+                x.name,
+                x.detail.as_deref().unwrap_or("()")
+            ));
+            let docs = x
+                .docs
+                .as_ref()
+                .and_then(|docs| DocString::from_docstring(DocStringKind::Starlark, docs));
+            render_docs(&mut res, &docs);
+            res
+        }
+        StarlarkSymbolKind::Variable | StarlarkSymbolKind::Constant => {
+            render_code(x.span.source_span())
+        }
+        StarlarkSymbolKind::Load => format!(
+            "{}Loaded from `{}`\n",
+            render_code(&x.name),
+            x.detail.as_deref().unwrap_or_default()
+        ),
+    }
+}
+
+fn find_symbol<'a>(xs: &'a [Symbol], kind: StarlarkSymbolKind, name: &str) -> Option<&'a Symbol> {
+    xs.iter().find_map(|x| {
+        if x.kind == kind && x.name == name {
+            Some(x)
+        } else {
+            find_symbol(&x.children, kind, name)
+        }
+    })
+}
+
+/// The markdown to show when hovering at a 0-based `line` and `column` (in characters)
+/// of `module`.
+/// Names are looked up as local functions, then other names bound at the top-level
+/// of the module, then the `globals`.
+fn hover_markdown(
+    module: &AstModule,
+    globals: &Globals,
+    line: usize,
+    column: usize,
+) -> Option<(String, ResolvedSpan)> {
+    let (name, span) = module.identifier_at(line, column)?;
+    let symbols = module.symbols();
+    let local = [
+        StarlarkSymbolKind::Function,
+        StarlarkSymbolKind::Variable,
+        StarlarkSymbolKind::Constant,
+        StarlarkSymbolKind::Load,
+    ]
+    .iter()
+    .find_map(|kind| find_symbol(&symbols, *kind, &name));
+    let markdown = match local {
+        Some(x) => render_symbol(x),
+        None => render_doc_item(&name, &globals.member_documentation().remove(&name)??),
+    };
+    Some((markdown, span.resolve_span()))
+}
+
+/// Where the identifier at a 0-based `line` and `column` (in characters) of `module`,
+/// whose file is `uri` and whose text is `text`, is defined. Names bound by a `load` are followed to the top-level definition in the
+/// loaded module, with `contents` giving the text of a file. Missing files or
+/// definitions give `None`.
+fn definition_location(
+    module: &AstModule,
+    uri: &Url,
+    text: &str,
+    line: usize,
+    column: usize,
+    resolver: &dyn LoadResolver,
+    contents: impl Fn(&Url) -> Option<String>,
+) -> Option<Location> {
+    match module.definition_at(line, column)? {
+        Definition::Local(span) => Some(Location::new(
+            uri.clone(),
+            to_range(text, span.resolve_span()),
+        )),
+        Definition::Load { module, symbol, .. } => {
+            let uri = resolver.resolve(&module, uri)?;
+            let loaded_text = contents(&uri)?;
+            let loaded = AstModule::parse(uri.as_str(), loaded_text.clone(), &dialect()).ok()?;
+            match loaded.top_level_definition(&symbol)? {
+                Definition::Local(span) | Definition::Load { span, .. } => Some(Location::new(
+                    uri,
+                    to_range(&loaded_text, span.resolve_span()),
+                )),
+            }
+        }
+    }
 }
 
 /// The logic implementations of stuff
 impl Backend {
     fn server_capabilities() -> ServerCapabilities {
         ServerCapabilities {
-            text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::Full)),
+            text_document_sync: Some(TextDocumentSyncCapability::Options(
+                TextDocumentSyncOptions {
+                    open_close: Some(true),
+                    change: Some(TextDocumentSyncKind::Full),
+                    save: Some(TextDocumentSyncSaveOptions::SaveOptions(SaveOptions {
+                        include_text: Some(true),
+                    })),
+                    ..TextDocumentSyncOptions::default()
+                },
+            )),
+            code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
+            document_symbol_provider: Some(OneOf::Left(true)),
+            hover_provider: Some(HoverProviderCapability::Simple(true)),
+            definition_provider: Some(OneOf::Left(true)),
             ..ServerCapabilities::default()
         }
     }
 
     fn validate(&self, uri: Url, version: Option<i64>, text: String) {
+        self.documents
+            .borrow_mut()
+            .insert(uri.clone(), text.clone());
+        let dependents = self.checker.borrow_mut().file_changed(&uri, text);
+        self.publish_checked(uri, version);
+        self.publish_dependents(dependents);
+    }
+
+    fn publish_checked(&self, uri: Url, version: Option<i64>) {
+        let text = self
+            .documents
+            .borrow()
+            .get(&uri)
+            .cloned()
+            .unwrap_or_default();
         let diags = self
-            .starlark
-            .file_with_contents(&uri.to_string(), text)
-            .map(to_diagnostic)
+            .checker
+            .borrow_mut()
+            .diagnostics(&uri)
+            .into_iter()
+            .filter(|x| !x.suppressed)
+            .map(|x| to_diagnostic(&text, x))
             .collect();
         self.publish_diagnostics(uri, diags, version)
     }
 
+    // Files which load a changed file may have new diagnostics. Only the open ones are
+    // reported, as closed files have had their diagnostics cleared.
+    fn publish_dependents(&self, dependents: Vec<Url>) {
+        for uri in dependents {
+            if self.documents.borrow().contains_key(&uri) {
+                self.publish_checked(uri, None)
+            }
+        }
+    }
+
     fn did_open(&self, params: DidOpenTextDocumentParams) {
         self.validate(
             params.text_document.uri,
@@ -104,8 +515,96 @@ impl Backend {
         );
     }
 
+    fn did_save(&self, params: DidSaveTextDocumentParams) {
+        // Usually the same as the last change, in which case nothing is re-checked.
+        let text = params.text.or_else(|| {
+            self.documents
+                .borrow()
+                .get(&params.text_document.uri)
+                .cloned()
+        });
+        if let Some(text) = text {
+            self.validate(params.text_document.uri, None, text)
+        }
+    }
+
     fn did_close(&self, params: DidCloseTextDocumentParams) {
-        self.publish_diagnostics(params.text_document.uri, Vec::new(), None)
+        let uri = params.text_document.uri;
+        self.documents.borrow_mut().remove(&uri);
+        // Any unsaved edits are gone, so files loading this one now see what is on disk.
+        let dependents = {
+            let mut checker = self.checker.borrow_mut();
+            match checker.context().resolver.contents(&uri) {
+                Some(text) => checker.file_changed(&uri, text),
+                None => Vec::new(),
+            }
+        };
+        self.publish_diagnostics(uri, Vec::new(), None);
+        self.publish_dependents(dependents);
+    }
+
+    fn code_action(&self, id: RequestId, params: CodeActionParams) {
+        self.send_response(Response::new_ok(id, to_code_actions(params)))
+    }
+
+    fn document_symbol(&self, id: RequestId, params: DocumentSymbolParams) {
+        let uri = params.text_document.uri;
+        // Statements with syntax errors are left out of the outline, and diagnostics say why
+        let documents = self.documents.borrow();
+        let text = documents.get(&uri).map_or("", |x| x.as_str());
+        let symbols = AstModule::parse_with_recovery(uri.as_str(), text.to_owned(), &dialect())
+            .0
+            .symbols();
+        let res = DocumentSymbolResponse::Nested(symbols.into_map(|x| to_document_symbol(text, x)));
+        self.send_response(Response::new_ok(id, res))
+    }
+
+    fn hover(&self, id: RequestId, params: HoverParams) {
+        let uri = params.text_document_position_params.text_document.uri;
+        let position = params.text_document_position_params.position;
+        let line = position.line as usize;
+        let res = self.documents.borrow().get(&uri).and_then(|text| {
+            let module = AstModule::parse(uri.as_str(), text.clone(), &dialect()).ok()?;
+            let column = from_utf16_column(text, line, position.character);
+            let (markdown, span) = hover_markdown(&module, &globals(), line, column)?;
+            Some(Hover {
+                contents: HoverContents::Markup(MarkupContent {
+                    kind: MarkupKind::Markdown,
+                    value: markdown,
+                }),
+                range: Some(to_range(text, span)),
+            })
+        });
+        self.send_response(Response::new_ok(id, res))
+    }
+
+    fn goto_definition(&self, id: RequestId, params: GotoDefinitionParams) {
+        let uri = params.text_document_position_params.text_document.uri;
+        let position = params.text_document_position_params.position;
+        let documents = self.documents.borrow();
+        let checker = self.checker.borrow();
+        let line = position.line as usize;
+        let res = documents
+            .get(&uri)
+            .and_then(|text| {
+                let module = AstModule::parse(uri.as_str(), text.clone(), &dialect()).ok()?;
+                definition_location(
+                    &module,
+                    &uri,
+                    text,
+                    line,
+                    from_utf16_column(text, line, position.character),
+                    &*checker.context().resolver,
+                    |url| {
+                        documents
+                            .get(url)
+                            .cloned()
+                            .or_else(|| checker.context().resolver.contents(url))
+                    },
+                )
+            })
+            .map(GotoDefinitionResponse::Scalar);
+        self.send_response(Response::new_ok(id, res))
     }
 }
 
@@ -118,6 +617,10 @@ impl Backend {
             .unwrap()
     }
 
+    fn send_response(&self, x: Response) {
+        self.connection.sender.send(Message::Response(x)).unwrap()
+    }
+
     fn log_message(&self, typ: MessageType, message: &str) {
         self.send_notification(new_notification::<LogMessage>(LogMessageParams {
             typ,
@@ -139,13 +642,23 @@ impl Backend {
                     if self.connection.handle_shutdown(&req)? {
                         return Ok(());
                     }
-                    // Currently don't handle any other requests
+                    if let Some((id, params)) = as_request::<CodeActionRequest>(&req) {
+                        self.code_action(id, params)
+                    } else if let Some((id, params)) = as_request::<DocumentSymbolRequest>(&req) {
+                        self.document_symbol(id, params)
+                    } else if let Some((id, params)) = as_request::<HoverRequest>(&req) {
+                        self.hover(id, params)
+                    } else if let Some((id, params)) = as_request::<GotoDefinition>(&req) {
+                        self.goto_definition(id, params)
+                    }
                 }
                 Message::Notification(x) => {
                     if let Some(params) = as_notification::<DidOpenTextDocument>(&x) {
                         self.did_open(params)
                     } else if let Some(params) = as_notification::<DidChangeTextDocument>(&x) {
                         self.did_change(params)
+                    } else if let Some(params) = as_notification::<DidSaveTextDocument>(&x) {
+                        self.did_save(params)
                     } else if let Some(params) = as_notification::<DidCloseTextDocument>(&x) {
                         self.did_close(params)
                     }
@@ -167,10 +680,26 @@ pub fn server(starlark: Context) -> anyhow::Result<()> {
     // Run the server and wait for the two threads to end (typically by trigger LSP Exit event).
     let server_capabilities = serde_json::to_value(&Backend::server_capabilities()).unwrap();
     let initialization_params = connection.initialize(server_capabilities)?;
-    let initialization_params = serde_json::from_value(initialization_params).unwrap();
+    let initialization_params: InitializeParams =
+        serde_json::from_value(initialization_params).unwrap();
+    let root = match initialization_params
+        .root_uri
+        .as_ref()
+        .and_then(|x| x.to_file_path().ok())
+    {
+        Some(root) => root,
+        None => std::env::current_dir()?,
+    };
     Backend {
         connection,
-        starlark,
+        checker: RefCell::new(CheckerCache::new(
+            LspChecker {
+                starlark,
+                resolver: box WorkspaceResolver::new(root),
+            },
+            dialect(),
+        )),
+        documents: RefCell::new(HashMap::new()),
     }
     .main_loop(initialization_params)?;
     io_threads.join()?;
@@ -197,6 +726,20 @@ where
     }
 }
 
+fn as_request<T>(x: &Request) -> Option<(RequestId, T::Params)>
+where
+    T: lsp_types::request::Request,
+    T::Params: DeserializeOwned,
+{
+    if x.method == T::METHOD {
+        let params = serde_json::from_value(x.params.clone())
+            .unwrap_or_else(|err| panic!("Invalid request\nMethod: {}\n error: {}", x.method, err));
+        Some((x.id.clone(), params))
+    } else {
+        None
+    }
+}
+
 fn new_notification<T>(params: T::Params) -> Notification
 where
     T: lsp_types::notification::Notification,
@@ -206,3 +749,153 @@ where
         params: serde_json::to_value(&params).unwrap(),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use starlark::{environment::Module, eval::Evaluator};
+
+    use super::*;
+
+    fn hover(code: &str, line: usize, column: usize) -> Option<String> {
+        let module = AstModule::parse("hover.star", code.to_owned(), &dialect()).unwrap();
+        hover_markdown(&module, &globals(), line, column).map(|(markdown, _)| markdown)
+    }
+
+    #[test]
+    fn test_hover_global() {
+        let res = hover("x = len([])", 0, 5).unwrap();
+        assert!(res.starts_with("```starlark\ndef len("), "{}", res);
+        assert!(res.contains("get the length of a sequence"), "{}", res);
+    }
+
+    #[test]
+    fn test_hover_def() {
+        let code = r#"
+def add(x, y = 1):
+    """Add two numbers.
+
+    Works on anything with a `+`.
+    """
+    return x + y
+z = add(1)
+"#;
+        assert_eq!(
+            hover(code, 7, 5).unwrap(),
+            "```starlark\ndef add(x, y = 1)\n```\n\nAdd two numbers.\n\nWorks on anything with a `+`.\n"
+        );
+        assert_eq!(hover(code, 7, 0).unwrap(), "```starlark\nz = add(1)\n```\n");
+    }
+
+    #[test]
+    fn test_utf16_columns() {
+        // `é` is one UTF-16 code unit, `😀` is two.
+        let text = "x = 1\ns = \"é😀\" + name\n";
+        assert_eq!(to_utf16_column(text, 1, 7), 8);
+        assert_eq!(to_utf16_column(text, 1, 11), 12);
+        assert_eq!(from_utf16_column(text, 1, 12), 11);
+        // Within the surrogate pair is the emoji itself.
+        assert_eq!(from_utf16_column(text, 1, 7), 6);
+        assert_eq!(from_utf16_column(text, 0, 2), 2);
+        let range = to_range(
+            text,
+            ResolvedSpan {
+                begin_line: 1,
+                begin_column: 11,
+                end_line: 1,
+                end_column: 15,
+            },
+        );
+        assert_eq!(range.start, Position::new(1, 12));
+        assert_eq!(range.end, Position::new(1, 16));
+    }
+
+    #[test]
+    fn test_error_diagnostic() {
+        let code = "def f():\n    fail('oops', code = 'E1')\nf()\n";
+        let ast = AstModule::parse("file:///a.star", code.to_owned(), &dialect()).unwrap();
+        let module = Module::new();
+        let mut eval = Evaluator::new(&module);
+        let err = eval.eval_module(ast, &globals()).unwrap_err();
+        let diagnostic = to_diagnostic(code, StarlarkMessage::from_anyhow("file:///a.star", err));
+        // The code and call stack come from the error's `Diagnostic`.
+        assert_eq!(
+            Some(NumberOrString::String("E1".to_owned())),
+            diagnostic.code
+        );
+        let related = diagnostic.related_information.unwrap();
+        assert_eq!(
+            related.map(|x| (x.location.range.start, x.message.as_str())),
+            vec![
+                (Position::new(2, 0), "In call to `file:///a.star.f`"),
+                (Position::new(1, 4), "In call to `fail`"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_hover_unknown() {
+        assert_eq!(hover("x = unknown_name", 0, 6), None);
+        assert_eq!(hover("x = 1 + 2", 0, 6), None);
+    }
+
+    // Resolves loads within `/workspace`, reading the files from `files`.
+    struct MemoryResolver {
+        files: HashMap<Url, String>,
+    }
+
+    impl LoadResolver for MemoryResolver {
+        fn resolve(&self, path: &str, current_file: &Url) -> Option<Url> {
+            WorkspaceResolver::new(PathBuf::from("/workspace")).resolve(path, current_file)
+        }
+
+        fn contents(&self, url: &Url) -> Option<String> {
+            self.files.get(url).cloned()
+        }
+    }
+
+    #[test]
+    fn test_goto_definition() {
+        let lib = Url::parse("file:///workspace/lib/util.star").unwrap();
+        let resolver = MemoryResolver {
+            files: HashMap::from([(
+                lib.clone(),
+                "VERSION = 1\n\ndef helper(x):\n    return x\n".to_owned(),
+            )]),
+        };
+        let uri = Url::parse("file:///workspace/main.star").unwrap();
+        let code = r#"
+load("//lib:util.star", "helper", "missing", v = "VERSION")
+load("//lib:absent.star", "absent")
+def local(x):
+    return x
+y = local(helper(v)) + absent + missing
+"#;
+        let module = AstModule::parse(uri.as_str(), code.to_owned(), &dialect()).unwrap();
+        let goto = |line, column| {
+            definition_location(&module, &uri, code, line, column, &resolver, |url| {
+                resolver.contents(url)
+            })
+            .map(|x| {
+                format!(
+                    "{} {}:{}",
+                    x.uri.path(),
+                    x.range.start.line,
+                    x.range.start.character
+                )
+            })
+        };
+        // Definitions in this file.
+        assert_eq!(goto(5, 5).as_deref(), Some("/workspace/main.star 3:4"));
+        assert_eq!(goto(4, 11).as_deref(), Some("/workspace/main.star 3:10"));
+        // Through a load, including an alias.
+        assert_eq!(goto(5, 11).as_deref(), Some("/workspace/lib/util.star 2:4"));
+        assert_eq!(goto(5, 17).as_deref(), Some("/workspace/lib/util.star 0:0"));
+        // A missing file, or a symbol the file doesn't define.
+        assert_eq!(goto(5, 24), None);
+        assert_eq!(goto(5, 33), None);
+        // Not an identifier.
+        assert_eq!(goto(5, 20), None);
+    }
+}
@@ -25,22 +25,41 @@
 // Disagree these are good hints
 #![allow(clippy::type_complexity)]
 
-use std::{ffi::OsStr, fmt, fmt::Display, path::PathBuf, sync::Arc};
+use std::{
+    cell::RefCell,
+    ffi::OsStr,
+    fmt,
+    fmt::Display,
+    fs,
+    io::{self, Read, Write},
+    mem,
+    path::PathBuf,
+    sync::Arc,
+};
 
 use anyhow::anyhow;
 use eval::Context;
 use gazebo::prelude::*;
 use itertools::Either;
-use starlark::read_line::ReadLine;
+use starlark::{
+    errors::{Diagnostic, LintBaseline, LintConfig, LintSeverity},
+    read_line::ReadLine,
+    syntax::AstModule,
+};
 use structopt::{clap::AppSettings, StructOpt};
 use walkdir::WalkDir;
 
-use crate::types::{LintMessage, Message, Severity};
+use crate::{
+    types::{LintMessage, Message, Severity},
+    watch::Watched,
+};
 
 mod dap;
 mod eval;
 mod lsp;
+mod suppression;
 mod types;
+mod watch;
 
 #[derive(Debug, StructOpt)]
 #[structopt(
@@ -66,9 +85,57 @@ pub struct Args {
     #[structopt(long = "check", help = "Run checks and lints.")]
     check: bool,
 
-    #[structopt(long = "info", help = "Show information about the code.")]
+    #[structopt(
+        long = "info",
+        help = "Show information about the code, as one JSON object per file with `--json`."
+    )]
     info: bool,
 
+    #[structopt(
+        long = "ast",
+        help = "Print the parsed syntax tree, without evaluating."
+    )]
+    ast: bool,
+
+    #[structopt(
+        long = "dump-bytecode",
+        help = "Print the bytecode of each function after evaluating."
+    )]
+    dump_bytecode: bool,
+
+    #[structopt(
+        long = "lint-allow",
+        number_of_values = 1,
+        help = "A lint to not report, e.g. `unused-load`. May be repeated."
+    )]
+    lint_allow: Vec<String>,
+
+    #[structopt(
+        long = "lint-deny",
+        number_of_values = 1,
+        help = "A lint to report as an error. May be repeated."
+    )]
+    lint_deny: Vec<String>,
+
+    #[structopt(
+        long = "baseline",
+        number_of_values = 1,
+        help = "A file of known lints, written by `--write-baseline`, to report as disabled. May be repeated."
+    )]
+    baseline: Vec<PathBuf>,
+
+    #[structopt(
+        long = "write-baseline",
+        help = "Write the lints found to a file, for use with `--baseline`."
+    )]
+    write_baseline: Option<PathBuf>,
+
+    #[structopt(
+        long = "docs",
+        help = "Print the documentation of the evaluated files, or of the standard globals if there are none, as markdown, or JSON with `--json`."
+    )]
+    docs: bool,
+
     #[structopt(long = "json", help = "Show output as JSON lines.")]
     json: bool,
 
@@ -79,6 +146,20 @@ pub struct Args {
     )]
     repeat: usize,
 
+    #[structopt(
+        long = "jobs",
+        short = "j",
+        help = "Number of threads to evaluate files on. Each file is evaluated in its own module.",
+        default_value = "1"
+    )]
+    jobs: usize,
+
+    #[structopt(
+        long = "watch",
+        help = "After evaluating the files, keep watching them, re-checking each file when it changes."
+    )]
+    watch: bool,
+
     #[structopt(
         long = "extension",
         help = "File extension when searching directories."
@@ -96,6 +177,9 @@ pub struct Args {
     )]
     evaluate: Vec<String>,
 
+    #[structopt(long = "stdin", help = "Evaluate the code read from standard input.")]
+    stdin: bool,
+
     #[structopt(name = "FILE", help = "Files to evaluate.")]
     files: Vec<PathBuf>,
 }
@@ -128,14 +212,21 @@ struct Stats {
     warning: usize,
     advice: usize,
     disabled: usize,
+    /// Lints in the baseline which no longer occur.
+    stale: usize,
 }
 
 impl Display for Stats {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.write_str(&format!(
+        write!(
+            f,
             "{} files, {} errors, {} warnings, {} advices, {} disabled",
             self.file, self.error, self.warning, self.advice, self.disabled
-        ))
+        )?;
+        if self.stale > 0 {
+            write!(f, ", {} stale", self.stale)?;
+        }
+        Ok(())
     }
 }
 
@@ -155,29 +246,112 @@ impl Stats {
 }
 
 fn drain(xs: impl Iterator<Item = Message>, json: bool, stats: &mut Stats) {
+    // Printing to stdout panics on failure, so do the same here.
+    write_messages(xs, json, stats, &mut io::stdout()).unwrap()
+}
+
+fn write_messages(
+    xs: impl Iterator<Item = Message>,
+    json: bool,
+    stats: &mut Stats,
+    out: &mut impl Write,
+) -> io::Result<()> {
     for x in xs {
         stats.increment(x.severity);
+        if x.suppressed {
+            continue;
+        }
         if json {
-            println!("{}", serde_json::to_string(&LintMessage::new(x)).unwrap());
+            writeln!(
+                out,
+                "{}",
+                serde_json::to_string(&LintMessage::new(x)).unwrap()
+            )?;
         } else if let Some(error) = x.full_error_with_span {
             let mut error = error.to_owned();
             if !error.is_empty() && !error.ends_with('\n') {
                 error.push('\n');
             }
-            print!("{}", error);
+            write!(out, "{}", error)?;
         } else {
-            println!("{}", x);
+            writeln!(out, "{}", x)?;
+        }
+    }
+    Ok(())
+}
+
+fn lint_config(allow: &[String], deny: &[String]) -> anyhow::Result<LintConfig> {
+    let mut res = LintConfig::default();
+    for x in allow {
+        res.disable(x)?;
+    }
+    for x in deny {
+        res.set_severity(x, LintSeverity::Error)?;
+    }
+    Ok(res)
+}
+
+fn read_stdin() -> anyhow::Result<String> {
+    let mut content = String::new();
+    io::stdin().read_to_string(&mut content)?;
+    Ok(content)
+}
+
+/// Lines typed into the REPL which haven't been evaluated yet.
+#[derive(Default)]
+struct ReplInput {
+    code: String,
+    /// Set once the input opens a block, which is only finished by a blank line.
+    block: bool,
+}
+
+impl ReplInput {
+    fn prompt(&self) -> &'static str {
+        if self.code.is_empty() {
+            "$> "
+        } else {
+            "..> "
+        }
+    }
+
+    /// Add a line, returning the code to evaluate once it is complete,
+    /// or `None` if more lines are needed.
+    fn add_line(&mut self, line: &str) -> Option<String> {
+        let blank = line.trim().is_empty();
+        if blank && self.code.is_empty() {
+            return None;
+        }
+        self.code.push_str(line);
+        self.code.push('\n');
+        // A blank line finishes a block, or forces the error for broken input.
+        if !blank && (self.block || Self::is_incomplete(&self.code)) {
+            self.block |= line.trim_end().ends_with(':');
+            return None;
+        }
+        self.block = false;
+        Some(mem::take(&mut self.code))
+    }
+
+    fn is_incomplete(code: &str) -> bool {
+        match AstModule::parse("expression", code.to_owned(), &eval::dialect()) {
+            Ok(_) => false,
+            Err(e) => e
+                .downcast_ref::<Diagnostic>()
+                .map_or(false, |d| d.is_incomplete_input()),
         }
     }
 }
 
 fn interactive(ctx: &Context) -> anyhow::Result<()> {
     let mut rl = ReadLine::new();
+    let mut input = ReplInput::default();
     loop {
-        match rl.read_line("$> ")? {
+        match rl.read_line(input.prompt())? {
             Some(line) => {
-                let mut stats = Stats::default();
-                drain(ctx.expression(line), false, &mut stats);
+                if let Some(code) = input.add_line(&line) {
+                    let mut stats = Stats::default();
+                    drain(ctx.expression(code), false, &mut stats);
+                }
             }
             // User pressed EOF - disconnected terminal, or similar
             None => return Ok(()),
@@ -190,6 +364,15 @@ fn main() -> anyhow::Result<()> {
 
     let args = argfile::expand_args(argfile::parse_fromfile, argfile::PREFIX)?;
     let args = Args::from_iter(args);
+    if args.docs && args.files.is_empty() && args.evaluate.is_empty() && !args.stdin {
+        let docs = eval::globals().documentation();
+        if args.json {
+            println!("{}", serde_json::to_string(&docs)?);
+        } else {
+            print!("{}", docs.render_as_markdown("globals"));
+        }
+        return Ok(());
+    }
     let ext = args
         .extension
         .as_ref()
@@ -198,11 +381,39 @@ fn main() -> anyhow::Result<()> {
     let mut ctx = Context::new(
         args.check,
         args.info,
-        !args.check && !args.info,
+        args.ast,
+        args.json,
+        !args.check && !args.info && !args.ast,
+        args.dump_bytecode,
         &expand_dirs(ext, args.prelude).collect::<Vec<_>>(),
         args.interactive,
     )?;
 
+    ctx.lint_config = lint_config(&args.lint_allow, &args.lint_deny)?;
+    ctx.docs = args.docs;
+    if !args.baseline.is_empty() {
+        let mut baseline = LintBaseline::default();
+        for x in &args.baseline {
+            baseline.merge(LintBaseline::from_json(&fs::read_to_string(x)?)?);
+        }
+        ctx.baseline = Some(RefCell::new(baseline));
+    }
+    if args.write_baseline.is_some() {
+        ctx.write_baseline = Some(RefCell::new(LintBaseline::default()));
+    }
+
+    // Standard input can only be consumed once, so read it before any repeats.
+    let stdin = if args.stdin {
+        Some(read_stdin()?)
+    } else {
+        None
+    };
+
+    let mut watched = if args.watch {
+        Some(Watched::new(ext, &args.files)?)
+    } else {
+        None
+    };
     let mut stats = Stats::default();
     for _ in 0..args.repeat {
         for e in args.evaluate.clone() {
@@ -210,9 +421,46 @@ fn main() -> anyhow::Result<()> {
             drain(ctx.expression(e), args.json, &mut stats);
         }
 
-        for file in expand_dirs(ext, args.files.clone()) {
+        if args.jobs > 1 && ctx.module.is_none() {
+            let files = expand_dirs(ext, args.files.clone()).collect::<Vec<_>>();
+            for (file, messages) in files.iter().zip(ctx.files(&files, args.jobs)) {
+                stats.increment_file();
+                if let Some(watched) = &mut watched {
+                    watched.record(file, &messages);
+                }
+                drain(messages.into_iter(), args.json, &mut stats);
+            }
+        } else {
+            for file in expand_dirs(ext, args.files.clone()) {
+                stats.increment_file();
+                let messages = ctx.file(&file).collect::<Vec<_>>();
+                if let Some(watched) = &mut watched {
+                    watched.record(&file, &messages);
+                }
+                drain(messages.into_iter(), args.json, &mut stats);
+            }
+        }
+
+        if let Some(content) = &stdin {
             stats.increment_file();
-            drain(ctx.file(&file), args.json, &mut stats);
+            drain(
+                ctx.file_with_contents("<stdin>", content.clone()),
+                args.json,
+                &mut stats,
+            );
+        }
+    }
+
+    if let (Some(path), Some(baseline)) = (&args.write_baseline, &ctx.write_baseline) {
+        fs::write(path, baseline.borrow().to_json())?;
+    }
+    if let Some(baseline) = &ctx.baseline {
+        let stale = baseline.borrow().stale();
+        stats.stale = stale.len();
+        if !args.json {
+            for x in stale {
+                println!("Stale baseline lint: {}: {}", x.file, x.name);
+            }
         }
     }
 
@@ -223,6 +471,7 @@ fn main() -> anyhow::Result<()> {
     if args.lsp {
         ctx.check = true;
         ctx.info = false;
+        ctx.ast = false;
         ctx.run = false;
         lsp::server(ctx)?;
     } else if args.dap {
@@ -231,9 +480,241 @@ fn main() -> anyhow::Result<()> {
 
     if !args.json {
         println!("{}", stats);
-        if stats.error > 0 {
-            return Err(anyhow!("Failed with {} errors", stats.error));
-        }
+    }
+    if let Some(watched) = watched {
+        watched.run(&ctx, args.json)?;
+    }
+    if !args.json && stats.error > 0 {
+        return Err(anyhow!("Failed with {} errors", stats.error));
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_suppressed_lints() {
+        let ctx = Context::new(true, false, false, true, false, false, &[], false).unwrap();
+        let code = r#"
+x = 1
+load("a.bzl", "a")
+load("b.bzl", "b")  # starlark-lint: disable=unused-load, misplaced-load
+# starlark-lint: disable
+load("c.bzl", "c")
+y = a + b + c
+"#;
+        let mut stats = Stats::default();
+        let mut out = Vec::new();
+        write_messages(
+            ctx.file_with_contents("test.bzl", code.to_owned()),
+            true,
+            &mut stats,
+            &mut out,
+        )
+        .unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let reported = out
+            .lines()
+            .map(|x| serde_json::from_str::<serde_json::Value>(x).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(reported.len(), 1);
+        assert_eq!(reported[0]["name"], "misplaced-load");
+        assert_eq!(reported[0]["line"], 3);
+        assert_eq!(stats.warning, 1);
+        assert_eq!(stats.disabled, 2);
+    }
+
+    #[test]
+    fn test_json_error() {
+        let ctx = Context::new(false, false, false, true, true, false, &[], false).unwrap();
+        let code = "def f():\n    fail('oops', code = 'E1')\nf()\n";
+        let mut stats = Stats::default();
+        let mut out = Vec::new();
+        write_messages(
+            ctx.file_with_contents("test.bzl", code.to_owned()),
+            true,
+            &mut stats,
+            &mut out,
+        )
+        .unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let reported = out
+            .lines()
+            .map(|x| serde_json::from_str::<serde_json::Value>(x).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(reported.len(), 1);
+        assert_eq!(reported[0]["line"], 2);
+        // The error is the serialized `Diagnostic`, as library users get it.
+        let diagnostic = &reported[0]["diagnostic"];
+        assert_eq!(diagnostic["code"], "E1");
+        assert!(diagnostic["message"].as_str().unwrap().contains("oops"));
+        assert_eq!(
+            diagnostic["call_stack"],
+            serde_json::json!([
+                {
+                    "name": "test.bzl.f",
+                    "location": {
+                        "path": "test.bzl",
+                        "begin_line": 3,
+                        "begin_column": 1,
+                        "end_line": 3,
+                        "end_column": 4
+                    }
+                },
+                {
+                    "name": "fail",
+                    "location": {
+                        "path": "test.bzl",
+                        "begin_line": 2,
+                        "begin_column": 5,
+                        "end_line": 2,
+                        "end_column": 30
+                    }
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn test_lint_config() {
+        let code = "load('a.bzl', 'a')\ndef f():\n    return\n    print(1)\n";
+        let run = |ctx: &Context| {
+            let mut stats = Stats::default();
+            let mut out = Vec::new();
+            write_messages(
+                ctx.file_with_contents("test.bzl", code.to_owned()),
+                true,
+                &mut stats,
+                &mut out,
+            )
+            .unwrap();
+            let names = String::from_utf8(out)
+                .unwrap()
+                .lines()
+                .map(|x| serde_json::from_str::<serde_json::Value>(x).unwrap()["name"].to_string())
+                .collect::<Vec<_>>();
+            (names, stats)
+        };
+
+        let mut ctx = Context::new(true, false, false, true, false, false, &[], false).unwrap();
+        let (names, stats) = run(&ctx);
+        assert_eq!(names, &["\"unreachable\"", "\"unused-load\""]);
+        assert_eq!((stats.error, stats.warning, stats.disabled), (0, 1, 1));
+
+        ctx.lint_config = lint_config(&["unused-load".to_owned()], &[]).unwrap();
+        let (names, stats) = run(&ctx);
+        assert_eq!(names, &["\"unreachable\""]);
+        assert_eq!((stats.error, stats.warning, stats.disabled), (0, 1, 0));
+
+        ctx.lint_config = lint_config(&[], &["unreachable".to_owned()]).unwrap();
+        let (names, stats) = run(&ctx);
+        assert_eq!(names, &["\"unreachable\"", "\"unused-load\""]);
+        assert_eq!((stats.error, stats.warning, stats.disabled), (1, 0, 1));
+
+        let err = lint_config(&["unused-loads".to_owned()], &[]).unwrap_err();
+        assert!(err.to_string().contains("unused-load, "));
+    }
+
+    #[test]
+    fn test_baseline() {
+        let check = |ctx: &Context, code: &str| {
+            let mut stats = Stats::default();
+            write_messages(
+                ctx.file_with_contents("test.bzl", code.to_owned()),
+                true,
+                &mut stats,
+                &mut Vec::new(),
+            )
+            .unwrap();
+            (stats.warning, stats.disabled)
+        };
+        let code = "def f():\n    return 1\n    print(1)\n";
+
+        let mut ctx = Context::new(true, false, false, true, false, false, &[], false).unwrap();
+        ctx.write_baseline = Some(RefCell::new(LintBaseline::default()));
+        // Checking a file again, as with `--repeat` or `--watch`, doesn't record its lints twice.
+        assert_eq!(check(&ctx, code), (1, 0));
+        assert_eq!(check(&ctx, code), (1, 0));
+        let written = ctx.write_baseline.unwrap().into_inner();
+        assert_eq!(written.len(), 1);
+        let json = written.to_json();
+
+        let mut ctx = Context::new(true, false, false, true, false, false, &[], false).unwrap();
+        ctx.baseline = Some(RefCell::new(LintBaseline::from_json(&json).unwrap()));
+        // The known lint is disabled, but a new one still warns, each time the file is checked.
+        let code = format!("{}def g():\n    return 2\n    print(2)\n", code);
+        assert_eq!(check(&ctx, &code), (1, 1));
+        assert_eq!(check(&ctx, &code), (1, 1));
+        assert!(ctx.baseline.unwrap().into_inner().stale().is_empty());
+    }
+
+    #[test]
+    fn test_check_bad_file() {
+        let dir = std::env::temp_dir().join(format!("starlark_check_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("bad.bzl"), "x = 1\ny = \0\n").unwrap();
+        std::fs::write(dir.join("good.bzl"), "x = 1\n").unwrap();
+        std::fs::write(dir.join("binary.txt"), b"x = '\xff'\n").unwrap();
+
+        let ctx = Context::new(true, false, false, true, false, false, &[], false).unwrap();
+        let mut stats = Stats::default();
+        let mut messages = Vec::new();
+        for file in expand_dirs("bzl", vec![dir.clone()]) {
+            stats.increment_file();
+            for x in ctx.file(&file) {
+                stats.increment(x.severity);
+                messages.push(x);
+            }
+        }
+        let binary = ctx.file(&dir.join("binary.txt")).collect::<Vec<_>>();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // The bad file gives one error, and the good file is still checked.
+        assert_eq!((stats.file, stats.error, stats.warning), (2, 1, 0));
+        assert_eq!(messages.len(), 1);
+        assert!(messages[0].path.ends_with("bad.bzl"));
+        assert!(
+            messages[0]
+                .description
+                .contains("NUL byte at byte offset 10"),
+            "{}",
+            messages[0].description
+        );
+        assert_eq!(binary.len(), 1);
+        assert!(binary[0]
+            .description
+            .contains("invalid byte at byte offset 5"));
+    }
+
+    #[test]
+    fn test_repl_input() {
+        let mut input = ReplInput::default();
+        let mut feed = |line: &str| input.add_line(line);
+
+        assert_eq!(feed(""), None);
+        assert_eq!(feed("x = 1"), Some("x = 1\n".to_owned()));
+
+        // Blocks wait for a blank line.
+        assert_eq!(feed("def f(x):"), None);
+        assert_eq!(feed("    if x:"), None);
+        assert_eq!(feed("        return 1"), None);
+        assert_eq!(feed("    return 2"), None);
+        assert_eq!(
+            feed(""),
+            Some("def f(x):\n    if x:\n        return 1\n    return 2\n\n".to_owned())
+        );
+
+        // Brackets finish as soon as they are closed.
+        assert_eq!(feed("y = [1,"), None);
+        assert_eq!(feed("  2]"), Some("y = [1,\n  2]\n".to_owned()));
+        assert_eq!(feed("s = \"\"\"a"), None);
+        assert_eq!(feed("b\"\"\""), Some("s = \"\"\"a\nb\"\"\"\n".to_owned()));
+
+        // Genuine errors are evaluated straight away, incomplete ones on a blank line.
+        assert_eq!(feed("x = 1 +"), Some("x = 1 +\n".to_owned()));
+        assert_eq!(feed("f(1,"), None);
+        assert_eq!(feed(""), Some("f(1,\n\n".to_owned()));
+    }
+}
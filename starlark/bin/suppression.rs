@@ -0,0 +1,110 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Inline `# starlark-lint: disable=<lint-name>` comments.
+
+use std::collections::HashMap;
+
+use starlark::{codemap::CodeMap, errors::Lint};
+
+const MARKER: &str = "starlark-lint:";
+
+enum Suppression {
+    All,
+    Names(Vec<String>),
+}
+
+impl Suppression {
+    /// Parse the text following a `#`, e.g. ` starlark-lint: disable=a,b`.
+    fn parse(comment: &str) -> Option<Self> {
+        let rest = comment.trim().strip_prefix(MARKER)?.trim();
+        let rest = rest.strip_prefix("disable")?.trim();
+        if rest.is_empty() {
+            return Some(Suppression::All);
+        }
+        let names = rest.strip_prefix('=')?;
+        Some(Suppression::Names(
+            names
+                .split(',')
+                .map(|x| x.trim().to_owned())
+                .filter(|x| !x.is_empty())
+                .collect(),
+        ))
+    }
+
+    fn merge(&mut self, other: Suppression) {
+        match (self, other) {
+            (Suppression::Names(xs), Suppression::Names(ys)) => xs.extend(ys),
+            (me, _) => *me = Suppression::All,
+        }
+    }
+
+    fn covers(&self, name: &str) -> bool {
+        match self {
+            Suppression::All => true,
+            Suppression::Names(xs) => xs.iter().any(|x| x == name),
+        }
+    }
+}
+
+/// The lints silenced by comments in a file, keyed by the 0-based line they apply to.
+/// A comment after some code applies to its own line,
+/// a comment on a line by itself applies to the following line.
+#[derive(Default)]
+pub struct Suppressions(HashMap<usize, Suppression>);
+
+impl Suppressions {
+    pub fn new(codemap: &CodeMap) -> Self {
+        let mut res = HashMap::new();
+        for line in 0..codemap.num_lines() {
+            let text = codemap.source_line(line);
+            // Cheap rejection, since most lines won't mention the marker.
+            if !text.contains(MARKER) {
+                continue;
+            }
+            for (i, _) in text.match_indices('#') {
+                if let Some(x) = Suppression::parse(&text[i + 1..]) {
+                    let target = if text[..i].trim().is_empty() {
+                        line + 1
+                    } else {
+                        line
+                    };
+                    match res.get_mut(&target) {
+                        None => {
+                            res.insert(target, x);
+                        }
+                        Some(old) => old.merge(x),
+                    }
+                    break;
+                }
+            }
+        }
+        Self(res)
+    }
+
+    /// Is the lint silenced by a comment covering the line it starts on.
+    pub fn is_suppressed(&self, lint: &Lint) -> bool {
+        if self.0.is_empty() {
+            return false;
+        }
+        let line = lint.location.resolve_span().begin_line;
+        match self.0.get(&line) {
+            None => false,
+            Some(x) => x.covers(&lint.short_name),
+        }
+    }
+}
@@ -21,7 +21,7 @@ use gazebo::prelude::*;
 use serde::Serialize;
 use starlark::{
     codemap::ResolvedSpan,
-    errors::{Diagnostic, Lint},
+    errors::{Diagnostic, DiagnosticSpan, Lint, LintSeverity},
 };
 
 /// A standardised set of severities.
@@ -30,12 +30,21 @@ use starlark::{
 pub enum Severity {
     Error,
     Warning,
-    // Not all severities are used right now
-    #[allow(dead_code)]
     Advice,
     Disabled,
 }
 
+impl Severity {
+    fn from_lint(x: LintSeverity) -> Self {
+        match x {
+            LintSeverity::Error => Severity::Error,
+            LintSeverity::Warning => Severity::Warning,
+            LintSeverity::Advice => Severity::Advice,
+            LintSeverity::Disabled => Severity::Disabled,
+        }
+    }
+}
+
 impl Display for Severity {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.write_str(match self {
@@ -57,6 +66,47 @@ pub struct Message {
     pub full_error_with_span: Option<String>,
     /// The text referred to by span
     pub original: Option<String>,
+    /// An edit which would resolve the problem
+    pub fix: Option<Fix>,
+    /// Silenced by a `# starlark-lint: disable` comment, so counted but not reported
+    pub suppressed: bool,
+    /// The code, call stack and secondary spans of an error's `Diagnostic`
+    pub error: Option<ErrorDetails>,
+}
+
+/// The parts of a `Diagnostic` beyond its message and span.
+#[derive(Debug, Clone)]
+pub struct ErrorDetails {
+    /// See `Diagnostic::error_code`
+    pub code: Option<String>,
+    /// The name of each function called, outermost first, with the location of the call
+    pub call_stack: Vec<(String, Option<DiagnosticSpan>)>,
+    /// Other locations relevant to the error, each with a label
+    pub secondary_spans: Vec<(DiagnosticSpan, String)>,
+}
+
+impl ErrorDetails {
+    fn new(x: &Diagnostic) -> Self {
+        Self {
+            code: x.error_code().map(str::to_owned),
+            call_stack: x.call_stack.map(|x| {
+                (
+                    x.name.clone(),
+                    x.location.as_ref().map(DiagnosticSpan::from),
+                )
+            }),
+            secondary_spans: x
+                .secondary_spans
+                .map(|(span, label)| (DiagnosticSpan::from(span), label.clone())),
+        }
+    }
+}
+
+/// Replace the text at `span` with `replacement`.
+#[derive(Debug, Clone)]
+pub struct Fix {
+    pub span: ResolvedSpan,
+    pub replacement: String,
 }
 
 impl Display for Message {
@@ -71,14 +121,8 @@ impl Display for Message {
 
 impl Message {
     pub fn from_anyhow(file: &str, x: anyhow::Error) -> Self {
-        match x.downcast_ref::<Diagnostic>() {
-            Some(
-                d @ Diagnostic {
-                    message,
-                    span: Some(span),
-                    ..
-                },
-            ) => {
+        match Diagnostic::from_error(&x).and_then(|d| Some((d, d.span.as_ref()?))) {
+            Some((d, span)) => {
                 let original = span.source_span().to_owned();
                 let resolved_span = span.resolve_span();
                 Self {
@@ -86,9 +130,12 @@ impl Message {
                     span: Some(resolved_span),
                     severity: Severity::Error,
                     name: "error".to_owned(),
-                    description: format!("{:#}", message),
+                    description: d.primary_message(),
                     full_error_with_span: Some(d.to_string()),
                     original: Some(original),
+                    fix: None,
+                    suppressed: false,
+                    error: Some(ErrorDetails::new(d)),
                 }
             }
             _ => Self {
@@ -99,24 +146,32 @@ impl Message {
                 description: format!("{:#}", x),
                 full_error_with_span: None,
                 original: None,
+                fix: None,
+                suppressed: false,
+                error: None,
             },
         }
     }
 
-    pub fn from_lint(x: Lint) -> Self {
+    pub fn from_lint(x: Lint, suppressed: bool) -> Self {
         Self {
             path: x.location.file().filename().to_owned(),
             span: Some(x.location.resolve_span()),
-            severity: if x.serious {
-                Severity::Warning
-            } else {
-                // Start with all non-serious errors disabled, and ramp up from there
+            severity: if suppressed {
                 Severity::Disabled
+            } else {
+                Severity::from_lint(x.severity)
             },
             name: x.short_name,
             description: x.problem,
             full_error_with_span: None,
             original: Some(x.original),
+            fix: x.fix.map(|x| Fix {
+                span: x.location.resolve_span(),
+                replacement: x.replacement,
+            }),
+            suppressed,
+            error: None,
         }
     }
 }
@@ -134,10 +189,66 @@ pub struct LintMessage {
     description: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     original: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fix: Option<LintFix>,
+    /// An error in the same form as a serialized `Diagnostic`, as library users get it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    diagnostic: Option<LintDiagnostic>,
+}
+
+/// The fields of a serialized `Diagnostic`.
+#[derive(Debug, Clone, Serialize)]
+pub struct LintDiagnostic {
+    code: Option<String>,
+    message: String,
+    span: Option<DiagnosticSpan>,
+    call_stack: Vec<LintFrame>,
+    secondary_spans: Vec<LintSecondarySpan>,
+}
+
+/// A call stack frame of a [`LintDiagnostic`].
+#[derive(Debug, Clone, Serialize)]
+pub struct LintFrame {
+    name: String,
+    location: Option<DiagnosticSpan>,
+}
+
+/// A labelled secondary span of a [`LintDiagnostic`].
+#[derive(Debug, Clone, Serialize)]
+pub struct LintSecondarySpan {
+    span: DiagnosticSpan,
+    label: String,
+}
+
+/// A replacement for part of the file, with 1-based lines and characters.
+#[derive(Debug, Clone, Serialize)]
+pub struct LintFix {
+    line: usize,
+    char: usize,
+    end_line: usize,
+    end_char: usize,
+    replacement: String,
 }
 
 impl LintMessage {
     pub fn new(x: Message) -> Self {
+        let diagnostic = x.error.map(|error| LintDiagnostic {
+            code: error.code,
+            message: x.description.clone(),
+            span: x.span.map(|span| DiagnosticSpan {
+                path: x.path.clone(),
+                begin_line: span.begin_line + 1,
+                begin_column: span.begin_column + 1,
+                end_line: span.end_line + 1,
+                end_column: span.end_column + 1,
+            }),
+            call_stack: error
+                .call_stack
+                .into_map(|(name, location)| LintFrame { name, location }),
+            secondary_spans: error
+                .secondary_spans
+                .into_map(|(span, label)| LintSecondarySpan { span, label }),
+        });
         Self {
             path: x.path,
             line: x.span.map(|x| x.begin_line + 1),
@@ -147,6 +258,14 @@ impl LintMessage {
             name: x.name,
             description: Some(x.description),
             original: x.original,
+            fix: x.fix.map(|x| LintFix {
+                line: x.span.begin_line + 1,
+                char: x.span.begin_column + 1,
+                end_line: x.span.end_line + 1,
+                end_char: x.span.end_column + 1,
+                replacement: x.replacement,
+            }),
+            diagnostic,
         }
     }
 }
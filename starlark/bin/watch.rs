@@ -0,0 +1,392 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Re-check files as they change, for `--watch`.
+
+use std::{
+    collections::BTreeMap,
+    env,
+    ffi::OsStr,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::mpsc,
+    time::Duration,
+};
+
+use gazebo::prelude::*;
+use notify::{
+    event::{ModifyKind, RenameMode},
+    Event, EventKind, RecursiveMode, Watcher,
+};
+use serde::Serialize;
+
+use crate::{
+    eval::Context,
+    expand_dirs,
+    types::{Message, Severity},
+    write_messages, Stats,
+};
+
+/// Editors often save a file in several steps, so wait this long for more events
+/// before re-checking.
+const SETTLE: Duration = Duration::from_millis(50);
+
+/// A change to a path on disk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    Created(PathBuf),
+    Modified(PathBuf),
+    /// Deleted, or renamed to something else.
+    Removed(PathBuf),
+}
+
+impl Change {
+    /// The changes described by a filesystem event.
+    pub fn from_event(event: Event) -> Vec<Change> {
+        let mut paths = event.paths.into_iter();
+        match event.kind {
+            EventKind::Create(_) | EventKind::Modify(ModifyKind::Name(RenameMode::To)) => {
+                paths.map(Change::Created).collect()
+            }
+            EventKind::Remove(_) | EventKind::Modify(ModifyKind::Name(RenameMode::From)) => {
+                paths.map(Change::Removed).collect()
+            }
+            EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => {
+                match (paths.next(), paths.next()) {
+                    (Some(from), Some(to)) => vec![Change::Removed(from), Change::Created(to)],
+                    _ => Vec::new(),
+                }
+            }
+            // Some platforms don't say which side of the rename a path is on.
+            EventKind::Modify(ModifyKind::Name(_)) => paths
+                .map(|x| {
+                    if x.exists() {
+                        Change::Created(x)
+                    } else {
+                        Change::Removed(x)
+                    }
+                })
+                .collect(),
+            EventKind::Modify(ModifyKind::Metadata(_)) => Vec::new(),
+            EventKind::Modify(_) => paths.map(Change::Modified).collect(),
+            EventKind::Access(_) | EventKind::Any | EventKind::Other => Vec::new(),
+        }
+    }
+
+    fn path(&self) -> &Path {
+        match self {
+            Change::Created(x) | Change::Modified(x) | Change::Removed(x) => x,
+        }
+    }
+}
+
+/// Written in JSON mode for a file which no longer has any messages, because it was fixed
+/// or removed, so consumers know to drop the messages previously reported for it.
+#[derive(Serialize)]
+struct Cleared {
+    path: String,
+    cleared: bool,
+}
+
+/// The files being watched, and the severity of each message last reported for them.
+pub struct Watched {
+    extension: String,
+    cwd: PathBuf,
+    /// The files and directories given on the command line, made absolute.
+    roots: Vec<PathBuf>,
+    files: BTreeMap<PathBuf, Vec<Severity>>,
+}
+
+impl Watched {
+    pub fn new(extension: &str, roots: &[PathBuf]) -> anyhow::Result<Self> {
+        let cwd = env::current_dir()?;
+        Ok(Self {
+            extension: extension.to_owned(),
+            roots: roots.map(|x| cwd.join(x)),
+            cwd,
+            files: BTreeMap::new(),
+        })
+    }
+
+    /// Record the messages reported for `file`, replacing any from before.
+    pub fn record(&mut self, file: &Path, messages: &[Message]) {
+        self.files
+            .insert(self.cwd.join(file), messages.map(|x| x.severity));
+    }
+
+    /// The totals over the latest messages of every file.
+    pub fn stats(&self) -> Stats {
+        let mut stats = Stats::default();
+        for severities in self.files.values() {
+            stats.increment_file();
+            for x in severities {
+                stats.increment(*x);
+            }
+        }
+        stats
+    }
+
+    /// Given files are always watched, and other files if they are in a given directory
+    /// and have the right extension.
+    fn is_watched(&self, path: &Path) -> bool {
+        self.roots.iter().any(|root| {
+            path == root
+                || (path.starts_with(root) && path.extension() == Some(OsStr::new(&self.extension)))
+        })
+    }
+
+    /// Print paths under the current directory the same way as on the command line.
+    fn display_path<'a>(&self, path: &'a Path) -> &'a Path {
+        path.strip_prefix(&self.cwd).unwrap_or(path)
+    }
+
+    /// Re-check the files affected by `changes`, writing their messages to `out`,
+    /// followed by the refreshed totals unless writing JSON. When writing JSON,
+    /// files with no messages left, including removed files, get a `Cleared` record instead.
+    /// Returns `false` if none of the changes were to watched files.
+    pub fn update(
+        &mut self,
+        ctx: &Context,
+        changes: impl IntoIterator<Item = Change>,
+        json: bool,
+        out: &mut impl Write,
+    ) -> io::Result<bool> {
+        // Only the last change to each path matters.
+        let mut latest = BTreeMap::new();
+        for change in changes {
+            latest.insert(self.cwd.join(change.path()), change);
+        }
+
+        let mut changed = false;
+        for (path, change) in latest {
+            if let Change::Removed(_) = change {
+                // A removed directory takes all the files in it with it.
+                let removed = self
+                    .files
+                    .keys()
+                    .filter(|x| x.starts_with(&path))
+                    .cloned()
+                    .collect::<Vec<_>>();
+                for file in removed {
+                    self.files.remove(&file);
+                    if json {
+                        self.write_cleared(&file, out)?;
+                    }
+                    changed = true;
+                }
+                continue;
+            }
+            let files = if path.is_dir() {
+                expand_dirs(&self.extension, vec![path]).collect()
+            } else {
+                vec![path]
+            };
+            for file in files {
+                if !self.is_watched(&file) {
+                    continue;
+                }
+                let messages = ctx.file(self.display_path(&file)).collect::<Vec<_>>();
+                self.record(&file, &messages);
+                let clean = messages.iter().all(|x| x.suppressed);
+                write_messages(messages.into_iter(), json, &mut Stats::default(), out)?;
+                if json && clean {
+                    self.write_cleared(&file, out)?;
+                }
+                changed = true;
+            }
+        }
+        if changed && !json {
+            writeln!(out, "{}", self.stats())?;
+        }
+        Ok(changed)
+    }
+
+    fn write_cleared(&self, file: &Path, out: &mut impl Write) -> io::Result<()> {
+        let cleared = Cleared {
+            path: self.display_path(file).to_string_lossy().into_owned(),
+            cleared: true,
+        };
+        writeln!(out, "{}", serde_json::to_string(&cleared).unwrap())
+    }
+
+    /// Watch the files until interrupted, re-checking them as they change.
+    pub fn run(mut self, ctx: &Context, json: bool) -> anyhow::Result<()> {
+        let (tx, rx) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(tx)?;
+        for root in &self.roots {
+            if root.is_dir() {
+                watcher.watch(root, RecursiveMode::Recursive)?;
+            } else if let Some(parent) = root.parent() {
+                // Watch the directory, so we notice the file being recreated after a delete.
+                watcher.watch(parent, RecursiveMode::NonRecursive)?;
+            }
+        }
+        loop {
+            let mut changes = Change::from_event(rx.recv()??);
+            while let Ok(event) = rx.recv_timeout(SETTLE) {
+                changes.extend(Change::from_event(event?));
+            }
+            // Printing to stdout panics on failure, so do the same here.
+            self.update(ctx, changes, json, &mut io::stdout()).unwrap();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use notify::event::{CreateKind, DataChange, RemoveKind};
+
+    use super::*;
+
+    struct Fixture {
+        dir: PathBuf,
+        ctx: Context,
+        watched: Watched,
+    }
+
+    impl Fixture {
+        fn new(name: &str) -> Self {
+            let dir =
+                env::temp_dir().join(format!("starlark_watch_{}_{}", name, std::process::id()));
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join("good.bzl"), "x = 1\n").unwrap();
+            fs::write(dir.join("bad.bzl"), "x = (\n").unwrap();
+            let ctx = Context::new(true, false, false, true, false, false, &[], false).unwrap();
+            let mut watched = Watched::new("bzl", &[dir.clone()]).unwrap();
+            for file in expand_dirs("bzl", vec![dir.clone()]) {
+                watched.record(&file, &ctx.file(&file).collect::<Vec<_>>());
+            }
+            Self { dir, ctx, watched }
+        }
+
+        /// Apply the changes, returning the file names of the reported messages,
+        /// followed by " cleared" for files which no longer have any.
+        fn update(&mut self, changes: Vec<Change>) -> Vec<String> {
+            let mut out = Vec::new();
+            self.watched
+                .update(&self.ctx, changes, true, &mut out)
+                .unwrap();
+            String::from_utf8(out)
+                .unwrap()
+                .lines()
+                .map(|x| {
+                    let x = serde_json::from_str::<serde_json::Value>(x).unwrap();
+                    let path = PathBuf::from(x["path"].as_str().unwrap());
+                    let name = path.file_name().unwrap().to_string_lossy().into_owned();
+                    if x["cleared"].as_bool() == Some(true) {
+                        format!("{} cleared", name)
+                    } else {
+                        name
+                    }
+                })
+                .collect()
+        }
+
+        fn counts(&self) -> (usize, usize) {
+            let stats = self.watched.stats();
+            (stats.file, stats.error)
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    #[test]
+    fn test_watch_modify() {
+        let mut t = Fixture::new("modify");
+        assert_eq!(t.counts(), (2, 1));
+
+        let good = t.dir.join("good.bzl");
+        fs::write(&good, "x = [\n").unwrap();
+        let event = Event::new(EventKind::Modify(ModifyKind::Data(DataChange::Content)))
+            .add_path(good.clone());
+        // Only the changed file is reported.
+        assert_eq!(t.update(Change::from_event(event)), &["good.bzl"]);
+        assert_eq!(t.counts(), (2, 2));
+
+        let bad = t.dir.join("bad.bzl");
+        fs::write(&bad, "x = 2\n").unwrap();
+        assert_eq!(
+            t.update(vec![Change::Modified(bad.clone()), Change::Modified(bad)]),
+            &["bad.bzl cleared"]
+        );
+        assert_eq!(t.counts(), (2, 1));
+    }
+
+    #[test]
+    fn test_watch_delete() {
+        let mut t = Fixture::new("delete");
+        let bad = t.dir.join("bad.bzl");
+        fs::remove_file(&bad).unwrap();
+        let event = Event::new(EventKind::Remove(RemoveKind::File)).add_path(bad);
+        assert_eq!(t.update(Change::from_event(event)), &["bad.bzl cleared"]);
+        assert_eq!(t.counts(), (1, 0));
+
+        // Renaming a file drops the old name and checks the new one.
+        let good = t.dir.join("good.bzl");
+        let renamed = t.dir.join("renamed.bzl");
+        fs::rename(&good, &renamed).unwrap();
+        let event = Event::new(EventKind::Modify(ModifyKind::Name(RenameMode::Both)))
+            .add_path(good)
+            .add_path(renamed);
+        assert_eq!(
+            t.update(Change::from_event(event)),
+            &["good.bzl cleared", "renamed.bzl cleared"]
+        );
+        assert_eq!(t.counts(), (1, 0));
+        assert!(t.watched.files.keys().all(|x| x.ends_with("renamed.bzl")));
+
+        // Removing the whole directory drops everything in it.
+        let dir = t.dir.clone();
+        assert_eq!(t.update(vec![Change::Removed(dir)]), &["renamed.bzl cleared"]);
+        assert_eq!(t.counts(), (0, 0));
+    }
+
+    #[test]
+    fn test_watch_create() {
+        let mut t = Fixture::new("create");
+        let new = t.dir.join("sub").join("new.bzl");
+        fs::create_dir_all(new.parent().unwrap()).unwrap();
+        fs::write(&new, "y = )\n").unwrap();
+        fs::write(t.dir.join("other.txt"), "y = )\n").unwrap();
+        let event = Event::new(EventKind::Create(CreateKind::File))
+            .add_path(new)
+            .add_path(t.dir.join("other.txt"));
+        // Files without the extension are ignored.
+        assert_eq!(t.update(Change::from_event(event)), &["new.bzl"]);
+        assert_eq!(t.counts(), (3, 2));
+
+        // Nothing outside the watched directories is checked.
+        let mut out = Vec::new();
+        let changed = t
+            .watched
+            .update(
+                &t.ctx,
+                vec![Change::Created(env::temp_dir().join("elsewhere.bzl"))],
+                false,
+                &mut out,
+            )
+            .unwrap();
+        assert!(!changed);
+        assert!(out.is_empty());
+    }
+}
@@ -0,0 +1,368 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Baselines of known lints, so that on code with many existing lints,
+//! only the new ones need to be acted on.
+
+use std::{collections::HashMap, hash::Hasher};
+
+use fnv::FnvHasher;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    analysis::{Lint, LintSeverity},
+    syntax::AstModule,
+};
+
+// Bump whenever the format or the hash changes, so old baselines are rejected rather than misread.
+const VERSION: u32 = 1;
+
+#[derive(Debug, Error)]
+enum LintBaselineError {
+    #[error("Lint baseline has version {0}, but expected version {1}")]
+    Version(u32, u32),
+}
+
+/// A lint recorded in a [`LintBaseline`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[derive(Serialize, Deserialize)]
+pub struct LintBaselineEntry {
+    /// The file the lint was in.
+    pub file: String,
+    /// The [`short_name`](Lint::short_name) of the lint.
+    pub name: String,
+    /// A hash of the lint name and the trimmed source line the lint starts on,
+    /// so the entry still matches after lines are added or removed above it.
+    pub hash: String,
+}
+
+impl LintBaselineEntry {
+    /// The entry recording `lint`.
+    pub fn new(lint: &Lint) -> Self {
+        let file = lint.location.file();
+        let line = file.source_line(lint.location.resolve_span().begin_line);
+        // A hash that is stable between runs and versions, since baselines are checked in.
+        let mut hasher = FnvHasher::default();
+        hasher.write(lint.short_name.as_bytes());
+        hasher.write_u8(0);
+        hasher.write(line.trim().as_bytes());
+        Self {
+            file: file.filename().to_owned(),
+            name: lint.short_name.clone(),
+            hash: format!("{:016x}", hasher.finish()),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct SerializedLintBaseline {
+    version: u32,
+    lints: Vec<LintBaselineEntry>,
+}
+
+/// A set of lints which are already known about, e.g. when adopting the linter on
+/// existing code. Lints in the baseline can be reported as [`LintSeverity::Disabled`],
+/// so only new lints keep their severity.
+///
+/// Build a baseline by [`add`](LintBaseline::add)ing the current lints, or
+/// [`record`](LintBaseline::record)ing those of each file, and save it
+/// with [`to_json`](LintBaseline::to_json). On later runs, load it with
+/// [`from_json`](LintBaseline::from_json) and [`apply`](LintBaseline::apply) it to the
+/// lints of each file, after which [`stale`](LintBaseline::stale) gives the lints
+/// which have since been fixed.
+///
+/// Each lint in the baseline matches only one lint, so if a line has the same lint twice
+/// but the baseline only has it once, one of them is still reported. Checking a file
+/// again, e.g. when watching it, only counts the matches of the latest check.
+#[derive(Debug, Clone, Default)]
+pub struct LintBaseline {
+    /// The number of times each lint occurs.
+    entries: HashMap<LintBaselineEntry, usize>,
+    /// For each file the baseline has been applied to, the number of times each
+    /// lint was matched when it was last applied.
+    matched: HashMap<String, HashMap<LintBaselineEntry, usize>>,
+}
+
+impl LintBaseline {
+    /// Record a lint in the baseline. Lints which are [`LintSeverity::Disabled`]
+    /// are ignored, as they don't need acting on anyway.
+    pub fn add(&mut self, lint: &Lint) {
+        if lint.severity == LintSeverity::Disabled {
+            return;
+        }
+        *self
+            .entries
+            .entry(LintBaselineEntry::new(lint))
+            .or_default() += 1;
+    }
+
+    /// Record the lints of `module`, replacing any recorded for it before, so checking
+    /// the same file again doesn't record its lints twice. Lints which are
+    /// [`LintSeverity::Disabled`] are ignored, as for [`add`](LintBaseline::add).
+    pub fn record<'a>(&mut self, module: &AstModule, lints: impl IntoIterator<Item = &'a Lint>) {
+        let file = module.codemap.filename();
+        self.entries.retain(|x, _| x.file != file);
+        for lint in lints {
+            self.add(lint);
+        }
+    }
+
+    /// Add all the lints of another baseline, e.g. one for a different set of files.
+    pub fn merge(&mut self, other: LintBaseline) {
+        for (entry, count) in other.entries {
+            *self.entries.entry(entry).or_default() += count;
+        }
+        for (file, matched) in other.matched {
+            let res = self.matched.entry(file).or_default();
+            for (entry, count) in matched {
+                *res.entry(entry).or_default() += count;
+            }
+        }
+    }
+
+    /// Each lint in the baseline, with the number of its occurrences which have not been matched.
+    fn remaining(&self) -> impl Iterator<Item = (&LintBaselineEntry, usize)> {
+        self.entries.iter().map(move |(entry, count)| {
+            let matched = self
+                .matched
+                .get(&entry.file)
+                .and_then(|x| x.get(entry))
+                .copied()
+                .unwrap_or_default();
+            (entry, count.saturating_sub(matched))
+        })
+    }
+
+    /// The number of lints in the baseline which have not been matched.
+    pub fn len(&self) -> usize {
+        self.remaining().map(|(_, count)| count).sum()
+    }
+
+    /// Are there no lints in the baseline which have not been matched.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The lints which have not been matched, sorted, with one entry per occurrence.
+    fn unmatched(&self) -> Vec<LintBaselineEntry> {
+        let mut res = Vec::with_capacity(self.len());
+        for (entry, count) in self.remaining() {
+            res.extend(std::iter::repeat(entry).take(count).cloned());
+        }
+        res.sort();
+        res
+    }
+
+    /// Serialize the lints which have not been matched, in a stable order so the
+    /// result can be checked in and diffed.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(&SerializedLintBaseline {
+            version: VERSION,
+            lints: self.unmatched(),
+        })
+        .unwrap()
+    }
+
+    /// Load a baseline produced by [`to_json`](LintBaseline::to_json).
+    pub fn from_json(json: &str) -> anyhow::Result<Self> {
+        let baseline: SerializedLintBaseline = serde_json::from_str(json)?;
+        if baseline.version != VERSION {
+            return Err(LintBaselineError::Version(baseline.version, VERSION).into());
+        }
+        let mut res = Self::default();
+        for entry in baseline.lints {
+            *res.entries.entry(entry).or_default() += 1;
+        }
+        Ok(res)
+    }
+
+    /// Mark the `lints` of `module` which are in the baseline as [`LintSeverity::Disabled`],
+    /// so they are still reported but not acted on. Lints which are already disabled are
+    /// left alone, and don't use up the matching entry. Applying the baseline to a file
+    /// again starts afresh, forgetting the lints matched for it before.
+    pub fn apply(&mut self, module: &AstModule, lints: &mut [Lint]) {
+        let matched = self
+            .matched
+            .entry(module.codemap.filename().to_owned())
+            .or_default();
+        matched.clear();
+        for lint in lints {
+            if lint.severity == LintSeverity::Disabled {
+                continue;
+            }
+            let entry = LintBaselineEntry::new(lint);
+            let count = self.entries.get(&entry).copied().unwrap_or_default();
+            let used = matched.entry(entry).or_default();
+            if *used < count {
+                *used += 1;
+                lint.severity = LintSeverity::Disabled;
+            }
+        }
+    }
+
+    /// The lints in the baseline for the files it has been [`apply`](LintBaseline::apply)ed
+    /// to which were not matched, so no longer occur. Lints for other files aren't stale,
+    /// as they weren't checked.
+    pub fn stale(&self) -> Vec<LintBaselineEntry> {
+        self.unmatched()
+            .into_iter()
+            .filter(|x| self.matched.contains_key(&x.file))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use gazebo::prelude::*;
+
+    use super::*;
+    use crate::{analysis::LintConfig, syntax::Dialect};
+
+    fn lint(code: &str) -> (AstModule, Vec<Lint>) {
+        let module = AstModule::parse("legacy.bzl", code.to_owned(), &Dialect::Extended).unwrap();
+        let mut config = LintConfig::default();
+        config
+            .set_severity("unused-argument", LintSeverity::Warning)
+            .unwrap();
+        let lints = module.lint_with_config(None, &config);
+        (module, lints)
+    }
+
+    fn baseline(code: &str) -> LintBaseline {
+        let mut res = LintBaseline::default();
+        for lint in lint(code).1 {
+            res.add(&lint);
+        }
+        res
+    }
+
+    /// The lints of `code` which are not disabled once the baseline is applied.
+    fn apply(baseline: &mut LintBaseline, code: &str) -> Vec<(String, usize)> {
+        let (module, mut lints) = lint(code);
+        baseline.apply(&module, &mut lints);
+        lints
+            .into_iter()
+            .filter(|x| x.severity != LintSeverity::Disabled)
+            .map(|x| (x.short_name, x.location.resolve_span().begin_line))
+            .collect()
+    }
+
+    const CODE: &str = r#"
+def f():
+    return 1
+    print(1)
+def g(x):
+    if x:
+        return 1
+"#;
+
+    #[test]
+    fn test_baseline_round_trip() {
+        let original = baseline(CODE);
+        assert_eq!(original.len(), 2);
+        let json = original.to_json();
+        let mut loaded = LintBaseline::from_json(&json).unwrap();
+        assert_eq!(loaded.to_json(), json);
+        assert_eq!(
+            loaded.unmatched().map(|x| x.name.as_str()),
+            &["missing-return", "unreachable"]
+        );
+
+        assert_eq!(apply(&mut loaded, CODE), Vec::new());
+        assert!(loaded.is_empty());
+        assert_eq!(loaded.stale(), Vec::new());
+
+        let err =
+            LintBaseline::from_json(&json.replace("\"version\": 1", "\"version\": 0")).unwrap_err();
+        assert!(err.to_string().contains("has version 0"), "{}", err);
+    }
+
+    #[test]
+    fn test_baseline_shifted_line() {
+        let mut baseline = baseline(CODE);
+        // Moved down, and with trailing whitespace, the lines still match.
+        let shifted = format!(
+            "x = 1\n\n# A comment\n{}",
+            CODE.replace("print(1)\n", "print(1)   \n")
+        );
+        assert_eq!(apply(&mut baseline, &shifted), Vec::new());
+        assert_eq!(baseline.stale(), Vec::new());
+    }
+
+    #[test]
+    fn test_baseline_new_lint_on_same_line() {
+        // A different lint on the same line.
+        let mut baseline = baseline(CODE);
+        let code = CODE.replace("if x:", "if y:");
+        assert_eq!(
+            apply(&mut baseline, &code),
+            &[("unused-argument".to_owned(), 4)]
+        );
+        assert_eq!(baseline.stale(), Vec::new());
+
+        // The same lint on another copy of the line.
+        let mut baseline = self::baseline(CODE);
+        let code = format!("{}def h():\n    return 1\n    print(1)\n", CODE);
+        assert_eq!(
+            apply(&mut baseline, &code),
+            &[("unreachable".to_owned(), 9)]
+        );
+    }
+
+    #[test]
+    fn test_baseline_apply_again() {
+        // Checking the same file repeatedly, e.g. with `--watch`, matches the baseline each time.
+        let mut baseline = baseline(CODE);
+        for _ in 0..3 {
+            assert_eq!(apply(&mut baseline, CODE), Vec::new());
+            assert!(baseline.is_empty());
+            assert_eq!(baseline.stale(), Vec::new());
+        }
+
+        // Once fixed the lint is stale, and once back it matches again.
+        let fixed = CODE.replace("    print(1)\n", "");
+        assert_eq!(apply(&mut baseline, &fixed), Vec::new());
+        assert_eq!(baseline.stale().len(), 1);
+        assert_eq!(apply(&mut baseline, CODE), Vec::new());
+        assert_eq!(baseline.stale(), Vec::new());
+
+        // Recording the same file again replaces its lints.
+        let (module, lints) = lint(CODE);
+        let mut written = LintBaseline::default();
+        written.record(&module, &lints);
+        written.record(&module, &lints);
+        assert_eq!(written.to_json(), self::baseline(CODE).to_json());
+    }
+
+    #[test]
+    fn test_baseline_stale() {
+        let mut baseline = baseline(CODE);
+        let fixed = CODE.replace("    print(1)\n", "");
+        assert_eq!(apply(&mut baseline, &fixed), Vec::new());
+        assert_eq!(
+            baseline.stale().map(|x| (x.file.as_str(), x.name.as_str())),
+            &[("legacy.bzl", "unreachable")]
+        );
+
+        // Files which were never checked don't have stale lints.
+        let mut other = LintBaseline::default();
+        other.merge(self::baseline(CODE));
+        assert_eq!(other.len(), 2);
+        assert_eq!(other.stale(), Vec::new());
+    }
+}
@@ -0,0 +1,403 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Checks a set of files which load each other, only re-checking what an edit could affect.
+
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap, HashSet},
+    fmt::Display,
+    hash::{Hash, Hasher},
+};
+
+use gazebo::variants::VariantName;
+use thiserror::Error;
+
+use crate::{
+    analysis::{
+        types::{LintT, LintWarning},
+        Definition, Lint,
+    },
+    errors::Diagnostic,
+    syntax::{AstModule, Dialect},
+};
+
+#[derive(Error, Debug, VariantName)]
+pub(crate) enum Checker {
+    #[error("`{0}` is not exported by `{1}`")]
+    MissingSymbol(String, String),
+}
+
+impl LintWarning for Checker {
+    fn is_serious(&self) -> bool {
+        true
+    }
+}
+
+/// How a [`CheckerCache`] finds, reads and checks files.
+pub trait CheckerContext {
+    /// Identifies a file, e.g. by its path or URL. Displayed as the filename of its code.
+    type File: Clone + Eq + Hash + Ord + Display;
+    /// What is reported about a file.
+    type Message: Clone;
+
+    /// The file loaded by `load(path, ...)` in `file`, whether or not it exists, so that
+    /// creating it later re-checks `file`. `None` if `path` can't refer to a file.
+    fn resolve(&self, path: &str, file: &Self::File) -> Option<Self::File>;
+
+    /// The contents of a file which hasn't been given to
+    /// [`file_changed`](CheckerCache::file_changed), or `None` if it can't be read.
+    fn contents(&self, file: &Self::File) -> Option<String>;
+
+    /// Report a syntax error in a file.
+    fn syntax_error(&self, file: &Self::File, error: Diagnostic) -> Self::Message;
+
+    /// Check a file, which parsed as `ast` apart from the statements with syntax errors.
+    /// `missing_symbols` are lints for the symbols it loads which the loaded files don't export.
+    fn check(
+        &self,
+        file: &Self::File,
+        ast: &AstModule,
+        content: &str,
+        missing_symbols: Vec<Lint>,
+    ) -> Vec<Self::Message>;
+}
+
+struct File<C: CheckerContext> {
+    // The hash of the contents, so saving identical contents does nothing.
+    hash: u64,
+    content: String,
+    // Without the statements which have syntax errors.
+    ast: AstModule,
+    syntax_errors: Vec<C::Message>,
+    // The files this one loads, with the path each `load` was given.
+    loads: Vec<(String, C::File)>,
+    // `None` if the file needs checking again.
+    diagnostics: Option<Vec<C::Message>>,
+}
+
+/// The parsed files and their diagnostics, keyed by content hash, along with which files
+/// load which. Changing a file only re-checks it and the files which load it, transitively.
+/// Files which are loaded but never given to [`file_changed`](CheckerCache::file_changed)
+/// are read with [`CheckerContext::contents`].
+pub struct CheckerCache<C: CheckerContext> {
+    context: C,
+    dialect: Dialect,
+    files: HashMap<C::File, File<C>>,
+    // The files which directly load each file, including files which don't exist yet.
+    loaded_by: HashMap<C::File, HashSet<C::File>>,
+    // How many times a file has been parsed.
+    parses: usize,
+}
+
+fn hash(x: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    x.hash(&mut hasher);
+    hasher.finish()
+}
+
+impl<C: CheckerContext> CheckerCache<C> {
+    /// An empty cache, which parses files with `dialect`.
+    pub fn new(context: C, dialect: Dialect) -> Self {
+        Self {
+            context,
+            dialect,
+            files: HashMap::new(),
+            loaded_by: HashMap::new(),
+            parses: 0,
+        }
+    }
+
+    /// The context given to [`new`](CheckerCache::new).
+    pub fn context(&self) -> &C {
+        &self.context
+    }
+
+    /// How many times a file has been parsed, which only happens when its contents change.
+    pub fn parses(&self) -> usize {
+        self.parses
+    }
+
+    /// Record the new contents of a file. Returns the other files whose diagnostics
+    /// may have changed as a result, i.e. those which load it, directly or not.
+    /// If the contents are the same as before, nothing is re-checked.
+    pub fn file_changed(&mut self, file: &C::File, content: String) -> Vec<C::File> {
+        let hash = hash(&content);
+        if self.files.get(file).map(|x| x.hash) == Some(hash) {
+            return Vec::new();
+        }
+        self.parse(file, content, hash);
+        let dependents = self.dependents(file);
+        for x in &dependents {
+            self.files.get_mut(x).unwrap().diagnostics = None;
+        }
+        dependents
+    }
+
+    /// The diagnostics for a file, which are only computed if the file, or a file
+    /// it loads, has changed since they were last asked for.
+    pub fn diagnostics(&mut self, file: &C::File) -> Vec<C::Message> {
+        self.load(file);
+        match self.files.get(file) {
+            None => Vec::new(),
+            Some(File {
+                diagnostics: Some(res),
+                ..
+            }) => res.clone(),
+            Some(_) => {
+                let res = self.check(file);
+                self.files.get_mut(file).unwrap().diagnostics = Some(res.clone());
+                res
+            }
+        }
+    }
+
+    fn parse(&mut self, file: &C::File, content: String, hash: u64) {
+        self.parses += 1;
+        // Recover from syntax errors, so the rest of the file still gets checked while it is edited.
+        let (ast, syntax_errors) =
+            AstModule::parse_with_recovery(&file.to_string(), content.clone(), &self.dialect);
+        let syntax_errors = syntax_errors
+            .into_iter()
+            .map(|e| self.context.syntax_error(file, e))
+            .collect();
+        let loads: Vec<(String, C::File)> = ast
+            .loads()
+            .into_iter()
+            .filter_map(|path| Some((path.to_owned(), self.context.resolve(path, file)?)))
+            .collect();
+        if let Some(old) = self.files.get(file) {
+            for (_, x) in &old.loads {
+                if let Some(dependents) = self.loaded_by.get_mut(x) {
+                    dependents.remove(file);
+                }
+            }
+        }
+        for (_, x) in &loads {
+            self.loaded_by
+                .entry(x.clone())
+                .or_default()
+                .insert(file.clone());
+        }
+        self.files.insert(
+            file.clone(),
+            File {
+                hash,
+                content,
+                ast,
+                syntax_errors,
+                loads,
+                diagnostics: None,
+            },
+        );
+    }
+
+    // Make sure a file is in the cache, reading it if it isn't.
+    fn load(&mut self, file: &C::File) {
+        if !self.files.contains_key(file) {
+            if let Some(content) = self.context.contents(file) {
+                let hash = hash(&content);
+                self.parse(file, content, hash);
+            }
+        }
+    }
+
+    // The files which load `file`, directly or not, in order.
+    fn dependents(&self, file: &C::File) -> Vec<C::File> {
+        let mut seen = HashSet::new();
+        let mut todo = vec![file];
+        while let Some(x) = todo.pop() {
+            for dependent in self.loaded_by.get(x).into_iter().flatten() {
+                if dependent != file && seen.insert(dependent) {
+                    todo.push(dependent);
+                }
+            }
+        }
+        let mut res: Vec<C::File> = seen.into_iter().cloned().collect();
+        res.sort();
+        res
+    }
+
+    fn check(&mut self, file: &C::File) -> Vec<C::Message> {
+        let loads = self.files[file].loads.clone();
+        for (_, x) in &loads {
+            self.load(x);
+        }
+        let File {
+            ast,
+            content,
+            syntax_errors,
+            ..
+        } = &self.files[file];
+        let mut missing_symbols = Vec::new();
+        for load in ast.info().loads {
+            let loaded = loads
+                .iter()
+                .find(|(path, _)| *path == load.module)
+                .and_then(|(_, x)| self.files.get(x))
+                .filter(|x| x.syntax_errors.is_empty())
+                .map(|x| &x.ast);
+            // Files which are missing or don't parse have their own diagnostics.
+            let exports = match loaded {
+                Some(loaded) => loaded.exported_symbols(),
+                None => continue,
+            };
+            for symbol in load.symbols {
+                if exports.iter().any(|(_, name)| *name == symbol.symbol) {
+                    continue;
+                }
+                if let Some(Definition::Load { span, .. }) = ast.top_level_definition(&symbol.local)
+                {
+                    missing_symbols.push(
+                        LintT::new(
+                            &span.file,
+                            span.span,
+                            Checker::MissingSymbol(symbol.symbol, load.module.clone()),
+                        )
+                        .erase(),
+                    );
+                }
+            }
+        }
+        let mut res = syntax_errors.clone();
+        res.extend(self.context.check(file, ast, content, missing_symbols));
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Each `load` path is relative to the directory of the loading file.
+    // Messages are the names of the lints, and `syntax-error`.
+    struct MemoryContext {
+        files: HashMap<String, String>,
+    }
+
+    impl CheckerContext for MemoryContext {
+        type File = String;
+        type Message = String;
+
+        fn resolve(&self, path: &str, file: &String) -> Option<String> {
+            let (dir, _) = file.rsplit_once('/')?;
+            Some(format!("{}/{}", dir, path))
+        }
+
+        fn contents(&self, file: &String) -> Option<String> {
+            self.files.get(file).cloned()
+        }
+
+        fn syntax_error(&self, _file: &String, _error: Diagnostic) -> String {
+            "syntax-error".to_owned()
+        }
+
+        fn check(
+            &self,
+            _file: &String,
+            ast: &AstModule,
+            _content: &str,
+            missing_symbols: Vec<Lint>,
+        ) -> Vec<String> {
+            ast.lint(None)
+                .into_iter()
+                .chain(missing_symbols)
+                .map(|x| x.short_name)
+                .collect()
+        }
+    }
+
+    fn file(path: &str) -> String {
+        format!("/workspace/{}", path)
+    }
+
+    #[test]
+    fn test_checker_cache() {
+        let files = HashMap::from([
+            (file("a.star"), "load('b.star', 'b')\na = b\n".to_owned()),
+            (file("b.star"), "load('c.star', 'c')\nb = c\n".to_owned()),
+            (file("c.star"), "c = 1\n".to_owned()),
+        ]);
+        let mut cache = CheckerCache::new(MemoryContext { files }, Dialect::Extended);
+        let (a, b, c) = (file("a.star"), file("b.star"), file("c.star"));
+
+        // Opening `a` reads the files it loads from the context.
+        assert_eq!(
+            cache.file_changed(&a, "load('b.star', 'b')\na = b\n".to_owned()),
+            Vec::<String>::new()
+        );
+        assert_eq!(cache.diagnostics(&a), Vec::<String>::new());
+        assert_eq!(cache.diagnostics(&b), Vec::<String>::new());
+        assert_eq!(cache.diagnostics(&c), Vec::<String>::new());
+        assert_eq!(cache.parses(), 3);
+
+        // Editing the leaf invalidates exactly the files which load it.
+        assert_eq!(
+            cache.file_changed(&c, "d = 1\n".to_owned()),
+            vec![a.clone(), b.clone()]
+        );
+        assert_eq!(cache.parses(), 4);
+        assert!(cache.files[&a].diagnostics.is_none());
+        assert!(cache.files[&b].diagnostics.is_none());
+        assert_eq!(cache.diagnostics(&b), vec!["missing-symbol"]);
+        assert_eq!(cache.diagnostics(&a), Vec::<String>::new());
+        assert_eq!(cache.diagnostics(&c), Vec::<String>::new());
+
+        // Saving the same contents again doesn't parse or check anything.
+        assert_eq!(
+            cache.file_changed(&c, "d = 1\n".to_owned()),
+            Vec::<String>::new()
+        );
+        assert_eq!(cache.parses(), 4);
+        assert!(cache.files.values().all(|x| x.diagnostics.is_some()));
+
+        // Editing the middle file only invalidates the file loading it.
+        assert_eq!(
+            cache.file_changed(&b, "b = 1\n".to_owned()),
+            vec![a.clone()]
+        );
+        assert_eq!(cache.parses(), 5);
+        assert!(cache.files[&c].diagnostics.is_some());
+    }
+
+    #[test]
+    fn test_checker_cache_missing_file() {
+        let files = HashMap::from([(
+            file("lib/a.star"),
+            "load('b.star', 'b')\na = b\n".to_owned(),
+        )]);
+        let mut cache = CheckerCache::new(MemoryContext { files }, Dialect::Extended);
+        let (a, b) = (file("lib/a.star"), file("lib/b.star"));
+
+        // The load is relative to `a`, and the missing file has its own diagnostics.
+        assert_eq!(cache.diagnostics(&a), Vec::<String>::new());
+        assert!(!cache.files.contains_key(&b));
+
+        // Creating the missing file re-checks the file loading it.
+        assert_eq!(
+            cache.file_changed(&b, "c = 1\n".to_owned()),
+            vec![a.clone()]
+        );
+        assert_eq!(cache.diagnostics(&a), vec!["missing-symbol"]);
+
+        // Once `a` stops loading `b`, editing `b` doesn't affect it.
+        cache.file_changed(&a, "a = 1\n".to_owned());
+        assert_eq!(
+            cache.file_changed(&b, "b = 1\n".to_owned()),
+            Vec::<String>::new()
+        );
+    }
+}
@@ -0,0 +1,187 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::collections::{HashMap, HashSet};
+
+use gazebo::prelude::*;
+use thiserror::Error;
+
+/// The `short_name` of every lint [`AstModule::lint`](crate::syntax::AstModule::lint) can produce.
+/// Must be kept in sync with the variants of the lint enums.
+pub(crate) const LINT_NAMES: &[&str] = &[
+    // flow
+    "missing-return-expression",
+    "missing-return",
+    "unreachable",
+    "redundant-return",
+    "redundant-continue",
+    "misplaced-load",
+    // incompatible
+    "incompatible-type-check",
+    "duplicate-top-level-assign",
+    // dubious
+    "duplicate-key",
+    "shared-mutable-in-loop",
+    // names
+    "unused-load",
+    "unused-assign",
+    "unused-argument",
+    "using-unassigned",
+    "using-undefined",
+    "underscore-function",
+    "using-ignored",
+    // performance
+    "dict-without-star-star",
+    // recursion
+    "recursive-function",
+    // checker
+    "missing-symbol",
+];
+
+#[derive(Error, Debug)]
+enum LintConfigError {
+    #[error("Unknown lint `{0}`, expected one of: {}", LINT_NAMES.join(", "))]
+    UnknownLint(String),
+}
+
+/// How seriously a [`Lint`](crate::errors::Lint) should be treated.
+#[derive(Debug, Clone, Copy, Dupe, PartialEq, Eq)]
+pub enum LintSeverity {
+    /// The code is wrong, and checking should fail.
+    Error,
+    /// The code is highly likely to be wrong.
+    Warning,
+    /// The code could be improved.
+    Advice,
+    /// Reported, but not worth acting on.
+    Disabled,
+}
+
+/// Which lints [`AstModule::lint_with_config`](crate::syntax::AstModule::lint_with_config)
+/// reports, and their severity. Lints are named by their
+/// [`short_name`](crate::errors::Lint::short_name), e.g. `unused-load`.
+///
+/// The default reports every lint, with serious lints as [`LintSeverity::Warning`]
+/// and the rest as [`LintSeverity::Disabled`].
+#[derive(Debug, Clone, Default)]
+pub struct LintConfig {
+    disabled: HashSet<String>,
+    severity: HashMap<String, LintSeverity>,
+}
+
+impl LintConfig {
+    fn check_name(name: &str) -> anyhow::Result<()> {
+        if LINT_NAMES.contains(&name) {
+            Ok(())
+        } else {
+            Err(LintConfigError::UnknownLint(name.to_owned()).into())
+        }
+    }
+
+    /// Stop reporting the lints whose `short_name` is exactly `name`. The lint is still computed,
+    /// but dropped from the results. Fails if there is no such lint.
+    pub fn disable(&mut self, name: &str) -> anyhow::Result<()> {
+        Self::check_name(name)?;
+        self.disabled.insert(name.to_owned());
+        Ok(())
+    }
+
+    /// Report the named lint again, undoing [`disable`](LintConfig::disable).
+    /// Fails if there is no such lint.
+    pub fn enable(&mut self, name: &str) -> anyhow::Result<()> {
+        Self::check_name(name)?;
+        self.disabled.remove(name);
+        Ok(())
+    }
+
+    /// Report the named lint with the given severity. Fails if there is no such lint.
+    pub fn set_severity(&mut self, name: &str, severity: LintSeverity) -> anyhow::Result<()> {
+        Self::check_name(name)?;
+        self.severity.insert(name.to_owned(), severity);
+        Ok(())
+    }
+
+    /// Is the named lint reported.
+    pub fn is_enabled(&self, name: &str) -> bool {
+        !self.disabled.contains(name)
+    }
+
+    /// The severity for a lint with a given name and seriousness.
+    pub fn severity(&self, name: &str, serious: bool) -> LintSeverity {
+        match self.severity.get(name) {
+            Some(x) => *x,
+            None if serious => LintSeverity::Warning,
+            None => LintSeverity::Disabled,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syntax::{AstModule, Dialect};
+
+    fn lint(code: &str, config: &LintConfig) -> Vec<(String, LintSeverity)> {
+        let module = AstModule::parse("test.bzl", code.to_owned(), &Dialect::Extended).unwrap();
+        module
+            .lint_with_config(None, config)
+            .into_map(|x| (x.short_name, x.severity))
+    }
+
+    const CODE: &str = r#"
+load("a.bzl", "a")
+def f():
+    return
+    print(1)
+"#;
+
+    #[test]
+    fn test_lint_config_default() {
+        assert_eq!(
+            lint(CODE, &LintConfig::default()),
+            &[
+                ("unreachable".to_owned(), LintSeverity::Warning),
+                ("unused-load".to_owned(), LintSeverity::Disabled),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lint_config() {
+        let mut config = LintConfig::default();
+        config.disable("unused-load").unwrap();
+        config
+            .set_severity("unreachable", LintSeverity::Error)
+            .unwrap();
+        assert_eq!(
+            lint(CODE, &config),
+            &[("unreachable".to_owned(), LintSeverity::Error)]
+        );
+        config.enable("unused-load").unwrap();
+        assert_eq!(lint(CODE, &config).len(), 2);
+    }
+
+    #[test]
+    fn test_lint_config_unknown() {
+        let err = LintConfig::default()
+            .disable("unused-loads")
+            .unwrap_err()
+            .to_string();
+        assert!(err.starts_with("Unknown lint `unused-loads`, expected one of: "));
+        assert!(err.contains("unused-load,"));
+    }
+}
@@ -15,7 +15,7 @@
  * limitations under the License.
  */
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use gazebo::variants::VariantName;
 use thiserror::Error;
@@ -24,7 +24,7 @@ use crate::{
     analysis::types::{LintT, LintWarning},
     codemap::{CodeMap, FileSpan, Span},
     syntax::{
-        ast::{AstExpr, AstLiteral, Expr},
+        ast::{Argument, Assign, AstExpr, AstLiteral, AstStmt, Expr, Stmt},
         AstModule,
     },
     values::num::Num,
@@ -34,6 +34,8 @@ use crate::{
 pub(crate) enum Dubious {
     #[error("Duplicate dictionary key `{0}`, also used at {1}")]
     DuplicateKey(String, FileSpan),
+    #[error("Mutable `{0}` is created before the loop, so every iteration adds the same object")]
+    SharedMutableInLoop(String),
 }
 
 impl LintWarning for Dubious {
@@ -103,9 +105,131 @@ fn duplicate_dictionary_key(module: &AstModule, res: &mut Vec<LintT<Dubious>>) {
         .visit_expr(|x| expr(x, &module.codemap, res))
 }
 
+// A common mistake is to create a dict or list once, before a loop, then add it to
+// a container on every iteration, ending up with many references to a single object.
+fn shared_mutable_in_loop(module: &AstModule, res: &mut Vec<LintT<Dubious>>) {
+    fn is_mutable_literal(x: &AstExpr) -> bool {
+        match &**x {
+            Expr::List(_)
+            | Expr::Dict(_)
+            | Expr::ListComprehension(..)
+            | Expr::DictComprehension(..) => true,
+            Expr::Call(f, _) => {
+                matches!(&***f, Expr::Identifier(f, _) if f.node == "dict" || f.node == "list")
+            }
+            _ => false,
+        }
+    }
+
+    // Names assigned anywhere within a statement, not counting nested functions.
+    fn assigned<'a>(x: &'a AstStmt, res: &mut HashSet<&'a str>) {
+        match &**x {
+            Stmt::Assign(lhs, _) | Stmt::For(lhs, _) => lhs.visit_lvalue(|x| {
+                res.insert(&x.0);
+            }),
+            Stmt::Def(name, ..) => {
+                res.insert(&name.0);
+                return;
+            }
+            _ => {}
+        }
+        x.visit_stmt(|x| assigned(x, res));
+    }
+
+    // Variables added to a container anywhere within a statement, not counting nested functions.
+    // We look for `xs.append(v)`, `xs.insert(i, v)` and `xs[k] = v`.
+    fn pushed<'a>(x: &'a AstStmt, res: &mut Vec<&'a AstExpr>) {
+        match &**x {
+            Stmt::Expression(e) => match &**e {
+                Expr::Call(f, args) => match (&***f, args.as_slice()) {
+                    (Expr::Dot(_, name), [v]) if name.node == "append" => {
+                        if let Argument::Positional(v) = &**v {
+                            res.push(v);
+                        }
+                    }
+                    (Expr::Dot(_, name), [_, v]) if name.node == "insert" => {
+                        if let Argument::Positional(v) = &**v {
+                            res.push(v);
+                        }
+                    }
+                    _ => {}
+                },
+                _ => {}
+            },
+            Stmt::Assign(lhs, rhs) if matches!(&**lhs, Assign::ArrayIndirection(_)) => {
+                res.push(rhs)
+            }
+            Stmt::Def(..) => return,
+            _ => {}
+        }
+        x.visit_stmt(|x| pushed(x, res));
+    }
+
+    // Walk the statements in order, tracking which variables currently hold a mutable literal.
+    fn stmt<'a>(
+        x: &'a AstStmt,
+        mutable: &mut HashSet<&'a str>,
+        codemap: &CodeMap,
+        seen: &mut HashSet<Span>,
+        res: &mut Vec<LintT<Dubious>>,
+    ) {
+        match &**x {
+            Stmt::Assign(lhs, rhs) => match &**lhs {
+                Assign::Identifier(name) if is_mutable_literal(rhs) => {
+                    mutable.insert(&name.0);
+                }
+                _ => lhs.visit_lvalue(|x| {
+                    mutable.remove(x.0.as_str());
+                }),
+            },
+            Stmt::For(lhs, over_body) => {
+                lhs.visit_lvalue(|x| {
+                    mutable.remove(x.0.as_str());
+                });
+                let body = &over_body.1;
+                let mut reassigned = HashSet::new();
+                assigned(body, &mut reassigned);
+                let mut values = Vec::new();
+                pushed(body, &mut values);
+                for v in values {
+                    if let Expr::Identifier(name, _) = &**v {
+                        let name = name.node.as_str();
+                        // Nested loops will see the same value, only report it once.
+                        if mutable.contains(name)
+                            && !reassigned.contains(name)
+                            && seen.insert(v.span)
+                        {
+                            res.push(LintT::new(
+                                codemap,
+                                v.span,
+                                Dubious::SharedMutableInLoop(name.to_owned()),
+                            ));
+                        }
+                    }
+                }
+                stmt(body, mutable, codemap, seen, res);
+            }
+            Stmt::Def(name, _, _, body, _) => {
+                mutable.remove(name.0.as_str());
+                stmt(body, &mut HashSet::new(), codemap, seen, res);
+            }
+            _ => x.visit_stmt(|x| stmt(x, mutable, codemap, seen, res)),
+        }
+    }
+
+    stmt(
+        &module.statement,
+        &mut HashSet::new(),
+        &module.codemap,
+        &mut HashSet::new(),
+        res,
+    )
+}
+
 pub(crate) fn dubious(module: &AstModule) -> Vec<LintT<Dubious>> {
     let mut res = Vec::new();
     duplicate_dictionary_key(module, &mut res);
+    shared_mutable_in_loop(module, &mut res);
     res
 }
 
@@ -124,6 +248,7 @@ mod tests {
         fn about(&self) -> &String {
             match self {
                 Dubious::DuplicateKey(x, _) => x,
+                Dubious::SharedMutableInLoop(x) => x,
             }
         }
     }
@@ -154,4 +279,78 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn test_lint_shared_mutable_in_loop() {
+        let m = module(
+            r#"
+def bad(xs):
+    res = []
+    d = {}
+    for x in xs:
+        d["x"] = x
+        res.append(d)
+    return res
+
+def bad_insert(xs):
+    res = []
+    d = []
+    for x in xs:
+        res.insert(0, d)
+    return res
+
+def bad_index(xs):
+    res = {}
+    d = dict()
+    for x in xs:
+        res[x] = d
+    return res
+
+def bad_nested(xss):
+    res = []
+    row = [1]
+    for xs in xss:
+        item = {}
+        for x in xs:
+            res.append(item)
+            res.append(row)
+    return res
+
+def good(xs):
+    res = []
+    d = {}
+    for x in xs:
+        d = {"x": x}
+        res.append(d)
+    for x in xs:
+        res.append({"x": x})
+        res.append(dict(d))
+    e = {}
+    e = 1
+    for x in xs:
+        res.append(e)
+    for x in xs:
+        res.append(x)
+    return res
+
+def good_nested(xss):
+    res = []
+    for xs in xss:
+        for x in xs:
+            item = {}
+            res.append(item)
+    return res
+"#,
+        );
+        let mut res = Vec::new();
+        shared_mutable_in_loop(&m, &mut res);
+        assert_eq!(
+            res.map(|x| x.problem.about()),
+            &["d", "d", "d", "row", "item"]
+        );
+        assert_eq!(
+            res[0].to_string(),
+            "X:7:20-21: Mutable `d` is created before the loop, so every iteration adds the same object"
+        );
+    }
 }
@@ -0,0 +1,290 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Facts about a module, e.g. for build tooling, derived from the AST alone.
+
+use std::collections::HashSet;
+
+use serde::Serialize;
+
+use crate::{
+    syntax::{
+        ast::{Assign, AstExpr, AstLiteral, AstParameter, Expr, Parameter, Stmt},
+        AstModule,
+    },
+    values::docs::DocString,
+};
+
+/// Facts about a module, as returned by [`AstModule::info`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ModuleInfo {
+    /// The file name of the module.
+    pub path: String,
+    /// The raw module docstring, if the module starts with a string literal.
+    pub docstring: Option<String>,
+    /// The `load` statements, in the order they appear.
+    pub loads: Vec<LoadInfo>,
+    /// The symbols exported by the module, as per [`AstModule::exported_symbols`].
+    pub exports: Vec<ExportInfo>,
+}
+
+/// A `load` statement.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LoadInfo {
+    /// The module being loaded, as written.
+    pub module: String,
+    /// The symbols imported from the module.
+    pub symbols: Vec<LoadedSymbol>,
+}
+
+/// A symbol imported by a `load` statement, e.g. `load("m", local = "symbol")`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LoadedSymbol {
+    /// The name the symbol is bound to in this module.
+    pub local: String,
+    /// The name of the symbol in the loaded module.
+    pub symbol: String,
+}
+
+/// A symbol exported by a module.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ExportInfo {
+    pub name: String,
+    #[serde(flatten)]
+    pub kind: ExportKind,
+}
+
+/// What sort of thing an exported symbol is.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ExportKind {
+    /// A function defined with `def`.
+    Function {
+        /// The named parameters, both positional and keyword-only.
+        params: Vec<ParamInfo>,
+        /// The name of the `*args` parameter, if any.
+        args: Option<String>,
+        /// The name of the `**kwargs` parameter, if any.
+        kwargs: Option<String>,
+    },
+    /// A value bound by an assignment.
+    Constant {
+        /// The type of the value, if evident from the expression assigned, e.g. `int` for `X = 1`.
+        #[serde(rename = "type")]
+        typ: Option<String>,
+    },
+}
+
+/// A named parameter of a function.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ParamInfo {
+    pub name: String,
+    /// The source of the default value, if the parameter has one.
+    pub default: Option<String>,
+    /// Whether the parameter follows `*` or `*args`, so can only be passed by name.
+    pub keyword_only: bool,
+}
+
+fn function(params: &[AstParameter]) -> ExportKind {
+    let mut res = Vec::new();
+    let mut args = None;
+    let mut kwargs = None;
+    let mut keyword_only = false;
+    for p in params {
+        match &p.node {
+            Parameter::Normal(name, _) => res.push(ParamInfo {
+                name: name.0.clone(),
+                default: None,
+                keyword_only,
+            }),
+            Parameter::WithDefaultValue(name, _, default) => res.push(ParamInfo {
+                name: name.0.clone(),
+                default: Some(default.node.to_string()),
+                keyword_only,
+            }),
+            Parameter::NoArgs => keyword_only = true,
+            Parameter::Args(name, _) => {
+                args = Some(name.0.clone());
+                keyword_only = true;
+            }
+            Parameter::KwArgs(name, _) => kwargs = Some(name.0.clone()),
+        }
+    }
+    ExportKind::Function {
+        params: res,
+        args,
+        kwargs,
+    }
+}
+
+// The type of an expression, where that is obvious without evaluating anything.
+fn literal_type(x: &AstExpr) -> Option<&'static str> {
+    match &x.node {
+        Expr::Literal(AstLiteral::Int(_)) => Some("int"),
+        Expr::Literal(AstLiteral::Float(_)) => Some("float"),
+        Expr::Literal(AstLiteral::String(_)) => Some("string"),
+        Expr::Identifier(name, _) => match name.node.as_str() {
+            "True" | "False" => Some("bool"),
+            "None" => Some("NoneType"),
+            _ => None,
+        },
+        Expr::Tuple(_) => Some("tuple"),
+        Expr::List(_) | Expr::ListComprehension(..) => Some("list"),
+        Expr::Dict(_) | Expr::DictComprehension(..) => Some("dict"),
+        Expr::Lambda(..) => Some("function"),
+        _ => None,
+    }
+}
+
+impl AstModule {
+    /// Facts about this module useful to build tooling: its docstring, what it loads
+    /// and what it exports. Computed from the AST alone, without evaluating anything.
+    pub fn info(&self) -> ModuleInfo {
+        let mut loads = Vec::new();
+        // Only the first binding of each name is reported, as in `exported_symbols`.
+        let mut seen = HashSet::new();
+        let mut exports = Vec::new();
+        let mut export = |name: &str, kind: ExportKind| {
+            if !name.starts_with('_') && seen.insert(name.to_owned()) {
+                exports.push(ExportInfo {
+                    name: name.to_owned(),
+                    kind,
+                });
+            }
+        };
+        self.statement.visit_stmt(|x| match &**x {
+            Stmt::Load(load) => loads.push(LoadInfo {
+                module: load.node.module.node.clone(),
+                symbols: load
+                    .node
+                    .args
+                    .iter()
+                    .map(|(local, symbol)| LoadedSymbol {
+                        local: local.0.clone(),
+                        symbol: symbol.node.clone(),
+                    })
+                    .collect(),
+            }),
+            Stmt::Def(name, params, ..) => export(&name.0, function(params)),
+            Stmt::Assign(dest, rhs) => {
+                // Only a plain `x = ...` tells us the type of `x`.
+                let typ = match &dest.node {
+                    Assign::Identifier(_) => literal_type(rhs),
+                    _ => None,
+                };
+                dest.visit_lvalue(|name| {
+                    export(
+                        &name.0,
+                        ExportKind::Constant {
+                            typ: typ.map(str::to_owned),
+                        },
+                    )
+                });
+            }
+            Stmt::AssignModify(dest, _, _) => {
+                dest.visit_lvalue(|name| export(&name.0, ExportKind::Constant { typ: None }))
+            }
+            _ => {}
+        });
+        ModuleInfo {
+            path: self.codemap.filename().to_owned(),
+            docstring: DocString::extract_raw_starlark_docstring(&self.statement),
+            loads,
+            exports,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syntax::Dialect;
+
+    #[test]
+    fn test_module_info() {
+        let module = AstModule::parse(
+            "x.star",
+            r#"
+"""Rules for building things."""
+load("//lib:util.star", "helper", renamed = "original")
+def build(name, srcs = [], *args, visibility = None, **kwargs):
+    """Build it."""
+    pass
+def _private(x): pass
+VERSION = "1.0"
+COUNT = 3
+targets, extra = [], {}
+TABLE = {k: 1 for k in "ab".elems()}
+computed = helper(1)
+computed += 1
+"#
+            .to_owned(),
+            &Dialect::Extended,
+        )
+        .unwrap();
+        let expected = serde_json::json!({
+            "path": "x.star",
+            "docstring": "Rules for building things.",
+            "loads": [
+                {
+                    "module": "//lib:util.star",
+                    "symbols": [
+                        {"local": "helper", "symbol": "helper"},
+                        {"local": "renamed", "symbol": "original"},
+                    ],
+                },
+            ],
+            "exports": [
+                {
+                    "name": "build",
+                    "kind": "function",
+                    "params": [
+                        {"name": "name", "default": null, "keyword_only": false},
+                        {"name": "srcs", "default": "[]", "keyword_only": false},
+                        {"name": "visibility", "default": "None", "keyword_only": true},
+                    ],
+                    "args": "args",
+                    "kwargs": "kwargs",
+                },
+                {"name": "VERSION", "kind": "constant", "type": "string"},
+                {"name": "COUNT", "kind": "constant", "type": "int"},
+                {"name": "targets", "kind": "constant", "type": null},
+                {"name": "extra", "kind": "constant", "type": null},
+                {"name": "TABLE", "kind": "constant", "type": "dict"},
+                {"name": "computed", "kind": "constant", "type": null},
+            ],
+        });
+        assert_eq!(expected, serde_json::to_value(module.info()).unwrap());
+    }
+
+    #[test]
+    fn test_module_info_no_docstring() {
+        let module = AstModule::parse("x.star", "x = 1".to_owned(), &Dialect::Extended).unwrap();
+        let info = module.info();
+        assert_eq!(None, info.docstring);
+        assert!(info.loads.is_empty());
+        assert_eq!(
+            vec![ExportInfo {
+                name: "x".to_owned(),
+                kind: ExportKind::Constant {
+                    typ: Some("int".to_owned())
+                },
+            }],
+            info.exports
+        );
+    }
+}
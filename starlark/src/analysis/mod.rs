@@ -15,17 +15,28 @@
  * limitations under the License.
  */
 
-pub use types::Lint;
+pub use baseline::{LintBaseline, LintBaselineEntry};
+pub use checker::{CheckerCache, CheckerContext};
+pub use config::{LintConfig, LintSeverity};
+pub use info::{ExportInfo, ExportKind, LoadInfo, LoadedSymbol, ModuleInfo, ParamInfo};
+pub use symbols::{Definition, Symbol, SymbolKind};
+pub use types::{Lint, LintFix};
 
 use crate::{analysis::types::LintT, syntax::AstModule};
 
+mod baseline;
 mod bind;
+mod checker;
+mod config;
 mod dubious;
 mod exported;
 mod flow;
+mod info;
 mod incompatible;
 mod names;
 mod performance;
+pub(crate) mod recursion;
+mod symbols;
 mod types;
 
 impl AstModule {
@@ -33,6 +44,12 @@ impl AstModule {
     /// they can be passed as the `globals` argument, resulting in name-resolution lint errors.
     /// The precise checks run by the linter are not considered stable between versions.
     pub fn lint(&self, globals: Option<&[&str]>) -> Vec<Lint> {
+        self.lint_with_config(globals, &LintConfig::default())
+    }
+
+    /// Like [`lint`](AstModule::lint), but only reporting the lints enabled by `config`,
+    /// with the [`severity`](Lint::severity) it specifies.
+    pub fn lint_with_config(&self, globals: Option<&[&str]>, config: &LintConfig) -> Vec<Lint> {
         let mut res = Vec::new();
         res.extend(flow::flow_issues(self).into_iter().map(LintT::erase));
         res.extend(
@@ -47,6 +64,11 @@ impl AstModule {
                 .map(LintT::erase),
         );
         res.extend(performance::performance(self).into_iter().map(LintT::erase));
+        res.extend(recursion::recursion(self).into_iter().map(LintT::erase));
+        res.retain(|x| config.is_enabled(&x.short_name));
+        for x in &mut res {
+            x.severity = config.severity(&x.short_name, x.serious);
+        }
         res
     }
 }
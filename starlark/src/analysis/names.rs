@@ -88,7 +88,47 @@ pub(crate) fn name_warnings(
     }
     inappropriate_underscore(&module.codemap, &module.statement, true, &mut res);
     use_ignored(&module.codemap, &scope, None, &mut res);
-    res
+    res.into_map(|x| {
+        if matches!(x.problem, NameWarning::UnusedLoad(_)) {
+            if let Some(fix) = unused_load_fix(&module.codemap, &module.statement, x.location.span)
+            {
+                return x.with_fix(&module.codemap, fix, String::new());
+            }
+        }
+        x
+    })
+}
+
+/// The span to delete to remove the loaded symbol bound at `span`.
+/// If other symbols remain we remove just this one (and an adjacent comma),
+/// otherwise we remove the whole `load` statement.
+fn unused_load_fix(codemap: &CodeMap, x: &AstStmt, span: Span) -> Option<Span> {
+    match &**x {
+        Stmt::Statements(xs) => xs.iter().find_map(|x| unused_load_fix(codemap, x, span)),
+        Stmt::Load(load) => {
+            let args = &load.node.args;
+            let i = args.iter().position(|x| x.0.span == span)?;
+            // From the start of `name = ` (if present) to the end of the string
+            let item = |i: usize| args[i].0.span.merge(args[i].1.span);
+            Some(if args.len() == 1 {
+                // If the rest of the line is blank, remove the line too
+                let rest = codemap.source_span(Span::new(x.span.end(), codemap.full_span().end()));
+                match rest.find('\n') {
+                    Some(n) if rest[..n].trim().is_empty() => {
+                        Span::new(x.span.begin(), x.span.end() + (n as u32 + 1))
+                    }
+                    _ => x.span,
+                }
+            } else if i + 1 < args.len() {
+                // Remove up to the start of the next symbol
+                Span::new(item(i).begin(), item(i + 1).begin())
+            } else {
+                // Last symbol, so remove from the end of the previous symbol
+                Span::new(item(i - 1).end(), item(i).end())
+            })
+        }
+        _ => None,
+    }
 }
 
 fn undefined_variable(
@@ -98,7 +138,9 @@ fn undefined_variable(
     res: &mut Vec<LintT<NameWarning>>,
 ) {
     let globals: HashSet<&str> = globals.iter().copied().collect();
-    for (name, span) in &scope.free {
+    let mut free: Vec<_> = scope.free.iter().collect();
+    free.sort_by_key(|(_, span)| span.begin());
+    for (name, span) in free {
         if !globals.contains(name.as_str()) {
             res.push(LintT::new(
                 codemap,
@@ -171,6 +213,9 @@ fn unused_variable(codemap: &CodeMap, scope: &Scope, top: bool, res: &mut Vec<Li
         }
     }
 
+    // Report in the order of the source, rather than the arbitrary order of the map.
+    let mut warnings: Vec<_> = warnings.into_iter().collect();
+    warnings.sort_by_key(|(_, (_, span))| span.begin());
     for (name, (typ, span)) in warnings {
         res.push(NameWarning::unused(typ, codemap, span, name.clone()))
     }
@@ -338,6 +383,75 @@ def foo():
         assert_eq!(res, &["_no2", "_no4", "_no6", "no1", "no3", "no5"]);
     }
 
+    #[test]
+    fn test_lint_order() {
+        // Lints found by looking through hash maps are reported in the order of the source.
+        let m = module(
+            r#"
+def f():
+    a = 1
+    b = 2
+    c = 3
+    d = 4
+    e = 5
+    return x1 + x2 + x3 + x4 + x5
+"#,
+        );
+        let res = name_warnings(&m, Some(&[]));
+        assert_eq!(
+            res.map(|x| x.problem.about().as_str()),
+            &["a", "b", "c", "d", "e", "x1", "x2", "x3", "x4", "x5"]
+        );
+    }
+
+    #[test]
+    fn test_lint_unused_load_fix() {
+        // Apply the fix for the unused load of `no`, returning the new source
+        fn fixed(x: &str) -> String {
+            let m = module(x);
+            let res = name_warnings(&m, None);
+            let fixes = res
+                .iter()
+                .filter(|x| matches!(&x.problem, NameWarning::UnusedLoad(x) if x == "no"))
+                .map(|x| x.fix.as_ref().unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(fixes.len(), 1);
+            let span = fixes[0].location.span;
+            let full = m.codemap.full_span();
+            format!(
+                "{}{}{}",
+                m.codemap.source_span(Span::new(full.begin(), span.begin())),
+                fixes[0].replacement,
+                m.codemap.source_span(Span::new(span.end(), full.end()))
+            )
+        }
+
+        let uses = "\nprint(a, b)\n";
+        let check = |before: &str, after: &str| {
+            assert_eq!(
+                fixed(&format!("{}{}", before, uses)),
+                format!("{}{}", after, uses)
+            )
+        };
+        check(
+            r#"load("m", "no", "a", b = "c")"#,
+            r#"load("m", "a", b = "c")"#,
+        );
+        check(
+            r#"load("m", "a", no = "x", b = "c")"#,
+            r#"load("m", "a", b = "c")"#,
+        );
+        check(
+            r#"load("m", "a", b = "c", "no")"#,
+            r#"load("m", "a", b = "c")"#,
+        );
+        check(
+            r#"load("m", "a", b = "c",  "no" ,)"#,
+            r#"load("m", "a", b = "c" ,)"#,
+        );
+        assert_eq!(fixed("load(\"m\", \"no\")\nx = 1\n"), "x = 1\n");
+    }
+
     #[test]
     fn test_lint_duplicate_assign() {
         let m = module(
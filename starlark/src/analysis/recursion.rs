@@ -0,0 +1,332 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Detect functions in a module which call each other recursively.
+//! The Starlark standard forbids recursion, which is normally caught at runtime,
+//! but we can find most of it statically.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use gazebo::{prelude::*, variants::VariantName};
+use itertools::Itertools;
+use thiserror::Error;
+
+use crate::{
+    analysis::types::{LintT, LintWarning},
+    codemap::Span,
+    syntax::{
+        ast::{AstExpr, AstParameter, AstStmt, Clause, Expr, Stmt},
+        uniplate::Visit,
+        AstModule,
+    },
+};
+
+#[derive(Error, Debug, VariantName)]
+pub(crate) enum Recursion {
+    /// The function, the cycle, and whether the dialect forbids recursion,
+    /// which makes the lint serious.
+    #[error("Function `{0}` is recursive, via `{1}`")]
+    RecursiveFunction(String, String, bool),
+}
+
+impl LintWarning for Recursion {
+    fn is_serious(&self) -> bool {
+        match self {
+            Recursion::RecursiveFunction(_, _, forbidden) => *forbidden,
+        }
+    }
+}
+
+/// A group of functions which call each other.
+pub(crate) struct RecursiveCycle {
+    /// The first function defined which is part of the cycle.
+    pub(crate) name: String,
+    /// The shortest path from `name` back to itself, e.g. `f -> g -> f`.
+    pub(crate) path: String,
+    /// The calls between functions of the cycle, in source order, with the function called.
+    /// Never empty.
+    pub(crate) calls: Vec<(Span, String)>,
+}
+
+type Edges = Vec<Vec<(usize, Span)>>;
+
+// Find all the functions defined at the module level. We only consider names
+// which are bound exactly once, by a `def`, since otherwise we can't be sure
+// which value a call refers to.
+fn module_defs(x: &AstStmt) -> Vec<(&str, &[AstParameter], &AstStmt)> {
+    fn go<'a>(
+        x: &'a AstStmt,
+        counts: &mut HashMap<&'a str, usize>,
+        defs: &mut Vec<(&'a str, &'a [AstParameter], &'a AstStmt)>,
+    ) {
+        match &**x {
+            Stmt::Def(name, params, _, body, _) => {
+                *counts.entry(&name.0).or_insert(0) += 1;
+                defs.push((name.0.as_str(), params.as_slice(), &**body));
+                // Anything bound inside is local to the function
+                return;
+            }
+            Stmt::Assign(lhs, _) | Stmt::AssignModify(lhs, _, _) | Stmt::For(lhs, _) => {
+                lhs.visit_lvalue(|x| *counts.entry(&x.0).or_insert(0) += 1)
+            }
+            Stmt::Load(load) => {
+                for (x, _) in &load.node.args {
+                    *counts.entry(&x.0).or_insert(0) += 1;
+                }
+            }
+            _ => {}
+        }
+        x.visit_stmt(|x| go(x, counts, defs))
+    }
+
+    let mut counts = HashMap::new();
+    let mut defs = Vec::new();
+    go(x, &mut counts, &mut defs);
+    defs.retain(|x| counts[x.0] == 1);
+    defs
+}
+
+// Record all the calls to identifiers that happen while running `x`, along with all the
+// names which might be local. Bodies of nested `def` and `lambda` only run when called,
+// so are skipped.
+fn stmt_calls<'a>(x: &'a AstStmt, calls: &mut Vec<(Span, &'a str)>, locals: &mut HashSet<&'a str>) {
+    match &**x {
+        Stmt::Def(name, ..) => {
+            locals.insert(&name.0);
+            return;
+        }
+        Stmt::Assign(lhs, _) | Stmt::AssignModify(lhs, _, _) | Stmt::For(lhs, _) => lhs
+            .visit_lvalue(|x| {
+                locals.insert(&x.0);
+            }),
+        _ => {}
+    }
+    x.visit_children(|x| match x {
+        Visit::Stmt(x) => stmt_calls(x, calls, locals),
+        Visit::Expr(x) => expr_calls(x, calls, locals),
+    });
+}
+
+fn expr_calls<'a>(x: &'a AstExpr, calls: &mut Vec<(Span, &'a str)>, locals: &mut HashSet<&'a str>) {
+    match &**x {
+        Expr::Lambda(..) => return,
+        Expr::Call(f, _) => {
+            if let Expr::Identifier(name, _) = &***f {
+                calls.push((x.span, name.node.as_str()));
+            }
+        }
+        Expr::ListComprehension(_, for_, clauses) | Expr::DictComprehension(_, for_, clauses) => {
+            // Treating the comprehension variables as locals of the whole function is
+            // conservative, we might miss some recursion, but won't report any that isn't there.
+            let mut bind = |x: &'a str| {
+                locals.insert(x);
+            };
+            for_.var.visit_lvalue(|x| bind(&x.0));
+            for clause in clauses {
+                if let Clause::For(clause) = clause {
+                    clause.var.visit_lvalue(|x| bind(&x.0));
+                }
+            }
+        }
+        _ => {}
+    }
+    x.visit_expr(|x| expr_calls(x, calls, locals));
+}
+
+// Which functions can be reached from `start`, including `start` itself.
+fn reachable(edges: &Edges, start: usize) -> Vec<bool> {
+    let mut res = vec![false; edges.len()];
+    res[start] = true;
+    let mut todo = vec![start];
+    while let Some(x) = todo.pop() {
+        for (y, _) in &edges[x] {
+            if !res[*y] {
+                res[*y] = true;
+                todo.push(*y);
+            }
+        }
+    }
+    res
+}
+
+// The shortest path from `start` back to `start`, using only functions in `members`.
+fn cycle_path(edges: &Edges, members: &[bool], start: usize) -> Vec<usize> {
+    let mut parent = vec![None; edges.len()];
+    let mut todo = VecDeque::new();
+    todo.push_back(start);
+    while let Some(x) = todo.pop_front() {
+        for (y, _) in &edges[x] {
+            if !members[*y] || parent[*y].is_some() {
+                continue;
+            }
+            parent[*y] = Some(x);
+            if *y == start {
+                let mut res = vec![start];
+                let mut x = x;
+                while x != start {
+                    res.push(x);
+                    x = parent[x].unwrap();
+                }
+                res.push(start);
+                res.reverse();
+                return res;
+            }
+            todo.push_back(*y);
+        }
+    }
+    unreachable!("Function must be part of a cycle")
+}
+
+/// Find the groups of functions defined in this module which are recursive,
+/// in the order they are defined. Calls through `load()`ed modules are not considered.
+pub(crate) fn recursive_cycles(x: &AstStmt) -> Vec<RecursiveCycle> {
+    let defs = module_defs(x);
+    let index: HashMap<&str, usize> = defs.iter().enumerate().map(|(i, x)| (x.0, i)).collect();
+    let edges: Edges = defs
+        .iter()
+        .map(|(_, params, body)| {
+            let mut calls = Vec::new();
+            let mut locals: HashSet<&str> = params
+                .iter()
+                .filter_map(|x| x.split().0)
+                .map(|x| x.0.as_str())
+                .collect();
+            stmt_calls(body, &mut calls, &mut locals);
+            calls
+                .into_iter()
+                .filter(|(_, name)| !locals.contains(name))
+                .filter_map(|(span, name)| Some((*index.get(name)?, span)))
+                .collect()
+        })
+        .collect();
+
+    let reach: Vec<Vec<bool>> = (0..defs.len()).map(|i| reachable(&edges, i)).collect();
+    let mut done = vec![false; defs.len()];
+    let mut res = Vec::new();
+    for i in 0..defs.len() {
+        if done[i] || !edges[i].iter().any(|(j, _)| reach[*j][i]) {
+            continue;
+        }
+        let members: Vec<bool> = (0..defs.len())
+            .map(|j| reach[i][j] && reach[j][i])
+            .collect();
+        let mut calls = Vec::new();
+        for (j, edges) in edges.iter().enumerate() {
+            if members[j] {
+                done[j] = true;
+                for (k, span) in edges {
+                    if members[*k] {
+                        calls.push((*span, defs[*k].0.to_owned()));
+                    }
+                }
+            }
+        }
+        calls.sort_by_key(|(span, _)| span.begin());
+        let path = cycle_path(&edges, &members, i)
+            .iter()
+            .map(|x| defs[*x].0)
+            .join(" -> ");
+        res.push(RecursiveCycle {
+            name: defs[i].0.to_owned(),
+            path,
+            calls,
+        });
+    }
+    res
+}
+
+pub(crate) fn recursion(module: &AstModule) -> Vec<LintT<Recursion>> {
+    recursive_cycles(&module.statement).into_map(|x| {
+        LintT::new(
+            &module.codemap,
+            x.calls[0].0,
+            Recursion::RecursiveFunction(x.name, x.path, !module.enable_recursion),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syntax::Dialect;
+
+    fn module(x: &str) -> AstModule {
+        AstModule::parse("X", x.to_owned(), &Dialect::Extended).unwrap()
+    }
+
+    fn cycles(x: &str) -> Vec<String> {
+        recursive_cycles(&module(x).statement).into_map(|x| x.path)
+    }
+
+    #[test]
+    fn test_recursion_direct() {
+        assert_eq!(cycles("def f(x):\n  return f(x - 1)"), &["f -> f"]);
+    }
+
+    #[test]
+    fn test_recursion_mutual() {
+        let m = module(
+            r#"
+def f(): return g()
+def g(): return [h() for _ in []]
+def h():
+    if True:
+        return f()
+def ok(): return f()
+"#,
+        );
+        let res = recursion(&m);
+        assert_eq!(
+            res.map(|x| x.problem.to_string()),
+            &["Function `f` is recursive, via `f -> g -> h -> f`"]
+        );
+        assert_eq!(res[0].original, "g()");
+    }
+
+    #[test]
+    fn test_recursion_serious() {
+        // Only serious if the dialect forbids recursion, which `parse_with_recovery` reports as
+        // an error but still gives a module to lint.
+        let code = "def f(): return f()";
+        let serious = |dialect: &Dialect| {
+            let (m, _) = AstModule::parse_with_recovery("X", code.to_owned(), dialect);
+            recursion(&m).map(|x| x.problem.is_serious())
+        };
+        assert_eq!(serious(&Dialect::Extended), &[false]);
+        let mut dialect = Dialect::Extended;
+        dialect.enable_recursion = false;
+        assert_eq!(serious(&dialect), &[true]);
+    }
+
+    #[test]
+    fn test_recursion_false_positives() {
+        // Rebound names, locals, and uncalled lambdas aren't recursion
+        assert!(cycles(
+            r#"
+def f(): return f()
+f = lambda: 1
+def g(g): return g()
+def h(): return lambda: h()
+def i():
+    i = len
+    return i([])
+def j(): return [j() for j in []]
+"#
+        )
+        .is_empty());
+    }
+}
@@ -0,0 +1,515 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! An outline of the symbols defined by a module, e.g. for an editor.
+
+use std::collections::HashSet;
+
+use gazebo::prelude::*;
+use itertools::Itertools;
+
+use crate::{
+    codemap::{FileSpan, Pos, Span},
+    syntax::{
+        ast::{AstAssignIdent, AstExpr, AstParameter, AstStmt, Expr, Parameter, Stmt},
+        uniplate::Visit,
+        AstModule,
+    },
+    values::docs::DocString,
+};
+
+/// The kind of a [`Symbol`].
+#[derive(Debug, Clone, Copy, Dupe, PartialEq, Eq)]
+pub enum SymbolKind {
+    /// A function defined with `def`.
+    Function,
+    /// A name bound by an assignment.
+    Variable,
+    /// A name bound by an assignment which is all uppercase, e.g. `MAX_SIZE = 10`.
+    Constant,
+    /// A name bound by a `load` statement.
+    Load,
+}
+
+/// A symbol defined by a module, as returned by [`AstModule::symbols`].
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    /// The name of the symbol.
+    pub name: String,
+    /// What sort of thing defines the symbol.
+    pub kind: SymbolKind,
+    /// For a function its parameters, e.g. `(x, y = 1)`,
+    /// for a load the module it comes from.
+    pub detail: Option<String>,
+    /// For a function its raw docstring, if the body starts with a string literal.
+    pub docs: Option<String>,
+    /// The whole statement that defines the symbol, e.g. a `def` including its body.
+    pub span: FileSpan,
+    /// Just the name of the symbol, always contained in `span`.
+    pub name_span: FileSpan,
+    /// Functions defined inside this function.
+    pub children: Vec<Symbol>,
+}
+
+/// Where a name is bound, as returned by [`AstModule::definition_at`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Definition {
+    /// Bound in this module by an assignment, `def`, parameter or `for` loop,
+    /// at the span of the name.
+    Local(FileSpan),
+    /// Bound by a `load` statement, e.g. `load("m", local = "symbol")`.
+    Load {
+        /// The span of the local name in the `load` statement.
+        span: FileSpan,
+        /// The module being loaded, as written.
+        module: String,
+        /// The name of the symbol in the loaded module.
+        symbol: String,
+    },
+}
+
+fn is_constant(name: &str) -> bool {
+    name.chars().any(|c| c.is_ascii_uppercase()) && !name.chars().any(|c| c.is_ascii_lowercase())
+}
+
+fn def_symbol(
+    module: &AstModule,
+    x: &AstStmt,
+    name: &AstAssignIdent,
+    params: &[AstParameter],
+    body: &AstStmt,
+) -> Symbol {
+    let mut children = Vec::new();
+    nested_defs(module, body, &mut children);
+    Symbol {
+        name: name.0.clone(),
+        kind: SymbolKind::Function,
+        detail: Some(format!("({})", params.iter().map(|x| &x.node).join(", "))),
+        docs: DocString::extract_raw_starlark_docstring(body),
+        span: module.file_span(x.span),
+        name_span: module.file_span(name.span),
+        children,
+    }
+}
+
+// Only functions are interesting inside a function, the rest are locals.
+fn nested_defs(module: &AstModule, x: &AstStmt, res: &mut Vec<Symbol>) {
+    match &**x {
+        Stmt::Def(name, params, _, body, _) => res.push(def_symbol(module, x, name, params, body)),
+        _ => x.visit_stmt(|x| nested_defs(module, x, res)),
+    }
+}
+
+// Names bound more than once only report the first binding. Comprehensions and
+// lambdas are expressions, so their variables are never visited.
+fn top_symbols<'a>(
+    module: &AstModule,
+    x: &'a AstStmt,
+    seen: &mut HashSet<&'a str>,
+    res: &mut Vec<Symbol>,
+) {
+    match &**x {
+        Stmt::Def(name, params, _, body, _) => {
+            if seen.insert(&name.0) {
+                res.push(def_symbol(module, x, name, params, body));
+            }
+        }
+        Stmt::Assign(lhs, _) | Stmt::AssignModify(lhs, _, _) => lhs.visit_lvalue(|name| {
+            if seen.insert(&name.0) {
+                res.push(Symbol {
+                    name: name.0.clone(),
+                    kind: if is_constant(&name.0) {
+                        SymbolKind::Constant
+                    } else {
+                        SymbolKind::Variable
+                    },
+                    detail: None,
+                    docs: None,
+                    span: module.file_span(x.span),
+                    name_span: module.file_span(name.span),
+                    children: Vec::new(),
+                });
+            }
+        }),
+        Stmt::Load(load) => {
+            for (name, _) in &load.node.args {
+                if seen.insert(&name.0) {
+                    res.push(Symbol {
+                        name: name.0.clone(),
+                        kind: SymbolKind::Load,
+                        detail: Some(load.node.module.node.clone()),
+                        docs: None,
+                        span: module.file_span(x.span),
+                        name_span: module.file_span(name.span),
+                        children: Vec::new(),
+                    });
+                }
+            }
+        }
+        _ => x.visit_stmt(|x| top_symbols(module, x, seen, res)),
+    }
+}
+
+impl AstModule {
+    /// The symbols defined at the top-level of this module, in the order they are defined,
+    /// with any functions defined inside a function as its children.
+    /// Intended for producing an outline of the module in an editor.
+    pub fn symbols(&self) -> Vec<Symbol> {
+        let mut res = Vec::new();
+        top_symbols(self, &self.statement, &mut HashSet::new(), &mut res);
+        res
+    }
+
+    /// The identifier at a 0-based line and column (in characters) of this module, if any,
+    /// along with its span. Both uses of a name and the places it is bound are considered,
+    /// and a position just after the identifier counts as being on it.
+    pub fn identifier_at(&self, line: usize, column: usize) -> Option<(String, FileSpan)> {
+        if line >= self.codemap.num_lines() {
+            return None;
+        }
+        let line_span = self.codemap.line_span(line);
+        let text = self.codemap.source_span(line_span);
+        let offset = text
+            .char_indices()
+            .nth(column)
+            .map_or(text.len(), |(i, _)| i);
+        let pos = line_span.begin() + offset as u32;
+
+        let mut res = None;
+        identifier_at_stmt(&self.statement, pos, &mut res);
+        res.map(|(name, span)| (name.to_owned(), self.file_span(span)))
+    }
+
+    /// Where the identifier at a 0-based line and column of this module is bound, if it is
+    /// bound in this module at all. Names are looked up in the enclosing functions,
+    /// innermost first, then at the top-level, using the first binding in each.
+    pub fn definition_at(&self, line: usize, column: usize) -> Option<Definition> {
+        let (name, span) = self.identifier_at(line, column)?;
+        let mut defs = Vec::new();
+        enclosing_defs(&self.statement, span.span.begin(), &mut defs);
+        defs.iter()
+            .rev()
+            .find_map(|(params, body)| def_binding(params, body, &name))
+            .map(|span| Definition::Local(self.file_span(span)))
+            .or_else(|| self.top_level_definition(&name))
+    }
+
+    /// Where `name` is bound at the top-level of this module, using the first binding.
+    pub fn top_level_definition(&self, name: &str) -> Option<Definition> {
+        let mut res = None;
+        top_binding(self, &self.statement, name, &mut res);
+        res
+    }
+}
+
+// The functions containing `pos`, outermost first.
+fn enclosing_defs<'a>(x: &'a AstStmt, pos: Pos, res: &mut Vec<(&'a [AstParameter], &'a AstStmt)>) {
+    if !span_contains(x.span, pos) {
+        return;
+    }
+    match &**x {
+        Stmt::Def(_, params, _, body, _) => {
+            res.push((params, body));
+            enclosing_defs(body, pos, res);
+        }
+        _ => x.visit_stmt(|x| enclosing_defs(x, pos, res)),
+    }
+}
+
+// The first binding of `name` local to a function, not looking inside nested functions.
+fn def_binding(params: &[AstParameter], body: &AstStmt, name: &str) -> Option<Span> {
+    fn go(x: &AstStmt, name: &str, res: &mut Option<Span>) {
+        if res.is_some() {
+            return;
+        }
+        match &**x {
+            Stmt::Def(def_name, ..) => {
+                if def_name.0 == name {
+                    *res = Some(def_name.span);
+                }
+            }
+            Stmt::Assign(lhs, _) | Stmt::AssignModify(lhs, _, _) | Stmt::For(lhs, _) => {
+                lhs.visit_lvalue(|x| {
+                    if res.is_none() && x.0 == name {
+                        *res = Some(x.span);
+                    }
+                });
+                x.visit_stmt(|x| go(x, name, res));
+            }
+            _ => x.visit_stmt(|x| go(x, name, res)),
+        }
+    }
+
+    let param = params.iter().find_map(|p| match &p.node {
+        Parameter::Normal(x, _)
+        | Parameter::WithDefaultValue(x, _, _)
+        | Parameter::Args(x, _)
+        | Parameter::KwArgs(x, _)
+            if x.0 == name =>
+        {
+            Some(x.span)
+        }
+        _ => None,
+    });
+    param.or_else(|| {
+        let mut res = None;
+        go(body, name, &mut res);
+        res
+    })
+}
+
+fn top_binding(module: &AstModule, x: &AstStmt, name: &str, res: &mut Option<Definition>) {
+    if res.is_some() {
+        return;
+    }
+    match &**x {
+        Stmt::Def(def_name, ..) => {
+            if def_name.0 == name {
+                *res = Some(Definition::Local(module.file_span(def_name.span)));
+            }
+        }
+        Stmt::Assign(lhs, _) | Stmt::AssignModify(lhs, _, _) => lhs.visit_lvalue(|x| {
+            if res.is_none() && x.0 == name {
+                *res = Some(Definition::Local(module.file_span(x.span)));
+            }
+        }),
+        Stmt::For(lhs, _) => {
+            lhs.visit_lvalue(|x| {
+                if res.is_none() && x.0 == name {
+                    *res = Some(Definition::Local(module.file_span(x.span)));
+                }
+            });
+            x.visit_stmt(|x| top_binding(module, x, name, res));
+        }
+        Stmt::Load(load) => {
+            *res = load
+                .node
+                .args
+                .iter()
+                .find(|(local, _)| local.0 == name)
+                .map(|(local, symbol)| Definition::Load {
+                    span: module.file_span(local.span),
+                    module: load.node.module.node.clone(),
+                    symbol: symbol.node.clone(),
+                });
+        }
+        _ => x.visit_stmt(|x| top_binding(module, x, name, res)),
+    }
+}
+
+fn span_contains(span: Span, pos: Pos) -> bool {
+    span.begin() <= pos && pos <= span.end()
+}
+
+fn identifier_at_expr<'a>(x: &'a AstExpr, pos: Pos, res: &mut Option<(&'a str, Span)>) {
+    if !span_contains(x.span, pos) {
+        return;
+    }
+    match &**x {
+        Expr::Identifier(name, _) => *res = Some((&name.node, name.span)),
+        _ => x.visit_expr(|x| identifier_at_expr(x, pos, res)),
+    }
+}
+
+fn identifier_at_stmt<'a>(x: &'a AstStmt, pos: Pos, res: &mut Option<(&'a str, Span)>) {
+    if !span_contains(x.span, pos) {
+        return;
+    }
+    let mut binding = |name: &'a AstAssignIdent| {
+        if span_contains(name.span, pos) {
+            *res = Some((&name.0, name.span));
+        }
+    };
+    match &**x {
+        Stmt::Def(name, ..) => binding(name),
+        Stmt::Assign(lhs, _) | Stmt::AssignModify(lhs, _, _) | Stmt::For(lhs, _) => {
+            lhs.visit_lvalue(binding)
+        }
+        Stmt::Load(load) => load.node.args.iter().for_each(|(name, _)| binding(name)),
+        _ => {}
+    }
+    if res.is_none() {
+        x.visit_children(|x| match x {
+            Visit::Stmt(x) => identifier_at_stmt(x, pos, res),
+            Visit::Expr(x) => identifier_at_expr(x, pos, res),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syntax::Dialect;
+
+    // One line per symbol, with the name location and the first line of the whole statement.
+    fn outline(x: &str) -> Vec<String> {
+        fn go(indent: usize, xs: &[Symbol], res: &mut Vec<String>) {
+            for x in xs {
+                let mut line = format!("{}{:?} {}", " ".repeat(indent), x.kind, x.name);
+                if let Some(detail) = &x.detail {
+                    line.push_str(&format!(" {}", detail));
+                }
+                let source = x.span.source_span();
+                line.push_str(&format!(
+                    " {} `{}`",
+                    x.name_span.resolve_span(),
+                    source.lines().next().unwrap()
+                ));
+                res.push(line);
+                go(indent + 2, &x.children, res);
+            }
+        }
+
+        let module = AstModule::parse("X", x.to_owned(), &Dialect::Extended).unwrap();
+        let mut res = Vec::new();
+        go(0, &module.symbols(), &mut res);
+        res
+    }
+
+    #[test]
+    fn test_symbols() {
+        let res = outline(
+            r#"
+load("lib.star", "helper", alias = "other")
+MAX_SIZE = 10
+a, b = 1, 2
+def outer(x, y = 1, *args, **kwargs):
+    z = [w for w in []]
+    def inner():
+        def innermost(): pass
+    if x:
+        def branch(): pass
+ys = [q for q in range(3)]
+a += 1
+"#,
+        );
+        assert_eq!(
+            res,
+            &[
+                r#"Load helper lib.star 2:18-26 `load("lib.star", "helper", alias = "other")`"#,
+                r#"Load alias lib.star 2:28-33 `load("lib.star", "helper", alias = "other")`"#,
+                "Constant MAX_SIZE 3:1-9 `MAX_SIZE = 10`",
+                "Variable a 4:1-2 `a, b = 1, 2`",
+                "Variable b 4:4-5 `a, b = 1, 2`",
+                "Function outer (x, y = 1, *args, **kwargs) 5:5-10 \
+                 `def outer(x, y = 1, *args, **kwargs):`",
+                "  Function inner () 7:9-14 `def inner():`",
+                "    Function innermost () 8:13-22 `def innermost(): pass`",
+                "  Function branch () 10:13-19 `def branch(): pass`",
+                "Variable ys 11:1-3 `ys = [q for q in range(3)]`",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_identifier_at() {
+        let module = AstModule::parse(
+            "X",
+            "load('lib.star', 'helper')\ndef foo(x):\n    return len(x) + helper\n".to_owned(),
+            &Dialect::Extended,
+        )
+        .unwrap();
+        let at = |line, column| {
+            module
+                .identifier_at(line, column)
+                .map(|(name, span)| format!("{} {}", name, span.resolve_span()))
+        };
+        assert_eq!(at(0, 18).as_deref(), Some("helper 1:18-26"));
+        assert_eq!(at(1, 5).as_deref(), Some("foo 2:5-8"));
+        assert_eq!(at(1, 7).as_deref(), Some("foo 2:5-8"));
+        assert_eq!(at(2, 12).as_deref(), Some("len 3:12-15"));
+        assert_eq!(at(2, 15).as_deref(), Some("x 3:16-17"));
+        assert_eq!(at(2, 25).as_deref(), Some("helper 3:21-27"));
+        assert_eq!(at(2, 4), None);
+        assert_eq!(at(10, 0), None);
+    }
+
+    #[test]
+    fn test_definition_at() {
+        let module = AstModule::parse(
+            "X",
+            r#"
+load("lib.star", "helper", alias = "original")
+x = 1
+def foo(x, *args):
+    y = x + helper
+    for z in args:
+        y += z
+    def bar():
+        return y + alias + x
+    return bar() + unknown
+x = foo(x)
+"#
+            .to_owned(),
+            &Dialect::Extended,
+        )
+        .unwrap();
+        let at = |line, column| match module.definition_at(line, column)? {
+            Definition::Local(span) => Some(span.resolve_span().to_string()),
+            Definition::Load {
+                span,
+                module,
+                symbol,
+            } => Some(format!("{} {} {}", span.resolve_span(), module, symbol)),
+        };
+        // Parameters and locals.
+        assert_eq!(at(4, 8).as_deref(), Some("4:9-10"));
+        assert_eq!(at(6, 13).as_deref(), Some("6:9-10"));
+        assert_eq!(at(6, 8).as_deref(), Some("5:5-6"));
+        // Captured from the enclosing function.
+        assert_eq!(at(8, 15).as_deref(), Some("5:5-6"));
+        assert_eq!(at(8, 27).as_deref(), Some("4:9-10"));
+        assert_eq!(at(9, 11).as_deref(), Some("8:9-12"));
+        // Top-level, including the first binding of a name bound twice.
+        assert_eq!(at(10, 4).as_deref(), Some("4:5-8"));
+        assert_eq!(at(10, 8).as_deref(), Some("3:1-2"));
+        assert_eq!(at(10, 0).as_deref(), Some("3:1-2"));
+        // Loads, including an alias.
+        assert_eq!(at(4, 13).as_deref(), Some("2:18-26 lib.star helper"));
+        assert_eq!(at(8, 19).as_deref(), Some("2:28-33 lib.star original"));
+        // Not bound in this module.
+        assert_eq!(at(9, 20), None);
+        assert_eq!(
+            module.top_level_definition("foo"),
+            module.definition_at(3, 4)
+        );
+        assert_eq!(module.top_level_definition("y"), None);
+    }
+
+    #[test]
+    fn test_definition_at_for() {
+        let module = AstModule::parse(
+            "X",
+            r#"
+for i, (k, v) in enumerate({}.items()):
+    total = k
+print(i, v, total)
+"#
+            .to_owned(),
+            &Dialect::Extended,
+        )
+        .unwrap();
+        let at = |line, column| match module.definition_at(line, column)? {
+            Definition::Local(span) => Some(span.resolve_span().to_string()),
+            Definition::Load { .. } => None,
+        };
+        // Loop targets, and names bound in the loop body.
+        assert_eq!(at(3, 6).as_deref(), Some("2:5-6"));
+        assert_eq!(at(3, 9).as_deref(), Some("2:12-13"));
+        assert_eq!(at(3, 12).as_deref(), Some("3:5-10"));
+    }
+}
@@ -19,7 +19,10 @@ use std::fmt::{self, Display};
 
 use gazebo::variants::VariantName;
 
-use crate::codemap::{CodeMap, FileSpan, Span};
+use crate::{
+    analysis::config::{LintSeverity, LINT_NAMES},
+    codemap::{CodeMap, FileSpan, Span},
+};
 
 pub(crate) trait LintWarning: Display + VariantName {
     fn is_serious(&self) -> bool;
@@ -31,6 +34,7 @@ pub(crate) struct LintT<T> {
     pub location: FileSpan,
     pub original: String,
     pub problem: T,
+    pub fix: Option<LintFix>,
 }
 
 /// A lint produced by [`AstModule::lint`](crate::syntax::AstModule::lint).
@@ -43,10 +47,25 @@ pub struct Lint {
     /// Is this code highly-likely to be wrong, rather
     /// than merely stylistically non-ideal.
     pub serious: bool,
+    /// How seriously to treat this issue, as chosen by the
+    /// [`LintConfig`](crate::errors::LintConfig) used.
+    pub severity: LintSeverity,
     /// A description of the underlying problem.
     pub problem: String,
     /// The source code at [`location`](Lint::location).
     pub original: String,
+    /// An edit which would resolve this issue, if one is known.
+    pub fix: Option<LintFix>,
+}
+
+/// A machine-applicable fix for a [`Lint`], replacing the source code
+/// at [`location`](LintFix::location) with [`replacement`](LintFix::replacement).
+#[derive(Debug, Clone)]
+pub struct LintFix {
+    /// The code to replace, which may be empty for a pure insertion.
+    pub location: FileSpan,
+    /// The text to put in its place, which may be empty for a deletion.
+    pub replacement: String,
 }
 
 impl Display for Lint {
@@ -68,16 +87,39 @@ impl<T: LintWarning> LintT<T> {
             original: location.file.source_span(span).to_owned(),
             location,
             problem,
+            fix: None,
         }
     }
 
+    /// Attach a fix, replacing `span` with `replacement`.
+    pub(crate) fn with_fix(mut self, codemap: &CodeMap, span: Span, replacement: String) -> Self {
+        self.fix = Some(LintFix {
+            location: codemap.file_span(span),
+            replacement,
+        });
+        self
+    }
+
     pub(crate) fn erase(self) -> Lint {
+        let short_name = kebab(self.problem.variant_name());
+        debug_assert!(
+            LINT_NAMES.contains(&short_name.as_str()),
+            "Lint `{}` missing from LINT_NAMES",
+            short_name
+        );
+        let serious = self.problem.is_serious();
         Lint {
             location: self.location,
-            short_name: kebab(self.problem.variant_name()),
-            serious: self.problem.is_serious(),
+            short_name,
+            serious,
+            severity: if serious {
+                LintSeverity::Warning
+            } else {
+                LintSeverity::Disabled
+            },
             problem: self.problem.to_string(),
             original: self.original,
+            fix: self.fix,
         }
     }
 }
@@ -22,7 +22,7 @@
 // We want to carefully control the panic message.
 #![allow(clippy::if_then_panic)]
 
-use std::collections::HashMap;
+use std::{cell::RefCell, collections::HashMap, fs, path::Path};
 
 use anyhow::anyhow;
 use gazebo::prelude::*;
@@ -34,7 +34,7 @@ use crate::{
     collections::SmallMap,
     environment::{FrozenModule, Globals, GlobalsBuilder, Module},
     errors::Diagnostic,
-    eval::{Evaluator, ReturnFileLoader},
+    eval::{Evaluator, FileLoader},
     stdlib::PrintHandler,
     syntax::{
         lexer::{Lexer, Token},
@@ -197,10 +197,58 @@ fn test_methods(builder: &mut GlobalsBuilder) {
     }
 }
 
+// Loads the modules added by `module`, and evaluates those added by `module_dir`
+// the first time they are loaded.
+struct AssertLoader<'x, 'a> {
+    assert: &'x Assert<'a>,
+    gc: GcStrategy,
+    loaded: RefCell<HashMap<String, FrozenModule>>,
+    // The `module_dir` modules currently being evaluated, to detect cycles.
+    loading: RefCell<Vec<String>>,
+}
+
+impl<'x, 'a> FileLoader for AssertLoader<'x, 'a> {
+    fn load(&self, path: &str) -> anyhow::Result<FrozenModule> {
+        if let Some(module) = self.assert.modules.get(path) {
+            return Ok(module.dupe());
+        }
+        if let Some(module) = self.loaded.borrow().get(path) {
+            return Ok(module.dupe());
+        }
+        let program = self
+            .assert
+            .module_sources
+            .get(path)
+            .ok_or_else(|| anyhow!("Assert does not know the module `{}`", path))?;
+        if self.loading.borrow().iter().any(|x| x == path) {
+            return Err(anyhow!(
+                "Cyclic load of modules: {} -> {}",
+                self.loading.borrow().join(" -> "),
+                path
+            ));
+        }
+
+        self.loading.borrow_mut().push(path.to_owned());
+        let module = Module::new();
+        let res = self
+            .assert
+            .execute_with_loader(path, program, &module, self.gc, self);
+        self.loading.borrow_mut().pop();
+        res?;
+        let module = module.freeze()?;
+        self.loaded
+            .borrow_mut()
+            .insert(path.to_owned(), module.dupe());
+        Ok(module)
+    }
+}
+
 /// Environment in which to run assertion tests.
 pub struct Assert<'a> {
     dialect: Dialect,
     modules: HashMap<String, FrozenModule>,
+    // Modules added by `module_dir`, name to source, which are only evaluated when loaded.
+    module_sources: HashMap<String, String>,
     globals: Globals,
     gc_strategy: Option<GcStrategy>,
     setup_eval: Box<dyn Fn(&mut Evaluator)>,
@@ -220,6 +268,7 @@ impl<'a> Assert<'a> {
         Self {
             dialect: Dialect::Extended,
             modules: hashmap!["assert.star".to_owned() => Lazy::force(&ASSERT_STAR).dupe()],
+            module_sources: HashMap::new(),
             globals: Lazy::force(&GLOBALS).dupe(),
             gc_strategy: None,
             setup_eval: box |_| (),
@@ -262,11 +311,23 @@ impl<'a> Assert<'a> {
         module: &'v Module,
         gc: GcStrategy,
     ) -> anyhow::Result<Value<'v>> {
-        let mut modules = HashMap::with_capacity(self.modules.len());
-        for (k, v) in &self.modules {
-            modules.insert(k.as_str(), v);
-        }
-        let loader = ReturnFileLoader { modules: &modules };
+        let loader = AssertLoader {
+            assert: self,
+            gc,
+            loaded: RefCell::new(HashMap::new()),
+            loading: RefCell::new(Vec::new()),
+        };
+        self.execute_with_loader(path, program, module, gc, &loader)
+    }
+
+    fn execute_with_loader<'v>(
+        &self,
+        path: &str,
+        program: &str,
+        module: &'v Module,
+        gc: GcStrategy,
+        loader: &dyn FileLoader,
+    ) -> anyhow::Result<Value<'v>> {
         let ast = AstModule::parse(path, program.to_owned(), &self.dialect)?;
         let mut eval = Evaluator::new(module);
         (self.setup_eval)(&mut eval);
@@ -283,7 +344,7 @@ impl<'a> Assert<'a> {
             GcStrategy::Auto => {}
             GcStrategy::Always => eval.before_stmt(&gc_always),
         }
-        eval.set_loader(&loader);
+        eval.set_loader(loader);
         eval.eval_module(ast, &self.globals)
     }
 
@@ -357,6 +418,38 @@ impl<'a> Assert<'a> {
         self.modules.insert(name.to_owned(), module);
     }
 
+    /// Make every file in the directory `dir` available to future tests via `load`,
+    /// using the file name as the module name, e.g. `load("lib.star", "x")` for `dir/lib.star`.
+    /// Each module is only evaluated when it is loaded, so the files can load each other
+    /// in any order.
+    ///
+    /// Panics if the directory can't be read.
+    pub fn module_dir(&mut self, dir: impl AsRef<Path>) {
+        let dir = dir.as_ref();
+        let entries = fs::read_dir(dir).unwrap_or_else(|e| {
+            panic!(
+                "starlark::assert::module_dir, reading {}: {}",
+                dir.display(),
+                e
+            )
+        });
+        for entry in entries {
+            let path = entry.unwrap().path();
+            if !path.is_file() {
+                continue;
+            }
+            let name = path.file_name().unwrap().to_string_lossy().into_owned();
+            let program = fs::read_to_string(&path).unwrap_or_else(|e| {
+                panic!(
+                    "starlark::assert::module_dir, reading {}: {}",
+                    path.display(),
+                    e
+                )
+            });
+            self.module_sources.insert(name, program);
+        }
+    }
+
     /// Add a module to the environment that future tests can access.
     ///
     /// ```
@@ -444,6 +537,28 @@ impl<'a> Assert<'a> {
         self.fails_with_name("fails", program, msgs)
     }
 
+    /// A program that must fail with an error message that contains `msg`, and whose
+    /// [primary span](Diagnostic::primary_span) starts on the 1-based `line`.
+    ///
+    /// ```
+    /// # use starlark::assert::Assert;
+    /// Assert::new().fails_with_span("x = 1\nfail('hello')", 2, "ello");
+    /// ```
+    pub fn fails_with_span(&self, program: &str, line: usize, msg: &str) -> anyhow::Error {
+        let err = self.fails_with_name("fails_with_span", program, &[msg]);
+        match Diagnostic::from_error(&err).and_then(|d| d.primary_span()) {
+            Some(span) if span.begin_line == line => {}
+            span => {
+                Diagnostic::eprint(&err);
+                panic!(
+                    "starlark::assert::fails_with_span, failed at the wrong location!\nCode:\n{}\nExpected line:\n{}\nGot span:\n{:?}",
+                    program, line, span
+                )
+            }
+        }
+        err
+    }
+
     /// A program that must execute successfully without an exception. Often uses
     /// assert_eq. Returns the resulting value.
     ///
@@ -707,3 +822,55 @@ pub fn lex(program: &str) -> String {
 pub fn parse_fail(program: &str) -> anyhow::Error {
     Assert::new().parse_fail(program)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixtures() -> Assert<'static> {
+        let mut a = Assert::new();
+        a.module_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/testcases/assert"));
+        a
+    }
+
+    #[test]
+    fn test_module_dir() {
+        let a = fixtures();
+        a.is_true("load('derived.star', 'DERIVED'); DERIVED == 30");
+        a.is_true("load('base.star', 'scale'); scale(3) == 30");
+        a.fail(
+            "load('missing.star', 'x')",
+            "does not know the module `missing.star`",
+        );
+    }
+
+    #[test]
+    fn test_fails_with_span() {
+        let a = fixtures();
+        let err = a.fails_with_span("x = 1\nfail('bad')", 2, "bad");
+        assert_eq!(
+            "assert.bzl",
+            Diagnostic::from_error(&err)
+                .unwrap()
+                .primary_span()
+                .unwrap()
+                .path
+        );
+        // The span of an error inside a loaded module is in that module.
+        let err = a.fails_with_span("load('base.star', 'scale')\nscale(-1)", 7, "negative scale");
+        assert_eq!(
+            "base.star",
+            Diagnostic::from_error(&err)
+                .unwrap()
+                .primary_span()
+                .unwrap()
+                .path
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "failed at the wrong location")]
+    fn test_fails_with_span_wrong_line() {
+        Assert::new().fails_with_span("x = 1\nfail('bad')", 1, "bad");
+    }
+}
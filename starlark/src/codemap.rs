@@ -69,6 +69,11 @@ impl Span {
         Span { begin, end }
     }
 
+    /// The position in the codemap representing the first byte of the span.
+    pub fn begin(self) -> Pos {
+        self.begin
+    }
+
     /// The position after the last byte of the span.
     pub fn end(self) -> Pos {
         self.end
@@ -187,7 +192,7 @@ impl CodeMap {
         &self.0
     }
 
-    fn full_span(&self) -> Span {
+    pub(crate) fn full_span(&self) -> Span {
         Span {
             begin: Pos(0),
             end: Pos(self.0.source.len() as u32),
@@ -24,7 +24,7 @@ use gazebo::{coerce::Coerce, prelude::*};
 use indexmap::Equivalent;
 
 use crate as starlark;
-use crate::collections::{idhasher::mix_u32, StarlarkHasher};
+use crate::collections::{hasher::SEED, idhasher::mix_u32, StarlarkHasher};
 
 /// A hash value.
 ///
@@ -68,6 +68,12 @@ impl StarlarkHashValue {
     ///
     /// Input can also be a non-well swizzled hash to create better hash.
     pub(crate) const fn hash_64(h: u64) -> Self {
+        Self::hash_64_with_seed(h, SEED)
+    }
+
+    /// Like [`hash_64`](StarlarkHashValue::hash_64), with a different seed.
+    pub(crate) const fn hash_64_with_seed(h: u64, seed: u64) -> Self {
+        let h = h ^ seed;
         // `fmix64` function from MurMur3 hash (which is in public domain).
         // https://github.com/aappleby/smhasher/blob/61a0530f28277f2e850bfc39600ce61d02b518de/src/MurmurHash3.cpp#L81
 
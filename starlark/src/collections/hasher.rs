@@ -21,12 +21,31 @@ use fnv::FnvHasher;
 
 use crate::collections::StarlarkHashValue;
 
+/// The initial state of FNV, which the seed is mixed into.
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+
+/// The seed of every hash. Building with the `hash_seed` feature changes it, which changes
+/// the internal order of hash tables, but not the order of dictionaries or other output,
+/// so comparing output with and without the feature checks it doesn't depend on that order.
+#[cfg(not(feature = "hash_seed"))]
+pub(crate) const SEED: u64 = 0;
+#[cfg(feature = "hash_seed")]
+pub(crate) const SEED: u64 = 0x9e3779b97f4a7c15;
+
 /// A hasher used by Starlark implementation.
 ///
 /// Starlark relies on stable hashing, and this is the hasher.
-#[derive(Default)]
+/// It is FNV, by default without a seed, so a value hashes the same in every process,
+/// and the order of the hash tables which use it doesn't change between runs.
 pub struct StarlarkHasher(FnvHasher);
 
+impl Default for StarlarkHasher {
+    #[inline]
+    fn default() -> StarlarkHasher {
+        StarlarkHasher::with_seed(SEED)
+    }
+}
+
 impl StarlarkHasher {
     /// Creates a new hasher.
     #[inline]
@@ -34,6 +53,11 @@ impl StarlarkHasher {
         StarlarkHasher::default()
     }
 
+    #[inline]
+    pub(crate) fn with_seed(seed: u64) -> StarlarkHasher {
+        StarlarkHasher(FnvHasher::with_key(FNV_OFFSET_BASIS ^ seed))
+    }
+
     /// Finish the hash computation and return the result.
     #[inline]
     pub(crate) fn finish_small(self) -> StarlarkHashValue {
@@ -55,3 +79,55 @@ impl Hasher for StarlarkHasher {
         self.0.write(bytes)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::hash::Hash;
+
+    use super::*;
+    use crate::collections::{Hashed, SmallMap};
+
+    fn hash_with_seed<T: Hash + ?Sized>(seed: u64, x: &T) -> StarlarkHashValue {
+        let mut hasher = StarlarkHasher::with_seed(seed);
+        x.hash(&mut hasher);
+        hasher.finish_small()
+    }
+
+    #[test]
+    fn test_seed() {
+        // The default seed is plain FNV.
+        let mut fnv = FnvHasher::default();
+        "x".hash(&mut fnv);
+        assert_eq!(fnv.finish() as u32, hash_with_seed(0, "x").get());
+        assert_ne!(hash_with_seed(0, "x"), hash_with_seed(1, "x"));
+        assert_eq!(hash_with_seed(1, "x"), hash_with_seed(1, "x"));
+        assert_ne!(
+            StarlarkHashValue::hash_64_with_seed(7, 0),
+            StarlarkHashValue::hash_64_with_seed(7, 1)
+        );
+    }
+
+    #[test]
+    fn test_seed_doesnt_change_order() {
+        // Enough keys for the map to be indexed by hash, which must not affect its order.
+        let keys = (0..100)
+            .map(|i| format!("x{}", (i * 37) % 100))
+            .collect::<Vec<_>>();
+        let map = |seed| {
+            let mut res = SmallMap::new();
+            for (i, key) in keys.iter().enumerate() {
+                let hash = hash_with_seed(seed, key.as_str());
+                res.insert_hashed(Hashed::new_unchecked(hash, key.as_str()), i);
+            }
+            // Inserting again keeps the original position.
+            for key in keys.iter().rev() {
+                let hash = hash_with_seed(seed, key.as_str());
+                res.insert_hashed(Hashed::new_unchecked(hash, key.as_str()), 0);
+            }
+            res.keys().copied().collect::<Vec<_>>()
+        };
+        assert_eq!(map(0), keys);
+        assert_eq!(map(0), map(1));
+        assert_eq!(map(1), map(0x9e3779b97f4a7c15));
+    }
+}
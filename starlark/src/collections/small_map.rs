@@ -109,12 +109,6 @@ impl<K, V> SmallMap<K, V> {
         }
     }
 
-    /// Create with largest capacity which is represented by `Vec`.
-    #[inline]
-    pub(crate) fn with_capacity_largest_vec() -> Self {
-        Self::with_capacity(NO_INDEX_THRESHOLD)
-    }
-
     /// Drop the index if the map is too small, and the index is not really needed.
     ///
     /// We don't allocate index prematurely when we add entries the map,
@@ -961,4 +955,99 @@ mod tests {
         }
         assert!(map.is_empty());
     }
+
+    #[test]
+    fn test_index_threshold() {
+        fn hash_of(map: &SmallMap<i32, i32>) -> u64 {
+            let mut s = StarlarkHasher::new();
+            map.hash(&mut s);
+            s.finish()
+        }
+
+        let n = NO_INDEX_THRESHOLD as i32;
+        let mut map = SmallMap::new();
+        for i in 0..n {
+            map.insert(i, i * 10);
+        }
+        assert!(map.index.is_none());
+        let small = map.clone();
+
+        // One more entry creates the index.
+        map.insert(n, n * 10);
+        assert!(map.index.is_some());
+        map.state_check();
+        for i in 0..=n {
+            assert_eq!(Some(&(i * 10)), map.get(&i));
+        }
+        assert_eq!(
+            (0..=n).collect::<Vec<_>>(),
+            map.keys().copied().collect::<Vec<_>>()
+        );
+
+        // Removing it again keeps the index, but the map is the same as one without.
+        assert_eq!(Some(n * 10), map.remove(&n));
+        assert!(map.index.is_some());
+        map.state_check();
+        assert_eq!(small, map);
+        assert_eq!(hash_of(&small), hash_of(&map));
+        assert_eq!(
+            small.iter().collect::<Vec<_>>(),
+            map.iter().collect::<Vec<_>>()
+        );
+        for i in 0..n {
+            assert_eq!(small.get_full(&i), map.get_full(&i));
+        }
+        assert_eq!(None, map.get(&n));
+
+        // And dropping the index leaves the same map again.
+        map.maybe_drop_index();
+        assert!(map.index.is_none());
+        map.state_check();
+        assert_eq!(small, map);
+        assert_eq!(hash_of(&small), hash_of(&map));
+
+        // Removing down to empty works with or without the index.
+        let mut indexed: SmallMap<i32, i32> = (0..=n).map(|i| (i, i)).collect();
+        indexed.remove(&n);
+        let mut plain: SmallMap<i32, i32> = (0..n).map(|i| (i, i)).collect();
+        for i in 0..n {
+            assert_eq!(Some(i), indexed.remove(&i));
+            assert_eq!(Some(i), plain.remove(&i));
+            assert_eq!(plain, indexed);
+            assert_eq!(hash_of(&plain), hash_of(&indexed));
+            indexed.state_check();
+        }
+        assert!(indexed.is_empty());
+    }
+
+    #[test]
+    fn test_remove_reinsert_order() {
+        // Both with and without an index.
+        for n in [5, 50] {
+            let mut map: SmallMap<i32, i32> = (0..n).map(|i| (i, i)).collect();
+            let mid = n / 2;
+            assert_eq!(Some(mid), map.remove(&mid));
+            assert_eq!(Some(1), map.remove(&1));
+            map.insert(mid, -1);
+            map.state_check();
+
+            let mut expected: Vec<i32> = (0..n).filter(|&i| i != mid && i != 1).collect();
+            expected.push(mid);
+            assert_eq!(expected, map.keys().copied().collect::<Vec<_>>());
+            for k in &expected {
+                assert!(map.contains_key(k));
+            }
+
+            // What freezing does, which must keep the order and the lookups.
+            map.maybe_drop_index();
+            let (entries, index) = map.into_raw_parts();
+            let map = unsafe { SmallMap::from_raw_parts(entries, index) };
+            map.state_check();
+            assert_eq!(expected, map.keys().copied().collect::<Vec<_>>());
+            for k in &expected {
+                assert!(map.contains_key(k));
+            }
+            assert_eq!(Some(&-1), map.get(&mid));
+        }
+    }
 }
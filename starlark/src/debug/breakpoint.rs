@@ -18,22 +18,19 @@
 use gazebo::dupe::Dupe;
 
 use crate::{
-    codemap::{CodeMap, FileSpan},
+    codemap::FileSpan,
     syntax::{
         ast::{AstStmt, Stmt},
         AstModule,
     },
 };
 
-fn go(x: &AstStmt, codemap: &CodeMap, res: &mut Vec<FileSpan>) {
+fn go<'a>(x: &'a AstStmt, f: &mut impl FnMut(&'a AstStmt)) {
     match &**x {
         Stmt::Statements(_) => {} // These are not interesting statements that come up
-        _ => res.push(FileSpan {
-            span: x.span,
-            file: codemap.dupe(),
-        }),
+        _ => f(x),
     }
-    x.visit_stmt(|x| go(x, codemap, res))
+    x.visit_stmt(|x| go(x, f))
 }
 
 impl AstModule {
@@ -41,8 +38,18 @@ impl AstModule {
     /// to [`before_stmt`](crate::eval::Evaluator::before_stmt).
     pub fn stmt_locations(&self) -> Vec<FileSpan> {
         let mut res = Vec::new();
-        self.statement
-            .visit_stmt(|x| go(x, &self.codemap, &mut res));
+        self.visit_stmt_locations(|x| {
+            res.push(FileSpan {
+                span: x.span,
+                file: self.codemap.dupe(),
+            })
+        });
         res
     }
+
+    /// Call `f` on the statements whose locations [`stmt_locations`](AstModule::stmt_locations)
+    /// returns, in the same order.
+    pub(crate) fn visit_stmt_locations<'a>(&'a self, mut f: impl FnMut(&'a AstStmt)) {
+        self.statement.visit_stmt(|x| go(x, &mut f));
+    }
 }
@@ -26,6 +26,9 @@ impl<'v, 'a> Evaluator<'v, 'a> {
     /// There are lots of health warnings on this code. Might not work with frozen modules, unassigned variables,
     /// nested definitions etc. It would be a bad idea to rely on the results of continued execution
     /// after evaluating stuff randomly.
+    ///
+    /// For evaluating snippets at the module level, e.g. in a REPL, use
+    /// [`eval_snippet`](Evaluator::eval_snippet) instead.
     pub fn eval_statements(&mut self, statements: AstModule) -> anyhow::Result<Value<'v>> {
         // We are doing a lot of funky stuff here. It's amazing anything works, so let's not push our luck with GC.
         self.disable_gc();
@@ -15,29 +15,41 @@
  * limitations under the License.
  */
 
-use std::{collections::HashMap, mem, sync::Arc};
+use std::{collections::HashMap, sync::Arc};
 
 use derive_more::Display;
 use gazebo::prelude::*;
 use itertools::Itertools;
 use once_cell::sync::{Lazy, OnceCell};
 
-pub use crate::stdlib::LibraryExtension;
+pub use crate::stdlib::{experimental_time_rand, LibraryExtension};
 use crate::{
     collections::{
         symbol_map::{Symbol, SymbolMap},
         SmallMap,
     },
+    environment::EnvironmentError,
     eval::{Arguments, Evaluator},
     stdlib,
     values::{
+        bool::BOOL_TYPE,
+        bytes::{bytes_methods, StarlarkBytes},
+        dict::{dict_methods, Dict},
         docs,
         docs::{DocItem, DocString, DocStringKind},
+        float::StarlarkFloat,
         function::{NativeAttribute, NativeCallableRawDocs},
+        int::INT_TYPE,
         layout::value::ValueLike,
+        list::{list_methods, List},
+        range::Range,
+        set::{set_methods, Set},
+        string::{str_methods, STRING_TYPE},
         structs::FrozenStruct,
+        tuple::{tuple_methods, Tuple},
         types::function::{NativeFunction, NativeMethod},
-        AllocFrozenValue, FrozenHeap, FrozenHeapRef, FrozenStringValue, FrozenValue, Heap, Value,
+        AllocFrozenValue, FrozenHeap, FrozenHeapRef, FrozenStringValue, FrozenValue, Heap,
+        StarlarkValue, Value,
     },
 };
 
@@ -56,6 +68,7 @@ struct GlobalsData {
     heap: FrozenHeapRef,
     variables: SymbolMap<FrozenValue>,
     docstring: Option<String>,
+    extensions: Vec<LibraryExtension>,
 }
 
 #[derive(Debug)]
@@ -67,6 +80,14 @@ struct MethodsData {
     docstring: Option<String>,
 }
 
+/// Methods added by the embedder to built-in types, such as `string` or `list`.
+///
+/// They are found when the type doesn't have a built-in method or attribute of that name,
+/// by an [`Evaluator`] they were passed to with
+/// [`set_extra_methods`](Evaluator::set_extra_methods).
+#[derive(Debug, Default)]
+pub struct ExtraMethods(SmallMap<String, Methods>);
+
 /// Used to build a [`Globals`] value.
 #[derive(Debug)]
 pub struct GlobalsBuilder {
@@ -74,10 +95,12 @@ pub struct GlobalsBuilder {
     heap: FrozenHeap,
     // Normal top-level variables, e.g. True/hash
     variables: SymbolMap<FrozenValue>,
-    // Set to Some when we are in a struct builder, otherwise None
-    struct_fields: Option<SmallMap<FrozenStringValue, FrozenValue>>,
+    // Fields of the namespaces being built, innermost last, empty at the top-level
+    namespace_fields: Vec<SmallMap<FrozenStringValue, FrozenValue>>,
     // The raw docstring for this module
     docstring: Option<String>,
+    // The library extensions which have been added
+    extensions: Vec<LibraryExtension>,
 }
 
 /// Used to build a [`Methods`] value.
@@ -146,6 +169,11 @@ impl Globals {
         &self.0.heap
     }
 
+    /// Whether `x` was added to these globals with [`LibraryExtension::add`].
+    pub(crate) fn has_extension(&self, x: LibraryExtension) -> bool {
+        self.0.extensions.contains(&x)
+    }
+
     /// Print information about the values in this object.
     pub fn describe(&self) -> String {
         self.0
@@ -155,7 +183,9 @@ impl Globals {
             .join("\n")
     }
 
-    /// Get the documentation for both the object itself, and its members. Returned as an `Object`
+    /// Get the documentation for both the object itself, and its members. Returned as an `Object`,
+    /// where namespaces are nested as [`Member::Object`](crate::values::docs::Member::Object).
+    /// Use [`DocItem::render_as_markdown`] to produce human readable API documentation.
     pub fn documentation(&self) -> DocItem {
         common_documentation(&self.0.docstring, &self.0.variables)
     }
@@ -203,14 +233,79 @@ impl Methods {
     }
 }
 
+impl ExtraMethods {
+    /// Create an empty [`ExtraMethods`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The built-in types which methods can be added to, with their built-in methods.
+    fn builtin_types() -> [(&'static str, Option<&'static Methods>); 10] {
+        [
+            (STRING_TYPE, str_methods()),
+            (List::TYPE, list_methods()),
+            (Dict::TYPE, dict_methods()),
+            (Tuple::TYPE, tuple_methods()),
+            (StarlarkBytes::TYPE, bytes_methods()),
+            (Set::TYPE, set_methods()),
+            (StarlarkFloat::TYPE, StarlarkFloat(0.0).get_methods()),
+            (INT_TYPE, None),
+            (BOOL_TYPE, None),
+            (Range::TYPE, None),
+        ]
+    }
+
+    /// Add the methods defined by `f` to the built-in type `typ`, as returned by `type()`,
+    /// e.g. `string`. Fails if the type can't have methods added, or if a method has the same
+    /// name as a built-in method of the type, or one already added.
+    pub fn add(&mut self, typ: &str, f: impl FnOnce(&mut MethodsBuilder)) -> anyhow::Result<()> {
+        let builtin = match Self::builtin_types().iter().find(|x| x.0 == typ) {
+            Some(x) => x.1,
+            None => {
+                return Err(EnvironmentError::ExtraMethodsUnsupportedType(typ.to_owned()).into())
+            }
+        };
+        let mut builder = MethodsBuilder::new();
+        f(&mut builder);
+        let existing = self.0.get(typ);
+        for name in builder.members.keys() {
+            let name = name.as_str();
+            if builtin.and_then(|x| x.get_frozen(name)).is_some()
+                || existing.and_then(|x| x.get_frozen(name)).is_some()
+            {
+                return Err(EnvironmentError::ExtraMethodsDuplicate(
+                    typ.to_owned(),
+                    name.to_owned(),
+                )
+                .into());
+            }
+        }
+        if let Some(existing) = existing {
+            // Keep the methods added before, whose values live on the heap of `existing`.
+            for (name, value) in existing.members() {
+                builder.members.insert(name, value);
+            }
+            builder.heap.add_reference(&existing.0.heap);
+        }
+        self.0.insert(typ.to_owned(), builder.build());
+        Ok(())
+    }
+
+    /// The methods added to the type `typ`.
+    pub(crate) fn get(&self, typ: &str) -> Option<&Methods> {
+        self.0.get(typ)
+    }
+}
+
 impl GlobalsBuilder {
     /// Create an empty [`GlobalsBuilder`], with no functions in scope.
     pub fn new() -> Self {
         Self {
             heap: FrozenHeap::new(),
             variables: SymbolMap::new(),
-            struct_fields: None,
+            namespace_fields: Vec::new(),
             docstring: None,
+            extensions: Vec::new(),
         }
     }
 
@@ -236,20 +331,21 @@ impl GlobalsBuilder {
         res
     }
 
-    /// Add a nested struct to the builder. If `f` adds the definition `foo`,
-    /// it will end up on a struct `name`, accessible as `name.foo`.
-    /// This function cannot be called recursively from inside `f`.
-    pub fn struct_(&mut self, name: &str, f: impl Fn(&mut GlobalsBuilder)) {
-        assert!(
-            self.struct_fields.is_none(),
-            "Can't recursively nest GlobalsBuilder::struct_"
-        );
-        self.struct_fields = Some(SmallMap::new());
+    /// Add a namespace to the builder. If `f` adds the definition `foo`,
+    /// it will end up on a frozen struct `name`, accessible as `name.foo`.
+    /// Namespaces may be nested by calling `namespace` from inside `f`.
+    pub fn namespace(&mut self, name: &str, f: impl FnOnce(&mut GlobalsBuilder)) {
+        self.namespace_fields.push(SmallMap::new());
         f(self);
-        let fields = mem::take(&mut self.struct_fields).unwrap();
+        let fields = self.namespace_fields.pop().unwrap();
         self.set(name, FrozenStruct::new(fields));
     }
 
+    /// Add a nested struct to the builder, the same as [`namespace`](GlobalsBuilder::namespace).
+    pub fn struct_(&mut self, name: &str, f: impl Fn(&mut GlobalsBuilder)) {
+        self.namespace(name, f)
+    }
+
     /// A fluent API for modifying [`GlobalsBuilder`] and returning the result.
     pub fn with(mut self, f: impl FnOnce(&mut Self)) -> Self {
         f(&mut self);
@@ -262,19 +358,33 @@ impl GlobalsBuilder {
         self
     }
 
+    /// A fluent API for modifying [`GlobalsBuilder`] using [`namespace`](GlobalsBuilder::namespace).
+    pub fn with_namespace(mut self, name: &str, f: impl FnOnce(&mut GlobalsBuilder)) -> Self {
+        self.namespace(name, f);
+        self
+    }
+
     /// Called at the end to build a [`Globals`].
     pub fn build(self) -> Globals {
         Globals(Arc::new(GlobalsData {
             heap: self.heap.into_ref(),
             variables: self.variables,
             docstring: self.docstring,
+            extensions: self.extensions,
         }))
     }
 
+    /// Record that the extension `x` has been added.
+    pub(crate) fn add_extension(&mut self, x: LibraryExtension) {
+        if !self.extensions.contains(&x) {
+            self.extensions.push(x);
+        }
+    }
+
     /// Set a value in the [`GlobalsBuilder`].
     pub fn set<'v, V: AllocFrozenValue>(&'v mut self, name: &str, value: V) {
         let value = value.alloc_frozen_value(&self.heap);
-        match &mut self.struct_fields {
+        match self.namespace_fields.last_mut() {
             None => self.variables.insert(name, value),
             Some(fields) => {
                 let name = self.heap.alloc_str(name);
@@ -542,7 +652,8 @@ fn common_documentation(docstring: &Option<String>, members: &SymbolMap<FrozenVa
                     Some(docs::Member::Property(docs::Property { docs: ds, typ }))
                 }
                 None => val.to_value().documentation().and_then(|d| match d {
-                    DocItem::Module(_) | DocItem::Object(_) => None,
+                    DocItem::Module(_) => None,
+                    DocItem::Object(o) => Some(docs::Member::Object(o)),
                     DocItem::Function(f) => Some(docs::Member::Function(f)),
                 }),
             };
@@ -565,7 +676,7 @@ mod tests {
     use super::*;
     use crate::{
         self as starlark,
-        assert::Assert,
+        assert::{self, Assert},
         starlark_type,
         values::{NoSerialize, StarlarkValue},
     };
@@ -577,6 +688,19 @@ mod tests {
     {
     }
 
+    #[test]
+    fn test_documentation_deterministic() {
+        // Globals built separately produce the same documentation and names.
+        let render = || {
+            let globals = Globals::extended();
+            (
+                globals.documentation().render_as_markdown("globals"),
+                globals.names(),
+            )
+        };
+        assert_eq!(render(), render());
+    }
+
     #[test]
     fn test_set_attribute() {
         #[derive(Debug, Display, AnyLifetime, NoSerialize)]
@@ -602,4 +726,256 @@ assert_eq(magic.my_type, "magic")
 assert_eq(magic.my_value, 42)"#,
         );
     }
+
+    #[starlark_module]
+    fn namespace_functions(builder: &mut GlobalsBuilder) {
+        fn double(x: i32) -> anyhow::Result<i32> {
+            Ok(x * 2)
+        }
+
+        fn greet(name: &str) -> anyhow::Result<String> {
+            Ok(format!("Hello {}", name))
+        }
+    }
+
+    fn namespace_globals(builder: &mut GlobalsBuilder) {
+        builder.namespace("ctx", |builder| {
+            namespace_functions(builder);
+            builder.namespace("nested", |builder| builder.set("value", 42));
+        })
+    }
+
+    #[test]
+    fn test_namespace() {
+        let mut a = Assert::new();
+        a.globals_add(namespace_globals);
+        a.all_true(
+            r#"
+ctx.double(21) == 42
+ctx.greet("world") == "Hello world"
+ctx.nested.value == 42
+dir(ctx) == ["double", "greet", "nested", "to_dict", "to_json"]
+"#,
+        );
+        a.fail("ctx.missing()", "has no attribute `missing`");
+    }
+
+    #[starlark_module]
+    fn string_extras(builder: &mut MethodsBuilder) {
+        fn to_label(this: &str) -> anyhow::Result<String> {
+            Ok(format!("//{}", this))
+        }
+    }
+
+    #[starlark_module]
+    fn list_extras(builder: &mut MethodsBuilder) {
+        fn first(this: Value) -> anyhow::Result<Value<'v>> {
+            this.at(Value::new_int(0), heap)
+        }
+    }
+
+    static EXTRA_METHODS: Lazy<ExtraMethods> = Lazy::new(|| {
+        let mut extra = ExtraMethods::new();
+        extra.add("string", string_extras).unwrap();
+        extra.add("list", list_extras).unwrap();
+        extra
+    });
+
+    #[test]
+    fn test_extra_methods() {
+        let mut a = Assert::new();
+        a.setup_eval(|eval| eval.set_extra_methods(&EXTRA_METHODS));
+        a.pass(
+            r#"
+def label(x):
+    return x.to_label()
+assert_eq(label("foo"), "//foo")
+assert_eq("foo".to_label(), "//foo")
+assert_eq(getattr("foo", "to_label")(), "//foo")
+assert_eq(hasattr("foo", "to_label"), True)
+assert_eq("to_label" in dir("foo"), True)
+assert_eq(dir("foo"), sorted(dir("foo")))
+assert_eq([3, 4].first(), 3)
+assert_eq("foo".upper(), "FOO")
+assert_eq(hasattr((1,), "first"), False)
+"#,
+        );
+        a.fail("(1,).first()", "has no attribute `first`");
+        a.fail("getattr(1, 'to_label')", "not supported");
+
+        // Without the extra methods, they aren't found.
+        assert::fail("'foo'.to_label()", "has no attribute `to_label`");
+        assert::is_true("not hasattr('foo', 'to_label')");
+    }
+
+    #[test]
+    fn test_extra_methods_errors() {
+        #[starlark_module]
+        fn upper_extras(builder: &mut MethodsBuilder) {
+            fn upper(this: &str) -> anyhow::Result<String> {
+                Ok(this.to_owned())
+            }
+        }
+
+        #[starlark_module]
+        fn shout_extras(builder: &mut MethodsBuilder) {
+            fn shout(this: &str) -> anyhow::Result<String> {
+                Ok(format!("{}!", this))
+            }
+        }
+
+        let mut extra = ExtraMethods::new();
+        let err = extra.add("string", upper_extras).unwrap_err();
+        assert!(err.to_string().contains("already has a method"), "{}", err);
+        extra.add("string", string_extras).unwrap();
+        let err = extra.add("string", string_extras).unwrap_err();
+        assert!(err.to_string().contains("`to_label`"), "{}", err);
+        let err = extra.add("function", string_extras).unwrap_err();
+        assert!(err.to_string().contains("built-in types"), "{}", err);
+        // Methods added separately are all kept.
+        extra.add("string", shout_extras).unwrap();
+        let methods = extra.get("string").unwrap();
+        assert!(methods.get("to_label").is_some());
+        assert!(methods.get("shout").is_some());
+    }
+
+    #[test]
+    fn test_namespace_documentation() {
+        let globals = GlobalsBuilder::new().with(namespace_globals).build();
+        match globals.get("ctx").unwrap().documentation() {
+            Some(DocItem::Object(obj)) => {
+                let names = obj
+                    .members
+                    .iter()
+                    .map(|(name, _)| name.as_str())
+                    .collect::<Vec<_>>();
+                assert_eq!(names, vec!["double", "greet", "nested"]);
+                assert!(matches!(obj.members[0].1, docs::Member::Function(_)));
+            }
+            x => panic!("Expected an object, got {:?}", x),
+        }
+    }
+
+    /// Tools for working with paths.
+    #[starlark_module]
+    fn documented_globals(builder: &mut GlobalsBuilder) {
+        /// Join two paths together.
+        ///
+        /// # Arguments
+        /// * `a`: The first path.
+        /// * `b`: The second path, if any.
+        ///
+        /// # Returns
+        /// The joined path.
+        fn join(a: &str, b: Option<&str>) -> anyhow::Result<String> {
+            Ok(match b {
+                None => a.to_owned(),
+                Some(b) => format!("{}/{}", a, b),
+            })
+        }
+
+        /// Split a path on its separator.
+        ///
+        /// # Arguments
+        /// * `path`: The path to split.
+        /// * `limit`: The most splits to make.
+        fn split(path: &str, limit @ 3: i32) -> anyhow::Result<Vec<String>> {
+            Ok(path
+                .splitn(limit as usize, '/')
+                .map(|x| x.to_owned())
+                .collect())
+        }
+    }
+
+    #[test]
+    fn test_globals_documentation_tree() {
+        let globals = GlobalsBuilder::new()
+            .with(documented_globals)
+            .with(namespace_globals)
+            .build();
+        let docs = globals.documentation();
+        let obj = match &docs {
+            DocItem::Object(obj) => obj,
+            x => panic!("Expected an object, got {:?}", x),
+        };
+        assert_eq!(
+            obj.docs.as_ref().map(|d| d.summary.as_str()),
+            Some("Tools for working with paths.")
+        );
+        let names = obj
+            .members
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["ctx", "join", "split"]);
+
+        let params = |i: usize| match &obj.members[i].1 {
+            docs::Member::Function(f) => f
+                .params
+                .iter()
+                .map(|p| match p {
+                    docs::Param::Arg {
+                        name,
+                        docs,
+                        default_value,
+                        ..
+                    } => (
+                        name.clone(),
+                        docs.as_ref().unwrap().summary.clone(),
+                        default_value.clone(),
+                    ),
+                    x => panic!("Expected an argument, got {:?}", x),
+                })
+                .collect::<Vec<_>>(),
+            x => panic!("Expected a function, got {:?}", x),
+        };
+        assert_eq!(
+            params(1),
+            vec![
+                ("a".to_owned(), "The first path.".to_owned(), None),
+                (
+                    "b".to_owned(),
+                    "The second path, if any.".to_owned(),
+                    Some("None".to_owned())
+                ),
+            ]
+        );
+        assert_eq!(
+            params(2),
+            vec![
+                ("path".to_owned(), "The path to split.".to_owned(), None),
+                (
+                    "limit".to_owned(),
+                    "The most splits to make.".to_owned(),
+                    Some("3".to_owned())
+                ),
+            ]
+        );
+        match &obj.members[0].1 {
+            docs::Member::Object(ctx) => {
+                assert!(matches!(ctx.members[2], (ref n, docs::Member::Object(_)) if n == "nested"))
+            }
+            x => panic!("Expected an object, got {:?}", x),
+        }
+
+        let markdown = docs.render_as_markdown("paths");
+        for line in [
+            "# `paths`",
+            "Tools for working with paths.",
+            "## `join`",
+            "Join two paths together.",
+            "Split a path on its separator.",
+            "* `limit`: The most splits to make.",
+            "Returns: The joined path.",
+            "### `nested`",
+        ] {
+            assert!(
+                markdown.contains(line),
+                "Missing {:?} in:\n{}",
+                line,
+                markdown
+            );
+        }
+        assert!(markdown.contains("limit: i32 = 3)"), "{}", markdown);
+    }
 }
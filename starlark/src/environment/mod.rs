@@ -26,6 +26,7 @@ mod globals;
 mod module_dump;
 mod modules;
 pub(crate) mod names;
+mod serialize;
 pub(crate) mod slots;
 
 pub use globals::*;
@@ -50,6 +51,12 @@ pub(crate) enum EnvironmentError {
     ModuleHasNoSymbolDidYouMean(String, String),
     #[error("Module symbol `{0}` is not exported")]
     ModuleSymbolIsNotExported(String),
+    #[error("Module symbol `{0}` has type `{2}`, but expected `{1}`")]
+    ModuleSymbolWrongType(String, String, String),
     #[error("No imports are available, you tried `{0}` (no call to `Evaluator.set_loader`)")]
     NoImportsAvailable(String),
+    #[error("Can't add methods to type `{0}`, only to built-in types")]
+    ExtraMethodsUnsupportedType(String),
+    #[error("Can't add method `{1}` to type `{0}`, as it already has a method with that name")]
+    ExtraMethodsDuplicate(String, String),
 }
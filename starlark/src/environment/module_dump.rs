@@ -17,10 +17,12 @@
 
 use std::fmt::Write;
 
+use gazebo::prelude::*;
+
 use crate::{
     environment::FrozenModule,
     eval::FrozenDef,
-    values::{FrozenHeapRef, FrozenValueTyped},
+    values::{FrozenHeapRef, FrozenValueTyped, OwnedFrozenValue},
 };
 
 impl FrozenModule {
@@ -45,6 +47,27 @@ impl FrozenModule {
         }
         w
     }
+
+    /// Print the bytecode the function `name` was compiled to, one instruction per line,
+    /// or `None` if `name` is not a function defined with `def`. Useful to check whether
+    /// an optimization applied. The format is unstable.
+    pub fn dump_bytecode(&self, name: &str) -> Option<String> {
+        let def = self.get(name)?.downcast::<FrozenDef>().ok()?;
+        Some(def.as_ref().dump_bytecode())
+    }
+
+    /// The variables the function `name` captured from enclosing functions or from its module,
+    /// with their frozen values, or `None` if `name` is not a function defined with `def`.
+    /// Useful for tools to warn about functions which would fail trying to mutate them.
+    pub fn captured_variables(&self, name: &str) -> Option<Vec<(String, OwnedFrozenValue)>> {
+        let def = self.get(name)?.downcast::<FrozenDef>().ok()?;
+        Some(def.as_ref().captured().into_map(|(name, value)| {
+            // The values are reachable from the module, so are kept alive by its heap.
+            (name.to_owned(), unsafe {
+                OwnedFrozenValue::new(self.frozen_heap().dupe(), value)
+            })
+        }))
+    }
 }
 
 impl FrozenHeapRef {
@@ -43,7 +43,9 @@ use crate::{
     values::{
         docs,
         docs::{DocItem, DocString, DocStringKind},
-        Freezer, FrozenHeap, FrozenHeapRef, FrozenValue, Heap, OwnedFrozenValue, Value,
+        promise::check_never_set,
+        Freezer, FrozenHeap, FrozenHeapRef, FrozenStringValue, FrozenValue, Heap, HeapPool,
+        OwnedFrozenValue, OwnedFrozenValueTyped, StarlarkValue, Trace, Tracer, Value,
     },
 };
 
@@ -83,6 +85,9 @@ pub(crate) struct FrozenModuleData {
     pub(crate) names: FrozenNames,
     pub(crate) slots: FrozenSlots,
     docstring: Option<String>,
+    extra_value: Option<FrozenValue>,
+    /// Slots bound by `load`, or imported from other modules.
+    loaded: Vec<ModuleSlotId>,
 }
 
 /// Container for the documentation for a module
@@ -111,12 +116,16 @@ pub struct Module {
     // exported.
     slots: MutableSlots<'static>,
     docstring: RefCell<Option<String>>,
+    // Same variance caveats as `slots`.
+    extra_value: Cell<Option<Value<'static>>>,
     /// Module evaluation duration:
     /// * evaluation of the top-level statements
     /// * optimizations during that evaluation
     /// * does not include freezing time
     /// * does not include parsing time
     eval_duration: Cell<Duration>,
+    /// Slots bound by `load`, or imported from other modules.
+    loaded: RefCell<Vec<ModuleSlotId>>,
 }
 
 impl FrozenModule {
@@ -142,6 +151,36 @@ impl FrozenModule {
             })
     }
 
+    /// Get the value of the exported variable `name`, checking it has type `<T>`.
+    /// Unlike [`get`](FrozenModule::get), returns an error saying what went wrong
+    /// if the variable is missing, private or of a different type.
+    pub fn get_typed<T: StarlarkValue<'static>>(
+        &self,
+        name: &str,
+    ) -> anyhow::Result<OwnedFrozenValueTyped<T>> {
+        match self.get_any_visibility(name) {
+            None => Err(match did_you_mean(name, self.names()) {
+                Some(better) => EnvironmentError::ModuleHasNoSymbolDidYouMean(
+                    name.to_owned(),
+                    better.to_owned(),
+                )
+                .into(),
+                None => EnvironmentError::ModuleHasNoSymbol(name.to_owned()).into(),
+            }),
+            Some((_, Visibility::Private)) => {
+                Err(EnvironmentError::ModuleSymbolIsNotExported(name.to_owned()).into())
+            }
+            Some((value, Visibility::Public)) => value.downcast().map_err(|value| {
+                EnvironmentError::ModuleSymbolWrongType(
+                    name.to_owned(),
+                    T::get_type_value_static().as_str().to_owned(),
+                    value.value().get_type().to_owned(),
+                )
+                .into()
+            }),
+        }
+    }
+
     /// Iterate through all the names defined in this module.
     pub fn names(&self) -> impl Iterator<Item = &str> {
         self.module.0.names()
@@ -161,9 +200,32 @@ impl FrozenModule {
         self.module.0.all_items()
     }
 
-    /// Fetch the documentation for the module.
+    /// The documentation for the module as a [`DocItem::Module`], with the documentation of
+    /// each exported symbol as its members, sorted by name. Returns [`None`] if the module
+    /// has neither a docstring nor any exported symbols.
     pub fn documentation(&self) -> Option<DocItem> {
-        self.module.documentation()
+        self.documentation_impl(false)
+    }
+
+    /// Like [`documentation`](FrozenModule::documentation), but also including the private
+    /// symbols defined by the module, i.e. those starting with an underscore.
+    /// Symbols bound by `load` are never included, as they are documented by their own module.
+    pub fn documentation_including_private(&self) -> Option<DocItem> {
+        self.documentation_impl(true)
+    }
+
+    pub(crate) fn docstring(&self) -> Option<&str> {
+        self.module.0.docstring.as_deref()
+    }
+
+    /// Get the frozen value set with [`set_extra_value`](Module::set_extra_value),
+    /// if there was one when the module was frozen.
+    pub fn extra_value(&self) -> Option<OwnedFrozenValue> {
+        // This code is safe because we know the frozen module ref keeps the values alive
+        self.module
+            .0
+            .extra_value
+            .map(|x| unsafe { OwnedFrozenValue::new(self.heap.dupe(), x) })
     }
 
     /// The documentation for the module, and all of its top level values
@@ -180,10 +242,57 @@ impl FrozenModule {
             .collect();
 
         ModuleDocs {
-            module: self.documentation(),
+            module: self.module.documentation(),
             members,
         }
     }
+
+    fn documentation_impl(&self, include_private: bool) -> Option<DocItem> {
+        let data = &self.module.0;
+        let members: Vec<_> = data
+            .names
+            .all_symbols()
+            .filter_map(|(n, slot)| {
+                let (value, vis) = self.get_any_visibility(n)?;
+                // Loaded symbols are private (unless re-exported), but are documented by
+                // the module that defines them.
+                let visible = match vis {
+                    Visibility::Public => true,
+                    Visibility::Private => include_private && !data.loaded.contains(&slot),
+                };
+                if !visible {
+                    return None;
+                }
+                Some((n.to_owned(), Self::member_documentation(value.value())))
+            })
+            .sorted_by(|(l, _), (r, _)| Ord::cmp(l, r))
+            .collect();
+        let docs = self
+            .docstring()
+            .and_then(|d| DocString::from_docstring(DocStringKind::Starlark, d));
+        if docs.is_none() && members.is_empty() {
+            return None;
+        }
+        Some(DocItem::Module(docs::Module::new(docs, members)))
+    }
+
+    fn member_documentation(value: Value) -> docs::Member {
+        // Values with methods are documented by their type, which says nothing about the value.
+        let documentation = match value.get_ref().get_methods() {
+            Some(_) => None,
+            None => value.documentation(),
+        };
+        match documentation {
+            Some(DocItem::Function(f)) => docs::Member::Function(f),
+            Some(DocItem::Object(o)) => docs::Member::Object(o),
+            _ => docs::Member::Property(docs::Property {
+                docs: None,
+                typ: Some(docs::Type {
+                    raw_type: value.get_type().to_owned(),
+                }),
+            }),
+        }
+    }
 }
 
 impl FrozenModuleData {
@@ -228,9 +337,10 @@ impl FrozenModuleData {
 impl FrozenModuleRef {
     pub(crate) fn documentation(&self) -> Option<DocItem> {
         self.0.docstring.as_ref().map(|d| {
-            DocItem::Module(docs::Module {
-                docs: DocString::from_docstring(DocStringKind::Starlark, d),
-            })
+            DocItem::Module(docs::Module::new(
+                DocString::from_docstring(DocStringKind::Starlark, d),
+                Vec::new(),
+            ))
         })
     }
 }
@@ -244,13 +354,25 @@ impl Default for Module {
 impl Module {
     /// Create a new module environment with no contents.
     pub fn new() -> Self {
+        Self::with_heap(Heap::new())
+    }
+
+    /// Create a new module environment with no contents, whose [`heap`](Module::heap)
+    /// reuses memory from `pool`, as per [`Heap::new_with_pool`].
+    pub fn new_with_pool(pool: &HeapPool) -> Self {
+        Self::with_heap(Heap::new_with_pool(pool))
+    }
+
+    fn with_heap(heap: Heap) -> Self {
         Self {
-            heap: Heap::new(),
+            heap,
             frozen_heap: FrozenHeap::new(),
             names: MutableNames::new(),
             slots: MutableSlots::new(),
             docstring: RefCell::new(None),
+            extra_value: Cell::new(None),
             eval_duration: Cell::new(Duration::ZERO),
+            loaded: RefCell::new(Vec::new()),
         }
     }
 
@@ -264,6 +386,13 @@ impl Module {
         &self.frozen_heap
     }
 
+    /// Intern a string on the frozen heap of this module, see [`FrozenHeap::intern_str`].
+    /// Strings already interned by modules this module has loaded or imported from
+    /// (e.g. a prelude) are reused.
+    pub fn intern_str(&self, x: &str) -> FrozenStringValue {
+        self.frozen_heap.intern_str(x)
+    }
+
     pub(crate) fn names(&self) -> &MutableNames {
         &self.names
     }
@@ -292,13 +421,28 @@ impl Module {
 
     /// Freeze the environment, all its value will become immutable afterwards.
     pub fn freeze(self) -> anyhow::Result<FrozenModule> {
+        self.freeze_impl(false)
+    }
+
+    /// Like [`freeze`](Module::freeze), but only keeping the values of exported variables,
+    /// and of the private variables used by functions which are kept.
+    /// The values of other private variables, e.g. intermediate results or `load`ed symbols
+    /// which are no longer needed, are dropped, so the frozen heap is smaller.
+    /// Those variables are unassigned in the frozen module.
+    pub fn freeze_exports_only(self) -> anyhow::Result<FrozenModule> {
+        self.freeze_impl(true)
+    }
+
+    fn freeze_impl(self, exports_only: bool) -> anyhow::Result<FrozenModule> {
         let Module {
             names,
             slots,
             frozen_heap,
             heap,
             docstring,
+            extra_value,
             eval_duration,
+            loaded,
         } = self;
         let start = Instant::now();
         // This is when we do the GC/freeze, using the module slots as roots
@@ -306,11 +450,24 @@ impl Module {
         // slot-index in the code, and we don't walk into them, so don't know if
         // they are used.
         let freezer = Freezer::new(frozen_heap);
-        let slots = slots.freeze(&freezer)?;
+        // The extra value is frozen first, so the slots used by any functions it holds are kept.
+        let extra_value = match extra_value.into_inner() {
+            None => None,
+            Some(x) => Some(x.freeze(&freezer)?),
+        };
+        let slots = if exports_only {
+            let roots = names.exported_slots();
+            slots.freeze_reachable(&freezer, &names, roots)?
+        } else {
+            slots.freeze(&freezer, &names)?
+        };
+        check_never_set(freezer.promises_never_set.take())?;
         let rest = FrozenModuleRef(Arc::new(FrozenModuleData {
             names: names.freeze(),
             slots,
             docstring: docstring.into_inner(),
+            extra_value,
+            loaded: loaded.into_inner(),
         }));
         let frozen_module_ref = freezer.heap.alloc_any(rest.dupe());
         for frozen_def in freezer.frozen_defs.borrow().as_slice() {
@@ -337,6 +494,29 @@ impl Module {
         slots.set_slot(slot, value);
     }
 
+    /// Set an extra value associated with the module, which is not visible to Starlark
+    /// code, but can be used by the embedder to store per-module state.
+    /// The value is kept alive by garbage collection and frozen along with the module,
+    /// after which it is available from [`FrozenModule::extra_value`].
+    pub fn set_extra_value<'v>(&'v self, value: Value<'v>) {
+        // Not true because of variance, but mostly true. Don't export further.
+        let value = unsafe { transmute!(Value<'v>, Value<'static>, value) };
+        self.extra_value.set(Some(value));
+    }
+
+    /// Get the value set with [`set_extra_value`](Module::set_extra_value).
+    pub fn extra_value<'v>(&'v self) -> Option<Value<'v>> {
+        self.extra_value.get()
+    }
+
+    pub(crate) fn trace<'v>(&'v self, tracer: &Tracer<'v>) {
+        self.slots().get_slots_mut().trace(tracer);
+        if let Some(mut extra_value) = self.extra_value() {
+            extra_value.trace(tracer);
+            self.set_extra_value(extra_value);
+        }
+    }
+
     /// Symbols starting with underscore are considered private.
     pub(crate) fn default_visibility(symbol: &str) -> Visibility {
         match symbol.starts_with('_') {
@@ -349,6 +529,7 @@ impl Module {
     /// "private" to ensure that it is not re-exported
     pub(crate) fn set_private<'v>(&'v self, name: &str, value: Value<'v>) {
         let slot = self.names.add_name_visibility(name, Visibility::Private);
+        self.set_loaded(slot);
         let slots = self.slots();
         slots.ensure_slot(slot);
         slots.set_slot(slot, value);
@@ -366,6 +547,39 @@ impl Module {
         }
     }
 
+    /// Bind symbols exported by `module` in this module before it is evaluated, as though
+    /// by a `load()` statement at the start of the module. Each pair gives the name to bind
+    /// in this module and the name in `module`, so `("local", "symbol")` is like
+    /// `load("...", local = "symbol")`.
+    ///
+    /// The compiler resolves these names as module bindings, like loaded names, so they shadow
+    /// globals of the same name and a later assignment rebinds them. Like
+    /// [`import_public_symbols`](Module::import_public_symbols) the symbols are private,
+    /// so are not re-exported unless assigned. Fails if any symbol is private or missing,
+    /// in which case nothing is bound.
+    pub fn add_symbols_from(
+        &self,
+        module: &FrozenModule,
+        symbols: &[(&str, &str)],
+    ) -> anyhow::Result<()> {
+        let values = symbols
+            .iter()
+            .map(|(_, symbol)| self.load_symbol(module, symbol))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        for ((local, _), value) in symbols.iter().zip(values) {
+            self.set_private(local, value);
+        }
+        Ok(())
+    }
+
+    /// Record that `slot` is bound by `load`, rather than defined by the module.
+    pub(crate) fn set_loaded(&self, slot: ModuleSlotId) {
+        let mut loaded = self.loaded.borrow_mut();
+        if !loaded.contains(&slot) {
+            loaded.push(slot);
+        }
+    }
+
     pub(crate) fn load_symbol<'v>(
         &'v self,
         module: &FrozenModule,
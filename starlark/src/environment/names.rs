@@ -112,6 +112,16 @@ impl MutableNames {
             .collect()
     }
 
+    /// The slots of the names which are exported, i.e. not private.
+    pub(crate) fn exported_slots(&self) -> Vec<ModuleSlotId> {
+        self.0
+            .borrow()
+            .values()
+            .filter(|(_slot, vis)| *vis == Visibility::Public)
+            .map(|(slot, _vis)| *slot)
+            .collect()
+    }
+
     pub fn freeze(self) -> FrozenNames {
         FrozenNames(self.0.into_inner())
     }
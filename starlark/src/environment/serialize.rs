@@ -0,0 +1,301 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Serialize the exported data values of a [`FrozenModule`], so it can be
+//! reloaded without parsing or evaluating it again.
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    collections::SmallMap,
+    environment::{FrozenModule, Module},
+    syntax::ast::Visibility,
+    values::{
+        dict::Dict,
+        float::StarlarkFloat,
+        list::List,
+        structs::{Struct, StructBuilder},
+        tuple::Tuple,
+        Heap, Value,
+    },
+};
+
+// Bump whenever the format changes, so old buffers are rejected rather than misread.
+const VERSION: u32 = 2;
+
+#[derive(Debug, Error)]
+enum SerializeError {
+    #[error("Cannot serialize `{0}`, values of type `{1}` are not supported")]
+    Unsupported(String, String),
+    #[error("Cannot serialize `{0}`, it contains a cycle")]
+    Cycle(String),
+    #[error("Serialized module has version {0}, but expected version {1}")]
+    Version(u32, u32),
+}
+
+#[derive(Serialize, Deserialize)]
+enum Data {
+    None,
+    Bool(bool),
+    Int(i32),
+    // Stored as the bits, so that NaN and infinities survive
+    Float(u64),
+    String(String),
+    List(Vec<Data>),
+    Tuple(Vec<Data>),
+    Dict(Vec<(Data, Data)>),
+    Struct(Vec<(String, Data)>),
+}
+
+#[derive(Serialize, Deserialize)]
+struct SerializedModule {
+    version: u32,
+    docstring: Option<String>,
+    values: Vec<(String, Data)>,
+    extra_value: Option<Data>,
+}
+
+impl Data {
+    // `name` is the exported variable, for error messages.
+    // `stack` is the containers we are inside, to detect cycles.
+    fn new<'v>(x: Value<'v>, name: &str, stack: &mut Vec<Value<'v>>) -> anyhow::Result<Self> {
+        if x.is_none() {
+            return Ok(Data::None);
+        } else if let Some(x) = x.unpack_bool() {
+            return Ok(Data::Bool(x));
+        } else if let Some(x) = x.unpack_int() {
+            return Ok(Data::Int(x));
+        } else if let Some(x) = x.downcast_ref::<StarlarkFloat>() {
+            return Ok(Data::Float(x.0.to_bits()));
+        } else if let Some(x) = x.unpack_str() {
+            return Ok(Data::String(x.to_owned()));
+        }
+
+        if stack.iter().any(|v| v.ptr_eq(x)) {
+            return Err(SerializeError::Cycle(name.to_owned()).into());
+        }
+        stack.push(x);
+        let res = if let Some(xs) = List::from_value(x) {
+            Data::List(Self::list(xs.content(), name, stack)?)
+        } else if let Some(xs) = Tuple::from_value(x) {
+            Data::Tuple(Self::list(xs.content(), name, stack)?)
+        } else if let Some(xs) = Dict::from_value(x) {
+            let mut res = Vec::with_capacity(xs.len());
+            for (k, v) in xs.iter() {
+                res.push((Self::new(k, name, stack)?, Self::new(v, name, stack)?));
+            }
+            Data::Dict(res)
+        } else if let Some(xs) = Struct::from_value(x) {
+            let mut res = Vec::with_capacity(xs.fields.len());
+            for (k, v) in xs.fields.iter() {
+                res.push((k.as_str().to_owned(), Self::new(*v, name, stack)?));
+            }
+            Data::Struct(res)
+        } else {
+            return Err(
+                SerializeError::Unsupported(name.to_owned(), x.get_type().to_owned()).into(),
+            );
+        };
+        stack.pop();
+        Ok(res)
+    }
+
+    fn list<'v>(
+        xs: &[Value<'v>],
+        name: &str,
+        stack: &mut Vec<Value<'v>>,
+    ) -> anyhow::Result<Vec<Self>> {
+        xs.iter().map(|x| Self::new(*x, name, stack)).collect()
+    }
+
+    fn alloc<'v>(self, heap: &'v Heap) -> anyhow::Result<Value<'v>> {
+        Ok(match self {
+            Data::None => Value::new_none(),
+            Data::Bool(x) => Value::new_bool(x),
+            Data::Int(x) => Value::new_int(x),
+            Data::Float(x) => heap.alloc(f64::from_bits(x)),
+            Data::String(x) => heap.alloc(x),
+            Data::List(xs) => heap.alloc_list(&Self::alloc_list(xs, heap)?),
+            Data::Tuple(xs) => heap.alloc_tuple(&Self::alloc_list(xs, heap)?),
+            Data::Dict(xs) => {
+                let mut res = SmallMap::with_capacity(xs.len());
+                for (k, v) in xs {
+                    res.insert_hashed(k.alloc(heap)?.get_hashed()?, v.alloc(heap)?);
+                }
+                heap.alloc(Dict::new(res))
+            }
+            Data::Struct(xs) => {
+                let mut res = StructBuilder::with_capacity(heap, xs.len());
+                for (k, v) in xs {
+                    res.add(&k, v.alloc(heap)?);
+                }
+                heap.alloc(res.build())
+            }
+        })
+    }
+
+    fn alloc_list<'v>(xs: Vec<Self>, heap: &'v Heap) -> anyhow::Result<Vec<Value<'v>>> {
+        xs.into_iter().map(|x| x.alloc(heap)).collect()
+    }
+}
+
+impl FrozenModule {
+    /// Serialize the exported values of this module into bytes, which can be turned back
+    /// into a module with [`from_bytes`](FrozenModule::from_bytes).
+    /// Only data values are supported, namely `None`, booleans, integers, floats, strings,
+    /// lists, tuples, dictionaries and structs. Any other value (e.g. a function) is an error.
+    /// The module docstring and [`extra_value`](FrozenModule::extra_value) are also preserved,
+    /// and the extra value must be data too.
+    ///
+    /// This API is experimental, and the format may change between versions.
+    pub fn to_bytes(&self) -> anyhow::Result<Vec<u8>> {
+        let mut values = Vec::new();
+        for name in self.names() {
+            if Module::default_visibility(name) != Visibility::Public {
+                continue;
+            }
+            if let Some(x) = self.get(name) {
+                values.push((
+                    name.to_owned(),
+                    Data::new(x.value(), name, &mut Vec::new())?,
+                ));
+            }
+        }
+        let extra_value = match self.extra_value() {
+            None => None,
+            Some(x) => Some(Data::new(x.value(), "extra_value", &mut Vec::new())?),
+        };
+        let module = SerializedModule {
+            version: VERSION,
+            docstring: self.docstring().map(str::to_owned),
+            values,
+            extra_value,
+        };
+        Ok(serde_json::to_vec(&module)?)
+    }
+
+    /// Reconstruct a module, on a fresh heap, from the result of
+    /// [`to_bytes`](FrozenModule::to_bytes).
+    ///
+    /// This API is experimental, and the format may change between versions.
+    pub fn from_bytes(bytes: &[u8]) -> anyhow::Result<FrozenModule> {
+        let module: SerializedModule = serde_json::from_slice(bytes)?;
+        if module.version != VERSION {
+            return Err(SerializeError::Version(module.version, VERSION).into());
+        }
+        let res = Module::new();
+        for (name, x) in module.values {
+            res.set(&name, x.alloc(res.heap())?);
+        }
+        if let Some(docstring) = module.docstring {
+            res.set_docstring(docstring);
+        }
+        if let Some(x) = module.extra_value {
+            res.set_extra_value(x.alloc(res.heap())?);
+        }
+        res.freeze()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use itertools::Itertools;
+
+    use super::VERSION;
+    use crate::{
+        assert,
+        environment::{FrozenModule, Module},
+        values::{any::StarlarkAny, Value},
+    };
+
+    #[test]
+    fn test_serialize_round_trip() {
+        let original = assert::pass_module(
+            r#"
+"""The module docs"""
+x = {"a": [1, 2.5, (True, None)], "b": struct(c = "d", e = {1: -1})}
+y = x["a"]
+z = float("nan")
+_private = 1
+"#,
+        );
+        let bytes = original.to_bytes().unwrap();
+        let reloaded = FrozenModule::from_bytes(&bytes).unwrap();
+
+        assert_eq!(
+            reloaded.names().sorted().collect::<Vec<_>>(),
+            vec!["x", "y", "z"]
+        );
+        for name in ["x", "y"] {
+            let a = original.get(name).unwrap();
+            let b = reloaded.get(name).unwrap();
+            assert!(a.value().equals(b.value()).unwrap(), "{}", name);
+            assert_eq!(a.value().to_repr(), b.value().to_repr());
+        }
+        assert_eq!(reloaded.get("z").unwrap().value().to_repr(), "nan");
+        assert!(reloaded.get("_private").is_none());
+        assert_eq!(original.documentation(), reloaded.documentation());
+        assert!(reloaded.documentation().is_some());
+        assert!(reloaded.extra_value().is_none());
+    }
+
+    #[test]
+    fn test_serialize_round_trip_extra_value() {
+        let module = Module::new();
+        module.set("x", Value::new_int(1));
+        let heap = module.heap();
+        module.set_extra_value(heap.alloc_list(&[heap.alloc("state"), Value::new_int(2)]));
+        let bytes = module.freeze().unwrap().to_bytes().unwrap();
+        let reloaded = FrozenModule::from_bytes(&bytes).unwrap();
+        assert_eq!(
+            reloaded.extra_value().unwrap().value().to_repr(),
+            r#"["state", 2]"#
+        );
+        assert_eq!(reloaded.get("x").unwrap().value().to_repr(), "1");
+    }
+
+    #[test]
+    fn test_serialize_unsupported() {
+        let module = assert::pass_module("def f(): pass\nx = [f]");
+        let err = module.to_bytes().unwrap_err();
+        assert!(err.to_string().contains("Cannot serialize"));
+        assert!(err.to_string().contains("function"));
+    }
+
+    #[test]
+    fn test_serialize_unsupported_extra_value() {
+        let module = Module::new();
+        module.set("x", Value::new_int(1));
+        module.set_extra_value(module.heap().alloc(StarlarkAny::new(1)));
+        let err = module.freeze().unwrap().to_bytes().unwrap_err();
+        assert!(err.to_string().contains("Cannot serialize `extra_value`"));
+    }
+
+    #[test]
+    fn test_serialize_bad_bytes() {
+        assert!(FrozenModule::from_bytes(b"not a module").is_err());
+        let bytes = assert::pass_module("x = 1").to_bytes().unwrap();
+        let bytes = String::from_utf8(bytes)
+            .unwrap()
+            .replace(&format!("\"version\":{}", VERSION), "\"version\":999");
+        assert!(FrozenModule::from_bytes(bytes.as_bytes())
+            .unwrap_err()
+            .to_string()
+            .contains("version"));
+    }
+}
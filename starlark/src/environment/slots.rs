@@ -15,11 +15,17 @@
  * limitations under the License.
  */
 
-use std::cell::{RefCell, RefMut};
+use std::{
+    cell::{RefCell, RefMut},
+    mem,
+};
 
 use gazebo::prelude::*;
 
-use crate::values::{Freezer, FrozenValue, Value};
+use crate::{
+    environment::names::MutableNames,
+    values::{FreezeError, Freezer, FrozenValue, Value},
+};
 
 #[derive(Clone, Copy, Dupe, Debug, PartialEq, Eq)]
 pub(crate) struct ModuleSlotId(pub(crate) u32);
@@ -72,13 +78,72 @@ impl<'v> MutableSlots<'v> {
         }
     }
 
-    pub(crate) fn freeze(self, freezer: &Freezer) -> anyhow::Result<FrozenSlots> {
+    fn freeze_slot(
+        x: Option<Value<'v>>,
+        i: usize,
+        freezer: &Freezer,
+        names: &MutableNames,
+    ) -> anyhow::Result<Option<FrozenValue>> {
+        x.into_try_map(|x| {
+            x.freeze(freezer).map_err(|e| {
+                let name = names
+                    .get_slot(ModuleSlotId::new(i as u32))
+                    .unwrap_or_else(|| format!("<slot {}>", i));
+                FreezeError::add_step(e, name)
+            })
+        })
+    }
+
+    /// Freeze the slots. On failure, the error names the module variable
+    /// (as given by `names`) whose value could not be frozen.
+    pub(crate) fn freeze(
+        self,
+        freezer: &Freezer,
+        names: &MutableNames,
+    ) -> anyhow::Result<FrozenSlots> {
         let slots = self
             .0
             .into_inner()
-            .try_map(|x| x.into_try_map(|x| x.freeze(freezer)))?;
+            .into_iter()
+            .enumerate()
+            .map(|(i, x)| Self::freeze_slot(x, i, freezer, names))
+            .collect::<anyhow::Result<Vec<_>>>()?;
         Ok(FrozenSlots(slots))
     }
+
+    /// Like [`freeze`](MutableSlots::freeze), but only freezing the `roots`, and the slots
+    /// referred to by the functions frozen along the way, which may in turn freeze more
+    /// functions. The other slots are left unassigned, so their values are dropped.
+    pub(crate) fn freeze_reachable(
+        self,
+        freezer: &Freezer,
+        names: &MutableNames,
+        roots: Vec<ModuleSlotId>,
+    ) -> anyhow::Result<FrozenSlots> {
+        let slots = self.0.into_inner();
+        let mut res = vec![None; slots.len()];
+        let mut done = vec![false; slots.len()];
+        let mut todo = roots;
+        // The number of frozen defs whose slots are already in `todo`.
+        let mut defs_seen = 0;
+        loop {
+            while let Some(ModuleSlotId(i)) = todo.pop() {
+                let i = i as usize;
+                if i < slots.len() && !mem::replace(&mut done[i], true) {
+                    res[i] = Self::freeze_slot(slots[i], i, freezer, names)?;
+                }
+            }
+            let defs = freezer.frozen_defs.borrow();
+            if defs_seen == defs.len() {
+                break;
+            }
+            for def in &defs[defs_seen..] {
+                todo.extend_from_slice(def.referenced_module_slots());
+            }
+            defs_seen = defs.len();
+        }
+        Ok(FrozenSlots(res))
+    }
 }
 
 impl FrozenSlots {
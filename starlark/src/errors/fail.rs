@@ -0,0 +1,104 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! The error raised by the `fail()` builtin.
+
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+};
+
+use crate::errors::Diagnostic;
+
+/// The error produced by calling `fail()`, e.g. `fail("boom", code = "E123", attrs = {"url": x})`.
+///
+/// After evaluation it is usually wrapped in a [`Diagnostic`], which holds the span
+/// and call stack of the `fail()` call, so use [`StarlarkFailError::from_error`] to find it.
+/// It is displayed as `fail:` followed by the positional arguments,
+/// regardless of the `code` and `attrs`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StarlarkFailError {
+    message: String,
+    // Whether `fail()` was called with any positional arguments, so `fail()` and `fail("")`
+    // keep displaying differently.
+    has_args: bool,
+    code: Option<String>,
+    attrs: Vec<(String, String)>,
+}
+
+impl StarlarkFailError {
+    pub(crate) fn new(
+        args: Vec<String>,
+        code: Option<String>,
+        attrs: Vec<(String, String)>,
+    ) -> Self {
+        Self {
+            message: args.join(" "),
+            has_args: !args.is_empty(),
+            code,
+            attrs,
+        }
+    }
+
+    /// Get the [`StarlarkFailError`] stored in an error, if there is one,
+    /// looking inside the [`Diagnostic`] the error is usually wrapped in.
+    pub fn from_error(err: &anyhow::Error) -> Option<&StarlarkFailError> {
+        match Diagnostic::from_error(err) {
+            Some(diag) => diag.message.downcast_ref(),
+            None => err.downcast_ref(),
+        }
+    }
+
+    /// The positional arguments to `fail()`, separated by spaces.
+    /// Strings are used as they are, other values as their `repr`.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// The `code` argument to `fail()`, if it was given.
+    pub fn code(&self) -> Option<&str> {
+        self.code.as_deref()
+    }
+
+    /// The entries of the `attrs` argument to `fail()`, in order.
+    /// String values are used as they are, other values are converted to their `repr`,
+    /// so `attrs = {"n": 1, "s": "1"}` gives `[("n", "1"), ("s", "1")]`,
+    /// but `{"n": [1]}` gives `[("n", "[1]")]`.
+    pub fn attrs(&self) -> &[(String, String)] {
+        &self.attrs
+    }
+
+    /// The value of an entry of the `attrs` argument to `fail()`, as for [`attrs`](StarlarkFailError::attrs).
+    pub fn attr(&self, name: &str) -> Option<&str> {
+        self.attrs
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+impl Display for StarlarkFailError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.has_args {
+            write!(f, "fail: {}", self.message)
+        } else {
+            write!(f, "fail:")
+        }
+    }
+}
+
+impl Error for StarlarkFailError {}
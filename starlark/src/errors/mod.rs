@@ -26,11 +26,29 @@ use annotate_snippets::{
     display_list::{DisplayList, FormatOptions},
     snippet::{Annotation, AnnotationType, Slice, Snippet, SourceAnnotation},
 };
+use gazebo::prelude::*;
+use serde::{Serialize, Serializer};
 
-pub use crate::analysis::Lint;
-use crate::codemap::{CodeMap, FileSpan, Span};
+pub use crate::{
+    analysis::{
+        CheckerCache, CheckerContext, Lint, LintBaseline, LintBaselineEntry, LintConfig, LintFix,
+        LintSeverity,
+    },
+    errors::fail::StarlarkFailError,
+};
+use crate::{
+    codemap::{CodeMap, FileSpan, Span},
+    eval::runtime::{arguments::FunctionError, call_stack::CallStackError},
+    syntax::{
+        lexer::LexemeError,
+        parser::{IncompleteInput, ParseError},
+        validate::ValidateError,
+    },
+    values::{layout::HeapError, ValueError},
+};
 
 pub(crate) mod did_you_mean;
+mod fail;
 
 /// An error plus its origination location and call stack.
 ///
@@ -46,7 +64,13 @@ pub struct Diagnostic {
     pub span: Option<FileSpan>,
 
     /// Call stack of what called what. Most recent frames are at the end.
+    /// All the frames are kept, but when displayed, only those at either end
+    /// of a long stack are shown.
     pub call_stack: Vec<Frame>,
+
+    /// Other locations relevant to the error, each with a label,
+    /// e.g. the right operand of a binary operator which failed.
+    pub secondary_spans: Vec<(FileSpan, String)>,
 }
 
 /// A frame of the call-stack.
@@ -58,6 +82,90 @@ pub struct Frame {
     pub location: Option<FileSpan>,
 }
 
+/// A location in a file, as a file name with 1-based lines and columns.
+/// The end column is exclusive, matching how a [`FileSpan`] is displayed.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+pub struct DiagnosticSpan {
+    /// The name of the file.
+    pub path: String,
+    /// 1-based line number of the beginning of the span.
+    pub begin_line: usize,
+    /// 1-based column number of the beginning of the span.
+    pub begin_column: usize,
+    /// 1-based line number of the end of the span.
+    pub end_line: usize,
+    /// 1-based column number just after the end of the span.
+    pub end_column: usize,
+}
+
+impl From<&FileSpan> for DiagnosticSpan {
+    fn from(span: &FileSpan) -> Self {
+        let resolved = span.resolve_span();
+        Self {
+            path: span.file().filename().to_owned(),
+            begin_line: resolved.begin_line + 1,
+            begin_column: resolved.begin_column + 1,
+            end_line: resolved.end_line + 1,
+            end_column: resolved.end_column + 1,
+        }
+    }
+}
+
+/// The JSON form of a [`Frame`].
+#[derive(Serialize)]
+struct FrameJson<'a> {
+    name: &'a str,
+    location: Option<DiagnosticSpan>,
+}
+
+/// The JSON form of a secondary span of a [`Diagnostic`].
+#[derive(Serialize)]
+struct SecondarySpanJson<'a> {
+    span: DiagnosticSpan,
+    label: &'a str,
+}
+
+/// The JSON form of a [`Diagnostic`].
+#[derive(Serialize)]
+struct DiagnosticJson<'a> {
+    code: Option<&'a str>,
+    message: String,
+    span: Option<DiagnosticSpan>,
+    call_stack: &'a [Frame],
+    secondary_spans: Vec<SecondarySpanJson<'a>>,
+}
+
+impl Serialize for Frame {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        FrameJson {
+            name: &self.name,
+            location: self.location.as_ref().map(DiagnosticSpan::from),
+        }
+        .serialize(serializer)
+    }
+}
+
+/// Serialized as an object with fields `code` (see [`Diagnostic::error_code`], or `null`),
+/// `message` (see [`Diagnostic::primary_message`]),
+/// `span` (a [`DiagnosticSpan`] or `null`), `call_stack` (a list of objects with a `name`
+/// and `location`, outermost first) and `secondary_spans` (a list of objects with a
+/// `span` and `label`).
+impl Serialize for Diagnostic {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        DiagnosticJson {
+            code: self.error_code(),
+            message: self.primary_message(),
+            span: self.primary_span(),
+            call_stack: &self.call_stack,
+            secondary_spans: self.secondary_spans.map(|(span, label)| SecondarySpanJson {
+                span: DiagnosticSpan::from(span),
+                label,
+            }),
+        }
+        .serialize(serializer)
+    }
+}
+
 impl Display for Frame {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         f.write_str(&self.name)?;
@@ -83,6 +191,54 @@ impl Error for Diagnostic {
 }
 
 impl Diagnostic {
+    /// Get the [`Diagnostic`] stored in an error, if there is one.
+    /// Errors from parsing and evaluation usually have one.
+    pub fn from_error(err: &anyhow::Error) -> Option<&Diagnostic> {
+        err.downcast_ref::<Diagnostic>()
+    }
+
+    /// The error message, including any context, but without the location or call stack.
+    pub fn primary_message(&self) -> String {
+        format!("{:#}", self.message)
+    }
+
+    /// The location where the error originated, if known.
+    pub fn primary_span(&self) -> Option<DiagnosticSpan> {
+        self.span.as_ref().map(DiagnosticSpan::from)
+    }
+
+    /// A short code identifying the kind of error, which stays the same when the
+    /// wording of the message changes, so tools can match on it.
+    ///
+    /// For `fail(code = "...")` this is the given code, and otherwise it is one of
+    /// `"fail"` (any other `fail()`), `"syntax"` (lexing, parsing or validation),
+    /// `"value"` (an operation on a value failed, e.g. an unsupported operator or
+    /// an index out of range), `"arguments"` (a function was called with the wrong arguments),
+    /// `"stack_overflow"` or `"heap_limit"`. Other errors, e.g. those raised by
+    /// native functions, have no code.
+    pub fn error_code(&self) -> Option<&str> {
+        let e = &self.message;
+        if let Some(fail) = e.downcast_ref::<StarlarkFailError>() {
+            Some(fail.code().unwrap_or("fail"))
+        } else if e.is::<ParseError>()
+            || e.is::<IncompleteInput>()
+            || e.is::<LexemeError>()
+            || e.is::<ValidateError>()
+        {
+            Some("syntax")
+        } else if e.is::<ValueError>() {
+            Some("value")
+        } else if e.is::<FunctionError>() {
+            Some("arguments")
+        } else if e.is::<CallStackError>() {
+            Some("stack_overflow")
+        } else if e.is::<HeapError>() {
+            Some("heap_limit")
+        } else {
+            None
+        }
+    }
+
     /// Create a new [`Diagnostic`] containing an underlying error and span.
     /// If the given `message` is already a [`Diagnostic`] with a [`Span`],
     /// the new span will be ignored and the original `message` returned.
@@ -108,6 +264,7 @@ impl Diagnostic {
                     message: err,
                     span: None,
                     call_stack: Vec::new(),
+                    secondary_spans: Vec::new(),
                 };
                 f(&mut err);
                 err.into()
@@ -123,6 +280,11 @@ impl Diagnostic {
         }
     }
 
+    /// Add a labelled entry to [`Diagnostic::secondary_spans`].
+    pub(crate) fn add_secondary_span(&mut self, span: Span, codemap: &CodeMap, label: String) {
+        self.secondary_spans.push((codemap.file_span(span), label));
+    }
+
     /// Set the [`Diagnostic::call_stack`] field, unless it's already been set.
     pub fn set_call_stack(&mut self, call_stack: impl FnOnce() -> Vec<Frame>) {
         if self.call_stack.is_empty() {
@@ -131,6 +293,13 @@ impl Diagnostic {
         }
     }
 
+    /// Is this a parse error caused by the input ending too early, e.g. inside a block,
+    /// unclosed brackets or a triple-quoted string. Such input might parse once more lines
+    /// are added, which is what a REPL needs to know before asking for another line.
+    pub fn is_incomplete_input(&self) -> bool {
+        self.message.is::<IncompleteInput>()
+    }
+
     /// Print an error to the stderr stream. If the error is a [`Diagnostic`] it will use
     /// color-codes when printing.
     ///
@@ -153,10 +322,26 @@ impl Display for Diagnostic {
 
 struct CallStackFmt<'a>(&'a Vec<Frame>);
 
+/// How many frames to show at each end of a long call stack, omitting those in between.
+const CALL_STACK_FMT_ENDS: usize = 10;
+
 impl Display for CallStackFmt<'_> {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        for x in self.0.iter() {
-            writeln!(f, "* {}", x)?;
+        // A deep stack, e.g. after an overflow, is mostly noise in the middle.
+        if self.0.len() > CALL_STACK_FMT_ENDS * 2 + 1 {
+            let (first, rest) = self.0.split_at(CALL_STACK_FMT_ENDS);
+            let (omitted, last) = rest.split_at(rest.len() - CALL_STACK_FMT_ENDS);
+            for x in first {
+                writeln!(f, "* {}", x)?;
+            }
+            writeln!(f, "* \u{2026} {} frames omitted \u{2026}", omitted.len())?;
+            for x in last {
+                writeln!(f, "* {}", x)?;
+            }
+        } else {
+            for x in self.0.iter() {
+                writeln!(f, "* {}", x)?;
+            }
         }
         Ok(())
     }
@@ -181,7 +366,10 @@ fn get_display_list_for_diagnostic<'a>(
         (start_column, start_column + span_length)
     }
 
-    fn convert_span_to_slice<'a>(span: &'a FileSpan) -> Slice<'a> {
+    fn convert_span_to_slice<'a>(
+        span: &'a FileSpan,
+        secondary_spans: &'a [(FileSpan, String)],
+    ) -> Slice<'a> {
         let region = span.resolve_span();
 
         // we want the source_span to capture any whitespace ahead of the diagnostic span to
@@ -189,22 +377,47 @@ fn get_display_list_for_diagnostic<'a>(
         // on the last line for context.
         let first_line_span = span.file.line_span(region.begin_line);
         let last_line_span = span.file.line_span(region.end_line);
-        let source_span = span.span.merge(first_line_span).merge(last_line_span);
+        let mut source_span = span.span.merge(first_line_span).merge(last_line_span);
+
+        let mut annotations = vec![SourceAnnotation {
+            label: "",
+            annotation_type: AnnotationType::Error,
+            range: convert_span_to_range_relative_to_first_line(span.span, region.begin_column),
+        }];
+
+        // Secondary spans are only shown if they come after the start of the primary span
+        // in the same file, so the slice can still start at the primary span's first line.
+        for (secondary, label) in secondary_spans {
+            if secondary.file != span.file || secondary.span.begin() < first_line_span.begin() {
+                continue;
+            }
+            let secondary_region = secondary.resolve_span();
+            source_span = source_span.merge(secondary.file.line_span(secondary_region.end_line));
+            let start = span
+                .file
+                .source_span(Span::new(first_line_span.begin(), secondary.span.begin()))
+                .chars()
+                .count();
+            annotations.push(SourceAnnotation {
+                label,
+                annotation_type: AnnotationType::Note,
+                range: (start, start + secondary.source_span().chars().count()),
+            });
+        }
 
         Slice {
             source: span.file.source_span(source_span),
             line_start: 1 + region.begin_line,
             origin: Some(span.file.filename()),
             fold: false,
-            annotations: vec![SourceAnnotation {
-                label: "",
-                annotation_type: AnnotationType::Error,
-                range: convert_span_to_range_relative_to_first_line(span.span, region.begin_column),
-            }],
+            annotations,
         }
     }
 
-    let slice = x.span.as_ref().map(convert_span_to_slice);
+    let slice = x
+        .span
+        .as_ref()
+        .map(|span| convert_span_to_slice(span, &x.secondary_spans));
 
     let snippet = Snippet {
         title: Some(Annotation {
@@ -0,0 +1,213 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Evaluate many independent modules on several threads.
+
+use std::{
+    panic,
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use gazebo::prelude::*;
+
+use crate::{
+    environment::{FrozenModule, Globals, Module},
+    eval::Evaluator,
+    syntax::{AstModule, Dialect},
+};
+
+/// Parse, evaluate and freeze each of `sources`, given as pairs of file name and content,
+/// as an independent module, using up to `threads` threads.
+///
+/// Each module starts with the public symbols of every module in `prelude` imported,
+/// as by [`Module::import_public_symbols`]. The frozen prelude values are shared between
+/// the threads, not copied. Loads are not supported.
+///
+/// Returns one result per source, in the order of `sources`. A source which fails to
+/// parse, evaluate or freeze gives an error for that source only, and doesn't stop
+/// the evaluation of the others.
+pub fn eval_many(
+    prelude: &[FrozenModule],
+    sources: Vec<(String, String)>,
+    dialect: &Dialect,
+    globals: &Globals,
+    threads: usize,
+) -> Vec<anyhow::Result<FrozenModule>> {
+    let dialect = dialect.clone();
+    run_many(
+        prelude,
+        sources,
+        globals,
+        threads,
+        move |(name, content)| AstModule::parse(&name, content, &dialect),
+    )
+}
+
+/// Like [`eval_many`], but for modules which have already been parsed,
+/// e.g. because they were linted first.
+pub fn eval_many_parsed(
+    prelude: &[FrozenModule],
+    modules: Vec<AstModule>,
+    globals: &Globals,
+    threads: usize,
+) -> Vec<anyhow::Result<FrozenModule>> {
+    run_many(prelude, modules, globals, threads, Ok)
+}
+
+/// Evaluate each of `items`, which `parse` turns into a module on the worker thread.
+fn run_many<T: Send + 'static>(
+    prelude: &[FrozenModule],
+    items: Vec<T>,
+    globals: &Globals,
+    threads: usize,
+    parse: impl Fn(T) -> anyhow::Result<AstModule> + Clone + Send + 'static,
+) -> Vec<anyhow::Result<FrozenModule>> {
+    let count = items.len();
+    let queue = Arc::new(Mutex::new(items.into_iter().enumerate()));
+    let workers = (0..threads.max(1).min(count)).map(|_| {
+        let queue = queue.dupe();
+        let prelude = prelude.to_vec();
+        let globals = globals.dupe();
+        let parse = parse.clone();
+        thread::spawn(move || {
+            let mut results = Vec::new();
+            loop {
+                // Bind the next item first, so the lock is released before evaluating.
+                let next = queue.lock().unwrap().next();
+                match next {
+                    None => return results,
+                    Some((i, item)) => results.push((
+                        i,
+                        parse(item).and_then(|ast| eval_one(&prelude, ast, &globals)),
+                    )),
+                }
+            }
+        })
+    });
+    let workers: Vec<_> = workers.collect();
+
+    let mut results: Vec<Option<anyhow::Result<FrozenModule>>> = (0..count).map(|_| None).collect();
+    for worker in workers {
+        let worker_results = worker
+            .join()
+            .unwrap_or_else(|payload| panic::resume_unwind(payload));
+        for (i, result) in worker_results {
+            results[i] = Some(result);
+        }
+    }
+    results.into_map(|x| x.expect("every source is evaluated"))
+}
+
+fn eval_one(
+    prelude: &[FrozenModule],
+    ast: AstModule,
+    globals: &Globals,
+) -> anyhow::Result<FrozenModule> {
+    let module = Module::new();
+    for p in prelude {
+        module.import_public_symbols(p);
+    }
+    let mut eval = Evaluator::new(&module);
+    eval.eval_module(ast, globals)?;
+    drop(eval);
+    module.freeze()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_eval_many() {
+        let prelude = Module::new();
+        let mut eval = Evaluator::new(&prelude);
+        let ast = AstModule::parse(
+            "prelude.star",
+            "BASE = 100\ndef double(x):\n    return x * 2".to_owned(),
+            &Dialect::Extended,
+        )
+        .unwrap();
+        eval.eval_module(ast, &Globals::standard()).unwrap();
+        drop(eval);
+        let prelude = prelude.freeze().unwrap();
+
+        let sources = (0..50)
+            .map(|i| {
+                let content = match i {
+                    17 => "x = fail('broken module')".to_owned(),
+                    31 => "x = (".to_owned(),
+                    _ => format!("x = BASE + double({})", i),
+                };
+                (format!("m{}.star", i), content)
+            })
+            .collect::<Vec<_>>();
+
+        let results = eval_many(
+            &[prelude],
+            sources,
+            &Dialect::Extended,
+            &Globals::standard(),
+            4,
+        );
+        assert_eq!(50, results.len());
+        for (i, result) in results.iter().enumerate() {
+            match i {
+                17 => {
+                    let e = result.as_ref().unwrap_err().to_string();
+                    assert!(e.contains("broken module"), "{}", e);
+                }
+                31 => {
+                    let e = result.as_ref().unwrap_err().to_string();
+                    assert!(e.contains("m31.star"), "{}", e);
+                }
+                _ => assert_eq!(
+                    Some(100 + 2 * i as i32),
+                    result.as_ref().unwrap().get("x").unwrap().unpack_int()
+                ),
+            }
+        }
+    }
+
+    #[test]
+    fn test_eval_many_parsed() {
+        let modules = (0..10)
+            .map(|i| {
+                AstModule::parse(
+                    &format!("m{}.star", i),
+                    format!("x = {} * 2", i),
+                    &Dialect::Extended,
+                )
+                .unwrap()
+            })
+            .collect::<Vec<_>>();
+        let results = eval_many_parsed(&[], modules, &Globals::standard(), 3);
+        assert_eq!(10, results.len());
+        for (i, result) in results.iter().enumerate() {
+            assert_eq!(
+                Some(2 * i as i32),
+                result.as_ref().unwrap().get("x").unwrap().unpack_int()
+            );
+        }
+    }
+
+    #[test]
+    fn test_eval_many_empty() {
+        let results = eval_many(&[], Vec::new(), &Dialect::Extended, &Globals::standard(), 4);
+        assert!(results.is_empty());
+    }
+}
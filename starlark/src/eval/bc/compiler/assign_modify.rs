@@ -25,7 +25,8 @@ use crate::{
                 InstrAddAssign, InstrArrayIndexNoPop, InstrArrayIndexSet, InstrBitAnd,
                 InstrBitOrAssign, InstrBitXor, InstrDivide, InstrDup, InstrFloorDivide,
                 InstrLeftShift, InstrLoadModule, InstrMultiply, InstrObjectField,
-                InstrObjectSetField, InstrPercent, InstrRightShift, InstrStoreModule, InstrSub,
+                InstrObjectSetField, InstrPercent, InstrRightShift, InstrStoreModuleAndExport,
+                InstrSub,
             },
             writer::BcWriter,
         },
@@ -93,12 +94,12 @@ impl AssignModifyLhs {
                     Captured::No => bc.write_store_local(span, slot),
                 }
             }
-            AssignModifyLhs::Module(m) => {
+            AssignModifyLhs::Module(m, ref name) => {
                 let slot = m.node;
                 bc.write_instr::<InstrLoadModule>(span, slot);
                 rhs.write_bc(bc);
                 op.write_bc(span, bc);
-                bc.write_instr::<InstrStoreModule>(span, slot);
+                bc.write_instr::<InstrStoreModuleAndExport>(span, (slot, name.clone()));
             }
         }
     }
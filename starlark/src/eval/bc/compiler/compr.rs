@@ -19,7 +19,7 @@
 
 use crate::eval::{
     bc::{
-        compiler::if_compiler::write_if_then,
+        compiler::{for_loop::write_for, if_compiler::write_if_then},
         instr_impl::{
             InstrComprDictInsert, InstrComprListAppend, InstrContinue, InstrDictNew, InstrListNew,
         },
@@ -39,9 +39,7 @@ impl ClauseCompiled {
         rem: &[ClauseCompiled],
         term: impl FnOnce(&mut BcWriter),
     ) {
-        self.over.write_bc(bc);
-        bc.write_for(self.over.span, |bc| {
-            self.var.write_bc(bc);
+        write_for(&self.var, &self.over, self.over.span, bc, |bc| {
             for c in &self.ifs {
                 write_if_then(
                     c,
@@ -71,6 +69,7 @@ impl ComprCompiled {
         match *self {
             ComprCompiled::List(box ref expr, ref clauses) => {
                 bc.write_instr::<InstrListNew>(span, ());
+                bc.write_record_origin(span);
                 let (first, rem) = clauses.split_last();
                 first.write_bc(bc, rem, |bc| {
                     expr.write_bc(bc);
@@ -79,6 +78,7 @@ impl ComprCompiled {
             }
             ComprCompiled::Dict(box (ref k, ref v), ref clauses) => {
                 bc.write_instr::<InstrDictNew>(span, ());
+                bc.write_record_origin(span);
                 let (first, rem) = clauses.split_last();
                 first.write_bc(bc, rem, |bc| {
                     k.write_bc(bc);
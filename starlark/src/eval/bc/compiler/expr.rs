@@ -109,6 +109,7 @@ impl IrSpanned<ExprCompiled> {
                 ArgPopsStack(xs.len() as u32 * 2),
             );
         }
+        bc.write_record_origin(span);
     }
 
     fn write_not(expr: &IrSpanned<ExprCompiled>, bc: &mut BcWriter) {
@@ -186,6 +187,7 @@ impl IrSpanned<ExprCompiled> {
                     write_exprs(xs, bc);
                     bc.write_instr::<InstrListNPop>(span, ArgPopsStack(xs.len() as u32));
                 }
+                bc.write_record_origin(span);
             }
             ExprCompiled::Dict(ref xs) => Self::write_dict(span, xs, bc),
             ExprCompiled::Compr(ref compr) => {
@@ -265,19 +267,28 @@ impl IrSpanned<ExprCompiled> {
             ExprCompiled::Op(op, box (ref l, ref r)) => {
                 l.write_bc(bc);
                 r.write_bc(bc);
+                // Operand spans are used to point errors at the operands.
+                let slow_arg = BcInstrSlowArg {
+                    span,
+                    spans: vec![l.span, r.span],
+                };
                 match op {
-                    ExprBinOp::In => bc.write_instr::<InstrIn>(span, ()),
-                    ExprBinOp::Sub => bc.write_instr::<InstrSub>(span, ()),
-                    ExprBinOp::Add => bc.write_instr::<InstrAdd>(span, ()),
-                    ExprBinOp::Multiply => bc.write_instr::<InstrMultiply>(span, ()),
-                    ExprBinOp::Divide => bc.write_instr::<InstrDivide>(span, ()),
-                    ExprBinOp::FloorDivide => bc.write_instr::<InstrFloorDivide>(span, ()),
-                    ExprBinOp::Percent => bc.write_instr::<InstrPercent>(span, ()),
-                    ExprBinOp::BitAnd => bc.write_instr::<InstrBitAnd>(span, ()),
-                    ExprBinOp::BitOr => bc.write_instr::<InstrBitOr>(span, ()),
-                    ExprBinOp::BitXor => bc.write_instr::<InstrBitXor>(span, ()),
-                    ExprBinOp::LeftShift => bc.write_instr::<InstrLeftShift>(span, ()),
-                    ExprBinOp::RightShift => bc.write_instr::<InstrRightShift>(span, ()),
+                    ExprBinOp::In => bc.write_instr_explicit::<InstrIn>(slow_arg, ()),
+                    ExprBinOp::Sub => bc.write_instr_explicit::<InstrSub>(slow_arg, ()),
+                    ExprBinOp::Add => bc.write_instr_explicit::<InstrAdd>(slow_arg, ()),
+                    ExprBinOp::Multiply => bc.write_instr_explicit::<InstrMultiply>(slow_arg, ()),
+                    ExprBinOp::Divide => bc.write_instr_explicit::<InstrDivide>(slow_arg, ()),
+                    ExprBinOp::FloorDivide => {
+                        bc.write_instr_explicit::<InstrFloorDivide>(slow_arg, ())
+                    }
+                    ExprBinOp::Percent => bc.write_instr_explicit::<InstrPercent>(slow_arg, ()),
+                    ExprBinOp::BitAnd => bc.write_instr_explicit::<InstrBitAnd>(slow_arg, ()),
+                    ExprBinOp::BitOr => bc.write_instr_explicit::<InstrBitOr>(slow_arg, ()),
+                    ExprBinOp::BitXor => bc.write_instr_explicit::<InstrBitXor>(slow_arg, ()),
+                    ExprBinOp::LeftShift => bc.write_instr_explicit::<InstrLeftShift>(slow_arg, ()),
+                    ExprBinOp::RightShift => {
+                        bc.write_instr_explicit::<InstrRightShift>(slow_arg, ())
+                    }
                 }
             }
             ExprCompiled::PercentSOne(box (before, ref arg, after)) => {
@@ -0,0 +1,116 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Compile `for` loops, of both statements and comprehensions.
+
+use crate::eval::{
+    bc::{instr_impl::DictView, writer::BcWriter},
+    compiler::Constants,
+    fragment::{
+        call::CallCompiled, expr::ExprCompiled, span::IrSpanned, stmt::AssignCompiledValue,
+    },
+    runtime::call_stack::FrozenFileSpan,
+};
+
+/// Collections a loop can iterate over without allocating them.
+enum LoopOver<'a> {
+    /// `d.keys()`, `d.values()` or `d.items()`.
+    DictView(&'a IrSpanned<ExprCompiled>, DictView),
+    /// `enumerate(xs)`.
+    Enumerate(&'a IrSpanned<ExprCompiled>),
+}
+
+impl<'a> LoopOver<'a> {
+    fn new(over: &'a IrSpanned<ExprCompiled>) -> Option<LoopOver<'a>> {
+        let call = match over.node {
+            ExprCompiled::Call(ref call) => call,
+            _ => return None,
+        };
+        match call.node {
+            CallCompiled::Method(box (ref this, ref symbol, ref args)) if args.is_no_args() => {
+                Some(LoopOver::DictView(this, DictView::new(symbol.as_str())?))
+            }
+            CallCompiled::Call(box (ref fun, ref args))
+                if fun.as_value() == Some(Constants::new().fn_enumerate) =>
+            {
+                Some(LoopOver::Enumerate(args.one_pos()?))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// The two variables of a loop like `for k, v in ...`.
+fn unpack_pair(
+    var: &IrSpanned<AssignCompiledValue>,
+) -> Option<(
+    &IrSpanned<AssignCompiledValue>,
+    &IrSpanned<AssignCompiledValue>,
+)> {
+    match var.node {
+        AssignCompiledValue::Tuple(ref xs) => match xs.as_slice() {
+            [a, b] => Some((a, b)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Write a loop assigning each item of `over` to `var`, followed by `body`.
+///
+/// Loops over `d.keys()`, `d.values()`, `d.items()` or `enumerate(xs)` don't allocate
+/// the list, and if the items are unpacked into two variables, don't allocate the tuples.
+pub(crate) fn write_for(
+    var: &IrSpanned<AssignCompiledValue>,
+    over: &IrSpanned<ExprCompiled>,
+    span: FrozenFileSpan,
+    bc: &mut BcWriter,
+    body: impl FnOnce(&mut BcWriter),
+) {
+    match (LoopOver::new(over), unpack_pair(var)) {
+        (Some(LoopOver::DictView(this, DictView::Items)), Some((k, v))) => {
+            this.write_bc(bc);
+            bc.write_for_dict_items(over.span, |bc| {
+                k.write_bc(bc);
+                v.write_bc(bc);
+                body(bc);
+            });
+        }
+        (Some(LoopOver::DictView(this, view)), _) => {
+            this.write_bc(bc);
+            bc.write_for_dict_view(over.span, view, |bc| {
+                var.write_bc(bc);
+                body(bc);
+            });
+        }
+        (Some(LoopOver::Enumerate(xs)), Some((i, x))) => {
+            xs.write_bc(bc);
+            bc.write_for_enumerate(over.span, |bc| {
+                i.write_bc(bc);
+                x.write_bc(bc);
+                body(bc);
+            });
+        }
+        _ => {
+            over.write_bc(bc);
+            bc.write_for(span, |bc| {
+                var.write_bc(bc);
+                body(bc);
+            });
+        }
+    }
+}
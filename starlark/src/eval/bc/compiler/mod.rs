@@ -23,5 +23,6 @@ pub(crate) mod call;
 pub(crate) mod compr;
 pub(crate) mod def;
 pub(crate) mod expr;
+pub(crate) mod for_loop;
 pub(crate) mod if_compiler;
 pub(crate) mod stmt;
@@ -19,7 +19,10 @@ use crate::{
     eval::{
         bc::{
             bytecode::Bc,
-            compiler::if_compiler::{write_if_else, write_if_then},
+            compiler::{
+                for_loop::write_for,
+                if_compiler::{write_if_else, write_if_then},
+            },
             instr_impl::{
                 InstrBeforeStmt, InstrBreak, InstrContinue, InstrPossibleGc, InstrReturn,
                 InstrReturnConst,
@@ -131,11 +134,7 @@ impl IrSpanned<StmtCompiled> {
                 Self::write_if_else(c, t, f, compiler, bc);
             }
             StmtCompiled::For(box (ref assign, ref over, ref body)) => {
-                over.write_bc(bc);
-                bc.write_for(span, |bc| {
-                    assign.write_bc(bc);
-                    body.write_bc(compiler, bc);
-                });
+                write_for(assign, over, span, bc, |bc| body.write_bc(compiler, bc));
             }
             StmtCompiled::Break => {
                 bc.write_instr::<InstrBreak>(span, ());
@@ -154,7 +153,7 @@ impl StmtsCompiled {
         local_count: u32,
         heap: &FrozenHeap,
     ) -> Bc {
-        let mut bc = BcWriter::new(compiler.bc_profile, local_count, heap);
+        let mut bc = BcWriter::new(compiler.bc_profile, compiler.record_origins, local_count, heap);
         self.write_bc(compiler, &mut bc);
 
         // Small optimization: if the last statement is return,
@@ -32,7 +32,7 @@ use crate::{
             addr::{BcAddr, BcAddrOffset, BcPtrAddr},
             call::{BcCallArgsFull, BcCallArgsPos},
             instr::BcInstr,
-            instr_impl::InstrDefData,
+            instr_impl::{DictView, InstrDefData},
             opcode::{BcOpcode, BcOpcodeHandler},
             slow_arg::BcInstrSlowArg,
         },
@@ -455,6 +455,22 @@ impl BcInstrArg for KnownMethod {
     }
 }
 
+impl BcInstrArg for DictView {
+    fn fmt_append(param: &Self, _ip: BcAddr, f: &mut dyn Write) -> fmt::Result {
+        write!(f, " {}", param.name())
+    }
+
+    fn visit_jump_addr(_param: &Self, _consumer: &mut dyn FnMut(BcAddrOffset)) {}
+
+    fn pops_stack(_param: &Self) -> u32 {
+        0
+    }
+
+    fn pushes_stack(_param: &Self) -> u32 {
+        0
+    }
+}
+
 /// Instruction argument encodes how many values are popped
 /// off the stack manually in the instruction impl.
 #[derive(Copy, Clone, Dupe)]
@@ -17,13 +17,14 @@
 
 //! Instruction implementations.
 
-use std::{cmp::Ordering, marker, mem::MaybeUninit, ptr};
+use std::{cmp::Ordering, intrinsics::unlikely, marker, mem::MaybeUninit, ops::ControlFlow, ptr};
 
-use gazebo::coerce::coerce;
+use gazebo::{coerce::coerce, dupe::Dupe};
 
 use crate::{
     collections::{symbol_map::Symbol, Hashed, SmallMap},
     environment::slots::ModuleSlotId,
+    errors::Diagnostic,
     eval::{
         bc::{
             addr::{BcAddr, BcAddrOffset, BcPtrAddr},
@@ -50,13 +51,16 @@ use crate::{
     },
     values::{
         dict::Dict,
+        float::StarlarkFloat,
         function::NativeFunction,
+        int::IntError,
         known_methods::KnownMethod,
         list::List,
         string::interpolation::{format_one, percent_s_one},
         typed::FrozenValueTyped,
         typing::TypeCompiled,
         FrozenRef, FrozenStringValue, FrozenValue, Heap, StarlarkValue, StringValue, Value,
+        ValueError,
     },
 };
 
@@ -191,7 +195,6 @@ pub(crate) struct InstrLoadLocalCapturedImpl;
 pub(crate) struct InstrLoadModuleImpl;
 pub(crate) struct InstrStoreLocalImpl;
 pub(crate) struct InstrStoreLocalCapturedImpl;
-pub(crate) struct InstrStoreModuleImpl;
 pub(crate) struct InstrStoreModuleAndExportImpl;
 pub(crate) struct InstrUnpackImpl;
 pub(crate) struct InstrArrayIndexImpl;
@@ -209,7 +212,6 @@ pub(crate) type InstrLoadLocalCaptured = InstrNoFlow<InstrLoadLocalCapturedImpl>
 pub(crate) type InstrLoadModule = InstrNoFlow<InstrLoadModuleImpl>;
 pub(crate) type InstrStoreLocal = InstrNoFlow<InstrStoreLocalImpl>;
 pub(crate) type InstrStoreLocalCaptured = InstrNoFlow<InstrStoreLocalCapturedImpl>;
-pub(crate) type InstrStoreModule = InstrNoFlow<InstrStoreModuleImpl>;
 pub(crate) type InstrStoreModuleAndExport = InstrNoFlow<InstrStoreModuleAndExportImpl>;
 pub(crate) type InstrUnpack = InstrNoFlow<InstrUnpackImpl>;
 pub(crate) type InstrArrayIndex = InstrNoFlow<InstrArrayIndexImpl>;
@@ -399,23 +401,6 @@ impl InstrNoFlowImpl for InstrStoreModuleAndExportImpl {
     }
 }
 
-impl InstrNoFlowImpl for InstrStoreModuleImpl {
-    type Pop<'v> = Value<'v>;
-    type Push<'v> = ();
-    type Arg = ModuleSlotId;
-
-    fn run_with_args<'v>(
-        eval: &mut Evaluator<'v, '_>,
-        _stack: &mut BcStackPtr<'v, '_>,
-        _ip: BcPtrAddr,
-        slot: &ModuleSlotId,
-        v: Value<'v>,
-    ) -> anyhow::Result<()> {
-        eval.set_slot_module(*slot, v);
-        Ok(())
-    }
-}
-
 impl InstrNoFlowImpl for InstrUnpackImpl {
     type Pop<'v> = Value<'v>;
     type Push<'v> = ();
@@ -493,13 +478,14 @@ impl InstrNoFlowImpl for InstrSetArrayIndexImpl {
 
     #[inline(always)]
     fn run_with_args<'v>(
-        _eval: &mut Evaluator<'v, '_>,
+        eval: &mut Evaluator<'v, '_>,
         _stack: &mut BcStackPtr<'v, '_>,
         _ip: BcPtrAddr,
         (): &(),
         [value, array, index]: [Value<'v>; 3],
     ) -> anyhow::Result<()> {
-        array.set_at(index, value)
+        eval.heap()
+            .charge_growth(array, || array.set_at(index, value))
     }
 }
 
@@ -510,13 +496,14 @@ impl InstrNoFlowImpl for InstrArrayIndexSetImpl {
 
     #[inline(always)]
     fn run_with_args<'v>(
-        _eval: &mut Evaluator<'v, '_>,
+        eval: &mut Evaluator<'v, '_>,
         _stack: &mut BcStackPtr<'v, '_>,
         _ip: BcPtrAddr,
         (): &(),
         [array, index, value]: [Value<'v>; 3],
     ) -> anyhow::Result<()> {
-        array.set_at(index, value)
+        eval.heap()
+            .charge_growth(array, || array.set_at(index, value))
     }
 }
 
@@ -533,7 +520,7 @@ impl InstrNoFlowImpl for InstrObjectFieldImpl {
         symbol: &Symbol,
         object: Value<'v>,
     ) -> anyhow::Result<Value<'v>> {
-        get_attr_hashed_bind(object, symbol, eval.heap())
+        get_attr_hashed_bind(object, symbol, eval.heap(), eval.extra_methods)
     }
 }
 
@@ -649,8 +636,8 @@ impl InstrUnOpImpl for InstrMinusImpl {
 
 impl InstrUnOpImpl for InstrBitNotImpl {
     #[inline(always)]
-    fn eval<'v>(v: Value<'v>, _heap: &'v Heap) -> anyhow::Result<Value<'v>> {
-        Ok(Value::new_int(!v.to_int()?))
+    fn eval<'v>(v: Value<'v>, heap: &'v Heap) -> anyhow::Result<Value<'v>> {
+        v.bit_not(heap)
     }
 }
 
@@ -667,6 +654,48 @@ pub(crate) struct InstrUnOpWrapper<I: InstrUnOpImpl>(marker::PhantomData<I>);
 pub(crate) type InstrBinOp<I> = InstrNoFlow<InstrBinOpWrapper<I>>;
 pub(crate) type InstrUnOp<I> = InstrNoFlow<InstrUnOpWrapper<I>>;
 
+/// Point an unsupported operation error at the left operand, and label the right operand,
+/// if the instruction was written with the spans of both operands.
+/// Other errors, e.g. division by zero, are about the operation, so keep its span.
+#[cold]
+#[inline(never)]
+fn bin_op_error(e: anyhow::Error, ip: BcPtrAddr, rhs: Value) -> anyhow::Error {
+    if !matches!(
+        e.downcast_ref::<ValueError>(),
+        Some(
+            ValueError::OperationNotSupported { .. }
+                | ValueError::OperationNotSupportedBinary { .. }
+        )
+    ) {
+        return e;
+    }
+    match Bc::slow_arg_at_ptr(ip).spans.as_slice() {
+        [lhs, rhs_span] => Diagnostic::modify(e, |d| {
+            d.set_span(lhs.span, &lhs.file);
+            d.add_secondary_span(
+                rhs_span.span,
+                &rhs_span.file,
+                format!("right operand of type `{}`", rhs.get_type()),
+            );
+        }),
+        _ => e,
+    }
+}
+
+/// The result of an `int` operation which overflowed as a `float`, if the running code enables
+/// [`enable_int_overflow_to_float`](crate::syntax::Dialect::enable_int_overflow_to_float).
+#[cold]
+#[inline(never)]
+fn int_overflow_to_float<'v>(eval: &Evaluator<'v, '_>, e: &anyhow::Error) -> Option<Value<'v>> {
+    if !eval.def_info.int_overflow_to_float {
+        return None;
+    }
+    match e.downcast_ref::<IntError>() {
+        Some(IntError::ArithOverflow(_, res)) => Some(eval.heap().alloc(StarlarkFloat(*res))),
+        _ => None,
+    }
+}
+
 impl<I: InstrBinOpImpl> InstrNoFlowImpl for InstrBinOpWrapper<I> {
     type Pop<'v> = [Value<'v>; 2];
     type Push<'v> = Value<'v>;
@@ -676,11 +705,17 @@ impl<I: InstrBinOpImpl> InstrNoFlowImpl for InstrBinOpWrapper<I> {
     fn run_with_args<'v>(
         eval: &mut Evaluator<'v, '_>,
         _stack: &mut BcStackPtr<'v, '_>,
-        _ip: BcPtrAddr,
+        ip: BcPtrAddr,
         (): &(),
         [v0, v1]: [Value<'v>; 2],
     ) -> anyhow::Result<Value<'v>> {
-        I::eval(v0, v1, eval.heap())
+        match I::eval(v0, v1, eval.heap()) {
+            Ok(v) => Ok(v),
+            Err(e) => match int_overflow_to_float(eval, &e) {
+                Some(v) => Ok(v),
+                None => Err(bin_op_error(e, ip, v1)),
+            },
+        }
     }
 }
 
@@ -697,7 +732,10 @@ impl<I: InstrUnOpImpl> InstrNoFlowImpl for InstrUnOpWrapper<I> {
         (): &(),
         v: Value<'v>,
     ) -> anyhow::Result<Value<'v>> {
-        I::eval(v, eval.heap())
+        match I::eval(v, eval.heap()) {
+            Ok(v) => Ok(v),
+            Err(e) => int_overflow_to_float(eval, &e).ok_or(e),
+        }
     }
 }
 
@@ -899,7 +937,13 @@ pub(crate) struct InstrCompare<I: InstrCompareImpl>(marker::PhantomData<I>);
 impl<I: InstrCompareImpl> InstrBinOpImpl for InstrCompare<I> {
     #[inline(always)]
     fn eval<'v>(v0: Value<'v>, v1: Value<'v>, _heap: &'v Heap) -> anyhow::Result<Value<'v>> {
-        Ok(Value::new_bool(I::eval_compare(v0.compare(v1)?)))
+        // Most comparisons are between ints, so skip the dynamic dispatch for them.
+        // Anything else, including mixed int/float, goes through `compare`.
+        let ordering = match (v0.unpack_int(), v1.unpack_int()) {
+            (Some(i0), Some(i1)) => i0.cmp(&i1),
+            _ => v0.compare(v1)?,
+        };
+        Ok(Value::new_bool(I::eval_compare(ordering)))
     }
 }
 
@@ -1162,6 +1206,28 @@ impl InstrNoFlowImpl for InstrDictNewImpl {
     }
 }
 
+/// Record where the list or dict on top of the stack was created.
+/// Only written if value provenance was enabled when the code was compiled.
+pub(crate) struct InstrRecordOriginImpl;
+pub(crate) type InstrRecordOrigin = InstrNoFlow<InstrRecordOriginImpl>;
+
+impl InstrNoFlowImpl for InstrRecordOriginImpl {
+    type Pop<'v> = Value<'v>;
+    type Push<'v> = Value<'v>;
+    type Arg = ();
+
+    fn run_with_args<'v>(
+        eval: &mut Evaluator<'v, '_>,
+        _stack: &mut BcStackPtr<'v, '_>,
+        ip: BcPtrAddr,
+        (): &(),
+        value: Value<'v>,
+    ) -> anyhow::Result<Value<'v>> {
+        eval.value_provenance.record(value, Bc::slow_arg_at_ptr(ip).span);
+        Ok(value)
+    }
+}
+
 pub(crate) struct InstrComprListAppend;
 pub(crate) struct InstrComprDictInsert;
 
@@ -1193,7 +1259,7 @@ impl BcInstr for InstrComprDictInsert {
 
     #[inline(always)]
     fn run<'v, 'b>(
-        _eval: &mut Evaluator<'v, '_>,
+        eval: &mut Evaluator<'v, '_>,
         stack: &mut BcStackPtr<'v, '_>,
         _ip: BcPtrAddr<'b>,
         (): &(),
@@ -1204,9 +1270,11 @@ impl BcInstr for InstrComprDictInsert {
             Ok(key) => key,
             Err(e) => return InstrControl::Err(e),
         };
-        // SAFETY: in generated bytecode this slot can be only occupied by a mutable dict.
-        let mut dict = unsafe { Dict::from_value_unchecked_mut(dict) };
-        dict.insert_hashed(key, value);
+        eval.heap().charge_growth(dict, || {
+            // SAFETY: in generated bytecode this slot can be only occupied by a mutable dict.
+            let mut dict = unsafe { Dict::from_value_unchecked_mut(dict) };
+            dict.insert_hashed(key, value);
+        });
         InstrControl::LoopContinue
     }
 }
@@ -1274,9 +1342,153 @@ impl BcInstr for InstrIfNotBr {
 }
 
 pub(crate) struct InstrForLoop;
+pub(crate) struct InstrForLoopDictView;
+pub(crate) struct InstrForLoopDictItems;
+pub(crate) struct InstrForLoopEnumerate;
 pub(crate) struct InstrBreak;
 pub(crate) struct InstrContinue;
 
+enum LoopResult<'v> {
+    Ok,
+    Return(Value<'v>),
+    Err(EvalException),
+}
+
+/// Run the loop body for an item already pushed to the stack,
+/// and tell whether to proceed to the next item.
+#[inline(always)]
+fn run_loop_body<'v>(
+    eval: &mut Evaluator<'v, '_>,
+    stack: &mut BcStackPtr<'v, '_>,
+    loop_start: BcPtrAddr,
+    result: &mut LoopResult<'v>,
+) -> ControlFlow<()> {
+    // A single statement can loop for long enough to allocate far too much,
+    // e.g. a list comprehension, so stop as soon as the heap limit is crossed.
+    if unlikely(eval.heap().max_bytes_exceeded()) {
+        if let Err(e) = eval.heap().check_max_bytes(0) {
+            *result = LoopResult::Err(EvalException(e));
+            return ControlFlow::Break(());
+        }
+    }
+    // Likewise count iterations towards the instruction limit,
+    // since a comprehension has no statements to count.
+    if unlikely(eval.before_stmt.instruction_limit.is_some()) {
+        if let Err(e) = eval.before_stmt.count_instruction() {
+            *result = LoopResult::Err(EvalException(e));
+            return ControlFlow::Break(());
+        }
+    }
+    match run_block(eval, stack, loop_start) {
+        RunBlockResult::Continue => return ControlFlow::Continue(()),
+        RunBlockResult::Break => {}
+        RunBlockResult::Return(v) => *result = LoopResult::Return(v),
+        RunBlockResult::Err(e) => *result = LoopResult::Err(e),
+    }
+    ControlFlow::Break(())
+}
+
+/// Run the loop body for each of `items`, which are pushed to the stack with `push`.
+///
+/// The items are copied out of the collection before the loop starts,
+/// so the body is free to modify the collection, as it would be with a list
+/// returned by `dict.items()` or `enumerate()`.
+fn run_loop_over<'v, T: Copy>(
+    eval: &mut Evaluator<'v, '_>,
+    stack: &mut BcStackPtr<'v, '_>,
+    loop_start: BcPtrAddr,
+    items: &[T],
+    push: impl Fn(&mut BcStackPtr<'v, '_>, usize, T) -> anyhow::Result<()>,
+) -> (anyhow::Result<()>, LoopResult<'v>) {
+    let mut result = LoopResult::Ok;
+    for (i, item) in items.iter().enumerate() {
+        if let Err(e) = push(stack, i, *item) {
+            return (Err(e), result);
+        }
+        if run_loop_body(eval, stack, loop_start, &mut result).is_break() {
+            break;
+        }
+    }
+    (Ok(()), result)
+}
+
+/// Pop `this` off the stack and run the loop body for each of its entries,
+/// pushed to the stack with `push`, or if `this` is not a dict, for each element
+/// of `this.keys()`, `this.values()` or `this.items()`, pushed with `push_other`.
+fn run_loop_over_dict<'v>(
+    eval: &mut Evaluator<'v, '_>,
+    stack: &mut BcStackPtr<'v, '_>,
+    loop_start: BcPtrAddr,
+    view: DictView,
+    span: FrozenRef<'static, FrozenFileSpan>,
+    push: impl Fn(&mut BcStackPtr<'v, '_>, usize, (Value<'v>, Value<'v>)) -> anyhow::Result<()>,
+    push_other: impl Fn(
+        &mut Evaluator<'v, '_>,
+        &mut BcStackPtr<'v, '_>,
+        Value<'v>,
+    ) -> anyhow::Result<()>,
+) -> (anyhow::Result<()>, LoopResult<'v>) {
+    let this = stack.pop();
+    match Dict::from_value(this) {
+        Some(dict) => eval.alloca_init(
+            dict.len(),
+            || (Value::new_none(), Value::new_none()),
+            |entries, eval| {
+                for (entry, kv) in entries.iter_mut().zip(dict.iter()) {
+                    *entry = kv;
+                }
+                // Release the dict so the body can modify it.
+                drop(dict);
+                run_loop_over(eval, stack, loop_start, entries, push)
+            },
+        ),
+        None => {
+            let arguments = Arguments::default();
+            let collection =
+                match call_method_common(eval, this, &Symbol::new(view.name()), &arguments, span) {
+                    Ok(collection) => collection,
+                    Err(e) => return (Err(e), LoopResult::Ok),
+                };
+            let mut result = LoopResult::Ok;
+            let mut push_err = None;
+            let iter_ret = collection.for_each_element(eval.heap(), |item| {
+                if let Err(e) = push_other(eval, stack, item) {
+                    push_err = Some(e);
+                    return ControlFlow::Break(());
+                }
+                run_loop_body(eval, stack, loop_start, &mut result)
+            });
+            match push_err {
+                Some(e) => (Err(e), result),
+                None => (iter_ret, result),
+            }
+        }
+    }
+}
+
+/// Where to go once the loop is over.
+#[inline(always)]
+fn finish_loop<'v, 'b>(
+    stack: &BcStackPtr<'v, '_>,
+    ss: usize,
+    ip: BcPtrAddr<'b>,
+    loop_end: BcAddrOffset,
+    (iter_ret, result): (anyhow::Result<()>, LoopResult<'v>),
+) -> InstrControl<'v, 'b> {
+    match (iter_ret, result) {
+        (Ok(()), LoopResult::Ok) => {
+            debug_assert!(stack.stack_offset() + 1 == ss);
+            InstrControl::Next(ip.add_rel(loop_end))
+        }
+        (Ok(()), LoopResult::Return(v)) => {
+            debug_assert!(stack.stack_offset() + 1 == ss);
+            InstrControl::Return(v)
+        }
+        (Ok(()), LoopResult::Err(e)) => InstrControl::Err(e.0),
+        (Err(e), _) => InstrControl::Err(e),
+    }
+}
+
 impl BcInstr for InstrForLoop {
     type Pop<'v> = Value<'v>;
     type Push<'v> = Value<'v>;
@@ -1292,41 +1504,146 @@ impl BcInstr for InstrForLoop {
 
         let collection = stack.pop();
 
-        enum LoopResult<'v> {
-            Ok,
-            Return(Value<'v>),
-            Err(EvalException),
+        let loop_start = ip.add_instr::<Self>();
+        let mut result = LoopResult::Ok;
+        let iter_ret = collection.for_each_element(eval.heap(), |item| {
+            stack.push(item);
+            debug_assert!(stack.stack_offset() == ss);
+            run_loop_body(eval, stack, loop_start, &mut result)
+        });
+        finish_loop(stack, ss, ip, *loop_end, (iter_ret, result))
+    }
+}
+
+/// Which of the dict methods returning a list a loop is over.
+#[derive(Copy, Clone, Dupe, Debug, Eq, PartialEq)]
+pub(crate) enum DictView {
+    Keys,
+    Values,
+    Items,
+}
+
+impl DictView {
+    pub(crate) fn new(name: &str) -> Option<DictView> {
+        match name {
+            "keys" => Some(DictView::Keys),
+            "values" => Some(DictView::Values),
+            "items" => Some(DictView::Items),
+            _ => None,
         }
+    }
 
-        let iter_ret = collection.with_iterator(eval.heap(), |iter| {
-            let loop_start = ip.add_instr::<Self>();
-            for item in iter {
-                stack.push(item);
-                debug_assert!(stack.stack_offset() == ss);
-                match run_block(eval, stack, loop_start) {
-                    RunBlockResult::Continue => {}
-                    RunBlockResult::Break => return LoopResult::Ok,
-                    RunBlockResult::Return(v) => return LoopResult::Return(v),
-                    RunBlockResult::Err(e) => return LoopResult::Err(e),
-                }
-            }
-            LoopResult::Ok
-        });
-        match iter_ret {
-            Ok(LoopResult::Ok) => {
-                debug_assert!(stack.stack_offset() + 1 == ss);
-                InstrControl::Next(ip.add_rel(*loop_end))
-            }
-            Ok(LoopResult::Return(v)) => {
-                debug_assert!(stack.stack_offset() + 1 == ss);
-                InstrControl::Return(v)
-            }
-            Ok(LoopResult::Err(e)) => InstrControl::Err(e.0),
-            Err(e) => InstrControl::Err(e),
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            DictView::Keys => "keys",
+            DictView::Values => "values",
+            DictView::Items => "items",
         }
     }
 }
 
+/// `for x in d.keys()`, `d.values()` or `d.items()`, without allocating the list.
+impl BcInstr for InstrForLoopDictView {
+    type Pop<'v> = Value<'v>;
+    type Push<'v> = Value<'v>;
+    type Arg = (BcAddrOffset, DictView, FrozenRef<'static, FrozenFileSpan>);
+
+    fn run<'v, 'b>(
+        eval: &mut Evaluator<'v, '_>,
+        stack: &mut BcStackPtr<'v, '_>,
+        ip: BcPtrAddr<'b>,
+        (loop_end, view, span): &Self::Arg,
+    ) -> InstrControl<'v, 'b> {
+        let ss = stack.stack_offset();
+        let loop_start = ip.add_instr::<Self>();
+        let heap = eval.heap();
+        let view = *view;
+        let res = run_loop_over_dict(
+            eval,
+            stack,
+            loop_start,
+            view,
+            *span,
+            |stack, _, (k, v)| {
+                stack.push(match view {
+                    DictView::Keys => k,
+                    DictView::Values => v,
+                    DictView::Items => heap.alloc((k, v)),
+                });
+                debug_assert!(stack.stack_offset() == ss);
+                Ok(())
+            },
+            |_, stack, item| {
+                stack.push(item);
+                Ok(())
+            },
+        );
+        finish_loop(stack, ss, ip, *loop_end, res)
+    }
+}
+
+/// `for k, v in d.items()`, without allocating the list or the tuples.
+impl BcInstr for InstrForLoopDictItems {
+    type Pop<'v> = Value<'v>;
+    type Push<'v> = [Value<'v>; 2];
+    type Arg = (BcAddrOffset, FrozenRef<'static, FrozenFileSpan>);
+
+    fn run<'v, 'b>(
+        eval: &mut Evaluator<'v, '_>,
+        stack: &mut BcStackPtr<'v, '_>,
+        ip: BcPtrAddr<'b>,
+        (loop_end, span): &Self::Arg,
+    ) -> InstrControl<'v, 'b> {
+        let ss = stack.stack_offset();
+        let loop_start = ip.add_instr::<Self>();
+        let res = run_loop_over_dict(
+            eval,
+            stack,
+            loop_start,
+            DictView::Items,
+            *span,
+            |stack, _, (k, v)| {
+                // Same order as `Unpack`, so the first variable is assigned the key.
+                stack.push_array([v, k]);
+                debug_assert!(stack.stack_offset() == ss + 1);
+                Ok(())
+            },
+            |eval, stack, item| {
+                InstrUnpackImpl::run_with_args(eval, stack, ip, &ArgPushesStack(2), item)
+            },
+        );
+        finish_loop(stack, ss, ip, *loop_end, res)
+    }
+}
+
+/// `for i, x in enumerate(xs)`, without allocating the list or the tuples.
+impl BcInstr for InstrForLoopEnumerate {
+    type Pop<'v> = Value<'v>;
+    type Push<'v> = [Value<'v>; 2];
+    type Arg = BcAddrOffset;
+
+    fn run<'v, 'b>(
+        eval: &mut Evaluator<'v, '_>,
+        stack: &mut BcStackPtr<'v, '_>,
+        ip: BcPtrAddr<'b>,
+        loop_end: &BcAddrOffset,
+    ) -> InstrControl<'v, 'b> {
+        let ss = stack.stack_offset();
+        let collection = stack.pop();
+        let loop_start = ip.add_instr::<Self>();
+        let res = match collection.iterate_collect(eval.heap()) {
+            Ok(items) => run_loop_over(eval, stack, loop_start, &items, |stack, i, x| {
+                let i = i32::try_from(i).map_err(|_| ValueError::IntegerOverflow)?;
+                stack.push_array([x, Value::new_int(i)]);
+                debug_assert!(stack.stack_offset() == ss + 1);
+                Ok(())
+            }),
+            Err(e) => (Err(e), LoopResult::Ok),
+        };
+        finish_loop(stack, ss, ip, *loop_end, res)
+    }
+}
+
 impl BcInstr for InstrBreak {
     type Pop<'v> = ();
     type Push<'v> = ();
@@ -1624,7 +1941,7 @@ fn call_method_common<'v>(
     span: FrozenRef<'static, FrozenFileSpan>,
 ) -> anyhow::Result<Value<'v>> {
     // TODO: wrong span: should be span of `object.method`, not of the whole expression
-    let method = get_attr_hashed_raw(this, symbol, eval.heap())?;
+    let method = get_attr_hashed_raw(this, symbol, eval.heap(), eval.extra_methods)?;
     match method {
         MemberOrValue::Member(member) => {
             member.to_value().invoke_method(this, span, arguments, eval)
@@ -1743,8 +2060,7 @@ impl InstrNoFlowImpl for InstrBeforeStmtImpl {
         span: &Self::Arg,
         (): (),
     ) -> anyhow::Result<()> {
-        before_stmt(*span, eval);
-        Ok(())
+        before_stmt(*span, eval)
     }
 }
 
@@ -18,7 +18,7 @@
 //! Instructions serialized in byte array.
 
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     convert::TryInto,
     fmt,
     fmt::{Display, Formatter, Write},
@@ -30,7 +30,9 @@ use either::Either;
 use crate::eval::bc::{
     addr::{BcAddr, BcAddrOffset, BcPtrAddr},
     instr::BcInstr,
-    instr_impl::{InstrEnd, InstrForLoop},
+    instr_impl::{
+        InstrEnd, InstrForLoop, InstrForLoopDictItems, InstrForLoopDictView, InstrForLoopEnumerate,
+    },
     opcode::{BcOpcode, BcOpcodeHandler},
     repr::{BcInstrHeader, BcInstrRepr, BC_INSTR_ALIGN},
     slow_arg::BcInstrSlowArg,
@@ -230,9 +232,15 @@ impl BcInstrs {
             if newline {
                 writeln!(f)?;
             }
-            if opcode == BcOpcode::ForLoop {
-                let for_loop = ptr.get_instr::<InstrForLoop>();
-                loop_ends.push(ip.offset(for_loop.arg));
+            let loop_end = match opcode {
+                BcOpcode::ForLoop => Some(ptr.get_instr::<InstrForLoop>().arg),
+                BcOpcode::ForLoopDictView => Some(ptr.get_instr::<InstrForLoopDictView>().arg.0),
+                BcOpcode::ForLoopDictItems => Some(ptr.get_instr::<InstrForLoopDictItems>().arg.0),
+                BcOpcode::ForLoopEnumerate => Some(ptr.get_instr::<InstrForLoopEnumerate>().arg),
+                _ => None,
+            };
+            if let Some(loop_end) = loop_end {
+                loop_ends.push(ip.offset(loop_end));
             }
         }
         Ok(())
@@ -243,6 +251,49 @@ impl BcInstrs {
         self.fmt_impl(&mut w, true).unwrap();
         w
     }
+
+    /// One instruction per line, numbered by position rather than by address,
+    /// with jump targets resolved to those positions, and the source span
+    /// for instructions which can fail.
+    pub(crate) fn dump_bytecode(&self) -> String {
+        let instrs: Vec<(BcPtrAddr, BcAddr)> = self.iter().collect();
+        let index: HashMap<BcAddr, usize> = instrs
+            .iter()
+            .enumerate()
+            .map(|(i, (_, ip))| (*ip, i))
+            .collect();
+        // Bytecode always finishes with `End`, which holds the spans.
+        let (end, _) = instrs.last().unwrap();
+        let spans: HashMap<BcAddr, &BcInstrSlowArg> = end
+            .get_instr::<InstrEnd>()
+            .arg
+            .1
+            .iter()
+            .map(|(ip, arg)| (*ip, arg))
+            .collect();
+
+        let mut w = String::new();
+        for (i, (ptr, ip)) in instrs.iter().enumerate() {
+            let opcode = ptr.get_opcode();
+            // Jump arguments are printed as addresses, so show the address too.
+            write!(w, "{} {}: {:?}", i, ip, opcode).unwrap();
+            if opcode != BcOpcode::End {
+                opcode.fmt_append_arg(*ptr, *ip, &mut w).unwrap();
+            }
+            let mut targets = Vec::new();
+            opcode.visit_jump_addr(*ptr, &mut |offset| {
+                targets.push(index[&ip.offset(offset)].to_string())
+            });
+            if !targets.is_empty() {
+                write!(w, " -> {}", targets.join(", ")).unwrap();
+            }
+            if let Some(arg) = spans.get(ip) {
+                write!(w, " # {}", arg.span).unwrap();
+            }
+            writeln!(w).unwrap();
+        }
+        w
+    }
 }
 
 impl Display for BcInstrs {
@@ -43,7 +43,6 @@ pub(crate) enum BcOpcode {
     LoadModule,
     StoreLocal,
     StoreLocalCaptured,
-    StoreModule,
     StoreModuleAndExport,
     Unpack,
     ArrayIndex,
@@ -92,12 +91,16 @@ pub(crate) enum BcOpcode {
     DictNPop,
     DictOfConsts,
     DictConstKeys,
+    RecordOrigin,
     ComprListAppend,
     ComprDictInsert,
     Br,
     IfBr,
     IfNotBr,
     ForLoop,
+    ForLoopDictView,
+    ForLoopDictItems,
+    ForLoopEnumerate,
     Break,
     Continue,
     Return,
@@ -17,7 +17,7 @@
 
 //! Bytecode writer.
 
-use std::{cmp, mem};
+use std::{cmp, mem, ptr};
 
 use crate::{
     eval::{
@@ -26,10 +26,11 @@ use crate::{
             bytecode::Bc,
             instr::BcInstr,
             instr_impl::{
-                InstrBr, InstrConst, InstrConst2, InstrConst3, InstrConst4, InstrContinue,
-                InstrForLoop, InstrIfBr, InstrIfNotBr, InstrLoadLocal, InstrLoadLocal2,
+                DictView, InstrBr, InstrConst, InstrConst2, InstrConst3, InstrConst4,
+                InstrContinue, InstrForLoop, InstrForLoopDictItems, InstrForLoopDictView,
+                InstrForLoopEnumerate, InstrIfBr, InstrIfNotBr, InstrLoadLocal, InstrLoadLocal2,
                 InstrLoadLocal3, InstrLoadLocal4, InstrLoadLocalAndConst, InstrLoadLocalCaptured,
-                InstrProfileBc, InstrStoreLocal, InstrStoreLocalCaptured,
+                InstrProfileBc, InstrRecordOrigin, InstrStoreLocal, InstrStoreLocalCaptured,
             },
             instrs::{BcInstrsWriter, PatchAddr},
             opcode::BcOpcode,
@@ -45,6 +46,8 @@ use crate::{
 pub(crate) struct BcWriter<'f> {
     /// Insert bytecode profiling instructions.
     profile: bool,
+    /// Insert instructions recording where lists and dicts are created.
+    record_origins: bool,
 
     /// Serialized instructions.
     instrs: BcInstrsWriter,
@@ -67,9 +70,15 @@ pub(crate) struct BcWriter<'f> {
 
 impl<'f> BcWriter<'f> {
     /// Empty.
-    pub(crate) fn new(profile: bool, local_count: u32, heap: &'f FrozenHeap) -> BcWriter<'f> {
+    pub(crate) fn new(
+        profile: bool,
+        record_origins: bool,
+        local_count: u32,
+        heap: &'f FrozenHeap,
+    ) -> BcWriter<'f> {
         BcWriter {
             profile,
+            record_origins,
             instrs: BcInstrsWriter::new(),
             slow_args: Vec::new(),
             stack_size: 0,
@@ -85,6 +94,7 @@ impl<'f> BcWriter<'f> {
     pub(crate) fn finish(self) -> Bc {
         let BcWriter {
             profile: has_before_instr,
+            record_origins,
             instrs,
             slow_args: spans,
             stack_size,
@@ -95,6 +105,7 @@ impl<'f> BcWriter<'f> {
             heap,
         } = self;
         let _ = has_before_instr;
+        let _ = record_origins;
         let _ = heap;
         assert!(queued_locals.is_empty());
         assert!(queued_consts.is_empty());
@@ -284,6 +295,14 @@ impl<'f> BcWriter<'f> {
         );
     }
 
+    /// Record where the list or dict just written to the stack was created,
+    /// if value provenance is enabled.
+    pub(crate) fn write_record_origin(&mut self, span: FrozenFileSpan) {
+        if self.record_origins {
+            self.write_instr::<InstrRecordOrigin>(span, ());
+        }
+    }
+
     /// Write load constant instruction.
     pub(crate) fn write_const(&mut self, span: FrozenFileSpan, value: FrozenValue) {
         // Do not write it yet, queue it, so we could batch it.
@@ -367,20 +386,75 @@ impl<'f> BcWriter<'f> {
         self.patch_addr(patch_addr);
     }
 
-    /// Write for loop.
-    pub(crate) fn write_for(&mut self, span: FrozenFileSpan, body: impl FnOnce(&mut Self)) {
-        let arg = self.write_instr_ret_arg::<InstrForLoop>(span, BcAddrOffset::FORWARD);
-        let end_patch = self.instrs.addr_to_patch(arg);
+    /// Write a loop instruction, whose argument has the address of the end of the loop
+    /// at `loop_end`, followed by the loop body.
+    fn write_loop<I: BcInstr>(
+        &mut self,
+        span: FrozenFileSpan,
+        arg: I::Arg,
+        loop_end: impl FnOnce(*const I::Arg) -> *const BcAddrOffset,
+        body: impl FnOnce(&mut Self),
+    ) {
+        let npushs = I::npushs(&arg);
+        let (addr, arg) = self.write_instr_ret_arg::<I>(span, arg);
+        let end_patch = self.instrs.addr_to_patch((addr, loop_end(arg)));
         let ss = self.stack_size();
         body(self);
         assert!(
-            self.stack_size() + 1 == ss,
+            self.stack_size() + npushs == ss,
             "Loop body must consume stack variable"
         );
         self.write_instr::<InstrContinue>(span, ());
         self.patch_addr(end_patch);
     }
 
+    /// Write for loop.
+    pub(crate) fn write_for(&mut self, span: FrozenFileSpan, body: impl FnOnce(&mut Self)) {
+        self.write_loop::<InstrForLoop>(span, BcAddrOffset::FORWARD, |arg| arg, body);
+    }
+
+    /// Write for loop over `d.keys()`, `d.values()` or `d.items()`, with `d` on the stack.
+    pub(crate) fn write_for_dict_view(
+        &mut self,
+        span: FrozenFileSpan,
+        view: DictView,
+        body: impl FnOnce(&mut Self),
+    ) {
+        let file_span = self.alloc_file_span(span);
+        self.write_loop::<InstrForLoopDictView>(
+            span,
+            (BcAddrOffset::FORWARD, view, file_span),
+            |arg| unsafe { ptr::addr_of!((*arg).0) },
+            body,
+        );
+    }
+
+    /// Write for loop over `d.items()`, with `d` on the stack,
+    /// where the body is given the key and the value on the stack.
+    pub(crate) fn write_for_dict_items(
+        &mut self,
+        span: FrozenFileSpan,
+        body: impl FnOnce(&mut Self),
+    ) {
+        let file_span = self.alloc_file_span(span);
+        self.write_loop::<InstrForLoopDictItems>(
+            span,
+            (BcAddrOffset::FORWARD, file_span),
+            |arg| unsafe { ptr::addr_of!((*arg).0) },
+            body,
+        );
+    }
+
+    /// Write for loop over `enumerate(xs)`, with `xs` on the stack,
+    /// where the body is given the index and the element on the stack.
+    pub(crate) fn write_for_enumerate(
+        &mut self,
+        span: FrozenFileSpan,
+        body: impl FnOnce(&mut Self),
+    ) {
+        self.write_loop::<InstrForLoopEnumerate>(span, BcAddrOffset::FORWARD, |arg| arg, body);
+    }
+
     pub(crate) fn stack_add(&mut self, add: u32) {
         self.stack_size += add;
         self.max_stack_size = cmp::max(self.max_stack_size, self.stack_size);
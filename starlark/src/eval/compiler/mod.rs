@@ -41,6 +41,7 @@ pub(crate) struct EvalException(pub(crate) anyhow::Error);
 #[cold]
 #[inline(never)]
 fn add_span_to_error(e: anyhow::Error, span: FrozenFileSpan, eval: &Evaluator) -> anyhow::Error {
+    let e = eval.value_provenance.add_unpack_origin(e);
     Diagnostic::modify(e, |d: &mut Diagnostic| {
         d.set_span(span.span, &span.file);
         d.set_call_stack(|| eval.call_stack.to_diagnostic_frames());
@@ -79,6 +80,8 @@ pub(crate) struct Compiler<'v, 'a, 'e> {
     pub(crate) constants: Constants,
     pub(crate) has_before_stmt: bool,
     pub(crate) bc_profile: bool,
+    pub(crate) record_origins: bool,
+    pub(crate) int_overflow_to_float: bool,
 }
 
 impl Compiler<'_, '_, '_> {
@@ -94,6 +97,7 @@ impl Compiler<'_, '_, '_> {
 
 #[derive(Clone, Copy, Dupe)]
 pub(crate) struct Constants {
+    pub(crate) fn_enumerate: FrozenValue,
     pub(crate) fn_len: FrozenValue,
     pub(crate) fn_type: FrozenValue,
 }
@@ -103,6 +107,7 @@ impl Constants {
         static RES: Lazy<Constants> = Lazy::new(|| {
             let g = Globals::standard();
             Constants {
+                fn_enumerate: g.get_frozen("enumerate").unwrap(),
                 fn_len: g.get_frozen("len").unwrap(),
                 fn_type: g.get_frozen("type").unwrap(),
             }
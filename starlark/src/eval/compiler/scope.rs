@@ -47,6 +47,9 @@ pub(crate) struct Scope<'a> {
     unscopes: Vec<Unscope>,
     codemap: FrozenRef<'static, CodeMap>,
     globals: FrozenRef<'static, Globals>,
+    /// Resolve unknown identifiers in `def` and `lambda` to new module variables,
+    /// which may be assigned by later evaluation of the same module.
+    forward_module_refs: bool,
     pub(crate) errors: Vec<anyhow::Error>,
 }
 
@@ -74,6 +77,12 @@ pub(crate) struct ScopeNames {
     /// Slots to copy from the parent. (index in parent, index in child).
     /// Module-level identifiers are not copied over, to avoid excess copying.
     pub parent: Vec<(LocalSlotId, LocalSlotId)>,
+    /// Module variables referenced from this scope, if it is a `def` or `lambda`,
+    /// in the order they are first referenced.
+    pub module_captured: Vec<(String, ModuleSlotId)>,
+    /// Module variables referenced from this scope or any function nested in it,
+    /// which must be kept when freezing only the exported variables.
+    pub module_referenced: Vec<ModuleSlotId>,
 }
 
 impl ScopeNames {
@@ -169,6 +178,7 @@ impl<'a> Scope<'a> {
         code: &mut CstStmt,
         globals: FrozenRef<'static, Globals>,
         codemap: FrozenRef<'static, CodeMap>,
+        forward_module_refs: bool,
     ) -> Self {
         // Not really important, sanity check
         assert_eq!(scope_id, ScopeId::module());
@@ -203,6 +213,7 @@ impl<'a> Scope<'a> {
             unscopes: Vec::new(),
             codemap,
             globals,
+            forward_module_refs,
             errors: Vec::new(),
         };
         scope.resolve_idents(code);
@@ -371,6 +382,9 @@ impl<'a> Scope<'a> {
             None => {
                 // Must be a global, since we know all variables
                 match self.globals.get_frozen(ident) {
+                    None if self.forward_module_refs && self.locals.len() > 1 => {
+                        ResolvedIdent::Slot(self.add_forward_module_binding(ident))
+                    }
                     None => {
                         self.errors.push(self.variable_not_found_err(ident));
                         return;
@@ -380,6 +394,34 @@ impl<'a> Scope<'a> {
             }
             Some(slot) => ResolvedIdent::Slot(slot),
         });
+        if let Some(ResolvedIdent::Slot((Slot::Module(slot), _))) = resolved_ident {
+            if self.locals.len() > 1 {
+                let scope_id = self.top_scope_id();
+                let captured = &mut self.scope_data.mut_scope(scope_id).module_captured;
+                if !captured.iter().any(|(name, _)| name == &ident.node) {
+                    captured.push((ident.node.clone(), *slot));
+                }
+                for &scope_id in &self.locals[1..] {
+                    let referenced = &mut self.scope_data.mut_scope(scope_id).module_referenced;
+                    if !referenced.contains(slot) {
+                        referenced.push(*slot);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Create a module variable for an identifier referenced from a function,
+    /// but not assigned yet.
+    fn add_forward_module_binding(&mut self, name: &str) -> (Slot, BindingId) {
+        let vis = Module::default_visibility(name);
+        let slot = Slot::Module(self.module.add_name_visibility(name, vis));
+        let (binding_id, binding) = self.scope_data.new_binding(vis, AssignCount::Any);
+        binding.slot = Some(slot);
+        binding.captured = Captured::Yes;
+        let old_binding = self.module_bindings.insert(name.to_owned(), binding_id);
+        assert!(old_binding.is_none());
+        (slot, binding_id)
     }
 
     fn resolve_idents_in_compr(
@@ -731,7 +773,7 @@ impl ScopeData {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub(crate) enum ResolvedIdent {
     Slot((Slot, BindingId)),
     Global(FrozenValue),
@@ -821,6 +863,7 @@ mod tests {
             &mut cst,
             FrozenRef::new(Globals::empty()),
             codemap,
+            false,
         );
         assert!(scope.errors.is_empty());
         let (.., scope_data) = scope.exit_module();
@@ -31,8 +31,12 @@ use once_cell::sync::Lazy;
 
 use crate::{
     self as starlark,
-    codemap::CodeMap,
-    environment::{FrozenModuleRef, Globals},
+    codemap::{CodeMap, FileSpan, Span},
+    environment::{
+        slots::{ModuleSlotId, MutableSlots},
+        FrozenModuleRef, Globals,
+    },
+    errors::Diagnostic,
     eval::{
         bc::{bytecode::Bc, frame::alloca_frame},
         compiler::{
@@ -56,13 +60,20 @@ use crate::{
     values::{
         docs,
         docs::{DocItem, DocString, DocStringKind},
-        function::FUNCTION_TYPE,
+        function::{Signature, SignatureParamKind, FUNCTION_TYPE},
         typing::TypeCompiled,
-        AtomicFrozenRefOption, Freeze, Freezer, FrozenHeap, FrozenRef, FrozenStringValue,
-        FrozenValue, Heap, StarlarkValue, Trace, Tracer, Value, ValueLike,
+        value_captured_get, AtomicFrozenRefOption, Freeze, FreezeError, Freezer, FrozenHeap,
+        FrozenRef, FrozenStringValue, FrozenValue, FrozenValueMutated, Heap, StarlarkValue, Trace,
+        Tracer, Value, ValueLike,
     },
 };
 
+#[derive(Debug, thiserror::Error)]
+enum DefError {
+    #[error("Can't mutate `{0}`, captured by function `{1}` defined at {2}, because it is frozen")]
+    CapturedFrozen(String, String, String),
+}
+
 /// Store frozen `StmtCompiled`.
 /// This is initialized in `post_freeze`.
 struct StmtCompiledCell {
@@ -166,6 +177,8 @@ impl<T> ParameterCompiled<T> {
 pub(crate) struct DefInfo {
     /// Codemap of the file where the function is declared.
     pub(crate) codemap: FrozenRef<'static, CodeMap>,
+    /// Span of the `def` statement or `lambda` expression.
+    pub(crate) span: Span,
     /// The raw docstring pulled out of the AST.
     pub(crate) docstring: Option<String>,
     pub(crate) scope_names: ScopeNames,
@@ -183,6 +196,9 @@ pub(crate) struct DefInfo {
     /// Globals captured during function or module creation.
     /// Only needed for debugger evaluation.
     pub(crate) globals: FrozenRef<'static, Globals>,
+    /// Whether `int` arithmetic which overflows gives a `float`,
+    /// as per the [`Dialect`](crate::syntax::Dialect) of the module.
+    pub(crate) int_overflow_to_float: bool,
 }
 
 impl DefInfo {
@@ -190,6 +206,7 @@ impl DefInfo {
         static EMPTY_CODEMAP: Lazy<CodeMap> = Lazy::new(CodeMap::default);
         static EMPTY: Lazy<DefInfo> = Lazy::new(|| DefInfo {
             codemap: FrozenRef::new(&EMPTY_CODEMAP),
+            span: Span::default(),
             docstring: None,
             scope_names: ScopeNames::default(),
             stmt_compiled: Bc::default(),
@@ -197,6 +214,7 @@ impl DefInfo {
             stmt_compile_context: StmtCompileContext::default(),
             inline_def_body: None,
             globals: FrozenRef::new(Globals::empty()),
+            int_overflow_to_float: false,
         });
         FrozenRef::new(&EMPTY)
     }
@@ -205,9 +223,11 @@ impl DefInfo {
         codemap: FrozenRef<'static, CodeMap>,
         scope_names: ScopeNames,
         globals: FrozenRef<'static, Globals>,
+        int_overflow_to_float: bool,
     ) -> DefInfo {
         DefInfo {
             codemap,
+            span: Span::default(),
             docstring: None,
             scope_names,
             stmt_compiled: Bc::default(),
@@ -215,6 +235,7 @@ impl DefInfo {
             stmt_compile_context: StmtCompileContext::default(),
             inline_def_body: None,
             globals,
+            int_overflow_to_float,
         }
     }
 }
@@ -423,6 +444,7 @@ impl Compiler<'_, '_, '_> {
     pub fn function(
         &mut self,
         name: &str,
+        span: Span,
         scope_id: ScopeId,
         params: Vec<CstParameter>,
         return_type: Option<Box<CstExpr>>,
@@ -449,6 +471,7 @@ impl Compiler<'_, '_, '_> {
 
         let info = self.eval.module_env.frozen_heap().alloc_any(DefInfo {
             codemap: self.codemap,
+            span,
             docstring,
             scope_names,
             stmt_compiled: body.as_bc(
@@ -460,6 +483,7 @@ impl Compiler<'_, '_, '_> {
             inline_def_body,
             stmt_compile_context: self.compile_context(),
             globals: self.globals,
+            int_overflow_to_float: self.int_overflow_to_float,
         });
 
         ExprCompiled::Def(DefCompiled {
@@ -496,9 +520,35 @@ pub(crate) struct DefGen<V> {
     optimized_on_freeze_stmt: StmtCompiledCell,
 }
 
-impl<V> Display for DefGen<V> {
+impl<'v, V: ValueLike<'v>> Display for DefGen<V> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.parameters.signature())
+        // E.g. `f(x, y = 1, *, z, **kwargs)`, for both `str` and `repr`.
+        let signature = self.def_signature();
+        write!(f, "{}(", signature.name)?;
+        // Whether we have written a `*` or `*args`, after which parameters are keyword-only.
+        let mut star = false;
+        for (i, param) in signature.params.iter().enumerate() {
+            if i != 0 {
+                f.write_str(", ")?;
+            }
+            match param.kind {
+                SignatureParamKind::Args => {
+                    star = true;
+                    f.write_str("*")?;
+                }
+                SignatureParamKind::Kwargs => f.write_str("**")?,
+                SignatureParamKind::KeywordOnly if !star => {
+                    star = true;
+                    f.write_str("*, ")?;
+                }
+                _ => {}
+            }
+            f.write_str(&param.name)?;
+            if let Some(default) = param.default {
+                write!(f, " = {}", default)?;
+            }
+        }
+        f.write_str(")")
     }
 }
 
@@ -562,12 +612,66 @@ impl<'v, T1: ValueLike<'v>> DefGen<T1> {
 
         Some(DocItem::Function(function_docs))
     }
+
+    fn def_signature(&self) -> Signature<'v> {
+        let parameter_types = self
+            .parameter_types
+            .iter()
+            .map(|(i, _, v, _)| (*i as usize, v.to_value().to_str()))
+            .collect();
+        let mut params = self
+            .parameters
+            .signature_params(parameter_types, &HashMap::new());
+        // The parameters are the first locals, so that is where the names of `*args`
+        // and `**kwargs` are.
+        for (i, param) in params.iter_mut().enumerate() {
+            if matches!(
+                param.kind,
+                SignatureParamKind::Args | SignatureParamKind::Kwargs
+            ) {
+                if let Some(name) = self.scope_names().used.get(i) {
+                    param.name = name.clone();
+                }
+            }
+        }
+        Signature {
+            name: self.function_name().to_owned(),
+            params,
+            return_type: self
+                .return_type
+                .as_ref()
+                .map(|(v, _)| v.to_value().to_str()),
+        }
+    }
 }
 
 impl<T1> DefGen<T1> {
     pub(crate) fn scope_names(&self) -> &ScopeNames {
         &self.def_info.scope_names
     }
+
+    /// The name this function was defined with.
+    pub(crate) fn function_name(&self) -> &str {
+        self.parameters.function_name()
+    }
+
+    /// Where this function was defined.
+    pub(crate) fn location(&self) -> FileSpan {
+        self.def_info.codemap.file_span(self.def_info.span)
+    }
+}
+
+/// The name of a function in stack traces and profiles. Functions defined in Starlark
+/// include their parameters in their `repr`, which is too much detail there,
+/// so just use their name.
+pub(crate) fn function_display_name(x: Value) -> String {
+    if let Some(x) = x.downcast_ref::<FrozenDef>() {
+        x.function_name().to_owned()
+    } else if let Some(x) = x.downcast_ref::<Def>() {
+        x.function_name().to_owned()
+    } else {
+        x.to_repr()
+    }
 }
 
 unsafe impl<'v> Trace<'v> for Def<'v> {
@@ -596,7 +700,18 @@ impl<'v> Freeze for Def<'v> {
         let return_type = self
             .return_type
             .into_try_map(|(v, t)| anyhow::Ok((v.freeze(freezer)?, t)))?;
-        let captured = self.captured.try_map(|x| x.freeze(freezer))?;
+        let scope_names = &self.def_info.scope_names;
+        let captured = self
+            .captured
+            .iter()
+            .zip(scope_names.parent.iter())
+            .map(|(x, (_, me))| {
+                x.freeze(freezer).map_err(|e| {
+                    let name = &scope_names.used[me.0 as usize];
+                    FreezeError::add_step(e, format!(".<captured {}>", name))
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
         let module = AtomicFrozenRefOption::new(self.module.load_relaxed());
         Ok(FrozenDef {
             parameters,
@@ -611,6 +726,93 @@ impl<'v> Freeze for Def<'v> {
     }
 }
 
+impl<'v, V: ValueLike<'v>> DefGen<V> {
+    /// Variables this function captured from enclosing functions or its module, with their
+    /// values, skipping any which are not assigned. If the module is not frozen yet,
+    /// its variables are looked up in `module_slots`, if given.
+    fn captured_values(&self, module_slots: Option<&MutableSlots<'v>>) -> Vec<(&str, Value<'v>)> {
+        let scope_names = self.scope_names();
+        let mut res = Vec::new();
+        for (x, (_, me)) in self.captured.iter().zip(scope_names.parent.iter()) {
+            if let Some(v) = value_captured_get(x.to_value()) {
+                res.push((scope_names.used[me.0 as usize].as_str(), v));
+            }
+        }
+        let module = self.module.load_relaxed();
+        for (name, slot) in &scope_names.module_captured {
+            let v = match module {
+                Some(module) => module
+                    .get_module_data()
+                    .get_slot(*slot)
+                    .map(|v| v.to_value()),
+                None => module_slots.and_then(|slots| slots.get_slot(*slot)),
+            };
+            if let Some(v) = v {
+                res.push((name.as_str(), v));
+            }
+        }
+        res
+    }
+
+    /// If `e` is an attempt to mutate a frozen value held by a captured variable,
+    /// add context saying which variable and function it was.
+    #[cold]
+    fn name_mutated_capture(
+        &self,
+        e: anyhow::Error,
+        eval: &Evaluator<'v, '_>,
+    ) -> anyhow::Error {
+        let message = match e.downcast_ref::<Diagnostic>() {
+            Some(d) => &d.message,
+            None => &e,
+        };
+        let ptr = match message.downcast_ref::<FrozenValueMutated>() {
+            Some(x) => x.0,
+            None => return e,
+        };
+        let name = match self
+            .captured_values(Some(eval.module_env.slots()))
+            .into_iter()
+            .find(|(_, v)| v.ptr_value() == ptr)
+        {
+            Some((name, _)) => name.to_owned(),
+            None => return e,
+        };
+        // Only say where the function starts, not the span of its whole body.
+        let location = self.location();
+        let begin = location.resolve_span();
+        let location = format!(
+            "{}:{}:{}",
+            location.file().filename(),
+            begin.begin_line + 1,
+            begin.begin_column + 1
+        );
+        let context = DefError::CapturedFrozen(name, self.function_name().to_owned(), location);
+        match e.downcast::<Diagnostic>() {
+            Ok(mut d) => {
+                d.message = d.message.context(context);
+                d.into()
+            }
+            Err(e) => e.context(context),
+        }
+    }
+}
+
+impl FrozenDef {
+    /// Variables this function captured from enclosing functions or its module,
+    /// with their frozen values, in the order they are first referenced.
+    /// Variables which were never assigned are skipped.
+    ///
+    /// Mutating any of these values fails, so tools can use this to warn about
+    /// functions which would fail when called.
+    pub(crate) fn captured(&self) -> Vec<(&str, FrozenValue)> {
+        self.captured_values(None)
+            .into_iter()
+            .filter_map(|(name, v)| Some((name, v.unpack_frozen()?)))
+            .collect()
+    }
+}
+
 pub(crate) trait DefLike<'v> {
     const FROZEN: bool;
 }
@@ -636,16 +838,24 @@ where
         eval: &mut Evaluator<'v, '_>,
     ) -> anyhow::Result<Value<'v>> {
         let bc = self.bc();
-        alloca_frame(eval, bc.local_count, bc.max_stack_size, |eval| {
+        let res = alloca_frame(eval, bc.local_count, bc.max_stack_size, |eval| {
             let slots = eval.current_frame.locals();
             self.parameters.collect_inline(args, slots, eval.heap())?;
             self.invoke_raw(eval)
-        })
+        });
+        match res {
+            Err(e) => Err(self.name_mutated_capture(e, eval)),
+            Ok(v) => Ok(v),
+        }
     }
 
     fn documentation(&self) -> Option<DocItem> {
         self.docs()
     }
+
+    fn signature(&self) -> Option<Signature<'v>> {
+        Some(self.def_signature())
+    }
 }
 
 impl<'v, V: ValueLike<'v>> DefGen<V>
@@ -672,7 +882,9 @@ where
                     None => {
                         panic!("Not allowed optional unassigned with type annotations on them")
                     }
-                    Some(v) => v.check_type_compiled(ty.to_value(), ty2, Some(arg_name))?,
+                    Some(v) => v
+                        .check_type_compiled(ty.to_value(), ty2, Some(arg_name))
+                        .map_err(|e| eval.value_provenance.add_origin(e, v))?,
                 }
             }
         }
@@ -734,9 +946,23 @@ where
             .for_each(|l| writeln!(w, "  {}", l).unwrap());
         w
     }
+
+    pub(crate) fn dump_bytecode(&self) -> String {
+        self.bc().instrs.dump_bytecode()
+    }
 }
 
 impl FrozenDef {
+    /// The variables of the module being frozen which this function, or the functions nested
+    /// in it, refer to. Empty if the function belongs to a module which is already frozen,
+    /// since the variables are then in that module.
+    pub(crate) fn referenced_module_slots(&self) -> &[ModuleSlotId] {
+        match self.module.load_relaxed() {
+            Some(_) => &[],
+            None => &self.def_info.scope_names.module_referenced,
+        }
+    }
+
     pub(crate) fn post_freeze(
         &self,
         module: FrozenRef<FrozenModuleRef>,
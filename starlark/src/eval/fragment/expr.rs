@@ -24,7 +24,7 @@ use thiserror::Error;
 use crate::{
     codemap::Spanned,
     collections::symbol_map::Symbol,
-    environment::slots::ModuleSlotId,
+    environment::{slots::ModuleSlotId, ExtraMethods},
     errors::did_you_mean::did_you_mean,
     eval::{
         compiler::{
@@ -44,12 +44,17 @@ use crate::{
         function::BoundMethodGen,
         string::{interpolation::parse_percent_s_one, StarlarkStr},
         types::{
-            bool::StarlarkBool,
+            bool::{StarlarkBool, BOOL_TYPE},
             dict::Dict,
             float::StarlarkFloat,
+            int::INT_TYPE,
             list::{FrozenList, List},
+            none::NoneType,
             range::Range,
-            string::interpolation::{format_one, percent_s_one},
+            string::{
+                interpolation::{format_one, percent_s_one},
+                STRING_TYPE,
+            },
             tuple::Tuple,
             unbound::MaybeUnboundValue,
         },
@@ -106,7 +111,7 @@ impl ExprUnOp {
         match self {
             ExprUnOp::Minus => v.minus(heap),
             ExprUnOp::Plus => v.plus(heap),
-            ExprUnOp::BitNot => Ok(Value::new_int(!v.to_int()?)),
+            ExprUnOp::BitNot => v.bit_not(heap),
         }
     }
 }
@@ -720,7 +725,7 @@ impl ExprCompiled {
         frozen_heap: &FrozenHeap,
     ) -> Option<FrozenValue> {
         // We assume `getattr` has no side effects.
-        let v = get_attr_hashed_raw(left.to_value(), attr, heap).ok()?;
+        let v = get_attr_hashed_raw(left.to_value(), attr, heap, None).ok()?;
         match v {
             MemberOrValue::Member(m) => match MaybeUnboundValue::new(m) {
                 MaybeUnboundValue::Method(m) => {
@@ -918,12 +923,32 @@ fn eval_equals(l: IrSpanned<ExprCompiled>, r: IrSpanned<ExprCompiled>) -> ExprCo
     ExprCompiled::Equals(box (l, r))
 }
 
+/// The string `type()` returns for the builtin type named `x`, if there is one.
+/// Compiling string literals to these shared statics makes the common `type(x) == "list"`
+/// succeed on the pointer equality check, without comparing the strings.
+fn builtin_type_string(x: &str) -> Option<FrozenStringValue> {
+    Some(match x {
+        NoneType::TYPE => Value::new_none().get_type_value(),
+        BOOL_TYPE => <StarlarkBool as StarlarkValue>::get_type_value_static(),
+        INT_TYPE => Value::new_int(0).get_type_value(),
+        STRING_TYPE => <StarlarkStr as StarlarkValue>::get_type_value_static(),
+        StarlarkFloat::TYPE => <StarlarkFloat as StarlarkValue>::get_type_value_static(),
+        List::TYPE => List::get_type_value_static(),
+        Dict::TYPE => Dict::get_type_value_static(),
+        Tuple::TYPE => <Tuple as StarlarkValue>::get_type_value_static(),
+        _ => return None,
+    })
+}
+
 impl AstLiteral {
     fn compile(&self, heap: &FrozenHeap) -> FrozenValue {
         match self {
             AstLiteral::Int(i) => FrozenValue::new_int(i.node),
             AstLiteral::Float(f) => heap.alloc(f.node),
-            AstLiteral::String(x) => heap.alloc(x.node.as_str()),
+            AstLiteral::String(x) => match builtin_type_string(&x.node) {
+                Some(x) => x.unpack(),
+                None => heap.alloc(x.node.as_str()),
+            },
         }
     }
 }
@@ -987,11 +1012,24 @@ pub(crate) enum MemberOrValue<'v> {
     Value(Value<'v>),
 }
 
+/// Find `attribute` in the methods the embedder added to the type of `x`,
+/// which are only used when the type has no attribute of that name.
+fn get_extra_method(
+    x: Value,
+    attribute: &Symbol,
+    extra_methods: Option<&ExtraMethods>,
+) -> Option<FrozenValue> {
+    extra_methods?
+        .get(x.get_type())?
+        .get_frozen_symbol(attribute)
+}
+
 #[inline(always)]
 pub(crate) fn get_attr_hashed_raw<'v>(
     x: Value<'v>,
     attribute: &Symbol,
     heap: &'v Heap,
+    extra_methods: Option<&ExtraMethods>,
 ) -> anyhow::Result<MemberOrValue<'v>> {
     let aref = x.get_ref();
     if let Some(methods) = aref.get_methods() {
@@ -999,8 +1037,14 @@ pub(crate) fn get_attr_hashed_raw<'v>(
             return Ok(MemberOrValue::Member(v));
         }
     }
+    if let Some(v) = aref.get_attr_method(x, attribute.as_str(), heap) {
+        return Ok(MemberOrValue::Value(v));
+    }
     match aref.get_attr(attribute.as_str(), heap) {
-        None => Err(get_attr_no_attr_error(x, attribute)),
+        None => match get_extra_method(x, attribute, extra_methods) {
+            Some(v) => Ok(MemberOrValue::Member(v)),
+            None => Err(get_attr_no_attr_error(x, attribute)),
+        },
         Some(x) => Ok(MemberOrValue::Value(x)),
     }
 }
@@ -1009,6 +1053,7 @@ pub(crate) fn get_attr_hashed_bind<'v>(
     x: Value<'v>,
     attribute: &Symbol,
     heap: &'v Heap,
+    extra_methods: Option<&ExtraMethods>,
 ) -> anyhow::Result<Value<'v>> {
     let aref = x.get_ref();
     if let Some(methods) = aref.get_methods() {
@@ -1016,8 +1061,14 @@ pub(crate) fn get_attr_hashed_bind<'v>(
             return MaybeUnboundValue::new(v).bind(x, heap);
         }
     }
+    if let Some(v) = aref.get_attr_method(x, attribute.as_str(), heap) {
+        return Ok(v);
+    }
     match aref.get_attr(attribute.as_str(), heap) {
-        None => Err(get_attr_no_attr_error(x, attribute)),
+        None => match get_extra_method(x, attribute, extra_methods) {
+            Some(v) => MaybeUnboundValue::new(v).bind(x, heap),
+            None => Err(get_attr_no_attr_error(x, attribute)),
+        },
         Some(x) => {
             // Only `get_methods` is allowed to return unbound methods,
             // so we assume the value is bound here.
@@ -1092,7 +1143,7 @@ impl Compiler<'_, '_, '_> {
                     span: expr.span,
                     node: StmtP::Return(Some(inner)),
                 };
-                self.function("lambda", scope_id, params, None, suite)
+                self.function("lambda", expr.span, scope_id, params, None, suite)
             }
             ExprP::Tuple(exprs) => {
                 let xs = exprs.into_map(|x| self.expr(x));
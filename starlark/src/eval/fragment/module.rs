@@ -66,6 +66,9 @@ impl<'v> Compiler<'v, '_, '_> {
                 },
                 self.eval,
             )?;
+            // Unlike an assignment, an aliased load doesn't call `export_as`, since the value
+            // is frozen, so not on our heap, and may be shared with other modules.
+            self.eval.module_env.set_loaded(slot);
             self.eval.set_slot_module(slot, value)
         }
 
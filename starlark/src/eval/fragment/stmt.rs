@@ -41,11 +41,7 @@ use crate::{
             expr::ExprCompiled, expr_bool::ExprCompiledBool, known::list_to_tuple,
             small_vec_1::SmallVec1, span::IrSpanned,
         },
-        runtime::{
-            call_stack::FrozenFileSpan,
-            evaluator::{Evaluator, GC_THRESHOLD},
-            slots::LocalSlotId,
-        },
+        runtime::{call_stack::FrozenFileSpan, evaluator::Evaluator, slots::LocalSlotId},
     },
     syntax::ast::{AssignOp, AssignP, StmtP},
     values::{dict::Dict, list::List, FrozenHeap, FrozenValue, Heap, Value, ValueError},
@@ -56,7 +52,7 @@ pub(crate) enum AssignModifyLhs {
     Dot(IrSpanned<ExprCompiled>, String),
     Array(IrSpanned<ExprCompiled>, IrSpanned<ExprCompiled>),
     Local(IrSpanned<(LocalSlotId, Captured)>),
-    Module(IrSpanned<ModuleSlotId>),
+    Module(IrSpanned<ModuleSlotId>, String),
 }
 
 #[derive(Clone, Debug)]
@@ -82,6 +78,8 @@ pub(crate) enum StmtCompiled {
 pub(crate) struct StmtCompileContext {
     pub(crate) has_before_stmt: bool,
     pub(crate) bc_profile: bool,
+    /// Record where lists and dicts are created, for value provenance.
+    pub(crate) record_origins: bool,
 }
 
 pub(crate) struct OptimizeOnFreezeContext<'a> {
@@ -103,7 +101,7 @@ impl AssignModifyLhs {
                 AssignModifyLhs::Array(expr.optimize_on_freeze(ctx), index.optimize_on_freeze(ctx))
             }
             AssignModifyLhs::Local(slot) => AssignModifyLhs::Local(*slot),
-            AssignModifyLhs::Module(slot) => AssignModifyLhs::Module(*slot),
+            AssignModifyLhs::Module(slot, name) => AssignModifyLhs::Module(*slot, name.clone()),
         }
     }
 }
@@ -450,7 +448,11 @@ impl Compiler<'_, '_, '_> {
                         };
                         StmtsCompiled::one(IrSpanned {
                             span: span_stmt,
-                            node: StmtCompiled::AssignModify(AssignModifyLhs::Module(lhs), op, rhs),
+                            node: StmtCompiled::AssignModify(
+                                AssignModifyLhs::Module(lhs, ident.node.0.clone()),
+                                op,
+                                rhs,
+                            ),
                         })
                     }
                 }
@@ -463,29 +465,33 @@ impl Compiler<'_, '_, '_> {
 }
 
 // This function should be called before every meaningful statement.
-// The purposes are GC, profiling and debugging.
+// The purposes are GC, profiling, debugging and enforcing the heap limit.
 //
 // This function is called only if `before_stmt` is set before compilation start.
-pub(crate) fn before_stmt(span: FrozenFileSpan, eval: &mut Evaluator) {
+pub(crate) fn before_stmt(span: FrozenFileSpan, eval: &mut Evaluator) -> anyhow::Result<()> {
     assert!(
         eval.before_stmt.enabled(),
         "this code should not be called if `before_stmt` is set"
     );
+    eval.heap().check_max_bytes(0)?;
+    eval.before_stmt.count_instruction()?;
     let fs = mem::take(&mut eval.before_stmt.before_stmt);
-    for f in &fs {
-        f(
+    // Stop at the first error, but always put the functions back.
+    let res = fs.iter().try_for_each(|f| {
+        f.call(
             FileSpanRef {
                 span: span.span,
                 file: &span.file,
             },
             eval,
         )
-    }
+    });
     let added = mem::replace(&mut eval.before_stmt.before_stmt, fs);
     assert!(
         added.is_empty(),
         "`before_stmt` cannot be modified during evaluation"
     );
+    res
 }
 
 // There are two requirements to perform a GC:
@@ -528,7 +534,10 @@ pub(crate) fn possible_gc(eval: &mut Evaluator) {
         unsafe {
             eval.garbage_collect()
         }
-        eval.next_gc_level = eval.heap().allocated_bytes() + GC_THRESHOLD;
+        eval.next_gc_level = eval
+            .heap()
+            .allocated_bytes()
+            .saturating_add(eval.gc_threshold);
     }
 }
 
@@ -546,21 +555,30 @@ pub(crate) fn bit_or_assign<'v>(
     let lhs_aref = lhs.get_ref();
     let lhs_ty = lhs_aref.static_type_of_value();
 
-    if Dict::is_dict_type(lhs_ty) {
-        // If the value is None, that must mean its a FrozenList, thus turn it into an immutable error
-        let mut dict = Dict::from_value_mut(lhs)?
-            .ok_or_else(|| anyhow!(ValueError::CannotMutateImmutableValue))?;
-        if lhs.ptr_eq(rhs) {
-            // Nothing to do as union is idempotent
-        } else {
-            let rhs = Dict::from_value(rhs).map_or_else(
-                || ValueError::unsupported_owned(lhs_aref.get_type(), "|=", Some(rhs.get_type())),
-                Ok,
-            )?;
-            for (k, v) in rhs.iter_hashed() {
-                dict.insert_hashed(k, v);
+    // A frozen dict can't be mutated, so `d |= x` rebinds `d` to a fresh dict instead,
+    // which lets it work on a dict loaded from another module.
+    if Dict::is_dict_type(lhs_ty) && lhs.unpack_frozen().is_none() {
+        heap.charge_growth(lhs, || {
+            let mut dict = Dict::from_value_mut(lhs)?.expect("checked it is an unfrozen dict");
+            if lhs.ptr_eq(rhs) {
+                // Nothing to do as union is idempotent
+            } else {
+                let rhs = Dict::from_value(rhs).map_or_else(
+                    || {
+                        ValueError::unsupported_owned(
+                            lhs_aref.get_type(),
+                            "|=",
+                            Some(rhs.get_type()),
+                        )
+                    },
+                    Ok,
+                )?;
+                for (k, v) in rhs.iter_hashed() {
+                    dict.insert_hashed(k, v);
+                }
             }
-        }
+            anyhow::Ok(())
+        })?;
         Ok(lhs)
     } else {
         lhs_aref.bit_or(rhs, heap)
@@ -604,12 +622,7 @@ pub(crate) fn add_assign<'v>(
             // If the value is None, that must mean its a FrozenList, thus turn it into an immutable error
             let list = List::from_value_mut(lhs)?
                 .ok_or_else(|| anyhow!(ValueError::CannotMutateImmutableValue))?;
-            if lhs.ptr_eq(rhs) {
-                list.double(heap);
-            } else {
-                // TODO: if RHS is list, consider calling `List::extend_from_slice`.
-                rhs.with_iterator(heap, |it| list.extend(it, heap))?;
-            }
+            list.extend_from_value(lhs, rhs, heap)?;
             Ok(lhs)
         } else {
             lhs_aref.add(rhs, heap)
@@ -622,6 +635,7 @@ impl Compiler<'_, '_, '_> {
         StmtCompileContext {
             has_before_stmt: self.has_before_stmt,
             bc_profile: self.bc_profile,
+            record_origins: self.record_origins,
         }
     }
 
@@ -705,7 +719,7 @@ impl Compiler<'_, '_, '_> {
         match stmt.node {
             StmtP::Def(name, params, return_type, suite, scope_id) => {
                 let rhs = IrSpanned {
-                    node: self.function(&name.0, scope_id, params, return_type, *suite),
+                    node: self.function(&name.0, stmt.span, scope_id, params, return_type, *suite),
                     span,
                 };
                 let lhs = self.assign(Spanned {
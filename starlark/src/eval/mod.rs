@@ -20,13 +20,14 @@
 
 use std::{intrinsics::unlikely, mem, time::Instant};
 
+pub use batch::{eval_many, eval_many_parsed};
 pub(crate) use compiler::scope::ScopeNames;
 pub(crate) use fragment::def::{Def, FrozenDef};
 use gazebo::prelude::*;
 pub use runtime::{
-    arguments::{Arguments, ParametersParser, ParametersSpec},
+    arguments::{Arguments, ArgumentsBuilder, ParametersParser, ParametersSpec},
     evaluator::Evaluator,
-    file_loader::{FileLoader, ReturnFileLoader},
+    file_loader::{AstFileLoader, AstLoader, FileLoader, ReturnFileLoader},
 };
 
 use crate::{
@@ -38,11 +39,13 @@ use crate::{
             Compiler, Constants,
         },
         fragment::def::DefInfo,
+        runtime::evaluator::EvaluatorError,
     },
     syntax::ast::AstModule,
-    values::{docs::DocString, Value},
+    values::{docs::DocString, repr_limits::set_repr_limits, Value},
 };
 
+mod batch;
 pub(crate) mod bc;
 mod compiler;
 mod fragment;
@@ -52,13 +55,62 @@ pub use runtime::profile::ProfileMode;
 #[cfg(test)]
 mod tests;
 
+/// How [`Evaluator::eval_module_impl`] treats the module.
+#[derive(Copy, Clone, Dupe, Eq, PartialEq)]
+enum ModuleEvalMode {
+    /// A complete module, see [`Evaluator::eval_module`].
+    Module,
+    /// A snippet continuing the module, see [`Evaluator::eval_snippet`].
+    Statements,
+}
+
 impl<'v, 'a> Evaluator<'v, 'a> {
     /// Evaluate an [`AstModule`] with this [`Evaluator`], modifying the in-scope
     /// [`Module`](crate::environment::Module) as appropriate.
     pub fn eval_module(&mut self, ast: AstModule, globals: &Globals) -> anyhow::Result<Value<'v>> {
+        self.eval_module_impl(ast, globals, ModuleEvalMode::Module)
+    }
+
+    /// Evaluate an [`AstModule`] as a continuation of the statements previously
+    /// evaluated in the in-scope [`Module`](crate::environment::Module).
+    /// Intended for interactive use, such as a REPL, where a module is built
+    /// up from a sequence of snippets.
+    ///
+    /// Unlike [`eval_module`](Evaluator::eval_module), this function:
+    ///
+    /// * Does not treat a leading string as the module docstring.
+    /// * Allows a `def` or `lambda` to refer to module variables which are
+    ///   not defined yet, but will be assigned by a later snippet.
+    ///   Calling such a function before the variable is assigned is a runtime error.
+    ///
+    /// The result is the value of the last statement if it is an expression,
+    /// otherwise `None`.
+    pub fn eval_snippet(&mut self, ast: AstModule, globals: &Globals) -> anyhow::Result<Value<'v>> {
+        self.eval_module_impl(ast, globals, ModuleEvalMode::Statements)
+    }
+
+    fn eval_module_impl(
+        &mut self,
+        ast: AstModule,
+        globals: &Globals,
+        mode: ModuleEvalMode,
+    ) -> anyhow::Result<Value<'v>> {
+        // The statements with errors are missing, so running the rest would be misleading.
+        if ast.has_syntax_errors {
+            return Err(EvaluatorError::SyntaxErrors.into());
+        }
+
         let start = Instant::now();
 
-        let AstModule { codemap, statement } = ast;
+        self.stmt_coverage.add_module(&ast);
+
+        let AstModule {
+            codemap,
+            statement,
+            int_overflow_to_float,
+            enable_recursion: _,
+            has_syntax_errors: _,
+        } = ast;
 
         let codemap = self
             .module_env
@@ -73,8 +125,10 @@ impl<'v, 'a> Evaluator<'v, 'a> {
 
         let mut statement = statement.into_map_payload(&mut CompilerAstMap(&mut scope_data));
 
-        if let Some(docstring) = DocString::extract_raw_starlark_docstring(&statement) {
-            self.module_env.set_docstring(docstring)
+        if mode == ModuleEvalMode::Module {
+            if let Some(docstring) = DocString::extract_raw_starlark_docstring(&statement) {
+                self.module_env.set_docstring(docstring)
+            }
         }
 
         let mut scope = Scope::enter_module(
@@ -84,6 +138,7 @@ impl<'v, 'a> Evaluator<'v, 'a> {
             &mut statement,
             globals,
             codemap,
+            mode == ModuleEvalMode::Statements,
         );
 
         // We want to grab the first error only, with ownership, so drop all but the first
@@ -103,6 +158,7 @@ impl<'v, 'a> Evaluator<'v, 'a> {
                 codemap,
                 scope_names,
                 globals,
+                int_overflow_to_float,
             )),
         );
 
@@ -116,6 +172,7 @@ impl<'v, 'a> Evaluator<'v, 'a> {
         }
 
         // Evaluation
+        let repr_limits_guard = set_repr_limits(self.repr_limits);
         let mut compiler = Compiler {
             scope_data,
             locals: Vec::new(),
@@ -124,10 +181,13 @@ impl<'v, 'a> Evaluator<'v, 'a> {
             constants: Constants::new(),
             has_before_stmt: self.before_stmt.enabled(),
             bc_profile: self.bc_profile.enabled(),
+            record_origins: self.value_provenance.enabled(),
+            int_overflow_to_float,
             eval: self,
         };
 
         let res = compiler.eval_module(statement, local_count);
+        drop(repr_limits_guard);
 
         // Clean up the world, putting everything back
         self.call_stack.pop();
@@ -144,6 +204,7 @@ impl<'v, 'a> Evaluator<'v, 'a> {
     }
 
     /// Evaluate a function stored in a [`Value`], passing in `positional` and `named` arguments.
+    /// To also pass `*args` or `**kwargs`, use [`ArgumentsBuilder`].
     pub fn eval_function(
         &mut self,
         function: Value<'v>,
@@ -159,6 +220,15 @@ impl<'v, 'a> Evaluator<'v, 'a> {
             args: None,
             kwargs: None,
         };
-        function.invoke(&params, self)
+        self.invoke(function, &params)
+    }
+
+    pub(crate) fn invoke(
+        &mut self,
+        function: Value<'v>,
+        params: &Arguments<'v, '_>,
+    ) -> anyhow::Result<Value<'v>> {
+        let _guard = set_repr_limits(self.repr_limits);
+        function.invoke(params, self)
     }
 }
@@ -31,11 +31,13 @@ use crate::{
         symbol_map::{Symbol, SymbolMap},
         Hashed, SmallMap,
     },
+    errors::did_you_mean::did_you_mean,
     eval::Evaluator,
     values::{
         dict::{Dict, DictRef},
         docs,
         docs::DocString,
+        function::{SignatureParam, SignatureParamKind},
         Freezer, FrozenValue, Heap, StringValue, Trace, Tracer, UnpackValue, Value, ValueError,
         ValueLike,
     },
@@ -52,10 +54,18 @@ pub(crate) enum FunctionError {
         names: Vec<String>,
         function: String,
     },
+    #[error("Found extra named parameter `{name}` for call to {function}, did you mean `{better}`?")]
+    ExtraNamedParameterDidYouMean {
+        name: String,
+        better: String,
+        function: String,
+    },
     #[error("Parameter `{name}` occurs both explicitly and in **kwargs")]
     RepeatedParameter { name: String },
-    #[error("The argument provided for *args is not an identifier")]
-    ArgsValueIsNotString,
+    #[error("Repeated named argument `{0}`")]
+    RepeatedNamedArgument(String),
+    #[error("Keys of **kwargs must be strings, but got `{0}` of type `{1}`")]
+    KwArgsKeyIsNotString(String, &'static str),
     #[error("The argument provided for *args is not iterable")]
     ArgsArrayIsNotIterable,
     #[error("The argument provided for **kwargs is not a dictionary")]
@@ -227,6 +237,11 @@ impl<V> ParametersSpec<V> {
         collector
     }
 
+    /// The name of the function these parameters belong to.
+    pub(crate) fn function_name(&self) -> &str {
+        &self.function_name
+    }
+
     /// Figure out the argument name at an index in kinds.
     /// Only called in the error path, so is not optimised.
     pub(crate) fn param_name_at(&self, index: usize) -> String {
@@ -249,8 +264,9 @@ impl<V> ParametersSpec<V> {
         collector.push_str(&self.function_name);
 
         // We used to make the "name" of a function include all its parameters, but that is a lot of
-        // details and visually crowds out everything else. Try disabling, although we might want it
-        // in some contexts, so don't delete it.
+        // details and visually crowds out everything else in error messages. Functions defined in
+        // Starlark show their parameters in `str` and `repr` instead, so debugging output has them.
+        // Try disabling, although we might want it in some contexts, so don't delete it.
         if false {
             collector.push('(');
 
@@ -384,9 +400,56 @@ impl<'v, V: ValueLike<'v>> ParametersSpec<V> {
             return Ok(());
         }
 
+        // Calls with only positional arguments (which is what the bytecode
+        // positional call instructions produce) that don't overflow into `*args`
+        // only need defaults filling in, so skip the general machinery.
+        if args.pos.len() <= self.positional
+            && args.named.is_empty()
+            && args.args.is_none()
+            && args.kwargs.is_none()
+        {
+            return self.collect_positional_only(args.pos, slots, heap);
+        }
+
         self.collect_slow(args, slots, heap)
     }
 
+    /// Collect arguments where there are no named arguments, `*args` or `**kwargs`,
+    /// and no more positional arguments than positional parameters.
+    /// Must produce the same results and errors as `collect_slow`.
+    #[inline(always)]
+    fn collect_positional_only(
+        &self,
+        pos: &[Value<'v>],
+        slots: &[Cell<Option<Value<'v>>>],
+        heap: &'v Heap,
+    ) -> anyhow::Result<()> {
+        let kinds = &self.kinds;
+        // We might do unchecked stuff later on, so make sure we have as many slots as we expect
+        assert!(slots.len() >= kinds.len());
+
+        for (v, s) in pos.iter().zip(slots.iter()) {
+            s.set(Some(*v));
+        }
+        for index in pos.len()..kinds.len() {
+            let slot = unsafe { slots.get_unchecked(index) };
+            match unsafe { kinds.get_unchecked(index) } {
+                ParameterKind::Required => {
+                    return Err(FunctionError::MissingParameter {
+                        name: self.param_name_at(index),
+                        function: self.signature(),
+                    }
+                    .into());
+                }
+                ParameterKind::Defaulted(x) => slot.set(Some(x.to_value())),
+                ParameterKind::Optional => {}
+                ParameterKind::Args => slot.set(Some(heap.alloc_tuple(&[]))),
+                ParameterKind::KWargs => slot.set(Some(heap.alloc(Dict::default()))),
+            }
+        }
+        Ok(())
+    }
+
     fn collect_slow(
         &self,
         args: &Arguments<'v, '_>,
@@ -394,9 +457,11 @@ impl<'v, V: ValueLike<'v>> ParametersSpec<V> {
         heap: &'v Heap,
     ) -> anyhow::Result<()> {
         /// Lazily initialized `kwargs` object.
-        #[derive(Default)]
         struct LazyKwargs<'v> {
             kwargs: Option<Box<SmallMap<StringValue<'v>, Value<'v>>>>,
+            /// Capacity to create the map with. Most `kwargs` are small, so sizing it
+            /// by the number of named arguments avoids reserving room that is never used.
+            capacity: usize,
         }
 
         impl<'v> LazyKwargs<'v> {
@@ -405,7 +470,7 @@ impl<'v, V: ValueLike<'v>> ParametersSpec<V> {
             fn insert(&mut self, key: Hashed<StringValue<'v>>, val: Value<'v>) -> bool {
                 match &mut self.kwargs {
                     None => {
-                        let mut mp = SmallMap::with_capacity_largest_vec();
+                        let mut mp = SmallMap::with_capacity(self.capacity);
                         mp.insert_hashed(key, val);
                         self.kwargs = Some(box mp);
                         false
@@ -428,7 +493,10 @@ impl<'v, V: ValueLike<'v>> ParametersSpec<V> {
         assert!(slots.len() >= len);
 
         let mut star_args = Vec::new();
-        let mut kwargs = LazyKwargs::default();
+        let mut kwargs = LazyKwargs {
+            kwargs: None,
+            capacity: args.names.len(),
+        };
         let mut next_position = 0;
 
         // First deal with positional parameters
@@ -498,27 +566,45 @@ impl<'v, V: ValueLike<'v>> ParametersSpec<V> {
         if let Some(param_kwargs) = args.kwargs {
             match Dict::from_value(param_kwargs) {
                 Some(y) => {
-                    for (k, v) in y.iter_hashed() {
-                        match StringValue::new(*k.key()) {
-                            None => return Err(FunctionError::ArgsValueIsNotString.into()),
-                            Some(s) => {
-                                let repeat = match self
-                                    .names
-                                    .get_hashed_string_value(Hashed::new_unchecked(k.hash(), s))
-                                {
-                                    None => kwargs.insert(Hashed::new_unchecked(k.hash(), s), v),
-                                    Some(i) => {
-                                        let this_slot = &slots[*i];
-                                        let repeat = this_slot.get().is_some();
-                                        this_slot.set(Some(v));
-                                        repeat
+                    // Fast path for forwarding `**kwargs` unchanged, e.g. `def f(**kwargs): g(**kwargs)`.
+                    // If nothing else is going into our kwargs, and no key names a parameter,
+                    // we can clone the whole map, rather than rehashing and inserting each entry.
+                    // The map is still copied on every call: a dict can't share its contents
+                    // copy-on-write, so a new dict is materialised, just more cheaply.
+                    let forward = if self.kwargs.is_some() && kwargs.kwargs.is_none() {
+                        self.forwardable_kwargs(&y)
+                    } else {
+                        None
+                    };
+                    if let Some(forward) = forward {
+                        // The callee gets its own copy, so if it mutates its kwargs,
+                        // the caller doesn't see the change.
+                        kwargs.kwargs = Some(box forward.clone());
+                    } else {
+                        for (k, v) in y.iter_hashed() {
+                            match StringValue::new(*k.key()) {
+                                None => return Err(Arguments::kwargs_key_error(*k.key())),
+                                Some(s) => {
+                                    let repeat = match self
+                                        .names
+                                        .get_hashed_string_value(Hashed::new_unchecked(k.hash(), s))
+                                    {
+                                        None => {
+                                            kwargs.insert(Hashed::new_unchecked(k.hash(), s), v)
+                                        }
+                                        Some(i) => {
+                                            let this_slot = &slots[*i];
+                                            let repeat = this_slot.get().is_some();
+                                            this_slot.set(Some(v));
+                                            repeat
+                                        }
+                                    };
+                                    if unlikely(repeat) {
+                                        return Err(FunctionError::RepeatedParameter {
+                                            name: s.as_str().to_owned(),
+                                        }
+                                        .into());
                                     }
-                                };
-                                if unlikely(repeat) {
-                                    return Err(FunctionError::RepeatedParameter {
-                                        name: s.as_str().to_owned(),
-                                    }
-                                    .into());
                                 }
                             }
                         }
@@ -573,15 +659,61 @@ impl<'v, V: ValueLike<'v>> ParametersSpec<V> {
         if let Some(kwargs_pos) = self.kwargs {
             slots[kwargs_pos].set(Some(kwargs.alloc(heap)));
         } else if let Some(kwargs) = kwargs.kwargs {
-            return Err(FunctionError::ExtraNamedParameters {
-                names: kwargs.keys().map(|x| x.as_str().to_owned()).collect(),
-                function: self.signature(),
-            }
-            .into());
+            return Err(self.extra_named_parameters_error(
+                kwargs.keys().map(|x| x.as_str().to_owned()).collect(),
+            ));
         }
         Ok(())
     }
 
+    /// The contents of a `**kwargs` dictionary, if all the keys are strings
+    /// and none of them are the names of parameters.
+    fn forwardable_kwargs<'a>(
+        &self,
+        kwargs: &'a Dict<'v>,
+    ) -> Option<&'a SmallMap<StringValue<'v>, Value<'v>>> {
+        let res = kwargs.downcast_ref_key_string()?;
+        if res.iter_hashed().all(|(k, _)| {
+            self.names
+                .get_hashed_string_value(k.unborrow_copy())
+                .is_none()
+        }) {
+            Some(res)
+        } else {
+            None
+        }
+    }
+
+    /// Produce the error for named arguments which don't match any parameter,
+    /// suggesting a parameter name if there is a single unknown argument
+    /// and it looks like a typo.
+    /// Only called in the error path, so is not optimised.
+    #[cold]
+    #[inline(never)]
+    fn extra_named_parameters_error(&self, names: Vec<String>) -> anyhow::Error {
+        if let [name] = names.as_slice() {
+            // Names starting with `$` are positional-only, so can't be suggested.
+            let variants = self
+                .names
+                .keys()
+                .map(|x| x.as_str())
+                .filter(|x| !x.starts_with('$'));
+            if let Some(better) = did_you_mean(name, variants) {
+                return FunctionError::ExtraNamedParameterDidYouMean {
+                    name: name.clone(),
+                    better: better.to_owned(),
+                    function: self.signature(),
+                }
+                .into();
+            }
+        }
+        FunctionError::ExtraNamedParameters {
+            names,
+            function: self.signature(),
+        }
+        .into()
+    }
+
     /// Generate documentation for each of the parameters.
     ///
     /// # Arguments
@@ -589,9 +721,21 @@ impl<'v, V: ValueLike<'v>> ParametersSpec<V> {
     /// * `parameter_docs` should be a mapping of parameter name to possible documentation for
     ///                    that parameter
     pub fn documentation(
+        &self,
+        parameter_types: HashMap<usize, docs::Type>,
+        parameter_docs: HashMap<String, Option<DocString>>,
+    ) -> Vec<docs::Param> {
+        self.documentation_with_defaults(parameter_types, parameter_docs, &HashMap::new())
+    }
+
+    /// Like [`documentation`](ParametersSpec::documentation), but `parameter_defaults` gives
+    /// the Rust source of the defaults of native parameters which are `Optional` in the
+    /// signature, since their default is applied when unpacking rather than stored here.
+    pub(crate) fn documentation_with_defaults(
         &self,
         mut parameter_types: HashMap<usize, docs::Type>,
         mut parameter_docs: HashMap<String, Option<DocString>>,
+        parameter_defaults: &HashMap<usize, &str>,
     ) -> Vec<docs::Param> {
         let mut params: Vec<docs::Param> = self
             .iter_params()
@@ -610,7 +754,13 @@ impl<'v, V: ValueLike<'v>> ParametersSpec<V> {
                         name,
                         docs,
                         typ,
-                        default_value: Some("None".to_owned()),
+                        default_value: Some(match parameter_defaults.get(&i) {
+                            // Show Rust literals the way they are written in Starlark.
+                            Some(&"true") => "True".to_owned(),
+                            Some(&"false") => "False".to_owned(),
+                            Some(x) => (*x).to_owned(),
+                            None => "None".to_owned(),
+                        }),
                     },
                     ParameterKind::Defaulted(v) => docs::Param::Arg {
                         name,
@@ -632,6 +782,43 @@ impl<'v, V: ValueLike<'v>> ParametersSpec<V> {
         params
     }
 
+    /// Describe the parameters, for [`Value::signature`]. `parameter_types` gives the type
+    /// annotation of the parameter at each index, and `parameter_defaults` the native
+    /// parameters whose default is applied in Rust, rather than being `None`.
+    pub(crate) fn signature_params(
+        &self,
+        mut parameter_types: HashMap<usize, String>,
+        parameter_defaults: &HashMap<usize, &str>,
+    ) -> Vec<SignatureParam<'v>> {
+        // Positional-only parameters of native functions have a `$` on the front.
+        let positional_only = |i: usize| {
+            self.names
+                .iter()
+                .any(|(name, j)| *j == i && name.as_str().starts_with('$'))
+        };
+        self.iter_params()
+            .map(|(i, name, kind)| SignatureParam {
+                name: name.trim_start_matches('*').to_owned(),
+                kind: match kind {
+                    ParameterKind::Args => SignatureParamKind::Args,
+                    ParameterKind::KWargs => SignatureParamKind::Kwargs,
+                    _ if i >= self.positional => SignatureParamKind::KeywordOnly,
+                    _ if positional_only(i) => SignatureParamKind::PositionalOnly,
+                    _ => SignatureParamKind::Normal,
+                },
+                has_default: matches!(kind, ParameterKind::Optional | ParameterKind::Defaulted(_)),
+                default: match kind {
+                    ParameterKind::Defaulted(v) => Some(v.to_value()),
+                    ParameterKind::Optional if !parameter_defaults.contains_key(&i) => {
+                        Some(Value::new_none())
+                    }
+                    _ => None,
+                },
+                typ: parameter_types.remove(&i),
+            })
+            .collect()
+    }
+
     /// Create a [`ParametersParser`] for given arguments.
     pub fn parser<R, F>(
         &self,
@@ -746,29 +933,28 @@ impl<'v, 'a> Arguments<'v, 'a> {
             }
             Some(kwargs) => {
                 if self.names.is_empty() {
-                    match kwargs.downcast_ref_key_string() {
-                        Some(kwargs) => Ok(kwargs.clone()),
-                        None => Err(FunctionError::ArgsValueIsNotString.into()),
+                    if let Some(kwargs) = kwargs.downcast_ref_key_string() {
+                        return Ok(kwargs.clone());
                     }
-                } else {
-                    // We have to insert the names before the kwargs since the iteration order is observable
-                    let mut result = SmallMap::with_capacity(self.names.len() + kwargs.len());
-                    for (k, v) in self.names.iter().zip(self.named) {
-                        result.insert_hashed(Hashed::new_unchecked(k.0.small_hash(), k.1), *v);
-                    }
-                    for (k, v) in kwargs.iter_hashed() {
-                        let s = Arguments::unpack_kwargs_key_as_value(*k.key())?;
-                        let k = Hashed::new_unchecked(k.hash(), s);
-                        let old = result.insert_hashed(k, v);
-                        if unlikely(old.is_some()) {
-                            return Err(FunctionError::RepeatedParameter {
-                                name: s.as_str().to_owned(),
-                            }
-                            .into());
+                    // Otherwise some key is not a string, which the loop below reports.
+                }
+                // We have to insert the names before the kwargs since the iteration order is observable
+                let mut result = SmallMap::with_capacity(self.names.len() + kwargs.len());
+                for (k, v) in self.names.iter().zip(self.named) {
+                    result.insert_hashed(Hashed::new_unchecked(k.0.small_hash(), k.1), *v);
+                }
+                for (k, v) in kwargs.iter_hashed() {
+                    let s = Arguments::unpack_kwargs_key_as_value(*k.key())?;
+                    let k = Hashed::new_unchecked(k.hash(), s);
+                    let old = result.insert_hashed(k, v);
+                    if unlikely(old.is_some()) {
+                        return Err(FunctionError::RepeatedParameter {
+                            name: s.as_str().to_owned(),
                         }
+                        .into());
                     }
-                    Ok(result)
                 }
+                Ok(result)
             }
         }
     }
@@ -807,11 +993,15 @@ impl<'v, 'a> Arguments<'v, 'a> {
         }
     }
 
+    fn kwargs_key_error(k: Value<'v>) -> anyhow::Error {
+        FunctionError::KwArgsKeyIsNotString(k.to_repr(), k.get_type()).into()
+    }
+
     /// Confirm that a key in the `kwargs` field is indeed a string, or [`Err`].
     #[inline(always)]
     pub(crate) fn unpack_kwargs_key_as_value(k: Value<'v>) -> anyhow::Result<StringValue<'v>> {
         match StringValue::new(k) {
-            None => Err(FunctionError::ArgsValueIsNotString.into()),
+            None => Err(Arguments::kwargs_key_error(k)),
             Some(k) => Ok(k),
         }
     }
@@ -998,17 +1188,100 @@ impl Arguments<'_, '_> {
     ) -> anyhow::Result<Option<T>> {
         match x {
             None => Ok(None),
-            Some(x) => Ok(Some(T::unpack_value(x).ok_or_else::<anyhow::Error, _>(
-                || {
-                    ValueError::IncorrectParameterTypeNamedWithExpected(
-                        name.to_owned(),
-                        T::expected(),
-                        x.get_type().to_owned(),
-                    )
-                    .into()
-                },
-            )?)),
+            Some(x) => Ok(Some(T::unpack_named_param(x, name)?)),
+        }
+    }
+}
+
+/// Builds the arguments for calling a function from Rust, including `*args` and `**kwargs`,
+/// which [`Evaluator::eval_function`] doesn't support.
+///
+/// ```
+/// # use starlark::environment::{Globals, Module};
+/// # use starlark::eval::{ArgumentsBuilder, Evaluator};
+/// # use starlark::syntax::{AstModule, Dialect};
+/// # use starlark::values::Value;
+/// let module = Module::new();
+/// let mut eval = Evaluator::new(&module);
+/// let code = "def f(a, b, c, d): return [a, b, c, d]\nf";
+/// let ast = AstModule::parse("f.star", code.to_owned(), &Dialect::Standard).unwrap();
+/// let f = eval.eval_module(ast, &Globals::standard()).unwrap();
+/// let heap = module.heap();
+/// let res = ArgumentsBuilder::new()
+///     .positional([Value::new_int(1)])
+///     .args(heap.alloc((2, 3)))
+///     .named([("d", Value::new_int(4))])
+///     .call(f, &mut eval)
+///     .unwrap();
+/// assert_eq!(res.to_str(), "[1, 2, 3, 4]");
+/// ```
+///
+/// The arguments are passed the same way as `f(*args, **kwargs)` in Starlark, so e.g. if
+/// `args` isn't iterable, or `kwargs` isn't a dictionary, the call fails with the same error.
+#[derive(Debug, Default)]
+pub struct ArgumentsBuilder<'v> {
+    pos: Vec<Value<'v>>,
+    named: Vec<(String, Value<'v>)>,
+    args: Option<Value<'v>>,
+    kwargs: Option<Value<'v>>,
+}
+
+impl<'v> ArgumentsBuilder<'v> {
+    /// A builder with no arguments.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add positional arguments, after any added before.
+    pub fn positional(mut self, values: impl IntoIterator<Item = Value<'v>>) -> Self {
+        self.pos.extend(values);
+        self
+    }
+
+    /// Add named arguments, after any added before. Each name may only be given once.
+    pub fn named<'a>(mut self, pairs: impl IntoIterator<Item = (&'a str, Value<'v>)>) -> Self {
+        self.named
+            .extend(pairs.into_iter().map(|(k, v)| (k.to_owned(), v)));
+        self
+    }
+
+    /// Set the `*args` argument, whose items are passed after the positional arguments.
+    pub fn args(mut self, args: Value<'v>) -> Self {
+        self.args = Some(args);
+        self
+    }
+
+    /// Set the `**kwargs` argument, whose entries are passed as named arguments.
+    pub fn kwargs(mut self, kwargs: Value<'v>) -> Self {
+        self.kwargs = Some(kwargs);
+        self
+    }
+
+    /// Call `function` with the arguments.
+    pub fn call(
+        self,
+        function: Value<'v>,
+        eval: &mut Evaluator<'v, '_>,
+    ) -> anyhow::Result<Value<'v>> {
+        let mut names = Vec::with_capacity(self.named.len());
+        for (name, _) in &self.named {
+            let symbol = Symbol::new(name);
+            if names.iter().any(|(x, _)| *x == symbol) {
+                return Err(FunctionError::RepeatedNamedArgument(name.clone()).into());
+            }
+            names.push((symbol, eval.heap().alloc_str(name)));
         }
+        let named = self.named.map(|x| x.1);
+        eval.invoke(
+            function,
+            &Arguments {
+                pos: &self.pos,
+                named: &named,
+                names: &names,
+                args: self.args,
+                kwargs: self.kwargs,
+            },
+        )
     }
 }
 
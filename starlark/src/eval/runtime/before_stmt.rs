@@ -17,21 +17,67 @@
 
 //! Configuration of `BeforeStmt` instrumentation of bytecode.
 
-use crate::{codemap::FileSpanRef, eval::Evaluator};
+use gazebo::prelude::*;
+
+use crate::{
+    codemap::FileSpanRef,
+    eval::{runtime::evaluator::EvaluatorError, Evaluator},
+};
+
+/// A function to run before each statement.
+#[derive(Clone, Copy, Dupe)]
+pub(crate) enum BeforeStmtFunc<'v, 'a> {
+    Fn(&'a dyn Fn(FileSpanRef, &mut Evaluator<'v, 'a>)),
+    /// An error stops evaluation.
+    Fallible(&'a dyn Fn(FileSpanRef, &mut Evaluator<'v, 'a>) -> anyhow::Result<()>),
+}
+
+impl<'v, 'a> BeforeStmtFunc<'v, 'a> {
+    pub(crate) fn call(
+        self,
+        span: FileSpanRef,
+        eval: &mut Evaluator<'v, 'a>,
+    ) -> anyhow::Result<()> {
+        match self {
+            BeforeStmtFunc::Fn(f) => {
+                f(span, eval);
+                Ok(())
+            }
+            BeforeStmtFunc::Fallible(f) => f(span, eval),
+        }
+    }
+}
 
 /// Configuration of `BeforeStmt` instrumentation of bytecode.
 #[derive(Default)]
 pub(crate) struct BeforeStmt<'v, 'a> {
     /// Functions to run before each statement.
-    pub(crate) before_stmt: Vec<&'a dyn Fn(FileSpanRef, &mut Evaluator<'v, 'a>)>,
+    pub(crate) before_stmt: Vec<BeforeStmtFunc<'v, 'a>>,
     /// Explicitly request generation of `BeforeStmt` instructions
     /// even if no `before_stmt` functions are registered.
     /// This is needed when compiling dependencies of a file to be profiled.
     pub(crate) instrument: bool,
+    /// Number of instrumented statements, and loop iterations if there is a limit,
+    /// executed so far.
+    pub(crate) instructions_executed: u64,
+    /// Stop evaluation once more than this many instructions are executed.
+    pub(crate) instruction_limit: Option<u64>,
 }
 
 impl<'v, 'a> BeforeStmt<'v, 'a> {
     pub(crate) fn enabled(&self) -> bool {
         self.instrument || !self.before_stmt.is_empty()
     }
+
+    /// Count an executed instruction, failing if that goes over the limit.
+    #[inline(always)]
+    pub(crate) fn count_instruction(&mut self) -> anyhow::Result<()> {
+        self.instructions_executed += 1;
+        if let Some(limit) = self.instruction_limit {
+            if self.instructions_executed > limit {
+                return Err(EvaluatorError::InstructionLimitExceeded(limit).into());
+            }
+        }
+        Ok(())
+    }
 }
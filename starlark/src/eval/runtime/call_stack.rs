@@ -32,11 +32,13 @@ use std::{
 
 use gazebo::prelude::*;
 use once_cell::sync::Lazy;
+use thiserror::Error;
 
 use crate::{
     codemap::{CodeMap, FileSpan, Span},
     errors::Frame,
-    values::{ControlError, FrozenRef, Trace, Tracer, Value},
+    eval::fragment::def::function_display_name,
+    values::{FrozenRef, Trace, Tracer, Value, ValueLike},
 };
 
 #[derive(Debug, Clone, Copy, Dupe)]
@@ -62,7 +64,7 @@ impl Display for FrozenFileSpan {
 }
 
 impl FrozenFileSpan {
-    fn to_file_span(&self) -> FileSpan {
+    pub(crate) fn to_file_span(&self) -> FileSpan {
         FileSpan {
             file: (*self.file).dupe(),
             span: self.span,
@@ -98,7 +100,7 @@ impl CheapFrame<'_> {
 
     fn to_frame(&self) -> Frame {
         Frame {
-            name: self.function.to_repr(),
+            name: function_display_name(self.function),
             location: self.location(),
         }
     }
@@ -113,44 +115,54 @@ impl Debug for CheapFrame<'_> {
     }
 }
 
+#[derive(Debug, Error)]
+pub(crate) enum CallStackError {
+    #[error(
+        "Starlark call stack overflow, more than {0} nested calls (too many recursion levels)"
+    )]
+    Overflow(usize),
+}
+
 /// Starlark call stack.
 #[derive(Debug)]
 pub(crate) struct CallStack<'v> {
-    count: usize,
-    stack: [CheapFrame<'v>; MAX_CALLSTACK_RECURSION],
+    stack: Vec<CheapFrame<'v>>,
+    /// The most entries the stack may have, including the module.
+    max_size: usize,
 }
 
 impl<'v> Default for CallStack<'v> {
     fn default() -> Self {
         Self {
-            count: 0,
-            stack: [CheapFrame {
-                function: Value::new_none(),
-                span: None,
-            }; MAX_CALLSTACK_RECURSION],
+            stack: Vec::new(),
+            max_size: DEFAULT_MAX_CALLSTACK_SIZE,
         }
     }
 }
 
 // At 50 we see the C stack overflowing, so limit to 40 (which seems quite
-// low...)
-const MAX_CALLSTACK_RECURSION: usize = 40;
+// low...). Users running on a bigger stack can raise it.
+const DEFAULT_MAX_CALLSTACK_SIZE: usize = 40;
 
 unsafe impl<'v> Trace<'v> for CallStack<'v> {
     fn trace(&mut self, tracer: &Tracer<'v>) {
-        for x in self.stack[0..self.count].iter_mut() {
+        for x in self.stack.iter_mut() {
             x.function.trace(tracer);
         }
-        // Not required, but since we are chosing not to walk those above
-        // the current stack depth, it's good practice to blank those values out
-        for x in self.stack[self.count..].iter_mut() {
-            x.function = Value::new_none();
-            x.span = None;
-        }
     }
 }
 
 impl<'v> CallStack<'v> {
+    /// Is a function currently being called.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.stack.is_empty()
+    }
+
+    /// Set the most entries the stack may have, including the module.
+    pub(crate) fn set_max_size(&mut self, max_size: usize) {
+        self.max_size = max_size;
+    }
+
     /// Push an element to the stack. It is important the each `push` is paired
     /// with a `pop`.
     pub(crate) fn push(
@@ -158,39 +170,33 @@ impl<'v> CallStack<'v> {
         function: Value<'v>,
         span: Option<FrozenRef<'static, FrozenFileSpan>>,
     ) -> anyhow::Result<()> {
-        if unlikely(self.count >= MAX_CALLSTACK_RECURSION) {
-            return Err(ControlError::TooManyRecursionLevel.into());
+        if unlikely(self.stack.len() >= self.max_size) {
+            return Err(CallStackError::Overflow(self.max_size).into());
         }
-        self.stack[self.count] = CheapFrame { function, span };
-        self.count += 1;
+        self.stack.push(CheapFrame { function, span });
         Ok(())
     }
 
     /// Remove the top element from the stack. Called after `push`.
     pub(crate) fn pop(&mut self) {
-        debug_assert!(self.count >= 1);
-        // We could clear the elements, but don't need to bother
-        self.count -= 1;
+        debug_assert!(!self.stack.is_empty());
+        self.stack.pop();
     }
 
     /// The location at the top of the stack. May be `None` if
     /// either there the stack is empty, or the top of the stack lacks location
     /// information (e.g. called from Rust).
     pub(crate) fn top_location(&self) -> Option<FileSpan> {
-        if self.count == 0 {
-            None
-        } else {
-            self.stack[self.count - 1].location()
-        }
+        self.stack.last()?.location()
     }
 
     pub(crate) fn to_diagnostic_frames(&self) -> Vec<Frame> {
         // The first entry is just the entire module, so skip it
-        self.stack[1..self.count].map(CheapFrame::to_frame)
+        self.stack[1..].map(CheapFrame::to_frame)
     }
 
     /// List the entries on the stack as values
     pub(crate) fn to_function_values(&self) -> Vec<Value<'v>> {
-        self.stack[1..self.count].map(|x| x.function)
+        self.stack[1..].map(|x| x.function)
     }
 }
@@ -0,0 +1,213 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use anyhow::Context;
+use gazebo::prelude::*;
+use serde_json::json;
+
+use crate::{
+    codemap::{CodeMap, FileSpanRef, Span},
+    eval::runtime::stmt_profile::FileId,
+    syntax::{ast::Stmt, AstModule},
+};
+
+// When coverage is not enabled, we want this to be small and cheap
+pub(crate) struct StmtCoverage(Option<Box<StmtCoverageData>>);
+
+struct StmtCoverageData {
+    files: HashMap<FileId, CodeMap>,
+    // Statements which were seen, either executed or from the AST, with their hit counts.
+    stmts: HashMap<(FileId, Span), usize>,
+}
+
+impl StmtCoverageData {
+    fn add_codemap(&mut self, codemap: &CodeMap) -> FileId {
+        let id = FileId::new(codemap);
+        self.files.entry(id).or_insert_with(|| codemap.dupe());
+        id
+    }
+
+    fn before_stmt(&mut self, span: Span, codemap: &CodeMap) {
+        let id = self.add_codemap(codemap);
+        *self.stmts.entry((id, span)).or_insert(0) += 1;
+    }
+
+    fn add_module(&mut self, module: &AstModule) {
+        let id = self.add_codemap(&module.codemap);
+        module.visit_stmt_locations(|x| match x.node {
+            // Not compiled to code of their own, so would always show as not covered.
+            Stmt::Pass | Stmt::Load(..) => {}
+            _ => {
+                self.stmts.entry((id, x.span)).or_insert(0);
+            }
+        });
+    }
+
+    fn write(&self, filename: &Path) -> anyhow::Result<()> {
+        let file = File::create(filename).with_context(|| {
+            format!(
+                "When creating coverage output file `{}`",
+                filename.display()
+            )
+        })?;
+        self.write_to(BufWriter::new(file)).with_context(|| {
+            format!(
+                "When writing to coverage output file `{}`",
+                filename.display()
+            )
+        })
+    }
+
+    fn write_to(&self, mut file: impl Write) -> anyhow::Result<()> {
+        let mut items = self
+            .stmts
+            .iter()
+            .map(|((file, span), count)| {
+                let codemap = &self.files[file];
+                (codemap.filename(), codemap.resolve_span(*span), *count)
+            })
+            .collect::<Vec<_>>();
+        items.sort_by_key(|(filename, span, _)| {
+            (
+                *filename,
+                span.begin_line,
+                span.begin_column,
+                span.end_line,
+                span.end_column,
+            )
+        });
+
+        for (filename, span, count) in items {
+            let line = json!({
+                "file": filename,
+                // Lines are 1-based, like in every other tool.
+                "start_line": span.begin_line + 1,
+                "end_line": span.end_line + 1,
+                "hits": count,
+            });
+            writeln!(file, "{}", line)?;
+        }
+        file.flush()?;
+        Ok(())
+    }
+}
+
+impl StmtCoverage {
+    pub fn new() -> Self {
+        Self(None)
+    }
+
+    pub fn enable(&mut self) {
+        self.0 = Some(box StmtCoverageData {
+            files: HashMap::new(),
+            stmts: HashMap::new(),
+        })
+    }
+
+    pub fn before_stmt(&mut self, span: FileSpanRef) {
+        if let Some(box data) = &mut self.0 {
+            data.before_stmt(span.span, span.file)
+        }
+    }
+
+    /// Register all the statements of a module, so those that never execute are reported too.
+    pub fn add_module(&mut self, module: &AstModule) {
+        if let Some(box data) = &mut self.0 {
+            data.add_module(module)
+        }
+    }
+
+    // None = not applicable because not enabled
+    pub fn write(&self, filename: &Path) -> Option<anyhow::Result<()>> {
+        self.0.as_ref().map(|data| data.write(filename))
+    }
+
+    #[cfg(test)]
+    pub(crate) fn write_to_string(&self) -> Option<String> {
+        let data = self.0.as_ref()?;
+        let mut res = Vec::new();
+        data.write_to(&mut res).unwrap();
+        Some(String::from_utf8(res).unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value as JsonValue;
+
+    use crate::{
+        environment::{Globals, Module},
+        eval::{Evaluator, ProfileMode},
+        syntax::{AstModule, Dialect},
+    };
+
+    // Run the program with coverage, and return (start_line, hits) pairs.
+    fn coverage(program: &str) -> Vec<(u64, u64)> {
+        let module = Module::new();
+        let globals = Globals::standard();
+        let mut eval = Evaluator::new(&module);
+        eval.enable_profile(&ProfileMode::Coverage);
+        let ast = AstModule::parse("cov.star", program.to_owned(), &Dialect::Extended).unwrap();
+        eval.eval_module(ast, &globals).unwrap();
+        let out = eval.stmt_coverage.write_to_string().unwrap();
+        out.lines()
+            .map(|line| {
+                let v: JsonValue = serde_json::from_str(line).unwrap();
+                assert_eq!(v["file"], "cov.star");
+                (
+                    v["start_line"].as_u64().unwrap(),
+                    v["hits"].as_u64().unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_coverage_branch_not_taken() {
+        let res = coverage(
+            r#"
+x = 1
+if x == 2:
+    y = 3
+z = [i for i in range(10)]
+"#,
+        );
+        assert_eq!(res, vec![(2, 1), (3, 1), (4, 0), (5, 1)]);
+    }
+
+    #[test]
+    fn test_coverage_def_never_called() {
+        let res = coverage(
+            r#"
+def f():
+    return 1
+def g():
+    return 2
+g()
+g()
+"#,
+        );
+        assert_eq!(res, vec![(2, 1), (3, 0), (4, 1), (5, 2), (6, 1), (7, 1)]);
+    }
+}
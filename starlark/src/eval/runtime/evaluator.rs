@@ -28,20 +28,25 @@ use thiserror::Error;
 use crate::{
     codemap::{FileSpan, FileSpanRef},
     collections::{alloca::Alloca, string_pool::StringPool},
-    environment::{slots::ModuleSlotId, EnvironmentError, FrozenModuleRef, Module},
+    environment::{
+        slots::ModuleSlotId, EnvironmentError, ExtraMethods, FrozenModuleRef, Methods, Module,
+    },
     errors::{Diagnostic, Frame},
     eval::{
         bc::frame::BcFramePtr,
         fragment::def::DefInfo,
         runtime::{
             bc_profile::BcProfile,
-            before_stmt::BeforeStmt,
+            before_stmt::{BeforeStmt, BeforeStmtFunc},
             call_stack::{CallStack, FrozenFileSpan},
+            coverage::StmtCoverage,
             flame_profile::FlameProfile,
+            heap_line_profile::HeapLineProfile,
             heap_profile::{HeapProfile, HeapProfileFormat},
             profile::ProfileMode,
             slots::LocalSlotId,
             stmt_profile::StmtProfile,
+            value_provenance::ValueProvenance,
         },
         FileLoader,
     },
@@ -50,8 +55,8 @@ use crate::{
         extra::{PrintHandler, StderrPrintHandler},
     },
     values::{
-        value_captured_get, FrozenHeap, FrozenRef, Heap, Trace, Tracer, Value, ValueCaptured,
-        ValueLike,
+        unbound::MaybeUnboundValue, value_captured_get, FrozenHeap, FrozenRef, GcStats, Heap,
+        ReprLimits, Trace, Tracer, Value, ValueCaptured, ValueLike,
     },
 };
 
@@ -64,12 +69,18 @@ pub(crate) enum EvaluatorError {
     StmtProfilingNotEnabled,
     #[error("Can't call `write_flame_profile` unless you first call `enable_flame_profile`.")]
     FlameProfilingNotEnabled,
+    #[error("Can't write coverage unless you first enable coverage profiling.")]
+    CoverageNotEnabled,
     #[error("Can't call `write_bc_profile` unless you first call `enable_bc_profile`.")]
     BcProfilingNotEnabled,
+    #[error("Evaluation exceeded the limit of {0} instructions")]
+    InstructionLimitExceeded(u64),
+    #[error("Can't evaluate a module with syntax errors")]
+    SyntaxErrors,
 }
 
-/// Number of bytes to allocate between GC's.
-pub(crate) const GC_THRESHOLD: usize = 100000;
+/// Default number of bytes to allocate between GC's.
+const GC_THRESHOLD: usize = 100000;
 
 /// Holds everything about an ongoing evaluation (local variables, globals, module resolution etc).
 pub struct Evaluator<'v, 'a> {
@@ -96,12 +107,22 @@ pub struct Evaluator<'v, 'a> {
     // If true, the interpreter prints to stderr on GC.
     // This is used for debugging.
     pub(crate) verbose_gc: bool,
+    // Check the declared return types of native functions.
+    pub(crate) runtime_typecheck: bool,
     // Size of the heap when we should next perform a GC.
     pub(crate) next_gc_level: usize,
+    // Number of bytes to allocate after a GC before performing the next one.
+    pub(crate) gc_threshold: usize,
     // Extra functions to run on each statement, usually empty
     pub(crate) before_stmt: BeforeStmt<'v, 'a>,
     // Used for line profiling
     stmt_profile: StmtProfile,
+    // Used for heap profiling by line
+    heap_line_profile: HeapLineProfile,
+    // Where lists and dicts were created, if enabled
+    pub(crate) value_provenance: ValueProvenance,
+    // Used for statement coverage
+    pub(crate) stmt_coverage: StmtCoverage,
     // Bytecode profile.
     pub(crate) bc_profile: BcProfile,
     // Used for stack-like allocation
@@ -118,6 +139,10 @@ pub struct Evaluator<'v, 'a> {
     pub(crate) breakpoint_handler: Option<Box<dyn Fn() -> Box<dyn BreakpointConsole>>>,
     /// Use in implementation of `print` function.
     pub(crate) print_handler: &'a (dyn PrintHandler + 'a),
+    /// Limits on reprs in error messages and `print` output.
+    pub(crate) repr_limits: Option<ReprLimits>,
+    /// Methods added to built-in types by the embedder.
+    pub(crate) extra_methods: Option<&'a ExtraMethods>,
     // The Starlark-level call-stack of functions.
     // Must go last because it's quite a big structure
     pub(crate) call_stack: CallStack<'v>,
@@ -125,8 +150,7 @@ pub struct Evaluator<'v, 'a> {
 
 unsafe impl<'v> Trace<'v> for Evaluator<'v, '_> {
     fn trace(&mut self, tracer: &Tracer<'v>) {
-        let mut roots = self.module_env.slots().get_slots_mut();
-        roots.trace(tracer);
+        self.module_env.trace(tracer);
         self.current_frame.trace(tracer);
         self.call_stack.trace(tracer);
         self.flame_profile.trace(tracer);
@@ -148,10 +172,14 @@ impl<'v, 'a> Evaluator<'v, 'a> {
             extra: None,
             extra_v: None,
             next_gc_level: GC_THRESHOLD,
+            gc_threshold: GC_THRESHOLD,
             disable_gc: false,
             alloca: Alloca::new(),
             heap_profile: HeapProfile::new(),
             stmt_profile: StmtProfile::new(),
+            heap_line_profile: HeapLineProfile::default(),
+            value_provenance: ValueProvenance::default(),
+            stmt_coverage: StmtCoverage::new(),
             bc_profile: BcProfile::new(),
             flame_profile: FlameProfile::new(),
             heap_or_flame_profile: false,
@@ -160,7 +188,10 @@ impl<'v, 'a> Evaluator<'v, 'a> {
             string_pool: StringPool::default(),
             breakpoint_handler: None,
             print_handler: &StderrPrintHandler,
+            repr_limits: Some(ReprLimits::default()),
+            extra_methods: None,
             verbose_gc: false,
+            runtime_typecheck: cfg!(debug_assertions),
         }
     }
 
@@ -171,11 +202,113 @@ impl<'v, 'a> Evaluator<'v, 'a> {
         self.disable_gc = true;
     }
 
+    /// Check the values returned by native functions against their declared
+    /// `#[starlark(return_type("..."))]`. Enabled by default in debug builds.
+    pub fn enable_runtime_typecheck(&mut self, enable: bool) {
+        self.runtime_typecheck = enable;
+    }
+
     /// Enable GC logging.
     pub fn verbose_gc(&mut self) {
         self.verbose_gc = true;
     }
 
+    /// Set how many bytes may be allocated after a garbage collection before
+    /// the next one is triggered. Defaults to 100,000 bytes.
+    pub fn set_gc_threshold(&mut self, bytes: usize) {
+        self.gc_threshold = bytes;
+        self.next_gc_level = self.heap().allocated_bytes().saturating_add(bytes);
+    }
+
+    /// Limit how many bytes the module heap may have allocated. Allocations are checked
+    /// against the limit, and once one crosses it evaluation stops with an error at the start
+    /// of the next statement or loop iteration, while operations building large values,
+    /// such as repeating a list with `*`, fail before allocating.
+    /// Freezing and the frozen heap don't count towards the limit.
+    ///
+    /// This function may have no effect if called mid evaluation.
+    pub fn set_max_heap_bytes(&mut self, bytes: usize) {
+        self.heap().set_max_bytes(bytes);
+        // The limit is checked before each statement.
+        self.before_stmt.instrument = true;
+    }
+
+    /// Limit how many instructions evaluation may execute, where running a statement or
+    /// an iteration of a loop or comprehension counts as one instruction, so the count is
+    /// the same on every run and platform. Once the limit is crossed evaluation stops with
+    /// an error at the start of the next statement or iteration.
+    /// Native functions calling back into Starlark with this evaluator share the same budget.
+    ///
+    /// Statements are counted by instrumentation added when code is compiled, so only
+    /// statements compiled after this call are counted. Code compiled earlier, such as
+    /// functions from a module which was loaded and frozen without instrumentation, only has
+    /// its loop and comprehension iterations counted, so can run many more statements than the
+    /// limit suggests. To count everything, evaluate loaded modules after calling
+    /// [`enable_before_stmt_instrumentation`](Evaluator::enable_before_stmt_instrumentation).
+    /// This function may have no effect if called mid evaluation.
+    pub fn set_instruction_limit(&mut self, limit: u64) {
+        self.before_stmt.instruction_limit = Some(limit);
+        self.before_stmt.instrument = true;
+    }
+
+    /// The number of instructions executed so far, as counted by
+    /// [`set_instruction_limit`](Evaluator::set_instruction_limit).
+    /// Statements are only counted when `before_stmt` instrumentation is enabled,
+    /// e.g. by setting a limit, otherwise this is zero, and loop iterations only
+    /// when there is a limit.
+    pub fn instructions_executed(&self) -> u64 {
+        self.before_stmt.instructions_executed
+    }
+
+    /// Limit the length and nesting depth of the reprs generated during evaluation, e.g. for
+    /// error messages or `print`, truncating them with `...<truncated>`. `None` removes the
+    /// limits. Explicit conversions such as `repr()`, `str()` and string formatting are never
+    /// limited. Defaults to [`ReprLimits::default`].
+    pub fn set_repr_limits(&mut self, limits: Option<ReprLimits>) {
+        self.repr_limits = limits;
+    }
+
+    /// Limit how deeply function calls may be nested, counting the module being evaluated
+    /// as one, after which calls fail with a call stack overflow error. Defaults to 40.
+    /// Each nested call also uses the native stack, and 40 is about as deep as the default
+    /// stack of the main thread allows, so raising the limit requires evaluating on a thread
+    /// with a larger stack, e.g. 64MiB for a limit of a hundred. The limit is not checked
+    /// against the native stack, so a limit too large for the thread will crash it.
+    pub fn set_max_call_stack_size(&mut self, size: usize) {
+        self.call_stack.set_max_size(size);
+    }
+
+    /// Statistics about the garbage collections performed on the module heap.
+    pub fn gc_stats(&self) -> GcStats {
+        self.heap().gc_stats()
+    }
+
+    /// Record where each list and dict created by a literal or comprehension from now on
+    /// was created, so it can be found with [`value_origin`](Evaluator::value_origin), and
+    /// errors from type annotations on function parameters, or from unpacking the parameters
+    /// of native functions, mention it. Intended for debugging, as it costs time and memory.
+    /// The origins are kept across garbage collections, but not when the values are frozen.
+    /// Passing `false` forgets all the origins.
+    ///
+    /// Like [`before_stmt`](Evaluator::before_stmt), this only affects code compiled after
+    /// the call, so there is no cost when it is never enabled, but values created by
+    /// functions defined earlier (e.g. in loaded modules) have no known origin.
+    pub fn enable_value_provenance(&mut self, enable: bool) {
+        self.value_provenance.enable(enable);
+    }
+
+    /// Where a list or dict was created, if [`enable_value_provenance`](Evaluator::enable_value_provenance)
+    /// was enabled at the time.
+    pub fn value_origin(&self, value: Value<'v>) -> Option<FileSpan> {
+        self.value_provenance.origin(value)
+    }
+
+    /// Add the [`value_origin`](Evaluator::value_origin) of `value` to an error about it,
+    /// if it is known. Useful for native functions checking their arguments.
+    pub fn add_value_origin(&self, error: anyhow::Error, value: Value<'v>) -> anyhow::Error {
+        self.value_provenance.add_origin(error, value)
+    }
+
     /// Set the [`FileLoader`] used to resolve `load()` statements.
     /// A list of all load statements can be obtained through
     /// [`AstModule::loads`](crate::syntax::AstModule::loads).
@@ -195,14 +328,29 @@ impl<'v, 'a> Evaluator<'v, 'a> {
                 // to store a complete list of what happened in linear order.
                 self.disable_gc = true;
             }
+            ProfileMode::HeapAllocatedByLine => {
+                // We only need the heap profile to mark calls, so we know which
+                // statement to return to.
+                self.heap_profile.enable();
+                self.heap_or_flame_profile = true;
+                self.heap_line_profile.enable();
+                self.before_stmt(&|span, eval| {
+                    let heap = eval.heap();
+                    eval.heap_line_profile.before_stmt(span, heap)
+                });
+            }
             ProfileMode::Stmt => {
                 self.stmt_profile.enable();
                 self.before_stmt(&|span, eval| eval.stmt_profile.before_stmt(span));
             }
-            ProfileMode::Flame => {
+            ProfileMode::Flame | ProfileMode::TimeFlame => {
                 self.flame_profile.enable();
                 self.heap_or_flame_profile = true;
             }
+            ProfileMode::Coverage => {
+                self.stmt_coverage.enable();
+                self.before_stmt(&|span, eval| eval.stmt_coverage.before_stmt(span));
+            }
             ProfileMode::Bytecode => {
                 self.bc_profile.enable_1();
             }
@@ -212,7 +360,6 @@ impl<'v, 'a> Evaluator<'v, 'a> {
         }
     }
 
-
     /// Generate instructions to invoke before stmt callbacks when evaluating the module,
     /// even if this module does not use any such callbacks.
     ///
@@ -257,6 +404,10 @@ impl<'v, 'a> Evaluator<'v, 'a> {
                     HeapProfileFormat::FlameGraph,
                 )
                 .unwrap_or_else(|| Err(EvaluatorError::HeapProfilingNotEnabled.into())),
+            ProfileMode::HeapAllocatedByLine => self
+                .heap_line_profile
+                .write(filename.as_ref(), self.heap())
+                .unwrap_or_else(|| Err(EvaluatorError::HeapProfilingNotEnabled.into())),
             ProfileMode::Stmt => self
                 .stmt_profile
                 .write(filename.as_ref())
@@ -268,6 +419,14 @@ impl<'v, 'a> Evaluator<'v, 'a> {
                 .flame_profile
                 .write(filename.as_ref())
                 .unwrap_or_else(|| Err(EvaluatorError::FlameProfilingNotEnabled.into())),
+            ProfileMode::TimeFlame => self
+                .flame_profile
+                .write_json(filename.as_ref())
+                .unwrap_or_else(|| Err(EvaluatorError::FlameProfilingNotEnabled.into())),
+            ProfileMode::Coverage => self
+                .stmt_coverage
+                .write(filename.as_ref())
+                .unwrap_or_else(|| Err(EvaluatorError::CoverageNotEnabled.into())),
         }
     }
 
@@ -279,12 +438,19 @@ impl<'v, 'a> Evaluator<'v, 'a> {
     }
 
     /// Obtain the current call-stack, suitable for use with [`Diagnostic`].
+    /// Each [`Frame`] is named after the function being called, with the location
+    /// it was called from, if available. The outermost call is first, and the module
+    /// itself is not included. Inside a native function, the last frame is the
+    /// call to the native function.
     pub fn call_stack(&self) -> Vec<Frame> {
         self.call_stack.to_diagnostic_frames()
     }
 
     /// Obtain the top location on the call-stack. May be [`None`] if the
     /// call happened via native functions.
+    /// Inside a native function, this is the location the native function was called from,
+    /// and is cheaper than [`call_stack`](Evaluator::call_stack), since it only resolves
+    /// a single location.
     pub fn call_stack_top_location(&self) -> Option<FileSpan> {
         self.call_stack.top_location()
     }
@@ -293,9 +459,24 @@ impl<'v, 'a> Evaluator<'v, 'a> {
     /// A list of all possible statements can be obtained in advance by
     /// [`AstModule::stmt_locations`](crate::syntax::AstModule::stmt_locations).
     ///
-    /// This function may have no effect is called mid evaluation.
+    /// This function may have no effect if called mid evaluation.
     pub fn before_stmt(&mut self, f: &'a dyn Fn(FileSpanRef, &mut Evaluator<'v, 'a>)) {
-        self.before_stmt.before_stmt.push(f)
+        self.before_stmt.before_stmt.push(BeforeStmtFunc::Fn(f))
+    }
+
+    /// Like [`before_stmt`](Evaluator::before_stmt), but if the function returns an error
+    /// then evaluation stops, and the error is returned (e.g. from
+    /// [`eval_module`](Evaluator::eval_module)) with the span of the statement attached.
+    /// Useful to cancel a long running evaluation.
+    ///
+    /// This function may have no effect if called mid evaluation.
+    pub fn before_stmt_fallible(
+        &mut self,
+        f: &'a dyn Fn(FileSpanRef, &mut Evaluator<'v, 'a>) -> anyhow::Result<()>,
+    ) {
+        self.before_stmt
+            .before_stmt
+            .push(BeforeStmtFunc::Fallible(f))
     }
 
     /// Set the handler invoked when `print` function is used.
@@ -303,6 +484,63 @@ impl<'v, 'a> Evaluator<'v, 'a> {
         self.print_handler = handler;
     }
 
+    /// Set the methods added to built-in types, which are found after the built-in
+    /// methods and attributes of the type.
+    pub fn set_extra_methods(&mut self, methods: &'a ExtraMethods) {
+        self.extra_methods = Some(methods);
+    }
+
+    /// The methods added to the type of `x` with
+    /// [`set_extra_methods`](Evaluator::set_extra_methods).
+    fn extra_methods_of(&self, x: Value<'v>) -> Option<&'a Methods> {
+        self.extra_methods?.get(x.get_type())
+    }
+
+    /// Get `attribute` of `x` as `x.attribute` would, looking at the type of `x`
+    /// and then the methods from [`set_extra_methods`](Evaluator::set_extra_methods).
+    /// Used by `getattr`, and agrees with [`has_attr`](Evaluator::has_attr)
+    /// and [`dir_attr`](Evaluator::dir_attr).
+    pub(crate) fn get_attr(
+        &self,
+        x: Value<'v>,
+        attribute: &str,
+    ) -> anyhow::Result<Option<Value<'v>>> {
+        if let Some(v) = x.get_attr(attribute, self.heap())? {
+            return Ok(Some(v));
+        }
+        let method = self
+            .extra_methods_of(x)
+            .and_then(|m| m.get_frozen(attribute));
+        match method {
+            None => Ok(None),
+            Some(method) => Ok(Some(MaybeUnboundValue::new(method).bind(x, self.heap())?)),
+        }
+    }
+
+    /// Whether [`get_attr`](Evaluator::get_attr) would find `attribute`. Used by `hasattr`.
+    /// Does the same lookup, but doesn't compute attributes defined by
+    /// [`get_methods`](crate::values::StarlarkValue::get_methods), so never fails.
+    pub(crate) fn has_attr(&self, x: Value<'v>, attribute: &str) -> bool {
+        // Types needn't implement `has_attr`, so ask those which don't for the attribute.
+        let aref = x.get_ref();
+        x.has_attr(attribute)
+            || aref.get_attr_method(x, attribute, self.heap()).is_some()
+            || aref.get_attr(attribute, self.heap()).is_some()
+            || self
+                .extra_methods_of(x)
+                .map_or(false, |m| m.get(attribute).is_some())
+    }
+
+    /// The names [`get_attr`](Evaluator::get_attr) would find, sorted. Used by `dir`.
+    pub(crate) fn dir_attr(&self, x: Value<'v>) -> Vec<String> {
+        let mut res = x.dir_attr();
+        if let Some(methods) = self.extra_methods_of(x) {
+            res.extend(methods.names());
+            res.sort();
+        }
+        res
+    }
+
     pub(crate) fn check_types(&self) -> bool {
         // We currently always check types. We suspect that for performance reasons one day
         // we'll want to make it optional, so guard the relevant places behind this test.
@@ -366,6 +604,11 @@ impl<'v, 'a> Evaluator<'v, 'a> {
         res
     }
 
+    /// The [`Module`] being evaluated, whose variables are set by top-level statements.
+    pub fn module(&self) -> &'v Module {
+        self.module_env
+    }
+
     /// The active heap where [`Value`]s are allocated.
     pub fn heap(&self) -> &'v Heap {
         self.module_env.heap()
@@ -515,7 +758,22 @@ impl<'v, 'a> Evaluator<'v, 'a> {
                 self.heap().allocated_bytes()
             );
         }
-        self.heap().garbage_collect(|tracer| self.trace(tracer));
+        if unlikely(self.heap_line_profile.enabled() || self.value_provenance.enabled()) {
+            // Take the profile out, since tracing needs all of `self`.
+            let mut profile = mem::take(&mut self.heap_line_profile);
+            let mut provenance = mem::take(&mut self.value_provenance);
+            let mut provenance_gc = provenance.gc();
+            profile.garbage_collect(
+                self.heap(),
+                |tracer| self.trace(tracer),
+                |address, x| provenance_gc.visit(address, x),
+            );
+            provenance_gc.finish();
+            self.heap_line_profile = profile;
+            self.value_provenance = provenance;
+        } else {
+            self.heap().garbage_collect(|tracer| self.trace(tracer));
+        }
         if self.verbose_gc {
             eprintln!(
                 "Starlark: GC complete. Allocated bytes: {}.",
@@ -524,6 +782,32 @@ impl<'v, 'a> Evaluator<'v, 'a> {
         }
     }
 
+    /// Perform a garbage collection if one is allowed at this point,
+    /// returning `true` if it happened. It is not allowed if GC has been disabled
+    /// with [`disable_gc`](Evaluator::disable_gc), if [`extra_v`](Evaluator::extra_v)
+    /// is set, or if a function is being evaluated (the call stack is not empty),
+    /// so the typical use is between calls to [`eval_module`](Evaluator::eval_module).
+    ///
+    /// The values which survive are those reachable from the variables of the
+    /// module being evaluated, everything else is reclaimed.
+    ///
+    /// # Safety
+    ///
+    /// As with [`garbage_collect`](Evaluator::garbage_collect), any [`Value`] held
+    /// outside the module, e.g. the result of a previous evaluation, will be invalid
+    /// after a collection, and using it will lead to a segfault.
+    pub unsafe fn try_gc(&mut self) -> bool {
+        if self.disable_gc || self.extra_v.is_some() || !self.call_stack.is_empty() {
+            return false;
+        }
+        self.garbage_collect();
+        self.next_gc_level = self
+            .heap()
+            .allocated_bytes()
+            .saturating_add(self.gc_threshold);
+        true
+    }
+
     /// Note that the `Drop` for the `T` will not be called. That's safe if there is no `Drop`,
     /// or you call it yourself.
     #[inline(always)]
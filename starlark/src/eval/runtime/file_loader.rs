@@ -18,12 +18,22 @@
 //! Define variants of the evaluation function with different support
 //! for the `load(...)` statement.
 
-use std::collections::HashMap;
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    error::Error,
+    fmt::{self, Display},
+};
 
 use anyhow::anyhow;
 use gazebo::prelude::*;
+use thiserror::Error;
 
-use crate::environment::FrozenModule;
+use crate::{
+    environment::{FrozenModule, Globals, Module},
+    eval::Evaluator,
+    syntax::AstModule,
+};
 
 /// A trait for turning a `path` given by a `load()` statement into a [`FrozenModule`].
 pub trait FileLoader {
@@ -52,3 +62,196 @@ impl<'a> FileLoader for ReturnFileLoader<'a> {
         }
     }
 }
+
+#[derive(Debug, Error)]
+enum AstFileLoaderError {
+    #[error("Cyclic load of modules: {0}")]
+    Cycle(String),
+}
+
+// Not using `thiserror`, since `anyhow::Error` isn't an `Error`, so can't be a `#[source]`.
+// We want a distinct type (rather than `anyhow` context) so the error is attributed to
+// the `load` statement in the parent, rather than looking like an error in the parent.
+#[derive(Debug)]
+struct LoadModuleError {
+    path: String,
+    error: anyhow::Error,
+}
+
+impl Display for LoadModuleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Error loading module `{}`", self.path)
+    }
+}
+
+impl Error for LoadModuleError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(self.error.as_ref())
+    }
+}
+
+/// A trait for obtaining the source of a module given by a `load()` statement,
+/// for modules which are evaluated on demand by [`AstFileLoader`].
+pub trait AstLoader {
+    /// Parse the module given by the load statement `path`.
+    fn load_ast(&self, path: &str) -> anyhow::Result<AstModule>;
+}
+
+/// [`FileLoader`] which evaluates and freezes modules the first time they are loaded,
+/// obtaining their source from an [`AstLoader`].
+///
+/// Each module is evaluated at most once, even if it is loaded by several modules,
+/// and subsequent loads return the same [`FrozenModule`]. Cyclic loads are reported as errors.
+/// Errors evaluating a module are reported at the `load()` statement which loaded it.
+pub struct AstFileLoader<'a> {
+    loader: &'a dyn AstLoader,
+    globals: &'a Globals,
+    loaded: RefCell<HashMap<String, FrozenModule>>,
+    // The modules currently being evaluated, outermost first
+    loading: RefCell<Vec<String>>,
+}
+
+impl<'a> AstFileLoader<'a> {
+    /// Create a new [`AstFileLoader`], where loaded modules are evaluated with the given `globals`.
+    pub fn new(loader: &'a dyn AstLoader, globals: &'a Globals) -> Self {
+        Self {
+            loader,
+            globals,
+            loaded: RefCell::new(HashMap::new()),
+            loading: RefCell::new(Vec::new()),
+        }
+    }
+
+    fn eval(&self, path: &str) -> anyhow::Result<FrozenModule> {
+        let ast = self.loader.load_ast(path)?;
+        let module = Module::new();
+        {
+            let mut eval = Evaluator::new(&module);
+            eval.set_loader(self);
+            eval.eval_module(ast, self.globals)?;
+        }
+        module.freeze()
+    }
+}
+
+impl<'a> FileLoader for AstFileLoader<'a> {
+    fn load(&self, path: &str) -> anyhow::Result<FrozenModule> {
+        if let Some(module) = self.loaded.borrow().get(path) {
+            return Ok(module.dupe());
+        }
+        {
+            let loading = self.loading.borrow();
+            if let Some(i) = loading.iter().position(|x| x == path) {
+                let mut cycle = loading[i..].to_vec();
+                cycle.push(path.to_owned());
+                return Err(AstFileLoaderError::Cycle(cycle.join(" -> ")).into());
+            }
+        }
+
+        self.loading.borrow_mut().push(path.to_owned());
+        let res = self.eval(path);
+        self.loading.borrow_mut().pop();
+
+        let module = res.map_err(|error| LoadModuleError {
+            path: path.to_owned(),
+            error,
+        })?;
+        self.loaded
+            .borrow_mut()
+            .insert(path.to_owned(), module.dupe());
+        Ok(module)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{errors::Diagnostic, syntax::Dialect};
+
+    struct MapLoader {
+        sources: HashMap<&'static str, &'static str>,
+        // Every module we were asked to parse, in order
+        requests: RefCell<Vec<String>>,
+    }
+
+    impl MapLoader {
+        fn new(sources: &[(&'static str, &'static str)]) -> Self {
+            Self {
+                sources: sources.iter().copied().collect(),
+                requests: RefCell::new(Vec::new()),
+            }
+        }
+
+        fn run(&self, program: &str) -> anyhow::Result<()> {
+            let globals = Globals::standard();
+            let loader = AstFileLoader::new(self, &globals);
+            let module = Module::new();
+            let mut eval = Evaluator::new(&module);
+            eval.set_loader(&loader);
+            let ast = AstModule::parse("main.star", program.to_owned(), &Dialect::Extended)?;
+            eval.eval_module(ast, &globals)?;
+            Ok(())
+        }
+    }
+
+    impl AstLoader for MapLoader {
+        fn load_ast(&self, path: &str) -> anyhow::Result<AstModule> {
+            self.requests.borrow_mut().push(path.to_owned());
+            match self.sources.get(path) {
+                Some(source) => AstModule::parse(path, (*source).to_owned(), &Dialect::Extended),
+                None => Err(anyhow!("No module `{}`", path)),
+            }
+        }
+    }
+
+    #[test]
+    fn test_ast_loader_diamond() {
+        let loader = MapLoader::new(&[
+            ("b.star", "load('d.star', 'd')\nb = d + 1"),
+            ("c.star", "load('d.star', 'd')\nc = d + 2"),
+            ("d.star", "d = 10"),
+        ]);
+        loader
+            .run("load('b.star', 'b')\nload('c.star', 'c')\nassert_eq(b + c, 23)")
+            .unwrap();
+        assert_eq!(
+            *loader.requests.borrow(),
+            vec!["b.star", "d.star", "c.star"]
+        );
+    }
+
+    #[test]
+    fn test_ast_loader_cycle() {
+        let loader = MapLoader::new(&[
+            ("a.star", "load('b.star', 'b')\na = 1"),
+            ("b.star", "load('a.star', 'a')\nb = 1"),
+        ]);
+        let err = loader.run("load('a.star', 'a')").unwrap_err();
+        assert!(
+            format!("{:#}", err).contains("a.star -> b.star -> a.star"),
+            "{:#}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_ast_loader_error_span() {
+        let loader = MapLoader::new(&[("b.star", "fail('oops')")]);
+        let err = loader.run("x = 1\nload('b.star', 'b')").unwrap_err();
+        let span = err
+            .downcast_ref::<Diagnostic>()
+            .unwrap()
+            .span
+            .as_ref()
+            .unwrap();
+        assert_eq!(span.file.filename(), "main.star");
+        assert_eq!(span.source_span(), "load('b.star', 'b')");
+        let message = format!("{:#}", err);
+        assert!(
+            message.contains("Error loading module `b.star`"),
+            "{}",
+            message
+        );
+        assert!(message.contains("oops"), "{}", message);
+    }
+}
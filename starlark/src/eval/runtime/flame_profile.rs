@@ -27,9 +27,13 @@ use std::{
 
 use anyhow::Context;
 use gazebo::prelude::*;
+use serde::Serialize;
 
 use crate as starlark;
-use crate::values::{Trace, Tracer, Value};
+use crate::{
+    eval::{fragment::def::function_display_name, Def, FrozenDef},
+    values::{Trace, Tracer, Value},
+};
 
 /// Index into FlameData.values
 #[derive(Hash, PartialEq, Eq, Clone, Copy, Dupe)]
@@ -134,7 +138,10 @@ impl<'a> Stacks<'a> {
         if count > 0 {
             writeln!(file, "{} {}", buffer, count)?;
         }
-        for x in self.children.values() {
+        // Sort by name, so the output doesn't depend on the order of the hash map.
+        let mut children: Vec<_> = self.children.iter().collect();
+        children.sort_by_key(|(i, x)| (x.name, i.0));
+        for (_, x) in children {
             x.render_with_buffer(file, buffer)?;
         }
         buffer.truncate(start_len);
@@ -147,6 +154,95 @@ impl<'a> Stacks<'a> {
     }
 }
 
+/// The time spent in a single function, for the JSON output.
+#[derive(Serialize)]
+struct FunctionSummary {
+    /// The name of the function, qualified with the file name if it was defined in Starlark.
+    name: String,
+    /// Where the function was defined, if it was defined in Starlark.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    location: Option<String>,
+    calls: usize,
+    /// Time in seconds spent in this function, including the functions it calls.
+    time: f64,
+    /// Time in seconds spent in this function, excluding the functions it calls.
+    self_time: f64,
+}
+
+#[derive(Default, Clone, Copy)]
+struct Totals {
+    calls: usize,
+    time: Duration,
+    self_time: Duration,
+}
+
+fn location(x: Value) -> Option<String> {
+    let span = match x.downcast_ref::<FrozenDef>() {
+        Some(x) => x.location(),
+        None => x.downcast_ref::<Def>()?.location(),
+    };
+    Some(span.to_string())
+}
+
+fn summarise(x: &FlameData) -> Vec<FunctionSummary> {
+    // Each evaluation of a nested `def` or `lambda` creates a new function value,
+    // so aggregate by name and definition location rather than by value.
+    let mut keys: HashMap<(String, Option<String>), usize> = HashMap::new();
+    let mut functions: Vec<(String, Option<String>)> = Vec::new();
+    let ids: Vec<usize> = x
+        .values
+        .iter()
+        .map(|v| {
+            let key = (function_display_name(*v), location(*v));
+            *keys.entry(key.clone()).or_insert_with(|| {
+                functions.push(key);
+                functions.len() - 1
+            })
+        })
+        .collect();
+
+    let mut totals = vec![Totals::default(); functions.len()];
+    // The function, when it was entered, and the time spent in functions it called
+    let mut stack: Vec<(usize, Instant, Duration)> = Vec::new();
+    for (frame, time) in &x.frames {
+        match frame {
+            Frame::Push(i) => stack.push((ids[i.0], *time, Duration::default())),
+            Frame::Pop => {
+                // Might not have a matching push if profiling was enabled mid-call
+                if let Some((i, start, children)) = stack.pop() {
+                    let elapsed = time.duration_since(start);
+                    let res = &mut totals[i];
+                    res.calls += 1;
+                    res.self_time += elapsed.saturating_sub(children);
+                    // For recursive calls, the outermost call already covers this time
+                    if !stack.iter().any(|x| x.0 == i) {
+                        res.time += elapsed;
+                    }
+                    if let Some(parent) = stack.last_mut() {
+                        parent.2 += elapsed;
+                    }
+                }
+            }
+        }
+    }
+
+    let mut res: Vec<FunctionSummary> = functions
+        .into_iter()
+        .zip(totals)
+        .filter(|(_, t)| t.calls > 0)
+        .map(|((name, location), t)| FunctionSummary {
+            name,
+            location,
+            calls: t.calls,
+            time: t.time.as_secs_f64(),
+            self_time: t.self_time.as_secs_f64(),
+        })
+        .collect();
+    // Most expensive first
+    res.sort_by(|a, b| b.time.total_cmp(&a.time).then_with(|| a.name.cmp(&b.name)));
+    res
+}
+
 impl<'v> FlameProfile<'v> {
     pub(crate) fn new() -> Self {
         Self(None)
@@ -185,14 +281,25 @@ impl<'v> FlameProfile<'v> {
     pub(crate) fn write(&self, filename: &Path) -> Option<anyhow::Result<()>> {
         self.0
             .as_ref()
-            .map(|box x| Self::write_enabled(x, filename))
+            .map(|box x| Self::write_enabled(x, filename, Self::write_profile_to))
     }
 
-    fn write_enabled(x: &FlameData, filename: &Path) -> anyhow::Result<()> {
+    /// Write the per-function summary as JSON.
+    pub(crate) fn write_json(&self, filename: &Path) -> Option<anyhow::Result<()>> {
+        self.0
+            .as_ref()
+            .map(|box x| Self::write_enabled(x, filename, Self::write_json_to))
+    }
+
+    fn write_enabled(
+        x: &FlameData,
+        filename: &Path,
+        write: fn(&FlameData, File) -> io::Result<()>,
+    ) -> anyhow::Result<()> {
         let file = File::create(filename).with_context(|| {
             format!("When creating profile output file `{}`", filename.display())
         })?;
-        Self::write_profile_to(x, file).with_context(|| {
+        write(x, file).with_context(|| {
             format!(
                 "When writing to profile output file `{}`",
                 filename.display()
@@ -204,7 +311,111 @@ impl<'v> FlameProfile<'v> {
         // Need to write out lines which look like:
         // root;calls1;calls2 1
         // All the numbers at the end must be whole numbers (we use milliseconds)
-        let names = x.values.map(|x| x.to_repr());
+        let names = x.values.map(|x| function_display_name(*x));
         Stacks::new(&names, &x.frames).render(file)
     }
+
+    fn write_json_to(x: &FlameData, file: File) -> io::Result<()> {
+        serde_json::to_writer_pretty(file, &summarise(x))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use serde_json::Value as JsonValue;
+
+    use crate::{
+        environment::{Globals, Module},
+        eval::{Evaluator, ProfileMode},
+        syntax::{AstModule, Dialect},
+    };
+
+    #[test]
+    fn test_time_flame_json() {
+        let module = Module::new();
+        let globals = Globals::standard();
+        let mut eval = Evaluator::new(&module);
+        eval.enable_profile(&ProfileMode::TimeFlame);
+        let program = r#"
+def cheap():
+    x = 1
+    return x
+def expensive():
+    x = 0
+    for i in range(100000):
+        x += i
+    return str(x)
+def rec(n):
+    return 0 if n == 0 else rec(n - 1)
+for _ in range(10):
+    cheap()
+expensive()
+rec(5)
+"#;
+        let ast = AstModule::parse("prof.star", program.to_owned(), &Dialect::Extended).unwrap();
+        eval.eval_module(ast, &globals).unwrap();
+
+        let path = std::env::temp_dir().join(format!("time_flame_{}.json", std::process::id()));
+        eval.write_profile(&ProfileMode::TimeFlame, &path).unwrap();
+        let res: JsonValue = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let res = res.as_array().unwrap();
+        let find = |name: &str| {
+            let i = res.iter().position(|x| x["name"] == name).unwrap();
+            (i, &res[i])
+        };
+        let (cheap_i, cheap) = find("prof.star.cheap");
+        let (expensive_i, expensive) = find("prof.star.expensive");
+        let (_, rec) = find("prof.star.rec");
+        let (_, native) = find("str");
+        assert!(expensive_i < cheap_i);
+        assert_eq!(cheap["calls"], 10);
+        assert!(cheap["location"].as_str().unwrap().starts_with("prof.star:2:1-"));
+        assert_eq!(expensive["calls"], 1);
+        assert_eq!(rec["calls"], 6);
+        // Recursive calls count only once towards the cumulative time
+        assert!(rec["time"].as_f64().unwrap() >= rec["self_time"].as_f64().unwrap());
+        assert!(rec["time"].as_f64().unwrap() < expensive["time"].as_f64().unwrap());
+        assert_eq!(native["calls"], 1);
+        assert!(native.get("location").is_none());
+    }
+
+    #[test]
+    fn test_time_flame_json_nested_def() {
+        let module = Module::new();
+        let globals = Globals::standard();
+        let mut eval = Evaluator::new(&module);
+        eval.enable_profile(&ProfileMode::TimeFlame);
+        let program = r#"
+def outer():
+    def inner():
+        return 1
+    return inner() + (lambda: 2)()
+for _ in range(5):
+    outer()
+"#;
+        let ast = AstModule::parse("nested.star", program.to_owned(), &Dialect::Extended).unwrap();
+        eval.eval_module(ast, &globals).unwrap();
+
+        let path = std::env::temp_dir().join(format!(
+            "time_flame_nested_{}.json",
+            std::process::id()
+        ));
+        eval.write_profile(&ProfileMode::TimeFlame, &path).unwrap();
+        let res: JsonValue = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        // Every call to the inner functions, which are new values each time, is in one row
+        let res = res.as_array().unwrap();
+        let rows = |name: &str| res.iter().filter(|x| x["name"] == name).collect::<Vec<_>>();
+        for name in ["nested.star.outer", "nested.star.inner", "nested.star.lambda"] {
+            let rows = rows(name);
+            assert_eq!(rows.len(), 1, "{}", name);
+            assert_eq!(rows[0]["calls"], 5, "{}", name);
+        }
+    }
 }
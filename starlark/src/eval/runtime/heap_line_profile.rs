@@ -0,0 +1,397 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Heap allocations attributed to the statement which made them.
+//!
+//! Before each statement we allocate a marker recording its line, so walking the heap in
+//! allocation order attributes each value to the statement that allocated it. Calls are
+//! marked by the [`HeapProfile`](crate::eval::runtime::heap_profile::HeapProfile), so that
+//! allocations after a call returns go back to the calling statement.
+//!
+//! Garbage collection frees the markers, so at each collection we walk the old heap,
+//! counting what was allocated and which values survived. The survivors are remembered by
+//! their new address, so later walks know their line without a marker.
+
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    fs::File,
+    io::Write,
+    path::Path,
+};
+
+use anyhow::Context;
+use derive_more::Display;
+use either::Either;
+use gazebo::{any::AnyLifetime, prelude::*};
+
+use crate as starlark;
+use crate::{
+    codemap::{CodeMap, FileSpanRef},
+    eval::runtime::{
+        csv::CsvWriter,
+        heap_profile::{CallMarker, MaybeDrop, NeedsDrop, NoDrop},
+        stmt_profile::FileId,
+    },
+    values::{Heap, StarlarkValue, Tracer, Value},
+};
+
+/// An index into [`HeapLineProfileData::locations`].
+#[derive(Copy, Clone, Dupe, Debug, Eq, PartialEq, Hash)]
+struct LineId(u32);
+
+impl LineId {
+    /// Allocations made before any statement was recorded.
+    const UNKNOWN: LineId = LineId(0);
+}
+
+#[derive(Debug, Display, AnyLifetime, NoSerialize)]
+#[display(fmt = "StmtStart")]
+struct StmtStart<D: MaybeDrop + 'static> {
+    line: LineId,
+    maybe_drop: D,
+}
+
+impl<'v, D: MaybeDrop + AnyLifetime<'static>> StarlarkValue<'v> for StmtStart<D> {
+    starlark_type!("stmt_start");
+}
+
+// When the profile is not enabled, we want this to be small and cheap.
+#[derive(Default)]
+pub(crate) struct HeapLineProfile(Option<Box<HeapLineProfileData>>);
+
+#[derive(Clone)]
+struct HeapLineProfileData {
+    /// The line of each `LineId`, as the file and 0-based line, `None` for `UNKNOWN`.
+    locations: Vec<Option<(CodeMap, usize)>>,
+    ids: HashMap<(FileId, usize), LineId>,
+    /// Bytes allocated, indexed by `LineId`.
+    allocated: Vec<usize>,
+    /// Bytes allocated and not yet freed by a garbage collection, indexed by `LineId`.
+    retained: Vec<usize>,
+    /// The values which survived the last garbage collection, by address,
+    /// with the line that allocated them.
+    survivors: HashMap<usize, LineId>,
+}
+
+/// The state while walking the heap in allocation order.
+struct Walk<'a> {
+    data: &'a mut HeapLineProfileData,
+    /// Whether we are walking the old heap during a garbage collection,
+    /// so any value not moved is being freed.
+    gc: bool,
+    /// The survivors of the garbage collection being observed.
+    new_survivors: HashMap<usize, LineId>,
+    is_drop: bool,
+    line: LineId,
+    /// The line of each statement which made a call still in progress.
+    callers: Vec<LineId>,
+}
+
+impl<'a> Walk<'a> {
+    fn new(data: &'a mut HeapLineProfileData, gc: bool) -> Self {
+        Self {
+            data,
+            gc,
+            new_survivors: HashMap::new(),
+            is_drop: true,
+            line: LineId::UNKNOWN,
+            callers: Vec::new(),
+        }
+    }
+
+    /// Visit the next value in allocation order, found at `address`. The value is either
+    /// still in the heap, or was moved to the given address by the garbage collection.
+    fn visit(&mut self, is_drop: bool, address: usize, x: Either<Value, usize>, bytes: usize) {
+        if is_drop != self.is_drop {
+            // The drop and non-drop values each have their own markers.
+            self.is_drop = is_drop;
+            self.line = LineId::UNKNOWN;
+            self.callers.clear();
+        }
+        if let Either::Left(x) = x {
+            if let Some(stmt) = x.downcast_ref::<StmtStart<NeedsDrop>>() {
+                self.line = stmt.line;
+                return;
+            } else if let Some(stmt) = x.downcast_ref::<StmtStart<NoDrop>>() {
+                self.line = stmt.line;
+                return;
+            }
+            match CallMarker::of(x) {
+                Some(CallMarker::Enter) => {
+                    self.callers.push(self.line);
+                    return;
+                }
+                Some(CallMarker::Exit) => {
+                    self.line = self.callers.pop().unwrap_or(LineId::UNKNOWN);
+                    return;
+                }
+                None => {}
+            }
+        }
+
+        let line = match self.data.survivors.get(&address) {
+            // Already counted before the last garbage collection.
+            Some(line) => *line,
+            None => {
+                self.data.allocated[self.line.0 as usize] += bytes;
+                self.data.retained[self.line.0 as usize] += bytes;
+                self.line
+            }
+        };
+        match x {
+            Either::Left(_) => {
+                if self.gc {
+                    self.data.retained[line.0 as usize] -= bytes;
+                }
+            }
+            Either::Right(moved_to) => {
+                self.new_survivors.insert(moved_to, line);
+            }
+        }
+    }
+
+    fn finish(self) {
+        if self.gc {
+            self.data.survivors = self.new_survivors;
+        }
+    }
+}
+
+impl HeapLineProfileData {
+    fn new() -> Self {
+        Self {
+            locations: vec![None],
+            ids: HashMap::new(),
+            allocated: vec![0],
+            retained: vec![0],
+            survivors: HashMap::new(),
+        }
+    }
+
+    fn line_id(&mut self, span: FileSpanRef) -> LineId {
+        let line = span.file.find_line(span.span.begin());
+        match self.ids.entry((FileId::new(span.file), line)) {
+            Entry::Occupied(e) => *e.get(),
+            Entry::Vacant(e) => {
+                let id = LineId(self.locations.len().try_into().unwrap());
+                e.insert(id);
+                self.locations.push(Some((span.file.dupe(), line)));
+                self.allocated.push(0);
+                self.retained.push(0);
+                id
+            }
+        }
+    }
+
+    fn write(&self, filename: &Path, heap: &Heap) -> anyhow::Result<()> {
+        let file = File::create(filename).with_context(|| {
+            format!(
+                "When creating heap line profile output file `{}`",
+                filename.display()
+            )
+        })?;
+        self.write_to(file, heap).with_context(|| {
+            format!(
+                "When writing to heap line profile output file `{}`",
+                filename.display()
+            )
+        })
+    }
+
+    fn write_to(&self, mut file: impl Write, heap: &Heap) -> anyhow::Result<()> {
+        // Values allocated since the last garbage collection haven't been counted yet,
+        // so count them in a copy, leaving the real data for the next collection.
+        let mut data = self.clone();
+        let mut walk = Walk::new(&mut data, false);
+        unsafe {
+            heap.for_each_ordered_sized(|is_drop, address, x, bytes| {
+                walk.visit(is_drop, address, Either::Left(x), bytes)
+            });
+        }
+        walk.finish();
+
+        // Several `CodeMap`s may have the same file name, so merge them.
+        let mut items: HashMap<(&str, Option<usize>), (usize, usize)> = HashMap::new();
+        for (i, location) in data.locations.iter().enumerate() {
+            let key = match location {
+                None => ("(unknown)", None),
+                Some((file, line)) => (file.filename(), Some(*line + 1)),
+            };
+            let item = items.entry(key).or_default();
+            item.0 += data.allocated[i];
+            item.1 += data.retained[i];
+        }
+        let mut items: Vec<_> = items.into_iter().filter(|(_, (a, _))| *a != 0).collect();
+        items.sort_by(|(k1, (a1, _)), (k2, (a2, _))| a2.cmp(a1).then_with(|| k1.cmp(k2)));
+
+        let mut csv = CsvWriter::new(["File", "Line", "Allocated(bytes)", "Retained(bytes)"]);
+        csv.write_value("TOTAL");
+        csv.write_value("");
+        csv.write_value(items.iter().map(|(_, (a, _))| a).sum::<usize>());
+        csv.write_value(items.iter().map(|(_, (_, r))| r).sum::<usize>());
+        csv.finish_row();
+        for ((file, line), (allocated, retained)) in items {
+            csv.write_value(file);
+            match line {
+                Some(line) => csv.write_value(line),
+                None => csv.write_value(""),
+            }
+            csv.write_value(allocated);
+            csv.write_value(retained);
+            csv.finish_row();
+        }
+
+        file.write_all(csv.finish().as_bytes())?;
+        Ok(())
+    }
+}
+
+impl HeapLineProfile {
+    pub(crate) fn enable(&mut self) {
+        self.0 = Some(box HeapLineProfileData::new());
+    }
+
+    pub(crate) fn enabled(&self) -> bool {
+        self.0.is_some()
+    }
+
+    /// Mark the values allocated from now on as allocated by the statement at `span`.
+    pub(crate) fn before_stmt(&mut self, span: FileSpanRef, heap: &Heap) {
+        if let Some(box data) = &mut self.0 {
+            let line = data.line_id(span);
+            heap.alloc_simple(StmtStart {
+                line,
+                maybe_drop: NeedsDrop,
+            });
+            heap.alloc_simple(StmtStart {
+                line,
+                maybe_drop: NoDrop,
+            });
+        }
+    }
+
+    /// Perform a garbage collection, as [`Heap::garbage_collect`], recording what it frees.
+    /// Each value of the old heap is also given to `observe`, with its address, and either
+    /// the value (if it was freed) or the address it was moved to.
+    pub(crate) unsafe fn garbage_collect<'v>(
+        &mut self,
+        heap: &'v Heap,
+        f: impl FnOnce(&Tracer<'v>),
+        mut observe: impl FnMut(usize, Either<Value<'v>, usize>),
+    ) {
+        match &mut self.0 {
+            None => heap.garbage_collect_observe(f, |_, address, x, _| observe(address, x)),
+            Some(box data) => {
+                let mut walk = Walk::new(data, true);
+                heap.garbage_collect_observe(f, |is_drop, address, x, bytes| {
+                    observe(address, x);
+                    walk.visit(is_drop, address, x, bytes)
+                });
+                walk.finish();
+            }
+        }
+    }
+
+    // None = not applicable because not enabled
+    pub(crate) fn write(&self, filename: &Path, heap: &Heap) -> Option<anyhow::Result<()>> {
+        self.0.as_ref().map(|data| data.write(filename, heap))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::{
+        environment::{Globals, Module},
+        eval::{Evaluator, ProfileMode},
+        syntax::{AstModule, Dialect},
+    };
+
+    // The rows after the total, as (line, allocated, retained).
+    fn rows(eval: &Evaluator) -> Vec<(String, usize, usize)> {
+        let path = std::env::temp_dir().join(format!("heap_line_{}.csv", std::process::id()));
+        eval.write_profile(&ProfileMode::HeapAllocatedByLine, &path)
+            .unwrap();
+        let out = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        let mut lines = out.lines();
+        assert_eq!(
+            Some("File,Line,Allocated(bytes),Retained(bytes)"),
+            lines.next()
+        );
+        assert!(lines.next().unwrap().starts_with("\"TOTAL\","));
+        lines
+            .map(|row| {
+                let row: Vec<&str> = row.split(',').collect();
+                (
+                    row[1].to_owned(),
+                    row[2].parse().unwrap(),
+                    row[3].parse().unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_heap_line_profile() {
+        let ast = AstModule::parse(
+            "x.star",
+            r#"
+def make(n):
+    return ["item%d" % i for i in range(n)]
+big = make(2000)
+s = ""
+for i in range(200):
+    s += "abcdefghij"
+small = [len(big), len(s)]
+"#
+            .to_owned(),
+            &Dialect::Extended,
+        )
+        .unwrap();
+        let globals = Globals::standard();
+        let module = Module::new();
+        let mut eval = Evaluator::new(&module);
+        eval.enable_profile(&ProfileMode::HeapAllocatedByLine);
+        eval.eval_module(ast, &globals).unwrap();
+
+        // Nothing has been collected, so everything is retained.
+        let before = rows(&eval);
+        let mut top: Vec<&str> = before[..2].iter().map(|x| x.0.as_str()).collect();
+        top.sort_unstable();
+        assert_eq!(vec!["3", "7"], top);
+        for (_, allocated, retained) in &before {
+            assert_eq!(allocated, retained);
+        }
+
+        // After a collection, only the final `s` is retained, but `big` is still there,
+        // and the totals allocated don't change.
+        unsafe { eval.garbage_collect() };
+        let after = rows(&eval);
+        let find = |rows: &[(String, usize, usize)], line: &str| {
+            *rows.iter().find(|x| x.0 == line).unwrap()
+        };
+        for line in ["3", "7"] {
+            assert_eq!(find(&before, line).1, find(&after, line).1);
+        }
+        let (_, allocated, retained) = find(&after, "3");
+        assert!(retained > allocated / 2, "{} {}", allocated, retained);
+        let (_, allocated, retained) = find(&after, "7");
+        assert!(retained < allocated / 10, "{} {}", allocated, retained);
+    }
+}
@@ -17,6 +17,7 @@
 
 use std::{
     cell::RefCell,
+    cmp::Reverse,
     collections::{hash_map::Entry, HashMap},
     fmt::Debug,
     fs::File,
@@ -33,7 +34,7 @@ use gazebo::{any::AnyLifetime, prelude::*};
 
 use crate as starlark;
 use crate::{
-    eval::runtime::csv::CsvWriter,
+    eval::{fragment::def::function_display_name, runtime::csv::CsvWriter},
     values::{Freeze, Freezer, Heap, NoSimpleValue, StarlarkValue, Trace, Value, ValueLike},
 };
 
@@ -48,11 +49,11 @@ pub(crate) struct HeapProfile {
 }
 
 /// A type which is either drop or non-drop.
-trait MaybeDrop: Debug + Sync + Send + 'static {}
+pub(crate) trait MaybeDrop: Debug + Sync + Send + 'static {}
 
 /// Type which has `Drop`.
 #[derive(AnyLifetime, Debug, Trace)]
-struct NeedsDrop;
+pub(crate) struct NeedsDrop;
 impl Drop for NeedsDrop {
     fn drop(&mut self) {
         // Just make this type `Drop`.
@@ -63,7 +64,7 @@ impl Drop for NeedsDrop {
 
 /// Type which doesn't have `Drop`.
 #[derive(AnyLifetime, Debug, Trace)]
-struct NoDrop;
+pub(crate) struct NoDrop;
 
 impl MaybeDrop for NeedsDrop {}
 impl MaybeDrop for NoDrop {}
@@ -98,6 +99,31 @@ impl<'v, D: MaybeDrop + AnyLifetime<'static>> StarlarkValue<'v> for CallExit<D>
     starlark_type!("call_exit");
 }
 
+/// A record of a call made while profiling, as found when walking the heap.
+#[derive(Copy, Clone, Dupe, Debug, Eq, PartialEq)]
+pub(crate) enum CallMarker {
+    Enter,
+    Exit,
+}
+
+impl CallMarker {
+    /// If `x` was allocated by [`HeapProfile::record_call_enter`] or
+    /// [`HeapProfile::record_call_exit`], which one.
+    pub(crate) fn of(x: Value) -> Option<CallMarker> {
+        if x.downcast_ref::<CallEnter<NeedsDrop>>().is_some()
+            || x.downcast_ref::<CallEnter<NoDrop>>().is_some()
+        {
+            Some(CallMarker::Enter)
+        } else if x.downcast_ref::<CallExit<NeedsDrop>>().is_some()
+            || x.downcast_ref::<CallExit<NoDrop>>().is_some()
+        {
+            Some(CallMarker::Exit)
+        } else {
+            None
+        }
+    }
+}
+
 #[derive(Copy, Clone, Dupe, Debug, Eq, PartialEq, Hash)]
 struct FunctionId(usize);
 
@@ -126,7 +152,7 @@ impl FunctionIds {
         match self.values.entry(x.ptr_value()) {
             Entry::Occupied(v) => *v.get(),
             Entry::Vacant(outer) => {
-                let s = x.to_str();
+                let s = function_display_name(x);
                 match self.strings.entry(s) {
                     Entry::Occupied(inner) => {
                         let res = *inner.get();
@@ -274,7 +300,8 @@ impl HeapProfile {
         info[total_id.0] = totals;
         let mut info = info.iter().enumerate().collect::<Vec<_>>();
 
-        columns.sort_by_key(|x| -(x.1 as isize));
+        // Break ties by name, so the output doesn't depend on the order of the hash map.
+        columns.sort_by_key(|x| (-(x.1 as isize), x.0));
         info.sort_by_key(|x| -(x.1.time.as_nanos() as i128));
 
         let mut csv = CsvWriter::new(
@@ -296,10 +323,11 @@ impl HeapProfile {
         let un_ids = ids.invert();
         for (rowname, info) in info {
             let allocs = info.allocs.values().sum::<usize>();
+            // On a tie, pick the caller which was seen first.
             let callers = info
                 .callers
                 .iter()
-                .max_by_key(|x| x.1)
+                .max_by_key(|x| (x.1, Reverse(x.0.0)))
                 .unwrap_or((&blank, &0));
             assert!(
                 info.calls % 2 == 0,
@@ -497,14 +525,19 @@ mod flame {
         ) -> anyhow::Result<()> {
             let this = self.0.borrow();
 
-            for (k, v) in this.allocs.iter() {
+            // Sort by name, so the output doesn't depend on the order of the hash maps.
+            let mut allocs: Vec<_> = this.allocs.iter().collect();
+            allocs.sort_by_key(|x| x.0);
+            for (k, v) in allocs {
                 for e in stack.iter().chain(std::iter::once(k)).intersperse(&";") {
                     write!(file, "{}", e)?;
                 }
                 writeln!(file, " {}", v.bytes)?;
             }
 
-            for (id, frame) in this.callees.iter() {
+            let mut callees: Vec<_> = this.callees.iter().collect();
+            callees.sort_by_key(|x| ids[x.0.0]);
+            for (id, frame) in callees {
                 stack.push(ids[id.0]);
                 frame.write(file, stack, ids)?;
                 stack.pop();
@@ -645,6 +678,43 @@ f
         Ok(())
     }
 
+    // The parts of the profiles which don't depend on timing are the same on every run,
+    // even though each run uses hash maps with different random seeds.
+    #[test]
+    fn test_profile_deterministic() -> anyhow::Result<()> {
+        let profile = || -> anyhow::Result<(String, String)> {
+            let ast = AstModule::parse(
+                "foo.bzl",
+                r#"
+def g(x):
+    return ([x], {x: x}, (x, x), str(x), lambda: x)
+def f(x):
+    return [g(x), [x], {x: x}, (x, x), str(x)]
+f(1)
+"#
+                .to_owned(),
+                &Dialect::Extended,
+            )?;
+            let module = Module::new();
+            let mut eval = Evaluator::new(&module);
+            eval.enable_profile(&ProfileMode::Heap);
+            eval.eval_module(ast, &Globals::standard())?;
+            let mut summary = Vec::new();
+            HeapProfile::write_summarized_heap_profile_to(&mut summary, module.heap())?;
+            let mut flame = Vec::new();
+            HeapProfile::write_flame_heap_profile_to(&mut flame, module.heap())?;
+            // The rows of the summary are sorted by time, but the header lists the types
+            // allocated, and many of them are allocated the same number of times.
+            let header = String::from_utf8(summary)?.lines().next().unwrap().to_owned();
+            Ok((header, String::from_utf8(flame)?))
+        };
+        let first = profile()?;
+        for _ in 0..10 {
+            assert_eq!(first, profile()?);
+        }
+        Ok(())
+    }
+
     // Test data is collected from both drop and non-drop heaps.
     #[test]
     fn drop_non_drop() {
@@ -27,6 +27,11 @@ pub enum ProfileMode {
     /// Like heap profile, but writes output comparible with
     /// [flamegraph.pl](https://github.com/brendangregg/FlameGraph/blob/master/flamegraph.pl).
     HeapFlame,
+    /// Heap allocations attributed to the line of the statement which made them, written as
+    /// CSV with the bytes each line allocated in total and the bytes it retained, i.e. which
+    /// had not been freed by a garbage collection when the profile was written.
+    /// Unlike the heap profile, garbage collection stays enabled.
+    HeapAllocatedByLine,
     /// The statement profile mode provides information about time spent in each statement.
     Stmt,
     /// The bytecode profile mode provides information about bytecode instructions.
@@ -36,4 +41,11 @@ pub enum ProfileMode {
     /// Provide output compatible with
     /// [flamegraph.pl](https://github.com/brendangregg/FlameGraph/blob/master/flamegraph.pl).
     Flame,
+    /// Like flame profile, but aggregated per function, written as a JSON array with the
+    /// `name`, `location` (for functions defined in Starlark), number of `calls`, and
+    /// the cumulative `time` and `self_time` in seconds, most expensive first.
+    TimeFlame,
+    /// Statement coverage: which statements executed and how many times, including those
+    /// which never ran. Written as JSON lines with `file`, `start_line`, `end_line` and `hits`.
+    Coverage,
 }
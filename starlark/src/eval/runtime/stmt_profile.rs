@@ -40,12 +40,12 @@ pub(crate) struct StmtProfile(Option<Box<StmtProfileData>>);
 // somewhat delving into internal details.
 // Remains unique because we take a reference to the CodeMap.
 #[derive(Debug, Hash, PartialEq, Eq, Clone, Copy, Dupe)]
-struct FileId(*const crate::codemap::CodeMapData);
+pub(crate) struct FileId(*const crate::codemap::CodeMapData);
 
 impl FileId {
     const EMPTY: FileId = FileId(ptr::null());
 
-    fn new(codemap: &CodeMap) -> Self {
+    pub(crate) fn new(codemap: &CodeMap) -> Self {
         Self(Arc::as_ptr(codemap.get_ptr()))
     }
 }
@@ -153,7 +153,13 @@ impl StmtProfileData {
                 items.push(Item { span, time, count })
             }
         }
-        items.sort_by_key(|x| -(x.time.as_nanos() as i128));
+        // Break ties by location, so the output doesn't depend on the order of the hash map.
+        items.sort_by(|x, y| {
+            y.time
+                .cmp(&x.time)
+                .then_with(|| x.span.file.filename().cmp(y.span.file.filename()))
+                .then_with(|| x.span.span.begin().cmp(&y.span.span.begin()))
+        });
 
         let mut csv = CsvWriter::new(["File", "Span", "Duration(s)", "Count"]);
         csv.write_value("TOTAL");
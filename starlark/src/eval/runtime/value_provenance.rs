@@ -0,0 +1,222 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Where the lists and dicts on the heap were created, for error messages.
+//!
+//! Values are recorded by their address, so at each garbage collection we walk the old
+//! heap, moving the origins of the values which survived to their new address.
+
+use std::collections::HashMap;
+
+use either::Either;
+use thiserror::Error;
+
+use crate::{
+    codemap::FileSpan,
+    eval::runtime::call_stack::FrozenFileSpan,
+    values::{UnpackValueError, Value, ValueLike},
+};
+
+/// Context added to an error about a value with a known origin.
+#[derive(Debug, Error)]
+#[error("Value created at {0}")]
+struct ValueCreatedAt(FileSpan);
+
+// When not enabled, we want this to be small and cheap.
+#[derive(Default)]
+pub(crate) struct ValueProvenance(Option<Box<HashMap<usize, FrozenFileSpan>>>);
+
+/// The address of the value on the heap, if it has one.
+pub(crate) fn address(value: Value) -> Option<usize> {
+    match value.0.unpack() {
+        Either::Left(header) => Some(header as *const _ as usize),
+        Either::Right(_) => None,
+    }
+}
+
+impl ValueProvenance {
+    pub(crate) fn enable(&mut self, enable: bool) {
+        self.0 = if enable {
+            Some(box HashMap::new())
+        } else {
+            None
+        };
+    }
+
+    pub(crate) fn enabled(&self) -> bool {
+        self.0.is_some()
+    }
+
+    /// Record that `value` was created by the expression at `span`.
+    pub(crate) fn record(&mut self, value: Value, span: FrozenFileSpan) {
+        if let (Some(origins), Some(address)) = (&mut self.0, address(value)) {
+            origins.insert(address, span);
+        }
+    }
+
+    pub(crate) fn origin<'v>(&self, value: impl ValueLike<'v>) -> Option<FileSpan> {
+        let origins = self.0.as_ref()?;
+        Some(origins.get(&address(value.to_value())?)?.to_file_span())
+    }
+
+    /// Mention the origin of `value` in an error about it, if it is known.
+    pub(crate) fn add_origin<'v>(
+        &self,
+        error: anyhow::Error,
+        value: impl ValueLike<'v>,
+    ) -> anyhow::Error {
+        match self.origin(value) {
+            Some(origin) => error.context(ValueCreatedAt(origin)),
+            None => error,
+        }
+    }
+
+    /// Mention the origin of the value in an [`UnpackValueError`], if it is known.
+    /// The error is only recognised before it is wrapped in a
+    /// [`Diagnostic`](crate::errors::Diagnostic).
+    pub(crate) fn add_unpack_origin(&self, error: anyhow::Error) -> anyhow::Error {
+        let origins = match &self.0 {
+            Some(origins) => origins,
+            None => return error,
+        };
+        let origin = error
+            .downcast_ref::<UnpackValueError>()
+            .and_then(|e| origins.get(&e.address?));
+        match origin {
+            Some(origin) => error.context(ValueCreatedAt(origin.to_file_span())),
+            None => error,
+        }
+    }
+
+    /// Start a garbage collection, which must call [`visit`](ProvenanceGc::visit)
+    /// on every value of the old heap.
+    pub(crate) fn gc(&mut self) -> ProvenanceGc<'_> {
+        ProvenanceGc {
+            provenance: self,
+            survivors: HashMap::new(),
+        }
+    }
+}
+
+/// The state while walking the old heap during a garbage collection.
+pub(crate) struct ProvenanceGc<'a> {
+    provenance: &'a mut ValueProvenance,
+    /// The origins of the values which survived, by their new address.
+    survivors: HashMap<usize, FrozenFileSpan>,
+}
+
+impl<'a> ProvenanceGc<'a> {
+    /// Visit a value found at `address` in the old heap, which is either still there
+    /// (so is being freed), or was moved to the given address.
+    pub(crate) fn visit(&mut self, address: usize, x: Either<Value, usize>) {
+        if let (Some(origins), Either::Right(moved_to)) = (&self.provenance.0, x) {
+            if let Some(span) = origins.get(&address) {
+                self.survivors.insert(moved_to, *span);
+            }
+        }
+    }
+
+    pub(crate) fn finish(self) {
+        if let Some(origins) = &mut self.provenance.0 {
+            **origins = self.survivors;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        environment::{Globals, Module},
+        eval::Evaluator,
+        syntax::{AstModule, Dialect},
+        values::Value,
+    };
+
+    fn parse(code: &str) -> AstModule {
+        AstModule::parse("provenance.star", code.to_owned(), &Dialect::Extended).unwrap()
+    }
+
+    #[test]
+    fn test_value_origin() {
+        let module = Module::new();
+        let globals = Globals::standard();
+        let mut eval = Evaluator::new(&module);
+        eval.enable_value_provenance(true);
+        // Make sure garbage collections happen between the statements.
+        eval.set_gc_threshold(1000);
+        let code = r#"def make():
+    return [1, 2, "x"]
+def pass_around(xs):
+    return xs
+deps = pass_around(make())
+by_name = {"deps": deps}
+garbage = [str(i) for i in range(1000)]
+garbage = [str(i) for i in range(1000)]
+garbage = None
+"#;
+        eval.eval_module(parse(code), &globals).unwrap();
+        assert!(eval.gc_stats().collections > 0);
+
+        let deps = module.get("deps").unwrap();
+        let origin = eval.value_origin(deps).unwrap();
+        assert_eq!(origin.source_span(), r#"[1, 2, "x"]"#);
+        assert_eq!(origin.to_string(), "provenance.star:2:12-23");
+        let by_name = module.get("by_name").unwrap();
+        assert_eq!(
+            eval.value_origin(by_name).unwrap().source_span(),
+            r#"{"deps": deps}"#
+        );
+        assert!(eval.value_origin(Value::new_int(1)).is_none());
+
+        let code = r#"
+def check(xs: [int.type]):
+    pass
+check(deps)
+"#;
+        let err = eval.eval_module(parse(code), &globals).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("Value created at provenance.star:2:12-23"),
+            "{}",
+            err
+        );
+
+        // Native functions unpacking their parameters mention it too.
+        let err = eval.eval_module(parse("range(deps)"), &globals).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("Value created at provenance.star:2:12-23"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_value_origin_disabled() {
+        let module = Module::new();
+        let globals = Globals::standard();
+        let mut eval = Evaluator::new(&module);
+        eval.eval_module(parse("def make():\n    return [1]\nxs = [1, 2]"), &globals).unwrap();
+        assert!(eval.value_origin(module.get("xs").unwrap()).is_none());
+
+        // Only code compiled once enabled records where values are created.
+        eval.enable_value_provenance(true);
+        eval.eval_module(parse("ys = make()\nzs = [3]"), &globals).unwrap();
+        assert!(eval.value_origin(module.get("ys").unwrap()).is_none());
+        assert!(eval.value_origin(module.get("zs").unwrap()).is_some());
+    }
+}
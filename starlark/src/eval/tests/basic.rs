@@ -49,9 +49,53 @@ fn bitwise_test() {
 "#,
     );
 
-    // For now, we report negative shift amounts as integer overflow
-    assert::fail("1 << -13", "overflow");
-    assert::fail("1 >> -13", "overflow");
+    assert::fail("1 << -13", "Negative shift count computing `1 << -13`");
+    assert::fail("1 >> -13", "Negative shift count computing `1 >> -13`");
+}
+
+#[test]
+fn test_bit_not() {
+    use derive_more::Display;
+    use gazebo::any::AnyLifetime;
+
+    use crate as starlark;
+    use crate::values::{Heap, NoSerialize, StarlarkValue, Value};
+
+    #[derive(Debug, Display, AnyLifetime, NoSerialize)]
+    #[display(fmt = "flags({})", _0)]
+    struct Flags(i32);
+
+    starlark_simple_value!(Flags);
+    impl<'v> StarlarkValue<'v> for Flags {
+        starlark_type!("flags");
+
+        fn bit_not(&self, heap: &'v Heap) -> anyhow::Result<Value<'v>> {
+            Ok(heap.alloc(Flags(!self.0 & 0xf)))
+        }
+    }
+
+    #[starlark_module]
+    fn flags(builder: &mut GlobalsBuilder) {
+        fn flags(x: i32) -> anyhow::Result<Flags> {
+            Ok(Flags(x))
+        }
+    }
+
+    assert::all_true(
+        r#"
+~0 == -1
+~-1 == 0
+~2147483647 == -2147483647 - 1
+x = 5; ~x == -6
+"#,
+    );
+
+    let mut a = Assert::new();
+    a.globals_add(flags);
+    a.is_true("str(~flags(10)) == 'flags(5)'");
+    a.fail("~1.5", "Operation `~` not supported on type `float`");
+    a.fail("~True", "Operation `~` not supported on type `bool`");
+    a.fail("~'x'", "Operation `~` not supported on type `string`");
 }
 
 #[test]
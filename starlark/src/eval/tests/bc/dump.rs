@@ -0,0 +1,55 @@
+/*
+ * Copyright 2018 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Tests for the human readable bytecode dump.
+
+use crate::assert::Assert;
+
+#[test]
+fn test_dump_bytecode() {
+    let mut a = Assert::new();
+    let module = a.module(
+        "dump.star",
+        r#"
+def test(xs):
+    if xs:
+        y = 1
+    else:
+        y = 2
+    for x in xs:
+        y += x
+    return y
+"#,
+    );
+    let dump = module.dump_bytecode("test").unwrap();
+    let lines: Vec<&str> = dump.lines().collect();
+    for opcode in [": IfNotBr ", ": ForLoop ", ": Return", ": End"] {
+        assert!(dump.contains(opcode), "no `{}` in:\n{}", opcode, dump);
+    }
+    for (i, line) in lines.iter().enumerate() {
+        assert!(line.starts_with(&format!("{} @", i)), "{}", line);
+        if let Some((_, targets)) = line.split_once(" -> ") {
+            let targets = targets.split(" # ").next().unwrap();
+            for target in targets.split(", ") {
+                let target: usize = target.parse().unwrap();
+                assert!(target < lines.len(), "{}", line);
+            }
+        }
+    }
+    assert!(lines.iter().any(|x| x.contains(" # dump.star:8:")));
+    assert!(module.dump_bytecode("missing").is_none());
+}
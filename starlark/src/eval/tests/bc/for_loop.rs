@@ -0,0 +1,96 @@
+/*
+ * Copyright 2018 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Test compilation of loops over dict views and `enumerate`.
+
+use crate::eval::{bc::opcode::BcOpcode, tests::bc::test_instrs};
+
+#[test]
+fn test_for_dict_items_unpacked() {
+    test_instrs(
+        &[
+            BcOpcode::LoadLocal,
+            BcOpcode::ForLoopDictItems,
+            BcOpcode::StoreLocal,
+            BcOpcode::StoreLocal,
+            BcOpcode::Continue,
+            BcOpcode::ReturnConst,
+        ],
+        "def test(d):\n  for k, v in d.items(): pass",
+    );
+}
+
+#[test]
+fn test_for_dict_items() {
+    test_instrs(
+        &[
+            BcOpcode::LoadLocal,
+            BcOpcode::ForLoopDictView,
+            BcOpcode::StoreLocal,
+            BcOpcode::Continue,
+            BcOpcode::ReturnConst,
+        ],
+        "def test(d):\n  for kv in d.items(): pass",
+    );
+}
+
+#[test]
+fn test_for_dict_keys_in_compr() {
+    test_instrs(
+        &[
+            BcOpcode::ListNew,
+            BcOpcode::LoadLocal,
+            BcOpcode::ForLoopDictView,
+            BcOpcode::StoreLocal,
+            BcOpcode::LoadLocal,
+            BcOpcode::ComprListAppend,
+            BcOpcode::Continue,
+            BcOpcode::Return,
+        ],
+        "def test(d): return [k for k in d.keys()]",
+    );
+}
+
+#[test]
+fn test_for_enumerate_unpacked() {
+    test_instrs(
+        &[
+            BcOpcode::LoadLocal,
+            BcOpcode::ForLoopEnumerate,
+            BcOpcode::StoreLocal,
+            BcOpcode::StoreLocal,
+            BcOpcode::Continue,
+            BcOpcode::ReturnConst,
+        ],
+        "def test(xs):\n  for i, x in enumerate(xs): pass",
+    );
+}
+
+#[test]
+fn test_for_enumerate_not_unpacked() {
+    test_instrs(
+        &[
+            BcOpcode::LoadLocal,
+            BcOpcode::CallFrozenNativePos,
+            BcOpcode::ForLoop,
+            BcOpcode::StoreLocal,
+            BcOpcode::Continue,
+            BcOpcode::ReturnConst,
+        ],
+        "def test(xs):\n  for p in enumerate(xs): pass",
+    );
+}
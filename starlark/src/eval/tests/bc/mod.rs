@@ -19,7 +19,9 @@
 
 mod and_or;
 mod compr;
+mod dump;
 mod expr;
+mod for_loop;
 mod if_stmt;
 
 use crate::{
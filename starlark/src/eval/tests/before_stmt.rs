@@ -17,9 +17,12 @@
 
 use std::cell::Cell;
 
+use anyhow::anyhow;
+
 use crate::{
     codemap::FileSpanRef,
     environment::{Globals, Module},
+    errors::Diagnostic,
     eval::Evaluator,
     syntax::{AstModule, Dialect},
 };
@@ -45,3 +48,36 @@ f()            # 2
     evaluator.eval_module(ast, &globals).unwrap();
     assert_eq!(4, counter.get());
 }
+
+#[test]
+fn before_stmt_fallible() {
+    let module = Module::new();
+    let globals = Globals::standard();
+    let mut evaluator = Evaluator::new(&module);
+    let counter = Cell::new(0);
+    let before_stmt = |span: FileSpanRef, _eval: &mut Evaluator<'_, '_>| {
+        counter.set(counter.get() + 1);
+        if counter.get() == 100 {
+            Err(anyhow!("Cancelled at {}", span.file.filename()))
+        } else {
+            Ok(())
+        }
+    };
+    evaluator.before_stmt_fallible(&before_stmt);
+
+    let program = "\
+def f():
+  x = 0
+  for i in range(1000):
+    x += i
+  return x
+f()
+";
+    let ast = AstModule::parse("a.star", program.to_owned(), &Dialect::Extended).unwrap();
+    let err = evaluator.eval_module(ast, &globals).unwrap_err();
+    // Evaluation stops at the first error
+    assert_eq!(100, counter.get());
+    let diag = err.downcast_ref::<Diagnostic>().unwrap();
+    assert_eq!("Cancelled at a.star", diag.message.to_string());
+    assert_eq!("a.star:4:5-11", diag.span.as_ref().unwrap().to_string());
+}
@@ -122,6 +122,29 @@ fn test_extra_args_native() {
     assert::fail(r#""bonbon".find()"#, "Wrong number of");
 }
 
+#[test]
+fn test_extra_named_did_you_mean() {
+    let mut a = Assert::new();
+    a.module("m", "def f(x, timeout = 1): return timeout");
+    a.fail(
+        "load('m', 'f')\nf(1, timout = 2)",
+        "Found extra named parameter `timout` for call to f, did you mean `timeout`?",
+    );
+    let err = a.fail("load('m', 'f')\nf(1, colour = 2)", "extra named");
+    assert!(!err.to_string().contains("did you mean"));
+}
+
+#[test]
+fn test_extra_named_did_you_mean_native() {
+    assert::fail(
+        "sorted([1], revers = True)",
+        "Found extra named parameter `revers` for call to sorted, did you mean `reverse`?",
+    );
+    // Positional-only parameters are never suggested.
+    let err = assert::fail(r#""bonbon".find("on", needl = "on")"#, "extra named");
+    assert!(!err.to_string().contains("did you mean"));
+}
+
 #[test]
 fn test_insufficient_args_native() {
     assert::fails(
@@ -242,3 +265,99 @@ f(1)
         "Missing parameter `y`",
     );
 }
+
+#[test]
+fn test_forward_kwargs() {
+    // Forwarding `**kwargs` unchanged takes a fast path,
+    // make sure the callee still gets its own dictionary.
+    assert::pass(
+        r#"
+def inner(**kwargs):
+    kwargs["inner"] = True
+    return kwargs
+
+def middle(*args, **kwargs):
+    res = inner(*args, **kwargs)
+    kwargs["middle"] = True
+    return res
+
+def outer(**kwargs):
+    res = middle(**kwargs)
+    return (kwargs, res)
+
+d = {"a": 1, "b": 2}
+(seen, res) = outer(**d)
+assert_eq(d, {"a": 1, "b": 2})
+assert_eq(seen, {"a": 1, "b": 2})
+assert_eq(res, {"a": 1, "b": 2, "inner": True})
+res["extra"] = 1
+assert_eq(inner(**d), {"a": 1, "b": 2, "inner": True})
+"#,
+    );
+    // Keys which name a parameter still get bound to it.
+    assert::pass(
+        r#"
+def f(a, **kwargs):
+    return (a, kwargs)
+def g(**kwargs):
+    return f(**kwargs)
+assert_eq(g(a = 1, b = 2), (1, {"b": 2}))
+assert_eq(g(b = 2, a = 1), (1, {"b": 2}))
+"#,
+    );
+    assert::fail(
+        "def f(**kwargs): pass\nf(**{1: 2})",
+        "Keys of **kwargs must be strings",
+    );
+    assert::fail("def f(a, **kwargs): pass\nf(1, **{'a': 2})", "occurs both");
+}
+
+#[test]
+fn test_positional_only_call_errors() {
+    // Purely positional calls take a fast path, make sure it reports exactly
+    // the same errors as the general path.
+    let f = |x| format!("def f(a, b, c = 3, *args, d, **kwargs): pass\n{}", x);
+    assert::pass(&f("f(1, 2, d = 4)"));
+    assert::fail(&f("f(1)"), "Missing parameter `b` for call to f");
+    assert::fail(&f("f(1, 2, 3)"), "Missing parameter `d` for call to f");
+    assert::fail(
+        "def g(a, b = 1): pass\ng(1, 2, 3)",
+        "Found 1 extra positional parameter(s) for call to g",
+    );
+    assert::fail(
+        "def g(a, b = 1): pass\ng()",
+        "Missing parameter `a` for call to g",
+    );
+    assert::pass(
+        r#"
+def g(a, b = [], *args, **kwargs):
+    return (a, b, args, kwargs)
+assert_eq(g(1), (1, [], (), {}))
+assert_eq(g(1, 2), (1, 2, (), {}))
+assert_eq(g(1, 2, 3), (1, 2, (3,), {}))
+"#,
+    );
+}
+
+#[test]
+fn test_kwargs_grow() {
+    // The `kwargs` dict is sized by the number of named arguments,
+    // check it still grows past that, and past the size where it gets an index.
+    assert::pass(
+        r#"
+def f(x, **kwargs):
+    kwargs["extra"] = x
+    return kwargs
+
+assert_eq(f(1, a = 2), {"a": 2, "extra": 1})
+assert_eq(list(f(1, b = 2, a = 3).keys()), ["b", "a", "extra"])
+
+many = {"k{}".format(i): i for i in range(20)}
+res = f(0, z = 1, **many)
+assert_eq(len(res), 22)
+assert_eq(list(res.keys())[:3], ["z", "k0", "k1"])
+assert_eq(res["k19"], 19)
+assert_eq(res["extra"], 0)
+"#,
+    );
+}
@@ -17,7 +17,17 @@
 
 //! Test for `def` and `lambda`.
 
-use crate::{assert, assert::Assert, environment::Module, eval::Evaluator};
+use gazebo::prelude::*;
+
+use crate::{
+    assert,
+    assert::Assert,
+    environment::{FrozenModule, Globals, Module},
+    errors::Diagnostic,
+    eval::{Evaluator, FrozenDef},
+    syntax::{AstModule, Dialect},
+    values::{dict::Dict, ValueError},
+};
 
 #[test]
 fn test_lambda() {
@@ -174,6 +184,63 @@ value = {"test": "hello"}
     assert_eq!(res.to_str(), "hello");
 }
 
+#[test]
+fn test_freeze_exports_only() {
+    let program = r#"
+_big = [str(i) for i in range(10000)]
+_table = {"x": [1, 2, 3]}
+_offset = [10]
+def _get(k):
+    return _table[k]
+def lookup(k):
+    add = lambda: _offset[0]
+    return len(_get(k)) + add()
+answer = 42
+"#;
+    let freeze = |exports_only: bool| -> FrozenModule {
+        let module = Module::new();
+        {
+            let mut eval = Evaluator::new(&module);
+            let ast =
+                AstModule::parse("prelude.bzl", program.to_owned(), &Dialect::Extended).unwrap();
+            eval.eval_module(ast, &Globals::standard()).unwrap();
+        }
+        if exports_only {
+            module.freeze_exports_only().unwrap()
+        } else {
+            module.freeze().unwrap()
+        }
+    };
+    let all = freeze(false);
+    let exports = freeze(true);
+
+    // The big list isn't used by anything exported, so is dropped.
+    assert!(all.get_any_visibility("_big").is_some());
+    assert!(exports.get_any_visibility("_big").is_none());
+    assert!(
+        exports.frozen_heap().allocated_bytes() * 2 < all.frozen_heap().allocated_bytes(),
+        "{} vs {}",
+        exports.frozen_heap().allocated_bytes(),
+        all.frozen_heap().allocated_bytes()
+    );
+
+    // Values used by exported functions, directly, via other functions,
+    // or from nested functions, are kept.
+    for name in ["_table", "_offset", "_get"] {
+        assert!(exports.get_any_visibility(name).is_some(), "{}", name);
+    }
+    assert_eq!(
+        exports.get("answer").unwrap().value().unpack_int(),
+        Some(42)
+    );
+    let lookup = exports.get("lookup").unwrap();
+    let module = Module::new();
+    let mut eval = Evaluator::new(&module);
+    let x = module.heap().alloc("x");
+    let res = eval.eval_function(lookup.value(), &[x], &[]).unwrap();
+    assert_eq!(res.unpack_int(), Some(13));
+}
+
 #[test]
 fn test_context_captured() {
     let mut a = Assert::new();
@@ -187,3 +254,122 @@ fn test_lambda_errors() {
     // Test from https://github.com/facebookexperimental/starlark-rust/issues/36
     assert::fail("lambda a,a:a", "duplicated parameter name");
 }
+
+#[test]
+fn test_frozen_def_mutates_captured() {
+    let mut a = Assert::new();
+    a.module(
+        "m.bzl",
+        r#"
+rules = []
+def add_rule(x):
+    rules.append(x)
+def make():
+    names = {}
+    def add(x):
+        names[x] = True
+    return add
+add_name = make()
+"#,
+    );
+    a.fail(
+        "load('m.bzl', 'add_rule')\nadd_rule(1)",
+        "Can't mutate `rules`, captured by function `m.bzl.add_rule` defined at m.bzl:3:1",
+    );
+    a.fail(
+        "load('m.bzl', 'add_name')\nadd_name(1)",
+        "Can't mutate `names`, captured by function `m.bzl.add` defined at m.bzl:7:5",
+    );
+    // The original error is kept underneath.
+    let e = a.fail(
+        "load('m.bzl', 'add_rule')\nadd_rule(1)",
+        "frozen: Immutable",
+    );
+    assert!(matches!(
+        Diagnostic::from_error(&e)
+            .unwrap()
+            .message
+            .downcast_ref::<ValueError>(),
+        Some(ValueError::CannotMutateImmutableValue)
+    ));
+    // Loaded values are frozen, so mutating them from a function fails the same way.
+    a.fail(
+        "load('m.bzl', 'rules')\ndef f():\n  rules.append(1)\nf()",
+        "Can't mutate `rules`, captured by function `assert.bzl.f` defined at assert.bzl:2:1",
+    );
+    // Values which aren't captured are still just immutable.
+    a.fail(
+        "load('m.bzl', 'rules')\ndef f(xs):\n  xs.append(1)\nf(rules)",
+        "Immutable",
+    );
+}
+
+#[test]
+fn test_frozen_def_captured() {
+    let mut a = Assert::new();
+    let m = a.module(
+        "m.bzl",
+        r#"
+rules = []
+def add_rule(x):
+    rules.append(len(x) + limit)
+limit = 3
+def make():
+    names = {}
+    def add(x):
+        names[x] = limit
+    return add
+add_name = make()
+def pure(x):
+    return x
+"#,
+    );
+    let captured = |name| {
+        m.captured_variables(name)
+            .unwrap()
+            .into_map(|(name, value)| (name, value.value().to_repr()))
+    };
+    assert_eq!(
+        vec![
+            ("rules".to_owned(), "[]".to_owned()),
+            ("limit".to_owned(), "3".to_owned())
+        ],
+        captured("add_rule")
+    );
+    // Variables captured from an enclosing function come first.
+    assert_eq!(
+        vec![
+            ("names".to_owned(), "{}".to_owned()),
+            ("limit".to_owned(), "3".to_owned())
+        ],
+        captured("add_name")
+    );
+    assert!(captured("pure").is_empty());
+    assert!(m.captured_variables("rules").is_none());
+
+    let add_rule = m.get("add_rule").unwrap().downcast::<FrozenDef>().unwrap();
+    assert_eq!(
+        vec![("rules", "[]".to_owned()), ("limit", "3".to_owned())],
+        add_rule
+            .as_ref()
+            .captured()
+            .into_map(|(name, value)| (name, value.to_value().to_repr()))
+    );
+}
+
+#[test]
+fn test_frozen_value_mutated_error() {
+    let mut a = Assert::new();
+    let m = a.module("m.bzl", "names = {}");
+    let names = m.get("names").unwrap();
+    let e = Dict::from_value_mut(names.value()).err().unwrap();
+    // The public error is still `CannotMutateImmutableValue`.
+    assert!(matches!(
+        e.downcast_ref::<ValueError>(),
+        Some(ValueError::CannotMutateImmutableValue)
+    ));
+    assert_eq!(
+        "Value of type `dict` is frozen: Immutable",
+        format!("{:#}", e)
+    );
+}
@@ -218,7 +218,10 @@ def f4(a: "string") -> "string":
 
 #[test]
 fn test_module_docstring_parses() {
-    use crate::values::docs::{DocItem, DocString, Module};
+    use crate::{
+        environment::FrozenModule,
+        values::docs::{DocItem, DocString},
+    };
 
     let m1 = assert::pass_module(
         r#"
@@ -255,23 +258,22 @@ def f1():
 "#,
     );
 
-    let m1_docs = m1.documentation();
-    let m2_docs = m2.documentation();
-    let m3_docs = m3.documentation();
+    let docstring = |m: &FrozenModule| match m.documentation() {
+        Some(DocItem::Module(module)) => module.docs,
+        x => panic!("Expected module docs, got {:?}", x),
+    };
 
-    let expected_m1 = Some(DocItem::Module(Module {
-        docs: DocString::from_docstring(
-            DocStringKind::Starlark,
-            r"This is the summary of the module's docs
+    let expected_m1 = DocString::from_docstring(
+        DocStringKind::Starlark,
+        r"This is the summary of the module's docs
 
 Some extra details can go here,
     and indentation is kept as expected",
-        ),
-    }));
+    );
 
-    assert_eq!(expected_m1, m1_docs);
-    assert_eq!(None, m2_docs);
-    assert_eq!(None, m3_docs);
+    assert_eq!(expected_m1, docstring(&m1));
+    assert_eq!(None, docstring(&m2));
+    assert_eq!(None, docstring(&m3));
 }
 
 #[test]
@@ -347,6 +349,7 @@ def f1():
 Some extra details can go here,
     and indentation is kept as expected",
             ),
+            members: Vec::new(),
         })),
         members: hashmap! {
             "f1".to_owned() => Some(DocItem::Function(Function {
@@ -382,3 +385,144 @@ Some extra details can go here,
     assert_eq!(expected_m2, m2_docs);
     assert_eq!(expected_m3, m3_docs);
 }
+
+#[test]
+fn test_module_documentation_members() {
+    use gazebo::prelude::*;
+
+    use crate::values::docs::{DocItem, DocString, Member, Param, Property, Type};
+
+    let m = assert::pass_module(
+        r#"
+"""Rules for greeting."""
+
+def greet(name, greeting = "Hello"):
+    """
+    Greet someone.
+
+    Args:
+        name: Who to greet
+        greeting: What to say
+
+    Returns:
+        The greeting
+    """
+    return greeting + " " + name
+
+def shout(text):
+    """ Shout some text """
+    return text.upper()
+
+def _helper():
+    """ Not exported """
+    pass
+
+VERSION = "1.0"
+"#,
+    );
+
+    let module = match m.documentation() {
+        Some(DocItem::Module(module)) => module,
+        x => panic!("Expected module docs, got {:?}", x),
+    };
+    assert_eq!(
+        DocString::from_docstring(DocStringKind::Starlark, "Rules for greeting."),
+        module.docs
+    );
+    assert_eq!(
+        vec!["VERSION", "greet", "shout"],
+        module.members.map(|(name, _)| name.as_str())
+    );
+    assert_eq!(
+        Member::Property(Property {
+            docs: None,
+            typ: Some(Type {
+                raw_type: "string".to_owned()
+            }),
+        }),
+        module.members[0].1
+    );
+    let greet = match &module.members[1].1 {
+        Member::Function(f) => f,
+        x => panic!("Expected function docs, got {:?}", x),
+    };
+    assert_eq!(
+        DocString::from_docstring(DocStringKind::Starlark, "Greet someone."),
+        greet.docs
+    );
+    assert_eq!(
+        vec![
+            Param::Arg {
+                name: "name".to_owned(),
+                docs: DocString::from_docstring(DocStringKind::Starlark, "Who to greet"),
+                typ: None,
+                default_value: None,
+            },
+            Param::Arg {
+                name: "greeting".to_owned(),
+                docs: DocString::from_docstring(DocStringKind::Starlark, "What to say"),
+                typ: None,
+                default_value: Some("\"Hello\"".to_owned()),
+            },
+        ],
+        greet.params
+    );
+    assert_eq!(
+        DocString::from_docstring(DocStringKind::Starlark, "The greeting"),
+        greet.ret.docs
+    );
+
+    let all = match m.documentation_including_private() {
+        Some(DocItem::Module(module)) => module,
+        x => panic!("Expected module docs, got {:?}", x),
+    };
+    assert_eq!(
+        vec!["VERSION", "_helper", "greet", "shout"],
+        all.members.map(|(name, _)| name.as_str())
+    );
+
+    let markdown = m
+        .documentation()
+        .unwrap()
+        .render_as_markdown("greeting.bzl");
+    assert!(markdown.starts_with("# `greeting.bzl`\n\nRules for greeting.\n"));
+    assert!(markdown.contains("## `greet`\n"));
+    assert!(markdown.contains("* `name`: Who to greet\n"));
+    assert!(!markdown.contains("_helper"));
+}
+
+#[test]
+fn test_module_documentation_excludes_loaded() {
+    use crate::values::docs::DocItem;
+
+    let mut a = Assert::new();
+    a.module(
+        "lib.star",
+        r#"
+def helper():
+    pass
+def other():
+    pass
+"#,
+    );
+    let m = a.pass_module(
+        r#"
+load("lib.star", "helper", _alias = "other")
+def _local():
+    pass
+def exported():
+    pass
+"#,
+    );
+    let names = |docs: Option<DocItem>| -> Vec<String> {
+        match docs {
+            Some(DocItem::Module(module)) => module.members.into_iter().map(|(n, _)| n).collect(),
+            x => panic!("Expected module docs, got {:?}", x),
+        }
+    };
+    assert_eq!(vec!["exported"], names(m.documentation()));
+    assert_eq!(
+        vec!["_local", "exported"],
+        names(m.documentation_including_private())
+    );
+}
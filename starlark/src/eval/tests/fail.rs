@@ -0,0 +1,115 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Tests for the structured errors produced by `fail()`.
+
+use crate::{
+    assert,
+    environment::{Globals, Module},
+    errors::{Diagnostic, StarlarkFailError},
+    eval::Evaluator,
+    syntax::{AstModule, Dialect},
+};
+
+/// Evaluate `program`, which must fail, returning the error.
+fn eval_err(program: &str) -> anyhow::Error {
+    let module = Module::new();
+    let mut eval = Evaluator::new(&module);
+    let ast = AstModule::parse("a.star", program.to_owned(), &Dialect::Extended).unwrap();
+    eval.eval_module(ast, &Globals::standard()).unwrap_err()
+}
+
+#[test]
+fn test_fail_code_and_attrs() {
+    let err = eval_err(
+        r#"
+def check(x):
+    if x < 0:
+        fail("negative:", x, code = "E123", attrs = {"url": "https://example.com/E123", "value": x})
+check(-1)
+"#,
+    );
+    let fail = StarlarkFailError::from_error(&err).unwrap();
+    assert_eq!(Some("E123"), fail.code());
+    assert_eq!("negative: -1", fail.message());
+    assert_eq!(Some("https://example.com/E123"), fail.attr("url"));
+    assert_eq!(None, fail.attr("missing"));
+    assert_eq!(
+        &[
+            ("url".to_owned(), "https://example.com/E123".to_owned()),
+            ("value".to_owned(), "-1".to_owned())
+        ],
+        fail.attrs()
+    );
+
+    // The span and call stack are still available from the diagnostic.
+    let diag = Diagnostic::from_error(&err).unwrap();
+    assert_eq!(4, diag.primary_span().unwrap().begin_line);
+    assert_eq!(
+        vec!["a.star.check", "fail"],
+        diag.call_stack
+            .iter()
+            .map(|x| x.name.as_str())
+            .collect::<Vec<_>>()
+    );
+    // The rendered message doesn't mention the code or attributes.
+    assert_eq!("fail: negative: -1", diag.primary_message());
+}
+
+#[test]
+fn test_fail_without_code() {
+    let err = eval_err("fail('oops', 1, False, [2])");
+    let fail = StarlarkFailError::from_error(&err).unwrap();
+    assert_eq!(None, fail.code());
+    assert!(fail.attrs().is_empty());
+    assert_eq!("oops 1 False [2]", fail.message());
+    assert_eq!(
+        "fail: oops 1 False [2]",
+        Diagnostic::from_error(&err).unwrap().primary_message()
+    );
+    assert_eq!(
+        "fail:",
+        Diagnostic::from_error(&eval_err("fail()"))
+            .unwrap()
+            .primary_message()
+    );
+    assert_eq!(
+        "fail: ",
+        Diagnostic::from_error(&eval_err("fail('')"))
+            .unwrap()
+            .primary_message()
+    );
+}
+
+#[test]
+fn test_fail_attrs_stringified() {
+    let err = eval_err(r#"fail("x", attrs = {"s": "1", "n": 1, "l": [1, "a"], "none": None})"#);
+    let fail = StarlarkFailError::from_error(&err).unwrap();
+    assert_eq!(Some("1"), fail.attr("s"));
+    assert_eq!(Some("1"), fail.attr("n"));
+    assert_eq!(Some(r#"[1, "a"]"#), fail.attr("l"));
+    assert_eq!(Some("None"), fail.attr("none"));
+}
+
+#[test]
+fn test_fail_bad_arguments() {
+    assert::fail("fail('x', code = 1)", "Type of parameter");
+    assert::fail("fail('x', attrs = {1: 2})", "Type of parameter");
+    assert::fail("fail('x', attrs = [])", "Type of parameter");
+    // Errors other than `fail()` don't have a `StarlarkFailError`.
+    assert!(StarlarkFailError::from_error(&eval_err("1 + ''")).is_none());
+}
@@ -0,0 +1,227 @@
+/*
+ * Copyright 2018 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Test loops over dict views and `enumerate`, which are compiled specially.
+
+use crate::{
+    assert,
+    environment::{Globals, Module},
+    eval::Evaluator,
+    syntax::{AstModule, Dialect},
+};
+
+#[test]
+fn test_for_dict_views() {
+    assert::is_true(
+        r#"
+def f(d):
+    res = []
+    for k in d.keys():
+        res.append(k)
+    for v in d.values():
+        res.append(v)
+    for kv in d.items():
+        res.append(kv)
+    for k, v in d.items():
+        res.append(k + str(v))
+    return res
+f({"a": 1, "b": 2}) == ["a", "b", 1, 2, ("a", 1), ("b", 2), "a1", "b2"]
+"#,
+    );
+    assert::is_true(
+        r#"
+d = {"a": 1, "b": 2}
+[k + str(v) for k, v in d.items() if v > 1] == ["b2"] and {v: k for k, v in d.items()} == {1: "a", 2: "b"}
+"#,
+    );
+    assert::is_true(
+        r#"
+def f():
+    res = []
+    for k, v in {}.items():
+        res.append(k)
+    for i, x in enumerate([]):
+        res.append(i)
+    return res
+f() == []
+"#,
+    );
+}
+
+#[test]
+fn test_for_enumerate() {
+    assert::is_true(
+        r#"
+def f(xs):
+    res = []
+    for i, x in enumerate(xs):
+        res.append((x, i))
+    for p in enumerate(xs):
+        res.append(p)
+    return res
+f(["a", "b"]) == [("a", 0), ("b", 1), (0, "a"), (1, "b")]
+"#,
+    );
+    assert::is_true(r#"[i * x for i, x in enumerate(range(5, 8))] == [0, 6, 14]"#);
+    assert::fail(
+        "def f():\n  for i, x in enumerate(1): pass\nf()",
+        "not supported on type `int`",
+    );
+}
+
+#[test]
+fn test_for_dict_view_mutate() {
+    // The views are lists, so the dict can be modified while iterating over them.
+    assert::is_true(
+        r#"
+def f():
+    d = {"a": 1, "b": 2}
+    for k, v in d.items():
+        d[k + k] = v
+    for k in d.keys():
+        if len(k) == 1:
+            d.pop(k)
+    for v in d.values():
+        d.clear()
+    return d
+f() == {}
+"#,
+    );
+    assert::is_true(
+        r#"
+def f():
+    xs = [1, 2]
+    for i, x in enumerate(xs):
+        xs.append(i + x)
+    return xs
+f() == [1, 2, 1, 3]
+"#,
+    );
+    // But iterating over the dict itself still forbids that.
+    assert::fail(
+        r#"
+def f():
+    d = {"a": 1}
+    for k in d:
+        d[k + k] = 1
+f()
+"#,
+        "mutate an iterable",
+    );
+}
+
+#[test]
+fn test_for_dict_view_nested() {
+    assert::is_true(
+        r#"
+def f(d):
+    res = []
+    for k1, v1 in d.items():
+        for k2, v2 in d.items():
+            if k1 == k2:
+                continue
+            res.append(k1 + k2)
+            d[k1 + k2] = v1 + v2
+    return res
+d = {"a": 1, "b": 2}
+# Each loop sees the entries the dict had when it started.
+f(d) == ["ab", "ba", "bab"] and d == {"a": 1, "b": 2, "ab": 3, "ba": 3, "bab": 5}
+"#,
+    );
+    assert::is_true(
+        r#"
+def f(d):
+    for k, v in d.items():
+        for i, x in enumerate(v):
+            if x == "stop":
+                return (k, i)
+            if x == "skip":
+                break
+    return None
+f({"a": ["skip", "stop"], "b": ["x", "stop"]}) == ("b", 1)
+"#,
+    );
+}
+
+#[test]
+fn test_for_dict_view_not_dict() {
+    // The methods are called as usual on values which are not dicts.
+    assert::is_true(
+        r#"
+s = struct(items = lambda: [(1, 2), (3, 4)], keys = lambda: [5])
+def f():
+    res = []
+    for k, v in s.items():
+        res.append(k + v)
+    for k in s.keys():
+        res.append(k)
+    return res
+f() == [3, 7, 5]
+"#,
+    );
+    assert::fail(
+        "def f():\n  for k in [1].keys(): pass\nf()",
+        "has no attribute `keys`",
+    );
+    assert::fail(
+        r#"
+s = struct(items = lambda: [(1, 2, 3)])
+def f():
+    for k, v in s.items():
+        pass
+f()
+"#,
+        "Unpacked 3 values but expected 2",
+    );
+}
+
+#[test]
+fn test_for_dict_items_does_not_allocate() {
+    fn allocated_bytes(code: &str) -> usize {
+        let module = Module::new();
+        let mut eval = Evaluator::new(&module);
+        eval.disable_gc();
+        let code = format!(
+            r#"
+d = {{str(i): i for i in range(1000)}}
+items = d.items
+def f():
+    n = 0
+{}
+    return n
+n = f()
+"#,
+            code
+        );
+        let ast = AstModule::parse("alloc.star", code, &Dialect::Standard).unwrap();
+        eval.eval_module(ast, &Globals::standard()).unwrap();
+        assert_eq!(module.get("n").unwrap().unpack_int(), Some(999 * 1000 / 2));
+        module.heap().allocated_bytes()
+    }
+
+    // Calling the method through a variable allocates the list of tuples as usual.
+    let slow = allocated_bytes("    for k, v in items():\n        n += v");
+    let fast = allocated_bytes("    for k, v in d.items():\n        n += v");
+    // Neither the list nor the tuples were allocated.
+    let saved = 1000 * 3 * std::mem::size_of::<usize>();
+    assert!(
+        fast + saved < slow,
+        "Loop over d.items() allocated {} bytes, over items() {}",
+        fast,
+        slow
+    );
+}
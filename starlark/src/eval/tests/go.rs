@@ -77,7 +77,11 @@ fn test_go() {
     assert.conformance(test_case!("control.star"));
     assert.conformance_except(
         &ignore_bad_lines(
-            test_case!("dict.star"),
+            // We pop the last inserted pair, see test_popitem
+            &test_case!("dict.star").replace(
+                r#"[x7.popitem(), x7.popitem()], [("a", 1), ("b", 2)]"#,
+                r#"[x7.popitem(), x7.popitem()], [("b", 2), ("a", 1)]"#,
+            ),
             &[
                 "unknown binary op: dict \\\\+ dict",   // We support {} + {}
                 "cannot insert into frozen hash table", // We don't actually have freeze
@@ -93,8 +97,20 @@ fn test_go() {
         ],
     );
     assert.conformance(&ignore_bad_lines(
-        test_case!("float.star"),
+        // Ordering NaN is an error, rather than NaN being the greatest float
+        &test_case!("float.star")
+            .replace("[inf, neginf, nan, 1e300", "[inf, neginf, 1e300")
+            .replace("1e+300, +inf, nan]", "1e+300, +inf]"),
         &[
+            "nan >= nan",
+            "nan <= nan",
+            "(nan > nan)",
+            "(nan < nan)",
+            "sorted([7, 3, nan, 1, 9]",
+            "max([1, nan, 3])",
+            "max([nan, 2, 3])",
+            "min([1, nan, 3])",
+            "min([nan, 2, 3])",
             // int's outside our range
             "1229999999999999973",
             "9223372036854775808",
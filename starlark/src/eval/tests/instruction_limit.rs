@@ -0,0 +1,155 @@
+/*
+ * Copyright 2026 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use crate::{
+    environment::{FrozenModule, Globals, Module},
+    eval::Evaluator,
+    syntax::{AstModule, Dialect},
+};
+
+/// Evaluate `program` in a fresh module, returning the error message (if any)
+/// and the number of instructions executed.
+fn eval_with_limit(program: &str, limit: Option<u64>) -> (Option<String>, u64) {
+    let module = Module::new();
+    let globals = Globals::standard();
+    let mut eval = Evaluator::new(&module);
+    if let Some(limit) = limit {
+        eval.set_instruction_limit(limit);
+    }
+    let ast = AstModule::parse("a.star", program.to_owned(), &Dialect::Extended).unwrap();
+    let res = eval.eval_module(ast, &globals);
+    (
+        res.err().map(|e| e.to_string()),
+        eval.instructions_executed(),
+    )
+}
+
+#[test]
+fn test_instruction_limit_infinite_loop() {
+    let program = "\
+def f():
+  for x in range(2147483647):
+    y = x
+f()
+";
+    let first = eval_with_limit(program, Some(1000));
+    let second = eval_with_limit(program, Some(1000));
+    assert!(first
+        .0
+        .as_ref()
+        .unwrap()
+        .contains("exceeded the limit of 1000 instructions"));
+    // The statement which crossed the limit is counted, but not run.
+    assert_eq!(1001, first.1);
+    assert_eq!(first, second);
+}
+
+#[test]
+fn test_instruction_limit_native_callback() {
+    let program = "\
+def f(x):
+  y = -x
+  return y
+sorted(range(100), key = f)
+";
+    let (err, executed) = eval_with_limit(program, Some(1_000_000));
+    assert_eq!(None, err);
+    // Each call to `f` runs two statements.
+    assert!(executed >= 200, "{}", executed);
+    // Statements run by the callback are counted against the same limit.
+    let (err, limited) = eval_with_limit(program, Some(executed - 1));
+    assert!(err.unwrap().contains("exceeded the limit"));
+    assert_eq!(executed, limited);
+    assert_eq!(None, eval_with_limit(program, Some(executed)).0);
+}
+
+#[test]
+fn test_instruction_limit_not_set() {
+    assert_eq!((None, 0), eval_with_limit("x = 1\ny = x", None));
+}
+
+#[test]
+fn test_instruction_limit_counts_loops() {
+    // A comprehension has no statements, so only its iterations are counted.
+    let (err, executed) = eval_with_limit("x = [y for y in range(2147483647)]", Some(1000));
+    assert!(err
+        .unwrap()
+        .contains("exceeded the limit of 1000 instructions"));
+    assert_eq!(1001, executed);
+
+    for (program, iterations) in [
+        ("x = [y for y in range(2000)]", 2000),
+        ("x = {y: y for y in range(2000) if y}", 2000),
+        (
+            "def f():\n  for i, x in enumerate(range(2000)):\n    pass\nf()",
+            2000,
+        ),
+        (
+            "\
+d = dict(zip(range(2000), range(2000)))
+def f():
+  for k, v in d.items():
+    pass
+  for k in d.keys():
+    pass
+f()
+",
+            4000,
+        ),
+    ] {
+        let (err, executed) = eval_with_limit(program, Some(u64::MAX));
+        assert_eq!(None, err);
+        assert!(executed >= iterations, "{}: {}", program, executed);
+        let (err, limited) = eval_with_limit(program, Some(executed - 1));
+        assert!(err.unwrap().contains("exceeded the limit"), "{}", program);
+        assert_eq!(executed, limited);
+        assert_eq!(None, eval_with_limit(program, Some(executed)).0);
+    }
+}
+
+#[test]
+fn test_instruction_limit_precompiled() {
+    // `f` is compiled when the library is loaded, so its statements are only counted if that
+    // was instrumented, while its loop iterations are always counted.
+    fn load(instrument: bool) -> FrozenModule {
+        let module = Module::new();
+        let globals = Globals::standard();
+        let mut eval = Evaluator::new(&module);
+        if instrument {
+            eval.enable_before_stmt_instrumentation();
+        }
+        let program = "def f():\n  a = 1\n  b = 2\n  for x in range(10):\n    y = x\n";
+        let ast = AstModule::parse("lib.star", program.to_owned(), &Dialect::Extended).unwrap();
+        eval.eval_module(ast, &globals).unwrap();
+        module.freeze().unwrap()
+    }
+
+    let run = |lib: &FrozenModule| {
+        let module = Module::new();
+        module.import_public_symbols(lib);
+        let globals = Globals::standard();
+        let mut eval = Evaluator::new(&module);
+        eval.set_instruction_limit(u64::MAX);
+        let ast = AstModule::parse("a.star", "f()".to_owned(), &Dialect::Extended).unwrap();
+        eval.eval_module(ast, &globals).unwrap();
+        eval.instructions_executed()
+    };
+    // The call statement, and the 10 loop iterations.
+    assert_eq!(11, run(&load(false)));
+    // Plus the 3 statements of `f`, and the statement in each iteration.
+    assert_eq!(24, run(&load(true)));
+}
@@ -30,10 +30,12 @@ use crate as starlark;
 use crate::{
     assert,
     assert::Assert,
-    environment::{GlobalsBuilder, Module},
-    eval::Evaluator,
+    environment::{FrozenModule, Globals, GlobalsBuilder, Module},
+    eval::{ArgumentsBuilder, Evaluator, ReturnFileLoader},
     syntax::{AstModule, Dialect},
-    values::{any::StarlarkAny, none::NoneType, Freeze, NoSerialize, StarlarkValue, Value},
+    values::{
+        any::StarlarkAny, dict::Dict, none::NoneType, Freeze, NoSerialize, StarlarkValue, Value,
+    },
 };
 
 #[test]
@@ -117,6 +119,255 @@ v == '{}' or v == '{}'"#,
     ))
 }
 
+#[test]
+fn test_export_as_all_module_assignments() {
+    use std::fmt;
+
+    use crate::values::Heap;
+
+    #[derive(Debug, Default, AnyLifetime, NoSerialize)]
+    struct Recorder(Mutex<Vec<String>>);
+
+    impl fmt::Display for Recorder {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "recorder({})", self.0.lock().unwrap().join(", "))
+        }
+    }
+
+    starlark_simple_value!(Recorder);
+    impl<'v> StarlarkValue<'v> for Recorder {
+        starlark_type!("recorder");
+
+        fn export_as(&self, variable_name: &str, _eval: &mut Evaluator<'v, '_>) {
+            self.0.lock().unwrap().push(variable_name.to_owned());
+        }
+
+        // Produces a fresh recorder, so `+=` shows what name the result is stored under.
+        fn add(&self, _rhs: Value<'v>, heap: &'v Heap) -> anyhow::Result<Value<'v>> {
+            Ok(heap.alloc(Recorder::default()))
+        }
+    }
+
+    #[starlark_module]
+    fn recorder(builder: &mut GlobalsBuilder) {
+        fn recorder() -> anyhow::Result<Recorder> {
+            Ok(Recorder::default())
+        }
+    }
+
+    let mut a = Assert::new();
+    a.globals_add(recorder);
+    a.module(
+        "a",
+        r#"
+x = recorder()
+y = x
+xs = [recorder() for _ in range(1)]
+def make():
+    r = recorder()
+    return r
+made = make()
+for looped in [recorder()]:
+    pass
+summed = recorder()
+summed += 1
+"#,
+    );
+    a.pass(
+        r#"
+load('a', 'x', 'y', 'xs', 'looped', 'summed', alias = 'made')
+assert_eq(str(x), 'recorder(x, y)')
+assert_eq(str(xs[0]), 'recorder()')
+assert_eq(str(looped), 'recorder(looped)')
+assert_eq(str(summed), 'recorder(summed)')
+# Loaded values are frozen, so are not exported again, even under an alias.
+assert_eq(str(alias), 'recorder(made)')
+"#,
+    );
+}
+
+#[test]
+fn test_freeze_error_path() {
+    use std::fmt;
+
+    use crate::values::{AllocValue, Freezer, Heap, Trace};
+
+    #[derive(Debug, Trace, AnyLifetime, NoSerialize)]
+    struct Unfreezable;
+
+    impl fmt::Display for Unfreezable {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "unfreezable")
+        }
+    }
+
+    impl<'v> StarlarkValue<'v> for Unfreezable {
+        starlark_type!("unfreezable");
+    }
+
+    impl<'v> AllocValue<'v> for Unfreezable {
+        fn alloc_value(self, heap: &'v Heap) -> Value<'v> {
+            heap.alloc_complex(self)
+        }
+    }
+
+    impl Freeze for Unfreezable {
+        type Frozen = Unfreezable;
+        fn freeze(self, _freezer: &Freezer) -> anyhow::Result<Self::Frozen> {
+            Err(anyhow::anyhow!("nope"))
+        }
+    }
+
+    #[starlark_module]
+    fn unfreezable(builder: &mut GlobalsBuilder) {
+        fn unfreezable() -> anyhow::Result<Unfreezable> {
+            Ok(Unfreezable)
+        }
+    }
+
+    fn freeze_error(program: &str) -> String {
+        let module = Module::new();
+        let globals = GlobalsBuilder::extended().with(unfreezable).build();
+        let mut eval = Evaluator::new(&module);
+        let ast = AstModule::parse("a", program.to_owned(), &Dialect::Extended).unwrap();
+        eval.eval_module(ast, &globals).unwrap();
+        // The underlying error is the source, so only shown with the alternate format.
+        format!("{:#}", module.freeze().unwrap_err())
+    }
+
+    assert_eq!(
+        "Failed to freeze `xs[1][\"key\"]`: nope",
+        freeze_error("xs = [1, {\"key\": unfreezable()}]")
+    );
+    assert_eq!(
+        "Failed to freeze `t[0].field[2]`: nope",
+        freeze_error("t = (struct(field = (1, 2, unfreezable())),)")
+    );
+    assert_eq!(
+        "Failed to freeze `f.<captured x>`: nope",
+        freeze_error(
+            r#"
+def make():
+    x = unfreezable()
+    def f():
+        return x
+    return f
+f = make()
+"#
+        )
+    );
+}
+
+#[test]
+fn test_for_each_element() {
+    use std::{
+        ops::ControlFlow,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use crate::{environment::Globals, values::Heap};
+
+    // The numbers below `limit`, counting how often a `for` loop iterates them.
+    #[derive(Debug, Display, AnyLifetime, NoSerialize)]
+    #[display(fmt = "numbers({})", limit)]
+    struct Numbers {
+        limit: i32,
+        loops: AtomicUsize,
+    }
+
+    starlark_simple_value!(Numbers);
+    impl<'v> StarlarkValue<'v> for Numbers {
+        starlark_type!("numbers");
+
+        fn iterate<'a>(
+            &'a self,
+            _heap: &'v Heap,
+        ) -> anyhow::Result<Box<dyn Iterator<Item = Value<'v>> + 'a>>
+        where
+            'v: 'a,
+        {
+            Ok(box (0..self.limit).map(Value::new_int))
+        }
+
+        fn for_each_element(
+            &self,
+            _heap: &'v Heap,
+            f: &mut dyn FnMut(Value<'v>) -> ControlFlow<()>,
+        ) -> anyhow::Result<()> {
+            self.loops.fetch_add(1, Ordering::SeqCst);
+            for i in 0..self.limit {
+                if f(Value::new_int(i)).is_break() {
+                    break;
+                }
+            }
+            Ok(())
+        }
+    }
+
+    let module = Module::new();
+    let numbers = |limit| Numbers {
+        limit,
+        loops: AtomicUsize::new(0),
+    };
+    module.set("big", module.heap().alloc(numbers(1000)));
+    module.set("small", module.heap().alloc(numbers(6)));
+    let mut eval = Evaluator::new(&module);
+    let ast = AstModule::parse(
+        "a.star",
+        r#"
+def count(xs):
+    n = 0
+    for x in xs:
+        n += 1
+    return n
+def first_over(xs, limit):
+    for x in xs:
+        if x > limit:
+            return x
+def count_until(xs, stop):
+    n = 0
+    for x in xs:
+        if x == stop:
+            break
+        n += 1
+    return n
+def evens(xs):
+    n = 0
+    for x in xs:
+        if x % 2:
+            continue
+        n += 1
+    return n
+def fails(xs):
+    for x in xs:
+        if x == 3:
+            fail("stop at 3")
+(count(big), first_over(big, 10), count_until(big, 5), evens(small), list(small))
+"#
+        .to_owned(),
+        &Dialect::Extended,
+    )
+    .unwrap();
+    let res = eval.eval_module(ast, &Globals::standard()).unwrap();
+    assert_eq!("(1000, 11, 5, 3, [0, 1, 2, 3, 4, 5])", res.to_repr());
+
+    let fails = module.get("fails").unwrap();
+    let small = module.get("small").unwrap();
+    let err = eval.eval_function(fails, &[small], &[]).unwrap_err();
+    assert!(err.to_string().contains("stop at 3"), "{}", err);
+
+    // Each loop went through `for_each_element` exactly once, while `list` uses `iterate`.
+    let loops = |name| {
+        let x = module.get(name).unwrap();
+        x.downcast_ref::<Numbers>()
+            .unwrap()
+            .loops
+            .load(Ordering::SeqCst)
+    };
+    assert_eq!(3, loops("big"));
+    assert_eq!(2, loops("small"));
+}
+
 #[test]
 // Test that we can express something that loads symbols into the exported module
 fn test_load_symbols() {
@@ -147,6 +398,66 @@ fn test_load_public_symbols_does_not_reexport() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_add_symbols_from() -> anyhow::Result<()> {
+    // Pre-bound symbols are never re-exported, so compare against loads which aren't either.
+    let mut dialect = Dialect::Extended;
+    dialect.enable_load_reexport = false;
+    let mut a = Assert::new();
+    a.dialect(&dialect);
+    let lib = a.module(
+        "lib",
+        "def double(x):\n    return x * 2\nTEN = 10\n_hidden = 1",
+    );
+
+    // `len` is bound at module level, so must shadow the global, and `ten` is reassigned.
+    const PROGRAM: &str = "y = double(ten)\nz = len\nten = 11\n";
+    let run = |prefix: &str, symbols: &[(&str, &str)]| -> anyhow::Result<FrozenModule> {
+        let module = Module::new();
+        module.add_symbols_from(&lib, symbols)?;
+        let modules = HashMap::from([("lib", &lib)]);
+        let loader = ReturnFileLoader { modules: &modules };
+        let mut eval = Evaluator::new(&module);
+        eval.set_loader(&loader);
+        let ast = AstModule::parse("m.star", format!("{}{}", prefix, PROGRAM), &dialect)?;
+        eval.eval_module(ast, &Globals::extended())?;
+        drop(eval);
+        module.freeze()
+    };
+    let loaded = run("load('lib', 'double', ten = 'TEN', len = 'TEN')\n", &[])?;
+    let bound = run("", &[("double", "double"), ("ten", "TEN"), ("len", "TEN")])?;
+
+    let describe = |m: &FrozenModule| {
+        ["double", "len", "ten", "y", "z"]
+            .iter()
+            .map(|name| {
+                let (value, vis) = m.get_any_visibility(name).unwrap();
+                format!("{} = {} {:?}", name, value.value(), vis)
+            })
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(describe(&loaded), describe(&bound));
+    assert_eq!(20, bound.get("y").unwrap().value().unpack_int().unwrap());
+    assert_eq!(10, bound.get("z").unwrap().value().unpack_int().unwrap());
+    assert_eq!(11, bound.get("ten").unwrap().value().unpack_int().unwrap());
+    assert!(bound.get("double").is_none());
+
+    // A failure binds none of the symbols.
+    let module = Module::new();
+    let err = module
+        .add_symbols_from(&lib, &[("ten", "TEN"), ("hidden", "_hidden")])
+        .unwrap_err();
+    assert!(
+        err.to_string().contains("private symbol `_hidden`"),
+        "{}",
+        err
+    );
+    let err = module.add_symbols_from(&lib, &[("x", "TEM")]).unwrap_err();
+    assert!(err.to_string().contains("did you mean `TEN`"), "{}", err);
+    assert!(module.get_any_visibility("ten").is_none());
+    Ok(())
+}
+
 #[test]
 // Test that we can express something that loads symbols into the exported module,
 // but not using the very dubious `set_module_variable_at_some_point`.
@@ -183,6 +494,93 @@ fn test_load_symbols_extra() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_module_extra_value() -> anyhow::Result<()> {
+    #[starlark_module]
+    fn module(builder: &mut GlobalsBuilder) {
+        fn record(name: &str, value: Value<'v>) -> anyhow::Result<NoneType> {
+            let extra = eval.module().extra_value().unwrap();
+            let mut extra = Dict::from_value_mut(extra)?.unwrap();
+            extra.insert_hashed(heap.alloc(name).get_hashed()?, value);
+            Ok(NoneType)
+        }
+    }
+
+    let modu = Module::new();
+    let globals = GlobalsBuilder::extended().with(module).build();
+    modu.set_extra_value(modu.heap().alloc(Dict::default()));
+    let mut eval = Evaluator::new(&modu);
+    eval.eval_module(
+        AstModule::parse(
+            "a",
+            "record('x', [1, 2])\nrecord('y', 'hello')".to_owned(),
+            &Dialect::Extended,
+        )?,
+        &globals,
+    )?;
+    // The extra value must survive a GC, and follow its contents if they move
+    unsafe { eval.garbage_collect() };
+    assert_eq!(
+        modu.extra_value().unwrap().to_repr(),
+        r#"{"x": [1, 2], "y": "hello"}"#
+    );
+
+    let frozen = modu.freeze()?;
+    let extra = frozen.extra_value().unwrap();
+    assert!(extra.value().unpack_frozen().is_some());
+    assert_eq!(extra.value().to_repr(), r#"{"x": [1, 2], "y": "hello"}"#);
+    assert!(Module::new().freeze()?.extra_value().is_none());
+    Ok(())
+}
+
+#[test]
+fn test_native_return_type() -> anyhow::Result<()> {
+    use crate::values::docs::{DocItem, Type};
+
+    #[starlark_module]
+    fn module(builder: &mut GlobalsBuilder) {
+        #[starlark(return_type("[str.type]"))]
+        fn strings(good: bool) -> anyhow::Result<Value<'v>> {
+            Ok(if good {
+                heap.alloc(vec!["a", "b"])
+            } else {
+                heap.alloc(vec![1, 2])
+            })
+        }
+    }
+
+    let globals = GlobalsBuilder::new().with(module).build();
+    let run = |code: &str, check: bool| {
+        let modu = Module::new();
+        let mut eval = Evaluator::new(&modu);
+        eval.enable_runtime_typecheck(check);
+        eval.eval_module(
+            AstModule::parse("a", code.to_owned(), &Dialect::Extended)?,
+            &globals,
+        )
+        .map(|x| x.to_repr())
+    };
+    assert_eq!(run("strings(True)", true)?, r#"["a", "b"]"#);
+    assert_eq!(run("strings(False)", false)?, "[1, 2]");
+    let err = format!("{:#}", run("strings(False)", true).unwrap_err());
+    assert!(
+        err.contains("for return type of `strings`"),
+        "Wrong error: {}",
+        err
+    );
+
+    match globals.member_documentation().remove("strings") {
+        Some(Some(DocItem::Function(f))) => assert_eq!(
+            f.ret.typ,
+            Some(Type {
+                raw_type: "[str.type]".to_owned()
+            })
+        ),
+        x => panic!("Expected function documentation, got {:?}", x),
+    }
+    Ok(())
+}
+
 #[test]
 fn test_repr_str() {
     #[derive(AnyLifetime, Debug, Display)]
@@ -446,3 +844,428 @@ fun
         .unwrap();
     assert_eq!(v.unpack_str(), Some("(8, \"hello\", 1)"))
 }
+
+#[test]
+fn test_arguments_builder() {
+    let code = r#"
+def f(a, b, c = 3, *args, d, **kwargs):
+    return str((a, b, c, args, d, kwargs))
+"#;
+    let f = assert::pass(&format!("{}f", code));
+    let args = assert::pass("(2, 30, 40)");
+    let kwargs = assert::pass("{'d': 4, 'e': 5}");
+    let env = Module::new();
+    let mut eval = Evaluator::new(&env);
+    let one = Value::new_int(1);
+
+    let res = ArgumentsBuilder::new()
+        .positional([one])
+        .named([("z", Value::new_int(0))])
+        .args(args.value())
+        .kwargs(kwargs.value())
+        .call(f.value(), &mut eval)
+        .unwrap();
+    let expected = assert::pass(&format!(
+        "{}f(1, z = 0, *(2, 30, 40), **{{'d': 4, 'e': 5}})",
+        code
+    ));
+    assert_eq!(res.unpack_str(), expected.value().unpack_str());
+    assert_eq!(
+        res.unpack_str(),
+        Some("(1, 2, 30, (40,), 4, {\"z\": 0, \"e\": 5})")
+    );
+
+    // The same errors as passing the arguments from Starlark.
+    for (builder, call, msg) in [
+        (
+            ArgumentsBuilder::new().positional([one]).args(one),
+            "f(1, *1)",
+            "The argument provided for *args is not iterable",
+        ),
+        (
+            ArgumentsBuilder::new().positional([one]).kwargs(one),
+            "f(1, **1)",
+            "The argument provided for **kwargs is not a dictionary",
+        ),
+        (
+            ArgumentsBuilder::new()
+                .positional([one, one])
+                .named([("d", one)])
+                .kwargs(kwargs.value()),
+            "f(1, 1, d = 1, **{'d': 4, 'e': 5})",
+            "Parameter `d` occurs both explicitly and in **kwargs",
+        ),
+    ] {
+        let err = builder.call(f.value(), &mut eval).unwrap_err();
+        assert!(err.to_string().contains(msg), "{}", err);
+        assert::fail(&format!("{}{}", code, call), msg);
+    }
+
+    // Starlark rejects this when parsing, but a builder can't.
+    let err = ArgumentsBuilder::new()
+        .positional([one, one])
+        .named([("d", one), ("d", one)])
+        .call(f.value(), &mut eval)
+        .unwrap_err();
+    assert!(err.to_string().contains("Repeated named argument `d`"));
+}
+
+#[test]
+fn test_get_attr_method() {
+    use once_cell::sync::Lazy;
+
+    use crate::{
+        environment::Globals,
+        values::{Heap, ValueLike},
+    };
+
+    #[starlark_module]
+    fn helpers(builder: &mut GlobalsBuilder) {
+        fn double(this: Value<'v>) -> anyhow::Result<Value<'v>> {
+            this.mul(Value::new_int(2), heap)
+        }
+    }
+
+    static HELPERS: Lazy<Globals> = Lazy::new(|| GlobalsBuilder::new().with(helpers).build());
+
+    // A value whose methods are resolved dynamically
+    #[derive(Debug, Display, AnyLifetime, NoSerialize)]
+    #[display(fmt = "proxy({})", _0)]
+    struct Proxy(i32);
+    starlark_simple_value!(Proxy);
+    impl<'v> StarlarkValue<'v> for Proxy {
+        starlark_type!("proxy");
+
+        fn mul(&self, other: Value<'v>, _heap: &'v Heap) -> anyhow::Result<Value<'v>> {
+            Ok(Value::new_int(self.0 * other.unpack_int().unwrap()))
+        }
+
+        fn get_attr_method(
+            &self,
+            me: Value<'v>,
+            attribute: &str,
+            heap: &'v Heap,
+        ) -> Option<Value<'v>> {
+            match attribute {
+                "double" => {
+                    let double = HELPERS.get_frozen("double").unwrap();
+                    Some(heap.alloc_bound_method(me, double.to_value()))
+                }
+                _ => None,
+            }
+        }
+
+        fn dir_attr(&self) -> Vec<String> {
+            vec!["double".to_owned()]
+        }
+    }
+
+    let mut a = Assert::new();
+    a.globals_add(|gb| gb.set("obj", Proxy(21)));
+    a.is_true("obj.double() == obj * 2");
+    a.is_true("obj.double() == 42");
+    a.is_true("getattr(obj, 'double')() == 42");
+    a.is_true("f = obj.double\nf() == 42");
+    a.is_true("hasattr(obj, 'double') and dir(obj) == ['double']");
+    a.fail("obj.double(1)", "extra positional");
+    a.fail("obj.triple()", "has no attribute");
+}
+
+#[test]
+fn test_getattr_hasattr_dir_consistent() {
+    use crate::{
+        environment::{Methods, MethodsBuilder, MethodsStatic},
+        values::Heap,
+    };
+
+    #[starlark_module]
+    fn methods(builder: &mut MethodsBuilder) {
+        #[starlark(attribute)]
+        fn broken(this: Value) -> anyhow::Result<i32> {
+            Err(anyhow::anyhow!("broken attribute"))
+        }
+
+        fn answer(this: Value) -> anyhow::Result<i32> {
+            Ok(42)
+        }
+    }
+
+    // Implements `get_attr` and `dir_attr`, but not `has_attr`, which `hasattr` does without.
+    #[derive(Debug, Display, AnyLifetime, NoSerialize)]
+    #[display(fmt = "custom")]
+    struct Custom;
+    starlark_simple_value!(Custom);
+    impl<'v> StarlarkValue<'v> for Custom {
+        starlark_type!("custom");
+
+        fn get_methods(&self) -> Option<&'static Methods> {
+            static RES: MethodsStatic = MethodsStatic::new();
+            RES.methods(methods)
+        }
+
+        fn get_attr(&self, attribute: &str, _heap: &'v Heap) -> Option<Value<'v>> {
+            match attribute {
+                "field" => Some(Value::new_int(1)),
+                _ => None,
+            }
+        }
+
+        fn dir_attr(&self) -> Vec<String> {
+            vec!["field".to_owned()]
+        }
+    }
+
+    let mut a = Assert::new();
+    a.globals_add(|gb| gb.set("custom", Custom));
+    a.eq("dir(custom)", "['answer', 'broken', 'field']");
+    a.is_true("hasattr(custom, 'field') and hasattr(custom, 'answer')");
+    // `hasattr` doesn't compute the attribute, so doesn't fail.
+    a.is_true("hasattr(custom, 'broken')");
+    a.is_true("not hasattr(custom, 'missing')");
+    a.eq("getattr(custom, 'field', None)", "1");
+    a.eq("getattr(custom, 'answer')()", "42");
+    a.eq("getattr(custom, 'missing', 'default')", "'default'");
+    a.is_true("getattr(struct(x = 1), 'missing', None) == None");
+    a.eq("getattr(struct(x = 1), 'x', None)", "1");
+    // A found attribute which fails is an error, not a reason to use the default.
+    a.fail("getattr(custom, 'broken', 'default')", "broken attribute");
+    a.fail("getattr(custom, 'missing')", "has no attribute");
+
+    // Everything `dir` lists can be fetched, and `hasattr` agrees.
+    a.pass(
+        r#"
+def check(x):
+    for name in dir(x):
+        assert_true(hasattr(x, name))
+        getattr(x, name)
+check("abc")
+check([1])
+check({1: 2})
+check(struct(a = 1, b = 2))
+check(len)
+check(range(3))
+"#,
+    );
+}
+
+#[test]
+fn test_dir_sorted() {
+    use std::collections::HashSet;
+
+    // Attributes listed in the order of a hash map with a random seed.
+    #[derive(Debug, Display, AnyLifetime, NoSerialize)]
+    #[display(fmt = "unordered")]
+    struct Unordered(HashSet<String>);
+    starlark_simple_value!(Unordered);
+    impl<'v> StarlarkValue<'v> for Unordered {
+        starlark_type!("unordered");
+
+        fn dir_attr(&self) -> Vec<String> {
+            self.0.iter().cloned().collect()
+        }
+    }
+
+    let names = || {
+        (0..20)
+            .map(|i| format!("x{:02}", i))
+            .collect::<HashSet<_>>()
+    };
+    let mut a = Assert::new();
+    a.globals_add(|gb| {
+        gb.set("a", Unordered(names()));
+        gb.set("b", Unordered(names()));
+    });
+    a.is_true("dir(a) == sorted(dir(a)) and dir(a) == dir(b) and len(dir(a)) == 20");
+}
+
+#[test]
+fn test_frozen_module_get_typed() {
+    use crate::values::{string::StarlarkStr, PointerI32};
+
+    #[derive(Debug, Display, AnyLifetime, NoSerialize)]
+    #[display(fmt = "example({})", _0)]
+    struct Example(i32);
+    starlark_simple_value!(Example);
+    impl<'v> StarlarkValue<'v> for Example {
+        starlark_type!("example");
+    }
+
+    let module = Module::new();
+    module.set("i", Value::new_int(17));
+    module.set("s", module.heap().alloc("test"));
+    module.set("e", module.heap().alloc(Example(42)));
+    module.set("_private", Value::new_int(1));
+    let module = module.freeze().unwrap();
+
+    assert_eq!(
+        17,
+        module.get_typed::<PointerI32>("i").unwrap().as_ref().get()
+    );
+    assert_eq!(
+        "test",
+        &**module.get_typed::<StarlarkStr>("s").unwrap().as_ref()
+    );
+    assert_eq!(42, module.get_typed::<Example>("e").unwrap().as_ref().0);
+
+    let e = module.get("e").unwrap();
+    assert_eq!(42, e.downcast_ref::<Example>().unwrap().0);
+    assert!(e.downcast_ref::<StarlarkStr>().is_none());
+    assert!(module.get("i").unwrap().downcast_ref::<Example>().is_none());
+
+    fn err<T>(res: anyhow::Result<T>) -> String {
+        res.err().unwrap().to_string()
+    }
+
+    assert_eq!(
+        "Module symbol `i` has type `int`, but expected `example`",
+        err(module.get_typed::<Example>("i"))
+    );
+    assert_eq!(
+        "Module symbol `s` has type `string`, but expected `int`",
+        err(module.get_typed::<PointerI32>("s"))
+    );
+    assert_eq!(
+        "Module symbol `e` has type `example`, but expected `string`",
+        err(module.get_typed::<StarlarkStr>("e"))
+    );
+    assert_eq!(
+        "Module has no symbol `missing`",
+        err(module.get_typed::<Example>("missing"))
+    );
+    assert_eq!(
+        "Module has no symbol `ee`, did you mean `e`?",
+        err(module.get_typed::<Example>("ee"))
+    );
+    assert_eq!(
+        "Module symbol `_private` is not exported",
+        err(module.get_typed::<PointerI32>("_private"))
+    );
+}
+
+#[test]
+fn test_call_stack_from_native() {
+    use itertools::Itertools;
+
+    #[starlark_module]
+    fn inspect(builder: &mut GlobalsBuilder) {
+        fn caller() -> anyhow::Result<String> {
+            Ok(eval.call_stack_top_location().unwrap().to_string())
+        }
+
+        fn stack() -> anyhow::Result<String> {
+            Ok(eval
+                .call_stack()
+                .iter()
+                .map(|x| match &x.location {
+                    None => x.name.clone(),
+                    Some(loc) => format!("{}@{}", x.name, loc.resolve_span().begin_line + 1),
+                })
+                .join(" "))
+        }
+
+        fn call(f: Value<'v>) -> anyhow::Result<Value<'v>> {
+            f.invoke_pos(&[], eval)
+        }
+    }
+
+    let mut a = Assert::new();
+    a.globals_add(inspect);
+    a.pass(
+        r#"
+x = caller()
+y = caller()
+assert_eq(x, "assert.bzl:2:5-13")
+assert_eq(y, "assert.bzl:3:5-13")
+
+def g():
+    return stack()
+def f():
+    return call(g)
+assert_eq(f(), "assert.bzl.f@11 call@10 assert.bzl.g stack@8")
+"#,
+    );
+}
+
+#[test]
+fn test_unpack_err() {
+    use crate::{errors::Diagnostic, values::UnpackValueError};
+
+    #[starlark_module]
+    fn unpacking(builder: &mut GlobalsBuilder) {
+        fn automatic(x: &str) -> anyhow::Result<String> {
+            Ok(x.to_owned())
+        }
+
+        fn manual(x: Value<'v>) -> anyhow::Result<String> {
+            Ok(x.unpack_named_err::<&str>("x")?.to_owned())
+        }
+
+        fn manual_str(x: Value<'v>) -> anyhow::Result<String> {
+            Ok(x.unpack_str_err()?.to_owned())
+        }
+
+        fn manual_int(x: Value<'v>) -> anyhow::Result<i32> {
+            x.unpack_int_err()
+        }
+
+        fn manual_list(x: Value<'v>) -> anyhow::Result<i32> {
+            Ok(x.unpack_list_err()?.len() as i32)
+        }
+    }
+
+    let mut a = Assert::new();
+    a.globals_add(unpacking);
+    // The repr in the error is truncated even if evaluation doesn't limit reprs.
+    a.setup_eval(|eval| eval.set_repr_limits(None));
+    let unpack_error = |program: &str| {
+        let e = a.fail(program, "doesn't match");
+        Diagnostic::from_error(&e)
+            .unwrap()
+            .message
+            .downcast_ref::<UnpackValueError>()
+            .unwrap()
+            .clone()
+    };
+
+    // The same message whether the macro or the function checks the type.
+    let automatic = unpack_error("automatic([1, 2])");
+    assert_eq!(automatic, unpack_error("manual([1, 2])"));
+    assert_eq!(
+        automatic.to_string(),
+        "Type of parameter `x` doesn't match, expected `str`, actual `list` (`[1, 2]`)"
+    );
+    assert_eq!(
+        unpack_error("automatic(x = None)").to_string(),
+        unpack_error("manual(x = None)").to_string()
+    );
+
+    assert_eq!(
+        unpack_error("manual_str(1)").to_string(),
+        "Type of value doesn't match, expected `str`, actual `int` (`1`)"
+    );
+    assert_eq!(
+        unpack_error("manual_int('1')").to_string(),
+        "Type of value doesn't match, expected `int`, actual `string` (`\"1\"`)"
+    );
+    // Positional parameters unpacked by the built-in types give the same message too.
+    assert_eq!(
+        unpack_error("def mul(x, y):\n    return x * y\nmul('x', '1')").to_string(),
+        unpack_error("manual_int('1')").to_string()
+    );
+
+    let huge = unpack_error("manual_list('x' * 1000)");
+    assert_eq!((huge.expected(), huge.actual()), ("list", "string"));
+    assert_eq!(
+        huge.repr(),
+        format!(
+            "\"{}...<truncated>",
+            "x".repeat(UnpackValueError::MAX_REPR_LEN - 1)
+        )
+    );
+    let huge = unpack_error("manual_str(list(range(1000)))");
+    assert!(huge.repr().starts_with("[0, 1, 2, "), "{}", huge.repr());
+    assert_eq!(
+        huge.repr().len(),
+        UnpackValueError::MAX_REPR_LEN + "...<truncated>".len()
+    );
+}
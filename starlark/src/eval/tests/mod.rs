@@ -38,10 +38,15 @@ mod call;
 mod comprehension;
 mod def;
 mod docstring;
+mod fail;
+mod for_loop;
 mod go;
+mod instruction_limit;
 mod interop;
 mod opt;
+mod repr_limits;
 mod runtime;
+mod statements;
 mod type_is;
 
 #[test]
@@ -451,7 +456,7 @@ add3(8)"#,
 * imported.bzl.add2 (called from assert.bzl:4:5-12)
 * imported.bzl.add (called from imported.bzl:9:3-9)
 * append (called from imported.bzl:11:3-14)
-error: Immutable
+error: Can't mutate `x`, captured by function `imported.bzl.add` defined at imported.bzl:10:1, because it is frozen: Value of type `list` is frozen: Immutable
   --> imported.bzl:11:3
    |
 11 |   x.append(z)
@@ -466,7 +471,7 @@ error: Immutable
 * imported.bzl.add2 (called from assert.bzl:4:5-12)
 * imported.bzl.add (called from imported.bzl:9:3-9)
 * append (called from imported.bzl:11:3-14)
-error: Immutable
+error: Can't mutate `x`, captured by function `imported.bzl.add` defined at imported.bzl:10:1, because it is frozen: Value of type `list` is frozen: Immutable
   --> imported.bzl:11:3
    |
 11 |   x.append(z)
@@ -476,6 +481,102 @@ error: Immutable
     );
 }
 
+#[test]
+fn test_diagnostic_json() {
+    let module = Module::new();
+    let mut eval = Evaluator::new(&module);
+    let ast = AstModule::parse(
+        "test.star",
+        r#"
+def inner():
+    fail("oops")
+def outer():
+    inner()
+outer()
+"#
+        .to_owned(),
+        &Dialect::Extended,
+    )
+    .unwrap();
+    let err = eval.eval_module(ast, &Globals::standard()).unwrap_err();
+    let diag = Diagnostic::from_error(&err).unwrap();
+    assert_eq!("fail: oops", diag.primary_message());
+
+    let span = |line: usize, begin: usize, end: usize| {
+        serde_json::json!({
+            "path": "test.star",
+            "begin_line": line,
+            "begin_column": begin,
+            "end_line": line,
+            "end_column": end,
+        })
+    };
+    assert_eq!(
+        serde_json::json!({
+            "code": "fail",
+            "message": "fail: oops",
+            "span": span(3, 5, 17),
+            "call_stack": [
+                {"name": "test.star.outer", "location": span(6, 1, 8)},
+                {"name": "test.star.inner", "location": span(5, 5, 12)},
+                {"name": "fail", "location": span(3, 5, 17)},
+            ],
+            "secondary_spans": [],
+        }),
+        serde_json::to_value(diag).unwrap()
+    );
+}
+
+#[test]
+fn test_diagnostic_error_code() {
+    let code = |program: &str| {
+        let err = assert::fails(program, &[]);
+        Diagnostic::from_error(&err)
+            .unwrap()
+            .error_code()
+            .map(str::to_owned)
+    };
+    assert_eq!(Some("E123"), code("fail('x', code = 'E123')").as_deref());
+    assert_eq!(Some("fail"), code("fail('x')").as_deref());
+    assert_eq!(Some("syntax"), code("x = (1").as_deref());
+    assert_eq!(Some("syntax"), code("break").as_deref());
+    assert_eq!(Some("value"), code("1 + 'x'").as_deref());
+    assert_eq!(Some("value"), code("[1][2]").as_deref());
+    assert_eq!(Some("arguments"), code("def f(x): pass\nf()").as_deref());
+    assert_eq!(Some("stack_overflow"), code("def f(): f()\nf()").as_deref());
+}
+
+#[test]
+fn test_bin_op_error_spans() {
+    let diag = assert::fail(
+        "def f(x, y):\n    return (x +\n            y)\nf([1], 'x')",
+        "not supported",
+    );
+    let diag = diag.downcast_ref::<Diagnostic>().unwrap();
+    // The error points at the left operand.
+    let span = diag.span.as_ref().unwrap().resolve_span();
+    assert_eq!((span.begin_line, span.begin_column), (1, 12));
+    assert_eq!((span.end_line, span.end_column), (1, 13));
+    // And mentions the right operand.
+    assert_eq!(diag.secondary_spans.len(), 1);
+    let (secondary, label) = &diag.secondary_spans[0];
+    let secondary = secondary.resolve_span();
+    assert_eq!((secondary.begin_line, secondary.begin_column), (2, 12));
+    assert_eq!(label, "right operand of type `string`");
+    assert!(diag.to_string().contains("right operand of type `string`"));
+}
+
+#[test]
+fn test_bin_op_error_spans_only_unsupported() {
+    // Division by zero is about the operation, not the operand types.
+    let diag = assert::fail("def f(x, y):\n    return x // y\nf(1, 0)", "divide by zero");
+    let diag = diag.downcast_ref::<Diagnostic>().unwrap();
+    let span = diag.span.as_ref().unwrap().resolve_span();
+    assert_eq!((span.begin_line, span.begin_column), (1, 11));
+    assert_eq!((span.end_line, span.end_column), (1, 17));
+    assert!(diag.secondary_spans.is_empty());
+}
+
 #[test]
 fn test_load_reexport() {
     let mut a = Assert::new();
@@ -844,6 +945,39 @@ xs.items() == [(1, 7), (3, 4), (5, 6)]
     );
 }
 
+#[test]
+fn test_dict_union_frozen() {
+    let mut a = Assert::new();
+    a.module("m.star", "frozen = {1: 2, 3: 4}");
+    a.is_true(
+        r#"
+load("m.star", "frozen")
+x = frozen | {3: 5, 6: 7}
+y = {3: 5, 6: 7} | frozen
+def f():
+    d = frozen
+    d |= {1: 8}
+    return d
+z = f()
+x == {1: 2, 3: 5, 6: 7} and y == {3: 4, 6: 7, 1: 2} and z == {1: 8, 3: 4} and frozen == {1: 2, 3: 4}
+"#,
+    );
+    // The results are fresh mutable dicts
+    a.is_true(
+        r#"
+load("m.star", "frozen")
+def f():
+    d = frozen
+    d |= {}
+    d[5] = 6
+    x = frozen | {}
+    x[7] = 8
+    return d == {1: 2, 3: 4, 5: 6} and x == {1: 2, 3: 4, 7: 8}
+f()
+"#,
+    );
+}
+
 #[test]
 fn test_dict_with_frozen_list_key_inlined() {
     let mut a = Assert::new();
@@ -15,7 +15,14 @@
  * limitations under the License.
  */
 
-use crate::eval::{bc::opcode::BcOpcode, tests::bc::test_instrs};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate as starlark;
+use crate::{
+    assert::Assert,
+    environment::GlobalsBuilder,
+    eval::{bc::opcode::BcOpcode, tests::bc::test_instrs},
+};
 
 #[test]
 fn test_methods_invoked_speculatively() {
@@ -39,3 +46,65 @@ def test():
 "#,
     );
 }
+
+#[test]
+fn test_native_function_invoked_speculatively() {
+    static PURE_CALLS: AtomicUsize = AtomicUsize::new(0);
+    static IMPURE_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+    #[starlark_module]
+    fn counted(builder: &mut GlobalsBuilder) {
+        #[starlark(speculative_exec_safe)]
+        fn pure_div(x: i32, y: i32) -> anyhow::Result<i32> {
+            PURE_CALLS.fetch_add(1, Ordering::SeqCst);
+            match x.checked_div(y) {
+                Some(r) => Ok(r),
+                None => Err(anyhow::anyhow!("division by zero")),
+            }
+        }
+
+        fn impure_div(x: i32, y: i32) -> anyhow::Result<i32> {
+            IMPURE_CALLS.fetch_add(1, Ordering::SeqCst);
+            Ok(x / y)
+        }
+    }
+
+    let mut a = Assert::new();
+    // Run each program once, so the counts are exact.
+    a.disable_gc();
+    a.globals_add(counted);
+    a.module(
+        "m.star",
+        r#"
+def constant():
+    return pure_div(6, 3)
+def variable(x):
+    return pure_div(x, 3)
+def unmarked():
+    return impure_div(6, 3)
+def failing():
+    return pure_div(1, 0)
+"#,
+    );
+    // Folding `constant` and trying to fold `failing` happened when the module was compiled.
+    assert_eq!(2, PURE_CALLS.load(Ordering::SeqCst));
+    assert_eq!(0, IMPURE_CALLS.load(Ordering::SeqCst));
+
+    a.is_true(
+        r#"
+load("m.star", "constant", "variable", "unmarked")
+constant() + constant() == 4 and variable(9) + variable(9) == 6 and unmarked() + unmarked() == 4
+"#,
+    );
+    assert_eq!(4, PURE_CALLS.load(Ordering::SeqCst));
+    assert_eq!(2, IMPURE_CALLS.load(Ordering::SeqCst));
+
+    a.fail(
+        r#"
+load("m.star", "failing")
+failing()
+"#,
+        "division by zero",
+    );
+    assert_eq!(5, PURE_CALLS.load(Ordering::SeqCst));
+}
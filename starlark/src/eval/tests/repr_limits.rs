@@ -0,0 +1,140 @@
+/*
+ * Copyright 2018 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::cell::RefCell;
+
+use crate::{
+    environment::{Globals, Module},
+    eval::Evaluator,
+    stdlib::PrintHandler,
+    syntax::{AstModule, Dialect},
+    values::ReprLimits,
+};
+
+struct CollectPrint(RefCell<Vec<String>>);
+
+impl PrintHandler for CollectPrint {
+    fn println(&self, text: &str) -> anyhow::Result<()> {
+        self.0.borrow_mut().push(text.to_owned());
+        Ok(())
+    }
+}
+
+/// Evaluate `program` with `limits` (`None` for no limits), returning the error message (if any)
+/// and the printed lines.
+fn eval_with_limits(program: &str, limits: Option<ReprLimits>) -> (Option<String>, Vec<String>) {
+    let module = Module::new();
+    let globals = Globals::standard();
+    let print = CollectPrint(RefCell::new(Vec::new()));
+    let mut eval = Evaluator::new(&module);
+    eval.set_print_handler(&print);
+    eval.set_repr_limits(limits);
+    let ast = AstModule::parse("a.star", program.to_owned(), &Dialect::Extended).unwrap();
+    let res = eval.eval_module(ast, &globals);
+    (res.err().map(|e| format!("{:#}", e)), print.0.into_inner())
+}
+
+const SMALL: ReprLimits = ReprLimits {
+    max_len: 10,
+    max_depth: 3,
+};
+
+#[test]
+fn test_repr_limits_error_message() {
+    let (err, _) = eval_with_limits("x = [1] * 1000000\nfail(x)", Some(ReprLimits::default()));
+    let err = err.unwrap();
+    assert!(err.contains("fail: [1, 1, 1"), "{}", err);
+    assert!(err.contains("1, ...<truncated>"), "{}", err);
+    // The repr is cut at the default of 10,000 bytes, plus a little for the location.
+    assert!(
+        err.len() < ReprLimits::default().max_len + 500,
+        "{}",
+        err.len()
+    );
+}
+
+#[test]
+fn test_repr_limits_length() {
+    let (err, printed) = eval_with_limits(
+        "x = [1, 2, 3, 4, 5, 6, 7, 8]\nprint(x, 'a' * 20, x)\nfail(x)",
+        Some(SMALL),
+    );
+    assert!(err.unwrap().contains("fail: [1, 2, 3, ...<truncated>"));
+    // Strings are printed as is, while each value is limited separately.
+    assert_eq!(
+        vec![format!(
+            "[1, 2, 3, ...<truncated> {} [1, 2, 3, ...<truncated>",
+            "a".repeat(20)
+        )],
+        printed
+    );
+}
+
+#[test]
+fn test_repr_limits_depth() {
+    let limits = ReprLimits {
+        max_len: 1000,
+        max_depth: 3,
+    };
+    let (err, printed) = eval_with_limits(
+        "x = [[[[1]]], {'a': 1}]\nprint(x)\nfail(x[0])",
+        Some(limits),
+    );
+    assert!(err.unwrap().contains("fail: [[[...<truncated>]]]"));
+    assert_eq!(
+        vec![r#"[[[...<truncated>]], {"a": 1}]"#.to_owned()],
+        printed
+    );
+}
+
+#[test]
+fn test_repr_limits_explicit_unlimited() {
+    let program = r#"
+x = [1, 2, 3, 4, 5, 6, 7, 8]
+y = [[[[[1]]]]]
+print(repr(x))
+print(str(y))
+print("%s %r" % (x, y))
+print("{} {!r}".format(x, y))
+print(len(repr([1] * 100000)))
+"#;
+    let (err, printed) = eval_with_limits(program, Some(SMALL));
+    assert_eq!(None, err);
+    assert_eq!(
+        vec![
+            "[1, 2, 3, 4, 5, 6, 7, 8]",
+            "[[[[[1]]]]]",
+            "[1, 2, 3, 4, 5, 6, 7, 8] [[[[[1]]]]]",
+            "[1, 2, 3, 4, 5, 6, 7, 8] [[[[[1]]]]]",
+            "300000",
+        ],
+        printed
+    );
+}
+
+#[test]
+fn test_repr_limits_small_unchanged() {
+    let program = "print([1, 'x', {'a': (None, True)}])\nfail([1, 'x'])";
+    let expected = eval_with_limits(program, Some(ReprLimits::default()));
+    let unlimited = eval_with_limits(program, None);
+    assert_eq!(unlimited, expected);
+    assert_eq!(
+        vec![r#"[1, "x", {"a": (None, True)}]"#.to_owned()],
+        expected.1
+    );
+    assert!(expected.0.unwrap().contains(r#"fail: [1, "x"]"#));
+}
@@ -20,6 +20,7 @@
 use std::{
     mem,
     sync::atomic::{AtomicUsize, Ordering},
+    thread,
 };
 
 use derive_more::Display;
@@ -29,7 +30,10 @@ use crate as starlark;
 use crate::{
     assert,
     assert::Assert,
-    environment::GlobalsBuilder,
+    environment::{Globals, GlobalsBuilder, Module},
+    errors::Diagnostic,
+    eval::Evaluator,
+    syntax::{AstModule, Dialect},
     values::{any::StarlarkAny, FrozenHeap, Heap},
 };
 
@@ -135,6 +139,46 @@ v1 + " " + v100 + " " + v1000
     );
 }
 
+#[test]
+fn test_try_gc() {
+    let module = Module::new();
+    let globals = Globals::standard();
+    let mut eval = Evaluator::new(&module);
+    // Stop the automatic GC, so all the garbage is still around at the end.
+    eval.set_gc_threshold(usize::MAX);
+    let ast = AstModule::parse(
+        "a.star",
+        r#"
+keep = [1, 2, 3]
+def garbage():
+    for i in range(1000):
+        x = [str(i)] * 10
+garbage()
+"#
+        .to_owned(),
+        &Dialect::Standard,
+    )
+    .unwrap();
+    eval.eval_module(ast, &globals).unwrap();
+
+    let before = eval.gc_stats();
+    assert_eq!(before.collections, 0);
+    assert_eq!(before.retained_bytes, 0);
+    assert_eq!(before.allocated_bytes, module.heap().allocated_bytes());
+
+    // Safe because we don't hold any values outside the module.
+    assert!(unsafe { eval.try_gc() });
+    let after = eval.gc_stats();
+    assert_eq!(after.collections, 1);
+    assert_eq!(after.retained_bytes, after.allocated_bytes);
+    assert!(after.retained_bytes * 2 < before.allocated_bytes);
+    assert_eq!(module.get("keep").unwrap().to_repr(), "[1, 2, 3]");
+
+    eval.disable_gc();
+    assert!(!unsafe { eval.try_gc() });
+    assert_eq!(eval.gc_stats().collections, 1);
+}
+
 #[test]
 fn test_garbage_collect_happens() {
     // GC is meant to be "not observable", but if we break it, we want this test to fail
@@ -202,6 +246,92 @@ f()
     assert!(d.to_string().contains("* fail"));
 }
 
+#[test]
+fn test_max_call_stack_size() {
+    // A chain of calls `f0` -> `f1` -> ... -> `f99`.
+    let mut code = String::new();
+    for i in 0..99 {
+        code.push_str(&format!("def f{}(): return f{}()\n", i, i + 1));
+    }
+    code.push_str("def f99(): return 99\nf0()\n");
+
+    let run = move || {
+        let eval_with_max = |max: usize| {
+            let module = Module::new();
+            let mut eval = Evaluator::new(&module);
+            eval.set_max_call_stack_size(max);
+            let ast = AstModule::parse("chain.star", code.clone(), &Dialect::Standard).unwrap();
+            eval.eval_module(ast, &Globals::standard())
+                .map(|v| v.unpack_int())
+        };
+
+        let err = eval_with_max(50).unwrap_err();
+        let diag = err.downcast_ref::<Diagnostic>().unwrap();
+        assert!(
+            diag.message
+                .to_string()
+                .contains("Starlark call stack overflow, more than 50 nested calls (too many recursion levels)"),
+            "{}",
+            err
+        );
+        // The module is the first entry, so `f48` was the last function called.
+        assert_eq!(diag.call_stack.len(), 49);
+        assert_eq!(diag.call_stack[0].name, "chain.star.f0");
+        assert_eq!(diag.call_stack[48].name, "chain.star.f48");
+        // Only the frames at either end are displayed.
+        let rendered = err.to_string();
+        let frames = rendered
+            .lines()
+            .filter(|x| x.starts_with("* "))
+            .collect::<Vec<_>>();
+        assert_eq!(frames.len(), 21, "{}", rendered);
+        assert!(frames[0].starts_with("* chain.star.f0"), "{}", rendered);
+        assert_eq!(frames[10], "* \u{2026} 29 frames omitted \u{2026}");
+        assert!(frames[20].starts_with("* chain.star.f48"), "{}", rendered);
+
+        assert_eq!(eval_with_max(101).unwrap(), Some(99));
+        assert!(eval_with_max(100).is_err());
+    };
+    // Deep calls need more native stack than test threads get by default.
+    thread::Builder::new()
+        .stack_size(64 << 20)
+        .spawn(run)
+        .unwrap()
+        .join()
+        .unwrap();
+}
+
+#[test]
+fn test_default_call_stack_size_fits_main_thread_stack() {
+    // Unbounded recursion must hit the default limit with a Starlark error
+    // before using up a native stack the size of the main thread's.
+    let run = || {
+        let module = Module::new();
+        let mut eval = Evaluator::new(&module);
+        let ast = AstModule::parse(
+            "recursion.star",
+            "def f(x): return f(x + 1)\nf(0)\n".to_owned(),
+            &Dialect::Standard,
+        )
+        .unwrap();
+        let err = eval
+            .eval_module(ast, &Globals::standard())
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("Starlark call stack overflow, more than 40 nested calls"),
+            "{}",
+            err
+        );
+    };
+    thread::Builder::new()
+        .stack_size(8 << 20)
+        .spawn(run)
+        .unwrap()
+        .join()
+        .unwrap();
+}
+
 #[test]
 fn test_display_debug() {
     let heap = Heap::new();
@@ -228,3 +358,84 @@ fn test_display_debug() {
     assert_eq!(format!("{:?}", v), "FrozenValue(\"test\")");
     assert_eq!(format!("{:#?}", v), "FrozenValue(\n    \"test\",\n)");
 }
+
+#[test]
+fn test_max_heap_bytes() {
+    let module = Module::new();
+    let globals = Globals::standard();
+    let mut eval = Evaluator::new(&module);
+    eval.set_max_heap_bytes(1_000_000);
+    let mut run = |name: &str, code: &str| {
+        let ast = AstModule::parse(name, code.to_owned(), &Dialect::Standard).unwrap();
+        eval.eval_module(ast, &globals).map(|v| v.unpack_int())
+    };
+
+    let grow = r#"
+def grow():
+    x = [0] * 1000
+    for _ in range(100):
+        x = x + x
+grow()
+"#;
+    let err = run("grow.star", grow).unwrap_err().to_string();
+    assert!(
+        err.contains("Heap limit of 1000000 bytes exceeded"),
+        "unexpected error: {}",
+        err
+    );
+
+    // Repetition fails before allocating anything.
+    let err = run("big.star", "[0] * 100000000").unwrap_err().to_string();
+    assert!(err.contains("Heap limit of 1000000 bytes exceeded, 8"));
+
+    // The evaluator can still be used afterwards.
+    assert_eq!(run("small.star", "1 + 2").unwrap(), Some(3));
+}
+
+#[test]
+fn test_max_heap_bytes_single_statement() {
+    let module = Module::new();
+    let globals = Globals::standard();
+    let mut eval = Evaluator::new(&module);
+    eval.set_max_heap_bytes(1_000_000);
+    // A single statement, so the limit must be noticed at allocation, not between statements.
+    let ast = AstModule::parse(
+        "compr.star",
+        "x = [str(i) for i in range(100000000)]".to_owned(),
+        &Dialect::Standard,
+    )
+    .unwrap();
+    let err = eval.eval_module(ast, &globals).unwrap_err().to_string();
+    assert!(
+        err.contains("Heap limit of 1000000 bytes exceeded"),
+        "unexpected error: {}",
+        err
+    );
+    // Stopped soon after the limit was crossed.
+    assert!(module.heap().peak_allocated_bytes() < 4_000_000);
+}
+
+#[test]
+fn test_max_heap_bytes_dict_set() {
+    let module = Module::new();
+    let globals = Globals::extended();
+    let mut eval = Evaluator::new(&module);
+    eval.set_max_heap_bytes(1_000_000);
+    // Small ints aren't allocated on the heap, so only the storage of the dict or set grows.
+    for code in [
+        "d = {}\nfor i in range(100000000):\n    d[i] = i",
+        "d = {}\nfor i in range(100000000):\n    d.setdefault(i)",
+        "d = {}\nfor i in range(100000000):\n    d |= {i: i}",
+        "s = set()\nfor i in range(100000000):\n    s.add(i)",
+        "d = {i: i for i in range(100000000)}",
+    ] {
+        let ast = AstModule::parse("grow.star", code.to_owned(), &Dialect::Extended).unwrap();
+        let err = eval.eval_module(ast, &globals).unwrap_err().to_string();
+        assert!(
+            err.contains("Heap limit of 1000000 bytes exceeded"),
+            "unexpected error for {:?}: {}",
+            code,
+            err
+        );
+    }
+}
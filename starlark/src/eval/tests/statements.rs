@@ -0,0 +1,73 @@
+/*
+ * Copyright 2018 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Tests for `Evaluator::eval_snippet`.
+
+use crate::{
+    environment::{Globals, Module},
+    eval::Evaluator,
+    syntax::{AstModule, Dialect},
+    values::Value,
+};
+
+fn eval_snippet<'v>(eval: &mut Evaluator<'v, '_>, code: &str) -> anyhow::Result<Value<'v>> {
+    let ast = AstModule::parse("repl.star", code.to_owned(), &Dialect::Extended)?;
+    eval.eval_snippet(ast, &Globals::standard())
+}
+
+#[test]
+fn test_eval_snippet_values() {
+    let module = Module::new();
+    let mut eval = Evaluator::new(&module);
+    assert!(eval_snippet(&mut eval, "x = 1").unwrap().is_none());
+    assert_eq!(
+        Some(2),
+        eval_snippet(&mut eval, "x + 1").unwrap().unpack_int()
+    );
+    assert_eq!(
+        Some(3),
+        eval_snippet(&mut eval, "x = 3\nx").unwrap().unpack_int()
+    );
+    assert!(eval_snippet(&mut eval, "undefined").is_err());
+}
+
+#[test]
+fn test_eval_snippet_forward_reference() {
+    let module = Module::new();
+    let mut eval = Evaluator::new(&module);
+    eval_snippet(&mut eval, "def f(): return g() + y").unwrap();
+    let err = eval_snippet(&mut eval, "f()").unwrap_err().to_string();
+    assert!(err.contains("referenced before assignment"), "{}", err);
+    eval_snippet(&mut eval, "def g(): return 1\ny = 10").unwrap();
+    assert_eq!(
+        Some(11),
+        eval_snippet(&mut eval, "f()").unwrap().unpack_int()
+    );
+    assert_eq!(
+        Some(21),
+        eval_snippet(&mut eval, "y = 20\nf()").unwrap().unpack_int()
+    );
+}
+
+#[test]
+fn test_eval_snippet_no_docstring() {
+    let module = Module::new();
+    let mut eval = Evaluator::new(&module);
+    eval_snippet(&mut eval, "\"not a docstring\"").unwrap();
+    drop(eval);
+    assert!(module.freeze().unwrap().documentation().is_none());
+}
@@ -412,6 +412,7 @@ mod debug;
 pub mod environment;
 pub mod errors;
 pub mod eval;
+pub mod lsp;
 pub mod read_line;
 mod stdlib;
 pub mod syntax;
@@ -438,4 +439,18 @@ pub mod __derive_refs {
     pub mod serde {
         pub use serde::{ser::Error, Serialize, Serializer};
     }
+
+    pub mod freeze {
+        use crate::values::{Freeze, FreezeError, Freezer};
+
+        /// Freeze a field, recording its name in the error if freezing fails.
+        pub fn freeze_field<T: Freeze>(
+            x: T,
+            freezer: &Freezer,
+            step: &'static str,
+        ) -> anyhow::Result<T::Frozen> {
+            x.freeze(freezer)
+                .map_err(|e| FreezeError::add_step(e, step.to_owned()))
+        }
+    }
 }
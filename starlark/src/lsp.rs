@@ -0,0 +1,188 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Support for language servers, e.g. finding the files that `load` statements refer to.
+
+use std::{
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+use lsp_types::Url;
+
+/// Finds the file a `load` refers to, so a language server can follow loads.
+pub trait LoadResolver {
+    /// The file loaded by `load(path, ...)` in `current_file`, whether or not it exists,
+    /// or `None` if `path` doesn't refer to a file this resolver can load.
+    fn resolve(&self, path: &str, current_file: &Url) -> Option<Url>;
+
+    /// The contents of a file returned by `resolve`, or `None` if it can't be read.
+    fn contents(&self, url: &Url) -> Option<String> {
+        fs::read_to_string(url.to_file_path().ok()?).ok()
+    }
+}
+
+/// Resolves `load` paths to files within the root of a workspace:
+///
+/// * `//pkg:x.star` is `pkg/x.star` under the root.
+/// * `:x.star`, `x.star` and `../x.star` are relative to the directory of the loading file.
+///
+/// Paths which would be outside the root, including those in other repositories
+/// like `@repo//pkg:x.star`, don't resolve.
+pub struct WorkspaceResolver {
+    root: PathBuf,
+}
+
+// Remove `.` and `..` components without looking at the file system, so symlinks
+// can't be used to tell whether a path is within the root. `None` if the path isn't absolute.
+fn normalize(path: &Path) -> Option<PathBuf> {
+    if !path.is_absolute() {
+        return None;
+    }
+    let mut res = PathBuf::new();
+    for x in path.components() {
+        match x {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                res.pop();
+            }
+            _ => res.push(x),
+        }
+    }
+    Some(res)
+}
+
+impl WorkspaceResolver {
+    /// Resolve loads to files within `root`, which must be absolute.
+    pub fn new(root: PathBuf) -> Self {
+        let root = normalize(&root).unwrap_or(root);
+        Self { root }
+    }
+
+    /// The file loaded by `load(path, ...)` in the file `current_file`,
+    /// or `None` if it isn't within the root.
+    pub fn resolve_path(&self, path: &str, current_file: &Path) -> Option<PathBuf> {
+        if path.starts_with('@') {
+            return None;
+        }
+        let res = match path.strip_prefix("//") {
+            Some(label) => {
+                let label = match label.split_once(':') {
+                    Some(("", name)) => name.to_owned(),
+                    Some((package, name)) => format!("{}/{}", package, name),
+                    None => label.to_owned(),
+                };
+                self.root.join(label)
+            }
+            None => {
+                let dir = normalize(current_file.parent()?)?;
+                if !dir.starts_with(&self.root) {
+                    return None;
+                }
+                dir.join(path.strip_prefix(':').unwrap_or(path))
+            }
+        };
+        // Joining an absolute path replaces the base, so that is checked here as well.
+        let res = normalize(&res)?;
+        if res.starts_with(&self.root) && res != self.root {
+            Some(res)
+        } else {
+            None
+        }
+    }
+}
+
+impl LoadResolver for WorkspaceResolver {
+    fn resolve(&self, path: &str, current_file: &Url) -> Option<Url> {
+        let current_file = current_file.to_file_path().ok()?;
+        Url::from_file_path(self.resolve_path(path, &current_file)?).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolve(path: &str) -> Option<String> {
+        let resolver = WorkspaceResolver::new(PathBuf::from("/workspace"));
+        resolver
+            .resolve_path(path, Path::new("/workspace/pkg/a.star"))
+            .map(|x| x.to_str().unwrap().to_owned())
+    }
+
+    #[test]
+    fn test_resolve_labels() {
+        assert_eq!(resolve(":x.star").as_deref(), Some("/workspace/pkg/x.star"));
+        assert_eq!(
+            resolve(":sub/x.star").as_deref(),
+            Some("/workspace/pkg/sub/x.star")
+        );
+        assert_eq!(
+            resolve("//pkg:x.star").as_deref(),
+            Some("/workspace/pkg/x.star")
+        );
+        assert_eq!(
+            resolve("//other/pkg:x.star").as_deref(),
+            Some("/workspace/other/pkg/x.star")
+        );
+        assert_eq!(resolve("//:x.star").as_deref(), Some("/workspace/x.star"));
+        assert_eq!(resolve("//x.star").as_deref(), Some("/workspace/x.star"));
+        assert_eq!(resolve("@repo//pkg:x.star"), None);
+    }
+
+    #[test]
+    fn test_resolve_relative() {
+        assert_eq!(resolve("x.star").as_deref(), Some("/workspace/pkg/x.star"));
+        assert_eq!(
+            resolve("./x.star").as_deref(),
+            Some("/workspace/pkg/x.star")
+        );
+        assert_eq!(resolve("../x.star").as_deref(), Some("/workspace/x.star"));
+        assert_eq!(
+            resolve("../other/x.star").as_deref(),
+            Some("/workspace/other/x.star")
+        );
+    }
+
+    #[test]
+    fn test_resolve_outside_root() {
+        assert_eq!(resolve("../../x.star"), None);
+        assert_eq!(resolve("../../workspace2/x.star"), None);
+        assert_eq!(resolve("//../x.star"), None);
+        assert_eq!(resolve("//pkg:../../x.star"), None);
+        assert_eq!(resolve(":/etc/x.star"), None);
+        assert_eq!(resolve(".."), None);
+
+        // Files outside the root can't load anything relative to themselves.
+        let resolver = WorkspaceResolver::new(PathBuf::from("/workspace"));
+        assert_eq!(
+            resolver.resolve_path(":x.star", Path::new("/elsewhere/a.star")),
+            None
+        );
+    }
+
+    #[test]
+    fn test_resolve_url() {
+        let resolver = WorkspaceResolver::new(PathBuf::from("/workspace"));
+        let current = Url::parse("file:///workspace/pkg/a.star").unwrap();
+        assert_eq!(
+            resolver.resolve(":x.star", &current),
+            Some(Url::parse("file:///workspace/pkg/x.star").unwrap())
+        );
+        assert_eq!(resolver.resolve("../../x.star", &current), None);
+    }
+}
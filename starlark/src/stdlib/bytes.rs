@@ -0,0 +1,115 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! The `bytes()` constructor and methods for the `bytes` type.
+
+use std::str;
+
+use anyhow::anyhow;
+use thiserror::Error;
+
+use crate as starlark;
+use crate::{
+    environment::{GlobalsBuilder, LibraryExtension, MethodsBuilder},
+    eval::Evaluator,
+    values::{bytes::StarlarkBytes, Value},
+};
+
+#[derive(Debug, Error)]
+enum BytesError {
+    #[error("`str.encode` requires the `bytes` type, which is not enabled")]
+    NotEnabled,
+}
+
+/// Check the code calling `str.encode` runs with globals which have
+/// [`LibraryExtension::Bytes`], so `bytes` values only appear when it is enabled.
+pub(crate) fn check_bytes_enabled(eval: &Evaluator) -> anyhow::Result<()> {
+    if eval.def_info.globals.has_extension(LibraryExtension::Bytes) {
+        Ok(())
+    } else {
+        Err(BytesError::NotEnabled.into())
+    }
+}
+
+/// Check `encoding` is UTF-8, the only encoding supported by `str.encode` and `bytes.decode`.
+pub(crate) fn check_encoding(encoding: Option<&str>) -> anyhow::Result<()> {
+    match encoding {
+        None => Ok(()),
+        Some(x) if x.eq_ignore_ascii_case("utf-8") || x.eq_ignore_ascii_case("utf8") => Ok(()),
+        Some(x) => Err(anyhow!(
+            "Unsupported encoding `{}`, only UTF-8 is supported",
+            x
+        )),
+    }
+}
+
+#[starlark_module]
+pub fn global(builder: &mut GlobalsBuilder) {
+    /// Create bytes from an iterable of ints, each in the range 0 to 255, or from other bytes.
+    /// Strings are converted with `s.encode()`.
+    ///
+    /// ```
+    /// # starlark::assert::all_true(r#"
+    /// bytes([104, 105]) == "hi".encode()
+    /// len(bytes([])) == 0
+    /// # "#);
+    /// ```
+    #[starlark(type(StarlarkBytes::TYPE))]
+    #[starlark(speculative_exec_safe)]
+    fn bytes(ref a: Value) -> anyhow::Result<StarlarkBytes> {
+        if let Some(a) = StarlarkBytes::from_value(a) {
+            return Ok(a.clone());
+        }
+        let mut res = Vec::new();
+        a.with_iterator(heap, |it| {
+            for x in it {
+                match x.unpack_int() {
+                    Some(b @ 0..=255) => res.push(b as u8),
+                    _ => {
+                        return Err(anyhow!(
+                            "bytes() expects ints in the range 0 to 255, got `{}`",
+                            x.to_repr()
+                        ));
+                    }
+                }
+            }
+            Ok(())
+        })??;
+        Ok(StarlarkBytes::new(res))
+    }
+}
+
+#[starlark_module]
+pub(crate) fn bytes_methods(builder: &mut MethodsBuilder) {
+    /// `b.decode(encoding = "utf-8")` returns the string the bytes encode, the reverse of
+    /// `str.encode`. Only UTF-8 is supported, and it fails if the bytes are not valid UTF-8.
+    ///
+    /// ```
+    /// # starlark::assert::all_true(r#"
+    /// bytes([104, 105]).decode() == "hi"
+    /// "ñandú".encode().decode() == "ñandú"
+    /// # "#);
+    /// ```
+    #[starlark(speculative_exec_safe)]
+    fn decode(this: &StarlarkBytes, ref encoding: Option<&str>) -> anyhow::Result<String> {
+        check_encoding(encoding)?;
+        match str::from_utf8(this.as_bytes()) {
+            Ok(x) => Ok(x.to_owned()),
+            Err(e) => Err(anyhow!("Bytes are not valid UTF-8: {}", e)),
+        }
+    }
+}
@@ -17,13 +17,14 @@
 
 //! Methods for the `dict` type.
 
-use std::{intrinsics::unlikely, mem};
+use std::mem;
 
 use anyhow::anyhow;
 
 use crate as starlark;
 use crate::{
     environment::MethodsBuilder,
+    stdlib::util::unpack_pair,
     values::{
         dict::{Dict, DictRef},
         none::NoneType,
@@ -186,10 +187,13 @@ pub(crate) fn dict_methods(registry: &mut MethodsBuilder) {
 
     /// [dict.popitem](
     /// https://github.com/google/skylark/blob/3705afa472e466b8b061cce44b47c9ddc6db696d/doc/spec.md#dict·popitem
-    /// ): returns and removes the first key/value pair of a dictionary.
+    /// ): returns and removes the last key/value pair of a dictionary.
     ///
-    /// `D.popitem()` returns the first key/value pair, removing it from the
-    /// dictionary.
+    /// `D.popitem()` returns the last key/value pair, removing it from the
+    /// dictionary. The last pair is the one which comes last in iteration
+    /// order, i.e. the pair whose key was inserted most recently.
+    /// Unlike the Go implementation, which removes the first pair, repeated calls
+    /// take pairs off the dictionary like a stack.
     ///
     /// `popitem` fails if the dictionary is empty, frozen, or has active
     /// iterators.
@@ -200,10 +204,10 @@ pub(crate) fn dict_methods(registry: &mut MethodsBuilder) {
     /// # starlark::assert::is_true(r#"
     /// x = {"one": 1, "two": 2}
     /// # (
-    /// x.popitem() == ("one", 1)
-    /// # and
     /// x.popitem() == ("two", 2)
     /// # and
+    /// x.popitem() == ("one", 1)
+    /// # and
     /// x == {}
     /// # )"#);
     /// ```
@@ -218,9 +222,8 @@ pub(crate) fn dict_methods(registry: &mut MethodsBuilder) {
     fn popitem(this: Value) -> anyhow::Result<(Value<'v>, Value<'v>)> {
         let mut this = Dict::from_value_mut(this)?.unwrap();
 
-        let key = this.iter_hashed().next().map(|(k, _)| k);
-        match key {
-            Some(k) => Ok((*k.key(), this.remove_hashed(k).unwrap())),
+        match this.pop_last() {
+            Some(x) => Ok(x),
             None => Err(anyhow!("Cannot .popitem() on an empty dictionary")),
         }
     }
@@ -261,14 +264,16 @@ pub(crate) fn dict_methods(registry: &mut MethodsBuilder) {
         ref key: Value,
         ref default: Option<Value>,
     ) -> anyhow::Result<Value<'v>> {
-        let mut this = Dict::from_value_mut(this)?.unwrap();
-        let key = key.get_hashed()?;
-        if let Some(r) = this.get_hashed(key) {
-            return Ok(r);
-        }
-        let def = default.unwrap_or_else(Value::new_none);
-        this.insert_hashed(key, def);
-        Ok(def)
+        heap.charge_growth(this, || {
+            let mut this = Dict::from_value_mut(this)?.unwrap();
+            let key = key.get_hashed()?;
+            if let Some(r) = this.get_hashed(key) {
+                return Ok(r);
+            }
+            let def = default.unwrap_or_else(Value::new_none);
+            this.insert_hashed(key, def);
+            Ok(def)
+        })
     }
 
     /// [dict.update](
@@ -312,31 +317,26 @@ pub(crate) fn dict_methods(registry: &mut MethodsBuilder) {
             pairs
         };
 
-        let mut this = Dict::from_value_mut(this)?.unwrap();
-        if let Some(pairs) = pairs {
-            if let Some(dict) = Dict::from_value(pairs) {
-                for (k, v) in dict.iter_hashed() {
-                    this.insert_hashed(k, v);
-                }
-            } else {
-                for v in pairs.iterate(heap)? {
-                    let mut it = v.iterate(heap)?;
-                    let k = it.next();
-                    let v = if k.is_some() { it.next() } else { None };
-                    if unlikely(v.is_none() || it.next().is_some()) {
-                        return Err(anyhow!(
-                            "dict.update expect a list of pairs or a dictionary as first argument, got a list of non-pairs.",
-                        ));
-                    };
-                    this.insert_hashed(k.unwrap().get_hashed()?, v.unwrap());
+        heap.charge_growth(this, || {
+            let mut this = Dict::from_value_mut(this)?.unwrap();
+            if let Some(pairs) = pairs {
+                if let Some(dict) = Dict::from_value(pairs) {
+                    for (k, v) in dict.iter_hashed() {
+                        this.insert_hashed(k, v);
+                    }
+                } else {
+                    for (i, pair) in pairs.iterate(heap)?.enumerate() {
+                        let (k, v) = unpack_pair(pair, i, "dict.update", heap)?;
+                        this.insert_hashed(k.get_hashed()?, v);
+                    }
                 }
             }
-        }
 
-        for (k, v) in kwargs.iter_hashed() {
-            this.insert_hashed(k, v);
-        }
-        Ok(NoneType)
+            for (k, v) in kwargs.iter_hashed() {
+                this.insert_hashed(k, v);
+            }
+            Ok(NoneType)
+        })
     }
 
     /// [dict.values](
@@ -363,7 +363,7 @@ pub(crate) fn dict_methods(registry: &mut MethodsBuilder) {
 
 #[cfg(test)]
 mod tests {
-    use crate::assert;
+    use crate::{assert, assert::Assert};
 
     #[test]
     fn test_error_codes() {
@@ -371,6 +371,88 @@ mod tests {
         assert::fail("x = {}; x.popitem()", "empty");
     }
 
+    #[test]
+    fn test_popitem() {
+        assert::is_true(
+            r#"
+x = {"a": 1, "b": 2, "c": 3}
+x.pop("c")
+x["a"] = 4
+x["d"] = 5
+[x.popitem(), x.popitem(), x.popitem()] == [("d", 5), ("b", 2), ("a", 4)] and x == {}
+"#,
+        );
+    }
+
+    #[test]
+    fn test_dict_pairs() {
+        assert::all_true(
+            r#"
+dict([(1, 2), [3, 4]], x = 5) == {1: 2, 3: 4, "x": 5}
+dict([("x", 1)], x = 2) == {"x": 2}
+dict(zip(["a", "b"], [1, 2])) == {"a": 1, "b": 2}
+dict(enumerate(["a", "b"])) == {0: "a", 1: "b"}
+"#,
+        );
+        assert::is_true(
+            r#"
+x = {"a": 0}
+x.update([("a", 1), ("b", 2)], b = 3, c = 4)
+x.update(zip(["d", "e"], [5, 6]))
+x == {"a": 1, "b": 3, "c": 4, "d": 5, "e": 6}
+"#,
+        );
+        assert::fail(
+            "dict([(1, 2), (3, 4, 5)])",
+            "Expected key/value pairs for `dict`, element 1 has length 3: (3, 4, 5)",
+        );
+        assert::fail(
+            "{}.update([(1, 2), (3, 4), [5]])",
+            "Expected key/value pairs for `dict.update`, element 2 has length 1: [5]",
+        );
+        assert::fail(
+            "{}.update([1])",
+            "Expected key/value pairs for `dict.update`, element 0 is not iterable: 1",
+        );
+    }
+
+    #[test]
+    fn test_setdefault_returns_stored() {
+        assert::is_true(
+            r#"
+x = {}
+x.setdefault("a", []).append(1)
+x.setdefault("a", []).append(2)
+x == {"a": [1, 2]}
+"#,
+        );
+    }
+
+    #[test]
+    fn test_order_after_pop_and_freeze() {
+        let mut a = Assert::new();
+        a.module(
+            "m",
+            r#"
+d = {"a": 1, "b": 2, "c": 3, "d": 4}
+d.pop("b")
+d["e"] = 5
+d["b"] = 6
+d.popitem()
+d["a"] = 7
+d["f"] = 8
+before = list(d)
+items = d.items()
+"#,
+        );
+        a.is_true(
+            r#"
+load("m", "d", "before", "items")
+before == ["a", "c", "d", "e", "f"] and list(d) == before and d.items() == items
+"#,
+        );
+    }
+
     #[test]
     fn test_dict_add() {
         assert::fail("{1: 2} + {3: 4}", "not supported");
@@ -32,7 +32,10 @@ pub fn global(builder: &mut GlobalsBuilder) {
 
 #[cfg(test)]
 mod tests {
-    use crate::assert::{self, Assert};
+    use crate::{
+        assert::{self, Assert},
+        values::{docs, docs::DocItem},
+    };
 
     #[test]
     fn test_enum() {
@@ -51,6 +54,18 @@ enum_type = enum("option1", "option2", True)
 enum_type(False)"#,
             &["Unknown enum element", "`False`", "option1"],
         );
+        assert::fail(
+            r#"
+Color = enum("red", "green", 3)
+Color("blue")"#,
+            r#"Unknown enum element `blue`, given to `Color`, expected one of "red", "green", 3"#,
+        );
+        assert::fail(
+            r#"
+Digit = enum(0, 1, 2, 3, 4, 5, 6, 7, 8, 9)
+Digit(10)"#,
+            "Unknown enum element `10`, given to `Digit`, expected one of 0, 1, 2, 3, 4, ...",
+        );
         assert::fails(
             r#"
 enum_type = enum("option1", "option2", True)
@@ -153,4 +168,66 @@ assert_ne(rt(1), diff(1))
 "#,
         );
     }
+
+    #[test]
+    fn test_enum_in() {
+        assert::pass(
+            r#"
+Color = enum("red", "green")
+Other = enum("red", "green", "blue")
+assert_eq(len(Color), 2)
+assert_eq(Color("red") in Color, True)
+assert_eq(Other("red") in Color, False)
+assert_eq(Other("blue") in Color, False)
+assert_eq("red" in Color, False)
+assert_eq("red" in Color.values(), True)
+"#,
+        );
+    }
+
+    #[test]
+    fn test_enum_dict_keys() {
+        let mut a = Assert::new();
+        a.module(
+            "m",
+            r#"
+Color = enum("red", "green")
+names = {Color("red"): "Red", Color("green"): "Green"}
+"#,
+        );
+        a.pass(
+            r#"
+load('m', 'Color', 'names')
+assert_eq(names[Color("green")], "Green")
+assert_eq("red" in names, False)
+local = {c: c.index for c in Color}
+assert_eq(local[Color("green")], 1)
+"#,
+        );
+    }
+
+    #[test]
+    fn test_enum_documentation() {
+        let m = assert::pass_module(r#"Color = enum("red", "green", 3)"#);
+        let docs = match m.get("Color").unwrap().value().documentation() {
+            Some(DocItem::Object(docs)) => docs,
+            x => panic!("Expected an object, got {:?}", x),
+        };
+        let variants = docs
+            .members
+            .into_iter()
+            .map(|(name, member)| match member {
+                docs::Member::Property(p) => (name, p.typ.unwrap().raw_type),
+                x => panic!("Expected a property, got {:?}", x),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            variants,
+            vec![
+                ("\"red\"".to_owned(), "Color".to_owned()),
+                ("\"green\"".to_owned(), "Color".to_owned()),
+                ("3".to_owned(), "Color".to_owned()),
+            ]
+        );
+    }
 }
@@ -26,6 +26,7 @@ use gazebo::{
     prelude::*,
 };
 use itertools::Itertools;
+use thiserror::Error;
 
 use crate::{
     self as starlark,
@@ -33,39 +34,38 @@ use crate::{
     environment::GlobalsBuilder,
     eval::{Arguments, Evaluator},
     values::{
-        dict::DictRef, function::FUNCTION_TYPE, none::NoneType, tuple::Tuple, Freeze, Freezer,
-        FrozenStringValue, FrozenValue, StarlarkValue, StringValue, StringValueLike, Trace, Value,
-        ValueLike,
+        dict::DictRef,
+        float::StarlarkFloat,
+        function::FUNCTION_TYPE,
+        int::IntError,
+        none::NoneType,
+        num::Num,
+        structs::{Struct, StructBuilder},
+        tuple::Tuple,
+        Freeze, Freezer, FrozenStringValue, FrozenValue, StarlarkValue, StringValue,
+        StringValueLike, Trace, Value, ValueError, ValueLike,
     },
 };
 
 #[starlark_module]
 pub fn filter(builder: &mut GlobalsBuilder) {
     fn filter(ref func: Value, ref seq: Value) -> anyhow::Result<Value<'v>> {
-        let mut res = Vec::new();
-
-        for v in seq.iterate(heap)? {
-            if func.is_none() {
-                if !v.is_none() {
-                    res.push(v);
-                }
-            } else if func.invoke_pos(&[v], eval)?.to_bool() {
-                res.push(v);
-            }
-        }
-        Ok(heap.alloc_list(&res))
+        heap.try_alloc_list_iter(seq.iterate(heap)?.filter_map(|v| {
+            let keep = if func.is_none() {
+                Ok(!v.is_none())
+            } else {
+                func.invoke_pos(&[v], eval).map(|x| x.to_bool())
+            };
+            keep.map(|keep| if keep { Some(v) } else { None })
+                .transpose()
+        }))
     }
 }
 
 #[starlark_module]
 pub fn map(builder: &mut GlobalsBuilder) {
     fn map(ref func: Value, ref seq: Value) -> anyhow::Result<Value<'v>> {
-        let it = seq.iterate(heap)?;
-        let mut res = Vec::with_capacity(it.size_hint().0);
-        for v in it {
-            res.push(func.invoke_pos(&[v], eval)?);
-        }
-        Ok(heap.alloc_list(&res))
+        heap.try_alloc_list_iter(seq.iterate(heap)?.map(|v| func.invoke_pos(&[v], eval)))
     }
 }
 
@@ -113,15 +113,7 @@ pub fn dedupe(builder: &mut GlobalsBuilder) {
     /// rather than by equality.
     fn dedupe(ref val: Value) -> anyhow::Result<Value<'v>> {
         let mut seen = HashSet::new();
-        let mut res = Vec::new();
-        for v in val.iterate(heap)? {
-            let p = v.ptr_value();
-            if !seen.contains(&p) {
-                seen.insert(p);
-                res.push(v);
-            }
-        }
-        Ok(heap.alloc_list(&res))
+        Ok(heap.alloc_list_iter(val.iterate(heap)?.filter(|v| seen.insert(v.ptr_value()))))
     }
 }
 
@@ -181,10 +173,164 @@ pub fn json(builder: &mut GlobalsBuilder) {
     }
 }
 
+#[starlark_module]
+pub fn signature(builder: &mut GlobalsBuilder) {
+    /// Describe the parameters of a function, as a struct with fields `name`, `params` and
+    /// `return_type`, the return type annotation or `None`. The `name` of a function defined
+    /// in Starlark is qualified with the file it was defined in, e.g. `lib.star.f`.
+    ///
+    /// Each of the `params` is a struct with fields `name`, `kind` (one of `"positional_only"`,
+    /// `"normal"`, `"args"`, `"keyword_only"` or `"kwargs"`), `has_default`, `default` and
+    /// `type`, the type annotation or `None`. Native functions apply most of their defaults
+    /// in Rust, in which case `has_default` is `True` but `default` is `None`, and never
+    /// report types.
+    fn signature(ref f: Value) -> anyhow::Result<Struct<'v>> {
+        let signature = match f.signature() {
+            Some(signature) => signature,
+            None => return ValueError::unsupported_owned(f.get_type(), "signature()", None),
+        };
+        let params = signature.params.into_map(|param| {
+            let mut res = StructBuilder::with_capacity(heap, 5);
+            res.add("name", param.name);
+            res.add("kind", param.kind.as_str());
+            res.add("has_default", param.has_default);
+            res.add("default", param.default.unwrap_or_else(Value::new_none));
+            res.add("type", param.typ);
+            heap.alloc(res.build())
+        });
+        let mut res = StructBuilder::with_capacity(heap, 3);
+        res.add("name", signature.name);
+        res.add("params", params);
+        res.add("return_type", signature.return_type);
+        Ok(res.build())
+    }
+}
+
 #[starlark_module]
 pub fn abs(builder: &mut GlobalsBuilder) {
     fn abs(ref x: i32) -> anyhow::Result<i32> {
-        Ok(x.abs())
+        match x.checked_abs() {
+            Some(x) => Ok(x),
+            None => Err(IntError::Overflow(format!("abs({})", x)).into()),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+enum MathError {
+    #[error("{0}() is not defined for `{1}`")]
+    NotFinite(&'static str, String),
+    #[error("{0}() result `{1}` is out of the range of int")]
+    Overflow(&'static str, f64),
+}
+
+// Round `x` to a whole number with `f`, then convert it to an int.
+fn round_to_int(function: &'static str, x: Num, f: impl Fn(f64) -> f64) -> anyhow::Result<i32> {
+    match x {
+        Num::Int(x) => Ok(x),
+        Num::Float(x) => {
+            if !x.is_finite() {
+                return Err(MathError::NotFinite(function, StarlarkFloat(x).to_string()).into());
+            }
+            let res = f(x);
+            // Fails if the result doesn't fit, and turns `-0.0` into `0`.
+            Ok(Num::Float(res)
+                .as_int()
+                .ok_or(MathError::Overflow(function, res))?)
+        }
+    }
+}
+
+// Round `x` to a whole number, with halfway cases rounded to the even number, like Python.
+fn round_half_even(x: f64) -> f64 {
+    let r = x.round();
+    if (x - x.trunc()).abs() == 0.5 {
+        2.0 * (x / 2.0).round()
+    } else {
+        r
+    }
+}
+
+// Round `x` to `ndigits` decimal places, like Python.
+fn round_digits(x: f64, ndigits: i32) -> f64 {
+    if ndigits >= 0 {
+        // Far beyond the smallest float, so the number can't change.
+        if ndigits > 400 {
+            return x;
+        }
+        // Formatting works on the exact value of the float and rounds ties to even,
+        // so gives the same answer as Python, e.g. `2.675` is really `2.67499...`.
+        format!("{:.*}", ndigits as usize, x).parse().unwrap()
+    } else if ndigits < -400 {
+        // Multiplying keeps the sign of zero.
+        x * 0.0
+    } else {
+        let scale = 10f64.powi(-ndigits);
+        round_half_even(x / scale) * scale
+    }
+}
+
+#[starlark_module]
+pub fn math(builder: &mut GlobalsBuilder) {
+    /// `floor(x)` returns the largest int less than or equal to the number `x`.
+    /// It is an error if `x` is NaN or infinite, or if the result doesn't fit in an int.
+    ///
+    /// ```
+    /// # starlark::assert::all_true(r#"
+    /// floor(2.5) == 2
+    /// floor(-2.5) == -3
+    /// floor(7) == 7
+    /// # "#);
+    /// ```
+    fn floor(ref x: Num) -> anyhow::Result<i32> {
+        round_to_int("floor", x, f64::floor)
+    }
+
+    /// `ceil(x)` returns the smallest int greater than or equal to the number `x`.
+    /// It is an error if `x` is NaN or infinite, or if the result doesn't fit in an int.
+    ///
+    /// ```
+    /// # starlark::assert::all_true(r#"
+    /// ceil(2.5) == 3
+    /// ceil(-2.5) == -2
+    /// ceil(7) == 7
+    /// # "#);
+    /// ```
+    fn ceil(ref x: Num) -> anyhow::Result<i32> {
+        round_to_int("ceil", x, f64::ceil)
+    }
+
+    /// `round(x)` returns the int nearest to the number `x`, with values exactly halfway
+    /// between two ints rounded to the even one.
+    /// `round(x, ndigits)` instead rounds to `ndigits` decimal places (or to a power of ten
+    /// if `ndigits` is negative) and returns a float.
+    /// Rounding is based on the exact value of the float, so `round(2.675, 2)` is `2.67`,
+    /// since the nearest float to `2.675` is slightly smaller.
+    /// It is an error if `x` is NaN or infinite, or if the int result doesn't fit in an int.
+    ///
+    /// ```
+    /// # starlark::assert::all_true(r#"
+    /// round(2.5) == 2
+    /// round(3.5) == 4
+    /// round(-0.4) == 0
+    /// round(3.14159, 2) == 3.14
+    /// round(1234, -2) == 1200.0
+    /// # "#);
+    /// ```
+    fn round(ref x: Num, ndigits: Option<i32>) -> anyhow::Result<Value<'v>> {
+        match ndigits {
+            None => {
+                let res = round_to_int("round", x, round_half_even)?;
+                Ok(Value::new_int(res))
+            }
+            Some(ndigits) => {
+                let x = x.as_float();
+                if !x.is_finite() {
+                    return Err(MathError::NotFinite("round", StarlarkFloat(x).to_string()).into());
+                }
+                Ok(heap.alloc(round_digits(x, ndigits)))
+            }
+        }
     }
 }
 
@@ -303,8 +449,10 @@ assert_eq([1, 2, 3], filter(positive, [1, 2, 3]))
 assert_eq([], filter(positive, [-1, -2, -3]))
 assert_eq([1, 2, 3], filter(positive, [-1, 1, 2, -2, -3, 3]))
 assert_eq(["hello world!"], filter(contains_hello, ["hello world!", "goodbye"]))
+assert_eq([0, 1, False, True, "", "x"], filter(None, [0, 1, None, False, True, "", "x"]))
 "#,
         );
+        assert::fail("filter(lambda x: x.foo, [1])", "has no attribute `foo`");
     }
 
     #[test]
@@ -318,8 +466,94 @@ assert_eq([], map(int, []))
 assert_eq([1,2,3], map(int, ["1","2","3"]))
 assert_eq(["0","1","2"], map(str, range(3)))
 assert_eq(["11",8], map(double, ["1",4]))
+assert_eq(list(range(0, 2000, 2)), map(double, range(1000)))
+assert_eq([3, 4], map(len, {"abc": 1, "defg": 2}))
+"#,
+        );
+        assert::fail("map(int, ['1', 'x'])", "not a valid number");
+    }
+
+    #[test]
+    fn test_map_filter_mutate_source() {
+        // The callback can't change the sequence being iterated over
+        assert::fail(
+            r#"
+xs = [1, 2, 3]
+def f(x):
+    xs.append(x)
+    return x
+map(f, xs)
+"#,
+            "mutate an iterable for an iterator while iterating",
+        );
+        assert::fail(
+            r#"
+xs = [1, 2, 3]
+def f(x):
+    xs.append(x)
+    return True
+filter(f, xs)
+"#,
+            "mutate an iterable for an iterator while iterating",
+        );
+        // But once finished the source is mutable again, and unaliased from the result
+        assert::pass(
+            r#"
+xs = [1, 2, 3]
+ys = map(lambda x: x, xs)
+zs = filter(None, xs)
+xs.append(4)
+assert_eq([1, 2, 3], ys)
+assert_eq([1, 2, 3], zs)
+"#,
+        );
+    }
+
+    #[test]
+    fn test_math() {
+        assert::pass(
+            r#"
+assert_eq(floor(-0.0), 0)
+assert_eq(ceil(-0.5), 0)
+assert_eq(round(-0.5), 0)
+assert_eq(round(0.5), 0)
+assert_eq(round(1.5), 2)
+assert_eq(floor(2147483647.5), 2147483647)
+assert_eq(ceil(-2147483648.5), -2147483648)
+assert_eq(round(2.675, 2), 2.67)
+assert_eq(round(0.125, 2), 0.12)
+assert_eq(round(0.375, 2), 0.38)
+assert_eq(round(1e300, 2), 1e300)
+assert_eq(round(5, 1), 5.0)
+assert_eq(type(round(5, 1)), "float")
+assert_eq(round(15.0, -1), 20.0)
+assert_eq(repr(round(-0.0, 1)), "-0.0")
+assert_eq(repr(round(-0.001, 2)), "-0.0")
+"#,
+        );
+        assert::fail("ceil(2147483647.5)", "out of the range of int");
+        assert::fail("floor(-2147483648.5)", "out of the range of int");
+        assert::fail("round(2147483647.5)", "out of the range of int");
+        assert::fail("floor(float('nan'))", "floor() is not defined for `nan`");
+        assert::fail("ceil(float('inf'))", "ceil() is not defined for `+inf`");
+        assert::fail(
+            "round(float('-inf'), 2)",
+            "round() is not defined for `-inf`",
+        );
+    }
+
+    #[test]
+    fn test_float_methods() {
+        assert::all_true(
+            r#"
+float("nan").is_nan()
+not float("-inf").is_nan()
+float("-inf").is_infinite()
+not (-0.0).is_infinite()
+not (1.0).is_nan()
 "#,
         );
+        assert::fail("(1).is_nan()", "has no attribute");
     }
 
     #[test]
@@ -377,6 +611,62 @@ assert_eq(dedupe([a,b,a]), [a,b])
         );
     }
 
+    #[test]
+    fn test_signature() {
+        assert::pass(
+            r#"
+def f(a, b = 1, *args, c, d = [2], **kw) -> str.type:
+    pass
+s = signature(f)
+assert_eq(s.name, "assert.bzl.f")
+assert_eq(s.return_type, "string")
+assert_eq([p.name for p in s.params], ["a", "b", "args", "c", "d", "kw"])
+assert_eq(
+    [p.kind for p in s.params],
+    ["normal", "normal", "args", "keyword_only", "keyword_only", "kwargs"],
+)
+assert_eq([p.has_default for p in s.params], [False, True, False, False, True, False])
+assert_eq([p.default for p in s.params], [None, 1, None, None, [2], None])
+assert_eq(repr(f), "assert.bzl.f(a, b = 1, *args, c, d = [2], **kw)")
+assert_eq(str(f), repr(f))
+assert_eq("{}".format(f), repr(f))
+
+def g(x: int.type, *, y = None):
+    pass
+s = signature(g)
+assert_eq(s.return_type, None)
+assert_eq([(p.name, p.kind, p.type) for p in s.params], [
+    ("x", "normal", "int"),
+    ("y", "keyword_only", None),
+])
+assert_eq(repr(g), "assert.bzl.g(x, *, y = None)")
+"#,
+        );
+    }
+
+    #[test]
+    fn test_signature_native() {
+        assert::pass(
+            r#"
+s = signature(filter)
+assert_eq(s.name, "filter")
+assert_eq([(p.name, p.kind) for p in s.params], [
+    ("func", "positional_only"),
+    ("seq", "positional_only"),
+])
+s = signature(fail)
+assert_eq([(p.name, p.kind, p.has_default) for p in s.params], [
+    ("args", "args", False),
+    ("code", "keyword_only", True),
+    ("attrs", "keyword_only", True),
+])
+repeat = signature(product).params[1]
+assert_eq((repeat.name, repeat.kind, repeat.has_default, repeat.default), ("repeat", "keyword_only", True, None))
+"#,
+        );
+        assert::fail("signature(1)", "signature()");
+    }
+
     #[test]
     fn test_print() {
         let s = Rc::new(RefCell::new(String::new()));
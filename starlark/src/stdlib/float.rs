@@ -0,0 +1,54 @@
+/*
+ * Copyright 2018 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Methods for the `float` type.
+
+use crate::{self as starlark, environment::MethodsBuilder, values::float::StarlarkFloat};
+
+#[starlark_module]
+pub(crate) fn float_methods(builder: &mut MethodsBuilder) {
+    /// `x.is_nan()` returns `True` if `x` is a NaN ("not a number") value.
+    ///
+    /// Examples:
+    ///
+    /// ```
+    /// # starlark::assert::all_true(r#"
+    /// float("nan").is_nan()
+    /// not float("inf").is_nan()
+    /// not (1.5).is_nan()
+    /// # "#);
+    /// ```
+    fn is_nan(this: StarlarkFloat) -> anyhow::Result<bool> {
+        Ok(this.0.is_nan())
+    }
+
+    /// `x.is_infinite()` returns `True` if `x` is positive or negative infinity.
+    ///
+    /// Examples:
+    ///
+    /// ```
+    /// # starlark::assert::all_true(r#"
+    /// float("inf").is_infinite()
+    /// float("-inf").is_infinite()
+    /// not float("nan").is_infinite()
+    /// not (1e308).is_infinite()
+    /// # "#);
+    /// ```
+    fn is_infinite(this: StarlarkFloat) -> anyhow::Result<bool> {
+        Ok(this.0.is_infinite())
+    }
+}
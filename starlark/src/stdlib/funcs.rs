@@ -21,33 +21,175 @@
 use std::{cmp::Ordering, num::NonZeroI32};
 
 use anyhow::anyhow;
+use gazebo::prelude::*;
 
 use crate::{
     self as starlark,
     collections::SmallMap,
     environment::GlobalsBuilder,
-    eval::Arguments,
+    errors::{Diagnostic, StarlarkFailError},
+    eval::{Arguments, Evaluator},
+    stdlib::util::unpack_pair,
     values::{
-        bool::BOOL_TYPE, dict::Dict, float::StarlarkFloat, int::INT_TYPE, list::List,
-        none::NoneType, num::Num, range::Range, string::STRING_TYPE, tuple::Tuple, Heap,
+        bool::BOOL_TYPE,
+        dict::{Dict, DictOf},
+        float::StarlarkFloat,
+        int::INT_TYPE,
+        list::List,
+        none::NoneType,
+        num::Num,
+        range::Range,
+        repr_limits::collect_repr_unlimited,
+        string::STRING_TYPE,
+        tuple::Tuple,
         StringValue, Value, ValueError, ValueLike,
     },
 };
 
-fn unpack_pair<'v>(pair: Value<'v>, heap: &'v Heap) -> anyhow::Result<(Value<'v>, Value<'v>)> {
-    pair.with_iterator(heap, |it| {
-        if let Some(first) = it.next() {
-            if let Some(second) = it.next() {
-                if it.next().is_none() {
-                    return Ok((first, second));
+/// Stable sort of the values in `items` (pairs of value and key), using `keys`,
+/// which are the keys of `items` converted to a type which Rust can compare directly.
+fn sort_by_keys<'v, K: Ord>(
+    items: Vec<(Value<'v>, Value<'v>)>,
+    keys: Vec<K>,
+    reverse: bool,
+) -> impl Iterator<Item = Value<'v>> {
+    let mut items: Vec<(K, Value<'v>)> = keys
+        .into_iter()
+        .zip(items)
+        .map(|(k, (v, _))| (k, v))
+        .collect();
+    if reverse {
+        items.sort_by(|x, y| y.0.cmp(&x.0));
+    } else {
+        items.sort_by(|x, y| x.0.cmp(&y.0));
+    }
+    items.into_iter().map(|x| x.1)
+}
+
+/// Shared implementation of `min` and `max`, named `name`, which replace the current result
+/// with an element when comparing the result (or its key) with the element gives `replace`.
+fn min_max<'v>(
+    name: &str,
+    replace: Ordering,
+    mut args: Vec<Value<'v>>,
+    key: Option<Value<'v>>,
+    default: Option<Value<'v>>,
+    eval: &mut Evaluator<'v, '_>,
+) -> anyhow::Result<Value<'v>> {
+    let heap = eval.heap();
+    let args = if args.len() == 1 {
+        args.swap_remove(0)
+    } else if default.is_some() {
+        return Err(anyhow!(
+            "{}() can't have a `default` with more than one positional argument",
+            name
+        ));
+    } else {
+        heap.alloc(args)
+    };
+    let mut it = args.iterate(heap)?;
+    let mut res = match it.next() {
+        Some(x) => x,
+        None => {
+            return match default {
+                Some(default) => Ok(default),
+                None => Err(anyhow!(
+                    "Argument is an empty iterable, {}() expect a non empty iterable",
+                    name
+                )),
+            };
+        }
+    };
+    match key {
+        None => {
+            for x in it {
+                if res.compare(x)? == replace {
+                    res = x;
                 }
             }
         }
-        Err(anyhow!(
-            "Found a non-pair element in the positional argument of dict(): {}",
-            pair.to_repr(),
-        ))
-    })?
+        Some(key) => {
+            let mut res_key = call_key(name, key, 0, res, eval)?;
+            for (i, x) in it.enumerate() {
+                let x_key = call_key(name, key, i + 1, x, eval)?;
+                if res_key.compare(x_key)? == replace {
+                    res = x;
+                    res_key = x_key;
+                }
+            }
+        }
+    }
+    Ok(res)
+}
+
+/// Call the `key` function of `min` or `max` on the element `x` at `index`,
+/// naming the element in any error.
+fn call_key<'v>(
+    name: &str,
+    key: Value<'v>,
+    index: usize,
+    x: Value<'v>,
+    eval: &mut Evaluator<'v, '_>,
+) -> anyhow::Result<Value<'v>> {
+    key.invoke_pos(&[x], eval).map_err(|e| {
+        let context = format!(
+            "{}() `key` function failed on element {} (`{}`)",
+            name,
+            index,
+            x.to_repr()
+        );
+        match e.downcast::<Diagnostic>() {
+            Ok(d) => Diagnostic {
+                message: d.message.context(context),
+                ..d
+            }
+            .into(),
+            Err(e) => e.context(context),
+        }
+    })
+}
+
+/// Why [`parse_int`] failed.
+enum ParseIntError {
+    /// The string isn't a number in the base.
+    Invalid,
+    /// The number is valid in the given base (which may have been inferred from the prefix),
+    /// but doesn't fit in an `i32`.
+    Overflow(u32),
+}
+
+/// Parse a string for `int()`, where `base` is between 2 and 36,
+/// or 0 to infer it from the prefix like an integer literal.
+/// If `base` is omitted, it is inferred from the prefix too, but is otherwise decimal,
+/// so leading zeros are allowed.
+fn parse_int(s: &str, base: Option<u32>) -> Result<i32, ParseIntError> {
+    let (negate, s) = match s.as_bytes().first() {
+        Some(b'+') => (false, &s[1..]),
+        Some(b'-') => (true, &s[1..]),
+        _ => (false, s),
+    };
+    let prefix = |x: &str| s.get(0..2).map_or(false, |p| p.eq_ignore_ascii_case(x));
+    let (base, digits) = match base {
+        None | Some(0 | 2) if prefix("0b") => (2, &s[2..]),
+        None | Some(0 | 8) if prefix("0o") => (8, &s[2..]),
+        None | Some(0 | 16) if prefix("0x") => (16, &s[2..]),
+        None => (10, s),
+        // Like the lexer, a decimal literal can't have leading zeros
+        Some(0) if s.starts_with('0') && s.bytes().any(|c| c != b'0') => {
+            return Err(ParseIntError::Invalid);
+        }
+        Some(0) => (10, s),
+        Some(base) => (base, s),
+    };
+    // Check the digits ourselves, since `from_str_radix` would accept a second sign.
+    // Underscores are rejected, as they are not valid in integer literals.
+    if digits.is_empty() || !digits.chars().all(|c| c.is_digit(base)) {
+        return Err(ParseIntError::Invalid);
+    }
+    // Parse as `i64` so that `i32::MIN`, whose magnitude doesn't fit in an `i32`, works.
+    // The digits are valid, so this can only fail if the number is too large.
+    let x = i64::from_str_radix(digits, base).map_err(|_| ParseIntError::Overflow(base))?;
+    i32::try_from(if negate { -x } else { x }).map_err(|_| ParseIntError::Overflow(base))
 }
 
 #[starlark_module]
@@ -58,6 +200,13 @@ pub(crate) fn global_functions(builder: &mut GlobalsBuilder) {
 
     /// fail: fail the execution
     ///
+    /// The positional arguments are joined with spaces to form the message,
+    /// using strings as they are and the `repr` of other values.
+    /// An embedder can attach structured data with the named arguments `code`,
+    /// a string, and `attrs`, a dict with string keys, and read it back from the error
+    /// as a [`StarlarkFailError`](crate::errors::StarlarkFailError). Values in `attrs`
+    /// which are not strings are converted to their `repr`.
+    ///
     /// Examples:
     /// ```
     /// # starlark::assert::fail(r#"
@@ -66,17 +215,34 @@ pub(crate) fn global_functions(builder: &mut GlobalsBuilder) {
     /// # starlark::assert::fail(r#"
     /// fail("oops", 1, False)  # fail: oops 1 False
     /// # "#, "oops 1 False");
+    /// # starlark::assert::fail(r#"
+    /// fail("boom", code = "E123", attrs = {"url": "https://example.com"})  # fail: boom
+    /// # "#, "fail: boom");
     /// ```
-    fn fail(args: Vec<Value>) -> anyhow::Result<NoneType> {
-        let mut s = String::new();
-        for x in args {
-            s.push(' ');
-            match x.unpack_str() {
-                Some(x) => s.push_str(x),
-                None => x.collect_repr(&mut s),
-            }
-        }
-        Err(anyhow!("fail:{}", s))
+    fn fail(
+        args: Vec<Value>,
+        code: Option<&str>,
+        attrs: Option<DictOf<'v, &'v str, Value<'v>>>,
+    ) -> anyhow::Result<NoneType> {
+        let args = args.into_map(|x| match x.unpack_str() {
+            Some(x) => x.to_owned(),
+            None => x.to_repr(),
+        });
+        let attrs = match attrs {
+            None => Vec::new(),
+            Some(attrs) => attrs.collect_entries().into_map(|(k, v)| {
+                let v = match v.unpack_str() {
+                    Some(v) => v.to_owned(),
+                    None => {
+                        let mut s = String::new();
+                        collect_repr_unlimited(v, &mut s);
+                        s
+                    }
+                };
+                (k.to_owned(), v)
+            }),
+        };
+        Err(StarlarkFailError::new(args, code.map(str::to_owned), attrs).into())
     }
 
     /// [any](
@@ -255,8 +421,8 @@ pub(crate) fn global_functions(builder: &mut GlobalsBuilder) {
                     }
                     None => pos.with_iterator(heap, |it| -> anyhow::Result<_> {
                         let mut result = SmallMap::with_capacity(it.size_hint().0 + kwargs.len());
-                        for el in it {
-                            let (k, v) = unpack_pair(el, heap)?;
+                        for (i, el) in it.enumerate() {
+                            let (k, v) = unpack_pair(el, i, "dict", heap)?;
                             let k = k.get_hashed()?;
                             result.insert_hashed(k, v);
                         }
@@ -286,9 +452,8 @@ pub(crate) fn global_functions(builder: &mut GlobalsBuilder) {
     /// "capitalize" in dir("abc")
     /// # "#);
     /// ```
-    #[starlark(speculative_exec_safe)]
     fn dir(ref x: Value) -> anyhow::Result<Vec<String>> {
-        Ok(x.dir_attr())
+        Ok(eval.dir_attr(x))
     }
 
     /// [enumerate](
@@ -308,15 +473,20 @@ pub(crate) fn global_functions(builder: &mut GlobalsBuilder) {
     /// # starlark::assert::all_true(r#"
     /// enumerate(["zero", "one", "two"]) == [(0, "zero"), (1, "one"), (2, "two")]
     /// enumerate(["one", "two"], 1) == [(1, "one"), (2, "two")]
+    /// enumerate(["one", "two"], start = -1) == [(-1, "one"), (0, "two")]
     /// # "#);
     /// ```
     #[starlark(speculative_exec_safe)]
     fn enumerate(ref it: Value, start @ 0: i32) -> anyhow::Result<Value<'v>> {
-        let v = it
-            .iterate(heap)?
-            .enumerate()
-            .map(|(k, v)| heap.alloc((k as i32 + start, v)));
-        Ok(heap.alloc_list_iter(v))
+        heap.try_alloc_list_iter(it.iterate(heap)?.enumerate().map(
+            |(k, v)| -> anyhow::Result<Value<'v>> {
+                let k = i32::try_from(k)
+                    .ok()
+                    .and_then(|k| k.checked_add(start))
+                    .ok_or(ValueError::IntegerOverflow)?;
+                Ok(heap.alloc((k, v)))
+            },
+        ))
     }
 
     /// [float](
@@ -386,9 +556,14 @@ pub(crate) fn global_functions(builder: &mut GlobalsBuilder) {
     ///
     /// `getattr(x, "f")` is equivalent to `x.f`.
     ///
+    /// `getattr(x, name, default)` returns `default` if x has no such attribute.
+    /// Errors raised while computing an attribute which does exist are not replaced
+    /// by the default.
+    ///
     /// ```
     /// # starlark::assert::all_true(r#"
     /// getattr("banana", "split")("a") == ["b", "n", "n", ""] # equivalent to "banana".split("a")
+    /// getattr("banana", "missing", 42) == 42
     /// # "#);
     /// ```
     #[starlark(speculative_exec_safe)]
@@ -397,8 +572,7 @@ pub(crate) fn global_functions(builder: &mut GlobalsBuilder) {
         ref attr: &str,
         ref default: Option<Value>,
     ) -> anyhow::Result<Value<'v>> {
-        // Make sure we check if its a function first, to be consistent with `a.f`
-        match a.get_attr(attr, heap)? {
+        match eval.get_attr(a, attr)? {
             Some(v) => Ok(v),
             None => match default {
                 Some(x) => Ok(x),
@@ -414,7 +588,7 @@ pub(crate) fn global_functions(builder: &mut GlobalsBuilder) {
     /// `hasattr(x, name)` reports whether x has an attribute (field or method)
     /// named `name`.
     fn hasattr(ref a: Value, ref attr: &str) -> anyhow::Result<bool> {
-        Ok(a.has_attr(attr))
+        Ok(eval.has_attr(a, attr))
     }
 
     /// [hash](
@@ -471,13 +645,18 @@ pub(crate) fn global_functions(builder: &mut GlobalsBuilder) {
     /// `+Inf`, `-Inf`).
     /// If x is a `bool`, the result is 0 for `False` or 1 for `True`.
     ///
-    /// If x is a string, it is interpreted like a string literal;
-    /// an optional base prefix (`0`, `0b`, `0B`, `0x`, `0X`) determines which
-    /// base to use. The string may specify an arbitrarily large integer,
-    /// whereas true integer literals are restricted to 64 bits.
-    /// If a non-zero `base` argument is provided, the string is interpreted
-    /// in that base and no base prefix is permitted; the base argument may
-    /// specified by name.
+    /// If x is a string, it is interpreted as a sequence of digits in the
+    /// specified base.
+    /// If `base` is omitted, an optional base prefix (`0b`, `0o` or `0x`, in either case)
+    /// determines the base, which is otherwise decimal.
+    /// If `base` is zero, x is interpreted like an integer literal, the base
+    /// being inferred from an optional base prefix (`0b`, `0o` or `0x`, in either case)
+    /// preceding the first digit.
+    /// When the base is provided explicitly, a matching base prefix is also
+    /// permitted, and has no effect.
+    /// Irrespective of base, the string may start with an optional `+` or `-`
+    /// sign indicating the value's sign. The result must fit in a 32-bit integer.
+    /// The base argument may be specified by name.
     ///
     /// `int()` with no arguments returns 0.
     ///
@@ -492,6 +671,10 @@ pub(crate) fn global_functions(builder: &mut GlobalsBuilder) {
     /// int('16', 10) == 16
     /// int('16', 8) == 14
     /// int('16', 16) == 22
+    /// int('0x16', 16) == 22
+    /// int('0x16') == 22
+    /// int('-0x16', 0) == -22
+    /// int('0b101', 0) == 5
     /// int(0.0) == 0
     /// int(3.14) == 3
     /// int(-12345.6789) == -12345
@@ -519,63 +702,28 @@ pub(crate) fn global_functions(builder: &mut GlobalsBuilder) {
         let a = a.unwrap();
         if let Some(s) = a.unpack_str() {
             let base = match base {
-                Some(base) => base.to_int()?,
-                None => 0,
+                Some(base) => Some(base.to_int()?),
+                None => None,
             };
-            if base == 1 || base < 0 || base > 36 {
-                return Err(anyhow!(
-                    "{} is not a valid base, int() base must be >= 2 and <= 36",
-                    base
-                ));
-            }
-            let (sign, s) = {
-                match s.chars().next() {
-                    Some('+') => (1, s.get(1..).unwrap()),
-                    Some('-') => (-1, s.get(1..).unwrap()),
-                    _ => (1, s),
-                }
-            };
-            let base = if base == 0 {
-                match s.get(0..2) {
-                    Some("0b") | Some("0B") => 2,
-                    Some("0o") | Some("0O") => 8,
-                    Some("0x") | Some("0X") => 16,
-                    _ => 10,
-                }
-            } else {
-                base as u32
-            };
-            let s = match base {
-                16 => {
-                    if s.starts_with("0x") || s.starts_with("0X") {
-                        s.get(2..).unwrap()
-                    } else {
-                        s
-                    }
+            if let Some(base) = base {
+                if base == 1 || base < 0 || base > 36 {
+                    return Err(anyhow!(
+                        "{} is not a valid base, int() base must be >= 2 and <= 36",
+                        base
+                    ));
                 }
-                8 => {
-                    if s.starts_with("0o") || s.starts_with("0O") {
-                        s.get(2..).unwrap()
-                    } else {
-                        s
-                    }
-                }
-                2 => {
-                    if s.starts_with("0b") || s.starts_with("0B") {
-                        s.get(2..).unwrap()
-                    } else {
-                        s
-                    }
-                }
-                _ => s,
-            };
-            match i32::from_str_radix(s, base) {
-                Ok(i) => Ok(sign * i),
-                Err(x) => Err(anyhow!(
-                    "{} is not a valid number in base {}: {}",
+            }
+            match parse_int(s, base.map(|x| x as u32)) {
+                Ok(i) => Ok(i),
+                Err(ParseIntError::Invalid) => Err(anyhow!(
+                    "{} is not a valid number in base {}",
                     a.to_repr(),
-                    base,
-                    x,
+                    base.unwrap_or(10)
+                )),
+                Err(ParseIntError::Overflow(base)) => Err(anyhow!(
+                    "Integer overflow converting {} to int in base {}, it must fit in 32 bits",
+                    a.to_repr(),
+                    base
                 )),
             }
         } else if let Some(base) = base {
@@ -644,8 +792,8 @@ pub(crate) fn global_functions(builder: &mut GlobalsBuilder) {
     #[starlark(speculative_exec_safe)]
     fn list(ref a: Option<Value>) -> anyhow::Result<Value<'v>> {
         Ok(if let Some(a) = a {
-            if let Some(xs) = List::from_value(a) {
-                heap.alloc_list(xs.content())
+            if let Some(xs) = List::copy_value(a, heap) {
+                xs
             } else {
                 a.with_iterator(heap, |it| heap.alloc_list_iter(it))?
             }
@@ -661,54 +809,28 @@ pub(crate) fn global_functions(builder: &mut GlobalsBuilder) {
     /// `max(x)` returns the greatest element in the iterable sequence x.
     ///
     /// It is an error if any element does not support ordered comparison,
-    /// or if the sequence is empty.
+    /// or if the sequence is empty, unless the named parameter `default` is given,
+    /// in which case it is returned for an empty sequence.
+    /// A `default` can't be given with more than one positional argument.
     ///
     /// The optional named parameter `key` specifies a function to be applied
-    /// to each element prior to comparison.
+    /// to each element prior to comparison. It is called once per element.
     ///
     /// ```
     /// # starlark::assert::all_true(r#"
     /// max([3, 1, 4, 1, 5, 9])               == 9
     /// max("two", "three", "four")           == "two"    # the lexicographically greatest
     /// max("two", "three", "four", key=len)  == "three"  # the longest
+    /// max([], default=0)                    == 0
     /// # "#);
     /// ```
     #[starlark(speculative_exec_safe)]
-    fn max(mut args: Vec<Value>, key: Option<Value>) -> anyhow::Result<Value<'v>> {
-        let args = if args.len() == 1 {
-            args.swap_remove(0)
-        } else {
-            heap.alloc(args)
-        };
-        let mut it = args.iterate(heap)?;
-        let mut max = match it.next() {
-            Some(x) => x,
-            None => {
-                return Err(anyhow!(
-                    "Argument is an empty iterable, max() expect a non empty iterable"
-                ));
-            }
-        };
-        match key {
-            None => {
-                for i in it {
-                    if max.compare(i)? == Ordering::Less {
-                        max = i;
-                    }
-                }
-            }
-            Some(key) => {
-                let mut cached = key.invoke_pos(&[max], eval)?;
-                for i in it {
-                    let keyi = key.invoke_pos(&[i], eval)?;
-                    if cached.compare(keyi)? == Ordering::Less {
-                        max = i;
-                        cached = keyi;
-                    }
-                }
-            }
-        };
-        Ok(max)
+    fn max(
+        args: Vec<Value>,
+        key: Option<Value>,
+        default: Option<Value>,
+    ) -> anyhow::Result<Value<'v>> {
+        min_max("max", Ordering::Less, args, key, default, eval)
     }
 
     /// [min](
@@ -725,44 +847,16 @@ pub(crate) fn global_functions(builder: &mut GlobalsBuilder) {
     /// min([3, 1, 4, 1, 5, 9])                 == 1
     /// min("two", "three", "four")             == "four"  # the lexicographically least
     /// min("two", "three", "four", key=len)    == "two"   # the shortest
+    /// min([], default=0)                      == 0
     /// # "#);
     /// ```
     #[starlark(speculative_exec_safe)]
-    fn min(mut args: Vec<Value>, key: Option<Value>) -> anyhow::Result<Value<'v>> {
-        let args = if args.len() == 1 {
-            args.swap_remove(0)
-        } else {
-            heap.alloc(args)
-        };
-        let mut it = args.iterate(heap)?;
-        let mut min = match it.next() {
-            Some(x) => x,
-            None => {
-                return Err(anyhow!(
-                    "Argument is an empty iterable, min() expect a non empty iterable"
-                ));
-            }
-        };
-        match key {
-            None => {
-                for i in it {
-                    if min.compare(i)? == Ordering::Greater {
-                        min = i;
-                    }
-                }
-            }
-            Some(key) => {
-                let mut cached = key.invoke_pos(&[min], eval)?;
-                for i in it {
-                    let keyi = key.invoke_pos(&[i], eval)?;
-                    if cached.compare(keyi)? == Ordering::Greater {
-                        min = i;
-                        cached = keyi;
-                    }
-                }
-            }
-        };
-        Ok(min)
+    fn min(
+        args: Vec<Value>,
+        key: Option<Value>,
+        default: Option<Value>,
+    ) -> anyhow::Result<Value<'v>> {
+        min_max("min", Ordering::Greater, args, key, default, eval)
     }
 
     /// [ord](
@@ -866,7 +960,7 @@ pub(crate) fn global_functions(builder: &mut GlobalsBuilder) {
     #[starlark(speculative_exec_safe)]
     fn repr(ref a: Value) -> anyhow::Result<StringValue<'v>> {
         let mut s = eval.string_pool.alloc();
-        a.collect_repr(&mut s);
+        collect_repr_unlimited(a, &mut s);
         let r = eval.heap().alloc_str(&s);
         eval.string_pool.release(s);
         Ok(r)
@@ -902,7 +996,8 @@ pub(crate) fn global_functions(builder: &mut GlobalsBuilder) {
     /// sequence x, in sorted order.  The sort algorithm is stable.
     ///
     /// The optional named parameter `reverse`, if true, causes `sorted` to
-    /// return results in reverse sorted order.
+    /// return results in reverse sorted order. Elements with equal keys
+    /// still keep their original relative order.
     ///
     /// The optional named parameter `key` specifies a function of one
     /// argument to apply to obtain the value's sort key.
@@ -935,9 +1030,28 @@ pub(crate) fn global_functions(builder: &mut GlobalsBuilder) {
             }
         };
 
+        let reverse = reverse.map_or(false, |x| x.to_bool());
+
+        // Lists of ints or strings are common and can be sorted without dynamic dispatch.
+        if let Some(keys) = it
+            .iter()
+            .map(|x| x.1.unpack_int())
+            .collect::<Option<Vec<_>>>()
+        {
+            return Ok(heap.alloc_list_iter(sort_by_keys(it, keys, reverse)));
+        }
+        if let Some(keys) = it
+            .iter()
+            .map(|x| x.1.unpack_str())
+            .collect::<Option<Vec<_>>>()
+        {
+            return Ok(heap.alloc_list_iter(sort_by_keys(it, keys, reverse)));
+        }
+
         let mut compare_ok = Ok(());
 
-        let reverse = reverse.map_or(false, |x| x.to_bool());
+        // `sort_by` is stable, and reversing the comparison (rather than the result)
+        // keeps elements with equal keys in their original order.
         it.sort_by(|x: &(Value, Value), y: &(Value, Value)| {
             let ord_or_err = if reverse {
                 x.1.compare(y.1).map(Ordering::reverse)
@@ -981,7 +1095,7 @@ pub(crate) fn global_functions(builder: &mut GlobalsBuilder) {
             Ok(a)
         } else {
             let mut s = eval.string_pool.alloc();
-            a.collect_repr(&mut s);
+            collect_repr_unlimited(a, &mut s);
             let r = eval.heap().alloc_str(&s);
             eval.string_pool.release(s);
             Ok(r)
@@ -1086,6 +1200,116 @@ mod tests {
         assert::fail("chr(0x110000)", "not a valid UTF-8");
     }
 
+    #[test]
+    fn test_int() {
+        // Based on the int() cases in the Go Starlark test suite int.star
+        assert::pass(
+            r#"
+assert_eq(int("123"), 123)
+assert_eq(int("-123"), -123)
+assert_eq(int("0123"), 123) # not octal
+assert_eq(int("-0123"), -123)
+assert_eq(int("0"), 0)
+assert_eq(int("00"), 0)
+assert_eq(int("-00", base=8), 0)
+assert_eq(int("+00", base=10), 0)
+assert_eq(int("11", base=9), 10)
+assert_eq(int("-11", base=9), -10)
+assert_eq(int("10011", base=2), 19)
+assert_eq(int("-10011", base=2), -19)
+assert_eq(int("123", 8), 83)
+assert_eq(int("0123", 8), 83) # redundant zeros permitted
+assert_eq(int("-00123", 8), -83)
+assert_eq(int("0o123", 8), 83)
+assert_eq(int("-0o123", 8), -83)
+assert_eq(int("123", 7), 66)
+assert_eq(int("12", 16), 18)
+assert_eq(int("0x12", 16), 18)
+assert_eq(int("-0x12", 16), -18)
+assert_eq(int("0b0101", 0), 5)
+assert_eq(int("0b0101", 2), 5) # prefix is redundant with explicit base
+assert_eq(int("0B00000", 0), 0)
+assert_eq(int("0b0", 16), 0xb0)
+assert_eq(int("0x0b0", 0), 0xb0)
+assert_eq(int("123", 0), 123)
+assert_eq(int("+123", 0), 123)
+assert_eq(int("-123", 0), -123)
+assert_eq(int("0X12", 0), 18)
+assert_eq(int("+0x12", 0), 18)
+assert_eq(int("-0x12", 0), -18)
+assert_eq(int("0o123", 0), 83)
+assert_eq(int("-0O123", 0), -83)
+assert_eq(int("zz", 36), 1295)
+assert_eq(int("2147483647"), 2147483647)
+assert_eq(int("-2147483648"), -2147483647 - 1)
+assert_eq(int("0x7fffffff", 0), 2147483647)
+assert_eq(int("-0x80000000", 0), -2147483647 - 1)
+# Without a base, a prefix is detected, but leading zeros are still decimal
+assert_eq(int("0x1f"), 31)
+assert_eq(int("-0X1F"), -31)
+assert_eq(int("0o17"), 15)
+assert_eq(int("+0b101"), 5)
+assert_eq(int("0017"), 17)
+"#,
+        );
+        for (s, base) in [
+            ("0x12", "10"),
+            ("-0x12", "10"),
+            ("0o123", "10"),
+            ("0b0", "10"),
+            ("0x123", "8"),
+            ("0o123", "16"),
+            ("0x110", "2"),
+            ("0123", "0"),
+            ("-0123", "0"),
+            ("0Oxa", "8"),
+            ("--4", "10"),
+            ("++4", "10"),
+            ("+-4", "10"),
+            ("0x-4", "16"),
+            ("0x", "0"),
+            ("", "10"),
+            ("1_000", "10"),
+            ("0x_1", "0"),
+        ] {
+            assert::fail(
+                &format!("int({:?}, {})", s, base),
+                &format!("\"{}\" is not a valid number in base {}", s, base),
+            );
+        }
+        for (s, base, inferred) in [
+            ("2147483648", "10", "10"),
+            ("-2147483649", "10", "10"),
+            ("0x80000000", "0", "16"),
+            ("ffffffff", "16", "16"),
+            ("99999999999999999999999", "10", "10"),
+        ] {
+            assert::fail(
+                &format!("int({:?}, {})", s, base),
+                &format!("Integer overflow converting \"{}\" to int in base {}", s, inferred),
+            );
+        }
+        assert::fail("int('0x')", "\"0x\" is not a valid number in base 10");
+        assert::fail("int('0b2')", "\"0b2\" is not a valid number in base 10");
+        assert::fail("int('1', 1)", "1 is not a valid base");
+        assert::fail("int('1', 37)", "37 is not a valid base");
+        assert::fail("int(3, 10)", "non-string with explicit base");
+    }
+
+    #[test]
+    fn test_enumerate() {
+        assert::all_true(
+            r#"
+enumerate(["a", "b"], 5) == [(5, "a"), (6, "b")]
+enumerate(["a", "b"], start = 5) == [(5, "a"), (6, "b")]
+enumerate(range(3), -2147483647 - 1) == [(-2147483647 - 1, 0), (-2147483647, 1), (-2147483646, 2)]
+enumerate([], 10) == []
+enumerate(["a"], 2147483647) == [(2147483647, "a")]
+"#,
+        );
+        assert::fail("enumerate(['a', 'b'], 2147483647)", "overflow");
+    }
+
     #[test]
     fn test_hash() {
         assert::eq("0", "hash('')");
@@ -1118,4 +1342,121 @@ hash(foo)
             "doesn't match",
         );
     }
+
+    #[test]
+    fn test_type() {
+        assert::all_true(
+            r#"
+type([]) == "list"
+type({}) == "dict"
+type(None) == "NoneType"
+type("x") == "string"
+type(1) != "list"
+type([]) != "lis" + "u"
+type([]) == "li" + "st"
+{"list": 1}[type([])] == 1
+{type([]): 1}["li" + "st"] == 1
+hash(type("")) == hash("str" + "ing")
+"#,
+        );
+    }
+
+    #[test]
+    fn test_type_shared() {
+        let m = assert::pass_module(
+            r#"
+a = type([])
+b = type([1, 2])
+c = "list"
+"#,
+        );
+        let [a, b, c] = ["a", "b", "c"].map(|x| m.get(x).unwrap());
+        assert!(a.value().ptr_eq(b.value()));
+        // String literals naming a type are the same string `type()` returns.
+        assert!(a.value().ptr_eq(c.value()));
+    }
+
+    #[test]
+    fn test_sorted_stable() {
+        assert::pass(
+            r#"
+pairs = [(1, "a"), (0, "b"), (1, "c"), (0, "d"), (1, "e")]
+first = lambda x: x[0]
+assert_eq(sorted(pairs, key=first), [(0, "b"), (0, "d"), (1, "a"), (1, "c"), (1, "e")])
+assert_eq(sorted(pairs, key=first, reverse=True), [(1, "a"), (1, "c"), (1, "e"), (0, "b"), (0, "d")])
+
+words = ["bb", "a", "cc", "d", "ee"]
+assert_eq(sorted(words, key=len), ["a", "d", "bb", "cc", "ee"])
+assert_eq(sorted(words, key=len, reverse=True), ["bb", "cc", "ee", "a", "d"])
+assert_eq(sorted(words, key=lambda x: x[0] if len(x) == 1 else "z"), ["a", "d", "bb", "cc", "ee"])
+
+# Keys of mixed types take the general path, which must also be stable.
+mixed = [(1, "a"), (0.5, "b"), (1, "c"), (0.5, "d")]
+assert_eq(sorted(mixed, key=first, reverse=True), [(1, "a"), (1, "c"), (0.5, "b"), (0.5, "d")])
+"#,
+        );
+        assert::fail("sorted([1, 'a'])", "not supported");
+    }
+
+    #[test]
+    fn test_sorted_fast_paths() {
+        // The int and string fast paths must agree with the general comparison.
+        // See `benchmark_sorted_100k` in `benchmark/benchmark.py` for larger lists.
+        assert::pass(
+            r#"
+xs = []
+x = 1
+for _ in range(2000):
+    x = (x * 75 + 74) % 65537
+    xs.append(x)
+assert_eq(sorted(xs), [x[0] for x in sorted([(x,) for x in xs])])
+assert_eq(sorted(xs, reverse=True), [x[0] for x in sorted([(x,) for x in xs], reverse=True)])
+strs = [str(x) for x in xs[:500]]
+assert_eq(sorted(strs, key=len), sorted(strs, key=lambda x: (len(x),)))
+assert_eq(sorted(strs, reverse=True), [x[0] for x in sorted([(x,) for x in strs], reverse=True)])
+"#,
+        );
+    }
+
+    #[test]
+    fn test_min_max() {
+        assert::pass(
+            r#"
+assert_eq(max(["a", "ccc", "bb"], key=len), "ccc")
+assert_eq(min(["a", "ccc", "bb"], key=len), "a")
+assert_eq(max("b", "ccc", "aa", key=len), "ccc")
+assert_eq(min("b", "ccc", "aa", key=len), "b")
+# The first of equal elements is returned.
+assert_eq(max(["ab", "cd"], key=len), "ab")
+assert_eq(min(["ab", "cd"], key=len), "ab")
+assert_eq(max([], default=None), None)
+assert_eq(min([], key=len, default=7), 7)
+assert_eq(max([1, 3, 2], default=0), 3)
+calls = []
+def key(x):
+    calls.append(x)
+    return -x
+assert_eq(max([1, 2, 3, 4], key=key), 1)
+assert_eq(calls, [1, 2, 3, 4])
+"#,
+        );
+        assert::fail("max([])", "max() expect a non empty iterable");
+        assert::fail("min([], key=len)", "min() expect a non empty iterable");
+        assert::fail("max(1, 2, default=0)", "can't have a `default`");
+        assert::fails(
+            r#"
+def key(x):
+    return 1 // x
+max([2, 1, 0, 3], key=key)
+"#,
+            &[
+                "max() `key` function failed on element 2 (`0`)",
+                "Cannot divide by zero",
+            ],
+        );
+        assert::fails(
+            "min(['a', 1], key=len)",
+            &["min() `key` function failed on element 1 (`1`)"],
+        );
+    }
 }
@@ -0,0 +1,470 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! The `chain()` and `product()` functions, which combine iterables lazily.
+//!
+//! The results are iterable values which only iterate over their inputs when they
+//! are iterated themselves. While they are being iterated, every input is being iterated,
+//! so mutating an input fails, as it would when iterating the input directly.
+
+use std::fmt::{self, Display};
+
+use gazebo::{any::AnyLifetime, coerce::Coerce, prelude::*};
+use thiserror::Error;
+
+use crate as starlark;
+use crate::{
+    environment::GlobalsBuilder,
+    values::{Freeze, Heap, StarlarkValue, Trace, Value, ValueLike},
+};
+
+#[derive(Debug, Error)]
+enum ItertoolsError {
+    #[error("Length of `{0}` is too big for an int")]
+    TooLong(&'static str),
+    #[error("`repeat` must not be negative, got {0}")]
+    NegativeRepeat(i32),
+}
+
+/// Write `name(x, y, ...)`, with `suffix` before the closing bracket.
+fn display_call<'v, V: ValueLike<'v>>(
+    f: &mut fmt::Formatter<'_>,
+    name: &str,
+    args: &[V],
+    suffix: &str,
+) -> fmt::Result {
+    write!(f, "{}(", name)?;
+    for (i, x) in args.iter().enumerate() {
+        if i != 0 {
+            write!(f, ", ")?;
+        }
+        Display::fmt(&x.to_value(), f)?;
+    }
+    write!(f, "{})", suffix)
+}
+
+/// Start iterating all of `iterables`, so errors are reported before any element is produced
+/// and every input is locked against mutation until iteration finishes.
+fn iterate_all<'v, V: ValueLike<'v>>(
+    iterables: &[V],
+    heap: &'v Heap,
+) -> anyhow::Result<Vec<Box<dyn Iterator<Item = Value<'v>> + 'v>>> {
+    iterables.try_map(|x| x.to_value().iterate(heap))
+}
+
+/// The result of `chain(*iterables)`, which iterates over each of the iterables in turn.
+#[derive(Debug, Trace, Coerce, Freeze, NoSerialize, AnyLifetime)]
+#[repr(C)]
+pub(crate) struct ChainGen<V> {
+    iterables: Vec<V>,
+}
+
+starlark_complex_value!(pub(crate) Chain);
+
+impl<V> ChainGen<V> {
+    /// The result of calling `type()` on the result of `chain()`.
+    pub(crate) const TYPE: &'static str = "chain";
+}
+
+impl<'v, V: ValueLike<'v>> Display for ChainGen<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        display_call(f, "chain", &self.iterables, "")
+    }
+}
+
+impl<'v, V: ValueLike<'v>> StarlarkValue<'v> for ChainGen<V>
+where
+    Self: AnyLifetime<'v>,
+{
+    starlark_type!(Chain::TYPE);
+
+    fn iterate<'a>(
+        &'a self,
+        heap: &'v Heap,
+    ) -> anyhow::Result<Box<dyn Iterator<Item = Value<'v>> + 'a>>
+    where
+        'v: 'a,
+    {
+        Ok(box iterate_all(&self.iterables, heap)?.into_iter().flatten())
+    }
+
+    fn length(&self) -> anyhow::Result<i32> {
+        let mut len: i32 = 0;
+        for x in &self.iterables {
+            len = len
+                .checked_add(x.to_value().length()?)
+                .ok_or(ItertoolsError::TooLong(Chain::TYPE))?;
+        }
+        Ok(len)
+    }
+}
+
+/// The result of `product(*iterables, repeat = 1)`, which iterates over tuples
+/// of every combination of elements from the iterables, repeated `repeat` times.
+#[derive(Debug, Trace, Coerce, Freeze, NoSerialize, AnyLifetime)]
+#[repr(C)]
+pub(crate) struct ProductGen<V> {
+    iterables: Vec<V>,
+    repeat: i32,
+}
+
+starlark_complex_value!(pub(crate) Product);
+
+impl<V> ProductGen<V> {
+    /// The result of calling `type()` on the result of `product()`.
+    pub(crate) const TYPE: &'static str = "product";
+}
+
+impl<'v, V: ValueLike<'v>> Display for ProductGen<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let repeat = if self.repeat == 1 {
+            String::new()
+        } else if self.iterables.is_empty() {
+            format!("repeat = {}", self.repeat)
+        } else {
+            format!(", repeat = {}", self.repeat)
+        };
+        display_call(f, "product", &self.iterables, &repeat)
+    }
+}
+
+/// Iterator over the tuples of [`ProductGen`], counting through the indices like an odometer,
+/// so the last pool changes fastest.
+struct ProductIter<'v> {
+    pools: Vec<Vec<Value<'v>>>,
+    indices: Vec<usize>,
+    done: bool,
+    heap: &'v Heap,
+    /// The iterators over the inputs, which are exhausted, but kept until iteration finishes
+    /// so the inputs can't be mutated.
+    _inputs: Vec<Box<dyn Iterator<Item = Value<'v>> + 'v>>,
+}
+
+impl<'v> Iterator for ProductIter<'v> {
+    type Item = Value<'v>;
+
+    fn next(&mut self) -> Option<Value<'v>> {
+        if self.done {
+            return None;
+        }
+        let res = self.heap.alloc_tuple(
+            &self
+                .indices
+                .iter()
+                .zip(&self.pools)
+                .map(|(i, pool)| pool[*i])
+                .collect::<Vec<_>>(),
+        );
+        self.done = true;
+        for (i, pool) in self.indices.iter_mut().zip(&self.pools).rev() {
+            *i += 1;
+            if *i < pool.len() {
+                self.done = false;
+                break;
+            }
+            *i = 0;
+        }
+        Some(res)
+    }
+}
+
+impl<'v, V: ValueLike<'v>> StarlarkValue<'v> for ProductGen<V>
+where
+    Self: AnyLifetime<'v>,
+{
+    starlark_type!(Product::TYPE);
+
+    fn iterate<'a>(
+        &'a self,
+        heap: &'v Heap,
+    ) -> anyhow::Result<Box<dyn Iterator<Item = Value<'v>> + 'a>>
+    where
+        'v: 'a,
+    {
+        // Each element is used in many tuples, so the elements of the inputs are collected
+        // when iteration starts, and the tuples are produced as they are needed.
+        let mut inputs = iterate_all(&self.iterables, heap)?;
+        let pools: Vec<Vec<_>> = inputs.iter_mut().map(|it| it.collect()).collect();
+        let pools: Vec<_> = (0..self.repeat)
+            .flat_map(|_| pools.iter().cloned())
+            .collect();
+        Ok(box ProductIter {
+            indices: vec![0; pools.len()],
+            done: pools.iter().any(|pool| pool.is_empty()),
+            pools,
+            heap,
+            _inputs: inputs,
+        })
+    }
+
+    fn length(&self) -> anyhow::Result<i32> {
+        let mut len: i32 = 1;
+        for x in &self.iterables {
+            len = len
+                .checked_mul(x.to_value().length()?)
+                .ok_or(ItertoolsError::TooLong(Product::TYPE))?;
+        }
+        len.checked_pow(self.repeat as u32)
+            .ok_or_else(|| ItertoolsError::TooLong(Product::TYPE).into())
+    }
+}
+
+#[starlark_module]
+pub fn global(builder: &mut GlobalsBuilder) {
+    /// `chain(*iterables)` iterates over the elements of each of the iterables in turn.
+    ///
+    /// The result is iterated lazily: the iterables are only iterated when the result is,
+    /// so changes made to them before then are seen. `len()` of the result is the sum
+    /// of the lengths of the iterables.
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// xs = [1, 2]
+    /// c = chain(xs, (3,), range(4, 6))
+    /// xs.append(10)
+    /// list(c) == [1, 2, 10, 3, 4, 5]
+    /// # "#);
+    /// ```
+    #[starlark(type(Chain::TYPE))]
+    fn chain(args: Vec<Value>) -> anyhow::Result<Chain<'v>> {
+        Ok(Chain { iterables: args })
+    }
+
+    /// `product(*iterables, repeat = 1)` iterates over tuples of every combination
+    /// of one element from each of the iterables, in order, so the last iterable varies fastest.
+    /// With `repeat`, the iterables are repeated that many times, so `product(xs, repeat = 2)`
+    /// is the same as `product(xs, xs)`.
+    ///
+    /// The result is iterated lazily: the iterables are only iterated when the result is,
+    /// and each tuple is only created when it is needed. `len()` of the result is the product
+    /// of the lengths of the iterables.
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// list(product([1, 2], "ab".elems())) == [(1, "a"), (1, "b"), (2, "a"), (2, "b")]
+    /// # "#);
+    /// # starlark::assert::is_true(r#"
+    /// list(product([0, 1], repeat = 2)) == [(0, 0), (0, 1), (1, 0), (1, 1)]
+    /// # "#);
+    /// ```
+    #[starlark(type(Product::TYPE))]
+    fn product(args: Vec<Value>, repeat @ 1: i32) -> anyhow::Result<Product<'v>> {
+        if repeat < 0 {
+            return Err(ItertoolsError::NegativeRepeat(repeat).into());
+        }
+        Ok(Product {
+            iterables: args,
+            repeat,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use derive_more::Display;
+    use gazebo::any::AnyLifetime;
+
+    use crate as starlark;
+    use crate::{
+        assert::{self, Assert},
+        environment::GlobalsBuilder,
+        values::{Heap, StarlarkValue, Value, ValueLike},
+    };
+
+    /// An iterable of `0..len` which counts how many elements were taken from it.
+    #[derive(Debug, Display, AnyLifetime, NoSerialize)]
+    #[display(fmt = "counted")]
+    struct Counted {
+        len: i32,
+        taken: AtomicUsize,
+    }
+
+    starlark_simple_value!(Counted);
+
+    impl<'v> StarlarkValue<'v> for Counted {
+        starlark_type!("counted");
+
+        fn iterate<'a>(
+            &'a self,
+            _heap: &'v Heap,
+        ) -> anyhow::Result<Box<dyn Iterator<Item = Value<'v>> + 'a>>
+        where
+            'v: 'a,
+        {
+            Ok(box (0..self.len).map(move |i| {
+                self.taken.fetch_add(1, Ordering::SeqCst);
+                Value::new_int(i)
+            }))
+        }
+    }
+
+    #[starlark_module]
+    fn counted(builder: &mut GlobalsBuilder) {
+        fn counted(len: i32) -> anyhow::Result<Counted> {
+            Ok(Counted {
+                len,
+                taken: AtomicUsize::new(0),
+            })
+        }
+
+        fn taken(ref x: Value) -> anyhow::Result<i32> {
+            Ok(x.downcast_ref::<Counted>()
+                .unwrap()
+                .taken
+                .load(Ordering::SeqCst) as i32)
+        }
+    }
+
+    #[test]
+    fn test_chain() {
+        assert::pass(
+            r#"
+assert_eq(list(chain([1, 2], (3,), range(4, 6), "ab".elems())), [1, 2, 3, 4, 5, "a", "b"])
+assert_eq(list(chain()), [])
+assert_eq(list(chain({"x": 1, "y": 2})), ["x", "y"])
+assert_eq(len(chain([1, 2], (3,), range(4, 6))), 5)
+assert_eq(type(chain()), "chain")
+assert_eq(repr(chain([1], (2,))), "chain([1], (2,))")
+assert_eq([x * 2 for x in chain([1], [2])], [2, 4])
+"#,
+        );
+        assert::fail("list(chain([1], 2))", "not supported");
+        assert::fail("len(chain([1], 'ab'.elems()))", "not supported");
+    }
+
+    #[test]
+    fn test_product() {
+        assert::pass(
+            r#"
+assert_eq(list(product([1, 2], ["a", "b"])), [(1, "a"), (1, "b"), (2, "a"), (2, "b")])
+assert_eq(list(product([1, 2], [])), [])
+assert_eq(list(product()), [()])
+assert_eq(list(product([0, 1], repeat = 2)), [(0, 0), (0, 1), (1, 0), (1, 1)])
+assert_eq(list(product([0, 1], ["x"], repeat = 2)), [(0, "x", 0, "x"), (0, "x", 1, "x"), (1, "x", 0, "x"), (1, "x", 1, "x")])
+assert_eq(list(product([0, 1], repeat = 0)), [()])
+assert_eq(len(product([1, 2, 3], range(4), repeat = 2)), 144)
+assert_eq(len(product(repeat = 5)), 1)
+assert_eq(type(product()), "product")
+assert_eq(repr(product([1], repeat = 2)), "product([1], repeat = 2)")
+assert_eq(repr(product(repeat = 2)), "product(repeat = 2)")
+"#,
+        );
+        assert::fail("product([1], repeat = -1)", "must not be negative");
+        assert::fail("len(product(range(100000), range(100000)))", "too big");
+        assert::fail("len(product(range(2), repeat = 40))", "too big");
+    }
+
+    #[test]
+    fn test_lazy() {
+        let mut a = Assert::new();
+        a.globals_add(counted);
+        a.pass(
+            r#"
+def first(xs):
+    for x in xs:
+        return x
+
+c = counted(5)
+ch = chain(c, [10])
+assert_eq(taken(c), 0)
+assert_eq(first(ch), 0)
+assert_eq(taken(c), 1)
+assert_eq(list(ch), [0, 1, 2, 3, 4, 10])
+assert_eq(taken(c), 6)
+
+c = counted(3)
+p = product(c, repeat = 2)
+assert_eq(taken(c), 0)
+assert_eq(first(p), (0, 0))
+assert_eq(taken(c), 3)
+
+# Changes to the inputs made before iterating are seen.
+xs = [1]
+ch = chain(xs)
+p = product(xs)
+xs.append(2)
+assert_eq(list(ch), [1, 2])
+assert_eq(list(p), [(1,), (2,)])
+"#,
+        );
+    }
+
+    #[test]
+    fn test_mutate_during_iteration() {
+        assert::fail(
+            r#"
+def f():
+    xs = [1, 2]
+    for x in chain([0], xs):
+        xs.append(x)
+f()
+"#,
+            "mutate an iterable",
+        );
+        assert::fail(
+            r#"
+def f():
+    xs = {1: 2}
+    for x in chain(xs):
+        xs[3] = 4
+f()
+"#,
+            "mutate an iterable",
+        );
+        // The inputs of a product are collected when iteration starts,
+        // but still can't be mutated until it finishes.
+        assert::fail(
+            r#"
+def f():
+    xs = [1, 2]
+    for x in product(xs, repeat = 2):
+        xs.append(x)
+f()
+"#,
+            "mutate an iterable",
+        );
+        assert::pass(
+            r#"
+xs = [1, 2]
+for x in list(chain(xs)):
+    xs.append(x)
+assert_eq(xs, [1, 2, 1, 2])
+"#,
+        );
+    }
+
+    #[test]
+    fn test_frozen() {
+        let mut a = Assert::new();
+        a.module(
+            "m",
+            r#"
+xs = [1, 2]
+ch = chain(xs, [3])
+p = product(xs, repeat = 2)
+"#,
+        );
+        a.is_true(
+            r#"
+load("m", "ch", "p")
+list(ch) == [1, 2, 3] and len(ch) == 3 and list(p) == [(1, 1), (1, 2), (2, 1), (2, 2)]
+"#,
+        );
+    }
+}
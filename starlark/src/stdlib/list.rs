@@ -101,15 +101,7 @@ pub(crate) fn list_methods(builder: &mut MethodsBuilder) {
     /// ```
     fn extend(this: Value, ref other: Value) -> anyhow::Result<NoneType> {
         let res = List::from_value_mut(this)?.unwrap();
-        if this.ptr_eq(other) {
-            // If the types alias, we can't borrow the `other` for iteration.
-            // But we can do something smarter to double the elements
-            res.double(heap);
-        } else {
-            other.with_iterator(heap, |it| {
-                res.extend(it, heap);
-            })?;
-        }
+        res.extend_from_value(this, other, heap)?;
         Ok(NoneType)
     }
 
@@ -157,7 +149,7 @@ pub(crate) fn list_methods(builder: &mut MethodsBuilder) {
                 }
             }
         }
-        Err(anyhow!("Element '{}' not found in '{}'", needle, this))
+        Err(anyhow!("Element {} not found in list", needle.to_repr()))
     }
 
     /// [list.insert](
@@ -272,7 +264,7 @@ pub(crate) fn list_methods(builder: &mut MethodsBuilder) {
             match position {
                 Some(i) => i,
                 None => {
-                    return Err(anyhow!("Element '{}' not found in list '{}'", needle, this));
+                    return Err(anyhow!("Element {} not found in list", needle.to_repr()));
                 }
             }
         };
@@ -301,6 +293,23 @@ mod tests {
     fn test_index() {
         // Should fail, but should not panic.
         assert::fail("[True].index(True, 1, 0)", "not found");
+        assert::all_true(
+            r#"
+[1, 2, 3, 1].index(1, 0, 1) == 0
+[1, 2, 3, 1].index(1, 1) == 3
+[1, 2, 3, 1].index(1, 3, 4) == 3
+[1, 2, 3, 1].index(1, 3, 100) == 3
+[1, 2, 3, 1].index(1, -1) == 3
+[1, 2, 3, 1].index(1, -100) == 0
+[1, 2, 3, 1].index(3, -100, -1) == 2
+[1, 2, 3, 1].index(2, None, None) == 1
+"#,
+        );
+        assert::fail("[1, 2, 3, 1].index(1, 1, 3)", "not found");
+        assert::fail("[1, 2, 3, 1].index(1, -2, -1)", "not found");
+        assert::fail("[1, 2, 3, 1].index(1, 4)", "not found");
+        assert::fail("[\"a\"].index(\"b\")", "Element \"b\" not found in list");
+        assert::fail("[1].remove([2])", "Element [2] not found in list");
     }
 
     #[test]
@@ -21,15 +21,22 @@
 use crate::environment::GlobalsBuilder;
 
 pub(crate) mod breakpoint;
+pub(crate) mod bytes;
 pub(crate) mod dict;
 pub(crate) mod enumeration;
 pub(crate) mod extra;
+pub(crate) mod float;
 mod funcs;
 use gazebo::prelude::*;
+pub(crate) mod itertools;
 pub(crate) mod list;
+pub(crate) mod promise;
 pub(crate) mod record;
+pub(crate) mod set;
 pub(crate) mod string;
 pub(crate) mod structs;
+mod time_rand;
+pub(crate) mod tuple;
 pub(crate) mod util;
 
 pub use extra::PrintHandler;
@@ -43,8 +50,18 @@ pub(crate) fn standard_environment() -> GlobalsBuilder {
     GlobalsBuilder::new().with(funcs::global_functions)
 }
 
+/// Add the functions `now_millis()`, `monotonic_micros()` and `random_seeded(seed)`, whose
+/// result has methods `next_int(bound)` and `shuffle(list)`.
+///
+/// These make evaluation depend on when it happens, so are not a [`LibraryExtension`]
+/// and are never part of [`Globals::extended`](crate::environment::Globals::extended).
+/// Add them explicitly, e.g. `GlobalsBuilder::extended().with(experimental_time_rand)`.
+pub fn experimental_time_rand(builder: &mut GlobalsBuilder) {
+    time_rand::global(builder)
+}
+
 /// The extra library definitions available in this Starlark implementation, but not in the standard.
-#[derive(PartialEq, Eq, Copy, Clone, Dupe)]
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Dupe)]
 pub enum LibraryExtension {
     /// Definitions to support the `struct` type, the `struct()` constructor.
     StructType,
@@ -75,6 +92,19 @@ pub enum LibraryExtension {
     Json,
     /// Add a function `abs()` which will take the absolute value of an int.
     Abs,
+    /// Add a function `signature(f)` which describes the parameters of a function.
+    Signature,
+    /// Definitions to support the `set` type, the `set()` constructor.
+    SetType,
+    /// Add functions `floor(x)`, `ceil(x)` and `round(x, ndigits)` for rounding numbers.
+    Math,
+    /// Definitions to support the `bytes` type, the `bytes()` constructor.
+    Bytes,
+    /// Definitions to support the `promise` type, the `promise()` constructor.
+    Promise,
+    /// Add functions `chain(*iterables)` and `product(*iterables, repeat = 1)`,
+    /// which combine iterables lazily.
+    Itertools,
     // Make sure if you add anything new, you add it to `all` below.
 }
 
@@ -84,13 +114,14 @@ impl LibraryExtension {
         use LibraryExtension::*;
         &[
             StructType, RecordType, EnumType, Map, Filter, Partial, Dedupe, Debug, Print, Pprint,
-            Breakpoint, Json, Abs,
+            Breakpoint, Json, Abs, Signature, SetType, Math, Bytes, Promise, Itertools,
         ]
     }
 
     /// Add a specific extension to a [`GlobalsBuilder`].
     pub fn add(self, builder: &mut GlobalsBuilder) {
         use LibraryExtension::*;
+        builder.add_extension(self);
         match self {
             StructType => structs::global(builder),
             RecordType => record::global(builder),
@@ -105,6 +136,12 @@ impl LibraryExtension {
             Breakpoint => breakpoint::global(builder),
             Json => extra::json(builder),
             Abs => extra::abs(builder),
+            Signature => extra::signature(builder),
+            SetType => set::global(builder),
+            Math => extra::math(builder),
+            Bytes => bytes::global(builder),
+            Promise => promise::global(builder),
+            Itertools => itertools::global(builder),
         }
     }
 }
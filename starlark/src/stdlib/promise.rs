@@ -0,0 +1,61 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! The `promise()` constructor and methods for the `promise` type.
+
+use crate as starlark;
+use crate::{
+    environment::{GlobalsBuilder, MethodsBuilder},
+    values::{none::NoneType, promise::Promise, Value},
+};
+
+#[starlark_module]
+pub fn global(builder: &mut GlobalsBuilder) {
+    /// Create a promise, a placeholder for a value which is only known later in evaluation.
+    /// Set it once with `p.set(x)` and read it with `p.get()`.
+    ///
+    /// When the module is frozen, each promise is replaced by the value it was set to,
+    /// so modules loading this one see the value itself. It is an error to freeze
+    /// a module which refers to a promise which was never set.
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// p = promise()
+    /// rules = [p]
+    /// p.set(1)
+    /// rules[0].get() == 1
+    /// # "#);
+    /// ```
+    #[starlark(type(Promise::TYPE))]
+    fn promise() -> anyhow::Result<Promise<'v>> {
+        Ok(Promise::new(eval.call_stack_top_location()))
+    }
+}
+
+#[starlark_module]
+pub(crate) fn promise_methods(builder: &mut MethodsBuilder) {
+    /// `p.get()` returns the value the promise was set to, failing if it has not been set.
+    fn get(this: Value) -> anyhow::Result<Value<'v>> {
+        Promise::from_value(this).unwrap().get()
+    }
+
+    /// `p.set(x)` sets the promise to `x`, failing if it has already been set.
+    fn set(this: Value, ref value: Value) -> anyhow::Result<NoneType> {
+        Promise::from_value(this).unwrap().set(value)?;
+        Ok(NoneType)
+    }
+}
@@ -31,6 +31,18 @@ use crate::{
 
 #[starlark_module]
 pub fn global(builder: &mut GlobalsBuilder) {
+    /// Creates a record type, whose keyword arguments are the names and types of the fields.
+    /// Calling the type checks every field is given, with a value of the right type.
+    ///
+    /// Examples:
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// Rule = record(name=str.type, deps=field([str.type], []))
+    /// rule = Rule(name="a", deps=["b"])
+    /// rule.deps == ["b"] and Rule(name="c").deps == []
+    /// # "#);
+    /// ```
     fn record(kwargs: SmallMap<String, Value>) -> anyhow::Result<RecordType<'v>> {
         // Every Value must either be a field or a value (the type)
         let mut mp = SmallMap::with_capacity(kwargs.len());
@@ -69,7 +81,10 @@ pub fn global(builder: &mut GlobalsBuilder) {
 
 #[cfg(test)]
 mod tests {
-    use crate::assert::{self, Assert};
+    use crate::{
+        assert::{self, Assert},
+        values::{docs, docs::DocItem},
+    };
 
     #[test]
     fn test_record() {
@@ -179,6 +194,107 @@ assert_ne(r1(host="test"), diff(host="test"))
         );
     }
 
+    #[test]
+    fn test_record_type_errors() {
+        assert::fails(
+            r#"
+IpAddress = record(host=str.type, port=field(int.type, 80))
+IpAddress(host="localhost", port="80")
+"#,
+            &[
+                "`80`",
+                "of type `string`",
+                "`int`",
+                "field `port` of record `IpAddress`",
+            ],
+        );
+        assert::fails(
+            r#"
+Rule = record(name=str.type, deps=[str.type])
+Rule(name="a", deps=["b", 3])
+"#,
+            &["field `deps` of record `Rule`", "`[\"b\", 3]`"],
+        );
+    }
+
+    #[test]
+    fn test_record_hash() {
+        assert::pass(
+            r#"
+IpAddress = record(host=str.type, port=[int.type, None])
+d = {IpAddress(host="a", port=1): 1, IpAddress(host="b", port=None): 2}
+assert_eq(d[IpAddress(host="a", port=1)], 1)
+assert_eq(d[IpAddress(host="b", port=None)], 2)
+assert_eq(IpAddress(host="a", port=3) in d, False)
+assert_eq(repr(IpAddress(host="a", port=1)), 'record(host="a", port=1)')
+"#,
+        );
+        assert::fail(
+            r#"
+Rule = record(name=str.type, deps=[str.type])
+{Rule(name="a", deps=[]): 1}
+"#,
+            "not hashable",
+        );
+    }
+
+    #[test]
+    fn test_record_freeze() {
+        let mut a = Assert::new();
+        a.module(
+            "m",
+            r#"
+IpAddress = record(host=str.type, port=[int.type, None])
+home = IpAddress(host="localhost", port=None)
+ports = {home: "home"}
+"#,
+        );
+        a.pass(
+            r#"
+load('m', 'IpAddress', 'home', 'ports')
+assert_eq(ports[IpAddress(host="localhost", port=None)], "home")
+assert_eq(home, IpAddress(host="localhost", port=None))
+assert_eq(home.host, "localhost")
+assert_eq(IpAddress(host="x", port=1).port, 1)
+"#,
+        );
+        a.fail(
+            r#"
+load('m', 'IpAddress')
+IpAddress(host="x", port="y")
+"#,
+            "field `port` of record `IpAddress`",
+        );
+    }
+
+    #[test]
+    fn test_record_documentation() {
+        let m = assert::pass_module(
+            r#"
+IpAddress = record(host=str.type, port=field(int.type, 80))
+"#,
+        );
+        let docs = match m.get("IpAddress").unwrap().value().documentation() {
+            Some(DocItem::Object(docs)) => docs,
+            x => panic!("Expected an object, got {:?}", x),
+        };
+        let fields = docs
+            .members
+            .into_iter()
+            .map(|(name, member)| match member {
+                docs::Member::Property(p) => (name, p.typ.unwrap().raw_type),
+                x => panic!("Expected a property, got {:?}", x),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            fields,
+            vec![
+                ("host".to_owned(), "\"string\"".to_owned()),
+                ("port".to_owned(), "\"int\"".to_owned()),
+            ]
+        );
+    }
+
     #[test]
     fn test_field_invalid() {
         assert::fails(
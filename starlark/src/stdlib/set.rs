@@ -0,0 +1,178 @@
+/*
+ * Copyright 2018 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! The `set()` constructor and methods for the `set` type.
+
+use std::mem;
+
+use anyhow::anyhow;
+
+use crate as starlark;
+use crate::{
+    collections::SmallMap,
+    environment::{GlobalsBuilder, MethodsBuilder},
+    values::{
+        none::NoneType,
+        set::{Set, SetRef},
+        Value,
+    },
+};
+
+#[starlark_module]
+pub fn global(builder: &mut GlobalsBuilder) {
+    /// Create a new set from the elements of an iterable, or an empty set.
+    ///
+    /// ```
+    /// # starlark::assert::all_true(r#"
+    /// len(set()) == 0
+    /// list(set([1, 2, 1])) == [1, 2]
+    /// # "#);
+    /// ```
+    #[starlark(type(Set::TYPE))]
+    #[starlark(speculative_exec_safe)]
+    fn set(ref a: Option<Value>) -> anyhow::Result<Set<'v>> {
+        match a {
+            Some(a) => Set::from_iterable(a, heap),
+            None => Ok(Set::default()),
+        }
+    }
+}
+
+#[starlark_module]
+pub(crate) fn set_methods(registry: &mut MethodsBuilder) {
+    /// `S.add(x)` adds `x` to the set, doing nothing if it is already present.
+    ///
+    /// It fails if `x` is not hashable, or if the set is frozen or has active iterators.
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// x = set([1])
+    /// x.add(2)
+    /// x.add(1)
+    /// list(x) == [1, 2]
+    /// # "#);
+    /// ```
+    fn add(this: Value, ref value: Value) -> anyhow::Result<NoneType> {
+        let value = value.get_hashed()?;
+        heap.charge_growth(this, || {
+            Set::from_value_mut(this)?.unwrap().insert_hashed(value);
+            Ok(NoneType)
+        })
+    }
+
+    /// `S.remove(x)` removes `x` from the set, failing if it is not present.
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// x = set([1, 2])
+    /// x.remove(1)
+    /// list(x) == [2]
+    /// # "#);
+    /// ```
+    ///
+    /// ```
+    /// # starlark::assert::fail(r#"
+    /// set([1]).remove(2)   # error: not found
+    /// # "#, "not found");
+    /// ```
+    fn remove(this: Value, ref value: Value) -> anyhow::Result<NoneType> {
+        let value = value.get_hashed()?;
+        let mut me = Set::from_value_mut(this)?.unwrap();
+        if me.remove_hashed(value) {
+            Ok(NoneType)
+        } else {
+            mem::drop(me);
+            Err(anyhow!(
+                "Value `{}` not found in set `{}`",
+                value.key().to_repr(),
+                this.to_repr()
+            ))
+        }
+    }
+
+    /// `S.union(xs)` returns a new set with the elements of `S` followed by
+    /// those of the iterable `xs`.
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// list(set([1, 2]).union([2, 3])) == [1, 2, 3]
+    /// # "#);
+    /// ```
+    #[starlark(speculative_exec_safe)]
+    fn union(this: SetRef, ref other: Value) -> anyhow::Result<Set<'v>> {
+        let mut res = (*this).clone();
+        other.with_iterator(heap, |it| {
+            for x in it {
+                res.insert_hashed(x.get_hashed()?);
+            }
+            anyhow::Ok(())
+        })??;
+        Ok(res)
+    }
+
+    /// `S.intersection(xs)` returns a new set with the elements of `S`
+    /// which are also in the iterable `xs`, in the order of `S`.
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// list(set([1, 2, 3]).intersection([3, 2])) == [2, 3]
+    /// # "#);
+    /// ```
+    #[starlark(speculative_exec_safe)]
+    fn intersection(this: SetRef, ref other: Value) -> anyhow::Result<Set<'v>> {
+        let other = Set::from_iterable(other, heap)?;
+        let content: SmallMap<Value, ()> = this
+            .iter_hashed()
+            .filter(|x| other.contains_hashed(*x))
+            .map(|x| (x, ()))
+            .collect();
+        Ok(Set::new(content))
+    }
+
+    /// `S.difference(xs)` returns a new set with the elements of `S`
+    /// which are not in the iterable `xs`.
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// list(set([1, 2, 3]).difference([2])) == [1, 3]
+    /// # "#);
+    /// ```
+    #[starlark(speculative_exec_safe)]
+    fn difference(this: SetRef, ref other: Value) -> anyhow::Result<Set<'v>> {
+        let other = Set::from_iterable(other, heap)?;
+        let content: SmallMap<Value, ()> = this
+            .iter_hashed()
+            .filter(|x| !other.contains_hashed(*x))
+            .map(|x| (x, ()))
+            .collect();
+        Ok(Set::new(content))
+    }
+
+    /// `S.issubset(xs)` returns `True` if every element of `S` is in the iterable `xs`.
+    ///
+    /// ```
+    /// # starlark::assert::all_true(r#"
+    /// set([1, 2]).issubset([3, 2, 1])
+    /// not set([1, 4]).issubset([1, 2])
+    /// # "#);
+    /// ```
+    #[starlark(speculative_exec_safe)]
+    fn issubset(this: SetRef, ref other: Value) -> anyhow::Result<bool> {
+        let other = Set::from_iterable(other, heap)?;
+        Ok(this.iter_hashed().all(|x| other.contains_hashed(x)))
+    }
+}
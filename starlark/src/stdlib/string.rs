@@ -26,14 +26,19 @@ use crate as starlark;
 use crate::{
     environment::MethodsBuilder,
     eval::Arguments,
-    stdlib::string::fast_string::convert_str_indices,
+    stdlib::{
+        bytes::{check_bytes_enabled, check_encoding},
+        string::fast_string::convert_str_indices,
+    },
     values::{
+        bytes::StarlarkBytes,
         none::NoneOr,
         string::{fast_string, interpolation},
         tuple::Tuple,
         types::string::{
             fast_string::StrIndices,
             iter::{iterate_chars, iterate_codepoints},
+            repr::string_repr,
         },
         StringValue, UnpackValue, Value, ValueOf,
     },
@@ -246,6 +251,29 @@ pub(crate) fn string_methods(builder: &mut MethodsBuilder) {
         }
     }
 
+    /// [string.encode](
+    /// https://docs.python.org/3/library/stdtypes.html#str.encode
+    /// ): the bytes of the string. _Not part of standard Starlark._
+    ///
+    /// `S.encode(encoding = "utf-8")` returns the UTF-8 encoding of `S` as `bytes`.
+    /// UTF-8 is the only encoding supported. `bytes.decode` reverses it.
+    /// Fails unless the `bytes` type is enabled, with
+    /// [`LibraryExtension::Bytes`](crate::environment::LibraryExtension::Bytes).
+    ///
+    /// Examples:
+    ///
+    /// ```
+    /// # starlark::assert::all_true(r#"
+    /// len("ñ".encode()) == 2
+    /// "hi".encode("utf-8").decode() == "hi"
+    /// # "#);
+    /// ```
+    fn encode(this: &str, ref encoding: Option<&str>) -> anyhow::Result<StarlarkBytes> {
+        check_bytes_enabled(eval)?;
+        check_encoding(encoding)?;
+        Ok(StarlarkBytes::new(this.as_bytes()))
+    }
+
     /// [string.endswith](
     /// https://github.com/google/skylark/blob/3705afa472e466b8b061cce44b47c9ddc6db696d/doc/spec.md#string·endswith
     /// ): determine if a string ends with a given suffix.
@@ -268,6 +296,31 @@ pub(crate) fn string_methods(builder: &mut MethodsBuilder) {
         }
     }
 
+    /// string.escape: escape a string for use in a string literal.
+    /// _Not part of standard Starlark._
+    ///
+    /// `S.escape()` returns `repr(S)` without the surrounding double quotes,
+    /// so `'"' + S.escape() + '"'` is a Starlark string literal which parses
+    /// back to S. Backslashes, double quotes, control characters and
+    /// non-printable characters are escaped, other characters are kept as is.
+    ///
+    /// Examples:
+    ///
+    /// ```
+    /// # starlark::assert::all_true(r#"
+    /// "plain".escape() == "plain"
+    /// "a\"b\\c\n".escape() == 'a\\"b\\\\c\\n'
+    /// "'日本'\t".escape() == "'日本'\\t"
+    /// '"' + "x\x00y".escape() + '"' == repr("x\x00y")
+    /// # "#);
+    /// ```
+    #[starlark(speculative_exec_safe)]
+    fn escape(this: &str) -> anyhow::Result<String> {
+        let mut res = String::new();
+        string_repr(this, &mut res);
+        Ok(res[1..res.len() - 1].to_owned())
+    }
+
     /// [string.find](
     /// https://github.com/google/skylark/blob/3705afa472e466b8b061cce44b47c9ddc6db696d/doc/spec.md#string·find
     /// ): find a substring in a string.
@@ -337,6 +390,9 @@ pub(crate) fn string_methods(builder: &mut MethodsBuilder) {
     /// If all the numeric field names form the sequence 0, 1, 2, and so on,
     /// they may be omitted and those values will be implied; however,
     /// the explicit and implicit forms may not be mixed.
+    /// The field name may be followed by any number of attribute accesses
+    /// `.name` and indexes `[index]`, where an index of only digits is an
+    /// int and any other index is a string, e.g. `{0[1].name}` or `{x[key]}`.
     ///
     /// The *conversion* specifies how to convert an argument value `x` to a
     /// string. It may be either `!r`, which converts the value using
@@ -345,7 +401,7 @@ pub(crate) fn string_methods(builder: &mut MethodsBuilder) {
     ///
     /// The *format specifier*, after a colon, specifies field width,
     /// alignment, padding, and numeric precision.
-    /// Currently it must be empty, but it is reserved for future use.
+    /// Format specifiers are not supported, and are reported as an error.
     ///
     /// Examples:
     ///
@@ -356,6 +412,7 @@ pub(crate) fn string_methods(builder: &mut MethodsBuilder) {
     /// "a{}b{}c".format(1, 2) == "a1b2c"
     /// "({1}, {0})".format("zero", "one") == "(one, zero)"
     /// "Is {0!r} {0!s}?".format("heterological") == "Is \"heterological\" heterological?"
+    /// "{0[1]}, {p.x}".format(("a", "b"), p=struct(x=1)) == "b, 1"
     /// # "#);
     /// ```
     #[starlark(speculative_exec_safe)]
@@ -636,8 +693,8 @@ pub(crate) fn string_methods(builder: &mut MethodsBuilder) {
     /// # "#);
     /// ```
     #[starlark(speculative_exec_safe)]
-    fn lower(this: &str) -> anyhow::Result<String> {
-        Ok(this.to_lowercase())
+    fn lower(this: &str) -> anyhow::Result<StringValue<'v>> {
+        Ok(heap.alloc_str_lowered(this))
     }
 
     /// [string.join](
@@ -1069,10 +1126,11 @@ pub(crate) fn string_methods(builder: &mut MethodsBuilder) {
     /// "one\n\ntwo".splitlines() == ["one", "", "two"]
     /// "one\n\ntwo".splitlines(True) == ["one\n", "\n", "two"]
     /// "a\nb".splitlines() == ["a", "b"]
+    /// "a\r\nb\r".splitlines(keepends = True) == ["a\r\n", "b\r"]
     /// # "#);
     /// ```
     #[starlark(speculative_exec_safe)]
-    fn splitlines(this: &str, ref keepends @ false: bool) -> anyhow::Result<Value<'v>> {
+    fn splitlines(this: &str, keepends @ false: bool) -> anyhow::Result<Value<'v>> {
         let mut s = this;
         let mut lines = Vec::new();
         loop {
@@ -1207,8 +1265,8 @@ pub(crate) fn string_methods(builder: &mut MethodsBuilder) {
     /// # "#);
     /// ```
     #[starlark(speculative_exec_safe)]
-    fn upper(this: &str) -> anyhow::Result<String> {
-        Ok(this.to_uppercase())
+    fn upper(this: &str) -> anyhow::Result<StringValue<'v>> {
+        Ok(heap.alloc_str_uppered(this))
     }
 
     /// [string.removeprefix](
@@ -1216,7 +1274,7 @@ pub(crate) fn string_methods(builder: &mut MethodsBuilder) {
     /// ): remove a prefix from a string. _Not part of standard Starlark._
     ///
     /// If the string starts with the prefix string, return `string[len(prefix):]`.
-    /// Otherwise, return a copy of the original string:
+    /// Otherwise, including when the prefix is empty, return the original string:
     ///
     /// Examples:
     ///
@@ -1225,6 +1283,7 @@ pub(crate) fn string_methods(builder: &mut MethodsBuilder) {
     /// "Hello, World!".removeprefix("Hello") == ", World!"
     /// "Hello, World!".removeprefix("Goodbye") == "Hello, World!"
     /// "Hello".removeprefix("Hello") == ""
+    /// "Hello".removeprefix("") == "Hello"
     /// # "#);
     /// ```
     #[starlark(speculative_exec_safe)]
@@ -1239,10 +1298,10 @@ pub(crate) fn string_methods(builder: &mut MethodsBuilder) {
 
     /// [string.removesuffix](
     /// https://docs.python.org/3.9/library/stdtypes.html#str.removesuffix
-    /// ): remove a prefix from a string. _Not part of standard Starlark._
+    /// ): remove a suffix from a string. _Not part of standard Starlark._
     ///
-    /// If the string starts with the prefix string, return `string[len(prefix):]`.
-    /// Otherwise, return a copy of the original string:
+    /// If the string ends with the suffix string, return `string[:-len(suffix)]`.
+    /// Otherwise, including when the suffix is empty, return the original string:
     ///
     /// Examples:
     ///
@@ -1251,6 +1310,7 @@ pub(crate) fn string_methods(builder: &mut MethodsBuilder) {
     /// "Hello, World!".removesuffix("World!") == "Hello, "
     /// "Hello, World!".removesuffix("World") == "Hello, World!"
     /// "Hello".removesuffix("Hello") == ""
+    /// "Hello".removesuffix("") == "Hello"
     /// # "#);
     /// ```
     #[starlark(speculative_exec_safe)]
@@ -1266,7 +1326,13 @@ pub(crate) fn string_methods(builder: &mut MethodsBuilder) {
 
 #[cfg(test)]
 mod tests {
-    use crate::assert;
+    use crate::{
+        assert,
+        values::{
+            docs::{DocItem, Member, Param},
+            string::str_methods,
+        },
+    };
 
     #[test]
     fn test_error_codes() {
@@ -1290,4 +1356,67 @@ mod tests {
         assert::is_true("type('foo'.elems()) != type([])");
         assert::is_true("type('foo'.codepoints()) != type([])");
     }
+
+    #[test]
+    fn test_remove_affix_unicode() {
+        assert::all_true(
+            r#"
+"ñandú".removeprefix("ñ") == "andú"
+"ñandú".removesuffix("ú") == "ñand"
+"日本語".removeprefix("日本") == "語"
+"日本語".removesuffix("本語") == "日"
+"é".removeprefix("e") == "é"
+"".removeprefix("") == ""
+"#,
+        );
+    }
+
+    #[test]
+    fn test_remove_affix_unchanged_is_same_value() {
+        let m = assert::pass_module(
+            r#"
+x = "prefix and suffix"
+a = x.removeprefix("suffix")
+b = x.removesuffix("prefix")
+c = x.removeprefix("")
+"#,
+        );
+        let x = m.get("x").unwrap();
+        for name in ["a", "b", "c"] {
+            assert!(x.value().ptr_eq(m.get(name).unwrap().value()), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_splitlines_keepends() {
+        assert::all_true(
+            r#"
+"a\nb\r\nc\rd".splitlines(keepends = True) == ["a\n", "b\r\n", "c\r", "d"]
+"a\nb\n".splitlines(keepends = False) == ["a", "b"]
+"#,
+        );
+    }
+
+    #[test]
+    fn test_string_methods_documentation() {
+        let docs = match str_methods().unwrap().documentation() {
+            DocItem::Object(x) => x,
+            _ => panic!("Expected object documentation for string methods"),
+        };
+        let function = |name: &str| {
+            docs.members
+                .iter()
+                .find_map(|(n, x)| match x {
+                    Member::Function(x) if n == name => Some(x),
+                    _ => None,
+                })
+                .unwrap_or_else(|| panic!("No documentation for `{}`", name))
+        };
+        assert!(function("removeprefix").docs.is_some());
+        assert!(function("removesuffix").docs.is_some());
+        assert!(function("splitlines").params.iter().any(|x| match x {
+            Param::Arg { name, .. } => name == "keepends",
+            _ => false,
+        }));
+    }
 }
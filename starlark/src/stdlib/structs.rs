@@ -16,11 +16,13 @@
  */
 
 //! Implementation of `struct` function.
+use gazebo::coerce::coerce;
+
 use crate as starlark;
 use crate::{
     environment::{GlobalsBuilder, MethodsBuilder},
     eval::Arguments,
-    values::{structs::Struct, Value},
+    values::{dict::Dict, structs::Struct, Value},
 };
 
 #[starlark_module]
@@ -38,4 +40,17 @@ pub(crate) fn struct_methods(builder: &mut MethodsBuilder) {
     fn to_json(this: Value) -> anyhow::Result<String> {
         this.to_json()
     }
+
+    /// Convert the struct to a dictionary from field name to value,
+    /// with the fields in the order they were defined.
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// struct(b = 1, a = 2).to_dict() == {"b": 1, "a": 2}
+    /// # "#);
+    /// ```
+    #[starlark(speculative_exec_safe)]
+    fn to_dict(this: &Struct<'v>) -> anyhow::Result<Dict<'v>> {
+        Ok(Dict::new(coerce(this.fields.clone())))
+    }
 }
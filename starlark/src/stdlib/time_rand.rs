@@ -0,0 +1,243 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Clock and pseudo-random number functions, which are deliberately not part of
+//! [`LibraryExtension`](crate::environment::LibraryExtension), since they make
+//! evaluation non-deterministic.
+
+use std::{
+    cell::Cell,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::anyhow;
+use derive_more::Display;
+use gazebo::any::AnyLifetime;
+use once_cell::sync::Lazy;
+
+use crate as starlark;
+use crate::{
+    environment::{GlobalsBuilder, Methods, MethodsBuilder, MethodsStatic},
+    values::{
+        list::{List, ListLike},
+        none::NoneType,
+        AllocValue, Freeze, Freezer, Heap, NoSerialize, StarlarkValue, Trace, Value, ValueError,
+        ValueLike,
+    },
+};
+
+// The point `monotonic_micros` counts from.
+static START: Lazy<Instant> = Lazy::new(Instant::now);
+
+#[starlark_module]
+pub(crate) fn global(builder: &mut GlobalsBuilder) {
+    /// The number of milliseconds since the Unix epoch, as a float since it doesn't fit in an `int`.
+    fn now_millis() -> anyhow::Result<f64> {
+        Ok(SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as f64)
+    }
+
+    /// The number of microseconds since an arbitrary point fixed for the life of the process,
+    /// as a float. Unlike `now_millis`, never goes backwards.
+    fn monotonic_micros() -> anyhow::Result<f64> {
+        Ok(START.elapsed().as_micros() as f64)
+    }
+
+    /// A pseudo-random number generator which produces the same sequence for the same `seed`,
+    /// on every platform and in every version.
+    fn random_seeded(seed: i32) -> anyhow::Result<Random> {
+        Ok(Random {
+            state: Cell::new(seed as u64),
+        })
+    }
+}
+
+#[starlark_module]
+fn random_methods(builder: &mut MethodsBuilder) {
+    /// `r.next_int(bound)` returns an int in the range `0` to `bound - 1` inclusive,
+    /// which must be positive.
+    fn next_int(this: Value, bound: i32) -> anyhow::Result<i32> {
+        let this = Random::from_value_mut(this)?;
+        if bound <= 0 {
+            return Err(anyhow!("next_int() bound must be positive, got {}", bound));
+        }
+        Ok(this.below(bound as u64) as i32)
+    }
+
+    /// `r.shuffle(xs)` randomly reorders the list `xs` in place, and returns `None`.
+    ///
+    /// It fails if `xs` is frozen or has active iterators.
+    fn shuffle(this: Value, ref xs: Value) -> anyhow::Result<NoneType> {
+        let this = Random::from_value_mut(this)?;
+        let list = match List::from_value_mut(xs)? {
+            Some(list) => list,
+            None => return Err(anyhow!("shuffle() expects a list, got `{}`", xs.get_type())),
+        };
+        // Fisher-Yates.
+        let mut content = list.content().to_vec();
+        for i in (1..content.len()).rev() {
+            let j = this.below(i as u64 + 1) as usize;
+            content.swap(i, j);
+        }
+        for (i, x) in content.into_iter().enumerate() {
+            list.set_at(i, x)?;
+        }
+        Ok(NoneType)
+    }
+}
+
+fn get_methods() -> Option<&'static Methods> {
+    static RES: MethodsStatic = MethodsStatic::new();
+    RES.methods(random_methods)
+}
+
+/// The generator returned by `random_seeded`.
+#[derive(Debug, Trace, Display, NoSerialize, AnyLifetime)]
+#[display(fmt = "random()")]
+struct Random {
+    state: Cell<u64>,
+}
+
+/// A frozen [`Random`], which can't produce any more numbers.
+#[derive(Debug, Display, NoSerialize, AnyLifetime)]
+#[display(fmt = "random()")]
+struct FrozenRandom;
+
+starlark_simple_value!(FrozenRandom);
+
+impl Random {
+    fn from_value_mut<'v>(x: Value<'v>) -> anyhow::Result<&'v Self> {
+        match x.downcast_ref::<Random>() {
+            Some(x) => Ok(x),
+            None => Err(ValueError::CannotMutateImmutableValue.into()),
+        }
+    }
+
+    // SplitMix64, chosen because it is small and its output is fully specified,
+    // so the sequence for a seed never changes.
+    fn next_u64(&self) -> u64 {
+        let state = self.state.get().wrapping_add(0x9E3779B97F4A7C15);
+        self.state.set(state);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+
+    // A number in `0 .. bound`, by scaling rather than `%`, which avoids
+    // favouring small numbers.
+    fn below(&self, bound: u64) -> u64 {
+        ((self.next_u64() as u128 * bound as u128) >> 64) as u64
+    }
+}
+
+impl<'v> AllocValue<'v> for Random {
+    fn alloc_value(self, heap: &'v Heap) -> Value<'v> {
+        heap.alloc_complex(self)
+    }
+}
+
+impl Freeze for Random {
+    type Frozen = FrozenRandom;
+    fn freeze(self, _freezer: &Freezer) -> anyhow::Result<Self::Frozen> {
+        Ok(FrozenRandom)
+    }
+}
+
+impl<'v> StarlarkValue<'v> for Random {
+    starlark_type!("random");
+
+    fn get_methods(&self) -> Option<&'static Methods> {
+        get_methods()
+    }
+}
+
+impl<'v> StarlarkValue<'v> for FrozenRandom {
+    starlark_type!("random");
+
+    fn get_methods(&self) -> Option<&'static Methods> {
+        get_methods()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        assert::Assert,
+        environment::{experimental_time_rand, Globals},
+    };
+
+    fn assert() -> Assert {
+        let mut a = Assert::new();
+        a.globals_add(experimental_time_rand);
+        a
+    }
+
+    #[test]
+    fn test_random_deterministic() {
+        let a = assert();
+        // Pinned, so any change to the sequence is noticed.
+        a.eq(
+            "[74, 15, 27, 34, 3, 86, 21, 80]",
+            "r = random_seeded(42)\n[r.next_int(100) for _ in range(8)]",
+        );
+        a.pass(
+            r#"
+def run(seed):
+    r = random_seeded(seed)
+    xs = list(range(20))
+    r.shuffle(xs)
+    return [r.next_int(1000) for _ in range(10)] + xs
+assert_eq(run(7), run(7))
+assert_true(run(7) != run(8))
+xs = run(7)[10:]
+assert_eq(list(range(20)), sorted(xs))
+assert_true(xs != list(range(20)))
+"#,
+        );
+        a.fail("random_seeded(1).next_int(0)", "must be positive");
+        a.fail("random_seeded(1).shuffle((1, 2))", "expects a list");
+    }
+
+    #[test]
+    fn test_random_frozen() {
+        let mut a = assert();
+        a.module("m", "xs = [1, 2, 3]\nr = random_seeded(1)");
+        a.fail("load('m', 'xs')\nrandom_seeded(1).shuffle(xs)", "Immutable");
+        a.fail("load('m', 'r')\nr.next_int(10)", "Immutable");
+        a.fail(
+            "xs = [1, 2, 3]\nfor x in xs:\n    random_seeded(1).shuffle(xs)",
+            "mutate an iterable",
+        );
+    }
+
+    #[test]
+    fn test_time() {
+        let a = assert();
+        a.is_true("now_millis() > 1.5e12");
+        a.is_true("x = monotonic_micros()\nmonotonic_micros() >= x");
+    }
+
+    #[test]
+    fn test_not_in_standard_globals() {
+        for globals in [Globals::standard(), Globals::extended()] {
+            let names = globals.names();
+            for name in ["now_millis", "monotonic_micros", "random_seeded"] {
+                assert!(!names.iter().any(|x| x == name), "{}", name);
+            }
+        }
+    }
+}
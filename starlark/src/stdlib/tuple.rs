@@ -0,0 +1,112 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Methods for the `tuple` type.
+
+use anyhow::anyhow;
+
+use crate::{
+    self as starlark,
+    environment::MethodsBuilder,
+    stdlib::util::convert_indices,
+    values::{none::NoneOr, tuple::Tuple, Value},
+};
+
+#[starlark_module]
+pub(crate) fn tuple_methods(builder: &mut MethodsBuilder) {
+    /// `T.count(x)` returns the number of elements of the tuple T equal to `x`.
+    ///
+    /// Examples:
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// (1, 2, 1, 3).count(1) == 2
+    /// # "#);
+    /// ```
+    #[starlark(speculative_exec_safe)]
+    fn count(this: Value, ref needle: Value) -> anyhow::Result<i32> {
+        let this = Tuple::from_value(this).unwrap();
+        let mut count = 0;
+        for x in this.iter() {
+            if x.equals(needle)? {
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    /// `T.index(x[, start[, end]])` finds `x` within the tuple T and returns its
+    /// index. The optional `start` and `end` parameters restrict the portion of
+    /// T that is inspected, as for `list.index`.
+    ///
+    /// `index` fails if `x` is not found in T.
+    ///
+    /// Examples:
+    ///
+    /// ```
+    /// # starlark::assert::is_true(r#"
+    /// x = ("b", "a", "n", "a", "n", "a")
+    /// # (
+    /// x.index("a") == 1      # bAnana
+    /// # and
+    /// x.index("a", 2) == 3   # banAna
+    /// # and
+    /// x.index("a", -2) == 5  # bananA
+    /// # )"#);
+    /// ```
+    #[starlark(speculative_exec_safe)]
+    fn index(
+        this: Value,
+        ref needle: Value,
+        ref start @ NoneOr::None: NoneOr<i32>,
+        ref end @ NoneOr::None: NoneOr<i32>,
+    ) -> anyhow::Result<i32> {
+        let this = Tuple::from_value(this).unwrap();
+        let (start, end) = convert_indices(this.len() as i32, start, end);
+        if let Some(haystack) = this.content().get(start..end) {
+            for (i, x) in haystack.iter().enumerate() {
+                if x.equals(needle)? {
+                    return Ok((i + start) as i32);
+                }
+            }
+        }
+        Err(anyhow!("Element {} not found in tuple", needle.to_repr()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::assert;
+
+    #[test]
+    fn test_index() {
+        assert::all_true(
+            r#"
+(1, 2, 3, 1).index(1) == 0
+(1, 2, 3, 1).index(1, 1) == 3
+(1, 2, 3, 1).index(1, -1, 100) == 3
+(1, 2, 3, 1).index(3, -100, -1) == 2
+(1, 2, 3, 1).count(1) == 2
+(1, 2, 3, 1).count(4) == 0
+().count(1) == 0
+"#,
+        );
+        assert::fail("(1, 2, 3, 1).index(1, 1, 3)", "not found");
+        assert::fail("(1,).index(1, 1, 0)", "not found");
+        assert::fail("(\"a\",).index(\"b\")", "Element \"b\" not found in tuple");
+    }
+}
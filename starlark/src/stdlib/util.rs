@@ -15,7 +15,9 @@
  * limitations under the License.
  */
 
-use crate::values::none::NoneOr;
+use anyhow::anyhow;
+
+use crate::values::{none::NoneOr, Heap, Value, ValueError};
 
 fn bound(val: i32, limit: i32) -> usize {
     if val <= 0 {
@@ -39,3 +41,47 @@ pub(crate) fn convert_index(len: i32, start: i32) -> usize {
     let start = if start < 0 { start + len } else { start };
     bound(start, len)
 }
+
+/// Unpack the element at `index` of an iterable of key/value pairs,
+/// as accepted by `dict()` and `dict.update`.
+pub(crate) fn unpack_pair<'v>(
+    pair: Value<'v>,
+    index: usize,
+    function: &str,
+    heap: &'v Heap,
+) -> anyhow::Result<(Value<'v>, Value<'v>)> {
+    let res = pair.with_iterator(heap, |it| {
+        let mut len = 0;
+        let mut items = [None; 2];
+        for x in it {
+            if let Some(item) = items.get_mut(len) {
+                *item = Some(x);
+            }
+            len += 1;
+        }
+        match items {
+            [Some(k), Some(v)] if len == 2 => Ok((k, v)),
+            _ => Err(len),
+        }
+    });
+    match res {
+        Ok(Ok(pair)) => Ok(pair),
+        Ok(Err(len)) => Err(anyhow!(
+            "Expected key/value pairs for `{}`, element {} has length {}: {}",
+            function,
+            index,
+            len,
+            pair.to_repr()
+        )),
+        Err(e) => match e.downcast_ref::<ValueError>() {
+            Some(ValueError::OperationNotSupported { op, .. }) if op == "(iter)" => Err(anyhow!(
+                "Expected key/value pairs for `{}`, element {} is not iterable: {}",
+                function,
+                index,
+                pair.to_repr()
+            )),
+            // Iterating failed for some other reason, which is more useful to report.
+            _ => Err(e),
+        },
+    }
+}
@@ -30,9 +30,9 @@ use crate::codemap::{CodeMap, Pos, Span, Spanned};
 
 /// Payload types attached to AST nodes.
 pub trait AstPayload: Debug {
-    type IdentPayload: Debug;
-    type IdentAssignPayload: Debug;
-    type DefPayload: Debug;
+    type IdentPayload: Debug + Clone;
+    type IdentAssignPayload: Debug + Clone;
+    type DefPayload: Debug + Clone;
 }
 
 /// Default implementation of payload, which attaches `()` to nodes.
@@ -84,17 +84,27 @@ assert_eq_size!(AstAssign, [usize; 7]);
 
 /// A representation of a Starlark module abstract syntax tree.
 ///
-/// Created with either [`parse`](AstModule::parse) or [`parse_file`](AstModule::parse_file),
-/// and evaluated with [`eval_module`](crate::eval::Evaluator::eval_module).
+/// Created with either [`parse`](AstModule::parse), [`parse_file`](AstModule::parse_file)
+/// or [`parse_with_recovery`](AstModule::parse_with_recovery), and evaluated
+/// with [`eval_module`](crate::eval::Evaluator::eval_module).
 ///
 /// The internal details (statements/expressions) are deliberately omitted, as they change
 /// more regularly. A few methods to obtain information about the AST are provided.
-#[derive(Derivative)]
+#[derive(Derivative, Clone)]
 #[derivative(Debug)]
 pub struct AstModule {
     #[derivative(Debug = "ignore")]
     pub(crate) codemap: CodeMap,
     pub(crate) statement: AstStmt,
+    /// From [`Dialect::enable_int_overflow_to_float`](crate::syntax::Dialect::enable_int_overflow_to_float),
+    /// which unlike the rest of the dialect matters at runtime.
+    pub(crate) int_overflow_to_float: bool,
+    /// From [`Dialect::enable_recursion`](crate::syntax::Dialect::enable_recursion),
+    /// so the linter knows whether recursion is allowed.
+    pub(crate) enable_recursion: bool,
+    /// Whether statements were left out because of syntax errors,
+    /// see [`parse_with_recovery`](AstModule::parse_with_recovery).
+    pub(crate) has_syntax_errors: bool,
 }
 
 // A trait rather than a function to allow .ast() chaining in the parser.
@@ -109,7 +119,7 @@ pub(crate) trait ToAst: Sized {
 
 impl<T> ToAst for T {}
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum ArgumentP<P: AstPayload> {
     Positional(AstExprP<P>),
     Named(AstString, AstExprP<P>),
@@ -117,7 +127,7 @@ pub enum ArgumentP<P: AstPayload> {
     KwArgs(AstExprP<P>),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum ParameterP<P: AstPayload> {
     Normal(AstAssignIdentP<P>, Option<Box<AstExprP<P>>>),
     WithDefaultValue(
@@ -137,7 +147,7 @@ pub enum AstLiteral {
     String(AstString),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum ExprP<P: AstPayload> {
     Tuple(Vec<AstExprP<P>>),
     Dot(Box<AstExprP<P>>, AstString),
@@ -169,7 +179,7 @@ pub enum ExprP<P: AstPayload> {
 }
 
 /// In some places e.g. AssignModify, the Tuple case is not allowed.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum AssignP<P: AstPayload> {
     // We use Tuple for both Tuple and List,
     // as these have the same semantics in Starlark.
@@ -184,20 +194,20 @@ pub enum AssignP<P: AstPayload> {
 pub struct AssignIdentP<P: AstPayload>(pub String, pub P::IdentAssignPayload);
 
 /// `load` statement.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct LoadP<P: AstPayload> {
     pub module: AstString,
     pub args: Vec<(AstAssignIdentP<P>, AstString)>,
     pub visibility: Visibility,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ForClauseP<P: AstPayload> {
     pub var: AstAssignP<P>,
     pub over: AstExprP<P>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum ClauseP<P: AstPayload> {
     For(ForClauseP<P>),
     If(AstExprP<P>),
@@ -249,7 +259,7 @@ pub enum Visibility {
     Public,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum StmtP<P: AstPayload> {
     Break,
     Continue,
@@ -18,9 +18,10 @@
 use thiserror::Error;
 
 use crate::{
+    analysis::recursion::recursive_cycles,
     codemap::{CodeMap, Pos, Span, Spanned},
     errors::Diagnostic,
-    syntax::ast::Visibility,
+    syntax::ast::{AstStmt, Visibility},
 };
 
 #[derive(Error, Debug)]
@@ -35,6 +36,8 @@ enum DialectError {
     KeywordOnlyArguments,
     #[error("type annotations are not allowed in this dialect")]
     Types,
+    #[error("recursion is not allowed in this dialect, `{0}` is recursive via `{1}`")]
+    Recursion(String, String),
 }
 
 /// Starlark language features to enable, e.g. [`Standard`](Dialect::Standard) to follow the Starlark standard.
@@ -65,6 +68,17 @@ pub struct Dialect {
     /// Are `for`, `if` and other statements allowed at the top level.
     /// Only enabled in [`Extended`](Dialect::Extended).
     pub enable_top_level_stmt: bool,
+    /// Are recursive `def`s permitted. If disabled, functions in a module which call
+    /// themselves, directly or via other functions in the same module, are a static error.
+    /// Otherwise, recursion is only limited at runtime by the maximum call stack depth.
+    /// Enabled in both [`Standard`](Dialect::Standard) and [`Extended`](Dialect::Extended).
+    pub enable_recursion: bool,
+    /// Do the operators `+`, `-`, `*`, `//` and unary `-` give a `float` when the result of
+    /// an operation on `int`s doesn't fit in an `int`, rather than failing.
+    /// Applies to the code in the module, including its functions when called from other
+    /// modules, but not to operations on values from Rust.
+    /// Disabled in both [`Standard`](Dialect::Standard) and [`Extended`](Dialect::Extended).
+    pub enable_int_overflow_to_float: bool,
 }
 
 // These are morally enumerations, so give them enumeration-like names
@@ -81,6 +95,8 @@ impl Dialect {
         enable_tabs: true,
         enable_load_reexport: true, // But they plan to change it
         enable_top_level_stmt: false,
+        enable_recursion: true,
+        enable_int_overflow_to_float: false,
     };
 
     /// A superset of [`Standard`](Dialect::Standard), including extra features (types, top-level statements etc).
@@ -93,6 +109,8 @@ impl Dialect {
         enable_tabs: true,
         enable_load_reexport: true,
         enable_top_level_stmt: true,
+        enable_recursion: true,
+        enable_int_overflow_to_float: false,
     };
 }
 
@@ -164,6 +182,34 @@ impl Dialect {
         }
     }
 
+    pub(crate) fn check_recursion(&self, codemap: &CodeMap, x: &AstStmt) -> anyhow::Result<()> {
+        if self.enable_recursion {
+            return Ok(());
+        }
+        // Like other static errors, we only report the first one
+        match recursive_cycles(x).into_iter().next() {
+            None => Ok(()),
+            Some(cycle) => {
+                let mut calls = cycle.calls.into_iter();
+                let (span, _) = calls.next().unwrap();
+                let e = Diagnostic::new(
+                    DialectError::Recursion(cycle.name, cycle.path),
+                    span,
+                    codemap,
+                );
+                Err(Diagnostic::modify(e, |d| {
+                    for (span, name) in calls {
+                        d.add_secondary_span(
+                            span,
+                            codemap,
+                            format!("recursive call to `{}`", name),
+                        );
+                    }
+                }))
+            }
+        }
+    }
+
     pub(crate) fn load_visibility(&self) -> Visibility {
         if self.enable_load_reexport {
             Visibility::Public
@@ -0,0 +1,289 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Print the tree produced by the parser, for debugging.
+
+use std::iter;
+
+use itertools::Itertools;
+use serde::Serialize;
+
+use crate::{
+    codemap::{CodeMap, ResolvedSpan, Span},
+    syntax::{
+        ast::{AstExpr, AstStmt, Clause, Expr, ForClause, Stmt},
+        uniplate::Visit,
+        AstModule,
+    },
+};
+
+#[derive(Serialize)]
+struct Position {
+    line: usize,
+    column: usize,
+}
+
+#[derive(Serialize)]
+struct Node {
+    kind: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    label: Option<String>,
+    #[serde(skip)]
+    span: ResolvedSpan,
+    begin: Position,
+    end: Position,
+    children: Vec<Node>,
+}
+
+impl Node {
+    fn new(
+        codemap: &CodeMap,
+        span: Span,
+        kind: &'static str,
+        label: Option<String>,
+        children: Vec<Node>,
+    ) -> Self {
+        let span = codemap.resolve_span(span);
+        // The codemap is 0-based, but editors and error messages are 1-based.
+        Node {
+            kind,
+            label,
+            span,
+            begin: Position {
+                line: span.begin_line + 1,
+                column: span.begin_column + 1,
+            },
+            end: Position {
+                line: span.end_line + 1,
+                column: span.end_column + 1,
+            },
+            children,
+        }
+    }
+
+    fn stmt(codemap: &CodeMap, x: &AstStmt) -> Self {
+        let (kind, label) = match &**x {
+            Stmt::Break => ("Break", None),
+            Stmt::Continue => ("Continue", None),
+            Stmt::Pass => ("Pass", None),
+            Stmt::Return(_) => ("Return", None),
+            Stmt::Expression(_) => ("Expression", None),
+            Stmt::Assign(lhs, _) => ("Assign", Some(lhs.node.to_string())),
+            Stmt::AssignModify(lhs, op, _) => (
+                "AssignModify",
+                Some(format!("{}{}", lhs.node, op).trim_end().to_owned()),
+            ),
+            Stmt::Statements(_) => ("Statements", None),
+            Stmt::If(..) => ("If", None),
+            Stmt::IfElse(..) => ("IfElse", None),
+            Stmt::For(lhs, _) => ("For", Some(lhs.node.to_string())),
+            Stmt::Def(name, params, ..) => (
+                "Def",
+                Some(format!(
+                    "{}({})",
+                    name.0,
+                    params.iter().map(|x| &x.node).join(", ")
+                )),
+            ),
+            Stmt::Load(load) => (
+                "Load",
+                Some(format!(
+                    "{}: {}",
+                    load.node.module.node,
+                    load.node
+                        .args
+                        .iter()
+                        .map(|(local, their)| if local.0 == their.node {
+                            local.0.clone()
+                        } else {
+                            format!("{} = {}", local.0, their.node)
+                        })
+                        .join(", ")
+                )),
+            ),
+        };
+        let mut children = Vec::new();
+        x.visit_children(|x| {
+            children.push(match x {
+                Visit::Stmt(x) => Self::stmt(codemap, x),
+                Visit::Expr(x) => Self::expr(codemap, x),
+            })
+        });
+        Self::new(codemap, x.span, kind, label, children)
+    }
+
+    fn expr(codemap: &CodeMap, x: &AstExpr) -> Self {
+        let (kind, label) = match &**x {
+            Expr::Tuple(_) => ("Tuple", None),
+            Expr::Dot(_, name) => ("Dot", Some(name.node.clone())),
+            Expr::Call(..) => ("Call", None),
+            Expr::ArrayIndirection(_) => ("Index", None),
+            Expr::Slice(..) => ("Slice", None),
+            Expr::Identifier(name, _) => ("Identifier", Some(name.node.clone())),
+            Expr::Lambda(params, ..) => (
+                "Lambda",
+                Some(format!("({})", params.iter().map(|x| &x.node).join(", "))),
+            ),
+            Expr::Literal(x) => ("Literal", Some(x.to_string())),
+            Expr::Not(_) => ("Not", None),
+            Expr::Minus(_) => ("Minus", None),
+            Expr::Plus(_) => ("Plus", None),
+            Expr::BitNot(_) => ("BitNot", None),
+            Expr::Op(_, op, _) => ("Op", Some(op.to_string().trim().to_owned())),
+            Expr::If(_) => ("If", None),
+            Expr::List(_) => ("List", None),
+            Expr::Dict(_) => ("Dict", None),
+            // The variables aren't expressions, so won't show up as children.
+            Expr::ListComprehension(_, for_, clauses) => {
+                ("ListComprehension", Some(comprehension_vars(for_, clauses)))
+            }
+            Expr::DictComprehension(_, for_, clauses) => {
+                ("DictComprehension", Some(comprehension_vars(for_, clauses)))
+            }
+        };
+        let mut children = Vec::new();
+        x.visit_expr(|x| children.push(Self::expr(codemap, x)));
+        Self::new(codemap, x.span, kind, label, children)
+    }
+
+    fn write(&self, indent: usize, res: &mut String) {
+        res.push_str(&" ".repeat(indent));
+        res.push_str(self.kind);
+        if let Some(label) = &self.label {
+            res.push(' ');
+            res.push_str(label);
+        }
+        res.push_str(&format!(" {}\n", self.span));
+        for x in &self.children {
+            x.write(indent + 2, res);
+        }
+    }
+}
+
+fn comprehension_vars(for_: &ForClause, clauses: &[Clause]) -> String {
+    iter::once(for_)
+        .chain(clauses.iter().filter_map(|x| match x {
+            Clause::For(x) => Some(x),
+            Clause::If(_) => None,
+        }))
+        .map(|x| format!("for {}", x.var.node))
+        .join(" ")
+}
+
+impl AstModule {
+    fn dump(&self) -> Node {
+        Node::new(
+            &self.codemap,
+            self.codemap.full_span(),
+            "Module",
+            Some(self.codemap.filename().to_owned()),
+            vec![Node::stmt(&self.codemap, &self.statement)],
+        )
+    }
+
+    /// Print the parsed tree, one node per line, indented by depth.
+    /// Each line has the kind of node, any name, literal or operator it contains,
+    /// and its 1-based `line:column` span. Intended for debugging, the format may change.
+    pub fn to_ast_string(&self) -> String {
+        let mut res = String::new();
+        self.dump().write(0, &mut res);
+        res
+    }
+
+    /// Like [`to_ast_string`](AstModule::to_ast_string), but as a single line of JSON.
+    /// Each node is an object with fields `kind`, `label` (if any), `begin` and `end`
+    /// (each with 1-based `line` and `column`) and `children`.
+    pub fn to_ast_json(&self) -> String {
+        serde_json::to_string(&self.dump()).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syntax::Dialect;
+
+    const CODE: &str = r#"def f(x, y = 1):
+    return [a * 2 for a in x if a]
+z = f(3) if True else None
+"#;
+
+    fn module() -> AstModule {
+        AstModule::parse("x.star", CODE.to_owned(), &Dialect::Extended).unwrap()
+    }
+
+    #[test]
+    fn test_ast_string() {
+        let res = module().to_ast_string();
+        // Drop the spans, which are checked separately
+        let shape = res
+            .lines()
+            .map(|x| x.rsplit_once(' ').unwrap().0)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            shape,
+            &[
+                "Module x.star",
+                "  Statements",
+                "    Def f(x, y = 1)",
+                "      Literal 1",
+                "      Statements",
+                "        Return",
+                "          ListComprehension for a",
+                "            Identifier x",
+                "            Identifier a",
+                "            Op *",
+                "              Identifier a",
+                "              Literal 2",
+                "    Assign z",
+                "      If",
+                "        Identifier True",
+                "        Call",
+                "          Identifier f",
+                "          Literal 3",
+                "        Identifier None",
+            ][..]
+        );
+        assert!(res.contains("\n          ListComprehension for a 2:12-35\n"));
+        assert!(res.contains("\n      If 3:5-27\n"));
+        assert!(res.contains("\n        Call 3:5-9\n"));
+    }
+
+    #[test]
+    fn test_ast_json() {
+        let res: serde_json::Value = serde_json::from_str(&module().to_ast_json()).unwrap();
+        assert_eq!(res["kind"], "Module");
+        assert_eq!(res["label"], "x.star");
+        let def = &res["children"][0]["children"][0];
+        assert_eq!(def["kind"], "Def");
+        assert_eq!(def["begin"], serde_json::json!({"line": 1, "column": 1}));
+        let comprehension = &def["children"][1]["children"][0]["children"][0];
+        assert_eq!(comprehension["kind"], "ListComprehension");
+        assert_eq!(
+            comprehension["begin"],
+            serde_json::json!({"line": 2, "column": 12})
+        );
+        assert_eq!(
+            comprehension["end"],
+            serde_json::json!({"line": 2, "column": 35})
+        );
+        let if_ = &res["children"][0]["children"][1]["children"][0];
+        assert_eq!(if_["kind"], "If");
+        assert!(if_.get("label").is_none());
+        assert_eq!(if_["children"].as_array().unwrap().len(), 3);
+    }
+}
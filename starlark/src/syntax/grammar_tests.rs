@@ -17,7 +17,14 @@
 
 use gazebo::prelude::*;
 
-use crate::{assert, assert::Assert, syntax::ast::Stmt};
+use crate::{
+    assert,
+    assert::Assert,
+    environment::{Globals, Module},
+    errors::Diagnostic,
+    eval::Evaluator,
+    syntax::{ast::Stmt, AstModule, Dialect},
+};
 
 #[test]
 fn test_empty() {
@@ -299,3 +306,104 @@ fn test_bad_assignment() {
     assert::parse_fail("[!x or y!] = 1");
     assert::parse_fail("![x]! += 1");
 }
+
+#[test]
+fn test_recursion() {
+    let mut a = Assert::new();
+    a.dialect_set(|x| x.enable_recursion = false);
+    a.parse_fail("def f(x):\n  return !f(x - 1)!\n");
+    let e = a.parse_fail(
+        "def f(): return !g()!\ndef g(): return h()\ndef h():\n  if True:\n    return f()\n",
+    );
+    assert!(e.to_string().contains("`f -> g -> h -> f`"));
+    // Once the name is rebound, calls no longer refer to the def
+    a.is_true("def f(): return f()\nf = lambda: 1\nf() == 1");
+
+    // By default recursion is only limited at runtime
+    assert::is_true("def f(x): return x if x == 0 else f(x - 1)\nf(3) == 0");
+}
+
+#[test]
+fn test_incomplete_input() {
+    fn incomplete(code: &str) -> bool {
+        AstModule::parse("x", code.to_owned(), &Dialect::Extended)
+            .unwrap_err()
+            .downcast_ref::<Diagnostic>()
+            .unwrap()
+            .is_incomplete_input()
+    }
+
+    assert!(incomplete("def f(x):\n"));
+    assert!(incomplete("def f(x):\n  if x:\n"));
+    assert!(incomplete("x = [1,\n"));
+    assert!(incomplete("f(1,\n  2\n"));
+    assert!(incomplete("x = \"\"\"abc\n"));
+    assert!(!incomplete("x = 1 +\n"));
+    assert!(!incomplete("def f(x):\nreturn x\n"));
+    assert!(!incomplete("x = [1 2]\n"));
+    assert!(!incomplete("x = \"abc\n"));
+}
+
+#[test]
+fn test_parse_with_recovery() {
+    let code = r#"
+a = 1 +
+def f(x):
+    return x
+def g():
+    y = x +
+    return 1
+b = f(**{}, x = 1)
+def h():
+    if a:
+        return a
+    else:
+        return f(a,, 1)
+c = 3
+"#;
+    let (module, errors) =
+        AstModule::parse_with_recovery("recover.star", code.to_owned(), &Dialect::Extended);
+    assert!(module.has_syntax_errors());
+    let spans = errors.map(|x| {
+        let span = x.span.as_ref().unwrap().resolve_span();
+        (span.begin_line, span.begin_column)
+    });
+    assert_eq!(spans, &[(1, 7), (5, 11), (7, 12), (12, 19)]);
+    let messages = errors.map(|x| x.message.to_string());
+    assert!(messages[0].starts_with("Parse error: unexpected new line"));
+    assert!(messages[1].starts_with("Parse error: unexpected new line"));
+    assert_eq!(messages[2], "named argument after *args or **kwargs");
+    assert!(messages[3].starts_with("Parse error: unexpected symbol ','"));
+
+    // Only the innermost statements with errors are left out, and an empty block gets a `pass`.
+    assert_eq!(
+        module.symbols().map(|x| x.name.as_str()),
+        &["f", "g", "h", "c"]
+    );
+    let expected = r#"
+def f(x):
+    return x
+def g():
+    return 1
+def h():
+    if a:
+        return a
+    else:
+        pass
+c = 3
+"#;
+    assert_eq!(module.statement.to_string(), assert::parse(expected));
+    let env = Module::new();
+    let err = Evaluator::new(&env)
+        .eval_module(module, &Globals::standard())
+        .unwrap_err();
+    assert!(err.to_string().contains("syntax errors"), "{}", err);
+
+    // Without errors, the result is the same as `parse`.
+    let code = "def f(x):\n    if x:\n        pass\n    elif x:\n        pass\n    else:\n        pass\nf(1)\n";
+    let (module, errors) =
+        AstModule::parse_with_recovery("ok.star", code.to_owned(), &Dialect::Extended);
+    assert!(errors.is_empty());
+    assert!(!module.has_syntax_errors());
+    assert_eq!(module.statement.to_string(), assert::parse(code));
+}
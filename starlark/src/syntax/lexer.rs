@@ -26,6 +26,7 @@ use crate::{
     syntax::{
         cursors::{CursorBytes, CursorChars},
         dialect::Dialect,
+        parser::IncompleteInput,
     },
 };
 
@@ -35,6 +36,8 @@ pub(crate) enum LexemeError {
     Indentation,
     #[error("Parse error: invalid input `{0}`")]
     InvalidInput(String),
+    #[error("Parse error: NUL byte at byte offset {0}")]
+    NulByte(usize),
     #[error("Parse error: tabs are not allowed in the dialect")]
     InvalidTab,
     #[error("Parse error: unfinished string literal")]
@@ -49,7 +52,7 @@ pub(crate) enum LexemeError {
     IntOverflow(String),
 }
 
-type Lexeme = anyhow::Result<(usize, Token, usize)>;
+pub(crate) type Lexeme = anyhow::Result<(usize, Token, usize)>;
 
 pub(crate) struct Lexer<'a> {
     // Information for spans
@@ -77,6 +80,14 @@ impl<'a> Lexer<'a> {
             done: false,
             dialect_allow_tabs: dialect.enable_tabs,
         };
+        // A NUL byte usually means a binary or corrupted file, so don't try to lex any of it.
+        if let Some(pos) = input.bytes().position(|b| b == 0) {
+            lexer2.done = true;
+            lexer2
+                .buffer
+                .push_back(lexer2.err_span(LexemeError::NulByte(pos), pos, pos + 1));
+            return lexer2;
+        }
         if let Err(e) = lexer2.calculate_indent() {
             lexer2.buffer.push_back(Err(e));
         }
@@ -95,6 +106,25 @@ impl<'a> Lexer<'a> {
         ))
     }
 
+    /// A string literal ran out of characters. Triple-quoted strings can span lines,
+    /// so for them that means the input stopped too early.
+    fn err_unfinished_string<T>(
+        &self,
+        triple: bool,
+        start: usize,
+        end: usize,
+    ) -> anyhow::Result<T> {
+        if triple {
+            Err(Diagnostic::new(
+                IncompleteInput(LexemeError::UnfinishedStringLiteral.to_string()),
+                Span::new(Pos::new(start as u32), Pos::new(end as u32)),
+                &self.codemap,
+            ))
+        } else {
+            self.err_span(LexemeError::UnfinishedStringLiteral, start, end)
+        }
+    }
+
     fn err_now<T>(&self, msg: fn(String) -> LexemeError) -> anyhow::Result<T> {
         self.err_span(
             msg(self.lexer.slice().to_owned()),
@@ -103,6 +133,22 @@ impl<'a> Lexer<'a> {
         )
     }
 
+    /// The input `logos` could not match. Its span may end within a multi-byte character,
+    /// so extend it to the end of that character.
+    fn err_invalid_input<T>(&self) -> anyhow::Result<T> {
+        let source = self.lexer.source();
+        let start = self.lexer.span().start;
+        let mut end = self.lexer.span().end;
+        while !source.is_char_boundary(end) {
+            end += 1;
+        }
+        self.err_span(
+            LexemeError::InvalidInput(source[start..end].escape_debug().to_string()),
+            start,
+            end,
+        )
+    }
+
     /// We have just seen a newline, read how many indents we have
     /// and then set self.indent properly
     fn calculate_indent(&mut self) -> anyhow::Result<()> {
@@ -292,11 +338,7 @@ impl<'a> Lexer<'a> {
         loop {
             match it.next_char() {
                 None => {
-                    return self.err_span(
-                        LexemeError::UnfinishedStringLiteral,
-                        string_start,
-                        string_end + it.pos(),
-                    );
+                    return self.err_unfinished_string(triple, string_start, string_end + it.pos());
                 }
                 Some(c) => {
                     if stop(c) {
@@ -368,11 +410,7 @@ impl<'a> Lexer<'a> {
         }
 
         // We ran out of characters
-        self.err_span(
-            LexemeError::UnfinishedStringLiteral,
-            string_start,
-            string_end + it.pos(),
-        )
+        self.err_unfinished_string(triple, string_start, string_end + it.pos())
     }
 
     pub fn next(&mut self) -> Option<Lexeme> {
@@ -415,7 +453,7 @@ impl<'a> Lexer<'a> {
                             }
                         }
                         Token::Reserved => Some(self.err_now(LexemeError::ReservedKeyword)),
-                        Token::Error => Some(self.err_now(LexemeError::InvalidInput)),
+                        Token::Error => Some(self.err_invalid_input()),
                         Token::Int(radix) => {
                             let mut s = self.lexer.slice();
                             if radix == 10 {
@@ -476,7 +514,9 @@ impl<'a> Lexer<'a> {
                             self.wrap(token)
                         }
                         Token::ClosingCurly | Token::ClosingRound | Token::ClosingSquare => {
-                            self.parens -= 1;
+                            // An unmatched closing bracket is a parse error, but don't let it
+                            // hide the newlines of the rest of the file.
+                            self.parens = (self.parens - 1).max(0);
                             self.wrap(token)
                         }
                         _ => self.wrap(token),
@@ -279,6 +279,10 @@ fn test_lexer_error_messages() {
         "reserved_word = !raise! + 1",
         "cannot use reserved keyword `raise`",
     );
+    f("x = !é! + 1", "invalid input `é`");
+    f("x = 1 + !\x01! 2", "invalid input `\\u{1}`");
+    f("x = 1 + !\0! 2", "NUL byte at byte offset 8");
+    f("s = 'a!\0!b'", "NUL byte at byte offset 6");
 }
 
 #[test]
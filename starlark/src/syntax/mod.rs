@@ -20,6 +20,11 @@
 pub use ast::AstModule;
 pub use dialect::Dialect;
 
+pub use crate::analysis::{
+    Definition, ExportInfo, ExportKind, LoadInfo, LoadedSymbol, ModuleInfo, ParamInfo, Symbol,
+    SymbolKind,
+};
+
 #[cfg(test)]
 mod grammar_tests;
 #[cfg(test)]
@@ -30,6 +35,7 @@ mod testcases;
 pub(crate) mod ast;
 pub(crate) mod cursors;
 mod dialect;
+mod dump;
 pub(crate) mod lexer;
 pub(crate) mod payload_map;
 pub(crate) mod validate;
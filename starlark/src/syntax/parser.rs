@@ -15,20 +15,20 @@
  * limitations under the License.
  */
 
-use std::{fs, path::Path};
+use std::{fs, iter::Peekable, mem, path::Path};
 
-use anyhow::anyhow;
 use gazebo::prelude::*;
 use lalrpop_util as lu;
+use thiserror::Error;
 
 use crate::{
     codemap::{CodeMap, FileSpan, Pos, Span},
     errors::Diagnostic,
     syntax::{
-        ast::{AstModule, AstStmt, Stmt},
+        ast::{AstModule, AstStmt, Stmt, ToAst},
         dialect::Dialect,
         grammar::StarlarkParser,
-        lexer::{Lexer, Token},
+        lexer::{Lexeme, Lexer, Token},
     },
 };
 
@@ -46,6 +46,18 @@ fn one_of(expected: &[String]) -> String {
     result
 }
 
+/// A parse error caused by the input ending while a statement was still open,
+/// e.g. inside a block, unclosed brackets or a triple-quoted string.
+/// See [`Diagnostic::is_incomplete_input`].
+#[derive(Error, Debug)]
+#[error("{0}")]
+pub(crate) struct IncompleteInput(pub(crate) String);
+
+/// Any other parse error, e.g. an unexpected token.
+#[derive(Error, Debug)]
+#[error("{0}")]
+pub(crate) struct ParseError(String);
+
 /// Convert the error to a codemap diagnostic.
 ///
 /// To build this diagnostic, the method needs the file span corresponding
@@ -91,7 +103,199 @@ pub(crate) fn parse_error_add_span(
         lu::ParseError::User { .. } => unreachable!(),
     };
 
-    Diagnostic::new(anyhow!(message), span, codemap)
+    // The lexer emits the final newline and dedents at the end of the input,
+    // so failing on one of those means the input stopped too early.
+    let incomplete = match &err {
+        lu::ParseError::UnrecognizedEOF { .. } => true,
+        lu::ParseError::UnrecognizedToken { token: (x, ..), .. } => *x >= len,
+        _ => false,
+    };
+    if incomplete {
+        Diagnostic::new(IncompleteInput(message), span, codemap)
+    } else {
+        Diagnostic::new(ParseError(message), span, codemap)
+    }
+}
+
+/// Split the tokens of a module into its top-level statements, each ending with the
+/// newline and dedents which finish it, so after a syntax error in one statement,
+/// parsing can carry on with the next.
+fn top_level_statements(lexer: Lexer) -> Vec<Vec<Lexeme>> {
+    // A statement carries on into an indented block, or an `elif` or `else`.
+    fn continues(tokens: &mut Peekable<Lexer>) -> bool {
+        matches!(
+            tokens.peek(),
+            Some(Ok((
+                _,
+                Token::Indent | Token::Dedent | Token::Elif | Token::Else,
+                _
+            )))
+        )
+    }
+
+    let mut res = Vec::new();
+    let mut statement = Vec::new();
+    let mut depth = 0usize;
+    let mut tokens = lexer.peekable();
+    while let Some(token) = tokens.next() {
+        let end_of_line = match &token {
+            Ok((_, Token::Indent, _)) => {
+                depth += 1;
+                false
+            }
+            Ok((_, Token::Dedent, _)) => {
+                depth = depth.saturating_sub(1);
+                true
+            }
+            Ok((_, Token::Newline, _)) => true,
+            _ => false,
+        };
+        statement.push(token);
+        if end_of_line && depth == 0 && !continues(&mut tokens) {
+            res.push(mem::take(&mut statement));
+        }
+    }
+    if !statement.is_empty() {
+        res.push(statement);
+    }
+    res
+}
+
+/// The start of an error, if it has a location.
+fn error_pos(e: &anyhow::Error) -> Option<Pos> {
+    Some(e.downcast_ref::<Diagnostic>()?.span.as_ref()?.span.begin())
+}
+
+/// Remove the innermost statement within an indented block which contains `pos`,
+/// leaving `pass` if the block would be empty.
+/// Returns `false` if `pos` isn't within a block, so there is nothing smaller to remove.
+fn remove_nested_statement(tokens: &mut Vec<(usize, Token, usize)>, pos: Pos) -> bool {
+    // Each line as the index of its first token other than indents and dedents,
+    // the index after its newline, and its depth.
+    let mut lines = Vec::new();
+    let mut depth = 0usize;
+    let mut start = None;
+    for (i, (_, t, _)) in tokens.iter().enumerate() {
+        match t {
+            Token::Indent => depth += 1,
+            Token::Dedent => depth = depth.saturating_sub(1),
+            _ => {
+                let begin = *start.get_or_insert(i);
+                if *t == Token::Newline {
+                    lines.push((begin, i + 1, depth));
+                    start = None;
+                }
+            }
+        }
+    }
+    if let Some(begin) = start {
+        lines.push((begin, tokens.len(), depth));
+    }
+
+    let is_clause =
+        |line: &(usize, usize, usize)| matches!(tokens[line.0].1, Token::Elif | Token::Else);
+    let mut first = match lines
+        .iter()
+        .rposition(|x| Pos::new(tokens[x.0].0 as u32) <= pos)
+    {
+        Some(first) => first,
+        None => return false,
+    };
+    let depth = lines[first].2;
+    if depth == 0 {
+        return false;
+    }
+    // An `elif` or `else` can't be left without its `if`, so remove the whole `if`.
+    while is_clause(&lines[first]) {
+        match lines[..first].iter().rposition(|x| x.2 <= depth) {
+            Some(i) if lines[i].2 == depth => first = i,
+            _ => return false,
+        }
+    }
+    // The statement carries on into an indented block, or an `elif` or `else`.
+    let mut last = first + 1;
+    while last < lines.len()
+        && (lines[last].2 > depth || (lines[last].2 == depth && is_clause(&lines[last])))
+    {
+        last += 1;
+    }
+
+    let begin = lines[first].0;
+    let mut end = lines[last - 1].1;
+    // Don't remove the `pass` left by an earlier removal, which has no width.
+    if tokens[begin..end].iter().all(|x| x.0 == x.2) {
+        return false;
+    }
+    // Blocks opened within the statement are closed by the dedents which follow it.
+    let mut open = 0isize;
+    for (_, t, _) in &tokens[begin..end] {
+        match t {
+            Token::Indent => open += 1,
+            Token::Dedent => open -= 1,
+            _ => {}
+        }
+    }
+    while open > 0 && tokens.get(end).map(|x| &x.1) == Some(&Token::Dedent) {
+        end += 1;
+        open -= 1;
+    }
+
+    let at = tokens[begin].0;
+    let empty_block = tokens[begin - 1].1 == Token::Indent
+        && tokens.get(end).map_or(true, |x| x.1 == Token::Dedent);
+    let placeholder = if empty_block {
+        vec![(at, Token::Pass, at), (at, Token::Newline, at)]
+    } else {
+        Vec::new()
+    };
+    tokens.splice(begin..end, placeholder);
+    true
+}
+
+/// Parse the tokens of a top-level statement. On an error within an indented block,
+/// leave out the innermost statement containing the error and try again, so the rest
+/// of the statement survives. Returns the statement, unless an error couldn't be
+/// recovered from, and all the errors found.
+fn parse_statement_with_recovery(
+    codemap: &CodeMap,
+    dialect: &Dialect,
+    tokens: Vec<Lexeme>,
+    end: usize,
+) -> (Option<AstStmt>, Vec<anyhow::Error>) {
+    let parse = |tokens: Vec<Lexeme>| {
+        StarlarkParser::new()
+            .parse(codemap, dialect, tokens)
+            .map_err(|e| parse_error_add_span(e, end, codemap))
+            .and_then(|x| {
+                Stmt::validate(codemap, &x, dialect)?;
+                Ok(x)
+            })
+    };
+
+    // Errors from the lexer can't be copied to parse the tokens again.
+    if tokens.iter().any(|x| x.is_err()) {
+        return match parse(tokens) {
+            Ok(x) => (Some(x), Vec::new()),
+            Err(e) => (None, vec![e]),
+        };
+    }
+    let mut tokens: Vec<_> = tokens.into_iter().filter_map(Result::ok).collect();
+    let mut errors = Vec::new();
+    loop {
+        match parse(tokens.iter().cloned().map(Ok).collect()) {
+            Ok(x) => return (Some(x), errors),
+            Err(e) => {
+                let removed = match error_pos(&e) {
+                    Some(pos) => remove_nested_statement(&mut tokens, pos),
+                    None => false,
+                };
+                errors.push(e);
+                if !removed {
+                    return (None, errors);
+                }
+            }
+        }
+    }
 }
 
 impl AstModule {
@@ -101,7 +305,14 @@ impl AstModule {
         dialect: &Dialect,
     ) -> anyhow::Result<AstModule> {
         Stmt::validate(&codemap, &statement, dialect)?;
-        Ok(AstModule { codemap, statement })
+        dialect.check_recursion(&codemap, &statement)?;
+        Ok(AstModule {
+            codemap,
+            statement,
+            int_overflow_to_float: dialect.enable_int_overflow_to_float,
+            enable_recursion: dialect.enable_recursion,
+            has_syntax_errors: false,
+        })
     }
 
     /// Parse a file stored on disk. For details see [`parse`](AstModule::parse).
@@ -133,6 +344,82 @@ impl AstModule {
         }
     }
 
+    /// Parse a Starlark module like [`parse`](AstModule::parse), but on a syntax error,
+    /// carry on parsing from the next statement, so all the syntax errors are
+    /// reported. Intended for editors, where a file being edited often doesn't parse,
+    /// but linting or an outline of the rest of the file is still useful.
+    ///
+    /// The innermost statements containing errors are left out of the resulting [`AstModule`],
+    /// e.g. a bad line in the body of a `def` loses that line, not the whole `def`,
+    /// and a block left empty gets a `pass`. So if there were any errors, the module
+    /// [has syntax errors](AstModule::has_syntax_errors) and can't be evaluated.
+    ///
+    /// ```
+    /// use starlark::syntax::{AstModule, Dialect};
+    ///
+    /// let code = "x = 1 +\ny = 2\nz = )\n";
+    /// let (module, errors) = AstModule::parse_with_recovery("filename", code.to_owned(), &Dialect::Standard);
+    /// assert_eq!(errors.len(), 2);
+    /// assert_eq!(errors[1].span.as_ref().unwrap().to_string(), "filename:3:5-6");
+    /// assert!(module.has_syntax_errors());
+    /// ```
+    pub fn parse_with_recovery(
+        filename: &str,
+        content: String,
+        dialect: &Dialect,
+    ) -> (AstModule, Vec<Diagnostic>) {
+        let codemap = CodeMap::new(filename.to_owned(), content);
+        let len = codemap.source().len();
+        let lexer = Lexer::new(codemap.source(), dialect, codemap.dupe());
+        let mut statements = Vec::new();
+        let mut errors = Vec::new();
+        let chunks = top_level_statements(lexer);
+        let last = chunks.len().saturating_sub(1);
+        for (i, tokens) in chunks.into_iter().enumerate() {
+            // For the parser, the input ends with the statement.
+            let end = if i == last {
+                len
+            } else {
+                tokens
+                    .iter()
+                    .rev()
+                    .find_map(|x| Some(x.as_ref().ok()?.2))
+                    .unwrap_or(len)
+            };
+            let (statement, errs) = parse_statement_with_recovery(&codemap, dialect, tokens, end);
+            errors.extend(errs);
+            if let Some(x) = statement {
+                match x.node {
+                    Stmt::Statements(xs) => statements.extend(xs),
+                    _ => statements.push(x),
+                }
+            }
+        }
+        let statement = Stmt::Statements(statements).ast(0, len);
+        if let Err(e) = dialect.check_recursion(&codemap, &statement) {
+            errors.push(e);
+        }
+        let module = AstModule {
+            codemap,
+            statement,
+            int_overflow_to_float: dialect.enable_int_overflow_to_float,
+            enable_recursion: dialect.enable_recursion,
+            has_syntax_errors: !errors.is_empty(),
+        };
+        let errors = errors.into_map(|e| {
+            Diagnostic::modify(e, |_| {})
+                .downcast::<Diagnostic>()
+                .unwrap()
+        });
+        (module, errors)
+    }
+
+    /// Whether the module was produced by [`parse_with_recovery`](AstModule::parse_with_recovery)
+    /// from code with syntax errors, in which case the statements with errors are missing.
+    pub fn has_syntax_errors(&self) -> bool {
+        self.has_syntax_errors
+    }
+
     /// Return the file names of all the `load` statements in the module.
     /// If the [`Dialect`] had [`enable_load`](Dialect::enable_load) set to [`false`] this will be an empty list.
     pub fn loads(&self) -> Vec<&str> {
@@ -35,7 +35,7 @@ use crate::{
 };
 
 #[derive(Error, Debug)]
-enum ValidateError {
+pub(crate) enum ValidateError {
     #[error("`break` cannot be used outside of a `for` loop")]
     BreakOutsideLoop,
     #[error("`continue` cannot be used outside of a `for` loop")]
@@ -0,0 +1,85 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+use std::{
+    cell::{Cell, RefCell},
+    mem,
+};
+
+use derive_more::Display;
+use gazebo::any::AnyLifetime;
+
+use crate as starlark;
+use crate::{
+    environment::Module,
+    values::{Freeze, FrozenValue, StarlarkValue, Trace, Value, ValueLike},
+};
+
+#[derive(Debug, Display, Trace, Freeze, AnyLifetime, NoSerialize)]
+#[display(fmt = "mutable")]
+struct MutableGen<V> {
+    items: RefCell<Vec<V>>,
+    count: Cell<i32>,
+}
+
+impl<'v, V: ValueLike<'v>> StarlarkValue<'v> for MutableGen<V>
+where
+    Self: AnyLifetime<'v>,
+{
+    starlark_type!("mutable");
+}
+
+fn module_with_mutable() -> Module {
+    let module = Module::new();
+    let heap = module.heap();
+    let mutable = MutableGen {
+        items: RefCell::new(vec![heap.alloc(vec![1]), heap.alloc("x")]),
+        count: Cell::new(2),
+    };
+    module.set("m", heap.alloc_complex(mutable));
+    module
+}
+
+#[test]
+fn test_derive_freeze_ref_cell() {
+    let module = module_with_mutable();
+    let frozen = module.freeze().unwrap();
+    let m = frozen.get("m").unwrap();
+    let m = m.downcast_ref::<MutableGen<FrozenValue>>().unwrap();
+    let items = m.items.borrow();
+    assert_eq!(2, items.len());
+    assert_eq!("[1]", items[0].to_value().to_repr());
+    assert_eq!("\"x\"", items[1].to_value().to_repr());
+    assert_eq!(2, m.count.get());
+}
+
+#[test]
+fn test_derive_freeze_ref_cell_borrowed() {
+    let module = module_with_mutable();
+    let m = module.get("m").unwrap();
+    // Leak a borrow, so the cell is still borrowed when the module is frozen.
+    mem::forget(
+        m.downcast_ref::<MutableGen<Value>>()
+            .unwrap()
+            .items
+            .borrow(),
+    );
+    assert_eq!(
+        "Failed to freeze `m.items`: `RefCell` is borrowed while being frozen",
+        format!("{:#}", module.freeze().unwrap_err())
+    );
+}
@@ -17,6 +17,7 @@
 
 mod derive_freeze_basic;
 mod derive_freeze_bounds;
+mod derive_freeze_ref_cell;
 mod derive_freeze_validator;
 mod derive_freeze_validator_order;
 mod freeze_access_value;
@@ -354,10 +354,21 @@ pub struct Type {
 
 /// Documents a full module.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct Module {
     /// In general, this should be the first statement of a loaded file, if that statement is
     /// a string literal.
     pub docs: Option<DocString>,
+    /// Name and details of each symbol exported by the module, sorted by name.
+    #[serde(default)]
+    pub members: Vec<(String, Member)>,
+}
+
+impl Module {
+    /// Create the documentation for a module from its docstring and members.
+    pub fn new(docs: Option<DocString>, members: Vec<(String, Member)>) -> Self {
+        Self { docs, members }
+    }
 }
 
 /// Documents a single function.
@@ -535,9 +546,12 @@ pub struct Property {
 /// A named member of an object.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(tag = "kind", rename_all = "snake_case")]
+#[non_exhaustive]
 pub enum Member {
     Property(Property),
     Function(Function),
+    /// A nested object, e.g. a namespace within [`Globals`](crate::environment::Globals).
+    Object(Object),
 }
 
 /// An object with named functions/properties.
@@ -556,6 +570,131 @@ pub enum DocItem {
     Function(Function),
 }
 
+impl DocItem {
+    /// Render the documentation as markdown, with `name` as the top level heading.
+    /// The members of objects are rendered in order, each nested one heading level deeper.
+    pub fn render_as_markdown(&self, name: &str) -> String {
+        let mut res = String::new();
+        markdown::item(&mut res, 1, name, self);
+        res
+    }
+}
+
+mod markdown {
+    use std::fmt::Write;
+
+    use super::*;
+
+    fn heading(res: &mut String, depth: usize, name: &str) {
+        writeln!(res, "{} `{}`\n", "#".repeat(depth.min(6)), name).unwrap();
+    }
+
+    fn docstring(res: &mut String, docs: &Option<DocString>) {
+        if let Some(docs) = docs {
+            writeln!(res, "{}\n", docs.summary).unwrap();
+            if let Some(details) = &docs.details {
+                writeln!(res, "{}\n", details).unwrap();
+            }
+        }
+    }
+
+    /// The docs of a parameter or return value as a single line of a list.
+    fn inline_docs(docs: &DocString) -> String {
+        match &docs.details {
+            None => docs.summary.clone(),
+            Some(details) => format!("{} {}", docs.summary, details.replace('\n', " ")),
+        }
+    }
+
+    fn typ(x: &Option<Type>) -> String {
+        match x {
+            Some(x) => format!(": {}", x.raw_type),
+            None => String::new(),
+        }
+    }
+
+    fn param(x: &Param) -> String {
+        match x {
+            Param::Arg {
+                name,
+                typ: t,
+                default_value,
+                ..
+            } => match default_value {
+                Some(d) => format!("{}{} = {}", name, typ(t), d),
+                None => format!("{}{}", name, typ(t)),
+            },
+            Param::NoArgs => "*".to_owned(),
+            Param::Args { name, typ: t, .. } => format!("*{}{}", name, typ(t)),
+            Param::Kwargs { name, typ: t, .. } => format!("**{}{}", name, typ(t)),
+        }
+    }
+
+    fn function(res: &mut String, name: &str, x: &Function) {
+        let params = x.params.map(param).join(", ");
+        let ret = match &x.ret.typ {
+            Some(t) => format!(" -> {}", t.raw_type),
+            None => String::new(),
+        };
+        writeln!(res, "```python\ndef {}({}){}\n```\n", name, params, ret).unwrap();
+        docstring(res, &x.docs);
+
+        let param_docs: Vec<_> = x
+            .params
+            .iter()
+            .filter_map(|p| match p {
+                Param::Arg { name, docs, .. } => Some((name.as_str(), docs.as_ref()?)),
+                Param::Args { name, docs, .. } => Some((name.as_str(), docs.as_ref()?)),
+                Param::Kwargs { name, docs, .. } => Some((name.as_str(), docs.as_ref()?)),
+                Param::NoArgs => None,
+            })
+            .collect();
+        if !param_docs.is_empty() {
+            res.push_str("Parameters:\n\n");
+            for (name, docs) in param_docs {
+                writeln!(res, "* `{}`: {}", name, inline_docs(docs)).unwrap();
+            }
+            res.push('\n');
+        }
+        if let Some(docs) = &x.ret.docs {
+            writeln!(res, "Returns: {}\n", inline_docs(docs)).unwrap();
+        }
+    }
+
+    fn object(res: &mut String, depth: usize, x: &Object) {
+        docstring(res, &x.docs);
+        members(res, depth, &x.members);
+    }
+
+    fn members(res: &mut String, depth: usize, members: &[(String, Member)]) {
+        for (name, member) in members {
+            heading(res, depth + 1, name);
+            match member {
+                Member::Property(p) => {
+                    if let Some(t) = &p.typ {
+                        writeln!(res, "Type: `{}`\n", t.raw_type).unwrap();
+                    }
+                    docstring(res, &p.docs);
+                }
+                Member::Function(f) => function(res, name, f),
+                Member::Object(o) => object(res, depth + 1, o),
+            }
+        }
+    }
+
+    pub(super) fn item(res: &mut String, depth: usize, name: &str, x: &DocItem) {
+        heading(res, depth, name);
+        match x {
+            DocItem::Module(m) => {
+                docstring(res, &m.docs);
+                members(res, depth, &m.members);
+            }
+            DocItem::Object(o) => object(res, depth, o),
+            DocItem::Function(f) => function(res, name, f),
+        }
+    }
+}
+
 /// The main structure that represents the documentation for a given symbol / module.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Doc {
@@ -737,8 +876,7 @@ mod tests {
                                 typ: Some(Type {
                                     raw_type: "i32".to_owned(),
                                 }),
-                                // TODO: This should actually show '1'...
-                                default_value: Some("None".to_owned()),
+                                default_value: Some("1".to_owned()),
                             },
                         ],
                         ret: Return {
@@ -39,6 +39,7 @@ pub enum ValueError {
     IntegerOverflow,
     #[error("Type of parameters mismatch, expected `{0}`, actual `{1}`")]
     IncorrectParameterTypeWithExpected(String, String),
+    #[deprecated(note = "Parameter type errors are now reported as `UnpackValueError`")]
     #[error("Type of parameter `{0}` doesn't match, expected `{1}`, actual `{2}`")]
     IncorrectParameterTypeNamedWithExpected(String, String, String),
     #[error("Type of parameters mismatch")]
@@ -71,6 +72,25 @@ pub(crate) enum ControlError {
     TooManyRecursionLevel,
 }
 
+/// Context of [`ValueError::CannotMutateImmutableValue`] when the value is frozen.
+/// The address of the value is kept, so a function which reached the value
+/// through a captured variable can name the variable.
+#[derive(Debug, Error)]
+#[error("Value of type `{1}` is frozen")]
+pub(crate) struct FrozenValueMutated(pub(crate) usize, &'static str);
+
+impl FrozenValueMutated {
+    #[cold]
+    pub(crate) fn error(x: Value) -> anyhow::Error {
+        Self::add_context(ValueError::CannotMutateImmutableValue.into(), x)
+    }
+
+    #[cold]
+    pub(crate) fn add_context(e: anyhow::Error, x: Value) -> anyhow::Error {
+        e.context(FrozenValueMutated(x.ptr_value(), x.get_type()))
+    }
+}
+
 impl ValueError {
     #[cold]
     pub(crate) fn unsupported_owned<T>(
@@ -15,9 +15,19 @@
  * limitations under the License.
  */
 
-use std::{cell::RefCell, marker, marker::PhantomData};
+use std::{
+    cell::{Cell, RefCell},
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    fmt,
+    fmt::Display,
+    hash::{BuildHasher, Hash},
+    marker,
+    marker::PhantomData,
+};
 
+use anyhow::anyhow;
 use gazebo::prelude::*;
+use thiserror::Error;
 
 use crate::{
     collections::{
@@ -47,6 +57,26 @@ use crate::{
 ///     data: AdditionalData,
 /// }
 /// ```
+///
+/// Mutable values usually keep their contents in a [`RefCell`] (or a [`Cell`] for `Copy` types).
+/// Freezing a cell freezes its contents, and when deriving `Freeze` a cell field is frozen
+/// into a new cell of the frozen contents, so the frozen type mirrors the unfrozen one:
+///
+/// ```
+/// use std::cell::RefCell;
+///
+/// use starlark::values::{Freeze, Value};
+///
+/// #[derive(Freeze)]
+/// struct MutableGen<V> {
+///     items: RefCell<Vec<V>>,
+/// }
+///
+/// type Mutable<'v> = MutableGen<Value<'v>>;
+/// ```
+///
+/// A [`RefCell`] which is still borrowed when frozen fails with an error,
+/// which for derived implementations names the field.
 pub trait Freeze {
     /// When type is frozen, it is frozen into this type.
     type Frozen;
@@ -60,6 +90,47 @@ pub trait Freeze {
     fn freeze(self, freezer: &Freezer) -> anyhow::Result<Self::Frozen>;
 }
 
+/// An error from freezing a value, along with the path to that value from a module variable,
+/// e.g. `xs[1]["key"]`. The path is built up as the error passes back through the containers
+/// which were being frozen, so costs nothing unless freezing fails.
+/// The message only has the path, as the error is the [`source`](std::error::Error::source).
+#[derive(Debug, Error)]
+pub(crate) struct FreezeError {
+    /// Steps to the value, innermost first, e.g. `["key"]`, `[1]`, `xs`.
+    path: Vec<String>,
+    #[source]
+    error: anyhow::Error,
+}
+
+impl FreezeError {
+    /// Record that the value which failed to freeze was reached by `step`,
+    /// e.g. `[1]`, `.field` or a module variable name.
+    #[cold]
+    pub(crate) fn add_step(error: anyhow::Error, step: String) -> anyhow::Error {
+        match error.downcast::<FreezeError>() {
+            Ok(mut e) => {
+                e.path.push(step);
+                e.into()
+            }
+            Err(error) => FreezeError {
+                path: vec![step],
+                error,
+            }
+            .into(),
+        }
+    }
+}
+
+impl Display for FreezeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Failed to freeze `")?;
+        for step in self.path.iter().rev() {
+            f.write_str(step)?;
+        }
+        write!(f, "`")
+    }
+}
+
 impl Freeze for String {
     type Frozen = String;
 
@@ -76,6 +147,22 @@ impl Freeze for i32 {
     }
 }
 
+impl Freeze for u32 {
+    type Frozen = u32;
+
+    fn freeze(self, _freezer: &Freezer) -> anyhow::Result<u32> {
+        Ok(self)
+    }
+}
+
+impl Freeze for u64 {
+    type Frozen = u64;
+
+    fn freeze(self, _freezer: &Freezer) -> anyhow::Result<u64> {
+        Ok(self)
+    }
+}
+
 impl Freeze for usize {
     type Frozen = usize;
 
@@ -84,6 +171,14 @@ impl Freeze for usize {
     }
 }
 
+impl Freeze for () {
+    type Frozen = ();
+
+    fn freeze(self, _freezer: &Freezer) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
 impl Freeze for bool {
     type Frozen = bool;
 
@@ -111,6 +206,28 @@ where
     }
 }
 
+impl<T> Freeze for VecDeque<T>
+where
+    T: Freeze,
+{
+    type Frozen = VecDeque<T::Frozen>;
+
+    fn freeze(self, freezer: &Freezer) -> anyhow::Result<VecDeque<T::Frozen>> {
+        self.into_iter().map(|v| v.freeze(freezer)).collect()
+    }
+}
+
+impl<T> Freeze for Box<T>
+where
+    T: Freeze,
+{
+    type Frozen = Box<T::Frozen>;
+
+    fn freeze(self, freezer: &Freezer) -> anyhow::Result<Box<T::Frozen>> {
+        Ok(Box::new((*self).freeze(freezer)?))
+    }
+}
+
 impl<T> Freeze for RefCell<T>
 where
     T: Freeze,
@@ -118,10 +235,38 @@ where
     type Frozen = T::Frozen;
 
     fn freeze(self, freezer: &Freezer) -> anyhow::Result<T::Frozen> {
+        // Values are moved out of the heap when frozen, so a borrow which was never released
+        // is still recorded in the cell, and `into_inner` would silently ignore it.
+        if self.try_borrow_mut().is_err() {
+            return Err(anyhow!("`RefCell` is borrowed while being frozen"));
+        }
         self.into_inner().freeze(freezer)
     }
 }
 
+impl<T> Freeze for Cell<T>
+where
+    T: Freeze + Copy,
+{
+    type Frozen = T::Frozen;
+
+    fn freeze(self, freezer: &Freezer) -> anyhow::Result<T::Frozen> {
+        self.get().freeze(freezer)
+    }
+}
+
+impl<T1, T2> Freeze for (T1, T2)
+where
+    T1: Freeze,
+    T2: Freeze,
+{
+    type Frozen = (T1::Frozen, T2::Frozen);
+
+    fn freeze(self, freezer: &Freezer) -> anyhow::Result<Self::Frozen> {
+        Ok((self.0.freeze(freezer)?, self.1.freeze(freezer)?))
+    }
+}
+
 impl<T> Freeze for Option<T>
 where
     T: Freeze,
@@ -151,6 +296,32 @@ where
     }
 }
 
+/// Like freezing a [`SmallMap`], but if a value fails to freeze, the error records
+/// the step to it, as produced by `step` from its (frozen) key.
+pub(crate) fn freeze_small_map_with_path<K, V>(
+    mut map: SmallMap<K, V>,
+    freezer: &Freezer,
+    step: impl Fn(&K::Frozen) -> String,
+) -> anyhow::Result<SmallMap<K::Frozen, V::Frozen>>
+where
+    K: Freeze,
+    V: Freeze,
+{
+    map.maybe_drop_index();
+    let (entries, index) = map.into_raw_parts();
+    let buckets = entries
+        .buckets
+        .into_try_map(|Bucket { hash, key, value }| {
+            let key = key.freeze(freezer)?;
+            let value = match value.freeze(freezer) {
+                Ok(value) => value,
+                Err(e) => return Err(FreezeError::add_step(e, step(&key))),
+            };
+            anyhow::Ok(Bucket { hash, key, value })
+        })?;
+    unsafe { Ok(SmallMap::from_raw_parts(VecMap { buckets }, index)) }
+}
+
 impl<K, V> Freeze for SmallMap<K, V>
 where
     K: Freeze,
@@ -166,6 +337,44 @@ where
     }
 }
 
+// Keys of the std collections can't contain values (see `Trace`), so are kept as is.
+impl<K, V, S> Freeze for HashMap<K, V, S>
+where
+    K: Eq + Hash,
+    V: Freeze,
+    S: BuildHasher + Default,
+{
+    type Frozen = HashMap<K, V::Frozen, S>;
+
+    fn freeze(self, freezer: &Freezer) -> anyhow::Result<HashMap<K, V::Frozen, S>> {
+        self.into_iter()
+            .map(|(k, v)| anyhow::Ok((k, v.freeze(freezer)?)))
+            .collect()
+    }
+}
+
+impl<K, V> Freeze for BTreeMap<K, V>
+where
+    K: Ord,
+    V: Freeze,
+{
+    type Frozen = BTreeMap<K, V::Frozen>;
+
+    fn freeze(self, freezer: &Freezer) -> anyhow::Result<BTreeMap<K, V::Frozen>> {
+        self.into_iter()
+            .map(|(k, v)| anyhow::Ok((k, v.freeze(freezer)?)))
+            .collect()
+    }
+}
+
+impl<T, S> Freeze for HashSet<T, S> {
+    type Frozen = HashSet<T, S>;
+
+    fn freeze(self, _freezer: &Freezer) -> anyhow::Result<HashSet<T, S>> {
+        Ok(self)
+    }
+}
+
 impl<'v> Freeze for Value<'v> {
     type Frozen = FrozenValue;
 
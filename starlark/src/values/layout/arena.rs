@@ -41,7 +41,10 @@ use either::Either;
 use gazebo::prelude::*;
 
 use crate::values::{
-    layout::avalue::{AValue, AValueDyn, BlackHole},
+    layout::{
+        avalue::{AValue, AValueDyn, BlackHole},
+        pool::HeapPool,
+    },
     StarlarkValue,
 };
 
@@ -64,6 +67,8 @@ pub(crate) struct Arena {
     non_drop: Bump,
     /// Arena for things which might need dropping (e.g. Vec, with memory on heap)
     drop: Bump,
+    /// Where the bumps came from, and are returned to when the arena is dropped.
+    pool: Option<HeapPool>,
 }
 
 #[derive(Hash, PartialEq, Eq, Clone)]
@@ -165,9 +170,41 @@ pub struct HeapSummary {
     pub summary: HashMap<String, (usize, usize)>,
 }
 
+impl HeapSummary {
+    /// For each type name (as returned by `type()`), the number of values
+    /// and the total bytes they use.
+    pub fn summary(&self) -> HashMap<String, (usize, usize)> {
+        self.summary.clone()
+    }
+}
+
 impl Arena {
+    /// Create an arena whose memory is taken from (and returned to) a pool.
+    pub(crate) fn with_pool(pool: HeapPool) -> Self {
+        Self {
+            non_drop: pool.take(),
+            drop: pool.take(),
+            pool: Some(pool),
+        }
+    }
+
+    /// Create an empty arena using the same pool as this one.
+    pub(crate) fn new_like(&self) -> Self {
+        match &self.pool {
+            Some(pool) => Self::with_pool(pool.dupe()),
+            None => Self::default(),
+        }
+    }
+
     pub fn allocated_bytes(&self) -> usize {
-        self.drop.allocated_bytes() + self.non_drop.allocated_bytes()
+        let bytes = self.drop.allocated_bytes() + self.non_drop.allocated_bytes();
+        if self.pool.is_some() {
+            // A bump from the pool starts with all the memory the heap which used it
+            // grew to, so only count what has been filled, or limits would be hit early.
+            bytes - self.available_bytes()
+        } else {
+            bytes
+        }
     }
 
     pub fn available_bytes(&self) -> usize {
@@ -287,6 +324,19 @@ impl Arena {
     }
 
     fn iter_chunk<'a>(chunk: &'a [MaybeUninit<u8>], mut f: impl FnMut(&'a AValueHeader)) {
+        Self::iter_chunk_with_forwards(chunk, |_, x, _| {
+            if let Either::Left(x) = x {
+                f(x)
+            }
+        })
+    }
+
+    /// Iterate over a chunk, including values which have been overwritten with a forward,
+    /// giving the address of each and the size in bytes it takes, including the header.
+    fn iter_chunk_with_forwards<'a>(
+        chunk: &'a [MaybeUninit<u8>],
+        mut f: impl FnMut(usize, Either<&'a AValueHeader, &'a AValueForward>, usize),
+    ) {
         unsafe {
             // We only allocate trait ptr then a payload immediately after
             // so find the first trait ptr, see how big it is, and keep skipping.
@@ -294,17 +344,15 @@ impl Arena {
             let end = chunk.as_ptr().add(chunk.len());
             while p < end {
                 let or_forward = &*(p as *const AValueOrForward);
-                let n = match or_forward.unpack() {
-                    Either::Left(ptr) => {
-                        f(&or_forward.header);
-                        ptr.unpack().memory_size()
-                    }
+                let (x, n) = match or_forward.unpack() {
+                    Either::Left(ptr) => (Either::Left(ptr), ptr.unpack().memory_size()),
                     Either::Right(forward) => {
                         // Overwritten, so the next word will be the size of the memory
-                        forward.object_size
+                        (Either::Right(forward), forward.object_size)
                     }
                 };
                 let n = mem::size_of::<AValueHeader>() + n;
+                f(p as usize, x, n);
                 let n = cmp::max(n, MIN_ALLOC);
                 p = p.add(n);
                 // We know the alignment requirements will never be greater than AValuePtr
@@ -317,16 +365,38 @@ impl Arena {
     // Iterate over the values in the heap in the order they
     // were added.
     pub fn for_each_ordered<'a>(&'a mut self, mut f: impl FnMut(&'a AValueHeader)) {
+        self.for_each_ordered_with_forwards(|_, _, x, _| {
+            if let Either::Left(x) = x {
+                f(x)
+            }
+        })
+    }
+
+    /// Like [`for_each_ordered`](Arena::for_each_ordered), but also visits the values which
+    /// have been overwritten with a forward during GC, giving the address they were moved to.
+    /// Each call also gets whether the value is in the drop bump, all of which are visited
+    /// first, its address, and its size in bytes including the header.
+    pub(crate) fn for_each_ordered_with_forwards<'a>(
+        &'a mut self,
+        mut f: impl FnMut(bool, usize, Either<&'a AValueHeader, usize>, usize),
+    ) {
         // We get the chunks from most newest to oldest as per the bumpalo spec.
         // And within each chunk, the values are filled newest to oldest.
         // So need to do two sets of reversing.
-        for bump in [&mut self.drop, &mut self.non_drop] {
+        for (is_drop, bump) in [(true, &mut self.drop), (false, &mut self.non_drop)] {
             let chunks = bump.iter_allocated_chunks().collect::<Vec<_>>();
             // Use a single buffer to reduce allocations, but clear it after use
             let mut buffer = Vec::new();
             for chunk in chunks.iter().rev() {
-                Self::iter_chunk(chunk, |x| buffer.push(x));
-                buffer.iter().rev().for_each(|x| f(*x));
+                Self::iter_chunk_with_forwards(chunk, |p, x, n| {
+                    // The forward keeps the string tag of the pointer, so drop it.
+                    let x = x.map_right(|x| x.forward_ptr() & !(mem::align_of::<usize>() - 1));
+                    buffer.push((p, x, n))
+                });
+                buffer
+                    .iter()
+                    .rev()
+                    .for_each(|(p, x, n)| f(is_drop, *p, *x, *n));
                 buffer.clear();
             }
         }
@@ -355,6 +425,10 @@ impl Arena {
         let mut entries: HashMap<AValueHeader, (&'static str, (usize, usize))> = HashMap::new();
         let mut f = |x: &AValueHeader| {
             let v = x.unpack();
+            // Reserved values which are not yet filled have no type.
+            if v.is_black_hole() {
+                return;
+            }
             let e = entries
                 .entry(x.dupe())
                 .or_insert_with(|| (v.get_type(), (0, 0)));
@@ -509,6 +583,10 @@ impl Drop for Arena {
         });
         self.non_drop.reset();
         self.drop.reset();
+        if let Some(pool) = &self.pool {
+            pool.give(mem::take(&mut self.non_drop));
+            pool.give(mem::take(&mut self.drop));
+        }
     }
 }
 
@@ -594,4 +672,15 @@ mod tests {
         assert_eq!(entry.0, 2);
         assert_eq!(entry.1, arena.allocated_bytes());
     }
+
+    #[test]
+    fn test_allocated_summary_skips_blackhole() {
+        let arena = Arena::default();
+        arena.alloc(mk_str("test"));
+        // reserve but do not fill!
+        reserve_str(&arena, &mk_str(""));
+        let res = arena.allocated_summary().summary();
+        assert_eq!(res.len(), 1);
+        assert_eq!(res.values().next().unwrap().0, 1);
+    }
 }
@@ -22,6 +22,7 @@ use std::{
     fmt::{Debug, Display},
     mem,
     mem::MaybeUninit,
+    ops::ControlFlow,
     ptr,
     ptr::metadata,
 };
@@ -39,6 +40,7 @@ use crate::{
         bool::StarlarkBool,
         docs::DocItem,
         float::StarlarkFloat,
+        function::Signature,
         layout::arena::{AValueForward, AValueHeader, AValueRepr},
         list::{FrozenList, List, ListGen},
         none::NoneType,
@@ -46,9 +48,10 @@ use crate::{
         string::StarlarkStr,
         types::{
             array::Array,
+            promise::Promise,
             tuple::{FrozenTuple, Tuple},
         },
-        ComplexValue, Freezer, FrozenStringValue, FrozenValue, Heap, StarlarkValue,
+        ComplexValue, FreezeError, Freezer, FrozenStringValue, FrozenValue, Heap, StarlarkValue,
         StarlarkValueDyn, Trace, Tracer, Value, ValueTyped,
     },
 };
@@ -189,6 +192,11 @@ pub(crate) trait AValueDyn<'v>: StarlarkValueDyn<'v> {
 
     fn is_str(&self) -> bool;
 
+    /// Is this a placeholder for a reserved but not yet filled value?
+    fn is_black_hole(&self) -> bool {
+        false
+    }
+
     fn get_hash(&self) -> anyhow::Result<StarlarkHashValue>;
 }
 
@@ -291,6 +299,10 @@ pub(crate) fn float_avalue<'v>(x: StarlarkFloat) -> impl AValue<'v, ExtraElem =
     AValueImpl(Direct, x)
 }
 
+pub(crate) fn promise_avalue<'v>(x: Promise<'v>) -> impl AValue<'v, ExtraElem = ()> {
+    AValueImpl(Direct, x)
+}
+
 // A type where the second element is in control of what instances are in scope
 pub(crate) struct Direct;
 
@@ -463,7 +475,15 @@ impl<'v> AValue<'v> for AValueImpl<Direct, Tuple<'v>> {
         AValueHeader::overwrite_with_forward::<Self>(me, fv.0.ptr_value());
 
         // TODO: this allocation is unnecessary
-        let frozen_values = content.try_map(|v| freezer.freeze(*v))?;
+        let frozen_values = content
+            .iter()
+            .enumerate()
+            .map(|(i, v)| {
+                freezer
+                    .freeze(*v)
+                    .map_err(|e| FreezeError::add_step(e, format!("[{}]", i)))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
         r.fill(AValueImpl(Direct, FrozenTuple::new(content.len())));
         MaybeUninit::write_slice(extra, &frozen_values);
 
@@ -548,8 +568,11 @@ impl<'v> AValue<'v> for AValueImpl<Direct, ListGen<List<'v>>> {
         AValueHeader::overwrite_with_forward::<Self>(me, fv.0.ptr_value());
         r.fill(AValueImpl(Direct, ListGen(FrozenList::new(content.len()))));
         assert_eq!(extra.len(), content.len());
-        for (elem_place, elem) in extra.iter_mut().zip(content) {
-            elem_place.write(freezer.freeze(*elem)?);
+        for (i, (elem_place, elem)) in extra.iter_mut().zip(content).enumerate() {
+            let elem = freezer
+                .freeze(*elem)
+                .map_err(|e| FreezeError::add_step(e, format!("[{}]", i)))?;
+            elem_place.write(elem);
         }
         Ok(fv)
     }
@@ -625,11 +648,14 @@ impl<'v> AValue<'v> for AValueImpl<Direct, Array<'v>> {
 
         content.trace(tracer);
 
-        // Note when copying we are dropping extra capacity.
-        r.fill(AValueImpl(
-            Direct,
-            Array::new(content.len() as u32, content.len() as u32),
-        ));
+        // Note when copying we are dropping extra capacity,
+        // so lists grown by repeated `append` don't keep their slack after a GC.
+        // Freezing a list also allocates exactly `len` elements (see `FrozenList`).
+        let array = Array::new(content.len() as u32, content.len() as u32);
+        // The lists sharing the array all point at the copy, so it is still shared.
+        array.set_sharers(x.1.sharers());
+        array.set_heap(x.1.heap());
+        r.fill(AValueImpl(Direct, array));
         MaybeUninit::write_slice(extra, content);
         v
     }
@@ -736,6 +762,43 @@ where
     }
 }
 
+impl<'v> AValue<'v> for AValueImpl<Direct, Promise<'v>> {
+    type StarlarkValue = Promise<'v>;
+
+    type ExtraElem = ();
+
+    fn extra_len(&self) -> usize {
+        0
+    }
+
+    fn offset_of_extra() -> usize {
+        mem::size_of::<Self>()
+    }
+
+    unsafe fn heap_freeze(
+        me: *mut AValueRepr<Self>,
+        freezer: &Freezer,
+    ) -> anyhow::Result<FrozenValue> {
+        // A promise freezes to the frozen value it was set to. The promise is not overwritten
+        // with a forward, as the value might be an int, which is not on the heap,
+        // so each reference to the promise freezes its value again, which is cheap
+        // since the value has been forwarded (or is an int) after the first time.
+        let value = (*me)
+            .payload
+            .1
+            .freeze_value(&mut freezer.promises_never_set.borrow_mut());
+        match value {
+            Some(value) => freezer.freeze(value),
+            // Reported after freezing, so that all such promises are listed.
+            None => Ok(FrozenValue::new_none()),
+        }
+    }
+
+    unsafe fn heap_copy(me: *mut AValueRepr<Self>, tracer: &Tracer<'v>) -> Value<'v> {
+        Self::heap_copy_impl(me, tracer, Trace::trace)
+    }
+}
+
 #[derive(Debug, Display)]
 #[display(fmt = "BlackHole")]
 pub(crate) struct BlackHole(pub(crate) usize);
@@ -761,6 +824,10 @@ impl<'v> AValueDyn<'v> for BlackHole {
         false
     }
 
+    fn is_black_hole(&self) -> bool {
+        true
+    }
+
     fn get_hash(&self) -> anyhow::Result<StarlarkHashValue> {
         unreachable!()
     }
@@ -808,6 +875,9 @@ impl<'v> StarlarkValueDyn<'v> for BlackHole {
     fn documentation(&self) -> Option<DocItem> {
         panic!()
     }
+    fn signature(&self) -> Option<Signature<'v>> {
+        panic!()
+    }
     fn collect_repr(&self, _collector: &mut String) {
         panic!()
     }
@@ -877,12 +947,27 @@ impl<'v> StarlarkValueDyn<'v> for BlackHole {
     ) -> anyhow::Result<()> {
         panic!()
     }
+    fn for_each_element(
+        &self,
+        _heap: &'v Heap,
+        _f: &mut dyn FnMut(Value<'v>) -> ControlFlow<()>,
+    ) -> anyhow::Result<()> {
+        panic!()
+    }
     fn length(&self) -> anyhow::Result<i32> {
         panic!()
     }
     fn get_attr(&self, _attribute: &str, _heap: &'v Heap) -> Option<Value<'v>> {
         panic!()
     }
+    fn get_attr_method(
+        &self,
+        _me: Value<'v>,
+        _attribute: &str,
+        _heap: &'v Heap,
+    ) -> Option<Value<'v>> {
+        panic!()
+    }
     fn has_attr(&self, _attribute: &str) -> bool {
         panic!()
     }
@@ -898,6 +983,9 @@ impl<'v> StarlarkValueDyn<'v> for BlackHole {
     fn minus(&self, _heap: &'v Heap) -> anyhow::Result<Value<'v>> {
         panic!()
     }
+    fn bit_not(&self, _heap: &'v Heap) -> anyhow::Result<Value<'v>> {
+        panic!()
+    }
     fn radd(&self, _lhs: Value<'v>, _heap: &'v Heap) -> Option<anyhow::Result<Value<'v>>> {
         panic!()
     }
@@ -991,6 +1079,9 @@ impl<'v, Mode: 'static, T: StarlarkValue<'v>> StarlarkValueDyn<'v> for AValueImp
     fn documentation(&self) -> Option<DocItem> {
         self.1.documentation()
     }
+    fn signature(&self) -> Option<Signature<'v>> {
+        self.1.signature()
+    }
     fn collect_repr(&self, collector: &mut String) {
         self.1.collect_repr(collector)
     }
@@ -1060,12 +1151,22 @@ impl<'v, Mode: 'static, T: StarlarkValue<'v>> StarlarkValueDyn<'v> for AValueImp
     ) -> anyhow::Result<()> {
         self.1.with_iterator(heap, f)
     }
+    fn for_each_element(
+        &self,
+        heap: &'v Heap,
+        f: &mut dyn FnMut(Value<'v>) -> ControlFlow<()>,
+    ) -> anyhow::Result<()> {
+        self.1.for_each_element(heap, f)
+    }
     fn length(&self) -> anyhow::Result<i32> {
         self.1.length()
     }
     fn get_attr(&self, attribute: &str, heap: &'v Heap) -> Option<Value<'v>> {
         self.1.get_attr(attribute, heap)
     }
+    fn get_attr_method(&self, me: Value<'v>, attribute: &str, heap: &'v Heap) -> Option<Value<'v>> {
+        self.1.get_attr_method(me, attribute, heap)
+    }
     fn has_attr(&self, attribute: &str) -> bool {
         self.1.has_attr(attribute)
     }
@@ -1081,6 +1182,9 @@ impl<'v, Mode: 'static, T: StarlarkValue<'v>> StarlarkValueDyn<'v> for AValueImp
     fn minus(&self, heap: &'v Heap) -> anyhow::Result<Value<'v>> {
         self.1.minus(heap)
     }
+    fn bit_not(&self, heap: &'v Heap) -> anyhow::Result<Value<'v>> {
+        self.1.bit_not(heap)
+    }
     fn radd(&self, lhs: Value<'v>, heap: &'v Heap) -> Option<anyhow::Result<Value<'v>>> {
         self.1.radd(lhs, heap)
     }
@@ -55,6 +55,13 @@ impl<T: Default> Default for FastCell<T> {
 }
 
 impl<T> FastCell<T> {
+    pub(crate) fn new(value: T) -> Self {
+        Self {
+            value: UnsafeCell::new(MaybeUninit::new(value)),
+            init: Cell::new(true),
+        }
+    }
+
     /// Get a reference to the value.
     ///
     /// This operation is safe under assumption that other `unsafe` operations
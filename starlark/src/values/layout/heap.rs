@@ -22,11 +22,11 @@ use std::{
     fmt,
     fmt::{Debug, Display, Formatter},
     hash::{Hash, Hasher},
-    intrinsics::copy_nonoverlapping,
+    intrinsics::{copy_nonoverlapping, unlikely},
     marker::PhantomData,
     mem::MaybeUninit,
     ops::Deref,
-    ptr,
+    ptr, str,
     sync::Arc,
     usize,
 };
@@ -34,8 +34,10 @@ use std::{
 use either::Either;
 use gazebo::{cast, prelude::*};
 use once_cell::sync::Lazy;
+use thiserror::Error;
 
 use crate::{
+    collections::StarlarkHasher,
     eval::FrozenDef,
     values::{
         any::StarlarkAny,
@@ -45,23 +47,60 @@ use crate::{
             arena::{AValueHeader, AValueRepr, Arena, HeapSummary, Reservation},
             avalue::{
                 array_avalue, complex, float_avalue, frozen_list_avalue, frozen_tuple_avalue,
-                list_avalue, simple, starlark_str, tuple_avalue, AValue, VALUE_EMPTY_ARRAY,
-                VALUE_EMPTY_FROZEN_LIST, VALUE_EMPTY_TUPLE,
+                list_avalue, promise_avalue, simple, starlark_str, tuple_avalue, AValue,
+                VALUE_EMPTY_ARRAY, VALUE_EMPTY_FROZEN_LIST, VALUE_EMPTY_TUPLE,
             },
             fast_cell::FastCell,
+            pool::HeapPool,
             value::{FrozenValue, Value},
         },
-        types::float::StarlarkFloat,
+        types::{float::StarlarkFloat, function::BoundFunctionGen, promise::Promise},
         AllocFrozenValue, ComplexValue, FrozenRef, FrozenStringValue, FrozenValueTyped,
         StarlarkValue, StringValue, StringValueLike, ValueTyped,
     },
 };
 
+#[derive(Error, Debug)]
+pub(crate) enum HeapError {
+    #[error("Heap limit of {0} bytes exceeded, {1} bytes would be allocated")]
+    MaxBytesExceeded(usize, usize),
+}
+
+/// Garbage collection statistics for a [`Heap`], obtained from [`Heap::gc_stats`].
+#[derive(Debug, Clone, Copy, Dupe, Default, PartialEq, Eq)]
+pub struct GcStats {
+    /// Bytes currently allocated, the same as [`Heap::allocated_bytes`].
+    pub allocated_bytes: usize,
+    /// Bytes still allocated immediately after the most recent garbage collection,
+    /// or `0` if there hasn't been one.
+    pub retained_bytes: usize,
+    /// Number of garbage collections performed.
+    pub collections: usize,
+}
+
 /// A heap on which [`Value`]s can be allocated. The values will be annotated with the heap lifetime.
 #[derive(Default)]
 pub struct Heap {
     /// Peak memory seen when a garbage collection takes place (may be lower than currently allocated)
     peak_allocated: Cell<usize>,
+    /// Bytes allocated immediately after the last garbage collection
+    retained_after_gc: Cell<usize>,
+    /// Number of garbage collections performed
+    gc_count: Cell<usize>,
+    /// Limit on allocated bytes, set by `Evaluator::set_max_heap_bytes`
+    max_bytes: Cell<Option<usize>>,
+    /// Bytes allocated when an allocation went over `max_bytes`,
+    /// until the error is reported by `check_max_bytes`.
+    max_bytes_exceeded: Cell<Option<usize>>,
+    /// Memory outside the arena used by the values on this heap, e.g. the storage of
+    /// dictionaries and sets, as reported by
+    /// [`extra_memory`](crate::values::StarlarkValue::extra_memory).
+    /// Only counted while `max_bytes` is set, and recomputed by garbage collection.
+    extra_bytes: Cell<usize>,
+    /// Addresses of the lists iterating an array shared with other lists,
+    /// once per active iterator. The iteration count of a shared array can't tell
+    /// which of its lists are being iterated, so can't be modified.
+    shared_list_iterators: RefCell<Vec<usize>>,
     arena: FastCell<Arena>,
 }
 
@@ -80,8 +119,9 @@ impl Debug for Heap {
 /// Can be kept alive by a [`FrozenHeapRef`].
 #[derive(Default)]
 pub struct FrozenHeap {
-    arena: Arena,                          // My memory
-    refs: RefCell<HashSet<FrozenHeapRef>>, // Memory I depend on
+    arena: Arena,                                  // My memory
+    refs: RefCell<HashSet<FrozenHeapRef>>,         // Memory I depend on
+    interned: RefCell<HashSet<FrozenStringValue>>, // Strings from `intern_str`
 }
 
 /// `FrozenHeap` when it is no longer modified and can be share between threads.
@@ -91,6 +131,7 @@ pub struct FrozenHeap {
 struct FrozenFrozenHeap {
     arena: Arena,
     refs: HashSet<FrozenHeapRef>,
+    interned: HashSet<FrozenStringValue>,
 }
 
 // Safe because we never mutate the Arena other than with &mut
@@ -183,10 +224,15 @@ impl FrozenHeap {
     /// [`FrozenHeapRef`] which can be [`clone`](Clone::clone)d, shared between threads,
     /// and ensures the underlying values allocated on the [`FrozenHeap`] remain valid.
     pub fn into_ref(self) -> FrozenHeapRef {
-        let FrozenHeap { arena, refs } = self;
+        let FrozenHeap {
+            arena,
+            refs,
+            interned,
+        } = self;
         FrozenHeapRef(Arc::new(FrozenFrozenHeap {
             arena,
             refs: refs.into_inner(),
+            interned: interned.into_inner(),
         }))
     }
 
@@ -216,6 +262,35 @@ impl FrozenHeap {
         }
     }
 
+    /// Allocate a string on this heap, or return an identical string previously
+    /// interned on this heap or on a heap added with [`add_reference`](FrozenHeap::add_reference).
+    /// Interning the same string repeatedly returns the same pointer, so the strings
+    /// compare equal with [`ptr_eq`](Value::ptr_eq). The hash of the string is computed eagerly.
+    ///
+    /// Strings from [`alloc_str`](FrozenHeap::alloc_str) are not interned.
+    /// Empty and single character strings are statically allocated, so are always identical.
+    pub fn intern_str(&self, x: &str) -> FrozenStringValue {
+        if let Some(x) = constant_string(x) {
+            return x;
+        }
+        // Look at our own strings first, so later references can't change the answer
+        if let Some(x) = self.interned.borrow().get(x) {
+            return *x;
+        }
+        let found = self
+            .refs
+            .borrow()
+            .iter()
+            .find_map(|r| r.0.interned.get(x).copied());
+        let res = found.unwrap_or_else(|| {
+            let res = self.alloc_str(x);
+            res.get_hash();
+            res
+        });
+        self.interned.borrow_mut().insert(res);
+        res
+    }
+
     /// Allocate a tuple with the given elements on this heap.
     pub fn alloc_tuple<'v>(&'v self, elems: &[FrozenValue]) -> FrozenValue {
         if elems.is_empty() {
@@ -315,6 +390,8 @@ pub struct Freezer {
     pub(crate) heap: FrozenHeap,
     /// Defs frozen by this freezer.
     pub(crate) frozen_defs: RefCell<Vec<FrozenRef<'static, FrozenDef>>>,
+    /// Where the promises which were frozen without being set were created.
+    pub(crate) promises_never_set: RefCell<Vec<String>>,
 }
 
 impl Freezer {
@@ -322,6 +399,7 @@ impl Freezer {
         Freezer {
             heap,
             frozen_defs: RefCell::new(Vec::new()),
+            promises_never_set: RefCell::new(Vec::new()),
         }
     }
 
@@ -379,6 +457,20 @@ impl Heap {
         Self::default()
     }
 
+    /// Create a new [`Heap`] whose memory is taken from `pool` where possible,
+    /// and returned to it when the heap is dropped.
+    ///
+    /// Only the memory filled by values counts towards [`allocated_bytes`](Heap::allocated_bytes),
+    /// not the unused memory taken from the pool, so garbage collection and
+    /// [`Evaluator::set_max_heap_bytes`](crate::eval::Evaluator::set_max_heap_bytes)
+    /// behave as they would on a new heap.
+    pub fn new_with_pool(pool: &HeapPool) -> Self {
+        Self {
+            arena: FastCell::new(Arena::with_pool(pool.dupe())),
+            ..Self::default()
+        }
+    }
+
     /// Number of bytes allocated on this heap, not including any memory
     /// represented by [`extra_memory`](crate::values::StarlarkValue::extra_memory).
     pub fn allocated_bytes(&self) -> usize {
@@ -391,11 +483,98 @@ impl Heap {
         cmp::max(self.allocated_bytes(), self.peak_allocated.get())
     }
 
+    /// Statistics about the garbage collections performed on this heap.
+    pub fn gc_stats(&self) -> GcStats {
+        GcStats {
+            allocated_bytes: self.allocated_bytes(),
+            retained_bytes: self.retained_after_gc.get(),
+            collections: self.gc_count.get(),
+        }
+    }
+
     /// Number of bytes allocated by the heap but not yet filled.
     pub fn available_bytes(&self) -> usize {
         self.arena.borrow().available_bytes()
     }
 
+    pub(crate) fn add_shared_list_iterator(&self, list: usize) {
+        self.shared_list_iterators.borrow_mut().push(list);
+    }
+
+    pub(crate) fn remove_shared_list_iterator(&self, list: usize) {
+        let mut lists = self.shared_list_iterators.borrow_mut();
+        let i = lists.iter().rposition(|x| *x == list);
+        debug_assert!(i.is_some());
+        if let Some(i) = i {
+            lists.swap_remove(i);
+        }
+    }
+
+    pub(crate) fn has_shared_list_iterator(&self, list: usize) -> bool {
+        self.shared_list_iterators.borrow().contains(&list)
+    }
+
+    pub(crate) fn set_max_bytes(&self, bytes: usize) {
+        self.max_bytes.set(Some(bytes));
+    }
+
+    /// Fail if an allocation has taken the heap over the limit set by
+    /// [`Evaluator::set_max_heap_bytes`](crate::eval::Evaluator::set_max_heap_bytes),
+    /// or allocating `extra` more bytes would.
+    pub(crate) fn check_max_bytes(&self, extra: usize) -> anyhow::Result<()> {
+        if let Some(max) = self.max_bytes.get() {
+            let used = match self.max_bytes_exceeded.take() {
+                Some(used) => used,
+                None => self
+                    .allocated_bytes()
+                    .saturating_add(self.extra_bytes.get())
+                    .saturating_add(extra),
+            };
+            if used > max {
+                return Err(HeapError::MaxBytesExceeded(max, used).into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether an allocation has gone over the limit, without reporting it.
+    /// Cheap enough to call on every loop iteration.
+    #[inline(always)]
+    pub(crate) fn max_bytes_exceeded(&self) -> bool {
+        self.max_bytes_exceeded.get().is_some()
+    }
+
+    /// Called after each allocation in `arena`. Allocation can't fail,
+    /// so going over the limit is recorded for `check_max_bytes` to report.
+    #[inline(always)]
+    fn check_max_bytes_after_alloc(&self, arena: &Arena) {
+        if let Some(max) = self.max_bytes.get() {
+            let used = arena
+                .allocated_bytes()
+                .saturating_add(self.extra_bytes.get());
+            if unlikely(used > max) {
+                self.max_bytes_exceeded.set(Some(used));
+            }
+        }
+    }
+
+    /// Run `f`, which may change the [`extra_memory`](crate::values::StarlarkValue::extra_memory)
+    /// of `x`, e.g. by inserting into a dictionary, and count the change towards the limit.
+    /// `x` must not be borrowed when this is called or when `f` returns.
+    #[inline(always)]
+    pub(crate) fn charge_growth<R>(&self, x: Value, f: impl FnOnce() -> R) -> R {
+        if self.max_bytes.get().is_none() {
+            return f();
+        }
+        let before = x.get_ref().extra_memory();
+        let res = f();
+        let after = x.get_ref().extra_memory();
+        self.extra_bytes
+            .set((self.extra_bytes.get() + after).saturating_sub(before));
+        self.check_max_bytes_after_alloc(&self.arena.borrow());
+        res
+    }
+
     fn alloc_raw<'v, 'v2: 'v2>(&'v self, x: impl AValue<'v2, ExtraElem = ()>) -> Value<'v> {
         let arena_ref = self.arena.borrow();
         let arena = &*arena_ref;
@@ -404,10 +583,16 @@ impl Heap {
         // We have an arena inside a RefCell which stores ValueMem<'v>
         // However, we promise not to clear the RefCell other than for GC
         // so we can make the `arena` available longer
-        unsafe {
+        let value = unsafe {
             let value = Value::new_repr(cast::ptr_lifetime(v));
             transmute!(Value, Value, value)
+        };
+        if self.max_bytes.get().is_some() {
+            self.extra_bytes
+                .set(self.extra_bytes.get() + value.get_ref().extra_memory());
         }
+        self.check_max_bytes_after_alloc(arena);
+        value
     }
 
     fn alloc_raw_typed<'v, A: AValue<'v, ExtraElem = ()>>(
@@ -426,6 +611,7 @@ impl Heap {
         let arena = &*arena_ref;
         let (v, extra) = arena.alloc_extra_non_drop::<_>(starlark_str(len));
         init(extra.as_mut_ptr() as *mut u8);
+        self.check_max_bytes_after_alloc(arena);
 
         // We have an arena inside a RefCell which stores ValueMem<'v>
         // However, we promise not to clear the RefCell other than for GC
@@ -480,6 +666,55 @@ impl Heap {
         }
     }
 
+    /// Allocate an ASCII string on the heap, transforming each byte with `f`,
+    /// and computing the string hash in the same pass.
+    fn alloc_str_ascii_map<'v>(&'v self, x: &str, f: impl Fn(u8) -> u8) -> StringValue<'v> {
+        debug_assert!(x.is_ascii());
+        if x.len() <= 1 {
+            // Short strings are allocated statically.
+            let mut dest = [0; 1];
+            for (d, b) in dest.iter_mut().zip(x.bytes()) {
+                *d = f(b);
+            }
+            // `f` maps ASCII to ASCII, so the result is valid UTF-8.
+            return self.alloc_str(unsafe { str::from_utf8_unchecked(&dest[..x.len()]) });
+        }
+        let mut hasher = StarlarkHasher::new();
+        let res = self.alloc_str_init(x.len(), |dest| unsafe {
+            for (i, b) in x.bytes().enumerate() {
+                let b = f(b);
+                *dest.add(i) = b;
+                hasher.write_u8(b);
+            }
+        });
+        // Must match `hash_string_value`, which uses `str::hash`, terminating the string with `0xff`.
+        hasher.write_u8(0xff);
+        res.set_hash(hasher.finish_small());
+        res
+    }
+
+    /// Allocate a string on the heap, converted to lowercase.
+    /// Equivalent to allocating `x.to_lowercase()`, but ASCII strings are
+    /// converted and hashed in a single pass without an intermediate `String`.
+    pub fn alloc_str_lowered<'v>(&'v self, x: &str) -> StringValue<'v> {
+        if x.is_ascii() {
+            self.alloc_str_ascii_map(x, |b| b.to_ascii_lowercase())
+        } else {
+            self.alloc_str(&x.to_lowercase())
+        }
+    }
+
+    /// Allocate a string on the heap, converted to uppercase.
+    /// Equivalent to allocating `x.to_uppercase()`, but ASCII strings are
+    /// converted and hashed in a single pass without an intermediate `String`.
+    pub fn alloc_str_uppered<'v>(&'v self, x: &str) -> StringValue<'v> {
+        if x.is_ascii() {
+            self.alloc_str_ascii_map(x, |b| b.to_ascii_uppercase())
+        } else {
+            self.alloc_str(&x.to_uppercase())
+        }
+    }
+
     /// Allocate a tuple with the given elements.
     pub fn alloc_tuple<'v>(&'v self, elems: &[Value<'v>]) -> Value<'v> {
         if elems.is_empty() {
@@ -490,6 +725,7 @@ impl Heap {
             let arena = self.arena.borrow();
             let (avalue, extra) = arena.alloc_extra_non_drop(tuple_avalue(elems.len()));
             MaybeUninit::write_slice(extra, elems);
+            self.check_max_bytes_after_alloc(&arena);
             Value::new_repr(&*avalue)
         }
     }
@@ -500,10 +736,9 @@ impl Heap {
         }
 
         unsafe {
-            let (avalue, _) = self
-                .arena
-                .borrow()
-                .alloc_extra_non_drop(array_avalue(cap as u32));
+            let arena = self.arena.borrow();
+            let (avalue, _) = arena.alloc_extra_non_drop(array_avalue(cap as u32));
+            self.check_max_bytes_after_alloc(&arena);
             ValueTyped::new_repr(&*avalue)
         }
     }
@@ -515,15 +750,39 @@ impl Heap {
         self.alloc_raw(list_avalue(array))
     }
 
+    /// Allocate a list using an existing array, which may be shared with other lists.
+    pub(crate) fn alloc_list_array<'v>(&'v self, array: ValueTyped<'v, Array<'v>>) -> Value<'v> {
+        self.alloc_raw(list_avalue(array))
+    }
+
     /// Allocate a list with the given elements.
+    /// The list is sized from the lower bound of the iterator's `size_hint`, grows if needed,
+    /// and is shrunk to its length at the end.
     pub fn alloc_list_iter<'v>(&'v self, elems: impl IntoIterator<Item = Value<'v>>) -> Value<'v> {
         let elems = elems.into_iter();
-        let array = self.alloc_array(0);
+        let array = self.alloc_array(elems.size_hint().0);
         let list = self.alloc_raw_typed(list_avalue(array));
         list.0.extend(elems, self);
+        list.0.shrink(self);
         list.to_value()
     }
 
+    /// Allocate a list with the given elements, stopping at the first error.
+    /// Like [`alloc_list_iter`](Heap::alloc_list_iter), avoids collecting into an intermediate `Vec`.
+    pub fn try_alloc_list_iter<'v, E>(
+        &'v self,
+        elems: impl IntoIterator<Item = Result<Value<'v>, E>>,
+    ) -> Result<Value<'v>, E> {
+        let elems = elems.into_iter();
+        let array = self.alloc_array(elems.size_hint().0);
+        let list = self.alloc_raw_typed(list_avalue(array));
+        for x in elems {
+            list.0.push(x?, self);
+        }
+        list.0.shrink(self);
+        Ok(list.to_value())
+    }
+
     /// Allocate a list by concatenating two slices.
     pub(crate) fn alloc_list_concat<'v>(&'v self, a: &[Value<'v>], b: &[Value<'v>]) -> Value<'v> {
         let array = self.alloc_array(a.len() + b.len());
@@ -542,6 +801,10 @@ impl Heap {
         self.alloc_raw(float_avalue(f))
     }
 
+    pub(crate) fn alloc_promise<'v>(&'v self, x: Promise<'v>) -> Value<'v> {
+        self.alloc_raw(promise_avalue(x))
+    }
+
     /// Allocate a simple [`StarlarkValue`] on this heap.
     ///
     /// Simple value is any starlark value which:
@@ -560,6 +823,13 @@ impl Heap {
         self.alloc_raw(complex(x))
     }
 
+    /// Allocate a function which calls `function` with `this` prepended to its
+    /// positional arguments, so it behaves like a method of `this`.
+    /// Mostly useful to implement [`StarlarkValue::get_attr_method`].
+    pub fn alloc_bound_method<'v>(&'v self, this: Value<'v>, function: Value<'v>) -> Value<'v> {
+        self.alloc_complex(BoundFunctionGen { function, this })
+    }
+
     pub(crate) unsafe fn for_each_ordered<'v>(&'v self, mut f: impl FnMut(Value<'v>)) {
         (*self.arena.get_mut()).for_each_ordered(|x| {
             // Otherwise the Value is constrainted by the borrow_mut, when
@@ -571,6 +841,26 @@ impl Heap {
         })
     }
 
+    /// Call `f` on each value in the order they were allocated, separately for the values
+    /// which need `Drop`, which come first, and those that don't. Each call gets whether the
+    /// value needs `Drop`, its address, the value, and its size in bytes in the heap,
+    /// including the header but not any memory it owns outside the heap.
+    pub(crate) unsafe fn for_each_ordered_sized<'v>(
+        &'v self,
+        mut f: impl FnMut(bool, usize, Value<'v>, usize),
+    ) {
+        (*self.arena.get_mut()).for_each_ordered_with_forwards(|is_drop, address, x, bytes| {
+            if let Either::Left(x) = x {
+                f(
+                    is_drop,
+                    address,
+                    Value::new_ptr_query_is_str(unsafe { cast::ptr_lifetime(x) }),
+                    bytes,
+                )
+            }
+        })
+    }
+
     /// Garbage collect any values that are unused. This function is _unsafe_ in
     /// the sense that any `Value<'v>` not returned by `Tracer` _will become
     /// invalid_. Furthermore, any references to values, e.g `&'v str` will
@@ -578,21 +868,62 @@ impl Heap {
     pub(crate) unsafe fn garbage_collect<'v>(&'v self, f: impl FnOnce(&Tracer<'v>)) {
         // Record the highest peak, so it never decreases
         self.peak_allocated.set(self.peak_allocated_bytes());
-        self.garbage_collect_internal(f)
+        self.garbage_collect_internal(f);
+        self.retained_after_gc.set(self.allocated_bytes());
+        self.gc_count.set(self.gc_count.get() + 1);
     }
 
-    unsafe fn garbage_collect_internal<'v>(&'v self, f: impl FnOnce(&Tracer<'v>)) {
+    /// Like [`garbage_collect`](Heap::garbage_collect), but before the old values are freed,
+    /// calls `old` on each of them as [`for_each_ordered_sized`](Heap::for_each_ordered_sized)
+    /// would. The values which survived have been overwritten, so are given as the address
+    /// they were moved to.
+    pub(crate) unsafe fn garbage_collect_observe<'v>(
+        &'v self,
+        f: impl FnOnce(&Tracer<'v>),
+        mut old: impl FnMut(bool, usize, Either<Value<'v>, usize>, usize),
+    ) {
+        self.peak_allocated.set(self.peak_allocated_bytes());
+        let mut arena = self.garbage_collect_internal(f);
+        arena.for_each_ordered_with_forwards(|is_drop, address, x, bytes| {
+            old(
+                is_drop,
+                address,
+                x.map_left(|x| Value::new_ptr_query_is_str(unsafe { cast::ptr_lifetime(x) })),
+                bytes,
+            )
+        });
+        self.retained_after_gc.set(self.allocated_bytes());
+        self.gc_count.set(self.gc_count.get() + 1);
+    }
+
+    /// Returns the old arena, which must be kept until the GC is done.
+    unsafe fn garbage_collect_internal<'v>(&'v self, f: impl FnOnce(&Tracer<'v>)) -> Arena {
         // Must rewrite all Value's so they point at the new heap.
         // Take the arena out of the heap to make sure nobody allocates in it,
         // but hold the reference until the GC is done.
-        let _arena = self.arena.take();
+        let arena = self.arena.take();
 
         let tracer = Tracer::<'v> {
-            arena: Arena::default(),
+            arena: arena.new_like(),
             phantom: PhantomData,
         };
         f(&tracer);
-        self.arena.set(tracer.arena);
+        let mut new_arena = tracer.arena;
+        // Only the values which survived count towards the limit.
+        if self.max_bytes.get().is_some() {
+            let mut extra_bytes = 0;
+            // Values which own memory outside the arena need dropping.
+            new_arena.for_each_drop_unordered(|x| {
+                let x = x.unpack();
+                if !x.is_black_hole() {
+                    extra_bytes += x.extra_memory();
+                }
+            });
+            self.extra_bytes.set(extra_bytes);
+        }
+        self.arena.set(new_arena);
+        self.max_bytes_exceeded.set(None);
+        arena
     }
 
     /// Obtain a summary of how much memory is currently allocated by this heap.
@@ -660,9 +991,100 @@ impl<'v> Tracer<'v> {
     }
 }
 
-#[test]
-fn test_send_sync()
-where
-    FrozenHeapRef: Send + Sync,
-{
+#[cfg(test)]
+mod tests {
+    use std::mem;
+
+    use super::*;
+
+    #[test]
+    fn test_send_sync()
+    where
+        FrozenHeapRef: Send + Sync,
+    {
+    }
+
+    #[test]
+    fn test_allocated_summary() {
+        let heap = Heap::new();
+        for i in 0..5 {
+            heap.alloc_str(&format!("string {}", i));
+        }
+        let x = heap.alloc_str("element").to_value();
+        heap.alloc_list(&[x, x]);
+        heap.alloc_list(&[x]);
+        heap.alloc_tuple(&[x, x, x]);
+
+        let summary = heap.allocated_summary().summary();
+        // Strings include the `"element"` inside the containers.
+        assert_eq!(summary["string"].0, 6);
+        assert_eq!(summary["list"].0, 2);
+        assert_eq!(summary["tuple"].0, 1);
+        for (count, bytes) in summary.values() {
+            assert!(*bytes >= *count * mem::size_of::<AValueHeader>());
+        }
+    }
+
+    #[test]
+    fn test_alloc_str_lowered_uppered() {
+        let heap = Heap::new();
+        for x in [
+            "",
+            "a",
+            "B",
+            "Hello, World!",
+            "straße",
+            "STRASSE",
+            "ıİ dotless",
+            "ǅungla",
+            "ΣΑΣ",
+        ] {
+            let lower = heap.alloc_str_lowered(x);
+            assert_eq!(x.to_lowercase(), lower.as_str());
+            assert_eq!(
+                heap.alloc_str(&x.to_lowercase()).get_hash(),
+                lower.get_hash()
+            );
+            let upper = heap.alloc_str_uppered(x);
+            assert_eq!(x.to_uppercase(), upper.as_str());
+            assert_eq!(
+                heap.alloc_str(&x.to_uppercase()).get_hash(),
+                upper.get_hash()
+            );
+        }
+    }
+
+    #[test]
+    fn test_intern_str() {
+        fn same(x: FrozenStringValue, y: FrozenStringValue) -> bool {
+            x.unpack().to_value().ptr_eq(y.unpack().to_value())
+        }
+
+        let heap = FrozenHeap::new();
+        let x = heap.intern_str("interned");
+        assert!(same(x, heap.intern_str(&format!("in{}", "terned"))));
+        assert!(!same(x, heap.alloc_str("interned")));
+        assert_eq!(x.get_hash(), heap.alloc_str("interned").get_hash());
+        // Short strings are already unique
+        assert!(same(heap.intern_str("a"), heap.alloc_str("a")));
+        assert!(same(heap.intern_str(""), heap.alloc_str("")));
+
+        let heap = heap.into_ref();
+        let child = FrozenHeap::new();
+        child.add_reference(&heap);
+        assert!(same(x, child.intern_str("interned")));
+        assert!(same(child.intern_str("other"), child.intern_str("other")));
+    }
+
+    #[test]
+    fn test_frozen_allocated_summary() {
+        let heap = FrozenHeap::new();
+        let x = heap.alloc_str("element").unpack();
+        heap.alloc_tuple(&[x, x]);
+        heap.alloc_list(&[x]);
+        let summary = heap.into_ref().allocated_summary().summary();
+        assert_eq!(summary["string"].0, 1);
+        assert_eq!(summary["tuple"].0, 1);
+        assert_eq!(summary["list"].0, 1);
+    }
 }
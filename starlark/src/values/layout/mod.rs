@@ -18,7 +18,10 @@
 // Possible optimisations:
 // Encoding none, bool etc in the pointer of frozen value
 
-pub use heap::{Freezer, FrozenHeap, FrozenHeapRef, Heap, Tracer};
+pub use arena::HeapSummary;
+pub(crate) use heap::HeapError;
+pub use heap::{Freezer, FrozenHeap, FrozenHeapRef, GcStats, Heap, Tracer};
+pub use pool::HeapPool;
 pub(crate) use pointer_i32::PointerI32;
 pub(crate) use string::StringValueLike;
 pub use string::{static_string::*, FrozenStringValue, StringValue};
@@ -31,6 +34,7 @@ mod heap;
 pub(crate) mod identity;
 mod pointer;
 mod pointer_i32;
+mod pool;
 mod string;
 pub(crate) mod typed;
 pub(crate) mod value;
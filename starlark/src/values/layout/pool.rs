@@ -0,0 +1,235 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! A pool of arena memory which heaps hand back when they are dropped,
+//! so the next heap can reuse it rather than allocating its own.
+
+use std::{
+    fmt::{self, Debug},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use bumpalo::Bump;
+use gazebo::prelude::*;
+
+struct HeapPoolInner {
+    max_bytes: usize,
+    /// The retained bumps, and the total bytes they hold.
+    bumps: Mutex<(Vec<Bump>, usize)>,
+    /// Number of bumps created because the pool was empty.
+    created: AtomicUsize,
+}
+
+/// A pool of memory shared by heaps created with [`Heap::new_with_pool`](crate::values::Heap::new_with_pool).
+///
+/// When such a heap is dropped (or garbage collected), the memory backing its values
+/// is returned to the pool, and handed to the next heap created with the pool,
+/// saving the cost of allocating and freeing it each time.
+/// Only the largest chunk of memory of each arena is kept, and it is not zeroed.
+/// The pool holds at most `max_bytes` bytes, any more memory returned to it is freed.
+///
+/// The pool is cheap to clone, and clones share the same memory.
+/// It can be used from several threads at once, so can serve a pool of workers.
+/// The memory is freed when the pool and all heaps using it are dropped.
+#[derive(Clone, Dupe)]
+pub struct HeapPool(Arc<HeapPoolInner>);
+
+impl Debug for HeapPool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HeapPool")
+            .field("max_bytes", &self.0.max_bytes)
+            .field("retained_bytes", &self.retained_bytes())
+            .finish()
+    }
+}
+
+impl HeapPool {
+    /// Create a pool which keeps at most `max_bytes` bytes of memory.
+    pub fn new(max_bytes: usize) -> Self {
+        Self(Arc::new(HeapPoolInner {
+            max_bytes,
+            bumps: Mutex::new((Vec::new(), 0)),
+            created: AtomicUsize::new(0),
+        }))
+    }
+
+    /// The limit on the bytes kept by the pool, as passed to [`HeapPool::new`].
+    pub fn max_bytes(&self) -> usize {
+        self.0.max_bytes
+    }
+
+    /// The number of bytes of memory currently kept by the pool, waiting to be reused.
+    pub fn retained_bytes(&self) -> usize {
+        self.0.bumps.lock().unwrap().1
+    }
+
+    /// Number of bumps which had to be created because the pool had nothing to reuse.
+    #[cfg(test)]
+    pub(crate) fn created(&self) -> usize {
+        self.0.created.load(Ordering::Relaxed)
+    }
+
+    /// Get an empty bump, reusing one from the pool if there is one.
+    pub(crate) fn take(&self) -> Bump {
+        let mut bumps = self.0.bumps.lock().unwrap();
+        match bumps.0.pop() {
+            Some(bump) => {
+                bumps.1 -= bump.allocated_bytes();
+                bump
+            }
+            None => {
+                self.0.created.fetch_add(1, Ordering::Relaxed);
+                Bump::new()
+            }
+        }
+    }
+
+    /// Return a bump to the pool, which must not contain anything that needs dropping.
+    pub(crate) fn give(&self, mut bump: Bump) {
+        // Frees all but the last (and largest) chunk.
+        bump.reset();
+        let bytes = bump.allocated_bytes();
+        let mut bumps = self.0.bumps.lock().unwrap();
+        if bumps.1 + bytes <= self.0.max_bytes {
+            bumps.0.push(bump);
+            bumps.1 += bytes;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        environment::{Globals, Module},
+        eval::Evaluator,
+        syntax::{AstModule, Dialect},
+        values::Heap,
+    };
+
+    fn alloc_some(heap: &Heap) {
+        for i in 0..1000 {
+            let s = heap.alloc_str(&format!("string {}", i)).to_value();
+            heap.alloc_list(&[s, s]);
+        }
+    }
+
+    #[test]
+    fn test_pool_reuses_arenas() {
+        let pool = HeapPool::new(10_000_000);
+        for _ in 0..100 {
+            alloc_some(&Heap::new_with_pool(&pool));
+        }
+        // The first heap creates a bump for values which need dropping and one for those
+        // which don't, after which they are reused.
+        assert_eq!(pool.created(), 2);
+        assert!(pool.retained_bytes() > 0);
+        assert!(pool.retained_bytes() <= pool.max_bytes());
+
+        // Heaps alive at the same time can't share.
+        let heaps = (0..3)
+            .map(|_| Heap::new_with_pool(&pool))
+            .collect::<Vec<_>>();
+        heaps.iter().for_each(alloc_some);
+        assert_eq!(pool.created(), 6);
+    }
+
+    #[test]
+    fn test_pool_max_bytes() {
+        let pool = HeapPool::new(0);
+        for _ in 0..10 {
+            alloc_some(&Heap::new_with_pool(&pool));
+        }
+        assert_eq!(pool.created(), 20);
+        assert_eq!(pool.retained_bytes(), 0);
+    }
+
+    #[test]
+    fn test_pool_max_heap_bytes() {
+        fn eval(module: Module, max_bytes: usize) -> anyhow::Result<usize> {
+            let ast = AstModule::parse(
+                "pool.star",
+                "xs = [str(i) for i in range(1000)]\n".to_owned(),
+                &Dialect::Standard,
+            )
+            .unwrap();
+            let globals = Globals::standard();
+            let mut eval = Evaluator::new(&module);
+            eval.set_max_heap_bytes(max_bytes);
+            eval.eval_module(ast, &globals)?;
+            drop(eval);
+            Ok(module.heap().allocated_bytes())
+        }
+
+        let pool = HeapPool::new(100_000_000);
+        let heap = Heap::new_with_pool(&pool);
+        for _ in 0..100 {
+            alloc_some(&heap);
+        }
+        drop(heap);
+
+        // The pool holds more memory than the limit, which must not count against it.
+        let max_bytes = eval(Module::new(), usize::MAX).unwrap() * 2;
+        assert!(pool.retained_bytes() > max_bytes);
+        assert_eq!(Heap::new_with_pool(&pool).allocated_bytes(), 0);
+        assert!(eval(Module::new_with_pool(&pool), max_bytes).unwrap() <= max_bytes);
+        assert!(eval(Module::new_with_pool(&pool), 1000).is_err());
+    }
+
+    #[test]
+    fn test_pool_eval() {
+        fn eval(module: Module) -> String {
+            let ast = AstModule::parse(
+                "pool.star",
+                r#"
+def f(n):
+    return [str(i) * 2 for i in range(n)]
+xs = f(100)
+garbage = [list(range(10)) for _ in range(100)]
+",".join(xs[-3:]) + " " + str(len(garbage)) + " " + repr({"x": xs[1:3]})
+"#
+                .to_owned(),
+                &Dialect::Standard,
+            )
+            .unwrap();
+            let globals = Globals::standard();
+            let mut eval = Evaluator::new(&module);
+            // Make sure the garbage collections take from and return to the pool too.
+            eval.set_gc_threshold(1000);
+            eval.eval_module(ast, &globals).unwrap().to_str()
+        }
+
+        let expected = eval(Module::new());
+        assert_eq!(expected, r#"9797,9898,9999 100 {"x": ["11", "22"]}"#);
+        let pool = HeapPool::new(10_000_000);
+        for _ in 0..20 {
+            assert_eq!(expected, eval(Module::new_with_pool(&pool)));
+        }
+        // At most the old and new arenas during a garbage collection.
+        assert!(pool.created() <= 4);
+    }
+
+    #[test]
+    fn test_send_sync()
+    where
+        HeapPool: Send + Sync,
+    {
+    }
+}
@@ -24,7 +24,6 @@ use std::{
     fmt::{Debug, Display, Formatter},
     hash::{Hash, Hasher},
     ops::Deref,
-    ptr,
 };
 
 use gazebo::{
@@ -108,7 +107,8 @@ impl<'v> Deref for StringValue<'v> {
 
 impl PartialEq for FrozenStringValue {
     fn eq(&self, other: &Self) -> bool {
-        ptr::eq(self, other) || self.as_str() == other.as_str()
+        // Type strings and interned strings are usually the same pointer.
+        self.0.to_value().ptr_eq(other.0.to_value()) || self.as_str() == other.as_str()
     }
 }
 
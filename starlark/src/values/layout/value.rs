@@ -33,6 +33,8 @@ use std::{
     cmp::Ordering,
     fmt,
     fmt::{Debug, Display},
+    io,
+    ops::ControlFlow,
 };
 
 use either::Either;
@@ -49,11 +51,12 @@ use crate::{
     collections::{Hashed, StarlarkHashValue, StarlarkHasher},
     eval::{runtime::call_stack::FrozenFileSpan, Arguments, Evaluator, FrozenDef},
     values::{
-        dict::FrozenDict,
+        dict::{DictRef, FrozenDict},
         docs::DocItem,
         enumeration::{EnumType, FrozenEnumValue},
+        error::FrozenValueMutated,
         float::StarlarkFloat,
-        function::{FrozenBoundMethod, NativeFunction, FUNCTION_TYPE},
+        function::{FrozenBoundMethod, NativeFunction, Signature, FUNCTION_TYPE},
         identity::ValueIdentity,
         layout::{
             arena::{AValueHeader, AValueRepr},
@@ -64,11 +67,12 @@ use crate::{
             pointer::{FrozenPointer, Pointer},
             pointer_i32::PointerI32,
         },
-        list::FrozenList,
+        list::{FrozenList, ListRef},
         num::Num,
         range::Range,
         record::{FrozenRecord, RecordType},
         recursive_repr_or_json_guard::{json_stack_push, repr_stack_push},
+        repr_limits::{collect_repr_limited, repr_limits},
         stack_guard,
         string::StarlarkStr,
         structs::FrozenStruct,
@@ -304,6 +308,46 @@ impl<'v> Value<'v> {
         self.unpack_starlark_str().map(|s| s.unpack())
     }
 
+    /// Unpack the value as a `T`, or give an [`UnpackValueError`](crate::values::UnpackValueError)
+    /// naming the expected type and describing the value, the same as for a mistyped argument
+    /// to a [`#[starlark_module]`](macro@starlark_module) function.
+    pub fn unpack_err<T: UnpackValue<'v>>(self) -> anyhow::Result<T> {
+        T::unpack_param(self)
+    }
+
+    /// Like [`unpack_err`](Value::unpack_err), but the error says the value
+    /// was passed as the parameter `name`.
+    pub fn unpack_named_err<T: UnpackValue<'v>>(self, name: &str) -> anyhow::Result<T> {
+        T::unpack_named_param(self, name)
+    }
+
+    /// Like [`unpack_str`](Value::unpack_str), but with an error as for [`unpack_err`](Value::unpack_err).
+    pub fn unpack_str_err(self) -> anyhow::Result<&'v str> {
+        self.unpack_err()
+    }
+
+    /// Like [`unpack_int`](Value::unpack_int), but with an error as for [`unpack_err`](Value::unpack_err).
+    pub fn unpack_int_err(self) -> anyhow::Result<i32> {
+        self.unpack_err()
+    }
+
+    /// Like [`unpack_bool`](Value::unpack_bool), but with an error as for [`unpack_err`](Value::unpack_err).
+    pub fn unpack_bool_err(self) -> anyhow::Result<bool> {
+        self.unpack_err()
+    }
+
+    /// Obtain the underlying list, with an error as for [`unpack_err`](Value::unpack_err)
+    /// if it isn't one.
+    pub fn unpack_list_err(self) -> anyhow::Result<&'v ListRef<'v>> {
+        self.unpack_err()
+    }
+
+    /// Obtain the underlying dictionary, with an error as for [`unpack_err`](Value::unpack_err)
+    /// if it isn't one.
+    pub fn unpack_dict_err(self) -> anyhow::Result<DictRef<'v>> {
+        self.unpack_err()
+    }
+
     /// Get a pointer to a [`AValue`].
     pub(crate) fn get_ref(self) -> &'v dyn AValueDyn<'v> {
         match self.0.unpack() {
@@ -424,6 +468,11 @@ impl<'v> Value<'v> {
         self.get_ref().minus(heap)
     }
 
+    /// `~x`.
+    pub fn bit_not(self, heap: &'v Heap) -> anyhow::Result<Value<'v>> {
+        self.get_ref().bit_not(heap)
+    }
+
     /// `x - other`.
     pub fn sub(self, other: Value<'v>, heap: &'v Heap) -> anyhow::Result<Value<'v>> {
         self.get_ref().sub(other, heap)
@@ -560,7 +609,21 @@ impl<'v> Value<'v> {
     ///
     /// Return an error if the value or any contained value does not support conversion to JSON.
     pub fn to_json(self) -> anyhow::Result<String> {
-        serde_json::to_string(&self).map_err(|e| anyhow::anyhow!(e))
+        let mut res = Vec::with_capacity(128);
+        self.to_json_writer(&mut res)?;
+        // `serde_json` never emits invalid UTF-8.
+        Ok(unsafe { String::from_utf8_unchecked(res) })
+    }
+
+    /// Convert the value to JSON, streaming the output to `writer` rather than
+    /// building an intermediate [`String`].
+    ///
+    /// Return an error if the value or any contained value does not support conversion to JSON,
+    /// in which case some output may already have been written.
+    pub fn to_json_writer(self, writer: &mut dyn io::Write) -> anyhow::Result<()> {
+        let mut serializer = serde_json::Serializer::new(writer);
+        self.serialize(&mut serializer)
+            .map_err(|e| anyhow::anyhow!(e))
     }
 
     /// Forwards to [`StarlarkValue::set_attr`].
@@ -570,7 +633,14 @@ impl<'v> Value<'v> {
 
     /// Forwards to [`StarlarkValue::set_at`].
     pub fn set_at(self, index: Value<'v>, alloc_value: Value<'v>) -> anyhow::Result<()> {
-        self.get_ref().set_at(index, alloc_value)
+        self.get_ref().set_at(index, alloc_value).map_err(|e| {
+            match e.downcast_ref::<ValueError>() {
+                Some(ValueError::CannotMutateImmutableValue) if self.unpack_frozen().is_some() => {
+                    FrozenValueMutated::add_context(e, self)
+                }
+                _ => e,
+            }
+        })
     }
 
     /// Forwards to [`StarlarkValue::documentation`].
@@ -578,6 +648,11 @@ impl<'v> Value<'v> {
         self.get_ref().documentation()
     }
 
+    /// Forwards to [`StarlarkValue::signature`].
+    pub fn signature(self) -> Option<Signature<'v>> {
+        self.get_ref().signature()
+    }
+
     /// Return the contents of an iterable collection, as an owned vector.
     pub fn iterate_collect(self, heap: &'v Heap) -> anyhow::Result<Vec<Value<'v>>> {
         // You might reasonably think this is mostly called on lists (I think it is),
@@ -601,6 +676,16 @@ impl<'v> Value<'v> {
         Ok(res.take().expect("with_iterator to call the callback"))
     }
 
+    /// Call `f` on each element of an iterable value, stopping early
+    /// if it returns [`ControlFlow::Break`].
+    pub fn for_each_element(
+        self,
+        heap: &'v Heap,
+        mut f: impl FnMut(Value<'v>) -> ControlFlow<()>,
+    ) -> anyhow::Result<()> {
+        self.get_ref().for_each_element(heap, &mut f)
+    }
+
     /// Produce an iterable from a value.
     pub fn iterate(
         self,
@@ -652,6 +737,9 @@ impl<'v> Value<'v> {
                 return Ok(Some(MaybeUnboundValue::new(v).bind(self, heap)?));
             }
         }
+        if let Some(v) = aref.get_attr_method(self, attribute, heap) {
+            return Ok(Some(v));
+        }
         Ok(aref.get_attr(attribute, heap))
     }
 
@@ -779,6 +867,11 @@ impl FrozenValue {
         Value::new_frozen(self)
     }
 
+    /// Forwards to [`Value::to_json_writer`].
+    pub fn to_json_writer(self, writer: &mut dyn io::Write) -> anyhow::Result<()> {
+        self.to_value().to_json_writer(writer)
+    }
+
     /// Is this type builtin? We perform certain optimizations only on builtin types
     /// because we know they have well defined semantics.
     pub(crate) fn is_builtin(self) -> bool {
@@ -932,13 +1025,17 @@ impl<'v> ValueLike<'v> for Value<'v> {
     }
 
     fn collect_repr(self, collector: &mut String) {
-        match repr_stack_push(self) {
+        let collect = |collector: &mut String| match repr_stack_push(self) {
             Ok(_guard) => {
                 self.get_ref().collect_repr(collector);
             }
             Err(..) => {
                 self.get_ref().collect_repr_cycle(collector);
             }
+        };
+        match repr_limits() {
+            None => collect(collector),
+            Some(limits) => collect_repr_limited(collector, limits, collect),
         }
     }
 
@@ -949,6 +1046,9 @@ impl<'v> ValueLike<'v> for Value<'v> {
     fn equals(self, other: Value<'v>) -> anyhow::Result<bool> {
         if self.ptr_eq(other) {
             Ok(true)
+        } else if let (Some(a), Some(b)) = (self.unpack_str(), other.unpack_str()) {
+            // Strings can't recurse, so skip the stack guard and dynamic dispatch.
+            Ok(a == b)
         } else {
             let _guard = stack_guard::stack_guard()?;
             self.get_ref().equals(other)
@@ -1002,3 +1102,69 @@ where
     FrozenValue: Send + Sync,
 {
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        environment::{Globals, Module},
+        eval::Evaluator,
+        syntax::{AstModule, Dialect},
+        values::Value,
+    };
+
+    fn eval<'v>(module: &'v Module, program: &str) -> anyhow::Result<Value<'v>> {
+        let mut eval = Evaluator::new(module);
+        let ast = AstModule::parse("json.star", program.to_owned(), &Dialect::Extended)?;
+        eval.eval_module(ast, &Globals::extended())
+    }
+
+    fn to_json_writer_string(x: Value) -> anyhow::Result<String> {
+        let mut res = Vec::new();
+        x.to_json_writer(&mut res)?;
+        Ok(String::from_utf8(res).unwrap())
+    }
+
+    #[test]
+    fn test_to_json_writer_same_as_to_string() {
+        let module = Module::new();
+        for program in [
+            "{'a': 1, 'b': [True, None], 'c': {'d': 'e\\n'}}",
+            "[[1, [2, [3, []]]], (4, 5)]",
+            "[1.5, 2.0, -0.0, 1e100, 0.1]",
+            "struct(x = 1, y = [struct(z = 'q')])",
+        ] {
+            let value = eval(&module, program).unwrap();
+            let expected = serde_json::to_string(&value).unwrap();
+            assert_eq!(expected, value.to_json().unwrap());
+            assert_eq!(expected, to_json_writer_string(value).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_to_json_writer_frozen() {
+        let module = Module::new();
+        module.set("x", eval(&module, "{'a': [1, 2.0]}").unwrap());
+        let module = module.freeze().unwrap();
+        let x = module.get("x").unwrap();
+        let mut res = Vec::new();
+        x.value()
+            .unpack_frozen()
+            .unwrap()
+            .to_json_writer(&mut res)
+            .unwrap();
+        assert_eq!(r#"{"a":[1,2.0]}"#, String::from_utf8(res).unwrap());
+    }
+
+    #[test]
+    fn test_to_json_writer_errors() {
+        let module = Module::new();
+        let cycle = eval(&module, "x = []; x.append(x); x").unwrap();
+        assert!(to_json_writer_string(cycle)
+            .unwrap_err()
+            .to_string()
+            .contains("Cycle detected"));
+        let function = eval(&module, "def f(): pass\n[f]").unwrap();
+        assert!(to_json_writer_string(function).is_err());
+        assert!(function.to_json().is_err());
+    }
+}
@@ -43,6 +43,7 @@ pub use crate::values::{
     frozen_ref::*,
     layout::{identity::*, value::*, *},
     owned::*,
+    repr_limits::ReprLimits,
     trace::*,
     traits::*,
     typed::*,
@@ -67,6 +68,7 @@ pub(crate) mod layout;
 pub(crate) mod num;
 mod owned;
 pub(crate) mod recursive_repr_or_json_guard;
+pub(crate) mod repr_limits;
 mod stack_guard;
 mod trace;
 mod traits;
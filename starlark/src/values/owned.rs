@@ -21,7 +21,7 @@ use gazebo::prelude::*;
 
 use crate::values::{
     none::NoneType, AllocFrozenValue, FrozenHeap, FrozenHeapRef, FrozenValue, FrozenValueTyped,
-    StarlarkValue, Value,
+    StarlarkValue, Value, ValueLike,
 };
 
 /// A [`FrozenValue`] along with a [`FrozenHeapRef`] that ensures it is kept alive.
@@ -101,6 +101,12 @@ impl OwnedFrozenValue {
         }
     }
 
+    /// Obtain a reference to the underlying `<T>`, or [`None`] if `self` does not reference `<T>`.
+    /// The reference is only valid while this [`OwnedFrozenValue`] is alive.
+    pub fn downcast_ref<T: StarlarkValue<'static>>(&self) -> Option<&T> {
+        self.value.downcast_ref::<T>()
+    }
+
     /// Obtain the [`Value`] stored inside.
     pub fn value<'v>(&'v self) -> Value<'v> {
         Value::new_frozen(self.value)
@@ -106,6 +106,14 @@ pub(crate) fn repr_stack_push(value: Value) -> Result<ReprStackGuard, ReprCycle>
     }
 }
 
+/// Number of values whose `repr` is currently being collected.
+pub(crate) fn repr_stack_len() -> usize {
+    let stack = REPR_STACK.take();
+    let len = stack.len();
+    REPR_STACK.set(stack);
+    len
+}
+
 /// Push a value to the stack, return error if it is already on the stack.
 pub(crate) fn json_stack_push(value: Value) -> Result<JsonStackGuard, JsonCycle> {
     let mut stack = JSON_STACK.take();
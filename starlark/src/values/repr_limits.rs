@@ -0,0 +1,150 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Limit the size of `repr` output produced implicitly, e.g. for error messages or `print`.
+//!
+//! The limits are per-thread, set for the duration of evaluation from
+//! [`Evaluator::set_repr_limits`](crate::eval::Evaluator::set_repr_limits).
+//! Explicit conversions (`repr()`, `str()` and string formatting) are never limited.
+
+use std::cell::Cell;
+
+use gazebo::prelude::*;
+
+use crate::values::{recursive_repr_or_json_guard::repr_stack_len, Value, ValueLike};
+
+/// Appended where a `repr` was truncated.
+pub(crate) const TRUNCATED: &str = "...<truncated>";
+
+/// Limits on the size of `repr` output produced implicitly during evaluation,
+/// such as reprs in error messages or printed by `print()`.
+/// When a limit is hit the output is truncated with `...<truncated>`.
+#[derive(Debug, Clone, Copy, Dupe, PartialEq, Eq)]
+pub struct ReprLimits {
+    /// Maximum length in bytes of the output, not counting the truncation marker.
+    pub max_len: usize,
+    /// Maximum depth of nested values, beyond which nested values are replaced
+    /// by the truncation marker.
+    pub max_depth: usize,
+}
+
+impl Default for ReprLimits {
+    fn default() -> Self {
+        Self {
+            max_len: 10_000,
+            max_depth: 100,
+        }
+    }
+}
+
+/// State of the outermost limited `repr` being collected.
+#[derive(Clone, Copy)]
+struct ReprState {
+    /// The buffer the outermost `repr` is collected into.
+    buffer: *const String,
+    /// Length of the buffer before the outermost `repr` started.
+    start: usize,
+    /// Length of the buffer after the truncation marker was written, if it has been.
+    truncated: Option<usize>,
+}
+
+#[thread_local]
+static REPR_LIMITS: Cell<Option<ReprLimits>> = Cell::new(None);
+
+#[thread_local]
+static REPR_STATE: Cell<Option<ReprState>> = Cell::new(None);
+
+/// Restore the previous limits on drop.
+pub(crate) struct ReprLimitsGuard(Option<ReprLimits>);
+
+impl Drop for ReprLimitsGuard {
+    fn drop(&mut self) {
+        REPR_LIMITS.set(self.0);
+    }
+}
+
+/// Apply `limits` to implicit reprs until the guard is dropped.
+pub(crate) fn set_repr_limits(limits: Option<ReprLimits>) -> ReprLimitsGuard {
+    ReprLimitsGuard(REPR_LIMITS.replace(limits))
+}
+
+/// The limits to apply to reprs collected now.
+pub(crate) fn repr_limits() -> Option<ReprLimits> {
+    REPR_LIMITS.get()
+}
+
+/// Collect the `repr` of `value` without any limits, for explicit conversions.
+pub(crate) fn collect_repr_unlimited(value: Value, collector: &mut String) {
+    let _guard = set_repr_limits(None);
+    value.collect_repr(collector);
+}
+
+/// Collect the `repr` of `value` with `limits`, where `collect` collects the `repr`
+/// of `value` itself, collecting any nested values with `collect_repr`.
+pub(crate) fn collect_repr_limited(
+    collector: &mut String,
+    limits: ReprLimits,
+    collect: impl FnOnce(&mut String),
+) {
+    let depth = repr_stack_len();
+    if depth >= limits.max_depth {
+        collector.push_str(TRUNCATED);
+        return;
+    }
+    let outermost = depth == 0;
+    if outermost {
+        REPR_STATE.set(Some(ReprState {
+            buffer: collector as *const String,
+            start: collector.len(),
+            truncated: None,
+        }));
+    }
+    // Length is only limited for the buffer of the outermost repr,
+    // not for any repr collected separately while producing it.
+    let truncated = REPR_STATE.get().map_or(false, |s| {
+        s.buffer == collector as *const String && s.truncated.is_some()
+    });
+    if truncated {
+        // Everything after the truncation will be removed.
+        return;
+    }
+
+    collect(collector);
+
+    if let Some(mut state) = REPR_STATE.get() {
+        if state.buffer == collector as *const String {
+            if state.truncated.is_none() && collector.len() - state.start > limits.max_len {
+                let mut end = state.start + limits.max_len;
+                while !collector.is_char_boundary(end) {
+                    end -= 1;
+                }
+                collector.truncate(end);
+                collector.push_str(TRUNCATED);
+                state.truncated = Some(collector.len());
+                REPR_STATE.set(Some(state));
+            }
+            if outermost {
+                // Containers continue to write separators and brackets after
+                // the truncation, which we remove.
+                if let Some(end) = state.truncated {
+                    collector.truncate(end);
+                }
+                REPR_STATE.set(None);
+            }
+        }
+    }
+}
@@ -17,6 +17,7 @@
 
 use std::{
     cell::{Cell, RefCell},
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
     marker,
 };
 
@@ -41,6 +42,12 @@ use crate::{
 ///    keys: Vec<Value<'v>>
 /// }
 /// ```
+///
+/// Instances are provided for the standard collections (`Vec`, `VecDeque`, `HashMap`,
+/// `BTreeMap`, `HashSet`) as well as [`SmallMap`], `Option`, `Box` and the cells.
+/// Keys of `HashMap`, `BTreeMap` and `HashSet` can't be modified in place,
+/// so they are required to be `'static`, which rules out keys containing [`Value`].
+/// Use [`SmallMap`] when the keys are themselves values.
 pub unsafe trait Trace<'v> {
     /// Recursively "trace" the value.
     ///
@@ -63,6 +70,12 @@ unsafe impl<'v, T: Trace<'v>> Trace<'v> for [T] {
     }
 }
 
+unsafe impl<'v, T: Trace<'v>> Trace<'v> for VecDeque<T> {
+    fn trace(&mut self, tracer: &Tracer<'v>) {
+        self.iter_mut().for_each(|x| x.trace(tracer));
+    }
+}
+
 unsafe impl<'v, T: Trace<'v>> Trace<'v> for RawTable<T> {
     fn trace(&mut self, tracer: &Tracer<'v>) {
         unsafe {
@@ -84,6 +97,23 @@ unsafe impl<'v, K: Trace<'v>, V: Trace<'v>> Trace<'v> for SmallMap<K, V> {
     }
 }
 
+// Keys are `'static`, so contain no values which need tracing.
+unsafe impl<'v, K: 'static, V: Trace<'v>, S> Trace<'v> for HashMap<K, V, S> {
+    fn trace(&mut self, tracer: &Tracer<'v>) {
+        self.values_mut().for_each(|v| v.trace(tracer));
+    }
+}
+
+unsafe impl<'v, K: 'static, V: Trace<'v>> Trace<'v> for BTreeMap<K, V> {
+    fn trace(&mut self, tracer: &Tracer<'v>) {
+        self.values_mut().for_each(|v| v.trace(tracer));
+    }
+}
+
+unsafe impl<'v, T: 'static, S> Trace<'v> for HashSet<T, S> {
+    fn trace(&mut self, _tracer: &Tracer<'v>) {}
+}
+
 unsafe impl<'v, T: Trace<'v>> Trace<'v> for Option<T> {
     fn trace(&mut self, tracer: &Tracer<'v>) {
         if let Some(x) = self {
@@ -147,6 +177,10 @@ unsafe impl<'v> Trace<'v> for u64 {
     fn trace(&mut self, _tracer: &Tracer<'v>) {}
 }
 
+unsafe impl<'v> Trace<'v> for () {
+    fn trace(&mut self, _tracer: &Tracer<'v>) {}
+}
+
 unsafe impl<'v> Trace<'v> for bool {
     fn trace(&mut self, _tracer: &Tracer<'v>) {}
 }
@@ -158,3 +192,82 @@ unsafe impl<'v> Trace<'v> for std::time::Instant {
 unsafe impl<'v, T> Trace<'v> for marker::PhantomData<T> {
     fn trace(&mut self, _tracer: &Tracer<'v>) {}
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+
+    use derive_more::Display;
+    use gazebo::any::AnyLifetime;
+
+    use crate::{
+        self as starlark,
+        environment::{Globals, Module},
+        eval::Evaluator,
+        syntax::{AstModule, Dialect},
+        values::{FrozenValue, StarlarkValue, Value, ValueLike},
+    };
+
+    #[derive(Debug, Display, Trace, Freeze, AnyLifetime, NoSerialize)]
+    #[display(fmt = "collections")]
+    struct CollectionsGen<V> {
+        hash_map: HashMap<String, V>,
+        btree_map: BTreeMap<String, V>,
+        hash_set: HashSet<String>,
+        deque: VecDeque<V>,
+        boxed: Option<Box<V>>,
+    }
+
+    impl<'v, V: ValueLike<'v>> StarlarkValue<'v> for CollectionsGen<V>
+    where
+        Self: AnyLifetime<'v>,
+    {
+        starlark_type!("collections");
+    }
+
+    fn check<'v, V: ValueLike<'v>>(x: &CollectionsGen<V>) {
+        assert_eq!(x.hash_map["a"].to_value().to_str(), "[1]");
+        assert_eq!(x.btree_map["b"].to_value().to_str(), "[2]");
+        assert!(x.hash_set.contains("c"));
+        assert_eq!(x.deque[0].to_value().to_str(), "[3]");
+        assert_eq!(x.boxed.as_ref().unwrap().to_value().to_str(), "[4]");
+    }
+
+    #[test]
+    fn test_trace_freeze_collections() {
+        let module = Module::new();
+        let heap = module.heap();
+        let list = |x: i32| heap.alloc(vec![x]);
+        let collections = CollectionsGen {
+            hash_map: HashMap::from([("a".to_owned(), list(1))]),
+            btree_map: BTreeMap::from([("b".to_owned(), list(2))]),
+            hash_set: HashSet::from(["c".to_owned()]),
+            deque: VecDeque::from([list(3)]),
+            boxed: Some(Box::new(list(4))),
+        };
+        module.set("x", heap.alloc_complex(collections));
+
+        {
+            let mut eval = Evaluator::new(&module);
+            let ast = AstModule::parse("a.star", "None".to_owned(), &Dialect::Standard).unwrap();
+            eval.eval_module(ast, &Globals::standard()).unwrap();
+            unsafe { eval.garbage_collect() };
+        }
+        check(
+            module
+                .get("x")
+                .unwrap()
+                .downcast_ref::<CollectionsGen<Value>>()
+                .unwrap(),
+        );
+
+        let frozen = module.freeze().unwrap();
+        check(
+            frozen
+                .get("x")
+                .unwrap()
+                .downcast_ref::<CollectionsGen<FrozenValue>>()
+                .unwrap(),
+        );
+    }
+}
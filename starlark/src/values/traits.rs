@@ -32,6 +32,7 @@ use std::{
     any::TypeId,
     cmp::Ordering,
     fmt::{Debug, Display, Write},
+    ops::ControlFlow,
 };
 
 use derive_more::Display;
@@ -44,8 +45,9 @@ use crate::{
     environment::Methods,
     eval::{Arguments, Evaluator},
     values::{
-        docs::DocItem, function::FUNCTION_TYPE, ControlError, Freeze, FrozenStringValue, Heap,
-        Trace, Value, ValueError,
+        docs::DocItem,
+        function::{Signature, FUNCTION_TYPE},
+        ControlError, Freeze, FrozenStringValue, Heap, Trace, Value, ValueError,
     },
 };
 
@@ -253,6 +255,12 @@ pub trait StarlarkValue<'v>: 'v + AnyLifetime<'v> + Debug + Display + Serialize
         self.get_methods().map(|methods| methods.documentation())
     }
 
+    /// Return the name and parameters of self, if it is a function,
+    /// as returned by the `signature()` function.
+    fn signature(&self) -> Option<Signature<'v>> {
+        None
+    }
+
     /// Return a string representation of self, as returned by the `repr()` function.
     /// Defaults to the `Display` instance - which should be fine for nearly all types.
     /// In many cases the `repr()` representation will also be a Starlark expression
@@ -426,6 +434,22 @@ pub trait StarlarkValue<'v>: 'v + AnyLifetime<'v> + Debug + Display + Serialize
         f(&mut *self.iterate(heap)?)
     }
 
+    /// Call `f` on each element of the value, as would be returned by [`iterate`](StarlarkValue::iterate),
+    /// stopping early if `f` returns [`ControlFlow::Break`]. This is how a `for` loop iterates.
+    /// Implementing it lets a collection be iterated without allocating a boxed iterator.
+    /// If you implement this function you must also implement [`iterate`](StarlarkValue::iterate),
+    /// and the elements must be the same.
+    fn for_each_element(
+        &self,
+        heap: &'v Heap,
+        f: &mut dyn FnMut(Value<'v>) -> ControlFlow<()>,
+    ) -> anyhow::Result<()> {
+        self.with_iterator(heap, &mut |it| {
+            for_each_in(it, f);
+            Ok(())
+        })
+    }
+
     /// Returns the length of the value, if this value is a sequence.
     fn length(&self) -> anyhow::Result<i32> {
         ValueError::unsupported(self, "len()")
@@ -443,12 +467,34 @@ pub trait StarlarkValue<'v>: 'v + AnyLifetime<'v> + Debug + Display + Serialize
         None
     }
 
+    /// Like [`get_attr`](StarlarkValue::get_attr), but also given `me`, the [`Value`]
+    /// for `self`, so that attributes can behave like methods.
+    /// Typically returns the result of [`Heap::alloc_bound_method`], so that
+    /// `x.attribute(1)` calls the function with arguments `(x, 1)`.
+    /// Consulted after [`get_methods`](StarlarkValue::get_methods) but before
+    /// [`get_attr`](StarlarkValue::get_attr), for both calls and plain attribute access,
+    /// so [`has_attr`](StarlarkValue::has_attr) and [`dir_attr`](StarlarkValue::dir_attr)
+    /// should include these attributes.
+    ///
+    /// This operations must have no side effects, because it can be called speculatively.
+    fn get_attr_method(
+        &self,
+        _me: Value<'v>,
+        _attribute: &str,
+        _heap: &'v Heap,
+    ) -> Option<Value<'v>> {
+        None
+    }
+
     /// Return true if an attribute of name `attribute` exists for the current
     /// value.
     ///
     /// The three methods [`get_attr`](StarlarkValue::get_attr),
     /// [`has_attr`](StarlarkValue::has_attr) and [`dir_attr`](StarlarkValue::dir_attr)
     /// must be consistent - if you implement one, you should probably implement all three.
+    ///
+    /// Checked by `hasattr` before calling [`get_attr_method`](StarlarkValue::get_attr_method)
+    /// and [`get_attr`](StarlarkValue::get_attr), so only worth implementing if they are expensive.
     fn has_attr(&self, _attribute: &str) -> bool {
         false
     }
@@ -458,6 +504,8 @@ pub trait StarlarkValue<'v>: 'v + AnyLifetime<'v> + Debug + Display + Serialize
     /// The three methods [`get_attr`](StarlarkValue::get_attr),
     /// [`has_attr`](StarlarkValue::has_attr) and [`dir_attr`](StarlarkValue::dir_attr)
     /// must be consistent - if you implement one, you should probably implement all three.
+    ///
+    /// The names can be in any order, e.g. that of a hash map, as `dir` sorts them.
     fn dir_attr(&self) -> Vec<String> {
         Vec::new()
     }
@@ -503,6 +551,19 @@ pub trait StarlarkValue<'v>: 'v + AnyLifetime<'v> + Debug + Display + Serialize
         ValueError::unsupported(self, "-")
     }
 
+    /// Apply the `~` unary operator to the current value.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # starlark::assert::all_true(r#"
+    /// ~1 == -2
+    /// # "#);
+    /// ```
+    fn bit_not(&self, _heap: &'v Heap) -> anyhow::Result<Value<'v>> {
+        ValueError::unsupported(self, "~")
+    }
+
     /// Add with the arguments the other way around. Should return [`None`]
     /// to fall through to normal add.
     fn radd(&self, _lhs: Value<'v>, _heap: &'v Heap) -> Option<anyhow::Result<Value<'v>>> {
@@ -709,6 +770,7 @@ pub(crate) trait StarlarkValueDyn<'v>: 'v + Serialize {
     fn matches_type(&self, _ty: &str) -> bool;
     fn get_methods(&self) -> Option<&'static Methods>;
     fn documentation(&self) -> Option<DocItem>;
+    fn signature(&self) -> Option<Signature<'v>>;
     fn collect_repr(&self, _collector: &mut String);
     fn collect_repr_cycle(&self, _collector: &mut String);
     fn to_bool(&self) -> bool;
@@ -749,13 +811,25 @@ pub(crate) trait StarlarkValueDyn<'v>: 'v + Serialize {
         _heap: &'v Heap,
         _f: &mut dyn FnMut(&mut dyn Iterator<Item = Value<'v>>) -> anyhow::Result<()>,
     ) -> anyhow::Result<()>;
+    fn for_each_element(
+        &self,
+        _heap: &'v Heap,
+        _f: &mut dyn FnMut(Value<'v>) -> ControlFlow<()>,
+    ) -> anyhow::Result<()>;
     fn length(&self) -> anyhow::Result<i32>;
     fn get_attr(&self, _attribute: &str, _heap: &'v Heap) -> Option<Value<'v>>;
+    fn get_attr_method(
+        &self,
+        _me: Value<'v>,
+        _attribute: &str,
+        _heap: &'v Heap,
+    ) -> Option<Value<'v>>;
     fn has_attr(&self, _attribute: &str) -> bool;
     fn dir_attr(&self) -> Vec<String>;
     fn is_in(&self, _other: Value<'v>) -> anyhow::Result<bool>;
     fn plus(&self, _heap: &'v Heap) -> anyhow::Result<Value<'v>>;
     fn minus(&self, _heap: &'v Heap) -> anyhow::Result<Value<'v>>;
+    fn bit_not(&self, _heap: &'v Heap) -> anyhow::Result<Value<'v>>;
     fn radd(&self, _lhs: Value<'v>, _heap: &'v Heap) -> Option<anyhow::Result<Value<'v>>>;
     fn add(&self, _rhs: Value<'v>, _heap: &'v Heap) -> anyhow::Result<Value<'v>>;
     fn sub(&self, _other: Value<'v>, _heap: &'v Heap) -> anyhow::Result<Value<'v>>;
@@ -774,3 +848,16 @@ pub(crate) trait StarlarkValueDyn<'v>: 'v + Serialize {
 }
 
 erased_serde::serialize_trait_object!(<'v> StarlarkValueDyn<'v>);
+
+/// Call `f` on each item of `it` until it returns [`ControlFlow::Break`],
+/// for implementing [`StarlarkValue::for_each_element`] given an iterator.
+pub(crate) fn for_each_in<'v>(
+    it: impl Iterator<Item = Value<'v>>,
+    f: &mut dyn FnMut(Value<'v>) -> ControlFlow<()>,
+) {
+    for x in it {
+        if f(x).is_break() {
+            return;
+        }
+    }
+}
@@ -29,7 +29,7 @@ use std::{
 use gazebo::any::AnyLifetime;
 use serde::{ser::SerializeSeq, Serialize};
 
-use crate::values::{types::list::display_list, StarlarkValue, Value};
+use crate::values::{types::list::display_list, Heap, StarlarkValue, Value};
 
 /// Fixed-capacity list.
 ///
@@ -52,6 +52,14 @@ pub(crate) struct Array<'v> {
     //   `iter_count_cap < 0` means `-iter_count_cap` active iterators,
     //     and iterator object holds the capacity.
     iter_count: UnsafeCell<u32>,
+    /// Number of lists sharing this array in addition to the first one, after copies
+    /// with [`List::copy`](crate::values::list::List::copy). A shared array is never
+    /// modified, lists copy it first. Lists which are garbage collected are not subtracted,
+    /// so an array might be copied when it is no longer shared.
+    sharers: UnsafeCell<u32>,
+    /// The heap the array is allocated in, recorded when it is first shared,
+    /// so a list can copy it without being given a heap.
+    heap: UnsafeCell<Option<&'v Heap>>,
     content: [Value<'v>; 0],
 }
 
@@ -79,6 +87,8 @@ impl<'v> Array<'v> {
             len: UnsafeCell::new(len),
             capacity,
             iter_count: UnsafeCell::new(0),
+            sharers: UnsafeCell::new(0),
+            heap: UnsafeCell::new(None),
             content: [],
         }
     }
@@ -145,6 +155,57 @@ impl<'v> Array<'v> {
         unsafe { *self.iter_count.get() != 0 }
     }
 
+    /// Is this array shared by more than one list.
+    pub(crate) fn is_shared(&self) -> bool {
+        unsafe { *self.sharers.get() != 0 }
+    }
+
+    pub(crate) fn sharers(&self) -> u32 {
+        unsafe { *self.sharers.get() }
+    }
+
+    pub(crate) fn set_sharers(&self, sharers: u32) {
+        debug_assert!(!self.is_statically_allocated() || sharers == 0);
+        unsafe {
+            *self.sharers.get() = sharers;
+        }
+    }
+
+    /// The heap this array is allocated in, if it has ever been shared.
+    pub(crate) fn heap(&self) -> Option<&'v Heap> {
+        unsafe { *self.heap.get() }
+    }
+
+    pub(crate) fn set_heap(&self, heap: Option<&'v Heap>) {
+        debug_assert!(!self.is_statically_allocated() || heap.is_none());
+        unsafe {
+            *self.heap.get() = heap;
+        }
+    }
+
+    /// Record another list sharing this array, which is allocated in `heap`.
+    /// Returns `false` if the array can't be shared,
+    /// because it is statically allocated, is being iterated or has too many sharers.
+    pub(crate) fn add_sharer(&self, heap: &'v Heap) -> bool {
+        if self.is_statically_allocated() || self.iter_count_is_non_zero() {
+            return false;
+        }
+        match self.sharers().checked_add(1) {
+            Some(sharers) => {
+                self.set_sharers(sharers);
+                self.set_heap(Some(heap));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Record that a list sharing this array stopped using it.
+    pub(crate) fn remove_sharer(&self) {
+        debug_assert!(self.is_shared());
+        self.set_sharers(self.sharers().saturating_sub(1));
+    }
+
     /// Create an iterator.
     ///
     /// Note this operation updates the iterator count of this object.
@@ -0,0 +1,331 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! The bytes type, an immutable sequence of bytes which need not be valid UTF-8.
+//!
+//! Bytes are not part of the Starlark standard, so the `bytes()` constructor, and `str.encode()`
+//! which also makes bytes, are only available with
+//! [`LibraryExtension::Bytes`](crate::environment::LibraryExtension::Bytes).
+
+use std::{
+    cmp::Ordering,
+    fmt::{self, Display, Write},
+};
+
+use gazebo::any::AnyLifetime;
+use thiserror::Error;
+
+use crate as starlark;
+use crate::{
+    collections::StarlarkHasher,
+    environment::{Methods, MethodsStatic},
+    values::{
+        index::{apply_slice, convert_index},
+        Heap, StarlarkValue, Value, ValueError,
+    },
+};
+
+#[derive(Debug, Error)]
+enum BytesError {
+    #[error("`in` on bytes expects an int in the range 0 to 255, got `{0}`")]
+    NotAByte(i32),
+}
+
+/// An immutable sequence of bytes, which unlike a string need not be valid UTF-8.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, AnyLifetime, NoSerialize)]
+pub struct StarlarkBytes(Box<[u8]>);
+
+starlark_simple_value!(StarlarkBytes);
+
+impl StarlarkBytes {
+    /// The result of calling `type()` on bytes.
+    pub const TYPE: &'static str = "bytes";
+
+    /// Create a new [`StarlarkBytes`].
+    pub fn new(bytes: impl Into<Box<[u8]>>) -> Self {
+        Self(bytes.into())
+    }
+
+    /// The bytes.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// Shown as a bytes literal, as in Python, e.g. `b"a\x00"`.
+impl Display for StarlarkBytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("b\"")?;
+        for b in self.0.iter() {
+            match *b {
+                b'"' => f.write_str("\\\"")?,
+                b'\\' => f.write_str("\\\\")?,
+                b'\n' => f.write_str("\\n")?,
+                b'\r' => f.write_str("\\r")?,
+                b'\t' => f.write_str("\\t")?,
+                b' '..=b'~' => f.write_char(*b as char)?,
+                b => write!(f, "\\x{:02x}", b)?,
+            }
+        }
+        f.write_char('"')
+    }
+}
+
+pub(crate) fn bytes_methods() -> Option<&'static Methods> {
+    static RES: MethodsStatic = MethodsStatic::new();
+    RES.methods(crate::stdlib::bytes::bytes_methods)
+}
+
+impl<'v> StarlarkValue<'v> for StarlarkBytes {
+    starlark_type!(StarlarkBytes::TYPE);
+
+    fn get_methods(&self) -> Option<&'static Methods> {
+        bytes_methods()
+    }
+
+    fn extra_memory(&self) -> usize {
+        self.0.len()
+    }
+
+    fn to_bool(&self) -> bool {
+        !self.0.is_empty()
+    }
+
+    fn length(&self) -> anyhow::Result<i32> {
+        Ok(self.0.len() as i32)
+    }
+
+    fn at(&self, index: Value, _heap: &'v Heap) -> anyhow::Result<Value<'v>> {
+        let i = convert_index(index, self.0.len() as i32)? as usize;
+        Ok(Value::new_int(self.0[i] as i32))
+    }
+
+    fn slice(
+        &self,
+        start: Option<Value>,
+        stop: Option<Value>,
+        stride: Option<Value>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<Value<'v>> {
+        Ok(heap.alloc(StarlarkBytes::new(apply_slice(
+            &self.0, start, stop, stride,
+        )?)))
+    }
+
+    fn equals(&self, other: Value<'v>) -> anyhow::Result<bool> {
+        match StarlarkBytes::from_value(other) {
+            Some(other) => Ok(self.0 == other.0),
+            None => Ok(false),
+        }
+    }
+
+    fn compare(&self, other: Value<'v>) -> anyhow::Result<Ordering> {
+        match StarlarkBytes::from_value(other) {
+            Some(other) => Ok(self.0.cmp(&other.0)),
+            None => ValueError::unsupported_with(self, "cmp()", other),
+        }
+    }
+
+    fn write_hash(&self, hasher: &mut StarlarkHasher) -> anyhow::Result<()> {
+        std::hash::Hash::hash(&self.0, hasher);
+        Ok(())
+    }
+
+    /// `x in b` is true if `x` is bytes which occur in `b`, or an int which is one of
+    /// the bytes of `b`. Ints which can't be a byte are an error.
+    fn is_in(&self, other: Value<'v>) -> anyhow::Result<bool> {
+        if let Some(other) = StarlarkBytes::from_value(other) {
+            Ok(other.0.is_empty() || self.0.windows(other.0.len()).any(|x| x == &*other.0))
+        } else if let Some(other) = other.unpack_int() {
+            match u8::try_from(other) {
+                Ok(other) => Ok(self.0.contains(&other)),
+                Err(_) => Err(BytesError::NotAByte(other).into()),
+            }
+        } else {
+            ValueError::unsupported_with(self, "in", other)
+        }
+    }
+
+    fn add(&self, other: Value<'v>, heap: &'v Heap) -> anyhow::Result<Value<'v>> {
+        match StarlarkBytes::from_value(other) {
+            Some(other) => Ok(heap.alloc(StarlarkBytes::new([&*self.0, &*other.0].concat()))),
+            None => ValueError::unsupported_with(self, "+", other),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        assert,
+        assert::Assert,
+        environment::{Globals, GlobalsBuilder, LibraryExtension},
+    };
+
+    #[test]
+    fn test_bytes_basic() {
+        assert::all_true(
+            r#"
+type(bytes([])) == "bytes"
+len(bytes([1, 2, 3])) == 3
+bytes([104, 105])[0] == 104
+bytes([104, 105])[-1] == 105
+bytes([104, 105]) == "hi".encode()
+bytes([104, 105]) != "hi"
+bytes([104, 105]) + bytes([33]) == "hi!".encode()
+not bytes([])
+bytes([0])
+bytes([1, 2]) < bytes([1, 3])
+bytes([1]) < bytes([1, 0])
+bytes(bytes([5])) == bytes([5])
+"ñ".encode() == bytes([195, 177])
+"#,
+        );
+        assert::fail("bytes([256])", "0 to 255");
+        assert::fail("bytes([-1])", "0 to 255");
+        assert::fail("bytes(['a'])", "0 to 255");
+        assert::fail("bytes([1])[1]", "out of bound");
+        assert::fail("bytes([1]) + 'a'", "not supported");
+    }
+
+    #[test]
+    fn test_bytes_slice() {
+        assert::all_true(
+            r#"
+b = bytes([0, 1, 2, 3, 4])
+b[1:3] == bytes([1, 2])
+b[:] == b
+b[3:1] == bytes([])
+b[-2:] == bytes([3, 4])
+b[::2] == bytes([0, 2, 4])
+b[::-1] == bytes([4, 3, 2, 1, 0])
+b[-100:100] == b
+b[100:] == bytes([])
+type(b[1:1]) == "bytes"
+"#,
+        );
+        assert::fail("bytes([1])[::0]", "out of bound");
+    }
+
+    #[test]
+    fn test_bytes_in() {
+        assert::all_true(
+            r#"
+b = "hello".encode()
+"ell".encode() in b
+"elo".encode() not in b
+bytes([]) in b
+bytes([]) in bytes([])
+104 in b
+105 not in b
+"#,
+        );
+        assert::fail("'e' in 'hello'.encode()", "not supported");
+        assert::fail("256 in 'hello'.encode()", "0 to 255");
+        assert::fail("-1 in 'hello'.encode()", "0 to 255");
+    }
+
+    #[test]
+    fn test_bytes_repr() {
+        assert::all_true(
+            r#"
+repr(bytes([])) == 'b""'
+repr("a\"b\\".encode()) == 'b"a\\"b\\\\"'
+repr(bytes([0, 9, 10, 13, 127, 255])) == 'b"\\x00\\t\\n\\r\\x7f\\xff"'
+str("hi".encode()) == 'b"hi"'
+"#,
+        );
+    }
+
+    #[test]
+    fn test_bytes_decode() {
+        assert::all_true(
+            r#"
+"ñandú".encode().decode() == "ñandú"
+"ñandú".encode("utf-8").decode("UTF-8") == "ñandú"
+"#,
+        );
+        assert::fail("bytes([255]).decode()", "not valid UTF-8");
+        assert::fail("'a'.encode('latin-1')", "Unsupported encoding");
+        assert::fail("bytes([97]).decode('ascii')", "Unsupported encoding");
+    }
+
+    #[test]
+    fn test_encode_needs_bytes_extension() {
+        let mut a = Assert::new();
+        a.globals(Globals::standard());
+        a.fail("'a'.encode()", "requires the `bytes` type");
+        a.fail(
+            "def f():\n    return 'a'.encode()\nf()",
+            "requires the `bytes` type",
+        );
+        // A user-defined `bytes` doesn't enable it.
+        a.fail("bytes = 1\n'a'.encode()", "requires the `bytes` type");
+        a.globals(GlobalsBuilder::extended_by(&[LibraryExtension::Bytes]).build());
+        a.is_true("'a'.encode() == bytes([97])");
+        a.is_true("getattr('a', 'encode')() == bytes([97])");
+        // Adding the extension twice is harmless.
+        a.globals(
+            GlobalsBuilder::extended_by(&[LibraryExtension::Bytes, LibraryExtension::Bytes])
+                .build(),
+        );
+        a.is_true("'a'.encode() == bytes([97])");
+    }
+
+    #[test]
+    fn test_bytes_hash() {
+        assert::all_true(
+            r#"
+d = {"ab".encode(): 1, bytes([97, 99]): 2}
+d[bytes([97, 98])] == 1
+d["ac".encode()] == 2
+"ab" not in d
+len({bytes([1, 2]): 1, bytes([1]) + bytes([2]): 2}) == 1
+"#,
+        );
+    }
+
+    #[test]
+    fn test_bytes_json() {
+        assert::fail("json(bytes([1]))", "not supported on type `bytes`");
+    }
+
+    #[test]
+    fn test_bytes_freeze_and_gc() {
+        let mut a = Assert::new();
+        a.module(
+            "m",
+            r#"
+big = bytes(list(range(256)) * 4096)
+"#,
+        );
+        a.pass(
+            r#"
+load("m", "big")
+garbage_collect()
+local = big[1:] + big[:1]
+garbage_collect()
+assert_eq(len(local), 1048576)
+assert_eq(local[-1], 0)
+assert_eq(local[255], 0)
+assert_eq(local[254], 255)
+assert_eq(big[::-1][0], 255)
+assert_eq(big, local[-1:] + local[:-1])
+"#,
+        );
+    }
+}
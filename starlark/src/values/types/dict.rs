@@ -25,7 +25,7 @@ use std::{
     hash::{Hash, Hasher},
     intrinsics::unlikely,
     marker::PhantomData,
-    ops::{Deref, DerefMut},
+    ops::{ControlFlow, Deref, DerefMut},
 };
 
 use gazebo::{
@@ -41,10 +41,14 @@ use crate::{
     collections::{BorrowHashed, Hashed, SmallMap},
     environment::{Methods, MethodsStatic},
     values::{
-        comparison::equals_small_map, display::display_keyed_container, error::ValueError,
-        iter::ARefIterator, string::hash_string_value, AllocFrozenValue, AllocValue, Freeze,
-        Freezer, FrozenHeap, FrozenStringValue, FrozenValue, Heap, StarlarkValue, StringValue,
-        Trace, UnpackValue, Value, ValueLike,
+        comparison::equals_small_map,
+        display::display_keyed_container,
+        error::{FrozenValueMutated, ValueError},
+        for_each_in, freeze_small_map_with_path,
+        iter::ARefIterator,
+        string::hash_string_value,
+        AllocFrozenValue, AllocValue, Freeze, Freezer, FrozenHeap, FrozenStringValue, FrozenValue,
+        Heap, StarlarkValue, StringValue, Trace, UnpackValue, Value, ValueLike,
     },
 };
 
@@ -143,7 +147,7 @@ impl<'v> Dict<'v> {
     /// Downcast the value to a mutable dict reference.
     pub fn from_value_mut(x: Value<'v>) -> anyhow::Result<Option<DictMut>> {
         if unlikely(x.unpack_frozen().is_some()) {
-            return Err(ValueError::CannotMutateImmutableValue.into());
+            return Err(FrozenValueMutated::error(x));
         }
         let ptr = x.downcast_ref::<DictGen<RefCell<Dict<'v>>>>();
         match ptr {
@@ -319,6 +323,11 @@ impl<'v> Dict<'v> {
         self.content.remove_hashed(key.borrow())
     }
 
+    /// Remove the most recently inserted key/value pair from the dictionary.
+    pub(crate) fn pop_last(&mut self) -> Option<(Value<'v>, Value<'v>)> {
+        self.content.pop()
+    }
+
     /// Remove all elements from the dictionary.
     pub fn clear(&mut self) {
         self.content.clear();
@@ -366,7 +375,9 @@ impl FrozenDict {
 impl<'v> Freeze for DictGen<RefCell<Dict<'v>>> {
     type Frozen = DictGen<FrozenDict>;
     fn freeze(self, freezer: &Freezer) -> anyhow::Result<Self::Frozen> {
-        let content = self.0.into_inner().content.freeze(freezer)?;
+        let content = freeze_small_map_with_path(self.0.into_inner().content, freezer, |k| {
+            format!("[{}]", k.to_value().to_repr())
+        })?;
         Ok(DictGen(FrozenDict { content }))
     }
 }
@@ -490,6 +501,16 @@ where
         f(&mut self.0.content().keys().copied())
     }
 
+    fn for_each_element(
+        &self,
+        _heap: &'v Heap,
+        f: &mut dyn FnMut(Value<'v>) -> ControlFlow<()>,
+    ) -> anyhow::Result<()> {
+        // Hold the borrow for the whole loop, so the dict can't be mutated by `f`.
+        for_each_in(self.0.content().keys().copied(), f);
+        Ok(())
+    }
+
     fn set_at(&self, index: Value<'v>, alloc_value: Value<'v>) -> anyhow::Result<()> {
         let index = index.get_hashed()?;
         self.0.set_at(index, alloc_value)
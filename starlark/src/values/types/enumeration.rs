@@ -32,6 +32,9 @@
 //! assert_eq(Colors[0], val)
 //! assert_eq(Colors.type, "Colors")
 //! assert_eq([v.value for v in Colors], ["Red", "Green", "Blue"])
+//! assert_eq(val in Colors, True)
+//! assert_eq("Red" in Colors, False)
+//! assert_eq("Red" in Colors.values(), True)
 //! # "#);
 //! ```
 use std::{
@@ -55,8 +58,8 @@ use crate::{
     environment::{Methods, MethodsBuilder, MethodsStatic},
     eval::{Arguments, Evaluator},
     values::{
-        display::display_container, function::FUNCTION_TYPE, index::convert_index, Freeze,
-        FrozenValue, Heap, StarlarkValue, Trace, Value, ValueLike,
+        display::display_container, docs, docs::DocItem, function::FUNCTION_TYPE,
+        index::convert_index, Freeze, FrozenValue, Heap, StarlarkValue, Trace, Value, ValueLike,
     },
 };
 
@@ -64,10 +67,13 @@ use crate::{
 enum EnumError {
     #[error("enum values must all be distinct, but repeated `{0}`")]
     DuplicateEnumValue(String),
-    #[error("Unknown enum element `{0}`, given to `{1}`")]
-    InvalidElement(String, String),
+    #[error("Unknown enum element `{0}`, given to `{1}`, expected one of {2}")]
+    InvalidElement(String, String, String),
 }
 
+// Large enums would make the error unreadable, so only the first few choices are listed.
+const MAX_CHOICES_IN_ERROR: usize = 5;
+
 /// The type of an enumeration, created by `enum()`.
 #[derive(Clone, Debug, Trace, Coerce, Freeze, NoSerialize, AnyLifetime)]
 #[repr(C)]
@@ -148,6 +154,16 @@ impl<'v> EnumType<'v> {
     }
 }
 
+impl<V, Typ: AsARef<Option<String>>> EnumTypeGen<V, Typ> {
+    /// The name of the type, which is the variable it was assigned to, or `enum`.
+    fn type_name(&self) -> String {
+        AsARef::as_aref(&self.typ)
+            .as_deref()
+            .unwrap_or(EnumValue::TYPE)
+            .to_owned()
+    }
+}
+
 impl<'v, V: ValueLike<'v>> EnumValueGen<V> {
     /// The result of calling `type()` on an enum value.
     pub const TYPE: &'static str = "enum";
@@ -179,7 +195,20 @@ where
             .either(|x| &x.elements, |x| coerce_ref(&x.elements));
         match elements.get_hashed(val.get_hashed()?.borrow()) {
             Some(v) => Ok(*v),
-            None => Err(EnumError::InvalidElement(val.to_str(), this.to_repr()).into()),
+            None => {
+                let mut choices = elements
+                    .keys()
+                    .take(MAX_CHOICES_IN_ERROR)
+                    .map(|k| k.to_repr())
+                    .collect::<Vec<_>>();
+                if elements.len() > MAX_CHOICES_IN_ERROR {
+                    choices.push("...".to_owned());
+                }
+                Err(
+                    EnumError::InvalidElement(val.to_str(), self.type_name(), choices.join(", "))
+                        .into(),
+                )
+            }
         }
     }
 
@@ -216,6 +245,17 @@ where
         f(&mut self.elements.values().map(|x| x.to_value()))
     }
 
+    fn is_in(&self, other: Value<'v>) -> anyhow::Result<bool> {
+        // Like iteration, this only considers the enum values, so `"a" in enum("a")` is false.
+        match EnumValue::from_value(other) {
+            Some(x) => match self.elements.get_index(x.index as usize) {
+                Some((_, v)) => v.to_value().equals(other),
+                None => Ok(false),
+            },
+            None => Ok(false),
+        }
+    }
+
     fn get_methods(&self) -> Option<&'static Methods> {
         static RES: MethodsStatic = MethodsStatic::new();
         RES.methods(enum_type_methods)
@@ -247,6 +287,29 @@ where
         }
     }
 
+    fn documentation(&self) -> Option<DocItem> {
+        let typ = docs::Type {
+            raw_type: self.type_name(),
+        };
+        let members = self
+            .elements
+            .keys()
+            .map(|k| {
+                (
+                    k.to_value().to_repr(),
+                    docs::Member::Property(docs::Property {
+                        docs: None,
+                        typ: Some(typ.clone()),
+                    }),
+                )
+            })
+            .collect();
+        Some(DocItem::Object(docs::Object {
+            docs: None,
+            members,
+        }))
+    }
+
     fn export_as(&self, variable_name: &str, _eval: &mut Evaluator<'v, '_>) {
         if let Some(typ) = AsARef::as_ref_cell(&self.typ) {
             let mut typ = typ.borrow_mut();
@@ -18,23 +18,32 @@
 //! The floating point number type (3.14, 4e2).
 
 use std::{
-    cmp::Ordering,
+    cmp::{self, Ordering},
     fmt::{self, Display, Write},
     hash::Hasher,
 };
 
 use gazebo::{any::AnyLifetime, prelude::*};
 use serde::Serialize;
+use thiserror::Error;
 
 use crate::{
     collections::StarlarkHasher,
+    environment::{Methods, MethodsStatic},
     values::{
         num::Num, AllocFrozenValue, AllocValue, FrozenHeap, FrozenValue, Heap, StarlarkValue,
         UnpackValue, Value, ValueError, ValueLike,
     },
 };
 
-const WRITE_PRECISION: usize = 6;
+#[derive(Debug, Error)]
+enum FloatError {
+    #[error("Cannot compare `{0}` with `{1}`, as NaN is unordered")]
+    UnorderedNaN(String, String),
+}
+
+/// The default precision when writing floats.
+pub(crate) const WRITE_PRECISION: usize = 6;
 
 fn write_non_finite<W: fmt::Write>(output: &mut W, f: f64) -> fmt::Result {
     debug_assert!(f.is_nan() || f.is_infinite());
@@ -49,69 +58,89 @@ fn write_non_finite<W: fmt::Write>(output: &mut W, f: f64) -> fmt::Result {
     }
 }
 
-pub(crate) fn write_decimal<W: fmt::Write>(output: &mut W, f: f64) -> fmt::Result {
+pub(crate) fn write_decimal<W: fmt::Write>(
+    output: &mut W,
+    f: f64,
+    precision: usize,
+) -> fmt::Result {
     if !f.is_finite() {
         write_non_finite(output, f)
     } else {
-        write!(output, "{:.prec$}", f, prec = WRITE_PRECISION)
+        write!(output, "{:.prec$}", f, prec = precision)
     }
 }
 
+/// Remove any trailing zeros after the decimal point, and the point itself if nothing is left.
+fn trim_trailing_zeros(x: &str) -> &str {
+    if x.contains('.') {
+        x.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        x
+    }
+}
+
+/// Write a finite float with a mantissa and exponent, e.g. `1.5e+03`.
+fn write_exponent<W: fmt::Write>(
+    output: &mut W,
+    f: f64,
+    exponent_char: char,
+    precision: usize,
+    strip_trailing_zeros: bool,
+) -> fmt::Result {
+    // Rust writes the exponent without a sign or padding, e.g. `1.5e3`.
+    let formatted = format!("{:.prec$e}", f, prec = precision);
+    let (mantissa, exponent) = formatted.split_once('e').unwrap();
+    let exponent: i32 = exponent.parse().unwrap();
+    let mantissa = if strip_trailing_zeros {
+        trim_trailing_zeros(mantissa)
+    } else {
+        mantissa
+    };
+    write!(output, "{}{}{:+03}", mantissa, exponent_char, exponent)
+}
+
 pub(crate) fn write_scientific<W: fmt::Write>(
     output: &mut W,
     f: f64,
     exponent_char: char,
+    precision: usize,
     strip_trailing_zeros: bool,
 ) -> fmt::Result {
     if !f.is_finite() {
         write_non_finite(output, f)
     } else {
-        let abs = f.abs();
-        let exponent = if f == 0.0 {
-            0
-        } else {
-            abs.log10().floor() as i32
-        };
-        let normal = if f == 0.0 {
-            0.0
-        } else {
-            abs / 10f64.powf(exponent as f64)
-        };
-
-        // start with "-" for a negative number
-        if f.is_sign_negative() {
-            output.write_char('-')?
-        }
-
-        // use the whole integral part of normal (a single digit)
-        output.write_fmt(format_args!("{}", normal.trunc()))?;
-
-        // calculate the fractional tail for given precision
-        let mut tail = (normal.fract() * 10f64.powf(WRITE_PRECISION as f64)).round() as u64;
-        let mut rev_tail = [0u8; WRITE_PRECISION];
-        let mut rev_tail_len = 0;
-        let mut removing_trailing_zeros = strip_trailing_zeros;
-        for _ in 0..WRITE_PRECISION {
-            let tail_digit = tail % 10;
-            if tail_digit != 0 || !removing_trailing_zeros {
-                removing_trailing_zeros = false;
-                rev_tail[rev_tail_len] = tail_digit as u8;
-                rev_tail_len += 1;
-            }
-            tail /= 10;
-        }
-
-        // write fractional part
-        if rev_tail_len != 0 {
-            output.write_char('.')?;
-        }
-        for digit in rev_tail[0..rev_tail_len].iter().rev() {
-            output.write_char((b'0' + digit) as char)?;
-        }
+        write_exponent(output, f, exponent_char, precision, strip_trailing_zeros)
+    }
+}
 
-        // add exponent part
-        output.write_char(exponent_char)?;
-        output.write_fmt(format_args!("{:+03}", exponent))
+/// Write with `precision` significant digits, choosing between decimal and scientific
+/// notation based on the exponent and removing trailing zeros, like Python's `%.<precision>g`.
+pub(crate) fn write_general<W: fmt::Write>(
+    output: &mut W,
+    f: f64,
+    exponent_char: char,
+    precision: usize,
+) -> fmt::Result {
+    if !f.is_finite() {
+        return write_non_finite(output, f);
+    }
+    let precision = cmp::max(precision, 1);
+    // Rounding to the precision may change the exponent, e.g. 9.99 to 1e+01, so round first.
+    let exponent: i32 = format!("{:.prec$e}", f, prec = precision - 1)
+        .split_once('e')
+        .unwrap()
+        .1
+        .parse()
+        .unwrap();
+    if exponent < -4 || exponent >= precision as i32 {
+        write_exponent(output, f, exponent_char, precision - 1, true)
+    } else {
+        let decimal = format!(
+            "{:.prec$}",
+            f,
+            prec = (precision as i32 - 1 - exponent) as usize
+        );
+        output.write_str(trim_trailing_zeros(&decimal))
     }
 }
 
@@ -132,7 +161,7 @@ pub(crate) fn write_compact<W: fmt::Write>(
 
         if exponent.abs() >= WRITE_PRECISION as i32 {
             // use scientific notation if exponent is outside of our precision (but strip 0s)
-            write_scientific(output, f, exponent_char, true)
+            write_scientific(output, f, exponent_char, WRITE_PRECISION, true)
         } else if f.fract() == 0.0 {
             // make sure there's a fractional part even if the number doesn't have it
             output.write_fmt(format_args!("{:.1}", f))
@@ -209,11 +238,17 @@ impl<'v> StarlarkValue<'v> for StarlarkFloat {
     }
 
     fn equals(&self, other: Value) -> anyhow::Result<bool> {
-        if other.unpack_num().is_some() {
-            Ok(self.compare(other)? == Ordering::Equal)
-        } else {
-            Ok(false)
-        }
+        // According to the spec (https://github.com/bazelbuild/starlark/blob/689f54426951638ef5b7c41a14d8fc48e65c5f77/spec.md#floating-point-numbers)
+        // all NaN values compare equal to each other. Ordering a NaN is an error, see `compare`.
+        Ok(match other.unpack_num().map(|n| n.as_float()) {
+            Some(other_float) => self.0 == other_float || (self.0.is_nan() && other_float.is_nan()),
+            None => false,
+        })
+    }
+
+    fn get_methods(&self) -> Option<&'static Methods> {
+        static RES: MethodsStatic = MethodsStatic::new();
+        RES.methods(crate::stdlib::float::float_methods)
     }
 
     fn collect_repr(&self, s: &mut String) {
@@ -286,20 +321,9 @@ impl<'v> StarlarkValue<'v> for StarlarkFloat {
 
     fn compare(&self, other: Value) -> anyhow::Result<Ordering> {
         if let Some(other_float) = other.unpack_num().map(|n| n.as_float()) {
-            // According to the spec (https://github.com/bazelbuild/starlark/blob/689f54426951638ef5b7c41a14d8fc48e65c5f77/spec.md#floating-point-numbers)
-            // All NaN values compare equal to each other, but greater than any non-NaN float value.
-            match (self.0.is_nan(), other_float.is_nan()) {
-                (true, true) => Ok(Ordering::Equal),
-                (true, false) => Ok(Ordering::Greater),
-                (false, true) => Ok(Ordering::Less),
-                (false, false) => {
-                    if let Some(ordering) = self.0.partial_cmp(&other_float) {
-                        Ok(ordering)
-                    } else {
-                        // This shouldn't happen as we handle potential NaNs above
-                        ValueError::unsupported_with(self, "==", other)
-                    }
-                }
+            match self.0.partial_cmp(&other_float) {
+                Some(ordering) => Ok(ordering),
+                None => Err(FloatError::UnorderedNaN(self.to_string(), other.to_repr()).into()),
             }
         } else {
             ValueError::unsupported_with(self, "==", other)
@@ -327,7 +351,7 @@ mod tests {
 
     fn decimal(f: f64) -> String {
         let mut buf = String::new();
-        write_decimal(&mut buf, f).unwrap();
+        write_decimal(&mut buf, f, WRITE_PRECISION).unwrap();
         buf
     }
 
@@ -345,7 +369,7 @@ mod tests {
 
     fn scientific(f: f64) -> String {
         let mut buf = String::new();
-        write_scientific(&mut buf, f, 'e', false).unwrap();
+        write_scientific(&mut buf, f, 'e', WRITE_PRECISION, false).unwrap();
         buf
     }
 
@@ -362,6 +386,29 @@ mod tests {
         assert_eq!(scientific(1e300), "1.000000e+300");
     }
 
+    #[test]
+    fn test_write_precision() {
+        fn f(op: impl Fn(&mut String) -> fmt::Result) -> String {
+            let mut buf = String::new();
+            op(&mut buf).unwrap();
+            buf
+        }
+        assert_eq!(f(|b| write_decimal(b, 2.675, 2)), "2.67");
+        assert_eq!(f(|b| write_decimal(b, 2.5, 0)), "2");
+        assert_eq!(
+            f(|b| write_scientific(b, 123456.0, 'e', 2, false)),
+            "1.23e+05"
+        );
+        assert_eq!(f(|b| write_scientific(b, 9.999, 'E', 2, false)), "1.00E+01");
+        assert_eq!(f(|b| write_general(b, 123456.0, 'e', 3)), "1.23e+05");
+        assert_eq!(f(|b| write_general(b, 123.456, 'e', 4)), "123.5");
+        assert_eq!(f(|b| write_general(b, 0.0001, 'e', 6)), "0.0001");
+        assert_eq!(f(|b| write_general(b, 0.00001, 'e', 6)), "1e-05");
+        assert_eq!(f(|b| write_general(b, 99999.9, 'e', 5)), "1e+05");
+        assert_eq!(f(|b| write_general(b, 1.5, 'e', 0)), "2");
+        assert_eq!(f(|b| write_general(b, f64::NAN, 'e', 3)), "nan");
+    }
+
     fn compact(f: f64) -> String {
         let mut buf = String::new();
         write_compact(&mut buf, f, 'e').unwrap();
@@ -423,18 +470,47 @@ assert_eq(1 in x, False)
 0.0 < 1
 1 > 0.0
 1.0 > 0
-0.0 < float("nan")
-float("+inf") < float("nan")
+float("nan") == float("nan")
+not (float("nan") != float("nan"))
+float("nan") != 1.0
 "#,
         );
+        assert::fail("0.0 < float('nan')", "NaN is unordered");
+        assert::fail("float('+inf') < float('nan')", "NaN is unordered");
+        assert::fail("float('nan') >= float('nan')", "NaN is unordered");
     }
 
     #[test]
     fn test_comparisons_by_sorting() {
         assert::all_true(
             r#"
-sorted([float('inf'), float('-inf'), float('nan'), 1e300, -1e300, 1.0, -1.0, 1, -1, 1e-300, -1e-300, 0, 0.0, float('-0.0'), 1e-300, -1e-300]) == [float('-inf'), -1e+300, -1.0, -1, -1e-300, -1e-300, 0, 0.0, -0.0, 1e-300, 1e-300, 1.0, 1, 1e+300, float('+inf'), float('nan')]
+sorted([float('inf'), float('-inf'), 1e300, -1e300, 1.0, -1.0, 1, -1, 1e-300, -1e-300, 0, 0.0, float('-0.0'), 1e-300, -1e-300]) == [float('-inf'), -1e+300, -1.0, -1, -1e-300, -1e-300, 0, 0.0, -0.0, 1e-300, 1e-300, 1.0, 1, 1e+300, float('+inf')]
+"#,
+        );
+        assert::fail("sorted([1, float('nan')])", "NaN is unordered");
+        assert::fail("sorted([float('nan'), 2.5, 1])", "NaN is unordered");
+    }
+
+    #[test]
+    fn test_mixed_ordering() {
+        assert::all_true(
+            r#"
+sorted([2, 1.5]) == [1.5, 2]
+sorted([1, 2.5, 2]) == [1, 2, 2.5]
+sorted([3, 1.0, 2], reverse = True) == [3, 2, 1.0]
+min(1, 1.5) == 1
+max(1, 1.5) == 1.5
+min([2.5, 2, 3]) == 2
+1 < 1.5 and 1.5 < 2 and not (2 < 1.5)
+1 <= 1.0 and 1.0 >= 1
+2 > 1.5 and 1.5 > 1
+1 == 1.0 and 1.0 == 1
+1 != 1.5
 "#,
         );
+        assert::fail("1 < float('nan')", "NaN is unordered");
+        assert::fail("float('nan') < 1", "NaN is unordered");
+        assert::fail("max([1, float('nan')])", "NaN is unordered");
+        assert::is_true("1 != float('nan')");
     }
 }
@@ -21,7 +21,9 @@ use std::collections::HashMap;
 
 use derivative::Derivative;
 use derive_more::Display;
-use gazebo::{any::AnyLifetime, coerce::Coerce};
+use gazebo::{any::AnyLifetime, coerce::Coerce, prelude::*};
+use once_cell::sync::OnceCell;
+use thiserror::Error;
 
 use crate as starlark;
 use crate::{
@@ -29,6 +31,7 @@ use crate::{
     values::{
         docs,
         docs::{DocItem, DocStringKind},
+        typing::TypeCompiled,
         AllocFrozenValue, AllocValue, Freeze, FrozenHeap, FrozenValue, FrozenValueTyped, Heap,
         StarlarkValue, Trace, Value, ValueLike,
     },
@@ -37,6 +40,66 @@ use crate::{
 /// Return value of `type(any function)`.
 pub const FUNCTION_TYPE: &str = "function";
 
+/// What sort of parameter a [`SignatureParam`] is.
+#[derive(Debug, Clone, Copy, Dupe, PartialEq, Eq)]
+pub enum SignatureParamKind {
+    /// Can only be passed positionally, which only native functions have.
+    PositionalOnly,
+    /// Can be passed either positionally or by name.
+    Normal,
+    /// The `*args` parameter.
+    Args,
+    /// Follows `*` or `*args`, so can only be passed by name.
+    KeywordOnly,
+    /// The `**kwargs` parameter.
+    Kwargs,
+}
+
+impl SignatureParamKind {
+    /// The name of the kind, e.g. `keyword_only`, as used by the `signature()` function.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::PositionalOnly => "positional_only",
+            Self::Normal => "normal",
+            Self::Args => "args",
+            Self::KeywordOnly => "keyword_only",
+            Self::Kwargs => "kwargs",
+        }
+    }
+}
+
+/// A parameter of a function, as per [`Signature`].
+#[derive(Debug, Clone)]
+pub struct SignatureParam<'v> {
+    /// The name of the parameter, without any `*` or `**`.
+    pub name: String,
+    /// What sort of parameter it is.
+    pub kind: SignatureParamKind,
+    /// Whether the parameter has a default, so can be omitted.
+    /// Always `false` for `*args` and `**kwargs`.
+    pub has_default: bool,
+    /// The default value, if the parameter has one which is a value.
+    /// Native functions apply most of their defaults in Rust, in which case
+    /// `has_default` is set but this is `None`.
+    pub default: Option<Value<'v>>,
+    /// The type annotation of the parameter, if it has one.
+    /// Always `None` for native functions, whose parameters have Rust types.
+    pub typ: Option<String>,
+}
+
+/// The name and parameters of a function, as returned by [`Value::signature`].
+#[derive(Debug, Clone)]
+pub struct Signature<'v> {
+    /// The name of the function, qualified with the file name for functions defined
+    /// in Starlark, e.g. `lib.star.f`.
+    pub name: String,
+    /// The parameters, in order.
+    pub params: Vec<SignatureParam<'v>>,
+    /// The return type annotation, if there is one.
+    /// Always `None` for native functions.
+    pub return_type: Option<String>,
+}
+
 /// A native function that can be evaluated.
 ///
 /// This trait is implemented by generated code and rarely needed to be implemented manually.
@@ -96,6 +159,8 @@ pub struct NativeCallableRawDocs {
     pub rust_docstring: Option<&'static str>,
     pub signature: ParametersSpec<FrozenValue>,
     pub parameter_types: HashMap<usize, docs::Type>,
+    /// The Rust source of the default of each parameter whose default is not a value.
+    pub parameter_defaults: HashMap<usize, &'static str>,
     pub return_type: Option<docs::Type>,
 }
 
@@ -105,8 +170,11 @@ impl NativeCallableRawDocs {
         docs::Function::from_docstring(
             DocStringKind::Rust,
             |param_docs| {
-                self.signature
-                    .documentation(self.parameter_types.clone(), param_docs)
+                self.signature.documentation_with_defaults(
+                    self.parameter_types.clone(),
+                    param_docs,
+                    &self.parameter_defaults,
+                )
             },
             self.return_type.clone(),
             self.rust_docstring,
@@ -114,6 +182,51 @@ impl NativeCallableRawDocs {
     }
 }
 
+#[derive(Debug, Error)]
+enum NativeReturnTypeError {
+    #[error("Invalid return type `{1}` declared for native function `{0}`: {2}")]
+    Invalid(String, &'static str, String),
+}
+
+/// The Starlark return type declared with `#[starlark(return_type("..."))]`,
+/// checked when [`enable_runtime_typecheck`](Evaluator::enable_runtime_typecheck) is set.
+#[doc(hidden)]
+pub struct NativeReturnType {
+    ty: &'static str,
+    // Compiled the first time it is needed, with any error rendered to a string.
+    compiled: OnceCell<Result<TypeCompiled, String>>,
+}
+
+#[doc(hidden)]
+impl NativeReturnType {
+    pub const fn new(ty: &'static str) -> Self {
+        Self {
+            ty,
+            compiled: OnceCell::new(),
+        }
+    }
+
+    pub fn check<'v>(
+        &self,
+        function: &str,
+        value: Value<'v>,
+        eval: &Evaluator<'v, '_>,
+    ) -> anyhow::Result<()> {
+        if !eval.runtime_typecheck {
+            return Ok(());
+        }
+        let compiled = self
+            .compiled
+            .get_or_init(|| TypeCompiled::from_expr(self.ty).map_err(|e| format!("{:#}", e)));
+        match compiled {
+            Ok(compiled) => value.check_return_type_compiled(self.ty, compiled, function),
+            Err(e) => {
+                Err(NativeReturnTypeError::Invalid(function.to_owned(), self.ty, e.clone()).into())
+            }
+        }
+    }
+}
+
 /// Starlark representation of native (Rust) functions.
 ///
 /// Almost always created with [`#[starlark_module]`](macro@starlark_module).
@@ -217,6 +330,10 @@ impl<'v> StarlarkValue<'v> for NativeFunction {
         None
     }
 
+    fn has_attr(&self, attribute: &str) -> bool {
+        attribute == "type" && self.typ.is_some()
+    }
+
     fn dir_attr(&self) -> Vec<String> {
         if self.typ.is_some() {
             vec!["type".to_owned()]
@@ -230,6 +347,18 @@ impl<'v> StarlarkValue<'v> for NativeFunction {
             .as_ref()
             .map(|raw_docs| DocItem::Function(raw_docs.documentation()))
     }
+
+    fn signature(&self) -> Option<Signature<'v>> {
+        // The types in the docs are Rust types, so aren't reported.
+        let raw_docs = self.raw_docs.as_ref()?;
+        Some(Signature {
+            name: self.name.clone(),
+            params: raw_docs
+                .signature
+                .signature_params(HashMap::new(), &raw_docs.parameter_defaults),
+            return_type: None,
+        })
+    }
 }
 
 #[derive(Derivative, Display, NoSerialize, AnyLifetime)]
@@ -338,3 +467,35 @@ where
             .invoke_method(self.method.to_value(), self.this.to_value(), args, eval)
     }
 }
+
+/// An arbitrary function with `this` bound as the first positional argument,
+/// created by [`Heap::alloc_bound_method`].
+#[derive(Clone, Debug, Trace, Coerce, Display, Freeze, NoSerialize, AnyLifetime)]
+#[repr(C)]
+#[display(fmt = "{}", function)]
+pub(crate) struct BoundFunctionGen<V> {
+    pub(crate) function: V,
+    pub(crate) this: V,
+}
+
+starlark_complex_value!(pub(crate) BoundFunction);
+
+impl<'v, V: ValueLike<'v>> StarlarkValue<'v> for BoundFunctionGen<V>
+where
+    Self: AnyLifetime<'v>,
+{
+    starlark_type!(FUNCTION_TYPE);
+
+    fn invoke(
+        &self,
+        _me: Value<'v>,
+        args: &Arguments<'v, '_>,
+        eval: &mut Evaluator<'v, '_>,
+    ) -> anyhow::Result<Value<'v>> {
+        let mut pos = Vec::with_capacity(args.pos.len() + 1);
+        pos.push(self.this.to_value());
+        pos.extend_from_slice(args.pos);
+        let args = Arguments { pos: &pos, ..*args };
+        self.function.to_value().invoke(&args, eval)
+    }
+}
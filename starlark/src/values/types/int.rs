@@ -30,6 +30,7 @@ use std::{
 };
 
 use serde::{Serialize, Serializer};
+use thiserror::Error;
 
 use crate::{
     collections::{StarlarkHashValue, StarlarkHasher},
@@ -43,6 +44,30 @@ use crate::{
 /// The result of calling `type()` on integers.
 pub const INT_TYPE: &str = "int";
 
+/// Errors from `int` arithmetic, each giving the operation that failed, e.g. `2147483647 + 1`.
+#[derive(Debug, Error)]
+pub(crate) enum IntError {
+    #[error("Integer overflow computing `{0}`")]
+    Overflow(String),
+    #[error("Integer overflow computing `{0}`")]
+    ArithOverflow(String, f64),
+    #[error("Negative shift count computing `{0}`")]
+    NegativeShift(String),
+}
+
+#[cold]
+fn overflow<T>(a: i32, op: &str, b: i32) -> anyhow::Result<T> {
+    Err(IntError::Overflow(format!("{} {} {}", a, op, b)).into())
+}
+
+/// An arithmetic operation whose exact result `res` doesn't fit in an `int`. The evaluator
+/// gives `res` as a `float` instead of the error if the running code has
+/// [`Dialect::enable_int_overflow_to_float`](crate::syntax::Dialect::enable_int_overflow_to_float) set.
+#[cold]
+fn overflow_arith<T>(a: i32, op: &str, b: i32, res: i64) -> anyhow::Result<T> {
+    Err(IntError::ArithOverflow(format!("{} {} {}", a, op, b), res as f64).into())
+}
+
 impl<'v> AllocValue<'v> for i32 {
     fn alloc_value(self, _heap: &'v Heap) -> Value<'v> {
         Value::new_int(self)
@@ -118,39 +143,51 @@ impl<'v> StarlarkValue<'v> for PointerI32 {
         Ok(Value::new_int(self.get()))
     }
     fn minus(&self, _heap: &'v Heap) -> anyhow::Result<Value<'v>> {
-        self.get()
-            .checked_neg()
-            .map(Value::new_int)
-            .ok_or_else(|| ValueError::IntegerOverflow.into())
+        match self.get().checked_neg() {
+            Some(x) => Ok(Value::new_int(x)),
+            None => Err(IntError::ArithOverflow(
+                format!("-({})", self.get()),
+                -(self.get() as f64),
+            )
+            .into()),
+        }
+    }
+    fn bit_not(&self, _heap: &'v Heap) -> anyhow::Result<Value<'v>> {
+        Ok(Value::new_int(!self.get()))
     }
     fn add(&self, other: Value<'v>, heap: &'v Heap) -> anyhow::Result<Value<'v>> {
         match other.unpack_num() {
-            Some(Num::Int(other)) => self
-                .get()
-                .checked_add(other)
-                .map(Value::new_int)
-                .ok_or_else(|| ValueError::IntegerOverflow.into()),
+            Some(Num::Int(other)) => {
+                let a = self.get();
+                match a.checked_add(other) {
+                    Some(x) => Ok(Value::new_int(x)),
+                    None => overflow_arith(a, "+", other, a as i64 + other as i64),
+                }
+            }
             Some(Num::Float(_)) => StarlarkFloat(self.get() as f64).add(other, heap),
             None => ValueError::unsupported_with(self, "+", other),
         }
     }
     fn sub(&self, other: Value<'v>, heap: &'v Heap) -> anyhow::Result<Value<'v>> {
         match other.unpack_num() {
-            Some(Num::Int(other)) => self
-                .get()
-                .checked_sub(other)
-                .map(Value::new_int)
-                .ok_or_else(|| ValueError::IntegerOverflow.into()),
+            Some(Num::Int(other)) => {
+                let a = self.get();
+                match a.checked_sub(other) {
+                    Some(x) => Ok(Value::new_int(x)),
+                    None => overflow_arith(a, "-", other, a as i64 - other as i64),
+                }
+            }
             Some(Num::Float(_)) => StarlarkFloat(self.get() as f64).sub(other, heap),
             None => ValueError::unsupported_with(self, "-", other),
         }
     }
     fn mul(&self, other: Value<'v>, heap: &'v Heap) -> anyhow::Result<Value<'v>> {
         if let Some(other) = other.unpack_int() {
-            self.get()
-                .checked_mul(other)
-                .ok_or_else(|| ValueError::IntegerOverflow.into())
-                .map(Value::new_int)
+            let a = self.get();
+            match a.checked_mul(other) {
+                Some(x) => Ok(Value::new_int(x)),
+                None => overflow_arith(a, "*", other, a as i64 * other as i64),
+            }
         } else {
             other.mul(Value::new_int(self.get()), heap)
         }
@@ -170,8 +207,8 @@ impl<'v> StarlarkValue<'v> for PointerI32 {
             if b == 0 {
                 return Err(ValueError::DivisionByZero.into());
             }
-            // In Rust `i32::min_value() % -1` is overflow, but we should eval it to zero.
-            if self.get() == i32::min_value() && b == -1 {
+            // In Rust `i32::MIN % -1` is overflow, but we should eval it to zero.
+            if b == -1 {
                 return Ok(0);
             }
             let r = a % b;
@@ -186,15 +223,24 @@ impl<'v> StarlarkValue<'v> for PointerI32 {
         if let Some(Num::Float(_)) = other.unpack_num() {
             return StarlarkFloat(self.get() as f64).floor_div(other, heap);
         }
+        // Only `i32::MIN // -1` overflows, and must be checked before `a % b`,
+        // which panics for the same operands.
+        if other.unpack_int() == Some(-1) {
+            let a = self.get();
+            return match a.checked_neg() {
+                Some(x) => Ok(Value::new_int(x)),
+                None => overflow_arith(a, "//", -1, -(a as i64)),
+            };
+        }
         i64_arith_bin_op(self.get(), other, "//", |a, b| {
             if b == 0 {
                 return Err(ValueError::DivisionByZero.into());
             }
-            let sig = b.signum() * a.signum();
-            let offset = if sig < 0 && a % b != 0 { 1 } else { 0 };
-            match a.checked_div(b) {
-                Some(div) => Ok(div - offset),
-                None => Err(ValueError::IntegerOverflow.into()),
+            let div = a / b;
+            if a % b != 0 && (a < 0) != (b < 0) {
+                Ok(div - 1)
+            } else {
+                Ok(div)
             }
         })
     }
@@ -233,12 +279,16 @@ impl<'v> StarlarkValue<'v> for PointerI32 {
 
     fn left_shift(&self, other: Value) -> anyhow::Result<Value<'v>> {
         if let Some(other) = other.unpack_int() {
-            other
-                .try_into()
-                .ok()
-                .and_then(|unsigned_other| self.get().checked_shl(unsigned_other))
-                .map(Value::new_int)
-                .ok_or_else(|| ValueError::IntegerOverflow.into())
+            let a = self.get();
+            if other < 0 {
+                return Err(IntError::NegativeShift(format!("{} << {}", a, other)).into());
+            }
+            // Bits shifted out of an `i32` are lost, but shifting by its width or more
+            // is an overflow.
+            match a.checked_shl(other as u32) {
+                Some(x) => Ok(Value::new_int(x)),
+                None => overflow(a, "<<", other),
+            }
         } else {
             ValueError::unsupported_with(self, "<<", other)
         }
@@ -246,12 +296,14 @@ impl<'v> StarlarkValue<'v> for PointerI32 {
 
     fn right_shift(&self, other: Value) -> anyhow::Result<Value<'v>> {
         if let Some(other) = other.unpack_int() {
-            other
-                .try_into()
-                .ok()
-                .and_then(|unsigned_other| self.get().checked_shr(unsigned_other))
-                .map(Value::new_int)
-                .ok_or_else(|| ValueError::IntegerOverflow.into())
+            let a = self.get();
+            if other < 0 {
+                return Err(IntError::NegativeShift(format!("{} >> {}", a, other)).into());
+            }
+            match a.checked_shr(other as u32) {
+                Some(x) => Ok(Value::new_int(x)),
+                None => overflow(a, ">>", other),
+            }
         } else {
             ValueError::unsupported_with(self, ">>", other)
         }
@@ -275,7 +327,13 @@ impl Serialize for PointerI32 {
 
 #[cfg(test)]
 mod tests {
-    use crate::assert;
+    use crate::{
+        assert::{self, Assert},
+        environment::{Globals, Module},
+        eval::Evaluator,
+        syntax::{AstModule, Dialect},
+        values::{float::StarlarkFloat, Heap, Value, ValueLike},
+    };
 
     #[test]
     fn test_arithmetic_operators() {
@@ -295,4 +353,230 @@ mod tests {
 "#,
         );
     }
+
+    // `-2147483648` isn't a valid literal, since `2147483648` doesn't fit in an int.
+    const EXTREMES: &str = "MAX = 2147483647\nMIN = -2147483647 - 1\n";
+
+    #[test]
+    fn test_int_overflow_error() {
+        let a = Assert::new();
+        let fail = |program: &str, operation: &str| {
+            a.fail(
+                &format!("{}{}", EXTREMES, program),
+                &format!("Integer overflow computing `{}`", operation),
+            );
+        };
+        fail("MAX + 1", "2147483647 + 1");
+        fail("MIN + -1", "-2147483648 + -1");
+        fail("MIN - 1", "-2147483648 - 1");
+        fail("MAX - MIN", "2147483647 - -2147483648");
+        fail("MAX * 2", "2147483647 * 2");
+        fail("MIN * -1", "-2147483648 * -1");
+        fail("MIN // -1", "-2147483648 // -1");
+        fail("-MIN", "-(-2147483648)");
+        fail("1 << 32", "1 << 32");
+        fail("0 << 100", "0 << 100");
+        fail("MIN >> 32", "-2147483648 >> 32");
+        fail("abs(MIN)", "abs(-2147483648)");
+        fail("x = MAX\nx += 1", "2147483647 + 1");
+        fail(
+            "def f(x):\n    return x * x\nf(MAX)",
+            "2147483647 * 2147483647",
+        );
+        a.all_true(&format!(
+            "{}{}",
+            EXTREMES,
+            r#"
+MAX + MIN == -1
+MAX - 1 + 1 == MAX
+MIN + 1 - 1 == MIN
+MIN * 1 == MIN
+MAX * -1 == -MAX
+-MAX == MIN + 1
+abs(-MAX) == MAX
+MIN // 1 == MIN
+MAX // -1 == -MAX
+MIN // 2 == -1073741824
+MIN // MAX == -2
+MAX // MIN == -1
+MIN % -1 == 0
+MIN % MAX == MAX - 1
+MAX % MIN == -1
+MIN % 2 == 0
+-1 << 31 == MIN
+1 << 31 == MIN
+MAX << 1 == -2
+MIN << 1 == 0
+1 << 30 == 1073741824
+MAX >> 31 == 0
+MIN >> 31 == -1
+type(MAX + 0.5) == "float"
+"#
+        ));
+    }
+
+    #[test]
+    fn test_int_overflow_to_float() {
+        let mut a = Assert::new();
+        a.dialect_set(|d| d.enable_int_overflow_to_float = true);
+        a.all_true(&format!(
+            "{}{}",
+            EXTREMES,
+            r#"
+MAX + 1 == 2147483648.0
+MIN + -1 == -2147483649.0
+MIN - 1 == -2147483649.0
+MAX - MIN == 4294967295.0
+MAX * 2 == 4294967294.0
+MIN * -1 == 2147483648.0
+MAX * MAX == 2147483647.0 * 2147483647.0
+MIN // -1 == 2147483648.0
+-MIN == 2147483648.0
+type(MAX + 1) == "float"
+type(MAX - 1 + 1) == "int"
+type(MIN // -2) == "int"
+MIN % -1 == 0
+"#
+        ));
+        a.is_true(&format!("{}x = MAX\nx += 1\nx == 2147483648.0", EXTREMES));
+        a.is_true(&format!(
+            "{}def f(x):\n    return x * x\nf(MAX) == 2147483647.0 * 2147483647.0",
+            EXTREMES
+        ));
+        // Only the arithmetic operators promote, shifts still fail.
+        a.fail(
+            &format!("{}1 << 32", EXTREMES),
+            "Integer overflow computing `1 << 32`",
+        );
+        a.fail(
+            &format!("{}abs(MIN)", EXTREMES),
+            "Integer overflow computing `abs(-2147483648)`",
+        );
+    }
+
+    #[test]
+    fn test_int_overflow_to_float_per_module() {
+        // The option belongs to the code doing the arithmetic, not the module calling it.
+        let globals = Globals::standard();
+        let mut dialect = Dialect::Standard;
+        dialect.enable_int_overflow_to_float = true;
+        let promoted = Module::new();
+        let ast = AstModule::parse(
+            "promoted.star",
+            "def inc(x):\n    return x + 1\n".to_owned(),
+            &dialect,
+        )
+        .unwrap();
+        Evaluator::new(&promoted)
+            .eval_module(ast, &globals)
+            .unwrap();
+        let inc = promoted.freeze().unwrap().get("inc").unwrap();
+
+        let module = Module::new();
+        module.set("inc", inc.owned_value(module.frozen_heap()));
+        let mut eval = Evaluator::new(&module);
+        let mut run = |code: &str| {
+            let ast = AstModule::parse("a.star", code.to_owned(), &Dialect::Standard).unwrap();
+            eval.eval_module(ast, &globals)
+        };
+        let res = run("inc(2147483647)").unwrap();
+        assert_eq!(
+            Some(2147483648.0),
+            res.downcast_ref::<StarlarkFloat>().map(|x| x.0)
+        );
+        assert!(run("2147483647 + 1").is_err());
+
+        // Operations from Rust never promote.
+        let heap = Heap::new();
+        assert!(Value::new_int(i32::MAX)
+            .add(Value::new_int(1), &heap)
+            .is_err());
+    }
+
+    #[test]
+    fn test_division_by_zero() {
+        for op in ["/", "//", "%"] {
+            for (x, y) in [("1", "0"), ("1", "0.0"), ("1.0", "0"), ("1.0", "0.0")] {
+                assert::fail(&format!("{} {} {}", x, op, y), "Cannot divide by zero");
+            }
+            assert::fail(
+                &format!("x = 2147483647\nx {}= 0", op),
+                "Cannot divide by zero",
+            );
+        }
+    }
+
+    #[test]
+    fn test_int_ops_never_panic() {
+        // Operands near the edges, where things go wrong, mixed with arbitrary ones.
+        let edges = [
+            i32::MIN,
+            i32::MIN + 1,
+            -65536,
+            -33,
+            -32,
+            -31,
+            -2,
+            -1,
+            0,
+            1,
+            2,
+            31,
+            32,
+            33,
+            65536,
+            i32::MAX - 1,
+            i32::MAX,
+        ];
+        // A fixed linear congruential generator, so failures are reproducible.
+        let mut state = 0x2545F4914F6CDD1Du64;
+        let mut operand = || {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            let x = (state >> 32) as u32;
+            if x % 3 == 0 {
+                edges[(x / 3) as usize % edges.len()]
+            } else {
+                x as i32
+            }
+        };
+        let globals = Globals::extended();
+        for promote in [false, true] {
+            let mut dialect = Dialect::Extended;
+            dialect.enable_int_overflow_to_float = promote;
+            for _ in 0..150 {
+                let (x, y) = (operand(), operand());
+                for op in [
+                    "+", "-", "*", "/", "//", "%", "&", "|", "^", "<<", ">>", "+=",
+                ] {
+                    let program = match op {
+                        "+=" => "x += y\nx".to_owned(),
+                        _ => format!("-x, abs(x), ~x, x {} y", op),
+                    };
+                    let module = Module::new();
+                    module.set("x", Value::new_int(x));
+                    module.set("y", Value::new_int(y));
+                    let ast = AstModule::parse("fuzz.star", program, &dialect).unwrap();
+                    let mut eval = Evaluator::new(&module);
+                    // Any result is fine, as long as it isn't a panic.
+                    let res = eval.eval_module(ast, &globals);
+                    if op == "+=" {
+                        let expected = x as i64 + y as i64;
+                        match (res, i32::try_from(expected)) {
+                            (Ok(v), Ok(expected)) => assert_eq!(Some(expected), v.unpack_int()),
+                            (Ok(v), Err(_)) if promote => assert_eq!(
+                                Some(expected as f64),
+                                v.downcast_ref::<StarlarkFloat>().map(|x| x.0)
+                            ),
+                            (Err(e), Err(_)) if !promote => {
+                                assert!(e.to_string().contains("Integer overflow"), "{}", e)
+                            }
+                            (res, _) => panic!("{} += {} gave {:?}", x, y, res.map(|v| v.to_str())),
+                        }
+                    }
+                }
+            }
+        }
+    }
 }
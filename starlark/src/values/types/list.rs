@@ -25,7 +25,8 @@ use std::{
     fmt::{self, Debug, Display, Formatter},
     intrinsics::{likely, unlikely},
     marker::PhantomData,
-    ops::Deref,
+    mem,
+    ops::{ControlFlow, Deref},
     slice,
 };
 
@@ -40,10 +41,11 @@ use crate::{
     self as starlark,
     environment::{Methods, MethodsStatic},
     values::{
-        array::Array,
+        array::{Array, ArrayIter},
         comparison::{compare_slice, equals_slice},
         display::display_container,
-        error::ValueError,
+        error::{FrozenValueMutated, ValueError},
+        for_each_in,
         index::{apply_slice, convert_index},
         AllocFrozenValue, AllocValue, FrozenHeap, FrozenStringValue, FrozenValue, Heap,
         StarlarkValue, UnpackValue, Value, ValueLike, ValueTyped,
@@ -124,7 +126,7 @@ impl<'v> List<'v> {
 
     pub(crate) fn from_value_mut(x: Value<'v>) -> anyhow::Result<Option<&'v Self>> {
         if unlikely(x.unpack_frozen().is_some()) {
-            return Err(ValueError::CannotMutateImmutableValue.into());
+            return Err(FrozenValueMutated::error(x));
         }
         let ptr = x.downcast_ref::<ListGen<List<'v>>>();
         match ptr {
@@ -148,36 +150,127 @@ impl<'v> List<'v> {
 
     /// Return an error if there's at least one iterator over the list.
     fn check_can_mutate(&self) -> anyhow::Result<()> {
-        if unlikely(self.content.get().as_ref().iter_count_is_non_zero()) {
+        let array = self.content.get();
+        if unlikely(array.iter_count_is_non_zero())
+            && !self.only_other_lists_iterate(array.as_ref())
+        {
             return Err(ValueError::MutationDuringIteration.into());
         }
         Ok(())
     }
 
+    /// The iterators of a shared array might all be over other lists sharing it,
+    /// in which case this list can still be modified, after copying the array.
+    #[cold]
+    #[inline(never)]
+    fn only_other_lists_iterate(&self, array: &Array<'v>) -> bool {
+        match array.heap() {
+            Some(heap) if array.is_shared() => !heap.has_shared_list_iterator(self.address()),
+            _ => false,
+        }
+    }
+
+    /// Address identifying this list while it is iterated,
+    /// which is stable as there is no garbage collection during iteration.
+    fn address(&self) -> usize {
+        self as *const Self as usize
+    }
+
+    /// Replace the array of this list with a copy with capacity `cap`.
+    fn copy_array(&self, cap: usize, heap: &'v Heap) {
+        let array = self.content.get();
+        let new_array = heap.alloc_array(cap);
+        new_array.extend_from_slice(array.content());
+        if array.is_shared() {
+            array.remove_sharer();
+        }
+        self.content.set(new_array);
+    }
+
     #[cold]
     #[inline(never)]
     fn reserve_additional_slow(&self, additional: usize, heap: &'v Heap) {
         let new_cap = cmp::max(self.len() + additional, self.len() * 2);
-        // Size of `Array` is 2 words and size of `List` is one word,
+        // Size of `Array` is 3 words and size of `List` is one word,
         // so allocating at least 4 words would not be too large waste.
         // Note `Vec` allocates 4 by default.
         // Also note `Array` removes extra capacity on GC.
         let new_cap = cmp::max(new_cap, 4);
 
-        let new_array = heap.alloc_array(new_cap);
-        new_array.extend_from_slice(self.content());
-        self.content.set(new_array);
+        self.copy_array(new_cap, heap);
+    }
+
+    /// Capacity which can be used without reallocating,
+    /// which is none if the array is shared with another list.
+    #[inline(always)]
+    fn remaining_capacity(&self) -> usize {
+        let array = self.content.get();
+        if unlikely(array.is_shared()) {
+            0
+        } else {
+            array.as_ref().remaining_capacity()
+        }
     }
 
     #[inline(always)]
     fn reserve_additional(&self, additional: usize, heap: &'v Heap) {
-        if likely(self.content.get().as_ref().remaining_capacity() >= additional) {
+        let array = self.content.get();
+        if likely(!array.is_shared() && array.as_ref().remaining_capacity() >= additional) {
             return;
         }
 
         self.reserve_additional_slow(additional, heap);
     }
 
+    /// Release the capacity beyond the length of this list, as `Vec::shrink_to_fit`.
+    /// A shared array is never modified, so it is left alone.
+    pub(crate) fn shrink(&self, heap: &'v Heap) {
+        let array = self.content.get();
+        if !array.is_shared() && array.capacity() > array.len() {
+            self.copy_array(array.len(), heap);
+        }
+    }
+
+    /// Stop sharing the array with other lists, so it can be modified.
+    #[inline(always)]
+    fn unshare(&self) {
+        if unlikely(self.content.get().is_shared()) {
+            self.unshare_slow();
+        }
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn unshare_slow(&self) {
+        let heap = self
+            .content
+            .get()
+            .heap()
+            .expect("shared array records its heap");
+        self.copy_array(self.len(), heap);
+    }
+
+    /// Copy this list, as `list(xs)` or `xs[:]`. The copy shares the array of this list
+    /// until either list is modified, so copying is `O(1)`.
+    pub(crate) fn copy(&self, heap: &'v Heap) -> Value<'v> {
+        let array = self.content.get();
+        if array.add_sharer(heap) {
+            heap.alloc_list_array(array)
+        } else {
+            heap.alloc_list(array.content())
+        }
+    }
+
+    /// Copy `x` if it is a list, see [`copy`](List::copy).
+    pub(crate) fn copy_value(x: Value<'v>, heap: &'v Heap) -> Option<Value<'v>> {
+        if x.unpack_frozen().is_some() {
+            let xs = x.downcast_ref::<ListGen<FrozenList>>()?;
+            Some(heap.alloc_list(coerce(xs.0.content())))
+        } else {
+            Some(x.downcast_ref::<ListGen<List>>()?.0.copy(heap))
+        }
+    }
+
     pub(crate) fn double(&self, heap: &'v Heap) {
         self.reserve_additional(self.len(), heap);
         self.content.get().double();
@@ -194,7 +287,7 @@ impl<'v> List<'v> {
                 // Extend will panic if upper bound is provided incorrectly.
                 self.content.get().extend(iter);
             }
-            Some(hi) if self.content.get().remaining_capacity() >= hi => {
+            Some(hi) if self.remaining_capacity() >= hi => {
                 // Enough capacity for upper bound.
                 // Extend will panic if upper bound is provided incorrectly.
                 self.content.get().extend(iter);
@@ -209,13 +302,46 @@ impl<'v> List<'v> {
         }
     }
 
+    pub(crate) fn extend_from_slice(&self, slice: &[Value<'v>], heap: &'v Heap) {
+        self.reserve_additional(slice.len(), heap);
+        self.content.get().extend_from_slice(slice);
+    }
+
+    /// Append the elements of the iterable `other` to this list, which is `me`.
+    /// This is `me.extend(other)` and `me += other` for lists.
+    pub(crate) fn extend_from_value(
+        &self,
+        me: Value<'v>,
+        other: Value<'v>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<()> {
+        if me.ptr_eq(other) {
+            // If the values alias, we can't borrow `other` for iteration,
+            // but appending a list to itself is just doubling it.
+            self.double(heap);
+        } else if let Some(other) = List::from_value(other) {
+            // Another list, so copy its elements directly without an iterator.
+            self.extend_from_slice(other.content(), heap);
+        } else {
+            other.with_iterator(heap, |it| self.extend(it, heap))?;
+        }
+        Ok(())
+    }
+
     pub(crate) fn push(&self, value: Value<'v>, heap: &'v Heap) {
         self.reserve_additional(1, heap);
         self.content.get().push(value);
     }
 
     pub(crate) fn clear(&self) {
-        self.content.get().clear();
+        let array = self.content.get();
+        if array.is_shared() {
+            array.remove_sharer();
+            let heap = array.heap().expect("shared array records its heap");
+            self.content.set(heap.alloc_array(0));
+        } else {
+            array.clear();
+        }
     }
 
     pub(crate) fn insert(&self, index: usize, value: Value<'v>, heap: &'v Heap) {
@@ -224,8 +350,70 @@ impl<'v> List<'v> {
     }
 
     pub(crate) fn remove(&self, index: usize) -> Value<'v> {
+        self.unshare();
         self.content.get().remove(index)
     }
+
+    pub(crate) fn set_at(&self, i: usize, v: Value<'v>) -> anyhow::Result<()> {
+        self.check_can_mutate()?;
+        self.unshare();
+        self.content.get().set_at(i, v);
+        Ok(())
+    }
+
+    /// Iterate the list, stopping it being modified until the iterator is dropped.
+    /// A shared array is iterated in place, as it is never modified,
+    /// and this list is recorded in the heap as iterating it.
+    fn list_iter<'a>(&'a self) -> ListIter<'a, 'v> {
+        let array = self.content.get().as_ref();
+        let heap = if unlikely(array.is_shared()) {
+            let heap = array.heap().expect("shared array records its heap");
+            heap.add_shared_list_iterator(self.address());
+            Some(heap)
+        } else {
+            None
+        };
+        ListIter {
+            list: self,
+            heap,
+            iter: array.iter(),
+        }
+    }
+}
+
+/// Iterator over a [`List`], which stops the list being modified until dropped.
+struct ListIter<'a, 'v> {
+    list: &'a List<'v>,
+    /// The heap this list is recorded in as iterating a shared array.
+    heap: Option<&'v Heap>,
+    iter: ArrayIter<'a, 'v>,
+}
+
+impl<'a, 'v> Iterator for ListIter<'a, 'v> {
+    type Item = Value<'v>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Value<'v>> {
+        self.iter.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<'a, 'v> ExactSizeIterator for ListIter<'a, 'v> {
+    fn len(&self) -> usize {
+        self.iter.len()
+    }
+}
+
+impl<'a, 'v> Drop for ListIter<'a, 'v> {
+    fn drop(&mut self) {
+        if let Some(heap) = self.heap {
+            heap.remove_shared_list_iterator(self.list.address());
+        }
+    }
 }
 
 impl<'v> Deref for ListRef<'v> {
@@ -238,7 +426,7 @@ impl<'v> Deref for ListRef<'v> {
 
 impl<'v, V: AllocValue<'v>> AllocValue<'v> for Vec<V> {
     fn alloc_value(self, heap: &'v Heap) -> Value<'v> {
-        heap.alloc_list_iter(self.into_map(|x| x.alloc_value(heap)))
+        heap.alloc_list_iter(self.into_iter().map(|x| x.alloc_value(heap)))
     }
 }
 
@@ -333,7 +521,7 @@ impl<'v> List<'v> {
     where
         'v: 'a,
     {
-        self.content.get().as_ref().iter()
+        self.list_iter()
     }
 }
 
@@ -368,6 +556,7 @@ impl FrozenList {
 // This trait need to be `pub(crate)` because `ListGen<T>` is.
 pub(crate) trait ListLike<'v>: Debug {
     fn content(&self) -> &[Value<'v>];
+    /// Set an element, copying the array first if it is shared with another list.
     fn set_at(&self, i: usize, v: Value<'v>) -> anyhow::Result<()>;
     fn iterate<'a>(&'a self) -> Box<dyn Iterator<Item = Value<'v>> + 'a>
     where
@@ -376,6 +565,8 @@ pub(crate) trait ListLike<'v>: Debug {
         &self,
         f: &mut dyn FnMut(&mut dyn Iterator<Item = Value<'v>>) -> anyhow::Result<()>,
     ) -> anyhow::Result<()>;
+    /// A new mutable list with the same elements.
+    fn copy(&self, heap: &'v Heap) -> Value<'v>;
 }
 
 impl<'v> ListLike<'v> for List<'v> {
@@ -384,23 +575,25 @@ impl<'v> ListLike<'v> for List<'v> {
     }
 
     fn set_at(&self, i: usize, v: Value<'v>) -> anyhow::Result<()> {
-        self.check_can_mutate()?;
-        self.content.get().set_at(i, v);
-        Ok(())
+        List::set_at(self, i, v)
     }
 
     fn iterate<'a>(&'a self) -> Box<dyn Iterator<Item = Value<'v>> + 'a>
     where
         'v: 'a,
     {
-        box self.content.get().as_ref().iter()
+        box self.list_iter()
     }
 
     fn with_iterator(
         &self,
         f: &mut dyn FnMut(&mut dyn Iterator<Item = Value<'v>>) -> anyhow::Result<()>,
     ) -> anyhow::Result<()> {
-        f(&mut self.content.get().iter())
+        f(&mut self.list_iter())
+    }
+
+    fn copy(&self, heap: &'v Heap) -> Value<'v> {
+        List::copy(self, heap)
     }
 }
 
@@ -426,6 +619,10 @@ impl<'v> ListLike<'v> for FrozenList {
     ) -> anyhow::Result<()> {
         f(&mut coerce(self.content()).iter().copied())
     }
+
+    fn copy(&self, heap: &'v Heap) -> Value<'v> {
+        heap.alloc_list(coerce(self.content()))
+    }
 }
 
 impl<T: Display> Display for ListGen<T> {
@@ -523,6 +720,9 @@ where
         stride: Option<Value>,
         heap: &'v Heap,
     ) -> anyhow::Result<Value<'v>> {
+        if start.is_none() && stop.is_none() && stride.is_none() {
+            return Ok(self.0.copy(heap));
+        }
         let xs = self.0.content();
         let res = apply_slice(&*xs, start, stop, stride)?;
         Ok(heap.alloc_list(&res))
@@ -546,6 +746,17 @@ where
         self.0.with_iterator(f)
     }
 
+    fn for_each_element(
+        &self,
+        _heap: &'v Heap,
+        f: &mut dyn FnMut(Value<'v>) -> ControlFlow<()>,
+    ) -> anyhow::Result<()> {
+        self.0.with_iterator(&mut |it| {
+            for_each_in(it, f);
+            Ok(())
+        })
+    }
+
     fn add(&self, other: Value<'v>, heap: &'v Heap) -> anyhow::Result<Value<'v>> {
         if let Some(other) = List::from_value(other) {
             Ok(heap.alloc_list_concat(self.0.content(), other.content()))
@@ -556,9 +767,12 @@ where
 
     fn mul(&self, other: Value, heap: &'v Heap) -> anyhow::Result<Value<'v>> {
         let l = i32::unpack_param(other)?;
-        let mut result = Vec::with_capacity(self.0.content().len() * cmp::max(0, l) as usize);
+        let content = self.0.content();
+        let len = content.len().saturating_mul(cmp::max(0, l) as usize);
+        heap.check_max_bytes(len.saturating_mul(mem::size_of::<Value>()))?;
+        let mut result = Vec::with_capacity(len);
         for _ in 0..l {
-            result.extend(self.0.content().iter());
+            result.extend(content.iter());
         }
         Ok(heap.alloc_list(&result))
     }
@@ -640,7 +854,81 @@ impl<'v, V: UnpackValue<'v>> Deref for ListOf<'v, V> {
 
 #[cfg(test)]
 mod tests {
-    use crate::assert::{self, Assert};
+    use std::mem;
+
+    use crate::{
+        assert::{self, Assert},
+        environment::{Globals, Module},
+        eval::Evaluator,
+        syntax::{AstModule, Dialect},
+        values::{list::List, FrozenValue, Heap, Value},
+    };
+
+    const GROWN_LEN: usize = 1025;
+
+    /// Create a list by repeated `push`, so its capacity is well beyond its length.
+    fn grown_list<'v>(module: &'v Module) -> Value<'v> {
+        let list = module.heap().alloc_list(&[]);
+        let list_mut = List::from_value_mut(list).unwrap().unwrap();
+        for i in 0..GROWN_LEN {
+            list_mut.push(Value::new_int(i as i32), module.heap());
+        }
+        assert!(list_mut.content.get().capacity() > GROWN_LEN + GROWN_LEN / 2);
+        list
+    }
+
+    #[test]
+    fn test_grown_list_shrinks_on_freeze() {
+        let module = Module::new();
+        module.set("x", grown_list(&module));
+        let frozen = module.freeze().unwrap();
+
+        let summary = frozen.frozen_heap().allocated_summary().summary();
+        let (count, bytes) = summary["list"];
+        assert_eq!(count, 1);
+        let tight = GROWN_LEN * mem::size_of::<FrozenValue>();
+        assert!(
+            bytes >= tight && bytes <= tight + tight / 50,
+            "Frozen list uses {} bytes, expected close to {}",
+            bytes,
+            tight
+        );
+    }
+
+    #[test]
+    fn test_grown_list_shrinks_on_gc() {
+        let module = Module::new();
+        module.set("x", grown_list(&module));
+        let mut eval = Evaluator::new(&module);
+        let ast = AstModule::parse("a.star", "None".to_owned(), &Dialect::Standard).unwrap();
+        eval.eval_module(ast, &Globals::standard()).unwrap();
+        unsafe { eval.garbage_collect() };
+
+        let x = module.get("x").unwrap();
+        let array = List::from_value_mut(x).unwrap().unwrap().content.get();
+        assert_eq!(GROWN_LEN, array.len());
+        assert_eq!(GROWN_LEN, array.capacity());
+    }
+
+    #[test]
+    fn test_grown_list_shrink() {
+        let module = Module::new();
+        let list = List::from_value_mut(grown_list(&module)).unwrap().unwrap();
+        list.shrink(module.heap());
+        assert_eq!(GROWN_LEN, list.content.get().len());
+        assert_eq!(GROWN_LEN, list.content.get().capacity());
+        assert_eq!(Value::new_int(7), list.content()[7]);
+    }
+
+    #[test]
+    fn test_alloc_list_iter_is_tight() {
+        let heap = Heap::new();
+        // A filter has no lower bound on its size, so the list grows while it is built.
+        let list = heap.alloc_list_iter((0..GROWN_LEN as i32).filter(|_| true).map(Value::new_int));
+        let array = List::from_value_mut(list).unwrap().unwrap().content.get();
+        assert_eq!(GROWN_LEN, array.len());
+        assert_eq!(GROWN_LEN, array.capacity());
+    }
 
     #[test]
     fn test_to_str() {
@@ -714,4 +1002,256 @@ def list_result():
         );
         a.is_true("load('x','list_result')\nx = list_result()\nx += [8]\nx == [1, 2, 4, 8]");
     }
+
+    #[test]
+    fn test_extend_aliased() {
+        assert::all_true(
+            r#"
+(lambda xs: [xs.extend(xs), xs][1])([1, 2]) == [1, 2, 1, 2]
+(lambda xs: [xs.extend(xs), xs.extend(xs), xs][2])([1]) == [1, 1, 1, 1]
+"#,
+        );
+        assert::is_true(
+            r#"
+xs = [1, 2]
+xs += xs
+ys = xs
+xs += ys
+xs == [1, 2, 1, 2, 1, 2, 1, 2] and ys == xs
+"#,
+        );
+    }
+
+    #[test]
+    fn test_extend_from_iterables() {
+        assert::is_true(
+            r#"
+xs = [0]
+xs += (1, 2)
+xs.extend([3])
+xs += range(4, 6)
+xs.extend({6: None, 7: None})
+xs.extend("ab".elems())
+xs == [0, 1, 2, 3, 4, 5, 6, 7, "a", "b"]
+"#,
+        );
+        assert::fail("xs = []; xs.extend(1)", "not supported");
+        assert::fail("xs = []; xs += 1", "not supported");
+    }
+
+    #[test]
+    fn test_extend_during_iteration() {
+        assert::fail(
+            "def f():\n  xs = [1]\n  for x in xs:\n    xs += [x]\nf()",
+            "mutate an iterable",
+        );
+        assert::fail(
+            "def f():\n  xs = [1]\n  for x in xs:\n    xs.extend(xs)\nf()",
+            "mutate an iterable",
+        );
+        // Extending a different list from the one being iterated is fine.
+        assert::is_true(
+            "def f():\n  xs = [1, 2]\n  ys = []\n  for x in xs:\n    ys += xs\n  return ys\nf() == [1, 2, 1, 2]",
+        );
+    }
+
+    #[test]
+    fn test_extend_range_reserves_exactly() {
+        const N: usize = 1000000;
+        let module = Module::new();
+        let mut eval = Evaluator::new(&module);
+        let ast = AstModule::parse(
+            "a.star",
+            format!("xs = [0]\nxs += range({})", N),
+            &Dialect::Standard,
+        )
+        .unwrap();
+        eval.eval_module(ast, &Globals::standard()).unwrap();
+        drop(eval);
+
+        let xs = module.get("xs").unwrap();
+        assert_eq!(N + 1, xs.length().unwrap() as usize);
+        // The exact size of the range is known up front, so the list grows once,
+        // instead of reallocating repeatedly by doubling.
+        let tight = N * mem::size_of::<Value>();
+        let bytes = module.heap().allocated_bytes();
+        assert!(
+            bytes < tight + tight / 4,
+            "Heap uses {} bytes, expected close to {}",
+            bytes,
+            tight
+        );
+    }
+
+    #[test]
+    fn test_copy_mutate_original() {
+        assert::is_true(
+            r#"
+def f():
+    res = []
+    for op in range(8):
+        xs = [1, 2, 3]
+        ys = xs[:] if op % 2 else list(xs)
+        if op == 0: xs.append(4)
+        if op == 1: xs[0] = 4
+        if op == 2: xs.pop()
+        if op == 3: xs.remove(2)
+        if op == 4: xs.clear()
+        if op == 5: xs.insert(0, 4)
+        if op == 6: xs.extend([4])
+        if op == 7: xs += [4]
+        res.append(ys == [1, 2, 3] and xs != ys)
+    return res
+f() == [True] * 8
+"#,
+        );
+    }
+
+    #[test]
+    fn test_copy_mutate_copy() {
+        assert::is_true(
+            r#"
+def f():
+    res = []
+    for op in range(8):
+        xs = [1, 2, 3]
+        ys = xs[:] if op % 2 else list(xs)
+        if op == 0: ys.append(4)
+        if op == 1: ys[0] = 4
+        if op == 2: ys.pop()
+        if op == 3: ys.remove(2)
+        if op == 4: ys.clear()
+        if op == 5: ys.insert(0, 4)
+        if op == 6: ys.extend([4])
+        if op == 7: ys += [4]
+        res.append(xs == [1, 2, 3] and xs != ys)
+    return res
+f() == [True] * 8
+"#,
+        );
+    }
+
+    #[test]
+    fn test_copy_of_copy() {
+        assert::is_true(
+            r#"
+def f():
+    xs = [1, 2]
+    ys = xs[:]
+    zs = ys[:]
+    ys.append(3)
+    zs.append(4)
+    xs.append(5)
+    return xs == [1, 2, 5] and ys == [1, 2, 3] and zs == [1, 2, 4]
+f()
+"#,
+        );
+    }
+
+    #[test]
+    fn test_copy_iterate() {
+        // Iterating a copy doesn't stop the original being modified, and vice versa.
+        assert::is_true(
+            r#"
+def f():
+    xs = [1, 2, 3]
+    for x in xs[:]:
+        xs.remove(x)
+    ys = [1, 2, 3]
+    zs = ys[:]
+    for y in ys:
+        zs.append(y)
+    return xs == [] and ys == [1, 2, 3] and zs == [1, 2, 3, 1, 2, 3]
+f()
+"#,
+        );
+        assert::fail(
+            "def f():\n  xs = [1]\n  ys = xs[:]\n  for y in ys:\n    ys.append(y)\nf()",
+            "mutate an iterable",
+        );
+        // A list being iterated can be copied, and the copy modified.
+        assert::is_true(
+            "def f():\n  xs = [1, 2]\n  for x in xs:\n    ys = list(xs)\n    ys.append(x)\n  return ys\nf() == [1, 2, 2]",
+        );
+        // While both lists sharing an array are iterated, neither can be modified,
+        // and once one loop ends that list can be.
+        assert::is_true(
+            r#"
+def f():
+    xs = [1, 2]
+    ys = xs[:]
+    for x in xs:
+        for y in ys:
+            pass
+        ys.append(x)
+    return xs == [1, 2] and ys == [1, 2, 1, 2]
+f()
+"#,
+        );
+        assert::fail(
+            "def f():\n  xs = [1]\n  ys = xs[:]\n  for x in xs:\n    for y in ys:\n      xs.append(y)\nf()",
+            "mutate an iterable",
+        );
+    }
+
+    #[test]
+    fn test_list_is_one_word() {
+        // Copy-on-write state is kept in the array, so lists stay small.
+        assert_eq!(mem::size_of::<usize>(), mem::size_of::<List>());
+    }
+
+    #[test]
+    fn test_copy_freeze() {
+        let mut a = Assert::new();
+        a.module(
+            "m",
+            r#"
+xs = [1, 2, 3]
+ys = xs[:]
+ys.append(4)
+shared = [5, 6]
+shared_copy = list(shared)
+def copy_shared():
+    return shared[:]
+"#,
+        );
+        a.is_true(
+            r#"
+load("m", "xs", "ys", "shared", "shared_copy", "copy_shared")
+zs = copy_shared()
+zs.append(7)
+xs == [1, 2, 3] and ys == [1, 2, 3, 4] and shared == [5, 6] and shared_copy == [5, 6] and zs == [5, 6, 7]
+"#,
+        );
+        a.fail(
+            "load('m', 'shared_copy')\nshared_copy.append(1)",
+            "Immutable",
+        );
+    }
+
+    #[test]
+    fn test_copy_is_constant_time() {
+        const N: usize = 100000;
+        const COPIES: usize = 5000;
+        let module = Module::new();
+        let mut eval = Evaluator::new(&module);
+        let program = format!(
+            "def f():\n  xs = list(range({}))\n  for _ in range({}):\n    ys = xs[:]\n    zs = list(xs)\n    for y in ys:\n      break\nf()",
+            N, COPIES
+        );
+        let ast = AstModule::parse("a.star", program, &Dialect::Standard).unwrap();
+        eval.eval_module(ast, &Globals::standard()).unwrap();
+        drop(eval);
+
+        // Copying the elements each time, including to iterate the copy,
+        // would allocate `N * COPIES` values.
+        let tight = N * mem::size_of::<Value>();
+        let bytes = module.heap().allocated_bytes();
+        assert!(
+            bytes < tight * 2,
+            "Heap uses {} bytes, expected close to {}",
+            bytes,
+            tight
+        );
+    }
 }
@@ -18,6 +18,7 @@
 pub mod any;
 pub mod array;
 pub mod bool;
+pub mod bytes;
 pub mod dict;
 pub mod enumeration;
 pub mod float;
@@ -26,8 +27,10 @@ pub mod int;
 pub(crate) mod known_methods;
 pub mod list;
 pub mod none;
+pub(crate) mod promise;
 pub mod range;
 pub mod record;
+pub mod set;
 pub mod string;
 pub mod structs;
 pub mod tuple;
@@ -0,0 +1,265 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! The promise type, a placeholder for a value which is only known later in evaluation.
+//!
+//! Promises are not part of the Starlark standard, so the `promise()` constructor is only
+//! available with [`LibraryExtension::Promise`](crate::environment::LibraryExtension::Promise).
+//!
+//! A promise is set exactly once with `p.set(x)`. When the module is frozen every promise
+//! is replaced by its value, so frozen values never contain promises. Freezing fails if
+//! any promise reachable from the module was never set.
+
+use std::{
+    cell::Cell,
+    fmt::{self, Display},
+    ptr,
+};
+
+use gazebo::any::AnyLifetime;
+use thiserror::Error;
+
+use crate as starlark;
+use crate::{
+    codemap::FileSpan,
+    environment::{Methods, MethodsStatic},
+    values::{AllocValue, Heap, StarlarkValue, Trace, Value, ValueLike},
+};
+
+#[derive(Debug, Error)]
+enum PromiseError {
+    #[error("Promise created at {0} has not been set")]
+    NotSet(String),
+    #[error("Promise created at {0} has already been set")]
+    AlreadySet(String),
+    #[error("Promise created at {0} can't be set to itself")]
+    SetToItself(String),
+    #[error("Can't freeze a module with promises which were never set, created at: {}", .0.join(", "))]
+    NeverSet(Vec<String>),
+}
+
+/// A value which is set once, later in evaluation, created by `promise()`.
+#[derive(Debug, Trace, AnyLifetime, NoSerialize)]
+pub(crate) struct Promise<'v> {
+    value: Cell<Option<Value<'v>>>,
+    /// Where `promise()` was called, to name the promise in errors.
+    #[trace(unsafe_ignore)]
+    created: Option<FileSpan>,
+}
+
+impl<'v> Promise<'v> {
+    /// The result of calling `type()` on promises.
+    pub(crate) const TYPE: &'static str = "promise";
+
+    pub(crate) fn new(created: Option<FileSpan>) -> Self {
+        Self {
+            value: Cell::new(None),
+            created,
+        }
+    }
+
+    pub(crate) fn from_value(x: Value<'v>) -> Option<&'v Self> {
+        x.downcast_ref()
+    }
+
+    fn created(&self) -> String {
+        match &self.created {
+            Some(span) => span.to_string(),
+            None => "<native>".to_owned(),
+        }
+    }
+
+    /// The value the promise was set to.
+    pub(crate) fn get(&self) -> anyhow::Result<Value<'v>> {
+        match self.value.get() {
+            Some(x) => Ok(x),
+            None => Err(PromiseError::NotSet(self.created()).into()),
+        }
+    }
+
+    /// Set the promise, which must not already be set.
+    pub(crate) fn set(&self, value: Value<'v>) -> anyhow::Result<()> {
+        if self.value.get().is_some() {
+            return Err(PromiseError::AlreadySet(self.created()).into());
+        }
+        // Freezing a promise freezes its value, so a chain of promises which leads
+        // back to this one would never finish freezing.
+        let mut x = value;
+        while let Some(p) = Promise::from_value(x) {
+            if ptr::eq(p, self) {
+                return Err(PromiseError::SetToItself(self.created()).into());
+            }
+            match p.value.get() {
+                Some(next) => x = next,
+                None => break,
+            }
+        }
+        self.value.set(Some(value));
+        Ok(())
+    }
+
+    /// The value to freeze in place of the promise. If the promise was never set,
+    /// records where it was created in `never_set` and returns `None`.
+    pub(crate) fn freeze_value(&self, never_set: &mut Vec<String>) -> Option<Value<'v>> {
+        let value = self.value.get();
+        if value.is_none() {
+            let created = self.created();
+            if !never_set.contains(&created) {
+                never_set.push(created);
+            }
+        }
+        value
+    }
+}
+
+/// Error for the promises reported by [`Promise::freeze_value`], if there are any.
+pub(crate) fn check_never_set(never_set: Vec<String>) -> anyhow::Result<()> {
+    if never_set.is_empty() {
+        Ok(())
+    } else {
+        Err(PromiseError::NeverSet(never_set).into())
+    }
+}
+
+impl<'v> Display for Promise<'v> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The value isn't shown, as it might contain the promise.
+        if self.value.get().is_some() {
+            write!(f, "<promise (set)>")
+        } else {
+            write!(f, "<promise (not set)>")
+        }
+    }
+}
+
+impl<'v> AllocValue<'v> for Promise<'v> {
+    fn alloc_value(self, heap: &'v Heap) -> Value<'v> {
+        heap.alloc_promise(self)
+    }
+}
+
+pub(crate) fn promise_methods() -> Option<&'static Methods> {
+    static RES: MethodsStatic = MethodsStatic::new();
+    RES.methods(crate::stdlib::promise::promise_methods)
+}
+
+impl<'v> StarlarkValue<'v> for Promise<'v> {
+    starlark_type!(Promise::TYPE);
+
+    fn get_methods(&self) -> Option<&'static Methods> {
+        promise_methods()
+    }
+
+    fn is_special() -> bool
+    where
+        Self: Sized,
+    {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        assert::{self, Assert},
+        environment::{Globals, Module},
+        eval::Evaluator,
+        syntax::{AstModule, Dialect},
+    };
+
+    #[test]
+    fn test_promise_set_get() {
+        assert::pass(
+            r#"
+p = promise()
+d = {"p": p}
+p.set(1)
+assert_eq(d["p"].get(), 1)
+q = promise()
+q.set([2])
+e = {"q": q}
+assert_eq(e["q"].get(), [2])
+assert_eq(type(p), "promise")
+"#,
+        );
+    }
+
+    #[test]
+    fn test_promise_errors() {
+        assert::fail(
+            "p = promise()\np.get()",
+            "Promise created at assert.bzl:1:5-14 has not been set",
+        );
+        assert::fail(
+            "p = promise()\np.set(1)\np.set(2)",
+            "Promise created at assert.bzl:1:5-14 has already been set",
+        );
+        assert::fail(
+            "p = promise()\nq = promise()\nq.set(p)\np.set(q)",
+            "can't be set to itself",
+        );
+    }
+
+    #[test]
+    fn test_promise_never_set() {
+        let module = Module::new();
+        {
+            let mut eval = Evaluator::new(&module);
+            let program = "p = promise()\nx = [promise(), p, p]\n";
+            let ast = AstModule::parse("m.star", program.to_owned(), &Dialect::Extended).unwrap();
+            eval.eval_module(ast, &Globals::extended()).unwrap();
+        }
+        assert_eq!(
+            "Can't freeze a module with promises which were never set, created at: m.star:1:5-14, m.star:2:6-15",
+            module.freeze().unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn test_promise_frozen() {
+        let mut a = Assert::new();
+        a.module(
+            "m",
+            r#"
+later = promise()
+rules = {"a": later, "b": [later]}
+chain = promise()
+chain.set(later)
+cycle = promise()
+cycle.set([cycle, 3])
+number = promise()
+number.set(4)
+def set_later():
+    later.set(struct(name = "x"))
+set_later()
+"#,
+        );
+        a.pass(
+            r#"
+load("m", "later", "rules", "chain", "cycle", "number")
+assert_eq(type(later), "struct")
+assert_eq(later.name, "x")
+assert_eq(rules["b"][0], later)
+assert_eq(chain, later)
+assert_eq(type(cycle), "list")
+assert_eq(cycle[1], 3)
+assert_eq(cycle[0][0][1], 3)
+assert_eq(number + 1, 5)
+"#,
+        );
+    }
+}
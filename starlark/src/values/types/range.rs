@@ -20,23 +20,34 @@
 use std::{
     fmt::{self, Display},
     marker::PhantomData,
-    num::NonZeroI32,
+    num::{NonZeroI32, NonZeroI64},
+    ops::ControlFlow,
 };
 
 use gazebo::{any::AnyLifetime, prelude::*};
+use thiserror::Error;
 
 use crate as starlark;
 use crate::values::{
+    for_each_in,
     index::{convert_index, convert_slice_indices},
     Heap, StarlarkValue, Value, ValueError, ValueLike,
 };
 
+#[derive(Debug, Error)]
+enum RangeError {
+    #[error("Length of `{0}` is {1}, which is too big for an int")]
+    TooLong(String, u64),
+}
+
 /// Representation of `range()` type.
 #[derive(Clone, Copy, Dupe, Debug, AnyLifetime, NoSerialize)]
 pub struct Range {
-    start: i32,
-    stop: i32,
-    step: NonZeroI32,
+    // The bounds are `i64` so that slicing can produce bounds just outside the `i32` range,
+    // e.g. `range(-2147483648, 0)[::-1]`. The values in the range always fit in an `i32`.
+    start: i64,
+    stop: i64,
+    step: NonZeroI64,
 }
 
 impl Display for Range {
@@ -59,12 +70,37 @@ impl Range {
 
     /// Create a new [`Range`].
     pub fn new(start: i32, stop: i32, step: NonZeroI32) -> Range {
-        Range { start, stop, step }
+        Range {
+            start: start as i64,
+            stop: stop as i64,
+            step: step.into(),
+        }
+    }
+
+    /// Number of elements, which may be more than fits in an `i32`.
+    fn len(&self) -> u64 {
+        // Use `i128` so the distance can't overflow, whatever the bounds are.
+        let (dist, step) = if self.step.get() > 0 {
+            (
+                self.stop as i128 - self.start as i128,
+                self.step.get() as i128,
+            )
+        } else {
+            (
+                self.start as i128 - self.stop as i128,
+                -(self.step.get() as i128),
+            )
+        };
+        if dist <= 0 {
+            0
+        } else {
+            ((dist - 1) / step + 1) as u64
+        }
     }
 
     fn equals_range(&self, other: &Range) -> anyhow::Result<bool> {
-        let self_length = self.length()?;
-        let other_length = other.length()?;
+        let self_length = self.len();
+        let other_length = other.len();
         if self_length == 0 || other_length == 0 {
             return Ok(self_length == other_length);
         }
@@ -97,14 +133,13 @@ impl<'a> Iterator for RangeIterator<'a> {
 
         let old_start = self.0.start;
         self.0.start = self.0.start.saturating_add(self.0.step.get());
-        Some(Value::new_int(old_start))
+        // Every value in the range fits in an `i32`.
+        Some(Value::new_int(old_start as i32))
     }
 
     fn size_hint(&self) -> (usize, Option<usize>) {
-        match self.0.length() {
-            Ok(n) => (n as usize, Some(n as usize)),
-            Err(_) => (0, None),
-        }
+        let n = self.0.len() as usize;
+        (n, Some(n))
     }
 }
 
@@ -117,39 +152,19 @@ impl<'v> StarlarkValue<'v> for Range {
     }
 
     fn length(&self) -> anyhow::Result<i32> {
-        if self.start == self.stop {
-            return Ok(0);
-        }
-
-        // If step is into opposite direction of stop, then length is zero.
-        if (self.stop >= self.start) != (self.step.get() > 0) {
-            return Ok(0);
-        }
-
-        // Convert range and step to `u64`
-        let (dist, step) = if self.step.get() >= 0 {
-            (
-                self.stop.wrapping_sub(self.start) as u64,
-                self.step.get() as u64,
-            )
-        } else {
-            (
-                self.start.wrapping_sub(self.stop) as u64,
-                self.step.get().wrapping_neg() as u64,
-            )
-        };
-        let i = ((dist - 1) / step + 1) as i32;
-        if i >= 0 {
-            Ok(i)
-        } else {
-            Err(ValueError::IntegerOverflow.into())
+        let len = self.len();
+        match i32::try_from(len) {
+            Ok(len) => Ok(len),
+            Err(_) => Err(RangeError::TooLong(self.to_string(), len).into()),
         }
     }
 
     fn at(&self, index: Value, _heap: &'v Heap) -> anyhow::Result<Value<'v>> {
         let index = convert_index(index, self.length()?)?;
         // Must not overflow if `length` is computed correctly
-        Ok(Value::new_int(self.start + self.step.get() * index))
+        Ok(Value::new_int(
+            (self.start + self.step.get() * index as i64) as i32,
+        ))
     }
 
     fn equals(&self, other: Value) -> anyhow::Result<bool> {
@@ -168,24 +183,20 @@ impl<'v> StarlarkValue<'v> for Range {
         heap: &'v Heap,
     ) -> anyhow::Result<Value<'v>> {
         let (start, stop, step) = convert_slice_indices(self.length()?, start, stop, stride)?;
+        let at = |index: i32| {
+            self.step
+                .get()
+                .checked_mul(index as i64)
+                .and_then(|x| x.checked_add(self.start))
+                .ok_or(ValueError::IntegerOverflow)
+        };
         return Ok(heap.alloc(Range {
-            start: self
-                .start
-                .checked_add(
-                    start
-                        .checked_mul(self.step.get())
-                        .ok_or(ValueError::IntegerOverflow)?,
-                )
-                .ok_or(ValueError::IntegerOverflow)?,
-            stop: self
-                .start
-                .checked_add(
-                    stop.checked_mul(self.step.get())
-                        .ok_or(ValueError::IntegerOverflow)?,
-                )
-                .ok_or(ValueError::IntegerOverflow)?,
-            step: NonZeroI32::new(
-                step.checked_mul(self.step.get())
+            start: at(start)?,
+            stop: at(stop)?,
+            step: NonZeroI64::new(
+                self.step
+                    .get()
+                    .checked_mul(step as i64)
                     .ok_or(ValueError::IntegerOverflow)?,
             )
             .unwrap(),
@@ -210,9 +221,18 @@ impl<'v> StarlarkValue<'v> for Range {
         f(&mut RangeIterator::<'v>(*self, PhantomData))
     }
 
+    fn for_each_element(
+        &self,
+        _heap: &'v Heap,
+        f: &mut dyn FnMut(Value<'v>) -> ControlFlow<()>,
+    ) -> anyhow::Result<()> {
+        for_each_in(RangeIterator::<'v>(*self, PhantomData), f);
+        Ok(())
+    }
+
     fn is_in(&self, other: Value) -> anyhow::Result<bool> {
         let other = match other.unpack_num().and_then(|n| n.as_int()) {
-            Some(other) => other,
+            Some(other) => other as i64,
             None => {
                 // Consider `"a" in range(3)`
                 //
@@ -222,26 +242,12 @@ impl<'v> StarlarkValue<'v> for Range {
                 return Ok(false);
             }
         };
-        if !self.to_bool() {
-            return Ok(false);
-        }
-        if self.start == other {
-            return Ok(true);
-        }
-        if self.step.get() > 0 {
-            if other < self.start || other >= self.stop {
-                return Ok(false);
-            }
-            Ok((other.wrapping_sub(self.start) as u64) % (self.step.get() as u64) == 0)
+        let in_bounds = if self.step.get() > 0 {
+            self.start <= other && other < self.stop
         } else {
-            if other > self.start || other <= self.stop {
-                return Ok(false);
-            }
-            Ok(
-                (self.start.wrapping_sub(other) as u64) % (self.step.get().wrapping_neg() as u64)
-                    == 0,
-            )
-        }
+            self.stop < other && other <= self.start
+        };
+        Ok(in_bounds && (other as i128 - self.start as i128) % (self.step.get() as i128) == 0)
     }
 }
 
@@ -256,14 +262,13 @@ impl PartialEq for Range {
 mod tests {
     use std::num::NonZeroI32;
 
-    use crate::values::{range::Range, Heap, StarlarkValue, Value};
+    use crate::{
+        assert,
+        values::{range::Range, Heap, StarlarkValue, Value},
+    };
 
     fn range(start: i32, stop: i32, range: i32) -> Range {
-        Range {
-            start,
-            stop,
-            step: NonZeroI32::new(range).unwrap(),
-        }
+        Range::new(start, stop, NonZeroI32::new(range).unwrap())
     }
 
     fn range_start_stop(start: i32, stop: i32) -> Range {
@@ -342,4 +347,44 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_large_ranges() {
+        assert::all_true(
+            r#"
+len(range(0, 2147483647, 2)) == 1073741824
+range(0, 2147483647, 2)[-1] == 2147483646
+2147483646 in range(0, 2147483647, 2)
+2147483645 not in range(0, 2147483647, 2)
+list(range(2147483640, 2147483647, 3)) == [2147483640, 2147483643, 2147483646]
+list(range(0, 2147483647, 2)[-2:]) == [2147483644, 2147483646]
+list(range(0, 2147483647, 1000000000)) == [0, 1000000000, 2000000000]
+range(0, 2147483647, 1000000000)[2] == 2000000000
+list(range((-2147483647 - 1), -2147483645)[::-1]) == [-2147483646, -2147483647, (-2147483647 - 1)]
+list(range(-2147483640, (-2147483647 - 1), -3)) == [-2147483640, -2147483643, -2147483646]
+-2147483647 in range(0, (-2147483647 - 1), -1)
+(-2147483647 - 1) not in range(0, (-2147483647 - 1), -1)
+(-2147483647 - 1) in range((-2147483647 - 1), 0, 7)
+list(range((-2147483647 - 1), 2147483647, 2147483647)) == [(-2147483647 - 1), -1, 2147483646]
+"#,
+        );
+        assert::fail(
+            "len(range(-1, 2147483647))",
+            "Length of `range(-1, 2147483647)` is 2147483648, which is too big for an int",
+        );
+        assert::fail(
+            "len(range(0, (-2147483647 - 1), -1))",
+            "Length of `range(0, -2147483648, -1)` is 2147483648",
+        );
+        // Iterating is lazy, so works even if the length doesn't fit in an int.
+        assert::is_true(
+            r#"
+def first_positive(xs):
+    for x in xs:
+        if x > 0:
+            return x
+first_positive(range(-1, 2147483647)) == 1
+"#,
+        );
+    }
 }
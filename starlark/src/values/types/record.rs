@@ -62,9 +62,9 @@ use crate::{
     collections::{SmallMap, StarlarkHasher},
     eval::{Arguments, Evaluator, ParametersSpec},
     values::{
-        comparison::equals_slice, display::display_keyed_container, function::FUNCTION_TYPE,
-        typing::TypeCompiled, Freeze, Freezer, FrozenValue, Heap, StarlarkValue, Trace, Value,
-        ValueLike,
+        comparison::equals_slice, display::display_keyed_container, docs, docs::DocItem,
+        function::FUNCTION_TYPE, typing::TypeCompiled, Freeze, Freezer, FrozenValue, Heap,
+        StarlarkValue, Trace, Value, ValueLike,
     },
 };
 
@@ -157,6 +157,19 @@ impl<V> FieldGen<V> {
     }
 }
 
+impl<V, Typ: AsARef<Option<String>>> RecordTypeGen<V, Typ> {
+    /// Describe a field for type errors, e.g. "field `port` of record `IpAddress`".
+    fn describe_field(&self, name: &str) -> String {
+        format!(
+            "field `{}` of record `{}`",
+            name,
+            AsARef::as_aref(&self.typ)
+                .as_deref()
+                .unwrap_or(Record::TYPE)
+        )
+    }
+}
+
 fn record_fields<'v>(
     x: Either<&'v RecordType<'v>, &'v FrozenRecordType>,
 ) -> &'v SmallMap<String, (FieldGen<Value<'v>>, TypeCompiled)> {
@@ -267,7 +280,9 @@ where
                     match field.0.default {
                         None => {
                             let v: Value = param_parser.next(name)?;
-                            v.check_type_compiled(field.0.typ, &field.1, Some(name))?;
+                            v.check_type_compiled_described(field.0.typ, &field.1, || {
+                                self.describe_field(name)
+                            })?;
                             values.push(v);
                         }
                         Some(default) => {
@@ -275,7 +290,9 @@ where
                             match v {
                                 None => values.push(default),
                                 Some(v) => {
-                                    v.check_type_compiled(field.0.typ, &field.1, Some(name))?;
+                                    v.check_type_compiled_described(field.0.typ, &field.1, || {
+                                        self.describe_field(name)
+                                    })?;
                                     values.push(v);
                                 }
                             }
@@ -297,6 +314,29 @@ where
         vec!["type".to_owned()]
     }
 
+    fn documentation(&self) -> Option<DocItem> {
+        let members = self
+            .fields
+            .iter()
+            .map(|(name, field)| {
+                let typ = docs::Type {
+                    raw_type: field.0.typ.to_value().to_repr(),
+                };
+                (
+                    name.clone(),
+                    docs::Member::Property(docs::Property {
+                        docs: None,
+                        typ: Some(typ),
+                    }),
+                )
+            })
+            .collect();
+        Some(DocItem::Object(docs::Object {
+            docs: None,
+            members,
+        }))
+    }
+
     fn has_attr(&self, attribute: &str) -> bool {
         attribute == "type"
     }
@@ -387,8 +427,10 @@ where
     }
 
     fn write_hash(&self, hasher: &mut StarlarkHasher) -> anyhow::Result<()> {
-        self.typ.write_hash(hasher)?;
-        for v in &self.values {
+        // Hash the field names rather than the record type, since field types like
+        // `[str.type]` are not hashable. Equal records always have the same fields.
+        for (name, v) in self.get_record_fields().keys().zip(&self.values) {
+            name.hash(hasher);
             v.write_hash(hasher)?;
         }
         Ok(())
@@ -0,0 +1,509 @@
+/*
+ * Copyright 2018 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! The set type, a mutable collection of unique hashable values, which iterates in insertion order.
+//!
+//! Sets are not part of the Starlark standard, so the `set()` constructor is only available
+//! with [`LibraryExtension::SetType`](crate::environment::LibraryExtension::SetType).
+
+use std::{
+    cell::{Ref, RefCell, RefMut},
+    fmt,
+    fmt::{Debug, Display},
+    intrinsics::unlikely,
+    ops::{Deref, DerefMut},
+};
+
+use gazebo::{
+    any::AnyLifetime,
+    cell::ARef,
+    coerce::{coerce_ref, Coerce},
+};
+use serde::{ser::SerializeSeq, Serialize};
+
+use crate::{
+    self as starlark,
+    collections::{Hashed, SmallMap},
+    environment::{Methods, MethodsStatic},
+    values::{
+        comparison::equals_small_map,
+        display::display_container,
+        error::{FrozenValueMutated, ValueError},
+        iter::ARefIterator,
+        AllocFrozenValue, AllocValue, Freeze, Freezer, FrozenHeap, FrozenStringValue, FrozenValue,
+        Heap, StarlarkValue, Trace, UnpackValue, Value, ValueLike,
+    },
+};
+
+#[derive(Clone, Default, Trace, Debug, AnyLifetime)]
+struct SetGen<T>(T);
+
+impl<'v, T: SetLike<'v>> Display for SetGen<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let content = self.0.content();
+        if content.is_empty() {
+            write!(f, "set()")
+        } else {
+            display_container(f, "{", "}", content.keys())
+        }
+    }
+}
+
+/// Define the set type. See [`Set`] and [`FrozenSet`] as the two possible representations.
+#[derive(Clone, Default, Trace, Debug, AnyLifetime)]
+#[repr(transparent)]
+pub struct Set<'v> {
+    /// The elements of the set. They must all be hashable values.
+    content: SmallMap<Value<'v>, ()>,
+}
+
+/// Define the set type. See [`Set`] and [`FrozenSet`] as the two possible representations.
+#[derive(Clone, Default, Debug, AnyLifetime)]
+#[repr(transparent)]
+pub struct FrozenSet {
+    /// The elements of the set. They must all be hashable values.
+    content: SmallMap<FrozenValue, ()>,
+}
+
+unsafe impl<'v> Coerce<Set<'v>> for FrozenSet {}
+
+impl<'v> AllocValue<'v> for Set<'v> {
+    fn alloc_value(self, heap: &'v Heap) -> Value<'v> {
+        heap.alloc_complex(SetGen(RefCell::new(self)))
+    }
+}
+
+impl AllocFrozenValue for FrozenSet {
+    fn alloc_frozen_value(self, heap: &FrozenHeap) -> FrozenValue {
+        heap.alloc_simple(SetGen(self))
+    }
+}
+
+/// Borrowed `Set`.
+pub struct SetRef<'v> {
+    aref: ARef<'v, Set<'v>>,
+}
+
+/// Mutably borrowed `Set`.
+pub struct SetMut<'v> {
+    aref: RefMut<'v, Set<'v>>,
+}
+
+impl<'v> Deref for SetRef<'v> {
+    type Target = Set<'v>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.aref
+    }
+}
+
+impl<'v> Deref for SetMut<'v> {
+    type Target = Set<'v>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.aref
+    }
+}
+
+impl<'v> DerefMut for SetMut<'v> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.aref
+    }
+}
+
+impl<'v> Set<'v> {
+    /// The result of calling `type()` on sets.
+    pub const TYPE: &'static str = "set";
+
+    /// Set type string as Starlark frozen string value.
+    pub fn get_type_value_static() -> FrozenStringValue {
+        SetGen::<FrozenSet>::get_type_value_static()
+    }
+
+    /// Create a new [`Set`].
+    pub fn new(content: SmallMap<Value<'v>, ()>) -> Self {
+        Self { content }
+    }
+
+    /// Downcast the value to a set.
+    pub fn from_value(x: Value<'v>) -> Option<SetRef<'v>> {
+        if x.unpack_frozen().is_some() {
+            x.downcast_ref::<SetGen<FrozenSet>>().map(|x| SetRef {
+                aref: ARef::new_ptr(coerce_ref(&x.0)),
+            })
+        } else {
+            let ptr = x.downcast_ref::<SetGen<RefCell<Set<'v>>>>()?;
+            Some(SetRef {
+                aref: ARef::new_ref(ptr.0.borrow()),
+            })
+        }
+    }
+
+    /// Downcast the value to a mutable set reference.
+    pub fn from_value_mut(x: Value<'v>) -> anyhow::Result<Option<SetMut>> {
+        if unlikely(x.unpack_frozen().is_some()) {
+            return Err(FrozenValueMutated::error(x));
+        }
+        let ptr = x.downcast_ref::<SetGen<RefCell<Set<'v>>>>();
+        match ptr {
+            None => Ok(None),
+            Some(ptr) => match ptr.0.try_borrow_mut() {
+                Ok(x) => Ok(Some(SetMut { aref: x })),
+                Err(_) => Err(ValueError::MutationDuringIteration.into()),
+            },
+        }
+    }
+
+    /// Number of elements in the set.
+    pub fn len(&self) -> usize {
+        self.content.len()
+    }
+
+    /// Is the set empty?
+    pub fn is_empty(&self) -> bool {
+        self.content.is_empty()
+    }
+
+    /// Iterate through the elements in insertion order.
+    pub fn iter<'a>(&'a self) -> impl ExactSizeIterator<Item = Value<'v>> + 'a {
+        self.content.keys().copied()
+    }
+
+    /// Iterate through the elements, retaining their hashes.
+    pub fn iter_hashed<'a>(&'a self) -> impl Iterator<Item = Hashed<Value<'v>>> + 'a
+    where
+        'v: 'a,
+    {
+        self.content.iter_hashed().map(|(x, _)| x.unborrow_copy())
+    }
+
+    /// Does the set contain the given value? Will be [`Err`] if the value is not hashable.
+    pub fn contains(&self, value: Value<'v>) -> anyhow::Result<bool> {
+        Ok(self.contains_hashed(value.get_hashed()?))
+    }
+
+    /// Does the set contain the given prehashed value?
+    pub fn contains_hashed(&self, value: Hashed<Value<'v>>) -> bool {
+        self.content.contains_key_hashed(value.borrow())
+    }
+
+    /// Insert a prehashed value, returning `true` if it was not already present.
+    pub fn insert_hashed(&mut self, value: Hashed<Value<'v>>) -> bool {
+        self.content.insert_hashed(value, ()).is_none()
+    }
+
+    /// Remove a prehashed value, returning `true` if it was present.
+    pub fn remove_hashed(&mut self, value: Hashed<Value<'v>>) -> bool {
+        self.content.remove_hashed(value.borrow()).is_some()
+    }
+
+    /// Remove all elements from the set.
+    pub fn clear(&mut self) {
+        self.content.clear();
+    }
+
+    /// Collect the elements of an iterable into a fresh [`Set`],
+    /// failing if any element is not hashable.
+    pub fn from_iterable(iterable: Value<'v>, heap: &'v Heap) -> anyhow::Result<Self> {
+        if let Some(set) = Set::from_value(iterable) {
+            return Ok(set.clone());
+        }
+        let mut content = SmallMap::new();
+        iterable.with_iterator(heap, |it| {
+            content.reserve(it.size_hint().0);
+            for x in it {
+                content.insert_hashed(x.get_hashed()?, ());
+            }
+            anyhow::Ok(())
+        })??;
+        Ok(Set { content })
+    }
+}
+
+impl<'v> UnpackValue<'v> for SetRef<'v> {
+    fn expected() -> String {
+        "set".to_owned()
+    }
+
+    fn unpack_value(value: Value<'v>) -> Option<SetRef<'v>> {
+        Set::from_value(value)
+    }
+}
+
+impl FrozenSet {
+    /// Obtain the [`FrozenSet`] pointed at by a [`FrozenValue`].
+    #[allow(clippy::trivially_copy_pass_by_ref)]
+    // We need a lifetime because FrozenValue doesn't contain the right lifetime
+    pub fn from_frozen_value(x: &FrozenValue) -> Option<&FrozenSet> {
+        x.downcast_ref::<SetGen<FrozenSet>>().map(|x| &x.0)
+    }
+
+    /// Iterate through the elements in insertion order.
+    pub fn iter<'a>(&'a self) -> impl ExactSizeIterator<Item = FrozenValue> + 'a {
+        self.content.keys().copied()
+    }
+
+    /// Number of elements in the set.
+    pub fn len(&self) -> usize {
+        self.content.len()
+    }
+
+    /// Is the set empty?
+    pub fn is_empty(&self) -> bool {
+        self.content.is_empty()
+    }
+}
+
+impl<'v> Freeze for SetGen<RefCell<Set<'v>>> {
+    type Frozen = SetGen<FrozenSet>;
+    fn freeze(self, freezer: &Freezer) -> anyhow::Result<Self::Frozen> {
+        let content = self.0.into_inner().content.freeze(freezer)?;
+        Ok(SetGen(FrozenSet { content }))
+    }
+}
+
+trait SetLike<'v>: Debug {
+    fn content(&self) -> ARef<SmallMap<Value<'v>, ()>>;
+}
+
+impl<'v> SetLike<'v> for RefCell<Set<'v>> {
+    fn content(&self) -> ARef<SmallMap<Value<'v>, ()>> {
+        ARef::new_ref(Ref::map(self.borrow(), |x| &x.content))
+    }
+}
+
+impl<'v> SetLike<'v> for FrozenSet {
+    fn content(&self) -> ARef<SmallMap<Value<'v>, ()>> {
+        ARef::new_ptr(&coerce_ref::<_, Set<'v>>(self).content)
+    }
+}
+
+pub(crate) fn set_methods() -> Option<&'static Methods> {
+    static RES: MethodsStatic = MethodsStatic::new();
+    RES.methods(crate::stdlib::set::set_methods)
+}
+
+impl<'v, T: SetLike<'v>> StarlarkValue<'v> for SetGen<T>
+where
+    Self: AnyLifetime<'v>,
+{
+    starlark_type!(Set::TYPE);
+
+    fn get_methods(&self) -> Option<&'static Methods> {
+        set_methods()
+    }
+
+    fn collect_repr(&self, r: &mut String) {
+        let content = self.0.content();
+        if content.is_empty() {
+            r.push_str("set()");
+            return;
+        }
+        r.push('{');
+        for (i, x) in content.keys().enumerate() {
+            if i != 0 {
+                r.push_str(", ");
+            }
+            x.collect_repr(r);
+        }
+        r.push('}');
+    }
+
+    fn collect_repr_cycle(&self, collector: &mut String) {
+        collector.push_str("{...}");
+    }
+
+    fn to_bool(&self) -> bool {
+        !self.0.content().is_empty()
+    }
+
+    fn equals(&self, other: Value<'v>) -> anyhow::Result<bool> {
+        match Set::from_value(other) {
+            None => Ok(false),
+            Some(other) => equals_small_map(&*self.0.content(), &other.content, |_, _| {
+                anyhow::Ok(true)
+            }),
+        }
+    }
+
+    fn extra_memory(&self) -> usize {
+        self.0.content().extra_memory()
+    }
+
+    fn length(&self) -> anyhow::Result<i32> {
+        Ok(self.0.content().len() as i32)
+    }
+
+    fn is_in(&self, other: Value<'v>) -> anyhow::Result<bool> {
+        Ok(self
+            .0
+            .content()
+            .contains_key_hashed(other.get_hashed()?.borrow()))
+    }
+
+    fn iterate<'a>(
+        &'a self,
+        _heap: &'v Heap,
+    ) -> anyhow::Result<Box<dyn Iterator<Item = Value<'v>> + 'a>>
+    where
+        'v: 'a,
+    {
+        Ok(box ARefIterator::new(self.0.content(), |x| {
+            x.keys().copied()
+        }))
+    }
+
+    fn with_iterator(
+        &self,
+        _heap: &'v Heap,
+        f: &mut dyn FnMut(&mut dyn Iterator<Item = Value<'v>>) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        f(&mut self.0.content().keys().copied())
+    }
+}
+
+impl<'v, T: SetLike<'v>> Serialize for SetGen<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let content = self.0.content();
+        let mut seq_serializer = serializer.serialize_seq(Some(content.len()))?;
+
+        for x in content.keys() {
+            seq_serializer.serialize_element(x)?;
+        }
+
+        seq_serializer.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        assert,
+        assert::Assert,
+        environment::{Globals, Module},
+        eval::Evaluator,
+        syntax::{AstModule, Dialect},
+    };
+
+    #[test]
+    fn test_set_basic() {
+        assert::all_true(
+            r#"
+s = set([3, 1, 2, 1])
+len(s) == 3
+list(s) == [3, 1, 2]
+1 in s
+4 not in s
+type(s) == "set"
+repr(s) == "{3, 1, 2}"
+repr(set()) == "set()"
+not set()
+"#,
+        );
+    }
+
+    #[test]
+    fn test_set_methods() {
+        assert::is_true(
+            r#"
+s = set([1, 2])
+s.add(3)
+s.add(1)
+s.remove(2)
+list(s) == [1, 3]
+"#,
+        );
+        assert::all_true(
+            r#"
+list(set([1, 2]).union([2, 3])) == [1, 2, 3]
+list(set([1, 2, 3]).intersection([3, 2])) == [2, 3]
+list(set([1, 2, 3]).difference(set([2]))) == [1, 3]
+set([1, 2]).issubset([3, 2, 1])
+not set([1, 4]).issubset([1, 2])
+"#,
+        );
+    }
+
+    #[test]
+    fn test_set_errors() {
+        assert::fail("set([[1]])", "not hashable");
+        assert::fail("s = set(); s.add({})", "not hashable");
+        assert::fail("s = set([1]); s.remove(2)", "not found");
+        assert::fail(
+            r#"
+def f():
+    s = set([1, 2])
+    for x in s:
+        s.add(3)
+f()
+"#,
+            "mutate an iterable",
+        );
+    }
+
+    #[test]
+    fn test_set_frozen() {
+        let mut a = Assert::new();
+        a.module("m", "s = set([1, 2, 3])");
+        a.all_true(
+            r#"
+load("m", "s")
+s == set([3, 2, 1])
+set([1, 2, 3]) == s
+list(s) == [1, 2, 3]
+2 in s
+"#,
+        );
+        a.fail(
+            r#"
+load("m", "s")
+s.add(4)
+"#,
+            "Immutable",
+        );
+    }
+
+    #[test]
+    fn test_set_not_in_standard() {
+        let mut a = Assert::new();
+        a.globals(Globals::standard());
+        a.fail("set()", "Variable `set` not found");
+    }
+
+    #[test]
+    fn test_set_freeze_round_trip() {
+        let globals = Globals::extended();
+        let module = Module::new();
+        {
+            let mut eval = Evaluator::new(&module);
+            let ast = AstModule::parse(
+                "m.star",
+                "s = set(['a', 'b', 'a', 'c'])".to_owned(),
+                &Dialect::Standard,
+            )
+            .unwrap();
+            eval.eval_module(ast, &globals).unwrap();
+        }
+        let frozen = module.freeze().unwrap();
+        let s = frozen.get("s").unwrap();
+        assert_eq!("{\"a\", \"b\", \"c\"}", s.value().to_repr());
+        assert_eq!(3, s.value().length().unwrap());
+    }
+}
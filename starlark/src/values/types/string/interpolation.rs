@@ -18,43 +18,231 @@
 //! String interpolation-related code.
 //! Based on <https://docs.python.org/3/library/stdtypes.html#printf-style-string-formatting>
 
-use std::{fmt::Write, mem, str::FromStr};
+use std::{iter, mem, str::FromStr};
 
 use anyhow::anyhow;
-use gazebo::{cast, prelude::*};
 use thiserror::Error;
 
 use crate::{
     collections::string_pool::StringPool,
     values::{
-        dict::Dict, float, num, num::Num, tuple::Tuple, Heap, StringValue, UnpackValue, Value,
-        ValueError, ValueLike,
+        dict::Dict,
+        float,
+        num::Num,
+        repr_limits::{collect_repr_unlimited, set_repr_limits},
+        tuple::Tuple,
+        Heap, StringValue, UnpackValue, Value, ValueError, ValueLike,
     },
 };
 
 /// Operator `%` format or evaluation errors
-#[derive(Clone, Dupe, Debug, Error)]
+#[derive(Debug, Error)]
 enum StringInterpolationError {
     /// Interpolation parameter is too big for the format string.
-    #[error("Too many arguments for format string")]
-    TooManyParameters,
+    #[error("Too many arguments for format string, used {0} of {1}")]
+    TooManyParameters(usize, usize),
     /// Interpolation parameter is too small for the format string.
-    #[error("Not enough arguments for format string")]
-    NotEnoughParameters,
+    #[error("Not enough arguments for format string, no argument at index {1} for `{0}`")]
+    NotEnoughParameters(String, usize),
+    #[error("Unsupported format character in `{0}`")]
+    UnsupportedFormat(String),
+    #[error("Incomplete format `{0}` at the end of the format string")]
+    IncompleteFormat(String),
+    #[error("Invalid argument at index {1} for `{0}`: {2}")]
+    InvalidArgument(String, usize, String),
+    #[error("Invalid replacement field `{{{0}}}` at position {1} in format string: {2}")]
+    InvalidField(String, usize, String),
+    #[error("Width or precision in `{0}` is too big, the maximum is {1}")]
+    TooBig(String, usize),
 }
 
-pub(crate) fn percent(format: &str, value: Value) -> anyhow::Result<String> {
-    // For performance reasons, we treat format as a list of bytes
-    // (which is fine, the only thing we care about are '%' and ASCII digits).
-    // As a result, we accumulate into a Vec<u8>, which we know at any point
-    // we are at the end or at a '%' must be a valid UTF8 buffer.
+/// Largest width or precision accepted in a `%` directive,
+/// so a format string can't request an arbitrarily large padding.
+const MAX_WIDTH: usize = 1_000_000;
+
+/// A single directive in a `%` format string, e.g. `%-8.3f`.
+struct PercentFormat {
+    /// `-` flag, pad on the right rather than the left.
+    left: bool,
+    /// `0` flag, pad numbers with zeros.
+    zero: bool,
+    /// `+` flag, always write the sign of a number.
+    plus: bool,
+    /// ` ` flag, write a space before non-negative numbers.
+    space: bool,
+    /// Minimum number of characters to write.
+    width: usize,
+    /// Digits after the point for floats, minimum digits for ints, maximum characters for strings.
+    precision: Option<usize>,
+    conversion: char,
+}
+
+impl PercentFormat {
+    /// Parse the directive at the start of `format`, which must start with `%`.
+    /// Returns the directive and its length in bytes.
+    fn parse(format: &str) -> anyhow::Result<(Self, usize)> {
+        fn number(format: &str, i: &mut usize) -> anyhow::Result<usize> {
+            let mut res: usize = 0;
+            while let Some(c) = format.as_bytes().get(*i).filter(|c| c.is_ascii_digit()) {
+                res = res * 10 + (c - b'0') as usize;
+                if res > MAX_WIDTH {
+                    let end = format[*i..]
+                        .find(|c: char| !c.is_ascii_digit())
+                        .map_or(format.len(), |e| *i + e);
+                    return Err(StringInterpolationError::TooBig(
+                        format[..end].to_owned(),
+                        MAX_WIDTH,
+                    )
+                    .into());
+                }
+                *i += 1;
+            }
+            Ok(res)
+        }
+
+        debug_assert!(format.starts_with('%'));
+        let bytes = format.as_bytes();
+        let mut res = PercentFormat {
+            left: false,
+            zero: false,
+            plus: false,
+            space: false,
+            width: 0,
+            precision: None,
+            conversion: '%',
+        };
+        let mut i = 1;
+        while let Some(c) = bytes.get(i) {
+            match c {
+                b'-' => res.left = true,
+                b'0' => res.zero = true,
+                b'+' => res.plus = true,
+                b' ' => res.space = true,
+                _ => break,
+            }
+            i += 1;
+        }
+        res.width = number(format, &mut i)?;
+        if bytes.get(i) == Some(&b'.') {
+            i += 1;
+            res.precision = Some(number(format, &mut i)?);
+        }
+        match format[i..].chars().next() {
+            None => Err(StringInterpolationError::IncompleteFormat(format.to_owned()).into()),
+            Some(c) => {
+                let len = i + c.len_utf8();
+                match c {
+                    '%' | 's' | 'r' | 'd' | 'i' | 'o' | 'x' | 'X' | 'e' | 'E' | 'f' | 'F' | 'g'
+                    | 'G' => {
+                        res.conversion = c;
+                        Ok((res, len))
+                    }
+                    _ => Err(
+                        StringInterpolationError::UnsupportedFormat(format[..len].to_owned())
+                            .into(),
+                    ),
+                }
+            }
+        }
+    }
+
+    /// Write `value` according to this directive.
+    fn write(&self, value: Value, res: &mut String) -> anyhow::Result<()> {
+        let start = res.len();
+        // Numbers get signs and zero padding, strings (and non-finite floats) don't.
+        let numeric = match self.conversion {
+            's' | 'r' => {
+                match value.unpack_str() {
+                    Some(s) if self.conversion == 's' => res.push_str(s),
+                    _ => collect_repr_unlimited(value, res),
+                }
+                if let Some(precision) = self.precision {
+                    if let Some((i, _)) = res[start..].char_indices().nth(precision) {
+                        res.truncate(start + i);
+                    }
+                }
+                false
+            }
+            'd' | 'i' | 'o' | 'x' | 'X' => {
+                let v = match value.unpack_num() {
+                    // Only `%d` accepts floats, truncating them.
+                    Some(Num::Float(v)) if matches!(self.conversion, 'd' | 'i') => {
+                        match Num::Float(v.trunc()).as_int() {
+                            None => return ValueError::unsupported(&float::StarlarkFloat(v), "%d"),
+                            Some(v) => v,
+                        }
+                    }
+                    _ => value.to_int()?,
+                };
+                let abs = v.unsigned_abs();
+                let digits = match self.conversion {
+                    'o' => format!("{:o}", abs),
+                    'x' => format!("{:x}", abs),
+                    'X' => format!("{:X}", abs),
+                    _ => abs.to_string(),
+                };
+                if v < 0 {
+                    res.push('-');
+                }
+                for _ in digits.len()..self.precision.unwrap_or(0) {
+                    res.push('0');
+                }
+                res.push_str(&digits);
+                true
+            }
+            _ => {
+                let v = Num::unpack_param(value)?.as_float();
+                let precision = self.precision.unwrap_or(float::WRITE_PRECISION);
+                match self.conversion {
+                    'e' | 'E' => {
+                        float::write_scientific(res, v, self.conversion, precision, false).unwrap()
+                    }
+                    'f' | 'F' => float::write_decimal(res, v, precision).unwrap(),
+                    _ => {
+                        let exponent_char = if self.conversion == 'g' { 'e' } else { 'E' };
+                        match self.precision {
+                            None => float::write_compact(res, v, exponent_char).unwrap(),
+                            Some(precision) => {
+                                float::write_general(res, v, exponent_char, precision).unwrap()
+                            }
+                        }
+                    }
+                }
+                v.is_finite()
+            }
+        };
 
+        let signed = res[start..].starts_with(&['-', '+'][..]);
+        if numeric && !signed && (self.plus || self.space) {
+            res.insert(start, if self.plus { '+' } else { ' ' });
+        }
+        let len = res[start..].chars().count();
+        if len < self.width {
+            let padding = self.width - len;
+            if self.left {
+                res.extend(iter::repeat(' ').take(padding));
+            } else if self.zero && numeric {
+                let sign = if res[start..].starts_with(&['-', '+', ' '][..]) {
+                    1
+                } else {
+                    0
+                };
+                res.insert_str(start + sign, &"0".repeat(padding));
+            } else {
+                res.insert_str(start, &" ".repeat(padding));
+            }
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn percent(format: &str, value: Value) -> anyhow::Result<String> {
     // NOTE(nga): use could reuse `Evaluator::string_pool` here, but
     //   * we don't have access to `Evaluator` in `StarlarkValue::percent`
     //   * after single %s made intrinsic, this code is not that hot now
 
     // random guess as a baseline capacity
-    let mut res: Vec<u8> = Vec::with_capacity(format.len() + 20);
+    let mut res = String::with_capacity(format.len() + 20);
 
     let tuple = Tuple::from_value(value);
     let one = &[value];
@@ -62,108 +250,38 @@ pub(crate) fn percent(format: &str, value: Value) -> anyhow::Result<String> {
         Some(xs) => xs.content(),
         None => one,
     };
-    let mut values = values.iter().copied();
-    let mut next_value = || -> anyhow::Result<Value> {
-        values
-            .next()
-            .ok_or_else(|| StringInterpolationError::NotEnoughParameters.into())
-    };
-
-    // because of the way format is defined, we can deal with it as bytes
-    let mut format = format.as_bytes().iter().copied();
-    while let Some(c) = format.next() {
-        if c == b'%' {
-            if let Some(c) = format.next() {
-                let out: &mut String = unsafe { cast::ptr_mut(&mut res) };
-                match c {
-                    b'%' => res.push(b'%'),
-                    b's' => {
-                        let arg = next_value()?;
-                        match arg.unpack_str() {
-                            None => arg.collect_repr(out),
-                            Some(s) => out.push_str(s),
-                        }
-                    }
-                    b'r' => next_value()?.collect_repr(out),
-                    b'd' => {
-                        let value = next_value()?;
-                        if let Some(num::Num::Float(v)) = value.unpack_num() {
-                            match num::Num::Float(v.trunc()).as_int() {
-                                None => {
-                                    return ValueError::unsupported(&float::StarlarkFloat(v), "%d");
-                                }
-                                Some(v) => write!(out, "{}", v).unwrap(),
-                            }
-                        } else {
-                            write!(out, "{}", value.to_int()?).unwrap()
-                        }
-                    }
-                    b'o' => {
-                        let v = next_value()?.to_int()?;
-                        write!(
-                            out,
-                            "{}{:o}",
-                            if v < 0 { "-" } else { "" },
-                            v.wrapping_abs() as u64
-                        )
-                        .unwrap();
-                    }
-                    b'x' => {
-                        let v = next_value()?.to_int()?;
-                        write!(
-                            out,
-                            "{}{:x}",
-                            if v < 0 { "-" } else { "" },
-                            v.wrapping_abs() as u64
-                        )
-                        .unwrap();
-                    }
-                    b'X' => {
-                        let v = next_value()?.to_int()?;
-                        write!(
-                            out,
-                            "{}{:X}",
-                            if v < 0 { "-" } else { "" },
-                            v.wrapping_abs() as u64
-                        )
-                        .unwrap()
-                    }
-                    b'e' => {
-                        let v = Num::unpack_param(next_value()?)?.as_float();
-                        float::write_scientific(out, v, 'e', false).unwrap()
-                    }
-                    b'E' => {
-                        let v = Num::unpack_param(next_value()?)?.as_float();
-                        float::write_scientific(out, v, 'E', false).unwrap()
-                    }
-                    b'f' | b'F' => {
-                        let v = Num::unpack_param(next_value()?)?.as_float();
-                        float::write_decimal(out, v).unwrap()
-                    }
-                    b'g' => {
-                        let v = Num::unpack_param(next_value()?)?.as_float();
-                        float::write_compact(out, v, 'e').unwrap()
-                    }
-                    b'G' => {
-                        let v = Num::unpack_param(next_value()?)?.as_float();
-                        float::write_compact(out, v, 'E').unwrap()
-                    }
-                    c => {
-                        res.push(b'%');
-                        res.push(c);
-                    }
-                }
-            } else {
-                res.push(b'%');
-            }
-        } else {
-            res.push(c);
+    let mut index = 0;
+
+    let mut rest = format;
+    while let Some(i) = rest.find('%') {
+        res.push_str(&rest[..i]);
+        rest = &rest[i..];
+        let (directive, len) = PercentFormat::parse(rest)?;
+        let text = &rest[..len];
+        rest = &rest[len..];
+        if directive.conversion == '%' {
+            res.push('%');
+            continue;
         }
+        let value = match values.get(index) {
+            None => {
+                return Err(
+                    StringInterpolationError::NotEnoughParameters(text.to_owned(), index).into(),
+                );
+            }
+            Some(v) => *v,
+        };
+        directive.write(value, &mut res).map_err(|e| {
+            StringInterpolationError::InvalidArgument(text.to_owned(), index, format!("{:#}", e))
+        })?;
+        index += 1;
     }
-    if values.next().is_some() {
-        Err(StringInterpolationError::TooManyParameters.into())
+    res.push_str(rest);
+
+    if index < values.len() {
+        Err(StringInterpolationError::TooManyParameters(index, values.len()).into())
     } else {
-        Ok(unsafe { String::from_utf8_unchecked(res) })
+        Ok(res)
     }
 }
 
@@ -234,7 +352,7 @@ pub(crate) fn format_one<'v>(
         None => {
             let mut result = String::with_capacity(before.len() + after.len() + 10);
             result.push_str(before);
-            arg.collect_repr(&mut result);
+            collect_repr_unlimited(arg, &mut result);
             result.push_str(after);
             heap.alloc_str(&result)
         }
@@ -253,9 +371,17 @@ pub(crate) fn percent_s_one<'v>(
         None => {
             let one = match Tuple::from_value(arg) {
                 Some(tuple) => match tuple.content() {
-                    [] => return Err(StringInterpolationError::NotEnoughParameters.into()),
+                    [] => {
+                        return Err(StringInterpolationError::NotEnoughParameters(
+                            "%s".to_owned(),
+                            0,
+                        )
+                        .into());
+                    }
                     [value] => *value,
-                    [_, _, ..] => return Err(StringInterpolationError::TooManyParameters.into()),
+                    xs => {
+                        return Err(StringInterpolationError::TooManyParameters(1, xs.len()).into());
+                    }
                 },
                 None => arg,
             };
@@ -396,6 +522,94 @@ impl<'a> FormatParser<'a> {
     }
 }
 
+/// Part of a replacement field after the argument name, e.g. `.x` or `[1]`.
+#[derive(Debug, PartialEq)]
+enum FormatAccessor<'a> {
+    /// `.name`, look up an attribute.
+    Attr(&'a str),
+    /// `[index]`, index with an int if all digits, otherwise with a string.
+    Index(&'a str),
+}
+
+/// A parsed replacement field in a `.format()` string, e.g. `{0[1].x!r}`.
+#[derive(Debug, PartialEq)]
+struct FormatField<'a> {
+    /// Empty for the next positional argument, digits for a positional index,
+    /// otherwise a keyword argument name.
+    arg: &'a str,
+    /// Applied to the argument in order.
+    accessors: Vec<FormatAccessor<'a>>,
+    /// Whether the value is converted with `repr` (`!r`) rather than `str` (`!s`).
+    repr: bool,
+}
+
+impl<'a> FormatField<'a> {
+    /// Parse the contents of a replacement field, without the curly braces.
+    fn parse(capture: &'a str) -> Result<Self, String> {
+        let end = capture
+            .find(|c| matches!(c, '.' | '[' | '!' | ':'))
+            .unwrap_or(capture.len());
+        let arg = &capture[..end];
+        if arg.contains(']') {
+            return Err("unexpected `]`".to_owned());
+        }
+        let mut rem = &capture[end..];
+        let mut accessors = Vec::new();
+        loop {
+            if let Some(r) = rem.strip_prefix('.') {
+                let end = r
+                    .find(|c| matches!(c, '.' | '[' | ']' | '!' | ':'))
+                    .unwrap_or(r.len());
+                if end == 0 {
+                    return Err("empty attribute name".to_owned());
+                }
+                accessors.push(FormatAccessor::Attr(&r[..end]));
+                rem = &r[end..];
+            } else if let Some(r) = rem.strip_prefix('[') {
+                let end = match r.find(']') {
+                    Some(end) => end,
+                    None => return Err("expected `]`".to_owned()),
+                };
+                if end == 0 {
+                    return Err("empty index".to_owned());
+                }
+                accessors.push(FormatAccessor::Index(&r[..end]));
+                rem = &r[end + 1..];
+            } else {
+                break;
+            }
+        }
+        let repr = match rem.strip_prefix('!') {
+            None => false,
+            Some(r) => {
+                let end = r.find(':').unwrap_or(r.len());
+                rem = &r[end..];
+                match &r[..end] {
+                    "s" => false,
+                    "r" => true,
+                    c => {
+                        return Err(format!(
+                            "`!{}` is not a valid conversion, only `!s` and `!r` are supported",
+                            c
+                        ));
+                    }
+                }
+            }
+        };
+        if rem.starts_with(':') {
+            return Err("format spec not supported".to_owned());
+        }
+        if !rem.is_empty() {
+            return Err(format!("unexpected `{}`", rem));
+        }
+        Ok(FormatField {
+            arg,
+            accessors,
+            repr,
+        })
+    }
+}
+
 pub(crate) fn format<'v>(
     this: &str,
     args: impl Iterator<Item = Value<'v>>,
@@ -409,11 +623,19 @@ pub(crate) fn format<'v>(
     };
     let mut result = string_pool.alloc();
     let mut args = FormatArgs::new(args);
-    while let Some(token) = parser.next()? {
+    loop {
+        let position = this.len() - parser.rem_input.len();
+        let token = match parser.next()? {
+            Some(token) => token,
+            None => break,
+        };
         match token {
             FormatToken::Text(text) => result.push_str(text),
             FormatToken::Capture(capture) => {
-                format_capture(capture, &mut args, &kwargs, &mut result)?
+                let field = FormatField::parse(capture).map_err(|e| {
+                    StringInterpolationError::InvalidField(capture.to_owned(), position, e)
+                })?;
+                format_capture(&field, &mut args, &kwargs, heap, &mut result)?
             }
         }
     }
@@ -423,58 +645,44 @@ pub(crate) fn format<'v>(
 }
 
 fn format_capture<'v, T: Iterator<Item = Value<'v>>>(
-    capture: &str,
+    field: &FormatField,
     args: &mut FormatArgs<'v, T>,
-    kwargs: &Dict,
+    kwargs: &Dict<'v>,
+    heap: &'v Heap,
     result: &mut String,
 ) -> anyhow::Result<()> {
-    let (n, conv) = {
-        if let Some((n, conv)) = capture.split_once('!') {
-            (n, conv)
-        } else {
-            (capture, "s")
-        }
-    };
-    let conv_s = |x: Value, result: &mut String| x.collect_str(result);
-    let conv_r = |x: Value, result: &mut String| x.collect_repr(result);
-    let conv: &dyn Fn(Value, &mut String) = match conv {
-        "s" => &conv_s,
-        "r" => &conv_r,
-        c => {
-            return Err(anyhow!(
-                concat!(
-                    "'{}' is not a valid format string specifier, only ",
-                    "'s' and 'r' are valid specifiers",
-                ),
-                c
-            ));
-        }
-    };
-    if n.is_empty() {
-        conv(args.next_ordered()?, result);
-        Ok(())
-    } else if n.chars().all(|c| c.is_ascii_digit()) {
-        let i = usize::from_str(n).unwrap();
-        conv(args.by_index(i)?, result);
-        Ok(())
+    let mut value = if field.arg.is_empty() {
+        args.next_ordered()?
+    } else if field.arg.chars().all(|c| c.is_ascii_digit()) {
+        let i = usize::from_str(field.arg).map_err(|_| ValueError::IndexOutOfBound(i32::MAX))?;
+        args.by_index(i)?
     } else {
-        if let Some(x) = n.chars().find(|c| match c {
-            '.' | ',' | '[' | ']' => true,
-            _ => false,
-        }) {
-            return Err(anyhow!(
-                "Invalid character '{}' inside replacement field",
-                x
-            ));
+        match kwargs.get_str(field.arg) {
+            None => return Err(ValueError::KeyNotFound(field.arg.to_owned()).into()),
+            Some(v) => v,
         }
-        match kwargs.get_str(n) {
-            None => Err(ValueError::KeyNotFound(n.to_owned()).into()),
-            Some(v) => {
-                conv(v, result);
-                Ok(())
+    };
+    for accessor in &field.accessors {
+        value = match accessor {
+            FormatAccessor::Attr(attr) => value.get_attr_error(attr, heap)?,
+            FormatAccessor::Index(index) => {
+                // Like Python, digits index by number and anything else is a string key.
+                let index = match i32::from_str(index) {
+                    Ok(i) if index.chars().all(|c| c.is_ascii_digit()) => Value::new_int(i),
+                    _ => heap.alloc(*index),
+                };
+                value.at(index, heap)?
             }
-        }
+        };
+    }
+    // Formatting is an explicit conversion, so never truncated.
+    let _guard = set_repr_limits(None);
+    if field.repr {
+        value.collect_repr(result);
+    } else {
+        value.collect_str(result);
     }
+    Ok(())
 }
 
 #[cfg(test)]
@@ -487,10 +695,12 @@ mod tests {
     fn format_capture_for_test<'v, T: Iterator<Item = Value<'v>>>(
         capture: &str,
         args: &mut FormatArgs<'v, T>,
-        kwargs: &Dict,
+        kwargs: &Dict<'v>,
+        heap: &'v Heap,
     ) -> anyhow::Result<String> {
         let mut result = String::new();
-        super::format_capture(capture, args, kwargs, &mut result)?;
+        let field = FormatField::parse(capture).map_err(|e| anyhow!(e))?;
+        super::format_capture(&field, args, kwargs, heap, &mut result)?;
         Ok(result)
     }
 
@@ -506,32 +716,32 @@ mod tests {
         kwargs.insert_hashed(heap.alloc_str("c").get_hashed(), heap.alloc("z"));
         let kwargs = Dict::new(coerce(kwargs));
         assert_eq!(
-            format_capture_for_test("", &mut args, &kwargs).unwrap(),
+            format_capture_for_test("", &mut args, &kwargs, &heap).unwrap(),
             "1"
         );
         assert_eq!(
-            format_capture_for_test("!s", &mut args, &kwargs).unwrap(),
+            format_capture_for_test("!s", &mut args, &kwargs, &heap).unwrap(),
             "2"
         );
         assert_eq!(
-            format_capture_for_test("!r", &mut args, &kwargs).unwrap(),
+            format_capture_for_test("!r", &mut args, &kwargs, &heap).unwrap(),
             "\"3\""
         );
         assert_eq!(
-            format_capture_for_test("a!r", &mut args, &kwargs).unwrap(),
+            format_capture_for_test("a!r", &mut args, &kwargs, &heap).unwrap(),
             "\"x\""
         );
         assert_eq!(
-            format_capture_for_test("a!s", &mut args, &kwargs).unwrap(),
+            format_capture_for_test("a!s", &mut args, &kwargs, &heap).unwrap(),
             "x"
         );
-        assert!(format_capture_for_test("1", &mut args, &kwargs).is_err());
+        assert!(format_capture_for_test("1", &mut args, &kwargs, &heap).is_err());
         let mut args = FormatArgs::new(original_args.iter().copied());
         assert_eq!(
-            format_capture_for_test("1", &mut args, &kwargs).unwrap(),
+            format_capture_for_test("1", &mut args, &kwargs, &heap).unwrap(),
             "2"
         );
-        assert!(format_capture_for_test("", &mut args, &kwargs).is_err());
+        assert!(format_capture_for_test("", &mut args, &kwargs, &heap).is_err());
     }
 
     #[test]
@@ -539,6 +749,123 @@ mod tests {
         assert::eq("'a{x}b{y}c{}'.format(1, x=2, y=3)", "'a2b3c1'")
     }
 
+    #[test]
+    fn test_format_field_parse() {
+        assert_eq!(
+            FormatField::parse("0[1].x!r"),
+            Ok(FormatField {
+                arg: "0",
+                accessors: vec![FormatAccessor::Index("1"), FormatAccessor::Attr("x")],
+                repr: true,
+            })
+        );
+        assert_eq!(
+            FormatField::parse("!s"),
+            Ok(FormatField {
+                arg: "",
+                accessors: Vec::new(),
+                repr: false,
+            })
+        );
+        assert!(FormatField::parse("x.").is_err());
+        assert!(FormatField::parse("x[1").is_err());
+        assert!(FormatField::parse("x]").is_err());
+        assert!(FormatField::parse("x!a").is_err());
+        assert!(FormatField::parse("x:>10").is_err());
+    }
+
+    #[test]
+    fn test_format_fields() {
+        assert::all_true(
+            r#"
+"{0[1]} {0[0]}".format(("a", "b")) == "b a"
+"{[1]}".format([1, 2]) == "2"
+"{x[key]}".format(x={"key": 3}) == "3"
+"{0[1][0]}".format([0, (4, 5)]) == "4"
+"{s.a}-{s.b}".format(s=struct(a=1, b="two")) == "1-two"
+"{0.a!r}".format(struct(a="x")) == '"x"'
+"{!r} {!s}".format("x", "y") == '"x" y'
+"{{{}}}".format(1) == "{1}"
+"#,
+        );
+        assert::fail(
+            r#""ab{x.}".format(x=1)"#,
+            "Invalid replacement field `{x.}` at position 2 in format string: empty attribute name",
+        );
+        assert::fail(
+            r#""{} {:>5}".format(1, 2)"#,
+            "Invalid replacement field `{:>5}` at position 3 in format string: format spec not supported",
+        );
+        assert::fail(r#""{0[}".format([1])"#, "expected `]`");
+        assert::fail(r#""{!x}".format(1)"#, "`!x` is not a valid conversion");
+        assert::fail(r#""{0[5]}".format([1])"#, "out of bound");
+        assert::fail(r#""{0.missing}".format(struct())"#, "missing");
+    }
+
+    #[test]
+    fn test_percent() {
+        assert::all_true(
+            r#"
+"%05d" % 42 == "00042"
+"%05d" % -42 == "-0042"
+"%-5d|" % 42 == "42   |"
+"%-05d|" % 42 == "42   |"
+"%+d %+d" % (5, -5) == "+5 -5"
+"% d" % 5 == " 5"
+"%.3d" % 7 == "007"
+"%5.3d" % -7 == " -007"
+"%i" % 3.9 == "3"
+"%04X %x %o" % (255, 255, 8) == "00FF ff 10"
+"%.2f" % 2.675 == "2.67"
+"%8.3f" % 3.14159 == "   3.142"
+"%08.2f" % -3.14159 == "-0003.14"
+"%.0f" % 3.7 == "4"
+"%06f" % float("inf") == "  +inf"
+"%10.3e" % 1234.56 == " 1.235e+03"
+"%.3g" % 1234.5 == "1.23e+03"
+"%.3g" % 0.5 == "0.5"
+"%g" % 1.5 == "1.5"
+"%-10s|" % "hi" == "hi        |"
+"%5s|" % "abc" == "  abc|"
+"%05s" % "ab" == "   ab"
+"%.2s" % "abcdef" == "ab"
+"%r" % "x" == '"x"'
+"%.3r" % "abc" == '"ab'
+"%r %s" % ([1], [1]) == "[1] [1]"
+"100%% %s" % "x" == "100% x"
+"%%" % () == "%"
+"%s" % (1,) == "1"
+"%s %s" % (1, 2) == "1 2"
+"%s" % [1, 2] == "[1, 2]"
+"%s" % ((1, 2),) == "(1, 2)"
+"test" % () == "test"
+"#,
+        );
+        assert::fail(
+            r#""%s %s" % 1"#,
+            "Not enough arguments for format string, no argument at index 1 for `%s`",
+        );
+        assert::fail(
+            r#""%s" % (1, 2)"#,
+            "Too many arguments for format string, used 1 of 2",
+        );
+        assert::fail(r#""%z" % 1"#, "Unsupported format character in `%z`");
+        assert::fail(r#""%-5" % 1"#, "Incomplete format `%-5`");
+        assert::fail(
+            r#""%s %d" % (1, "x")"#,
+            "Invalid argument at index 1 for `%d`",
+        );
+        assert::fail(
+            r#""%5.2f" % "x""#,
+            "Invalid argument at index 0 for `%5.2f`",
+        );
+        assert::fail(
+            r#""%99999999999d" % 1"#,
+            "Width or precision in `%99999999999` is too big, the maximum is 1000000",
+        );
+        assert::fail(r#""%.1000001f" % 1.0"#, "is too big");
+    }
+
     #[test]
     fn test_parse_format_one() {
         assert_eq!(
@@ -46,7 +46,7 @@ mod alloc_unpack;
 pub(crate) mod fast_string;
 pub(crate) mod interpolation;
 pub(crate) mod iter;
-mod repr;
+pub(crate) mod repr;
 pub(crate) mod simd;
 
 /// Index of a char in a string.
@@ -145,6 +145,12 @@ impl StarlarkStr {
         }
     }
 
+    /// Store a hash value computed elsewhere, e.g. while the string was being written.
+    /// The hash must be equal to what [`get_hash`](StarlarkStr::get_hash) would compute.
+    pub(crate) fn set_hash(&self, hash: StarlarkHashValue) {
+        self.str.hash.store(hash.get(), atomic::Ordering::Relaxed);
+    }
+
     /// Rust string reference along with its hash value.
     pub fn as_str_hashed(&self) -> BorrowHashed<str> {
         BorrowHashed::new_unchecked(self.get_hash(), self.unpack())
@@ -306,7 +312,9 @@ impl<'v> StarlarkValue<'v> for str {
 
     fn mul(&self, other: Value<'v>, heap: &'v Heap) -> anyhow::Result<Value<'v>> {
         let l = i32::unpack_param(other)?;
-        let mut result = String::with_capacity(self.len() * cmp::max(0, l) as usize);
+        let len = self.len().saturating_mul(cmp::max(0, l) as usize);
+        heap.check_max_bytes(len)?;
+        let mut result = String::with_capacity(len);
         for _i in 0..l {
             result.push_str(self)
         }
@@ -16,6 +16,16 @@
  */
 
 //! Implementation of `repr()`.
+//!
+//! The `repr` of a string is always a valid Starlark string literal which
+//! parses back to the original string. The quoting is stable:
+//!
+//! * the literal is enclosed in double quotes, single quotes are not escaped;
+//! * `\n`, `\r`, `\t`, `\\` and `\"` use the short escapes;
+//! * other control characters, `0x7f` and the rest of `0x80..=0xff` use `\xNN`;
+//! * other characters which are not alphanumeric (e.g. emoji, zero-width spaces)
+//!   use `\uNNNN`, or `\UNNNNNNNN` outside the basic multilingual plane;
+//! * everything else, including non-ASCII letters, is written as is.
 
 use std::{intrinsics::unlikely, mem};
 
@@ -84,6 +94,8 @@ fn need_escape(c: char) -> bool {
     }
 }
 
+/// Write `str` as a double-quoted Starlark string literal, following the rules
+/// in the module documentation.
 pub(crate) fn string_repr(str: &str, buffer: &mut String) {
     // this method is surprisingly hot
     // so we first try and do a fast pass that only works for ASCII-only
@@ -232,6 +244,7 @@ mod tests {
 
     use crate::{
         assert,
+        syntax::{lexer::Token, AstModule, Dialect},
         values::types::string::repr::{chunk_non_ascii_or_need_escape, string_repr},
     };
 
@@ -283,6 +296,39 @@ mod tests {
         r
     }
 
+    #[test]
+    fn test_repr_round_trips() {
+        let corpus = [
+            "",
+            "'",
+            "\"",
+            "'\"'\"\"\"",
+            "\\",
+            "\\\"",
+            "\\x41",
+            "\\u0041",
+            "\n\r\t\r\n",
+            "\0\x01\x1b\x7f",
+            "\u{80}\u{a0}\u{ad}\u{ff}",
+            "\u{200b}\u{2028}\u{feff}",
+            "Hello, 世界",
+            "ñandú",
+            "😀 👍🏽 🏳️‍🌈",
+            "\u{10ffff}",
+            "e\u{301}",
+            "0123456789abcdef\n0123456789abcdef\"",
+        ];
+        for s in corpus {
+            let repr = string_repr_for_test(s);
+            match assert::lex_tokens(&repr).as_slice() {
+                [(_, Token::String(x), _), ..] => assert_eq!(s, x, "repr: {}", repr),
+                tokens => panic!("Expected a string literal for {}, got {:?}", repr, tokens),
+            }
+        }
+        // Rust strings can't hold a lone surrogate, so the parser has to reject one.
+        assert!(AstModule::parse("x.star", r#""\ud800""#.to_owned(), &Dialect::Standard).is_err());
+    }
+
     #[test]
     fn to_repr_sse() {
         for i in 0..0x80 {
@@ -58,8 +58,8 @@ use crate::{
         docs,
         docs::DocItem,
         error::ValueError,
-        AllocValue, Freeze, FrozenValue, Heap, StarlarkValue, StringValue, StringValueLike, Trace,
-        UnpackValue, Value, ValueLike, ValueOf,
+        freeze_small_map_with_path, AllocValue, Freeze, Freezer, FrozenValue, Heap, StarlarkValue,
+        StringValue, StringValueLike, Trace, UnpackValue, Value, ValueLike, ValueOf,
     },
 };
 
@@ -79,7 +79,7 @@ impl<'v, V: ValueLike<'v>> StructGen<'v, V> {
 starlark_complex_value!(pub Struct<'v>);
 
 /// The result of calling `struct()`.
-#[derive(Clone, Default, Debug, Trace, Freeze, AnyLifetime)]
+#[derive(Clone, Default, Debug, Trace, AnyLifetime)]
 #[repr(C)]
 pub struct StructGen<'v, V: ValueLike<'v>> {
     /// The fields in a struct.
@@ -89,6 +89,16 @@ pub struct StructGen<'v, V: ValueLike<'v>> {
 
 unsafe impl<'v> Coerce<StructGen<'v, Value<'v>>> for StructGen<'static, FrozenValue> {}
 
+impl<'v> Freeze for Struct<'v> {
+    type Frozen = FrozenStruct;
+
+    fn freeze(self, freezer: &Freezer) -> anyhow::Result<Self::Frozen> {
+        let fields =
+            freeze_small_map_with_path(self.fields, freezer, |k| format!(".{}", k.as_str()))?;
+        Ok(StructGen::new(fields))
+    }
+}
+
 impl<'v, V: ValueLike<'v>> Display for StructGen<'v, V> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         display_keyed_container(
@@ -206,6 +216,7 @@ where
                     .to_owned();
                 match v.to_value().documentation() {
                     Some(DocItem::Function(f)) => (name, docs::Member::Function(f)),
+                    Some(DocItem::Object(o)) => (name, docs::Member::Object(o)),
                     _ => (
                         name,
                         docs::Member::Property(docs::Property {
@@ -347,6 +358,45 @@ struct(foo = [struct(bar = "some")]).to_json() == '{"foo":[{"bar":"some"}]}'
         );
     }
 
+    #[test]
+    fn test_to_dict() {
+        assert::all_true(
+            r#"
+struct(b = 1, a = 2, c = [3]).to_dict() == {"b": 1, "a": 2, "c": [3]}
+list(struct(b = 1, a = 2).to_dict().keys()) == ["b", "a"]
+struct().to_dict() == {}
+struct(**struct(b = 1, a = struct(x = 2)).to_dict()) == struct(b = 1, a = struct(x = 2))
+"#,
+        );
+    }
+
+    #[test]
+    fn test_frozen_field_order() {
+        let mut a = assert::Assert::new();
+        a.module("m", "s = struct(zz = 1, b = [2], a = 'x')");
+        a.pass(
+            r#"
+load("m", "s")
+assert_eq(s.to_json(), '{"zz":1,"b":[2],"a":"x"}')
+assert_eq(s.to_json(), struct(zz = 1, b = [2], a = 'x').to_json())
+assert_eq(list(s.to_dict().keys()), ["zz", "b", "a"])
+assert_eq(struct(**s.to_dict()), s)
+"#,
+        );
+    }
+
+    #[test]
+    fn test_kwargs_not_string() {
+        assert::fail(
+            "struct(**{1: 2})",
+            "Keys of **kwargs must be strings, but got `1` of type `int`",
+        );
+        assert::fail(
+            "struct(a = 1, **{'b': 2, (3,): 4})",
+            "Keys of **kwargs must be strings, but got `(3,)` of type `tuple`",
+        );
+    }
+
     #[test]
     fn test_docs() {
         let expected = DocItem::Object(docs::Object {
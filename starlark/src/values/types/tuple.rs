@@ -18,9 +18,12 @@
 //! The list type, an immutable sequence of values.
 
 use std::{
+    cmp,
     cmp::Ordering,
     fmt,
     fmt::{Debug, Display, Formatter},
+    mem,
+    ops::ControlFlow,
     slice,
 };
 
@@ -32,8 +35,10 @@ use serde::{ser::SerializeTuple, Serialize};
 
 use crate::{
     collections::StarlarkHasher,
+    environment::{Methods, MethodsStatic},
     values::{
         comparison::{compare_slice, equals_slice},
+        for_each_in,
         index::{apply_slice, convert_index},
         AllocValue, FrozenValue, Heap, StarlarkValue, UnpackValue, Value, ValueError, ValueLike,
     },
@@ -128,6 +133,11 @@ impl<'v, V: ValueLike<'v>> TupleGen<V> {
     }
 }
 
+pub(crate) fn tuple_methods() -> Option<&'static Methods> {
+    static RES: MethodsStatic = MethodsStatic::new();
+    RES.methods(crate::stdlib::tuple::tuple_methods)
+}
+
 impl<'v, V: ValueLike<'v>> StarlarkValue<'v> for TupleGen<V>
 where
     Self: AnyLifetime<'v>,
@@ -145,6 +155,10 @@ where
         self.len() != 0
     }
 
+    fn get_methods(&self) -> Option<&'static Methods> {
+        tuple_methods()
+    }
+
     fn write_hash(&self, hasher: &mut StarlarkHasher) -> anyhow::Result<()> {
         for v in self.content() {
             v.write_hash(hasher)?;
@@ -212,6 +226,15 @@ where
         f(&mut self.iter())
     }
 
+    fn for_each_element(
+        &self,
+        _heap: &'v Heap,
+        f: &mut dyn FnMut(Value<'v>) -> ControlFlow<()>,
+    ) -> anyhow::Result<()> {
+        for_each_in(self.iter(), f);
+        Ok(())
+    }
+
     fn add(&self, other: Value<'v>, heap: &'v Heap) -> anyhow::Result<Value<'v>> {
         if let Some(other) = Tuple::from_value(other) {
             let mut result = Vec::with_capacity(self.len() + other.len());
@@ -229,6 +252,11 @@ where
 
     fn mul(&self, other: Value, heap: &'v Heap) -> anyhow::Result<Value<'v>> {
         let l = i32::unpack_param(other)?;
+        heap.check_max_bytes(
+            self.len()
+                .saturating_mul(cmp::max(0, l) as usize)
+                .saturating_mul(mem::size_of::<Value>()),
+        )?;
         let mut result = Vec::new();
         for _i in 0..l {
             result.extend(self.content().iter().map(|e| e.to_value()));
@@ -22,6 +22,9 @@ use thiserror::Error;
 
 use crate::{
     collections::Hashed,
+    environment::{Globals, Module},
+    eval::Evaluator,
+    syntax::{AstModule, Dialect},
     values::{
         dict::{Dict, DictRef},
         list::{List, ListRef},
@@ -223,6 +226,15 @@ impl TypeCompiled {
         }
     }
 
+    /// Compile a type written as a Starlark expression, e.g. `[str.type]`.
+    pub(crate) fn from_expr(ty: &str) -> anyhow::Result<Self> {
+        let module = Module::new();
+        let mut eval = Evaluator::new(&module);
+        let ast = AstModule::parse("type", ty.to_owned(), &Dialect::Standard)?;
+        let ty = eval.eval_module(ast, &Globals::standard())?;
+        TypeCompiled::new(ty, module.heap())
+    }
+
     pub(crate) fn new<'h>(ty: Value<'h>, heap: &'h Heap) -> anyhow::Result<Self> {
         if let Some(s) = ty.unpack_str() {
             Ok(TypeCompiled::from_str(s))
@@ -262,15 +274,25 @@ impl<'v> Value<'v> {
     #[cold]
     #[inline(never)]
     fn check_type_error(value: Value, ty: Value, arg_name: Option<&str>) -> anyhow::Result<()> {
-        Err(TypingError::TypeAnnotationMismatch(
-            value.to_str(),
-            value.get_type().to_owned(),
-            ty.to_str(),
+        Self::check_type_error_described(
+            value,
+            ty,
             match arg_name {
                 None => "return type".to_owned(),
                 Some(x) => format!("argument `{}`", x),
             },
         )
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn check_type_error_described(value: Value, ty: Value, what: String) -> anyhow::Result<()> {
+        Err(TypingError::TypeAnnotationMismatch(
+            value.to_str(),
+            value.get_type().to_owned(),
+            ty.to_str(),
+            what,
+        )
         .into())
     }
 
@@ -287,6 +309,25 @@ impl<'v> Value<'v> {
         }
     }
 
+    pub(crate) fn check_return_type_compiled(
+        self,
+        ty: &str,
+        ty_compiled: &TypeCompiled,
+        function: &str,
+    ) -> anyhow::Result<()> {
+        if ty_compiled.0(self) {
+            Ok(())
+        } else {
+            Err(TypingError::TypeAnnotationMismatch(
+                self.to_str(),
+                self.get_type().to_owned(),
+                ty.to_owned(),
+                format!("return type of `{}`", function),
+            )
+            .into())
+        }
+    }
+
     pub(crate) fn check_type_compiled(
         self,
         ty: Value<'v>,
@@ -299,6 +340,21 @@ impl<'v> Value<'v> {
             Self::check_type_error(self, ty, arg_name)
         }
     }
+
+    /// Like [`check_type_compiled`](Value::check_type_compiled), but `what` describes
+    /// the value being checked in the error, e.g. "field `x` of record `R`".
+    pub(crate) fn check_type_compiled_described(
+        self,
+        ty: Value<'v>,
+        ty_compiled: &TypeCompiled,
+        what: impl FnOnce() -> String,
+    ) -> anyhow::Result<()> {
+        if ty_compiled.0(self) {
+            Ok(())
+        } else {
+            Self::check_type_error_described(self, ty, what())
+        }
+    }
 }
 
 #[cfg(test)]
@@ -17,12 +17,146 @@
 
 //! Parameter conversion utilities for `starlark_module` macros.
 
-use std::ops::Deref;
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+    ops::Deref,
+};
 
 use either::Either;
 use gazebo::prelude::*;
 
-use crate::values::{list::List, tuple::Tuple, Value, ValueError};
+use crate::{
+    eval::runtime::value_provenance,
+    values::{
+        list::List,
+        repr_limits::{set_repr_limits, ReprLimits, TRUNCATED},
+        string::repr::string_repr,
+        tuple::Tuple,
+        Value,
+    },
+};
+
+/// The error from unpacking a [`Value`] which is not of the expected type,
+/// whether as a parameter of a [`#[starlark_module]`](macro@starlark_module) function
+/// or explicitly, e.g. with [`Value::unpack_str_err`], so both give the same message.
+/// The message always ends with the `repr` of the value, truncated to
+/// [`MAX_REPR_LEN`](UnpackValueError::MAX_REPR_LEN), as the type alone is often not enough
+/// to tell which value was wrong.
+#[derive(Debug, Clone)]
+pub struct UnpackValueError {
+    name: Option<String>,
+    expected: String,
+    actual: String,
+    repr: String,
+    /// Where the value was on the heap, to find its origin if value provenance is enabled.
+    pub(crate) address: Option<usize>,
+}
+
+// The same error about different values is still the same error.
+impl PartialEq for UnpackValueError {
+    fn eq(&self, other: &Self) -> bool {
+        (&self.name, &self.expected, &self.actual, &self.repr)
+            == (&other.name, &other.expected, &other.actual, &other.repr)
+    }
+}
+
+impl Eq for UnpackValueError {}
+
+impl UnpackValueError {
+    /// The longest `repr` of the value included in the error, not counting the truncation marker.
+    pub const MAX_REPR_LEN: usize = 100;
+
+    /// The error for `value` not being unpackable as a `T`.
+    pub fn new<'v, T: UnpackValue<'v>>(value: Value<'v>) -> Self {
+        Self::new_impl(None, T::expected(), value)
+    }
+
+    /// The error for `value`, passed as the parameter `name`, not being unpackable as a `T`.
+    pub fn new_named<'v, T: UnpackValue<'v>>(value: Value<'v>, name: &str) -> Self {
+        Self::new_impl(Some(name.to_owned()), T::expected(), value)
+    }
+
+    fn new_impl(name: Option<String>, expected: String, value: Value) -> Self {
+        Self {
+            name,
+            expected,
+            actual: value.get_type().to_owned(),
+            repr: Self::limited_repr(value),
+            address: value_provenance::address(value),
+        }
+    }
+
+    // Huge values would make for unreadable errors, so always limit the repr,
+    // whatever the limits of the evaluation. The work done is bounded by the limit,
+    // rather than by the size of the value, as the error may never be displayed.
+    #[cold]
+    fn limited_repr(value: Value) -> String {
+        match value.unpack_str() {
+            // Escaping every character of a huge string would be wasted, and the
+            // repr of a prefix at least as long as the limit starts the same way.
+            Some(s) if s.len() > Self::MAX_REPR_LEN => {
+                let mut end = Self::MAX_REPR_LEN;
+                while !s.is_char_boundary(end) {
+                    end += 1;
+                }
+                let mut repr = String::new();
+                string_repr(&s[..end], &mut repr);
+                let mut end = Self::MAX_REPR_LEN;
+                while !repr.is_char_boundary(end) {
+                    end -= 1;
+                }
+                repr.truncate(end);
+                repr.push_str(TRUNCATED);
+                repr
+            }
+            // Nested values aren't collected once the limit is reached.
+            _ => {
+                let _guard = set_repr_limits(Some(ReprLimits {
+                    max_len: Self::MAX_REPR_LEN,
+                    max_depth: ReprLimits::default().max_depth,
+                }));
+                value.to_repr()
+            }
+        }
+    }
+
+    /// The name of the parameter, if the value was a parameter.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Description of the values which were expected, as per [`UnpackValue::expected`].
+    pub fn expected(&self) -> &str {
+        &self.expected
+    }
+
+    /// The type of the value, as per [`Value::get_type`].
+    pub fn actual(&self) -> &str {
+        &self.actual
+    }
+
+    /// The `repr` of the value, truncated to [`MAX_REPR_LEN`](UnpackValueError::MAX_REPR_LEN).
+    pub fn repr(&self) -> &str {
+        &self.repr
+    }
+}
+
+impl Display for UnpackValueError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "Type of parameter `{}` doesn't match", name)?,
+            None => write!(f, "Type of value doesn't match")?,
+        }
+        write!(
+            f,
+            ", expected `{}`, actual `{}` (`{}`)",
+            self.expected, self.actual, self.repr
+        )
+    }
+}
+
+impl Error for UnpackValueError {}
 
 /// How to convert a [`Value`] to a Rust type. Required for all arguments in a [`#[starlark_module]`](macro@starlark_module) definition.
 pub trait UnpackValue<'v>: Sized {
@@ -35,25 +169,13 @@ pub trait UnpackValue<'v>: Sized {
 
     /// Unpack value, but instead of `None` return error about incorrect argument type.
     fn unpack_param(value: Value<'v>) -> anyhow::Result<Self> {
-        Self::unpack_value(value).ok_or_else(|| {
-            ValueError::IncorrectParameterTypeWithExpected(
-                Self::expected(),
-                value.get_type().to_owned(),
-            )
-            .into()
-        })
+        Self::unpack_value(value).ok_or_else(|| UnpackValueError::new::<Self>(value).into())
     }
 
     /// Unpack value, but instead of `None` return error about incorrect named argument type.
     fn unpack_named_param(value: Value<'v>, param_name: &str) -> anyhow::Result<Self> {
-        Self::unpack_value(value).ok_or_else(|| {
-            ValueError::IncorrectParameterTypeNamedWithExpected(
-                param_name.to_owned(),
-                Self::expected(),
-                value.get_type().to_owned(),
-            )
-            .into()
-        })
+        Self::unpack_value(value)
+            .ok_or_else(|| UnpackValueError::new_named::<Self>(value, param_name).into())
     }
 }
 
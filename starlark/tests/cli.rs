@@ -0,0 +1,55 @@
+/*
+ * Copyright 2019 The Starlark in Rust Authors.
+ * Copyright (c) Facebook, Inc. and its affiliates.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     https://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+//! Tests which run the `starlark` binary.
+
+use std::{
+    io::Write,
+    process::{Command, Output, Stdio},
+};
+
+fn run_with_stdin(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_starlark"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn test_stdin() {
+    let out = run_with_stdin(&["--stdin"], "x = 1\nassert_eq(x + 1, 2)\n");
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    assert!(out.status.success(), "{}", stdout);
+    assert!(stdout.contains("1 files, 0 errors"), "{}", stdout);
+
+    let out = run_with_stdin(&["--stdin"], "fail('from stdin')\n");
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    assert!(!out.status.success(), "{}", stdout);
+    assert!(stdout.contains("from stdin"), "{}", stdout);
+    assert!(stdout.contains("<stdin>"), "{}", stdout);
+    assert!(stdout.contains("1 files, 1 errors"), "{}", stdout);
+}
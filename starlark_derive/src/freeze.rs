@@ -19,7 +19,7 @@ use proc_macro2::{Ident, TokenStream};
 use quote::{quote, quote_spanned};
 use syn::{
     parse::ParseStream, parse_macro_input, spanned::Spanned, Attribute, Data, DataEnum, DataStruct,
-    DeriveInput, Error, Fields, GenericParam, Index, LitStr, Token, WherePredicate,
+    DeriveInput, Error, Fields, GenericParam, Index, LitStr, Token, Type, WherePredicate,
 };
 
 struct Input<'a> {
@@ -190,6 +190,36 @@ fn is_identity(attrs: &[Attribute]) -> syn::Result<bool> {
     Ok(false)
 }
 
+/// If the type is `RefCell<T>` or `Cell<T>`, the name of the cell type.
+///
+/// Cells freeze to their frozen contents, so the derived code wraps the result in a new cell.
+fn cell_type(ty: &Type) -> Option<&Ident> {
+    match ty {
+        Type::Path(path) if path.qself.is_none() => {
+            let last = &path.path.segments.last()?.ident;
+            if last == "RefCell" || last == "Cell" {
+                Some(last)
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Freeze the field `self.#field`, described by `step` (e.g. `.field`) in errors
+/// from freezing a cell.
+fn freeze_field(field: TokenStream, ty: &Type, step: String) -> TokenStream {
+    match cell_type(ty) {
+        Some(cell) => quote! {
+            std::cell::#cell::new(starlark::__derive_refs::freeze::freeze_field(self.#field, freezer, #step)?)
+        },
+        None => quote! {
+            starlark::values::Freeze::freeze(self.#field, freezer)?
+        },
+    }
+}
+
 fn freeze_struct(name: &Ident, data: &DataStruct) -> syn::Result<TokenStream> {
     let res = match data.fields {
         Fields::Named(ref fields) => {
@@ -203,8 +233,10 @@ fn freeze_struct(name: &Ident, data: &DataStruct) -> syn::Result<TokenStream> {
                             #name: self.#name,
                         }
                     } else {
+                        let step = format!(".{}", name.as_ref().unwrap());
+                        let freeze = freeze_field(quote! { #name }, &f.ty, step);
                         quote_spanned! {f.span() =>
-                            #name: starlark::values::Freeze::freeze(self.#name, freezer)?,
+                            #name: #freeze,
                         }
                     };
 
@@ -230,7 +262,8 @@ fn freeze_struct(name: &Ident, data: &DataStruct) -> syn::Result<TokenStream> {
                             self.#i,
                         }
                     } else {
-                        quote_spanned! {f.span() => starlark::values::Freeze::freeze(self.#i, freezer)?,}
+                        let freeze = freeze_field(quote! { #i }, &f.ty, format!(".{}", i.index));
+                        quote_spanned! {f.span() => #freeze,}
                     };
 
                     syn::Result::Ok(res)
@@ -78,10 +78,15 @@ mod util;
 ///   an attribute on the value. Such a function must take exactly one argument, namely a value
 ///   of the type you have attached it to.
 /// * The attribute `#[starlark(type("test"))]` causes `f.type` to return `"test"`.
+/// * The attribute `#[starlark(return_type("[str.type]"))]` declares the Starlark type of the
+///   result, which is used in the documentation, and checked at runtime when
+///   `Evaluator::enable_runtime_typecheck` is set.
 /// * If a member is annotated with `#[starlark(speculative_exec_safe)]`, then a function
 ///   is considered safe to execute speculatively: the function should have
 ///   no global side effects, should not panic, and should finish in reasonable time.
-///   The evaluator may invoke such functions early to generate more efficient code.
+///   The evaluator may invoke such functions early to generate more efficient code,
+///   e.g. a call where all the arguments are constants is made once at compile time and
+///   replaced by its result. If that call fails, it is left to be made (and fail) at runtime.
 ///
 /// All these functions interoperate properly with `dir()`, `getattr()` and `hasattr()`.
 ///
@@ -18,8 +18,9 @@
 use gazebo::prelude::*;
 use proc_macro2::Span;
 use syn::{
-    spanned::Spanned, Attribute, FnArg, GenericArgument, Item, ItemConst, ItemFn, Meta,
-    MetaNameValue, NestedMeta, Pat, PatType, PathArguments, ReturnType, Stmt, Type, TypeReference,
+    spanned::Spanned, Attribute, FnArg, GenericArgument, Item, ItemConst, ItemFn, Lit, LitStr,
+    Meta, MetaNameValue, NestedMeta, Pat, PatType, PathArguments, ReturnType, Stmt, Type,
+    TypeReference,
 };
 
 use crate::{typ::*, util::*};
@@ -114,6 +115,7 @@ fn parse_const(x: ItemConst) -> StarConst {
 struct ProcessedAttributes {
     is_attribute: bool,
     type_attribute: Option<NestedMeta>,
+    return_type_attribute: Option<LitStr>,
     speculative_exec_safe: bool,
     docstring: Option<String>,
     /// Rest attributes
@@ -136,12 +138,13 @@ fn is_attribute_docstring(x: &Attribute) -> Option<String> {
 /// Parse `#[starlark(...)]` attribute.
 fn process_attributes(span: Span, xs: Vec<Attribute>) -> syn::Result<ProcessedAttributes> {
     const ERROR: &str = "Couldn't parse attribute. \
-        Expected `#[starlark(type(\"ty\")]`, \
+        Expected `#[starlark(type(\"ty\")]`, `#[starlark(return_type(\"ty\")]`, \
         `#[starlark(attribute)]` or `#[starlark(speculative_exec_safe)]`";
 
     let mut attrs = Vec::with_capacity(xs.len());
     let mut is_attribute = false;
     let mut type_attribute = None;
+    let mut return_type_attribute = None;
     let mut speculative_exec_safe = false;
     let mut doc_attrs = Vec::new();
     for x in xs {
@@ -166,6 +169,18 @@ fn process_attributes(span: Span, xs: Vec<Attribute>) -> syn::Result<ProcessedAt
                                         }
                                         _ => return Err(syn::Error::new(meta.span(), ERROR)),
                                     }
+                                } else if meta.path().is_ident("return_type") {
+                                    match meta {
+                                        Meta::List(list) => match list.nested.first() {
+                                            Some(NestedMeta::Lit(Lit::Str(ty)))
+                                                if list.nested.len() == 1 =>
+                                            {
+                                                return_type_attribute = Some(ty.clone());
+                                            }
+                                            _ => return Err(syn::Error::new(list.span(), ERROR)),
+                                        },
+                                        _ => return Err(syn::Error::new(meta.span(), ERROR)),
+                                    }
                                 } else if meta.path().is_ident("attribute") {
                                     is_attribute = true;
                                 } else if meta.path().is_ident("speculative_exec_safe") {
@@ -191,6 +206,12 @@ fn process_attributes(span: Span, xs: Vec<Attribute>) -> syn::Result<ProcessedAt
     if is_attribute && type_attribute.is_some() {
         return Err(syn::Error::new(span, "Can't be an attribute with a .type"));
     }
+    if is_attribute && return_type_attribute.is_some() {
+        return Err(syn::Error::new(
+            span,
+            "Can't be an attribute with a checked return type",
+        ));
+    }
     let docstring = if !doc_attrs.is_empty() {
         Some(doc_attrs.join("\n"))
     } else {
@@ -199,6 +220,7 @@ fn process_attributes(span: Span, xs: Vec<Attribute>) -> syn::Result<ProcessedAt
     Ok(ProcessedAttributes {
         is_attribute,
         type_attribute,
+        return_type_attribute,
         speculative_exec_safe,
         docstring,
         attrs,
@@ -251,6 +273,7 @@ fn parse_fun(func: ItemFn) -> syn::Result<StarStmt> {
     let ProcessedAttributes {
         is_attribute,
         type_attribute,
+        return_type_attribute,
         speculative_exec_safe,
         docstring,
         attrs,
@@ -315,6 +338,7 @@ fn parse_fun(func: ItemFn) -> syn::Result<StarStmt> {
             args,
             return_type: *return_type,
             return_type_arg,
+            return_type_attribute,
             speculative_exec_safe,
             body: *func.block,
             source: StarFunSource::Unknown,
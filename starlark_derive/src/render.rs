@@ -100,14 +100,7 @@ fn render_attr(x: StarAttr) -> TokenStream {
                 heap: &'v starlark::values::Heap,
             ) -> #return_type {
                 #[allow(unused_variables)]
-                let this: #arg = match starlark::values::UnpackValue::unpack_value(this) {
-                    None => return Err(starlark::values::ValueError::IncorrectParameterTypeNamedWithExpected(
-                        "this".to_owned(),
-                        <#arg as starlark::values::UnpackValue>::expected(),
-                        this.get_type().to_owned(),
-                    ).into()),
-                    Some(v) => v,
-                };
+                let this: #arg = starlark::values::UnpackValue::unpack_named_param(this, "this")?;
                 #body
             }
             Ok(heap.alloc(inner(this, heap)?))
@@ -132,6 +125,7 @@ fn render_fun(x: StarFun) -> TokenStream {
         args: _,
         return_type,
         return_type_arg: _,
+        return_type_attribute,
         speculative_exec_safe,
         body,
         source: _,
@@ -198,6 +192,15 @@ fn render_fun(x: StarFun) -> TokenStream {
         )
     };
 
+    let check_return_type = return_type_attribute.map(|ty| {
+        quote_spanned! {
+            span=>
+            static __RETURN_TYPE: starlark::values::function::NativeReturnType =
+                starlark::values::function::NativeReturnType::new(#ty);
+            __RETURN_TYPE.check(#name_str, v, eval)?;
+        }
+    });
+
     quote_spanned! {
         span=>
         #( #attrs )*
@@ -221,7 +224,11 @@ fn render_fun(x: StarFun) -> TokenStream {
                 #body
             }
             match inner(eval, #this_arg parameters, #signature_val) {
-                Ok(v) => Ok(eval.heap().alloc(v)),
+                Ok(v) => {
+                    let v = eval.heap().alloc(v);
+                    #check_return_type
+                    Ok(v)
+                }
                 Err(e) => Err(e),
             }
         }
@@ -398,7 +405,13 @@ fn render_documentation(x: &StarFun) -> TokenStream {
         Some(d) => quote_spanned!(span=> Some(#d)),
         None => quote_spanned!(span=> None),
     };
-    let return_type_arg = &x.return_type_arg;
+    let return_type = match &x.return_type_attribute {
+        Some(ty) => quote_spanned!(span=> #ty.to_owned()),
+        None => {
+            let return_type_arg = &x.return_type_arg;
+            quote_spanned!(span=> stringify!(#return_type_arg).to_owned())
+        }
+    };
     let parameter_types: Vec<_> = x.args
             .iter()
             .filter(|a| !a.is_this()) // "this" gets ignored when creating the signature, so make sure the indexes match up.
@@ -407,20 +420,35 @@ fn render_documentation(x: &StarFun) -> TokenStream {
                 let typ = &arg.ty;
                 quote_spanned!(span=> (#i, starlark::values::docs::Type { raw_type: stringify!(#typ).to_owned() }) )
             }).collect();
+    // Defaults which are not values are only known as the Rust expression.
+    let parameter_defaults: Vec<_> = x
+        .args
+        .iter()
+        .filter(|a| !a.is_this())
+        .enumerate()
+        .filter_map(|(i, arg)| match &arg.default {
+            Some(default) if !arg.is_value() => {
+                Some(quote_spanned!(span=> (#i, stringify!(#default))))
+            }
+            _ => None,
+        })
+        .collect();
 
     quote_spanned!(span=>
         let __documentation_renderer = {
             let signature = #documentation_signature;
             let parameter_types = std::collections::HashMap::from([#(#parameter_types),*]);
+            let parameter_defaults = std::collections::HashMap::from([#(#parameter_defaults),*]);
             let return_type = Some(
                 starlark::values::docs::Type {
-                    raw_type: stringify!(#return_type_arg).to_owned()
+                    raw_type: #return_type
                 }
             );
             starlark::values::function::NativeCallableRawDocs {
                 rust_docstring: #docs,
                 signature,
                 parameter_types,
+                parameter_defaults,
                 return_type,
             }
         };
@@ -16,7 +16,7 @@
  */
 
 use proc_macro2::{Ident, Span};
-use syn::{spanned::Spanned, Attribute, Block, Expr, NestedMeta, Pat, Type, Visibility};
+use syn::{spanned::Spanned, Attribute, Block, Expr, LitStr, NestedMeta, Pat, Type, Visibility};
 
 use crate::{parse::ModuleKind, util::*};
 
@@ -87,6 +87,8 @@ pub(crate) struct StarFun {
     pub return_type: Type,
     /// `T`.
     pub return_type_arg: Type,
+    /// The Starlark type from `#[starlark(return_type("..."))]`.
+    pub return_type_attribute: Option<LitStr>,
     pub speculative_exec_safe: bool,
     pub body: Block,
     pub source: StarFunSource,